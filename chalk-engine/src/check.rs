@@ -0,0 +1,139 @@
+//! Consistency checks and visualization for the forest. The checks
+//! run after each root answer is requested in debug builds (see
+//! `ensure_root_answer`); the DOT export is meant for debugging by
+//! hand.
+
+use crate::context::Context;
+use crate::forest::Forest;
+use crate::TableIndex;
+use std::fmt::Write;
+
+impl<C: Context> Forest<C> {
+    /// Asserts that the forest is in a consistent state between root
+    /// searches:
+    ///
+    /// - the stack is empty, i.e., every table pushed during the
+    ///   search was popped again;
+    /// - the DFNs on the stack (if any) are strictly increasing and
+    ///   were all handed out by `next_dfn`;
+    /// - each table is registered under its own goal, and no other;
+    /// - every strand's selected subgoal refers to an existing table;
+    /// - floundered tables have neither answers nor strands left.
+    ///
+    /// # Panics
+    ///
+    /// Panics, describing the first violated invariant, if any.
+    pub(crate) fn check_invariants(&self) {
+        assert!(
+            self.stack.is_empty(),
+            "forest invariant: stack not empty after root search ({} entries)",
+            self.stack.len()
+        );
+        self.check_stack();
+
+        assert_eq!(
+            self.tables.len(),
+            self.tables.num_indices(),
+            "forest invariant: table count disagrees with the goal index"
+        );
+        for (index, table) in self.tables.iter() {
+            assert_eq!(
+                self.tables.index_of(&table.table_goal),
+                Some(index),
+                "forest invariant: table {:?} is not registered under its goal",
+                index,
+            );
+
+            for strand in table.strands() {
+                if let Some(selected) = &strand.selected_subgoal {
+                    assert!(
+                        selected.subgoal_table.value < self.tables.len(),
+                        "forest invariant: strand of table {:?} refers to missing table {:?}",
+                        index,
+                        selected.subgoal_table,
+                    );
+                }
+            }
+
+            if table.is_floundered() {
+                assert!(
+                    table.num_cached_answers() == 0 && table.strands().next().is_none(),
+                    "forest invariant: floundered table {:?} still has answers or strands",
+                    index,
+                );
+            }
+        }
+    }
+
+    /// Asserts that the DFNs of the tables on the stack are strictly
+    /// increasing from the bottom of the stack to the top, and that
+    /// none is newer than the last DFN handed out.
+    fn check_stack(&self) {
+        let mut previous = None;
+        for entry in self.stack.iter() {
+            if let Some(previous) = previous {
+                assert!(
+                    previous < entry.dfn,
+                    "forest invariant: DFN {:?} of table {:?} out of order on the stack",
+                    entry.dfn,
+                    entry.table,
+                );
+            }
+            assert!(
+                entry.dfn < self.dfn,
+                "forest invariant: DFN {:?} of table {:?} was never handed out",
+                entry.dfn,
+                entry.table,
+            );
+            previous = Some(entry.dfn);
+        }
+    }
+
+    /// Renders the tables of the forest as a graph in the DOT
+    /// language. There is one node per table, labeled with its goal
+    /// and number of answers (floundered tables are drawn dashed),
+    /// and an edge from each table to the tables whose answers its
+    /// strands are currently waiting on.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph forest {{").unwrap();
+        for (index, table) in self.tables.iter() {
+            let label = format!(
+                "{:?}\\n{} answer(s)",
+                table.table_goal,
+                table.num_cached_answers()
+            );
+            writeln!(
+                out,
+                "    {} [label=\"{}\"{}];",
+                node(index),
+                label.replace('"', "\\\""),
+                if table.is_floundered() {
+                    ", style=dashed"
+                } else {
+                    ""
+                },
+            )
+            .unwrap();
+        }
+        for (index, table) in self.tables.iter() {
+            for strand in table.strands() {
+                if let Some(selected) = &strand.selected_subgoal {
+                    writeln!(
+                        out,
+                        "    {} -> {};",
+                        node(index),
+                        node(selected.subgoal_table)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+fn node(index: TableIndex) -> String {
+    format!("table{}", index.value)
+}
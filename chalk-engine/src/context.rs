@@ -10,6 +10,7 @@ use crate::hh::HhGoal;
 use crate::{Answer, ExClause};
 use std::fmt::Debug;
 use std::hash::Hash;
+use std::mem;
 
 pub(crate) mod prelude;
 
@@ -147,6 +148,23 @@ pub trait Context: Clone + Debug {
 
     fn num_universes(_: &Self::UCanonicalGoalInEnvironment) -> usize;
 
+    /// Approximate number of bytes retained by a table goal, for
+    /// memory accounting only (see `Forest::table_memory`). The
+    /// default counts the value itself, but not the data it points to.
+    fn approx_goal_size(goal: &Self::UCanonicalGoalInEnvironment) -> usize {
+        mem::size_of_val(goal)
+    }
+
+    /// As `approx_goal_size`, but for an answer.
+    fn approx_subst_size(subst: &Self::CanonicalConstrainedSubst) -> usize {
+        mem::size_of_val(subst)
+    }
+
+    /// As `approx_goal_size`, but for the ex-clause of a strand.
+    fn approx_ex_clause_size(ex_clause: &Self::CanonicalExClause) -> usize {
+        mem::size_of_val(ex_clause)
+    }
+
     /// Convert a goal G *from* the canonical universes *into* our
     /// local universes. This will yield a goal G' that is the same
     /// but for the universes of universally quantified names.
@@ -216,6 +234,13 @@ pub trait ContextOps<C: Context>: Sized + Clone + Debug + AggregateOps<C> {
 
     /// returns unique solution from answer
     fn constrained_subst_from_answer(&self, answer: Answer<C>) -> C::CanonicalConstrainedSubst;
+
+    /// True if a subgoal whose table is already complete should be
+    /// selected before the one that `next_subgoal_index` picks: its
+    /// answers are known, so it costs nothing to prove, and either
+    /// fails the strand at once or constrains the variables that the
+    /// other subgoals mention.
+    fn prefer_cached_subgoals(&self) -> bool;
 }
 
 /// Methods for combining solutions to yield an aggregate solution.
@@ -246,6 +271,11 @@ pub trait InferenceTable<C: Context>: ResolventOps<C> + TruncateOps<C> + Unifica
     /// Upcast this domain goal into a more general goal.
     fn into_goal(&self, domain_goal: C::DomainGoal) -> C::Goal;
 
+    /// Removes the subgoals of `ex_clause` that duplicate one of its
+    /// other subgoals, so that they are not proven more than once.
+    /// Used by: logic
+    fn dedup_subgoals(&mut self, ex_clause: &mut ExClause<C>);
+
     /// Selects the next appropriate subgoal index for evaluation.
     /// Used by: logic
     fn next_subgoal_index(&mut self, ex_clause: &ExClause<C>) -> usize;
@@ -258,8 +288,12 @@ pub struct Floundered;
 
 /// Methods for unifying and manipulating terms and binders.
 pub trait UnificationOps<C: Context> {
+    /// Instantiates the binders of `arg` with fresh placeholders, in a
+    /// new universe. Returns `None` if that would exceed a resource
+    /// limit, in which case the goal is ambiguous (whatever
+    /// `cannot_prove_holds` says).
     // Used by: simplify
-    fn instantiate_binders_universally(&mut self, arg: &C::BindersGoal) -> C::Goal;
+    fn instantiate_binders_universally(&mut self, arg: &C::BindersGoal) -> Option<C::Goal>;
 
     // Used by: simplify
     fn instantiate_binders_existentially(&mut self, arg: &C::BindersGoal) -> C::Goal;
@@ -300,6 +334,25 @@ pub trait UnificationOps<C: Context> {
         b: &C::Parameter,
         ex_clause: &mut ExClause<C>,
     ) -> Fallible<()>;
+
+    /// Whether a `CannotProve` goal is treated as holding, so that the
+    /// answers that depend on it are not ambiguous but carry a caveat
+    /// (see `ExClause::caveat`), rather than as ambiguous.
+    // Used by: simplify
+    fn cannot_prove_holds(&self) -> bool;
+
+    /// Adds the region constraint that `a` outlives `b` to the
+    /// ex-clause, unless it is known to hold.
+    ///
+    /// If it is known not to hold, then `Error` is returned
+    // Used by: simplify
+    fn outlives_into_ex_clause(
+        &mut self,
+        environment: &C::Environment,
+        a: &C::Parameter,
+        b: &C::Parameter,
+        ex_clause: &mut ExClause<C>,
+    ) -> Fallible<()>;
 }
 
 /// "Truncation" (called "abstraction" in the papers referenced below)
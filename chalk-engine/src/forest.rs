@@ -1,18 +1,30 @@
 use crate::context::prelude::*;
 use crate::context::AnswerStream;
 use crate::logic::RootSearchFail;
+use crate::profile::TableProfile;
 use crate::stack::{Stack, StackIndex};
 use crate::table::AnswerIndex;
 use crate::tables::Tables;
+use crate::watch::StackWatch;
 use crate::Answer;
 use crate::{DepthFirstNumber, TableIndex};
+use rustc_hash::FxHashMap;
 
 pub struct Forest<C: Context> {
     context: C,
     pub(crate) tables: Tables<C>,
     pub(crate) stack: Stack,
 
-    dfn: DepthFirstNumber,
+    /// The next DFN to hand out (see `next_dfn`).
+    pub(crate) dfn: DepthFirstNumber,
+
+    /// The work done on each table for the current root goal (see
+    /// `hottest_tables`).
+    pub(crate) profile: FxHashMap<TableIndex, TableProfile>,
+
+    /// The view of `stack` that other threads read, once one was asked
+    /// for (see `watch_stack`).
+    pub(crate) stack_watch: Option<StackWatch<C::UCanonicalGoalInEnvironment>>,
 }
 
 impl<C: Context> Forest<C> {
@@ -22,6 +34,21 @@ impl<C: Context> Forest<C> {
             tables: Tables::new(),
             stack: Stack::default(),
             dfn: DepthFirstNumber::MIN,
+            profile: FxHashMap::default(),
+            stack_watch: None,
+        }
+    }
+
+    /// Drops all the tables, leaving the forest as `new` makes it,
+    /// except that the stack stays watched if it was (see
+    /// `watch_stack`).
+    pub fn reset(&mut self) {
+        self.tables = Tables::new();
+        self.stack = Stack::default();
+        self.dfn = DepthFirstNumber::MIN;
+        self.profile.clear();
+        if let Some(watch) = &self.stack_watch {
+            watch.pop(0);
         }
     }
 
@@ -55,6 +82,7 @@ impl<C: Context> Forest<C> {
         goal: C::UCanonicalGoalInEnvironment,
         num_answers: usize,
     ) -> Option<Vec<Answer<C>>> {
+        self.reset_profile();
         let table = self.get_or_create_table_for_ucanonical_goal(context, goal);
         let mut answers = Vec::with_capacity(num_answers);
         for i in 0..num_answers {
@@ -91,6 +119,7 @@ impl<C: Context> Forest<C> {
         context: &'f impl ContextOps<C>,
         goal: &C::UCanonicalGoalInEnvironment,
     ) -> impl AnswerStream<C> + 'f {
+        self.reset_profile();
         let table = self.get_or_create_table_for_ucanonical_goal(context, goal.clone());
         let answer = AnswerIndex::ZERO;
         ForestSolver {
@@ -176,6 +205,67 @@ impl<C: Context> Forest<C> {
         let table = self.get_or_create_table_for_ucanonical_goal(context, goal.clone());
         self.tables[table].num_cached_answers()
     }
+
+    /// Returns the number of tables created so far. Useful for testing.
+    pub fn num_tables(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// Takes a snapshot of the tables in the forest, to which
+    /// `rollback_to` can later return, e.g. after solving a goal
+    /// speculatively. Snapshots can be nested.
+    ///
+    /// # Panics
+    ///
+    /// Panics if invoked while solving (i.e., with a non-empty stack).
+    pub fn snapshot(&self) -> ForestSnapshot {
+        assert!(self.stack.is_empty(), "snapshot taken while solving");
+        ForestSnapshot {
+            num_tables: self.tables.len(),
+        }
+    }
+
+    /// Drops the tables created since `snapshot` was taken. Answers
+    /// found since then for the tables that already existed are kept:
+    /// they hold regardless of the goals solved in between, and the
+    /// strands that produced them are gone. The strands of those
+    /// tables that were waiting on a dropped table go back to selecting
+    /// a subgoal, which creates the table anew if it is needed again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if invoked while solving (i.e., with a non-empty stack).
+    pub fn rollback_to(&mut self, snapshot: ForestSnapshot) {
+        assert!(self.stack.is_empty(), "rollback while solving");
+        let num_tables = snapshot.num_tables;
+        if num_tables >= self.tables.len() {
+            return;
+        }
+        debug!(
+            "rollback_to: dropping {} tables",
+            self.tables.len() - num_tables
+        );
+
+        self.tables.truncate(num_tables);
+        self.profile.retain(|table, _| table.value < num_tables);
+        for table in &mut self.tables {
+            for strand in table.strands_mut() {
+                let dropped = strand
+                    .selected_subgoal
+                    .as_ref()
+                    .is_some_and(|selected| selected.subgoal_table.value >= num_tables);
+                if dropped {
+                    strand.selected_subgoal = None;
+                }
+            }
+        }
+    }
+}
+
+/// A snapshot of the tables of a `Forest`; see `Forest::snapshot`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ForestSnapshot {
+    num_tables: usize,
 }
 
 struct ForestSolver<'me, C: Context, CO: ContextOps<C>> {
@@ -205,6 +295,7 @@ impl<'me, C: Context, CO: ContextOps<C>> AnswerStream<C> for ForestSolver<'me, C
                     return Some(Answer {
                         subst: self.context.identity_constrained_subst(table_goal),
                         ambiguous: true,
+                        caveat: false,
                     });
                 }
 
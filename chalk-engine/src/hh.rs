@@ -11,6 +11,11 @@ pub enum HhGoal<C: Context> {
     And(C::Goal, C::Goal),
     Not(C::Goal),
     Unify(C::Variance, C::Parameter, C::Parameter),
+
+    /// `a: b`, where `b` is a lifetime: that `a` (a type or lifetime)
+    /// outlives `b`. Like the lifetime equalities of unification, this
+    /// is not proven but recorded as a region constraint.
+    Outlives(C::Parameter, C::Parameter),
     DomainGoal(C::DomainGoal),
 
     /// Indicates something that cannot be proven to be true or false
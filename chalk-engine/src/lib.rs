@@ -66,17 +66,21 @@ use std::cmp::min;
 use std::fmt::Debug;
 use std::usize;
 
+mod check;
 pub mod context;
 mod derived;
 pub mod fallible;
 pub mod forest;
 pub mod hh;
 mod logic;
+pub mod memory;
+pub mod profile;
 mod simplify;
 mod stack;
 mod strand;
 mod table;
 mod tables;
+pub mod watch;
 
 index_struct! {
     pub struct TableIndex { // FIXME: pub b/c Fold
@@ -117,6 +121,12 @@ pub struct ExClause<C: Context> {
     /// but we don't bother, as we don't need that support.)
     pub ambiguous: bool,
 
+    /// True if we encountered a `CannotProve` goal but treated it as
+    /// holding, rather than as ambiguous (see
+    /// `UnificationOps::cannot_prove_holds`), or depended on an answer
+    /// that did.
+    pub caveat: bool,
+
     /// Region constraints we have accumulated.
     pub constraints: Vec<C::RegionConstraint>,
 
@@ -209,6 +219,10 @@ pub struct Answer<C: Context> {
     /// nor disproven. This could be the size of the answer exceeded
     /// `max_size` or because of a negative loop (e.g., `P :- not { P }`).
     pub ambiguous: bool,
+
+    /// If this flag is set, then the answer was proven by treating a
+    /// `CannotProve` goal as holding (see `ExClause::caveat`).
+    pub caveat: bool,
 }
 
 /// Either `A` or `~A`, where `A` is a `Env |- Goal`.
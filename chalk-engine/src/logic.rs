@@ -10,6 +10,7 @@ use crate::{
     DepthFirstNumber, ExClause, FlounderedSubgoal, Literal, Minimums, TableIndex, TimeStamp,
 };
 use std::mem;
+use std::time::Instant;
 
 type RootSearchResult<T> = Result<T, RootSearchFail>;
 
@@ -114,7 +115,13 @@ impl<C: Context> Forest<C> {
     ) -> RootSearchResult<()> {
         assert!(self.stack.is_empty());
 
-        match self.ensure_answer_recursively(context, table, answer) {
+        let result = self.ensure_answer_recursively(context, table, answer);
+
+        if cfg!(debug_assertions) {
+            self.check_invariants();
+        }
+
+        match result {
             Ok(EnsureSuccess::AnswerAvailable) => Ok(()),
             Err(RecursiveSearchFail::Floundered) => Err(RootSearchFail::Floundered),
             Err(RecursiveSearchFail::NoMoreSolutions) => Err(RootSearchFail::NoMoreSolutions),
@@ -210,8 +217,16 @@ impl<C: Context> Forest<C> {
 
         let dfn = self.next_dfn();
         let depth = self.stack.push(table, dfn);
+        if let Some(watch) = &self.stack_watch {
+            watch.push(self.stack.len(), &self.tables[table].table_goal);
+        }
+        let start = Instant::now();
         let result = crate::maybe_grow_stack(|| self.pursue_next_strand(context, depth));
-        self.stack.pop(table, depth);
+        let time = self.stack.pop(table, depth, start.elapsed());
+        if let Some(watch) = &self.stack_watch {
+            watch.pop(self.stack.len());
+        }
+        self.profile_activation(table, time);
         info!("ensure_answer: result = {:?}", result);
         result.map(|()| EnsureSuccess::AnswerAvailable)
     }
@@ -243,6 +258,7 @@ impl<C: Context> Forest<C> {
         loop {
             match self.tables[table].pop_next_strand() {
                 Some(canonical_strand) => {
+                    self.profile_strand(table);
                     let num_universes = C::num_universes(&self.tables[table].table_goal);
                     let CanonicalStrand {
                         canonical_ex_clause,
@@ -438,7 +454,15 @@ impl<C: Context> Forest<C> {
                     continue;
                 }
 
-                let subgoal_index = strand.infer.next_subgoal_index(&strand.ex_clause);
+                strand.infer.dedup_subgoals(&mut strand.ex_clause);
+                let mut subgoal_index = strand.infer.next_subgoal_index(&strand.ex_clause);
+                if context.prefer_cached_subgoals() {
+                    if let Some(cached_index) =
+                        self.cached_subgoal_index(&mut strand.infer, &strand.ex_clause)
+                    {
+                        subgoal_index = cached_index;
+                    }
+                }
 
                 // Get or create table for this subgoal.
                 match self.get_or_create_table_for_subgoal(
@@ -531,6 +555,7 @@ impl<C: Context> Forest<C> {
                     subst,
                     constraints,
                     ambiguous,
+                    caveat,
                     subgoals,
                     current_time: _,
                     floundered_subgoals,
@@ -546,6 +571,7 @@ impl<C: Context> Forest<C> {
         let answer = Answer {
             subst: answer_subst,
             ambiguous: ambiguous,
+            caveat,
         };
 
         // A "trivial" answer is one that is 'just true for all cases'
@@ -608,6 +634,7 @@ impl<C: Context> Forest<C> {
         // must be backed by an impl *eventually*).
         let is_trivial_answer = {
             !answer.ambiguous
+                && !answer.caveat
                 && C::is_trivial_substitution(&self.tables[table].table_goal, &answer.subst)
                 && C::empty_constraints(&answer.subst)
         };
@@ -675,6 +702,45 @@ impl<C: Context> Forest<C> {
         Some((table, universe_map))
     }
 
+    /// Returns the index of the positive subgoal of `ex_clause` whose
+    /// table is complete (i.e., has all of its answers) and has the
+    /// fewest answers, if any subgoal has a complete table: one
+    /// without answers fails the strand at once, and one with a single
+    /// answer fixes the variables it mentions for the other subgoals.
+    /// Ties go to the later subgoal, as with `next_subgoal_index`.
+    ///
+    /// A table on the stack is not complete, even if its strands are
+    /// out of it for the moment. Subgoals are looked up without
+    /// truncation, so one whose table is that of its truncated form is
+    /// not found.
+    fn cached_subgoal_index(
+        &self,
+        infer: &mut dyn InferenceTable<C>,
+        ex_clause: &ExClause<C>,
+    ) -> Option<usize> {
+        let mut best: Option<(usize, usize)> = None;
+        for (index, subgoal) in ex_clause.subgoals.iter().enumerate() {
+            let subgoal = match subgoal {
+                Literal::Positive(subgoal) => subgoal,
+                Literal::Negative(_) => continue,
+            };
+            let (ucanonical_subgoal, _) = infer.fully_canonicalize_goal(subgoal);
+            let table = match self.tables.index_of(&ucanonical_subgoal) {
+                Some(table) if self.stack.is_active(table).is_none() => &self.tables[table],
+                _ => continue,
+            };
+            if table.is_floundered() || table.strands().next().is_some() {
+                continue;
+            }
+            let num_answers = table.num_cached_answers();
+            if best.is_none_or(|(_, best_answers)| num_answers <= best_answers) {
+                best = Some((index, num_answers));
+            }
+        }
+        debug!("cached_subgoal_index: {:?}", best);
+        best.map(|(index, _)| index)
+    }
+
     /// Given a u-canonical goal, searches for an existing table. If
     /// one is found, it is returned, but otherwise a new table is
     /// created (and populated with its initial set of strands).
@@ -1015,6 +1081,9 @@ impl<C: Context> Forest<C> {
                             if answer.ambiguous {
                                 ex_clause.ambiguous = true;
                             }
+                            if answer.caveat {
+                                ex_clause.caveat = true;
+                            }
                         }
 
                         // Increment time counter because we received a new answer.
@@ -1263,6 +1332,7 @@ impl<C: Context> Forest<C> {
                     ExClause {
                         subst: truncated_subst,
                         ambiguous: true,
+                        caveat: false,
                         constraints: vec![],
                         subgoals: vec![],
                         current_time: TimeStamp::default(),
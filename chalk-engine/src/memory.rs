@@ -0,0 +1,54 @@
+//! Approximate memory accounting for the forest, for debugging memory
+//! blowups: which tables retain the most data, and in what form. The
+//! sizes are computed on demand by walking the forest, so there is no
+//! cost unless a report is requested.
+
+use crate::context::Context;
+use crate::forest::Forest;
+use std::cmp::Reverse;
+
+/// The approximate memory retained by one table of the forest (see
+/// `Forest::table_memory`). The sizes are as reported by the
+/// `approx_*_size` methods of the context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableMemory<G> {
+    /// The goal of the table.
+    pub goal: G,
+
+    /// Bytes retained by the goal.
+    pub goal_bytes: usize,
+
+    /// The number of cached answers.
+    pub num_answers: usize,
+
+    /// Bytes retained by the cached answers, including the index
+    /// used to detect duplicate answers.
+    pub answer_bytes: usize,
+
+    /// The number of strands still being pursued.
+    pub num_strands: usize,
+
+    /// Bytes retained by the strands.
+    pub strand_bytes: usize,
+}
+
+impl<G> TableMemory<G> {
+    /// Total bytes retained by the table.
+    pub fn total_bytes(&self) -> usize {
+        self.goal_bytes + self.answer_bytes + self.strand_bytes
+    }
+}
+
+impl<C: Context> Forest<C> {
+    /// Returns the approximate memory retained by each table of the
+    /// forest, largest first.
+    pub fn table_memory(&self) -> Vec<TableMemory<C::UCanonicalGoalInEnvironment>> {
+        let mut tables: Vec<_> = self
+            .tables
+            .iter()
+            .map(|(_, table)| table.memory())
+            .collect();
+        tables.sort_by_key(|table| Reverse(table.total_bytes()));
+        tables
+    }
+}
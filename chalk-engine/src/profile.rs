@@ -0,0 +1,80 @@
+//! Profiling of the tables worked on for the current root goal, so
+//! that users can see which subgoals (e.g., which bound in their where
+//! clauses) made the goal slow. The profile is reset whenever a new
+//! root goal is solved.
+
+use crate::context::Context;
+use crate::forest::Forest;
+use crate::TableIndex;
+use std::cmp::Reverse;
+use std::time::Duration;
+
+/// The work done on one table while solving the current root goal.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct TableProfile {
+    time: Duration,
+    strands: usize,
+    activations: usize,
+}
+
+/// The work done on one table (i.e., one canonical subgoal) while
+/// solving the current root goal; see `Forest::hottest_tables`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TableCost<G> {
+    /// The goal of the table.
+    pub goal: G,
+
+    /// The time spent on the table itself, excluding the time spent
+    /// on the tables of its subgoals.
+    pub time: Duration,
+
+    /// The number of strands pursued.
+    pub strands: usize,
+
+    /// The number of times the table was activated to look for another
+    /// answer. More than one means that the table was re-run, e.g.
+    /// because it was part of a cycle, or because more answers were
+    /// needed.
+    pub activations: usize,
+}
+
+impl<C: Context> Forest<C> {
+    /// Forgets the profile of the previous root goal.
+    pub(crate) fn reset_profile(&mut self) {
+        self.profile.clear();
+    }
+
+    /// Records that `table` spent `time` on the stack (not counting
+    /// its subgoals) in one activation.
+    pub(crate) fn profile_activation(&mut self, table: TableIndex, time: Duration) {
+        let profile = self.profile.entry(table).or_default();
+        profile.time += time;
+        profile.activations += 1;
+    }
+
+    /// Records that a strand of `table` was pursued.
+    pub(crate) fn profile_strand(&mut self, table: TableIndex) {
+        self.profile.entry(table).or_default().strands += 1;
+    }
+
+    /// Returns (at most) the `k` tables that the current root goal
+    /// spent the most time on, most expensive first. Ties (e.g., at
+    /// the resolution of the clock) are broken by the number of
+    /// strands pursued, and then by the order of creation.
+    pub fn hottest_tables(&self, k: usize) -> Vec<TableCost<C::UCanonicalGoalInEnvironment>> {
+        let mut tables: Vec<_> = self.profile.iter().collect();
+        tables.sort_by_key(|&(&table, profile)| {
+            (Reverse(profile.time), Reverse(profile.strands), table)
+        });
+        tables
+            .into_iter()
+            .take(k)
+            .map(|(&table, profile)| TableCost {
+                goal: self.tables[table].table_goal.clone(),
+                time: profile.time,
+                strands: profile.strands,
+                activations: profile.activations,
+            })
+            .collect()
+    }
+}
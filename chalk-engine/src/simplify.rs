@@ -17,6 +17,7 @@ impl<C: Context> Forest<C> {
         let mut ex_clause = ExClause {
             subst,
             ambiguous: false,
+            caveat: false,
             constraints: vec![],
             subgoals: vec![],
             current_time: TimeStamp::default(),
@@ -28,10 +29,14 @@ impl<C: Context> Forest<C> {
 
         while let Some((environment, hh_goal)) = pending_goals.pop() {
             match hh_goal {
-                HhGoal::ForAll(subgoal) => {
-                    let subgoal = infer.instantiate_binders_universally(&subgoal);
-                    pending_goals.push((environment, infer.into_hh_goal(subgoal)));
-                }
+                HhGoal::ForAll(subgoal) => match infer.instantiate_binders_universally(&subgoal) {
+                    Some(subgoal) => {
+                        pending_goals.push((environment, infer.into_hh_goal(subgoal)));
+                    }
+                    // Like truncation, running out of universes makes
+                    // the goal ambiguous rather than `CannotProve`.
+                    None => ex_clause.ambiguous = true,
+                },
                 HhGoal::Exists(subgoal) => {
                     let subgoal = infer.instantiate_binders_existentially(&subgoal);
                     pending_goals.push((environment, infer.into_hh_goal(subgoal)))
@@ -59,6 +64,9 @@ impl<C: Context> Forest<C> {
                     &b,
                     &mut ex_clause,
                 )?,
+                HhGoal::Outlives(a, b) => {
+                    infer.outlives_into_ex_clause(&environment, &a, &b, &mut ex_clause)?
+                }
                 HhGoal::DomainGoal(domain_goal) => {
                     ex_clause
                         .subgoals
@@ -68,7 +76,11 @@ impl<C: Context> Forest<C> {
                         )));
                 }
                 HhGoal::CannotProve => {
-                    ex_clause.ambiguous = true;
+                    if infer.cannot_prove_holds() {
+                        ex_clause.caveat = true;
+                    } else {
+                        ex_clause.ambiguous = true;
+                    }
                 }
             }
         }
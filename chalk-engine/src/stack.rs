@@ -1,5 +1,6 @@
 use crate::{DepthFirstNumber, TableIndex};
 use std::ops::{Index, IndexMut, Range};
+use std::time::Duration;
 
 /// See `Forest`.
 #[derive(Default)]
@@ -24,6 +25,10 @@ pub(crate) struct StackEntry {
 
     /// The DFN of this computation.
     pub(super) dfn: DepthFirstNumber,
+
+    /// The time spent on the tables pushed on top of this one, which
+    /// is not counted towards its own time (see `TableCost`).
+    pub(super) subgoal_time: Duration,
 }
 
 impl Stack {
@@ -31,6 +36,15 @@ impl Stack {
         self.stack.is_empty()
     }
 
+    pub(super) fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Iterates over the entries from the bottom of the stack to the top.
+    pub(super) fn iter(&self) -> impl Iterator<Item = &StackEntry> {
+        self.stack.iter()
+    }
+
     /// Searches the stack to see if `table` is active. If so, returns
     /// its stack index.
     pub(super) fn is_active(&self, table: TableIndex) -> Option<StackIndex> {
@@ -53,14 +67,26 @@ impl Stack {
 
     pub(super) fn push(&mut self, table: TableIndex, dfn: DepthFirstNumber) -> StackIndex {
         let old_len = self.stack.len();
-        self.stack.push(StackEntry { table, dfn });
+        self.stack.push(StackEntry {
+            table,
+            dfn,
+            subgoal_time: Duration::default(),
+        });
         StackIndex::from(old_len)
     }
 
-    pub(super) fn pop(&mut self, table: TableIndex, depth: StackIndex) {
+    /// Pops the table at the top of the stack, which was active for
+    /// `time`, returning the part of `time` that it spent on itself
+    /// rather than on the tables pushed on top of it. All of `time`
+    /// counts as subgoal time for the table below.
+    pub(super) fn pop(&mut self, table: TableIndex, depth: StackIndex, time: Duration) -> Duration {
         assert_eq!(self.stack.len(), depth.value + 1);
         assert_eq!(self[depth].table, table);
-        self.stack.pop();
+        let entry = self.stack.pop().unwrap();
+        if let Some(below) = self.stack.last_mut() {
+            below.subgoal_time += time;
+        }
+        time.checked_sub(entry.subgoal_time).unwrap_or_default()
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::context::prelude::*;
+use crate::memory::TableMemory;
 use crate::strand::CanonicalStrand;
 use crate::Answer;
 use rustc_hash::FxHashMap;
@@ -27,8 +28,9 @@ pub(crate) struct Table<C: Context> {
     /// detect duplicates. Not every answer in `answers` will be
     /// represented here -- we discard answers from `answers_hash`
     /// (but not `answers`) when better answers arrive (in particular,
-    /// answers with no ambiguity).
-    answers_hash: FxHashMap<C::CanonicalConstrainedSubst, bool>,
+    /// answers with no ambiguity). Each is mapped to the rank of the
+    /// best answer with that substitution (see `Answer::rank`).
+    answers_hash: FxHashMap<C::CanonicalConstrainedSubst, u8>,
 
     /// Stores the active strands that we can "pull on" to find more
     /// answers.
@@ -64,6 +66,10 @@ impl<C: Context> Table<C> {
         self.strands.extend(strands);
     }
 
+    pub(crate) fn strands(&self) -> impl Iterator<Item = &CanonicalStrand<C>> {
+        self.strands.iter()
+    }
+
     pub(crate) fn strands_mut(&mut self) -> impl Iterator<Item = &mut CanonicalStrand<C>> {
         self.strands.iter_mut()
     }
@@ -92,7 +98,9 @@ impl<C: Context> Table<C> {
     /// Adds `answer` to our list of answers, unless it (or some
     /// better answer) is already present. An answer A is better than
     /// an answer B if their substitutions are the same, but A has a subset
-    /// of the delayed literals that B does.
+    /// of the delayed literals that B does: an unambiguous answer is
+    /// better than an ambiguous one, and one without a caveat better
+    /// than one with.
     ///
     /// Returns true if `answer` was added.
     pub(super) fn push_answer(&mut self, answer: Answer<C>) -> bool {
@@ -104,18 +112,18 @@ impl<C: Context> Table<C> {
             self.answers_hash.get(&answer.subst)
         );
 
+        let rank = answer.rank();
         let added = match self.answers_hash.entry(answer.subst.clone()) {
             Entry::Vacant(entry) => {
-                entry.insert(answer.ambiguous);
+                entry.insert(rank);
                 true
             }
 
             Entry::Occupied(mut entry) => {
-                let was_ambiguous = entry.get();
-                if !was_ambiguous || answer.ambiguous {
+                if *entry.get() <= rank {
                     false
                 } else {
-                    *entry.get_mut() = false;
+                    *entry.get_mut() = rank;
                     true
                 }
             }
@@ -140,6 +148,38 @@ impl<C: Context> Table<C> {
         self.answers.len()
     }
 
+    /// Returns the approximate memory retained by this table.
+    pub(crate) fn memory(&self) -> TableMemory<C::UCanonicalGoalInEnvironment> {
+        // The answers are also stored as the keys of `answers_hash`.
+        let answer_bytes: usize = self
+            .answers
+            .iter()
+            .map(|answer| C::approx_subst_size(&answer.subst))
+            .chain(
+                self.answers_hash
+                    .keys()
+                    .map(|subst| C::approx_subst_size(subst) + mem::size_of::<bool>()),
+            )
+            .sum();
+        let strand_bytes = self
+            .strands
+            .iter()
+            .map(|strand| {
+                C::approx_ex_clause_size(&strand.canonical_ex_clause)
+                    + mem::size_of_val(&strand.selected_subgoal)
+            })
+            .sum();
+
+        TableMemory {
+            goal: self.table_goal.clone(),
+            goal_bytes: C::approx_goal_size(&self.table_goal),
+            num_answers: self.answers.len(),
+            answer_bytes,
+            num_strands: self.strands.len(),
+            strand_bytes,
+        }
+    }
+
     pub(super) fn next_answer_index(&self) -> AnswerIndex {
         AnswerIndex::from(self.answers.len())
     }
@@ -151,8 +191,20 @@ impl AnswerIndex {
 
 impl<C: Context> Answer<C> {
     /// An "unconditional" answer is one that must be true -- this is
-    /// the case so long as we have no delayed literals.
+    /// the case so long as we have no delayed literals. An answer with
+    /// a caveat is not: its `CannotProve` subgoal was only taken to
+    /// hold, so it cannot disprove a negated goal.
     pub(super) fn is_unconditional(&self) -> bool {
-        !self.ambiguous
+        !self.ambiguous && !self.caveat
+    }
+
+    /// Orders the answers with the same substitution, best first (see
+    /// `Table::push_answer`).
+    fn rank(&self) -> u8 {
+        match (self.ambiguous, self.caveat) {
+            (false, false) => 0,
+            (false, true) => 1,
+            (true, _) => 2,
+        }
     }
 }
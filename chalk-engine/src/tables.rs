@@ -40,6 +40,29 @@ impl<C: Context> Tables<C> {
         index
     }
 
+    pub(super) fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    /// The number of goals registered in the goal-to-table map. This
+    /// should always equal `len()`.
+    pub(super) fn num_indices(&self) -> usize {
+        self.table_indices.len()
+    }
+
+    /// Drops the tables with index `len` and above.
+    pub(super) fn truncate(&mut self, len: usize) {
+        self.tables.truncate(len);
+        self.table_indices.retain(|_, index| index.value < len);
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (TableIndex, &Table<C>)> {
+        self.tables
+            .iter()
+            .enumerate()
+            .map(|(value, table)| (TableIndex { value }, table))
+    }
+
     pub(super) fn index_of(&self, literal: &C::UCanonicalGoalInEnvironment) -> Option<TableIndex> {
         self.table_indices.get(literal).cloned()
     }
@@ -0,0 +1,71 @@
+//! Reading the stack of a forest from another thread while it solves,
+//! so that a watchdog can report what a slow query is working on
+//! without cancelling it (see `Forest::watch_stack`).
+
+use crate::context::Context;
+use crate::forest::Forest;
+use std::sync::{Arc, Mutex};
+
+/// A view of the goals of the tables on the stack of a `Forest`, which
+/// is kept up to date as the forest solves. Clones share the same
+/// view, and can be sent to other threads.
+#[derive(Debug)]
+pub struct StackWatch<G> {
+    frames: Arc<Mutex<Vec<G>>>,
+}
+
+impl<G> Clone for StackWatch<G> {
+    fn clone(&self) -> Self {
+        StackWatch {
+            frames: self.frames.clone(),
+        }
+    }
+}
+
+impl<G: Clone> StackWatch<G> {
+    fn new() -> Self {
+        StackWatch {
+            frames: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// The goals of the (at most) `n` tables at the top of the stack,
+    /// innermost first. The root goal is last, if there are no more
+    /// than `n` tables on the stack; the stack is empty when the
+    /// forest is not solving.
+    pub fn snapshot(&self, n: usize) -> Vec<G> {
+        let frames = self.frames.lock().unwrap();
+        frames.iter().rev().take(n).cloned().collect()
+    }
+
+    /// The number of tables on the stack.
+    pub fn depth(&self) -> usize {
+        self.frames.lock().unwrap().len()
+    }
+
+    /// Records that the table of `goal` was pushed, making the stack
+    /// `depth` tables deep. Any frames that a panic left above it are
+    /// dropped first.
+    pub(crate) fn push(&self, depth: usize, goal: &G) {
+        let mut frames = self.frames.lock().unwrap();
+        frames.truncate(depth - 1);
+        frames.push(goal.clone());
+    }
+
+    /// Records that a table was popped, leaving the stack `depth`
+    /// tables deep.
+    pub(crate) fn pop(&self, depth: usize) {
+        self.frames.lock().unwrap().truncate(depth);
+    }
+}
+
+impl<C: Context> Forest<C> {
+    /// Returns a `StackWatch` for the stack of this forest. Until the
+    /// first call, the stack is not watched, which costs nothing;
+    /// afterwards, each push and pop takes a lock, and each push
+    /// clones the goal of the table. All the watches of a forest share
+    /// the same view.
+    pub fn watch_stack(&mut self) -> StackWatch<C::UCanonicalGoalInEnvironment> {
+        self.stack_watch.get_or_insert_with(StackWatch::new).clone()
+    }
+}
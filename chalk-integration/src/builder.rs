@@ -0,0 +1,676 @@
+//! Builds programs in code, for crates (such as fuzzers and property
+//! tests) that would otherwise generate the text of a program only to
+//! parse it again.
+//!
+//! The items are given in their lowered form, with parameters as bound
+//! variables (see the `binders` of each datum in `chalk_rust_ir`). The
+//! builder assigns the ids, fills in the binders from the declared
+//! parameter kinds and, in `ProgramBuilder::build`, checks that the
+//! items refer to each other as lowering would have made sure of.
+
+use crate::error::RustIrError;
+use crate::lowering::Kinded;
+use crate::program::Program;
+use chalk_engine::fallible::{Fallible, NoSolution};
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{
+    super_fold_lifetime, super_fold_ty, DefaultInferenceFolder, DefaultPlaceholderFolder, Fold,
+    FreeVarFolder, TypeFolder,
+};
+use chalk_ir::*;
+use chalk_parse::ast::{self, Kind, Span};
+use chalk_rust_ir::TypeSort;
+use chalk_rust_ir::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+/// Builds a `Program` item by item. Structs and traits are declared
+/// (which gives them an id) before they are defined, so that items can
+/// refer to each other regardless of the order in which they are
+/// defined; `add_struct` and `add_trait` do both at once.
+///
+/// ```ignore
+/// let mut builder = ProgramBuilder::new();
+/// let foo = builder.add_trait("Foo", vec![], trait_bound, trait_flags);
+/// let u32 = builder.add_struct("u32", vec![], struct_bound, struct_flags);
+/// builder.add_impl(vec![], impl_bound, Polarity::Positive, ImplType::Local);
+/// let program = builder.build()?;
+/// ```
+#[derive(Default)]
+pub struct ProgramBuilder {
+    next_index: u32,
+    type_ids: BTreeMap<Identifier, TypeKindId>,
+    type_kinds: BTreeMap<TypeKindId, TypeKind>,
+    struct_data: BTreeMap<StructId, Arc<StructDatum>>,
+    trait_data: BTreeMap<TraitId, Arc<TraitDatum>>,
+    impl_data: BTreeMap<ImplId, Arc<ImplDatum>>,
+    associated_ty_data: BTreeMap<TypeId, Arc<AssociatedTyDatum>>,
+    associated_ty_values: BTreeMap<AssociatedTyValueId, Arc<AssociatedTyValue>>,
+    custom_clauses: Vec<ProgramClause<ChalkIr>>,
+    lang_items: BTreeMap<LangItem, TypeKindId>,
+    well_known_items: WellKnownItems,
+
+    /// The structs and traits declared but not defined (yet).
+    undefined: BTreeSet<TypeKindId>,
+
+    /// The first error found while adding items, which `build`
+    /// reports.
+    error: Option<RustIrError>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        ProgramBuilder::default()
+    }
+
+    fn next_id(&mut self) -> RawId {
+        let index = self.next_index;
+        self.next_index += 1;
+        RawId { index }
+    }
+
+    fn record_error(&mut self, error: RustIrError) {
+        self.error.get_or_insert(error);
+    }
+
+    fn declare(
+        &mut self,
+        id: TypeKindId,
+        sort: TypeSort,
+        name: &str,
+        binders: Vec<ParameterKind<()>>,
+    ) {
+        let name = chalk_ir::intern(name);
+        if self.type_ids.insert(name, id).is_some() {
+            self.record_error(RustIrError::DuplicateTypeName(ident(name)));
+        }
+        self.type_kinds.insert(
+            id,
+            TypeKind {
+                sort,
+                name,
+                binders: Binders {
+                    binders: binders.into(),
+                    value: (),
+                },
+            },
+        );
+        self.undefined.insert(id);
+    }
+
+    /// The kinds of the parameters declared for `id`, which must have
+    /// been declared by this builder.
+    fn declared_binders(&self, id: TypeKindId) -> ParameterKinds {
+        match self.type_kinds.get(&id) {
+            Some(type_kind) => type_kind.binders.binders,
+            None => panic!("`{:?}` was not declared by this builder", id),
+        }
+    }
+
+    /// Declares a struct with parameters of the kinds `binders`.
+    pub fn declare_struct(&mut self, name: &str, binders: Vec<ParameterKind<()>>) -> StructId {
+        let id = StructId(self.next_id());
+        self.declare(id.into(), TypeSort::Struct, name, binders);
+        id
+    }
+
+    /// Defines the struct `id`, whose parameters are the bound
+    /// variables of `bound`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` was not declared by this builder.
+    pub fn define_struct(&mut self, id: StructId, bound: StructDatumBound, flags: StructFlags) {
+        let binders = self.declared_binders(id.into());
+        if let Some(scalar) = flags.scalar {
+            self.well_known_items.insert_scalar(id, scalar);
+        }
+        self.undefined.remove(&id.into());
+        self.struct_data.insert(
+            id,
+            Arc::new(StructDatum {
+                binders: Binders {
+                    binders,
+                    value: bound,
+                },
+                id,
+                flags,
+            }),
+        );
+    }
+
+    pub fn add_struct(
+        &mut self,
+        name: &str,
+        binders: Vec<ParameterKind<()>>,
+        bound: StructDatumBound,
+        flags: StructFlags,
+    ) -> StructId {
+        let id = self.declare_struct(name, binders);
+        self.define_struct(id, bound, flags);
+        id
+    }
+
+    /// Declares a trait with parameters of the kinds `binders`, not
+    /// counting `Self`.
+    pub fn declare_trait(&mut self, name: &str, binders: Vec<ParameterKind<()>>) -> TraitId {
+        let id = TraitId(self.next_id());
+        self.declare(id.into(), TypeSort::Trait, name, binders);
+        id
+    }
+
+    /// Defines the trait `id`. In `bound`, `Self` is the bound
+    /// variable 0 and the declared parameters follow it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` was not declared by this builder.
+    pub fn define_trait(&mut self, id: TraitId, bound: TraitDatumBound, flags: TraitFlags) {
+        let binders = trait_binders(&self.declared_binders(id.into()));
+        let associated_ty_ids = self
+            .associated_ty_data
+            .values()
+            .filter(|datum| datum.trait_id == id)
+            .map(|datum| datum.id)
+            .collect();
+        self.undefined.remove(&id.into());
+        self.trait_data.insert(
+            id,
+            Arc::new(TraitDatum {
+                id,
+                binders: Binders {
+                    binders: binders.into(),
+                    value: bound,
+                },
+                flags,
+                associated_ty_ids,
+                methods: vec![],
+            }),
+        );
+    }
+
+    pub fn add_trait(
+        &mut self,
+        name: &str,
+        binders: Vec<ParameterKind<()>>,
+        bound: TraitDatumBound,
+        flags: TraitFlags,
+    ) -> TraitId {
+        let id = self.declare_trait(name, binders);
+        self.define_trait(id, bound, flags);
+        id
+    }
+
+    /// Adds an associated type to the trait `trait_id`, with
+    /// parameters of the kinds `binders` of its own. In `bound`, those
+    /// parameters come first, followed by `Self` and the parameters of
+    /// the trait (see `AssociatedTyDatum::binders`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `trait_id` was not declared by this builder.
+    pub fn add_associated_ty(
+        &mut self,
+        trait_id: TraitId,
+        name: &str,
+        mut binders: Vec<ParameterKind<()>>,
+        bound: AssociatedTyDatumBound,
+    ) -> TypeId {
+        binders.extend(trait_binders(&self.declared_binders(trait_id.into())));
+        let id = TypeId(self.next_id());
+        self.associated_ty_data.insert(
+            id,
+            Arc::new(AssociatedTyDatum {
+                trait_id,
+                id,
+                name: chalk_ir::intern(name),
+                binders: Binders {
+                    binders: binders.into(),
+                    value: bound,
+                },
+            }),
+        );
+        if let Some(trait_datum) = self.trait_data.get_mut(&trait_id) {
+            Arc::make_mut(trait_datum).associated_ty_ids.push(id);
+        }
+        id
+    }
+
+    /// Adds an impl with parameters of the kinds `binders`, which are
+    /// the bound variables of `bound`.
+    pub fn add_impl(
+        &mut self,
+        binders: Vec<ParameterKind<()>>,
+        bound: ImplDatumBound,
+        polarity: Polarity,
+        impl_type: ImplType,
+    ) -> ImplId {
+        let id = ImplId(self.next_id());
+        self.impl_data.insert(
+            id,
+            Arc::new(ImplDatum {
+                polarity,
+                binders: Binders {
+                    binders: binders.into(),
+                    value: bound,
+                },
+                impl_type,
+                associated_ty_value_ids: vec![],
+                methods: vec![],
+            }),
+        );
+        id
+    }
+
+    /// Adds the value `ty` of the associated type `associated_ty_id`
+    /// to the impl `impl_id`, with parameters of the kinds `binders`
+    /// of its own. In `ty`, those parameters come first, followed by
+    /// the parameters of the impl.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `impl_id` was not added by this builder.
+    pub fn add_associated_ty_value(
+        &mut self,
+        impl_id: ImplId,
+        associated_ty_id: TypeId,
+        mut binders: Vec<ParameterKind<()>>,
+        ty: Ty<ChalkIr>,
+    ) -> AssociatedTyValueId {
+        let id = AssociatedTyValueId(self.next_id());
+        let impl_datum = match self.impl_data.get_mut(&impl_id) {
+            Some(impl_datum) => Arc::make_mut(impl_datum),
+            None => panic!("`{:?}` was not added by this builder", impl_id),
+        };
+        binders.extend(impl_datum.binders.binders.iter().cloned());
+        impl_datum.associated_ty_value_ids.push(id);
+        self.associated_ty_values.insert(
+            id,
+            Arc::new(AssociatedTyValue {
+                impl_id,
+                associated_ty_id,
+                value: Binders {
+                    binders: binders.into(),
+                    value: AssociatedTyValueBound { ty },
+                },
+            }),
+        );
+        id
+    }
+
+    pub fn add_custom_clause(&mut self, clause: ProgramClause<ChalkIr>) {
+        self.custom_clauses.push(clause);
+    }
+
+    pub fn set_lang_item(&mut self, item: LangItem, id: TypeKindId) {
+        if self.lang_items.insert(item, id).is_some() {
+            self.record_error(RustIrError::DuplicateLangItem(item));
+        }
+    }
+
+    pub fn set_well_known_trait(&mut self, item: WellKnownTrait, trait_id: TraitId) {
+        if self.well_known_items.insert(item, trait_id).is_some() {
+            self.record_error(RustIrError::DuplicateWellKnownTrait(item));
+        }
+    }
+
+    /// Checks the items and returns the program. The checks are those
+    /// that lowering makes: every declared item is defined, and each
+    /// item only refers to items of the program, with the right
+    /// number and kinds of parameters, and to bound variables that it
+    /// binds. (Like a lowered program, the result is not checked for
+    /// well-formedness or coherence; see `LoweringDatabase` for
+    /// those.)
+    pub fn build(self) -> Result<Program, RustIrError> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+        if let Some(id) = self.undefined.iter().next() {
+            return Err(RustIrError::UndefinedItem(ident(self.type_kinds[id].name)));
+        }
+
+        let mut checker = Checker {
+            type_kinds: &self.type_kinds,
+            associated_ty_data: &self.associated_ty_data,
+            num_binders: 0,
+            error: None,
+        };
+
+        for struct_datum in self.struct_data.values() {
+            let name = self.type_kinds[&struct_datum.id.into()].name;
+            let num_binders = struct_datum.binders.len();
+            if struct_datum.flags.fundamental && num_binders != 1 {
+                Err(RustIrError::InvalidFundamentalTypesParameters(ident(name)))?;
+            }
+            if struct_datum.flags.scalar.is_some() && num_binders != 0 {
+                Err(RustIrError::ScalarParameters(ident(name)))?;
+            }
+            let bound = &struct_datum.binders.value;
+            checker.check(&bound.fields, num_binders)?;
+            checker.check_where_clauses(&bound.where_clauses, num_binders)?;
+        }
+
+        for trait_datum in self.trait_data.values() {
+            let name = self.type_kinds[&trait_datum.id.into()].name;
+            if trait_datum.is_auto_trait() {
+                if trait_datum.binders.len() != 1 {
+                    Err(RustIrError::AutoTraitParameters(ident(name)))?;
+                }
+                if !trait_datum.binders.value.where_clauses.is_empty() {
+                    Err(RustIrError::AutoTraitWhereClauses(ident(name)))?;
+                }
+                if !trait_datum.associated_ty_ids.is_empty() {
+                    Err(RustIrError::AutoTraitAssociatedTypes(ident(name)))?;
+                }
+            }
+            checker.check_where_clauses(
+                &trait_datum.binders.value.where_clauses,
+                trait_datum.binders.len(),
+            )?;
+        }
+
+        for associated_ty_datum in self.associated_ty_data.values() {
+            let num_binders = associated_ty_datum.binders.len();
+            let bound = &associated_ty_datum.binders.value;
+            for inline_bound in &bound.bounds {
+                let trait_bound = match &inline_bound.value {
+                    InlineBound::TraitBound(trait_bound) => trait_bound,
+                    InlineBound::ProjectionEqBound(projection_eq_bound) => {
+                        checker.check_associated_ty(
+                            projection_eq_bound.associated_ty_id,
+                            &[],
+                            false,
+                        )?;
+                        &projection_eq_bound.trait_bound
+                    }
+                };
+                checker.check_trait_parameters(trait_bound.trait_id, &trait_bound.args_no_self)?;
+            }
+            checker.check(&bound.bounds, num_binders)?;
+            checker.check_where_clauses(&bound.where_clauses, num_binders)?;
+            if let Some(default) = &bound.default {
+                checker.check(default, num_binders)?;
+            }
+        }
+
+        for impl_datum in self.impl_data.values() {
+            let num_binders = impl_datum.binders.len();
+            let bound = &impl_datum.binders.value;
+            checker.check_trait_ref(&bound.trait_ref)?;
+            checker.check(&bound.trait_ref, num_binders)?;
+            checker.check_where_clauses(&bound.where_clauses, num_binders)?;
+
+            let trait_name = self.type_kinds[&impl_datum.trait_id().into()].name;
+            if !impl_datum.is_positive() && !impl_datum.associated_ty_value_ids.is_empty() {
+                Err(RustIrError::NegativeImplAssociatedValues(ident(trait_name)))?;
+            }
+            for atv_id in &impl_datum.associated_ty_value_ids {
+                let atv = &self.associated_ty_values[atv_id];
+                match self.associated_ty_data.get(&atv.associated_ty_id) {
+                    Some(datum) if datum.trait_id == impl_datum.trait_id() => {}
+                    Some(datum) => Err(RustIrError::MissingAssociatedType(ident(datum.name)))?,
+                    None => Err(RustIrError::InvalidItemId(atv.associated_ty_id.into()))?,
+                }
+                checker.check(&atv.value.value.ty, atv.value.len())?;
+            }
+        }
+
+        checker.check(&self.custom_clauses, 0)?;
+
+        for &id in self.lang_items.values() {
+            if !self.type_kinds.contains_key(&id) {
+                Err(RustIrError::InvalidItemId(id))?;
+            }
+        }
+
+        Ok(Program {
+            type_ids: self.type_ids,
+            type_kinds: self.type_kinds,
+            struct_data: self.struct_data,
+            impl_data: self.impl_data,
+            inherent_impl_data: BTreeMap::new(),
+            associated_ty_values: self.associated_ty_values,
+            trait_data: self.trait_data,
+            associated_ty_data: self.associated_ty_data,
+            custom_clauses: self.custom_clauses,
+            lang_items: self.lang_items,
+            well_known_items: Arc::new(self.well_known_items),
+        })
+    }
+}
+
+/// The binders of a trait with the declared parameters `binders`:
+/// `Self`, followed by those.
+fn trait_binders(binders: &[ParameterKind<()>]) -> Vec<ParameterKind<()>> {
+    std::iter::once(ParameterKind::Ty(()))
+        .chain(binders.iter().cloned())
+        .collect()
+}
+
+/// An identifier for errors. Items built in code have no spans.
+fn ident(name: Identifier) -> ast::Identifier {
+    ast::Identifier {
+        str: lalrpop_intern::intern(name.as_str()),
+        span: Span::new(0, 0),
+    }
+}
+
+/// Checks the types in the items of a program as they are folded,
+/// recording the first error found.
+struct Checker<'p> {
+    type_kinds: &'p BTreeMap<TypeKindId, TypeKind>,
+    associated_ty_data: &'p BTreeMap<TypeId, Arc<AssociatedTyDatum>>,
+
+    /// The number of variables bound by the item being checked.
+    num_binders: usize,
+
+    error: Option<RustIrError>,
+}
+
+impl Checker<'_> {
+    /// Checks the types in `value`, which is under `num_binders`
+    /// binders.
+    fn check<T: Fold<ChalkIr>>(
+        &mut self,
+        value: &T,
+        num_binders: usize,
+    ) -> Result<(), RustIrError> {
+        self.num_binders = num_binders;
+        match value.fold_with(self, 0) {
+            Ok(_) => Ok(()),
+            Err(NoSolution) => Err(self.error.take().expect("error not recorded")),
+        }
+    }
+
+    fn check_where_clauses(
+        &mut self,
+        where_clauses: &[QuantifiedWhereClause<ChalkIr>],
+        num_binders: usize,
+    ) -> Result<(), RustIrError> {
+        for where_clause in where_clauses {
+            match &where_clause.value {
+                WhereClause::Implemented(trait_ref) => self.check_trait_ref(trait_ref)?,
+                WhereClause::ProjectionEq(projection_eq) => {
+                    let projection = &projection_eq.projection;
+                    self.check_associated_ty(
+                        projection.associated_ty_id,
+                        &projection.parameters,
+                        true,
+                    )?;
+                }
+            }
+        }
+        self.check(&where_clauses.to_vec(), num_binders)
+    }
+
+    /// Checks that `trait_ref` names a trait of the program, and gives `Self` and the
+    /// parameters of the trait.
+    fn check_trait_ref(&mut self, trait_ref: &TraitRef<ChalkIr>) -> Result<(), RustIrError> {
+        let type_kind = self.trait_kind(trait_ref.trait_id)?;
+        match trait_ref.parameters.first().map(|param| &param.0) {
+            Some(ParameterKind::Ty(_)) => {}
+            Some(ParameterKind::Lifetime(_)) => Err(RustIrError::IncorrectTraitParameterKind {
+                identifier: ident(type_kind.name),
+                expected: Kind::Ty,
+                actual: Kind::Lifetime,
+            })?,
+            None => Err(RustIrError::IncorrectNumberOfTypeParameters {
+                identifier: ident(type_kind.name),
+                expected: type_kind.binders.len() + 1,
+                actual: 0,
+            })?,
+        }
+        self.check_trait_parameters(trait_ref.trait_id, &trait_ref.parameters[1..])
+    }
+
+    /// Checks that `trait_id` names a trait whose parameters, apart
+    /// from `Self`, are `args_no_self`.
+    fn check_trait_parameters(
+        &mut self,
+        trait_id: TraitId,
+        args_no_self: &[Parameter<ChalkIr>],
+    ) -> Result<(), RustIrError> {
+        let type_kind = self.trait_kind(trait_id)?;
+        if args_no_self.len() != type_kind.binders.len() {
+            Err(RustIrError::IncorrectNumberOfTypeParameters {
+                identifier: ident(type_kind.name),
+                expected: type_kind.binders.len(),
+                actual: args_no_self.len(),
+            })?;
+        }
+        for (binder, param) in type_kind.binders.binders.iter().zip(args_no_self) {
+            if binder.kind() != param.kind() {
+                Err(RustIrError::IncorrectTraitParameterKind {
+                    identifier: ident(type_kind.name),
+                    expected: binder.kind(),
+                    actual: param.kind(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn trait_kind(&self, trait_id: TraitId) -> Result<&'_ TypeKind, RustIrError> {
+        self.type_kinds
+            .get(&trait_id.into())
+            .ok_or_else(|| RustIrError::InvalidItemId(trait_id.into()))
+    }
+
+    /// Checks that `id` is an associated type of the program, and (if
+    /// `check_parameters`) that `parameters` are its parameters.
+    fn check_associated_ty(
+        &self,
+        id: TypeId,
+        parameters: &[Parameter<ChalkIr>],
+        check_parameters: bool,
+    ) -> Result<(), RustIrError> {
+        let datum = match self.associated_ty_data.get(&id) {
+            Some(datum) => datum,
+            None => Err(RustIrError::InvalidItemId(id.into()))?,
+        };
+        if !check_parameters {
+            return Ok(());
+        }
+        if parameters.len() != datum.binders.len() {
+            Err(RustIrError::IncorrectNumberOfAssociatedTypeParameters {
+                identifier: ident(datum.name),
+                expected: datum.binders.len(),
+                actual: parameters.len(),
+            })?;
+        }
+        for (binder, param) in datum.binders.binders.iter().zip(parameters) {
+            if binder.kind() != param.kind() {
+                Err(RustIrError::IncorrectAssociatedTypeParameterKind {
+                    identifier: ident(datum.name),
+                    expected: binder.kind(),
+                    actual: param.kind(),
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_ty(&self, ty: &Ty<ChalkIr>) -> Result<(), RustIrError> {
+        match ty {
+            Ty::Apply(apply) => match apply.name {
+                TypeName::TypeKindId(id) => {
+                    let type_kind = match self.type_kinds.get(&id) {
+                        Some(type_kind) => type_kind,
+                        None => Err(RustIrError::InvalidItemId(id))?,
+                    };
+                    if apply.parameters.len() != type_kind.binders.len() {
+                        Err(RustIrError::IncorrectNumberOfTypeParameters {
+                            identifier: ident(type_kind.name),
+                            expected: type_kind.binders.len(),
+                            actual: apply.parameters.len(),
+                        })?;
+                    }
+                    for (binder, param) in type_kind.binders.binders.iter().zip(&apply.parameters) {
+                        if binder.kind() != param.kind() {
+                            Err(RustIrError::IncorrectParameterKind {
+                                identifier: ident(type_kind.name),
+                                expected: binder.kind(),
+                                actual: param.kind(),
+                            })?;
+                        }
+                    }
+                    Ok(())
+                }
+                TypeName::AssociatedType(id) => {
+                    self.check_associated_ty(id, &apply.parameters, true)
+                }
+                _ => Ok(()),
+            },
+            Ty::Projection(projection) => {
+                self.check_associated_ty(projection.associated_ty_id, &projection.parameters, true)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn fail<T>(&mut self, error: RustIrError) -> Fallible<T> {
+        self.error = Some(error);
+        Err(NoSolution)
+    }
+}
+
+impl TypeFolder<ChalkIr> for Checker<'_> {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        match self.check_ty(ty) {
+            Ok(()) => super_fold_ty(self, ty, binders),
+            Err(error) => self.fail(error),
+        }
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl FreeVarFolder<ChalkIr> for Checker<'_> {
+    fn fold_free_var_ty(&mut self, depth: usize, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        if depth >= self.num_binders {
+            return self.fail(RustIrError::UnboundVariable(depth));
+        }
+        Ok(Ty::BoundVar(depth + binders))
+    }
+
+    fn fold_free_var_lifetime(
+        &mut self,
+        depth: usize,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        if depth >= self.num_binders {
+            return self.fail(RustIrError::UnboundVariable(depth));
+        }
+        Ok(Lifetime::BoundVar(depth + binders))
+    }
+}
+
+impl DefaultInferenceFolder for Checker<'_> {}
+
+impl DefaultPlaceholderFolder for Checker<'_> {}
@@ -21,13 +21,21 @@ use chalk_rust_ir::AssociatedTyDatum;
 use chalk_rust_ir::AssociatedTyValue;
 use chalk_rust_ir::AssociatedTyValueId;
 use chalk_rust_ir::ImplDatum;
+use chalk_rust_ir::InherentImplDatum;
+use chalk_rust_ir::LangItem;
 use chalk_rust_ir::StructDatum;
 use chalk_rust_ir::TraitDatum;
+use chalk_rust_ir::WellKnownItems;
+use chalk_solve::coherence::SpecializationPriority;
+use chalk_solve::MemoryReport;
+use chalk_solve::MissingData;
+use chalk_solve::ProgramReport;
 use chalk_solve::RustIrDatabase;
 use chalk_solve::Solution;
+use chalk_solve::Solver;
 use chalk_solve::SolverChoice;
 use salsa::Database;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 #[salsa::database(Lowering)]
 #[derive(Debug, Default)]
@@ -43,7 +51,14 @@ impl Database for ChalkDatabase {
 
 impl ChalkDatabase {
     pub fn with(program_text: &str, solver_choice: SolverChoice) -> Self {
+        ChalkDatabase::with_prelude("", program_text, solver_choice)
+    }
+
+    /// A database for the program given by `program_text`, preceded by
+    /// the items of `prelude` (see `LoweringDatabase::program_prelude`).
+    pub fn with_prelude(prelude: &str, program_text: &str, solver_choice: SolverChoice) -> Self {
         let mut db = ChalkDatabase::default();
+        db.set_program_prelude(Arc::new(prelude.to_string()));
         db.set_program_text(Arc::new(program_text.to_string()));
         db.set_solver_choice(solver_choice);
         db
@@ -56,12 +71,12 @@ impl ChalkDatabase {
 
     pub fn parse_and_lower_goal(&self, text: &str) -> Result<Box<Goal<ChalkIr>>, ChalkError> {
         let program = self.checked_program()?;
-        Ok(chalk_parse::parse_goal(text)?.lower(&*program)?)
+        Ok(Box::new(chalk_parse::parse_goal(text)?.lower(&*program)?))
     }
 
     pub fn solve(&self, goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> Option<Solution> {
         let solver = self.solver();
-        let solution = solver.lock().unwrap().solve(self, goal);
+        let solution = lock_solver(&solver, self.solver_choice()).solve(self, goal);
         solution
     }
 
@@ -71,9 +86,37 @@ impl ChalkDatabase {
         f: impl FnMut(Canonical<ConstrainedSubst<ChalkIr>>, bool) -> bool,
     ) -> bool {
         let solver = self.solver();
-        let solution = solver.lock().unwrap().solve_multiple(self, goal, f);
+        let solution = lock_solver(&solver, self.solver_choice()).solve_multiple(self, goal, f);
         solution
     }
+
+    /// Checks the coherence and well-formedness of the whole program,
+    /// collecting every error rather than stopping at the first, as
+    /// `checked_program` does (see `chalk_solve::check_program`).
+    pub fn check_program(&self) -> Result<ProgramReport, ChalkError> {
+        let program = self.program_ir()?;
+        Ok(tls::set_current_program(&program, || {
+            chalk_solve::check_program(self, self.solver_choice(), &program.program_items())
+        }))
+    }
+
+    /// Returns the approximate memory retained by the tables of the
+    /// solver (see `Solver::memory_report`).
+    pub fn memory_report(&self) -> MemoryReport {
+        lock_solver(&self.solver(), self.solver_choice()).memory_report()
+    }
+}
+
+/// Locks `solver`. If a panic while solving poisoned the lock, the
+/// tables of the solver may be half updated, so it is replaced with a
+/// fresh one made from `solver_choice` before the lock is recovered.
+fn lock_solver(solver: &Mutex<Solver>, solver_choice: SolverChoice) -> MutexGuard<'_, Solver> {
+    solver.lock().unwrap_or_else(|poisoned| {
+        let mut guard = poisoned.into_inner();
+        *guard = solver_choice.into_solver();
+        solver.clear_poison();
+        guard
+    })
 }
 
 impl RustIrDatabase for ChalkDatabase {
@@ -101,6 +144,29 @@ impl RustIrDatabase for ChalkDatabase {
         self.program_ir().unwrap().struct_datum(id)
     }
 
+    fn try_associated_ty_data(&self, ty: TypeId) -> Result<Arc<AssociatedTyDatum>, MissingData> {
+        self.program_ir().unwrap().try_associated_ty_data(ty)
+    }
+
+    fn try_trait_datum(&self, id: TraitId) -> Result<Arc<TraitDatum>, MissingData> {
+        self.program_ir().unwrap().try_trait_datum(id)
+    }
+
+    fn try_struct_datum(&self, id: StructId) -> Result<Arc<StructDatum>, MissingData> {
+        self.program_ir().unwrap().try_struct_datum(id)
+    }
+
+    fn try_impl_datum(&self, id: ImplId) -> Result<Arc<ImplDatum>, MissingData> {
+        self.program_ir().unwrap().try_impl_datum(id)
+    }
+
+    fn try_associated_ty_value(
+        &self,
+        id: AssociatedTyValueId,
+    ) -> Result<Arc<AssociatedTyValue>, MissingData> {
+        self.program_ir().unwrap().try_associated_ty_value(id)
+    }
+
     fn impls_for_trait(&self, trait_id: TraitId, parameters: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
         self.program_ir()
             .unwrap()
@@ -113,12 +179,29 @@ impl RustIrDatabase for ChalkDatabase {
             .local_impls_to_coherence_check(trait_id)
     }
 
+    fn local_inherent_impls(&self) -> Vec<Arc<InherentImplDatum>> {
+        self.program_ir().unwrap().local_inherent_impls()
+    }
+
+    fn impl_specialization_priority(&self, impl_id: ImplId) -> Option<SpecializationPriority> {
+        let trait_id = self.impl_datum(impl_id).trait_id();
+        self.coherence().ok()?.get(&trait_id)?.get(impl_id)
+    }
+
     fn impl_provided_for(&self, auto_trait_id: TraitId, struct_id: StructId) -> bool {
         self.program_ir()
             .unwrap()
             .impl_provided_for(auto_trait_id, struct_id)
     }
 
+    fn lang_item(&self, item: LangItem) -> Option<TypeKindId> {
+        self.program_ir().unwrap().lang_item(item)
+    }
+
+    fn well_known_items(&self) -> Arc<WellKnownItems> {
+        self.program_ir().unwrap().well_known_items()
+    }
+
     fn type_name(&self, id: TypeKindId) -> Identifier {
         self.program_ir().unwrap().type_name(id)
     }
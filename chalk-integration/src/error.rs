@@ -1,7 +1,10 @@
+use chalk_ir::TypeKindId;
 use chalk_parse::ast::{Identifier, Kind};
-use chalk_rust_ir::LangItem;
+use chalk_rust_ir::{LangItem, WellKnownTrait};
 use chalk_solve::coherence::CoherenceError;
+use chalk_solve::conformance::ConformanceError;
 use chalk_solve::wf::WfError;
+use chalk_solve::MissingData;
 
 /// Wrapper type for the various errors that can occur during chalk
 /// processing.
@@ -36,6 +39,22 @@ impl From<CoherenceError> for ChalkError {
     }
 }
 
+impl From<ConformanceError> for ChalkError {
+    fn from(value: ConformanceError) -> Self {
+        ChalkError {
+            error_text: value.to_string(),
+        }
+    }
+}
+
+impl From<MissingData> for ChalkError {
+    fn from(value: MissingData) -> Self {
+        ChalkError {
+            error_text: value.to_string(),
+        }
+    }
+}
+
 impl From<RustIrError> for ChalkError {
     fn from(value: RustIrError) -> Self {
         ChalkError {
@@ -57,12 +76,16 @@ pub enum RustIrError {
     InvalidTypeName(Identifier),
     InvalidLifetimeName(Identifier),
     DuplicateLangItem(LangItem),
+    DuplicateWellKnownTrait(WellKnownTrait),
     NotTrait(Identifier),
+    NotFnTrait(Identifier),
     DuplicateOrShadowedParameters,
     AutoTraitAssociatedTypes(Identifier),
     AutoTraitParameters(Identifier),
     AutoTraitWhereClauses(Identifier),
+    DuplicateDefaultImpl(Identifier),
     InvalidFundamentalTypesParameters(Identifier),
+    ScalarParameters(Identifier),
     NegativeImplAssociatedValues(Identifier),
     MissingAssociatedType(Identifier),
     IncorrectNumberOfTypeParameters {
@@ -91,6 +114,10 @@ pub enum RustIrError {
         actual: Kind,
     },
     CannotApplyTypeParameter(Identifier),
+    DuplicateTypeName(Identifier),
+    UndefinedItem(Identifier),
+    InvalidItemId(TypeKindId),
+    UnboundVariable(usize),
 }
 
 impl std::fmt::Display for RustIrError {
@@ -99,11 +126,19 @@ impl std::fmt::Display for RustIrError {
             RustIrError::InvalidTypeName(name) => write!(f, "invalid type name `{}`", name),
             RustIrError::InvalidLifetimeName(name) => write!(f, "invalid lifetime name `{}`", name),
             RustIrError::DuplicateLangItem(item) => write!(f, "duplicate lang item `{:?}`", item),
+            RustIrError::DuplicateWellKnownTrait(item) => {
+                write!(f, "duplicate well-known trait `{:?}`", item)
+            }
             RustIrError::NotTrait(name) => write!(
                 f,
                 "expected a trait, found `{}`, which is not a trait",
                 name
             ),
+            RustIrError::NotFnTrait(name) => write!(
+                f,
+                "parenthesized parameters may only be used with a fn-family trait, not `{}`",
+                name
+            ),
             RustIrError::DuplicateOrShadowedParameters => {
                 write!(f, "duplicate or shadowed parameters")
             }
@@ -116,11 +151,17 @@ impl std::fmt::Display for RustIrError {
             RustIrError::AutoTraitWhereClauses(name) => {
                 write!(f, "auto trait `{}` cannot have where clauses", name)
             }
+            RustIrError::DuplicateDefaultImpl(name) => {
+                write!(f, "duplicate default impl for trait `{}`", name)
+            }
             RustIrError::InvalidFundamentalTypesParameters(name) => write!(
                 f,
                 "only a single parameter supported for fundamental type `{}`",
                 name
             ),
+            RustIrError::ScalarParameters(name) => {
+                write!(f, "scalar type `{}` cannot have parameters", name)
+            }
             RustIrError::NegativeImplAssociatedValues(name) => write!(
                 f,
                 "negative impl for trait `{}` cannot define associated values",
@@ -177,6 +218,14 @@ impl std::fmt::Display for RustIrError {
             RustIrError::CannotApplyTypeParameter(name) => {
                 write!(f, "cannot apply type parameter `{}`", name)
             }
+            RustIrError::DuplicateTypeName(name) => write!(f, "duplicate type name `{}`", name),
+            RustIrError::UndefinedItem(name) => {
+                write!(f, "`{}` is declared but never defined", name)
+            }
+            RustIrError::InvalidItemId(id) => write!(f, "no item with id `{:?}`", id),
+            RustIrError::UnboundVariable(depth) => {
+                write!(f, "bound variable `^{}` is not bound by any binder", depth)
+            }
         }
     }
 }
@@ -0,0 +1,202 @@
+//! A C ABI for posing goals from tools not written in Rust (Python or
+//! JS analyzers, teaching UIs, ...), through e.g. `ctypes` or a WASM
+//! host. It is enabled by the `ffi` feature, and is deliberately
+//! small: a program is loaded from text into a `ChalkProgram` handle,
+//! goals are posed to it as text, and each solution comes back as a
+//! JSON string. To get a shared library, build a `cdylib` crate that
+//! depends on this one with the feature enabled; the functions below
+//! are exported from it.
+//!
+//! All strings are NUL-terminated UTF-8. Handles and strings returned
+//! by these functions are owned by the caller, who must release them
+//! with `chalk_program_free` and `chalk_string_free` respectively (and
+//! not with the C `free`). A handle must not be used from two threads
+//! at once. Panics never cross the ABI: a panic while
+//! solving is reported as an error, and the next goal is posed to a
+//! fresh solver, as the tables of the old one may be half updated.
+//!
+//! The solution of a goal is a JSON object with a `status` field,
+//! which is one of:
+//!
+//! - `"unique"` or `"ambiguous"`: the goal has a solution, which the
+//!   `solution` field gives as text (as the REPL prints it, e.g.
+//!   `"Unique; substitution [?0 := Foo], lifetime constraints []"`);
+//! - `"no-solution"`: the goal does not hold;
+//! - `"error"`: the program or the goal is invalid (it does not parse
+//!   or lower, or the program fails its coherence or WF checks), or
+//!   solving panicked; the `error` field gives the message.
+
+use crate::db::ChalkDatabase;
+use crate::error::ChalkError;
+use crate::query::LoweringDatabase;
+use chalk_ir::tls;
+use chalk_solve::ext::GoalExt;
+use chalk_solve::{Solution, SolverChoice};
+use std::ffi::{CStr, CString};
+use std::fmt::Write;
+use std::os::raw::{c_char, c_uint};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+/// The version of this ABI, as returned by `chalk_ffi_version`. It is
+/// bumped whenever a function or the JSON format changes incompatibly.
+pub const FFI_VERSION: c_uint = 1;
+
+/// A loaded program, to which goals may be posed. This is opaque to C
+/// callers, who only ever hold a pointer to it.
+pub struct ChalkProgram {
+    db: ChalkDatabase,
+}
+
+/// Returns `FFI_VERSION`, so that callers can check that they were
+/// written against the ABI of the library they loaded.
+#[no_mangle]
+pub extern "C" fn chalk_ffi_version() -> c_uint {
+    FFI_VERSION
+}
+
+/// Loads the program given by the text `program`, to be solved with
+/// the default `SolverChoice`. The program is checked lazily: if it is
+/// invalid, posing a goal reports the error. Returns null if `program`
+/// is null or not UTF-8.
+///
+/// # Safety
+///
+/// `program` must be null or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chalk_program_load(program: *const c_char) -> *mut ChalkProgram {
+    match str_arg(program) {
+        Some(text) => Box::into_raw(Box::new(ChalkProgram {
+            db: ChalkDatabase::with(text, SolverChoice::default()),
+        })),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Releases a program returned by `chalk_program_load`. Does nothing
+/// if `program` is null.
+///
+/// # Safety
+///
+/// `program` must be null or a handle returned by `chalk_program_load`
+/// that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn chalk_program_free(program: *mut ChalkProgram) {
+    if !program.is_null() {
+        drop(Box::from_raw(program));
+    }
+}
+
+/// Solves the goal given by the text `goal` in `program`, and returns
+/// the solution as JSON (see the module docs). The solver's tables are
+/// kept with the program, so later goals reuse the work of earlier
+/// ones. Returns null only if `program` is null.
+///
+/// # Safety
+///
+/// `program` must be null or a live handle returned by
+/// `chalk_program_load`, and `goal` must be null or a valid
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn chalk_program_solve(
+    program: *const ChalkProgram,
+    goal: *const c_char,
+) -> *mut c_char {
+    let program = match program.as_ref() {
+        Some(program) => program,
+        None => return ptr::null_mut(),
+    };
+    let json = match str_arg(goal) {
+        Some(goal) => solve_to_json(&program.db, goal),
+        None => error_json("the goal is null or not UTF-8"),
+    };
+    // JSON strings escape NUL, so there is none in `json`.
+    CString::new(json).unwrap().into_raw()
+}
+
+/// Releases a string returned by `chalk_program_solve`. Does nothing
+/// if `string` is null.
+///
+/// # Safety
+///
+/// `string` must be null or a string returned by `chalk_program_solve`
+/// that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn chalk_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// The string that `arg` points to, or `None` if it is null or not
+/// UTF-8.
+unsafe fn str_arg<'a>(arg: *const c_char) -> Option<&'a str> {
+    if arg.is_null() {
+        None
+    } else {
+        CStr::from_ptr(arg).to_str().ok()
+    }
+}
+
+/// Solves `goal_text` in the program of `db`, and returns the solution
+/// as the JSON object that `chalk_program_solve` returns.
+pub fn solve_to_json(db: &ChalkDatabase, goal_text: &str) -> String {
+    let solve = || -> Result<String, ChalkError> {
+        let program = db.checked_program()?;
+        tls::set_current_program(&program, || {
+            let goal = db.parse_and_lower_goal(goal_text)?.into_peeled_goal();
+            Ok(match db.solve(&goal) {
+                Some(solution) => {
+                    let status = match solution {
+                        Solution::Unique(_) => "unique",
+                        Solution::Ambig(_) => "ambiguous",
+                    };
+                    format!(
+                        "{{\"status\":\"{}\",\"solution\":{}}}",
+                        status,
+                        json_string(&solution.to_string())
+                    )
+                }
+                None => "{\"status\":\"no-solution\"}".to_string(),
+            })
+        })
+    };
+    match panic::catch_unwind(AssertUnwindSafe(solve)) {
+        Ok(Ok(json)) => json,
+        Ok(Err(error)) => error_json(&error.to_string()),
+        Err(payload) => error_json(&format!(
+            "the solver panicked: {}",
+            payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default()
+        )),
+    }
+}
+
+fn error_json(message: &str) -> String {
+    format!(
+        "{{\"status\":\"error\",\"error\":{}}}",
+        json_string(message)
+    )
+}
+
+/// `text` as a JSON string literal, quotes included.
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
@@ -4,9 +4,13 @@
 #[macro_use]
 extern crate chalk_macros;
 
+pub mod builder;
 pub mod db;
 pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod lowering;
+pub mod minimize;
 pub mod program;
 pub mod program_environment;
 pub mod query;
@@ -6,7 +6,7 @@ use chalk_rust_ir as rust_ir;
 use chalk_rust_ir::{Anonymize, AssociatedTyValueId, IntoWhereClauses, ToParameter};
 use itertools::Itertools;
 use lalrpop_intern::intern;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
 
 use crate::error::RustIrError;
@@ -17,6 +17,7 @@ type TypeKinds = BTreeMap<chalk_ir::TypeKindId, rust_ir::TypeKind>;
 type AssociatedTyLookups = BTreeMap<(chalk_ir::TraitId, chalk_ir::Identifier), AssociatedTyLookup>;
 type AssociatedTyValueIds = BTreeMap<(chalk_ir::ImplId, chalk_ir::Identifier), AssociatedTyValueId>;
 type ParameterMap = BTreeMap<chalk_ir::ParameterKind<chalk_ir::Identifier>, usize>;
+type LangItems = BTreeMap<rust_ir::LangItem, chalk_ir::TypeKindId>;
 
 pub type LowerResult<T> = Result<T, RustIrError>;
 
@@ -25,6 +26,7 @@ struct Env<'k> {
     type_ids: &'k TypeIds,
     type_kinds: &'k TypeKinds,
     associated_ty_lookups: &'k AssociatedTyLookups,
+    lang_items: &'k LangItems,
     /// Parameter identifiers are used as keys, therefore
     /// all identifiers in an environment must be unique (no shadowing).
     parameter_map: ParameterMap,
@@ -46,12 +48,13 @@ struct Env<'k> {
 #[derive(Debug, PartialEq, Eq)]
 struct AssociatedTyLookup {
     id: chalk_ir::TypeId,
-    addl_parameter_kinds: Vec<chalk_ir::ParameterKind<()>>,
+    addl_parameter_kinds: chalk_ir::ParameterKinds,
 }
 
 enum NameLookup {
     Type(chalk_ir::TypeKindId),
     Parameter(usize),
+    Builtin(chalk_ir::BuiltinType),
 }
 
 enum LifetimeLookup {
@@ -65,22 +68,28 @@ impl<'k> Env<'k> {
     fn lookup(&self, name: Identifier) -> LowerResult<NameLookup> {
         if let Some(k) = self
             .parameter_map
-            .get(&chalk_ir::ParameterKind::Ty(name.str))
+            .get(&chalk_ir::ParameterKind::Ty(name.lower()))
         {
             return Ok(NameLookup::Parameter(*k));
         }
 
-        if let Some(id) = self.type_ids.get(&name.str) {
+        if let Some(id) = self.type_ids.get(&name.lower()) {
             return Ok(NameLookup::Type(*id));
         }
 
+        // Like a primitive type in Rust, `str` can be shadowed by a
+        // type of the program.
+        if name.lower().as_str() == "str" {
+            return Ok(NameLookup::Builtin(chalk_ir::BuiltinType::Str));
+        }
+
         Err(RustIrError::InvalidTypeName(name))?
     }
 
     fn lookup_lifetime(&self, name: Identifier) -> LowerResult<LifetimeLookup> {
         if let Some(k) = self
             .parameter_map
-            .get(&chalk_ir::ParameterKind::Lifetime(name.str))
+            .get(&chalk_ir::ParameterKind::Lifetime(name.lower()))
         {
             return Ok(LifetimeLookup::Parameter(*k));
         }
@@ -139,6 +148,20 @@ pub(crate) trait LowerProgram {
 
 impl LowerProgram for Program {
     fn lower(&self) -> LowerResult<LoweredProgram> {
+        std::slice::from_ref(self).lower()
+    }
+}
+
+/// Lowers several program fragments (e.g., a prelude shared by many
+/// programs, followed by the items of one of them) into a single
+/// program. Each fragment may refer to the items of the fragments
+/// before it. Ids are assigned to the items of all fragments in order,
+/// so the items of a fragment get different ids than they would if
+/// it were lowered on its own.
+impl LowerProgram for [Program] {
+    fn lower(&self) -> LowerResult<LoweredProgram> {
+        let items: Vec<&Item> = self.iter().flat_map(|program| &program.items).collect();
+
         let mut index = 0;
         let mut next_item_id = || -> chalk_ir::RawId {
             let i = index;
@@ -148,31 +171,28 @@ impl LowerProgram for Program {
 
         // Make a vector mapping each thing in `items` to an id,
         // based just on its position:
-        let raw_ids: Vec<_> = self.items.iter().map(|_| next_item_id()).collect();
+        let raw_ids: Vec<_> = items.iter().map(|_| next_item_id()).collect();
 
         // Create ids for associated type declarations and values
         let mut associated_ty_lookups = BTreeMap::new();
         let mut associated_ty_value_ids = BTreeMap::new();
-        for (item, &raw_id) in self.items.iter().zip(&raw_ids) {
+        for (item, &raw_id) in items.iter().zip(&raw_ids) {
             match item {
                 Item::TraitDefn(d) => {
-                    if d.flags.auto && !d.assoc_ty_defns.is_empty() {
-                        Err(RustIrError::AutoTraitAssociatedTypes(d.name))?;
-                    }
                     for defn in &d.assoc_ty_defns {
                         let addl_parameter_kinds = defn.all_parameters();
                         let lookup = AssociatedTyLookup {
                             id: TypeId(next_item_id()),
                             addl_parameter_kinds: addl_parameter_kinds.anonymize(),
                         };
-                        associated_ty_lookups.insert((TraitId(raw_id), defn.name.str), lookup);
+                        associated_ty_lookups.insert((TraitId(raw_id), defn.name.lower()), lookup);
                     }
                 }
 
                 Item::Impl(d) => {
                     for atv in &d.assoc_ty_values {
                         let atv_id = AssociatedTyValueId(next_item_id());
-                        associated_ty_value_ids.insert((ImplId(raw_id), atv.name.str), atv_id);
+                        associated_ty_value_ids.insert((ImplId(raw_id), atv.name.lower()), atv_id);
                     }
                 }
 
@@ -182,28 +202,74 @@ impl LowerProgram for Program {
 
         let mut type_ids = BTreeMap::new();
         let mut type_kinds = BTreeMap::new();
-        for (item, &raw_id) in self.items.iter().zip(&raw_ids) {
-            let (k, id) = match *item {
-                Item::StructDefn(ref d) => (d.lower_type_kind()?, StructId(raw_id).into()),
-                Item::TraitDefn(ref d) => (d.lower_type_kind()?, TraitId(raw_id).into()),
-                Item::Impl(_) => continue,
+        let mut lang_items = BTreeMap::new();
+        let mut well_known_items = rust_ir::WellKnownItems::new();
+        for (item, &raw_id) in items.iter().zip(&raw_ids) {
+            let (k, id, lang_item) = match *item {
+                Item::StructDefn(ref d) => {
+                    if let Some(scalar) = d.flags.scalar {
+                        well_known_items.insert_scalar(StructId(raw_id), scalar.lower());
+                    }
+                    (d.lower_type_kind()?, StructId(raw_id).into(), d.lang_item)
+                }
+                Item::TraitDefn(ref d) => {
+                    (d.lower_type_kind()?, TraitId(raw_id).into(), d.lang_item)
+                }
+                Item::Impl(_) | Item::InherentImpl(_) | Item::DefaultImpl(_) => continue,
                 Item::Clause(_) => continue,
             };
+            match lang_item.map(|lang_item| lang_item.lower()) {
+                Some(LoweredLangItem::LangItem(lang_item)) => {
+                    let previous = lang_items.insert(lang_item, id);
+                    if previous.is_some() {
+                        Err(RustIrError::DuplicateLangItem(lang_item))?;
+                    }
+                }
+                Some(LoweredLangItem::WellKnownTrait(item)) => {
+                    // The parser only accepts these names on traits.
+                    let trait_id = match id {
+                        TypeKindId::TraitId(trait_id) => trait_id,
+                        _ => panic!("well-known trait `{:?}` is not a trait", item),
+                    };
+                    if well_known_items.insert(item, trait_id).is_some() {
+                        Err(RustIrError::DuplicateWellKnownTrait(item))?;
+                    }
+                }
+                None => {}
+            }
             type_ids.insert(k.name, id);
             type_kinds.insert(id, k);
         }
 
+        // A trait with a default impl, `impl Send for .. { }`, is an
+        // auto trait, just as if it were declared `#[auto]`.
+        let mut default_impls = BTreeSet::new();
+        for item in &items {
+            if let Item::DefaultImpl(d) = item {
+                let trait_id = match type_ids.get(&d.trait_name.lower()) {
+                    Some(&TypeKindId::TraitId(trait_id)) => trait_id,
+                    Some(_) => Err(RustIrError::NotTrait(d.trait_name))?,
+                    None => Err(RustIrError::InvalidTypeName(d.trait_name))?,
+                };
+                if !default_impls.insert(trait_id) {
+                    Err(RustIrError::DuplicateDefaultImpl(d.trait_name))?;
+                }
+            }
+        }
+
         let mut struct_data = BTreeMap::new();
         let mut trait_data = BTreeMap::new();
         let mut impl_data = BTreeMap::new();
+        let mut inherent_impl_data = BTreeMap::new();
         let mut associated_ty_data = BTreeMap::new();
         let mut associated_ty_values = BTreeMap::new();
         let mut custom_clauses = Vec::new();
-        for (item, &raw_id) in self.items.iter().zip(&raw_ids) {
+        for (item, &raw_id) in items.iter().zip(&raw_ids) {
             let empty_env = Env {
                 type_ids: &type_ids,
                 type_kinds: &type_kinds,
                 associated_ty_lookups: &associated_ty_lookups,
+                lang_items: &lang_items,
                 parameter_map: BTreeMap::new(),
             };
 
@@ -216,11 +282,16 @@ impl LowerProgram for Program {
                     let trait_id = TraitId(raw_id);
                     trait_data.insert(
                         trait_id,
-                        Arc::new(trait_defn.lower_trait(trait_id, &empty_env)?),
+                        Arc::new(trait_defn.lower_trait(
+                            trait_id,
+                            default_impls.contains(&trait_id),
+                            &empty_env,
+                        )?),
                     );
 
                     for assoc_ty_defn in &trait_defn.assoc_ty_defns {
-                        let lookup = &associated_ty_lookups[&(trait_id, assoc_ty_defn.name.str)];
+                        let lookup =
+                            &associated_ty_lookups[&(trait_id, assoc_ty_defn.name.lower())];
 
                         // The parameters in scope for the associated
                         // type definitions are *both* those from the
@@ -249,6 +320,10 @@ impl LowerProgram for Program {
                             Ok(rust_ir::AssociatedTyDatumBound {
                                 bounds: assoc_ty_defn.bounds.lower(&env)?,
                                 where_clauses: assoc_ty_defn.where_clauses.lower(&env)?,
+                                default: match &assoc_ty_defn.default {
+                                    Some(default) => Some(default.lower(&env)?),
+                                    None => None,
+                                },
                             })
                         })?;
 
@@ -257,7 +332,7 @@ impl LowerProgram for Program {
                             Arc::new(rust_ir::AssociatedTyDatum {
                                 trait_id: TraitId(raw_id),
                                 id: lookup.id,
-                                name: assoc_ty_defn.name.str,
+                                name: assoc_ty_defn.name.lower(),
                                 binders: binders,
                             }),
                         );
@@ -274,8 +349,12 @@ impl LowerProgram for Program {
                     let trait_id = impl_datum.trait_id();
 
                     for atv in &impl_defn.assoc_ty_values {
-                        let atv_id = associated_ty_value_ids[&(impl_id, atv.name.str)];
-                        let lookup = &associated_ty_lookups[&(trait_id, atv.name.str)];
+                        let atv_id = associated_ty_value_ids[&(impl_id, atv.name.lower())];
+                        let lookup = match associated_ty_lookups.get(&(trait_id, atv.name.lower()))
+                        {
+                            Some(lookup) => lookup,
+                            None => Err(RustIrError::MissingAssociatedType(atv.name))?,
+                        };
 
                         // The parameters in scope for the associated
                         // type definitions are *both* those from the
@@ -301,6 +380,16 @@ impl LowerProgram for Program {
                         );
                     }
                 }
+                Item::InherentImpl(ref impl_defn) => {
+                    let impl_id = rust_ir::InherentImplId(raw_id);
+                    inherent_impl_data.insert(
+                        impl_id,
+                        Arc::new(impl_defn.lower_inherent_impl(&empty_env, impl_id)?),
+                    );
+                }
+                Item::DefaultImpl(_) => {
+                    // Handled above, by making the trait an auto trait.
+                }
                 Item::Clause(ref clause) => {
                     custom_clauses.extend(clause.lower_clause(&empty_env)?);
                 }
@@ -313,9 +402,12 @@ impl LowerProgram for Program {
             struct_data,
             trait_data,
             impl_data,
+            inherent_impl_data,
             associated_ty_values,
             associated_ty_data,
             custom_clauses,
+            lang_items,
+            well_known_items: Arc::new(well_known_items),
         };
 
         Ok(program)
@@ -388,6 +480,16 @@ impl LowerParameterMap for Impl {
     }
 }
 
+impl LowerParameterMap for InherentImpl {
+    fn synthetic_parameters(&self) -> Option<chalk_ir::ParameterKind<chalk_ir::Identifier>> {
+        None
+    }
+
+    fn declared_parameters(&self) -> &[ParameterKind] {
+        &self.parameter_kinds
+    }
+}
+
 impl LowerParameterMap for AssocTyDefn {
     fn synthetic_parameters(&self) -> Option<chalk_ir::ParameterKind<chalk_ir::Identifier>> {
         None
@@ -408,9 +510,19 @@ impl LowerParameterMap for AssocTyValue {
     }
 }
 
+impl LowerParameterMap for MethodDefn {
+    fn synthetic_parameters(&self) -> Option<chalk_ir::ParameterKind<chalk_ir::Identifier>> {
+        None
+    }
+
+    fn declared_parameters(&self) -> &[ParameterKind] {
+        &self.parameter_kinds
+    }
+}
+
 impl LowerParameterMap for TraitDefn {
     fn synthetic_parameters(&self) -> Option<chalk_ir::ParameterKind<chalk_ir::Identifier>> {
-        Some(chalk_ir::ParameterKind::Ty(intern(SELF)))
+        Some(chalk_ir::ParameterKind::Ty(chalk_ir::intern(SELF)))
     }
 
     fn declared_parameters(&self) -> &[ParameterKind] {
@@ -428,6 +540,16 @@ impl LowerParameterMap for Clause {
     }
 }
 
+trait LowerIdentifier {
+    fn lower(&self) -> chalk_ir::Identifier;
+}
+
+impl LowerIdentifier for Identifier {
+    fn lower(&self) -> chalk_ir::Identifier {
+        lalrpop_intern::read(|interner| chalk_ir::intern(interner.data(self.str)))
+    }
+}
+
 trait LowerParameterKind {
     fn lower(&self) -> chalk_ir::ParameterKind<chalk_ir::Identifier>;
 }
@@ -435,8 +557,8 @@ trait LowerParameterKind {
 impl LowerParameterKind for ParameterKind {
     fn lower(&self) -> chalk_ir::ParameterKind<chalk_ir::Identifier> {
         match *self {
-            ParameterKind::Ty(ref n) => chalk_ir::ParameterKind::Ty(n.str),
-            ParameterKind::Lifetime(ref n) => chalk_ir::ParameterKind::Lifetime(n.str),
+            ParameterKind::Ty(ref n) => chalk_ir::ParameterKind::Ty(n.lower()),
+            ParameterKind::Lifetime(ref n) => chalk_ir::ParameterKind::Lifetime(n.lower()),
         }
     }
 }
@@ -456,7 +578,7 @@ impl LowerTypeKind for StructDefn {
     fn lower_type_kind(&self) -> LowerResult<rust_ir::TypeKind> {
         Ok(rust_ir::TypeKind {
             sort: rust_ir::TypeSort::Struct,
-            name: self.name.str,
+            name: self.name.lower(),
             binders: chalk_ir::Binders {
                 binders: self.all_parameters().anonymize(),
                 value: (),
@@ -476,7 +598,7 @@ impl LowerTypeKind for TraitDefn {
         let binders: Vec<_> = self.parameter_kinds.iter().map(|p| p.lower()).collect();
         Ok(rust_ir::TypeKind {
             sort: rust_ir::TypeSort::Trait,
-            name: self.name.str,
+            name: self.name.lower(),
             binders: chalk_ir::Binders {
                 // for the purposes of the *type*, ignore `Self`:
                 binders: binders.anonymize(),
@@ -498,6 +620,12 @@ impl LowerWhereClauses for Impl {
     }
 }
 
+impl LowerWhereClauses for MethodDefn {
+    fn where_clauses(&self) -> &[QuantifiedWhereClause] {
+        &self.where_clauses
+    }
+}
+
 trait LowerWhereClauseVec {
     fn lower(&self, env: &Env) -> LowerResult<Vec<chalk_ir::QuantifiedWhereClause<ChalkIr>>>;
 }
@@ -517,7 +645,8 @@ trait LowerWhereClause<T> {
     /// Lower from an AST `where` clause to an internal IR.
     /// Some AST `where` clauses can lower to multiple ones, this is why we return a `Vec`.
     /// As for now, this is the only the case for `where T: Foo<Item = U>` which lowers to
-    /// `Implemented(T: Foo)` and `ProjectionEq(<T as Foo>::Item = U)`.
+    /// `Implemented(T: Foo)` and `ProjectionEq(<T as Foo>::Item = U)` (with one
+    /// `ProjectionEq` per binding, for `where T: Foo<A = X, B = Y>`).
     fn lower(&self, env: &Env) -> LowerResult<Vec<T>>;
 }
 
@@ -527,13 +656,24 @@ impl LowerWhereClause<chalk_ir::WhereClause<ChalkIr>> for WhereClause {
             WhereClause::Implemented { trait_ref } => {
                 vec![chalk_ir::WhereClause::Implemented(trait_ref.lower(env)?)]
             }
-            WhereClause::ProjectionEq { projection, ty } => vec![
-                chalk_ir::WhereClause::ProjectionEq(chalk_ir::ProjectionEq {
-                    projection: projection.lower(env)?,
-                    ty: ty.lower(env)?,
-                }),
-                chalk_ir::WhereClause::Implemented(projection.trait_ref.lower(env)?),
-            ],
+            WhereClause::ProjectionEq { ty, bound } => {
+                let self_ty = ty.lower(env)?;
+                let bounds = bound.lower(env)?;
+                let mut where_clauses: Vec<_> = bounds
+                    .iter()
+                    .map(|b| {
+                        chalk_ir::WhereClause::ProjectionEq(b.as_projection_eq(self_ty.clone()))
+                    })
+                    .collect();
+                where_clauses.push(chalk_ir::WhereClause::Implemented(
+                    bounds[0].trait_bound.as_trait_ref(self_ty),
+                ));
+                where_clauses
+            }
+            WhereClause::FnBound { ty, bound } => {
+                rust_ir::InlineBound::ProjectionEqBound(bound.lower(env)?)
+                    .into_where_clauses(ty.lower(env)?)
+            }
         };
         Ok(where_clauses)
     }
@@ -588,6 +728,20 @@ impl LowerDomainGoal for DomainGoal {
             DomainGoal::DownstreamType { ty } => {
                 vec![chalk_ir::DomainGoal::DownstreamType(ty.lower(env)?)]
             }
+            DomainGoal::LayoutCompatible { a, b } => {
+                vec![chalk_ir::DomainGoal::LayoutCompatible(
+                    chalk_ir::LayoutCompatible {
+                        a: a.lower(env)?,
+                        b: b.lower(env)?,
+                    },
+                )]
+            }
+            DomainGoal::Upcast { source, target } => {
+                vec![chalk_ir::DomainGoal::Upcast(chalk_ir::Upcast {
+                    source: source.lower(env)?,
+                    target: target.lower(env)?,
+                })]
+            }
         };
         Ok(goals)
     }
@@ -615,6 +769,14 @@ impl LowerLeafGoal for LeafGoal {
                 b: b.lower(env)?.cast(),
             }
             .cast()],
+            LeafGoal::TypeOutlives {
+                ref ty,
+                ref lifetime,
+            } => vec![chalk_ir::TypeOutlives {
+                ty: ty.lower(env)?,
+                lifetime: lifetime.lower(env)?,
+            }
+            .cast()],
         };
         Ok(goals)
     }
@@ -634,9 +796,14 @@ impl LowerStructDefn for StructDefn {
         struct_id: chalk_ir::StructId,
         env: &Env,
     ) -> LowerResult<rust_ir::StructDatum> {
-        if self.flags.fundamental && self.all_parameters().len() != 1 {
+        // `Box` is always fundamental.
+        let fundamental = self.flags.fundamental || self.lang_item == Some(LangItem::OwnedBox);
+        if fundamental && self.all_parameters().len() != 1 {
             Err(RustIrError::InvalidFundamentalTypesParameters(self.name))?;
         }
+        if self.flags.scalar.is_some() && !self.all_parameters().is_empty() {
+            Err(RustIrError::ScalarParameters(self.name))?;
+        }
 
         let binders = env.in_binders(self.all_parameters(), |env| {
             let fields: LowerResult<_> = self.fields.iter().map(|f| f.ty.lower(env)).collect();
@@ -650,7 +817,9 @@ impl LowerStructDefn for StructDefn {
 
         let flags = rust_ir::StructFlags {
             upstream: self.flags.upstream,
-            fundamental: self.flags.fundamental,
+            fundamental,
+            extern_type: self.flags.extern_type,
+            scalar: self.flags.scalar.map(|scalar| scalar.lower()),
         };
 
         Ok(rust_ir::StructDatum {
@@ -686,7 +855,7 @@ impl LowerTraitBound for TraitBound {
     fn lower(&self, env: &Env) -> LowerResult<rust_ir::TraitBound> {
         let trait_id = match env.lookup(self.trait_name)? {
             NameLookup::Type(TypeKindId::TraitId(trait_id)) => trait_id,
-            NameLookup::Type(_) | NameLookup::Parameter(_) => {
+            NameLookup::Type(_) | NameLookup::Parameter(_) | NameLookup::Builtin(_) => {
                 Err(RustIrError::NotTrait(self.trait_name))?
             }
         };
@@ -728,77 +897,150 @@ impl LowerTraitBound for TraitBound {
 }
 
 trait LowerProjectionEqBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::ProjectionEqBound>;
+    /// Lowers to one `ProjectionEqBound` per binding.
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::ProjectionEqBound>>;
 }
 
 impl LowerProjectionEqBound for ProjectionEqBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::ProjectionEqBound> {
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::ProjectionEqBound>> {
         let trait_bound = self.trait_bound.lower(env)?;
-        let lookup = match env
-            .associated_ty_lookups
-            .get(&(trait_bound.trait_id, self.name.str))
-        {
-            Some(lookup) => lookup,
-            None => Err(RustIrError::MissingAssociatedType(self.name))?,
-        };
-        let args: Vec<_> = self
-            .args
+        self.bindings
             .iter()
-            .map(|a| a.lower(env))
-            .collect::<LowerResult<_>>()?;
+            .map(|binding| {
+                let lookup = match env
+                    .associated_ty_lookups
+                    .get(&(trait_bound.trait_id, binding.name.lower()))
+                {
+                    Some(lookup) => lookup,
+                    None => Err(RustIrError::MissingAssociatedType(binding.name))?,
+                };
+                let args: Vec<_> = binding
+                    .args
+                    .iter()
+                    .map(|a| a.lower(env))
+                    .collect::<LowerResult<_>>()?;
 
-        if args.len() != lookup.addl_parameter_kinds.len() {
-            Err(RustIrError::IncorrectNumberOfAssociatedTypeParameters {
-                identifier: self.name,
-                expected: lookup.addl_parameter_kinds.len(),
-                actual: args.len(),
-            })?;
+                if args.len() != lookup.addl_parameter_kinds.len() {
+                    Err(RustIrError::IncorrectNumberOfAssociatedTypeParameters {
+                        identifier: binding.name,
+                        expected: lookup.addl_parameter_kinds.len(),
+                        actual: args.len(),
+                    })?;
+                }
+
+                for (param, arg) in lookup.addl_parameter_kinds.iter().zip(args.iter()) {
+                    if param.kind() != arg.kind() {
+                        Err(RustIrError::IncorrectAssociatedTypeParameterKind {
+                            identifier: binding.name,
+                            expected: param.kind(),
+                            actual: arg.kind(),
+                        })?;
+                    }
+                }
+
+                Ok(rust_ir::ProjectionEqBound {
+                    trait_bound: trait_bound.clone(),
+                    associated_ty_id: lookup.id,
+                    parameters: args,
+                    value: binding.value.lower(env)?,
+                })
+            })
+            .collect()
+    }
+}
+
+trait LowerFnBound {
+    fn lower(&self, env: &Env) -> LowerResult<rust_ir::ProjectionEqBound>;
+}
+
+impl LowerFnBound for FnBound {
+    /// Desugars `Fn(A, B) -> C` into the bound `Fn<(A, B)>` along with
+    /// `<Self as FnOnce<(A, B)>>::Output = C`. As in Rust, `Output` is
+    /// always the one declared by the `FnOnce` lang item, even if the
+    /// sugar names `Fn` or `FnMut`.
+    fn lower(&self, env: &Env) -> LowerResult<rust_ir::ProjectionEqBound> {
+        let trait_bound = TraitBound {
+            trait_name: self.trait_name,
+            args_no_self: vec![Parameter::Ty(Ty::Tuple {
+                types: self.inputs.clone(),
+            })],
         }
+        .lower(env)?;
 
-        for (param, arg) in lookup.addl_parameter_kinds.iter().zip(args.iter()) {
-            if param.kind() != arg.kind() {
-                Err(RustIrError::IncorrectAssociatedTypeParameterKind {
-                    identifier: self.name,
-                    expected: param.kind(),
-                    actual: arg.kind(),
-                })?;
-            }
+        let is_lang_item = |item| env.lang_items.get(&item) == Some(&trait_bound.trait_id.into());
+        if !(is_lang_item(rust_ir::LangItem::FnOnce)
+            || is_lang_item(rust_ir::LangItem::FnMut)
+            || is_lang_item(rust_ir::LangItem::Fn))
+        {
+            Err(RustIrError::NotFnTrait(self.trait_name))?;
         }
 
+        let output = Identifier {
+            str: intern("Output"),
+            span: self.trait_name.span,
+        };
+        let lookup = match env.lang_items.get(&rust_ir::LangItem::FnOnce) {
+            Some(&TypeKindId::TraitId(fn_once_id)) => {
+                env.associated_ty_lookups.get(&(fn_once_id, output.lower()))
+            }
+            _ => None,
+        };
+        let lookup = match lookup {
+            Some(lookup) if lookup.addl_parameter_kinds.is_empty() => lookup,
+            _ => Err(RustIrError::MissingAssociatedType(output))?,
+        };
+
+        let value = match &self.output {
+            Some(ty) => ty.lower(env)?,
+            None => Ty::Tuple { types: vec![] }.lower(env)?,
+        };
+
         Ok(rust_ir::ProjectionEqBound {
             trait_bound,
             associated_ty_id: lookup.id,
-            parameters: args,
-            value: self.value.lower(env)?,
+            parameters: vec![],
+            value,
         })
     }
 }
 
 trait LowerInlineBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::InlineBound>;
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::InlineBound>>;
 }
 
 impl LowerInlineBound for InlineBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::InlineBound> {
-        let bound = match self {
-            InlineBound::TraitBound(b) => rust_ir::InlineBound::TraitBound(b.lower(&env)?),
-            InlineBound::ProjectionEqBound(b) => {
-                rust_ir::InlineBound::ProjectionEqBound(b.lower(&env)?)
-            }
+    /// Lowers to a single bound, except for a bound with several
+    /// associated type bindings (see `LowerProjectionEqBound`).
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::InlineBound>> {
+        let bounds = match self {
+            InlineBound::TraitBound(b) => vec![rust_ir::InlineBound::TraitBound(b.lower(&env)?)],
+            InlineBound::ProjectionEqBound(b) => b
+                .lower(&env)?
+                .into_iter()
+                .map(rust_ir::InlineBound::ProjectionEqBound)
+                .collect(),
+            InlineBound::FnBound(b) => vec![rust_ir::InlineBound::ProjectionEqBound(b.lower(env)?)],
         };
-        Ok(bound)
+        Ok(bounds)
     }
 }
 
 trait LowerQuantifiedInlineBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::QuantifiedInlineBound>;
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::QuantifiedInlineBound>>;
 }
 
 impl LowerQuantifiedInlineBound for QuantifiedInlineBound {
-    fn lower(&self, env: &Env) -> LowerResult<rust_ir::QuantifiedInlineBound> {
+    fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::QuantifiedInlineBound>> {
         let parameter_kinds = self.parameter_kinds.iter().map(|pk| pk.lower());
-        let binders = env.in_binders(parameter_kinds, |env| Ok(self.bound.lower(env)?))?;
-        Ok(binders)
+        let chalk_ir::Binders { binders, value } =
+            env.in_binders(parameter_kinds, |env| Ok(self.bound.lower(env)?))?;
+        Ok(value
+            .into_iter()
+            .map(|bound| chalk_ir::Binders {
+                binders: binders,
+                value: bound,
+            })
+            .collect())
     }
 }
 
@@ -808,7 +1050,11 @@ trait LowerQuantifiedInlineBoundVec {
 
 impl LowerQuantifiedInlineBoundVec for [QuantifiedInlineBound] {
     fn lower(&self, env: &Env) -> LowerResult<Vec<rust_ir::QuantifiedInlineBound>> {
-        self.iter().map(|b| b.lower(env)).collect()
+        let mut bounds = vec![];
+        for b in self {
+            bounds.extend(b.lower(env)?);
+        }
+        Ok(bounds)
     }
 }
 
@@ -850,6 +1096,80 @@ impl LowerTraitFlags for TraitFlags {
             upstream: self.upstream,
             fundamental: self.fundamental,
             non_enumerable: self.non_enumerable,
+            sealed: self.sealed,
+        }
+    }
+}
+
+/// Lang items and well-known traits share the `#[lang(..)]` attribute,
+/// but are recorded separately.
+enum LoweredLangItem {
+    LangItem(rust_ir::LangItem),
+    WellKnownTrait(rust_ir::WellKnownTrait),
+}
+
+trait LowerLangItem {
+    fn lower(&self) -> LoweredLangItem;
+}
+
+impl LowerLangItem for LangItem {
+    fn lower(&self) -> LoweredLangItem {
+        use rust_ir::WellKnownTrait as W;
+        let lang_item = |item| LoweredLangItem::LangItem(item);
+        let well_known = |item| LoweredLangItem::WellKnownTrait(item);
+        match self {
+            LangItem::OwnedBox => lang_item(rust_ir::LangItem::OwnedBox),
+            LangItem::Sized => lang_item(rust_ir::LangItem::Sized),
+            LangItem::Unpin => lang_item(rust_ir::LangItem::Unpin),
+            LangItem::Drop => lang_item(rust_ir::LangItem::Drop),
+            LangItem::FnOnce => lang_item(rust_ir::LangItem::FnOnce),
+            LangItem::FnMut => lang_item(rust_ir::LangItem::FnMut),
+            LangItem::Fn => lang_item(rust_ir::LangItem::Fn),
+            LangItem::Copy => lang_item(rust_ir::LangItem::Copy),
+            LangItem::Clone => lang_item(rust_ir::LangItem::Clone),
+            LangItem::Send => lang_item(rust_ir::LangItem::Send),
+            LangItem::Sync => lang_item(rust_ir::LangItem::Sync),
+            LangItem::UnsafeCell => lang_item(rust_ir::LangItem::UnsafeCell),
+            LangItem::Deref => well_known(W::Deref),
+            LangItem::Index => well_known(W::Index),
+            LangItem::Add => well_known(W::Add),
+            LangItem::Sub => well_known(W::Sub),
+            LangItem::Mul => well_known(W::Mul),
+            LangItem::Div => well_known(W::Div),
+            LangItem::Rem => well_known(W::Rem),
+            LangItem::BitAnd => well_known(W::BitAnd),
+            LangItem::BitOr => well_known(W::BitOr),
+            LangItem::BitXor => well_known(W::BitXor),
+            LangItem::Shl => well_known(W::Shl),
+            LangItem::Shr => well_known(W::Shr),
+        }
+    }
+}
+
+trait LowerScalarKind {
+    fn lower(&self) -> rust_ir::ScalarKind;
+}
+
+impl LowerScalarKind for ScalarKind {
+    fn lower(&self) -> rust_ir::ScalarKind {
+        match self {
+            ScalarKind::Bool => rust_ir::ScalarKind::Bool,
+            ScalarKind::Char => rust_ir::ScalarKind::Char,
+            ScalarKind::Int => rust_ir::ScalarKind::Int,
+            ScalarKind::Float => rust_ir::ScalarKind::Float,
+        }
+    }
+}
+
+trait LowerMutability {
+    fn lower(&self) -> chalk_ir::Mutability;
+}
+
+impl LowerMutability for Mutability {
+    fn lower(&self) -> chalk_ir::Mutability {
+        match self {
+            Mutability::Not => chalk_ir::Mutability::Not,
+            Mutability::Mut => chalk_ir::Mutability::Mut,
         }
     }
 }
@@ -869,7 +1189,10 @@ impl LowerProjectionTy for ProjectionTy {
             trait_id,
             parameters: trait_parameters,
         } = trait_ref.lower(env)?;
-        let lookup = match env.associated_ty_lookups.get(&(trait_id.into(), name.str)) {
+        let lookup = match env
+            .associated_ty_lookups
+            .get(&(trait_id.into(), name.lower()))
+        {
             Some(lookup) => lookup,
             None => Err(RustIrError::MissingAssociatedType(self.name))?,
         };
@@ -929,36 +1252,35 @@ impl LowerTy for Ty {
                     }
                 }
                 NameLookup::Parameter(d) => Ok(chalk_ir::Ty::BoundVar(d)),
+                NameLookup::Builtin(builtin) => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                    name: chalk_ir::TypeName::Builtin(builtin),
+                    parameters: vec![],
+                })),
             },
 
             Ty::Dyn { ref bounds } => Ok(chalk_ir::Ty::Dyn(env.in_binders(
                 // FIXME: Figure out a proper name for this type parameter
-                Some(chalk_ir::ParameterKind::Ty(intern(FIXME_SELF))),
-                |env| {
-                    Ok(bounds
-                        .lower(env)?
-                        .iter()
-                        .flat_map(|qil| qil.into_where_clauses(chalk_ir::Ty::BoundVar(0)))
-                        .collect())
-                },
+                Some(chalk_ir::ParameterKind::Ty(chalk_ir::intern(FIXME_SELF))),
+                |env| Ok(bounds_where_clauses(&bounds.lower(env)?)),
             )?)),
 
             Ty::Opaque { ref bounds } => Ok(chalk_ir::Ty::Opaque(env.in_binders(
                 // FIXME: Figure out a proper name for this type parameter
-                Some(chalk_ir::ParameterKind::Ty(intern(FIXME_SELF))),
-                |env| {
-                    Ok(bounds
-                        .lower(env)?
-                        .iter()
-                        .flat_map(|qil| qil.into_where_clauses(chalk_ir::Ty::BoundVar(0)))
-                        .collect())
-                },
+                Some(chalk_ir::ParameterKind::Ty(chalk_ir::intern(FIXME_SELF))),
+                |env| Ok(bounds_where_clauses(&bounds.lower(env)?)),
             )?)),
 
             Ty::Apply { name, ref args } => {
                 let id = match env.lookup(name)? {
                     NameLookup::Type(id) => id,
                     NameLookup::Parameter(_) => Err(RustIrError::CannotApplyTypeParameter(name))?,
+                    NameLookup::Builtin(builtin) => {
+                        Err(RustIrError::IncorrectNumberOfTypeParameters {
+                            identifier: name,
+                            expected: builtin.arity(),
+                            actual: args.len(),
+                        })?
+                    }
                 };
 
                 let k = env.type_kind(id);
@@ -1000,7 +1322,7 @@ impl LowerTy for Ty {
                 let quantified_env = env.introduce(
                     lifetime_names
                         .iter()
-                        .map(|id| chalk_ir::ParameterKind::Lifetime(id.str)),
+                        .map(|id| chalk_ir::ParameterKind::Lifetime(id.lower())),
                 )?;
 
                 let ty = ty.lower(&quantified_env)?;
@@ -1010,10 +1332,82 @@ impl LowerTy for Ty {
                 };
                 Ok(chalk_ir::Ty::ForAll(Box::new(quantified_ty)))
             }
+
+            Ty::Tuple { ref types } => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                name: chalk_ir::TypeName::Tuple(types.len()),
+                parameters: types
+                    .iter()
+                    .map(|t| Ok(t.lower(env)?.cast()))
+                    .collect::<LowerResult<Vec<_>>>()?,
+            })),
+
+            // The arguments are lowered as a tuple; see `TypeName::FnPtr`.
+            Ty::FnPtr { ref args, ref ret } => {
+                let args = Ty::Tuple {
+                    types: args.clone(),
+                };
+                Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                    name: chalk_ir::TypeName::FnPtr,
+                    parameters: vec![args.lower(env)?.cast(), ret.lower(env)?.cast()],
+                }))
+            }
+
+            Ty::OpaqueConst { name, ref args } => {
+                Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                    name: chalk_ir::TypeName::OpaqueConst(name.lower()),
+                    parameters: args
+                        .iter()
+                        .map(|a| a.lower(env))
+                        .collect::<LowerResult<Vec<_>>>()?,
+                }))
+            }
+
+            Ty::Slice { ref ty } => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                name: chalk_ir::TypeName::Builtin(chalk_ir::BuiltinType::Slice),
+                parameters: vec![ty.lower(env)?.cast()],
+            })),
+
+            Ty::Array { ref ty, ref len } => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                name: chalk_ir::TypeName::Builtin(chalk_ir::BuiltinType::Array),
+                parameters: vec![ty.lower(env)?.cast(), len.lower(env)?.cast()],
+            })),
+
+            Ty::Ref {
+                ref lifetime,
+                mutability,
+                ref ty,
+            } => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                name: chalk_ir::TypeName::Builtin(chalk_ir::BuiltinType::Ref(mutability.lower())),
+                parameters: vec![ty.lower(env)?.cast(), lifetime.lower(env)?.cast()],
+            })),
+
+            Ty::Ptr { mutability, ref ty } => Ok(chalk_ir::Ty::Apply(chalk_ir::ApplicationTy {
+                name: chalk_ir::TypeName::Builtin(chalk_ir::BuiltinType::Ptr(mutability.lower())),
+                parameters: vec![ty.lower(env)?.cast()],
+            })),
         }
     }
 }
 
+/// The where clauses of the bounds of a `dyn` or `impl Trait` type,
+/// whose self type is `^0`. A bound with several associated type
+/// bindings lowers to several bounds, each of which would repeat the
+/// `Implemented` where clause; it is only kept once.
+fn bounds_where_clauses(
+    bounds: &[rust_ir::QuantifiedInlineBound],
+) -> Vec<chalk_ir::QuantifiedWhereClause<ChalkIr>> {
+    let mut where_clauses = vec![];
+    for wc in bounds
+        .iter()
+        .flat_map(|qil| qil.into_where_clauses(chalk_ir::Ty::BoundVar(0)))
+    {
+        if !where_clauses.contains(&wc) {
+            where_clauses.push(wc);
+        }
+    }
+    where_clauses
+}
+
 trait LowerParameter {
     fn lower(&self, env: &Env) -> LowerResult<chalk_ir::Parameter<ChalkIr>>;
 }
@@ -1064,7 +1458,9 @@ impl LowerImpl for Impl {
             let trait_ref = self.trait_ref.lower(env)?;
             debug!("trait_ref = {:?}", trait_ref);
 
-            if !polarity.is_positive() && !self.assoc_ty_values.is_empty() {
+            if !polarity.is_positive()
+                && (!self.assoc_ty_values.is_empty() || !self.method_defns.is_empty())
+            {
                 Err(RustIrError::NegativeImplAssociatedValues(
                     self.trait_ref.trait_name,
                 ))?;
@@ -1084,16 +1480,57 @@ impl LowerImpl for Impl {
         let associated_ty_value_ids = self
             .assoc_ty_values
             .iter()
-            .map(|atv| associated_ty_value_ids[&(impl_id, atv.name.str)])
+            .map(|atv| associated_ty_value_ids[&(impl_id, atv.name.lower())])
             .collect();
 
         debug!("associated_ty_value_ids = {:?}", associated_ty_value_ids);
 
+        let methods = self
+            .method_defns
+            .iter()
+            .map(|defn| defn.lower_method(empty_env, self.all_parameters()))
+            .collect::<LowerResult<_>>()?;
+
         Ok(rust_ir::ImplDatum {
             polarity,
             binders: binders,
             impl_type: self.impl_type.lower(),
             associated_ty_value_ids,
+            methods,
+        })
+    }
+}
+
+trait LowerInherentImpl {
+    fn lower_inherent_impl(
+        &self,
+        empty_env: &Env,
+        impl_id: rust_ir::InherentImplId,
+    ) -> LowerResult<rust_ir::InherentImplDatum>;
+}
+
+impl LowerInherentImpl for InherentImpl {
+    fn lower_inherent_impl(
+        &self,
+        empty_env: &Env,
+        impl_id: rust_ir::InherentImplId,
+    ) -> LowerResult<rust_ir::InherentImplDatum> {
+        debug_heading!(
+            "LowerInherentImpl::lower_inherent_impl(impl_id={:?})",
+            impl_id
+        );
+
+        let binders = empty_env.in_binders(self.all_parameters(), |env| {
+            Ok(rust_ir::InherentImplDatumBound {
+                self_ty: self.self_ty.lower(env)?,
+                where_clauses: self.where_clauses.lower(env)?,
+            })
+        })?;
+
+        Ok(rust_ir::InherentImplDatum {
+            id: impl_id,
+            binders,
+            items: self.items.iter().map(|item| item.name().lower()).collect(),
         })
     }
 }
@@ -1110,7 +1547,7 @@ impl LowerClause for Clause {
             let conditions: Vec<chalk_ir::Goal<ChalkIr>> = self
                 .conditions
                 .iter()
-                .map(|g| g.lower(env).map(|g| *g))
+                .map(|g| g.lower(env))
                 .rev() // (*)
                 .collect::<LowerResult<_>>()?;
 
@@ -1145,9 +1582,12 @@ impl LowerClause for Clause {
 }
 
 trait LowerTrait {
+    /// Lowers the trait; `default_impl` says whether the program has
+    /// a default impl for it, which makes it an auto trait.
     fn lower_trait(
         &self,
         trait_id: chalk_ir::TraitId,
+        default_impl: bool,
         env: &Env,
     ) -> LowerResult<rust_ir::TraitDatum>;
 }
@@ -1156,12 +1596,18 @@ impl LowerTrait for TraitDefn {
     fn lower_trait(
         &self,
         trait_id: chalk_ir::TraitId,
+        default_impl: bool,
         env: &Env,
     ) -> LowerResult<rust_ir::TraitDatum> {
+        let auto = self.flags.auto || default_impl;
+        if auto && !self.assoc_ty_defns.is_empty() {
+            Err(RustIrError::AutoTraitAssociatedTypes(self.name))?;
+        }
+
         let all_parameters = self.all_parameters();
         let all_parameters_len = all_parameters.len();
         let binders = env.in_binders(all_parameters, |env| {
-            if self.flags.auto {
+            if auto {
                 if all_parameters_len > 1 {
                     Err(RustIrError::AutoTraitParameters(self.name))?;
                 }
@@ -1178,24 +1624,73 @@ impl LowerTrait for TraitDefn {
         let associated_ty_ids: Vec<_> = self
             .assoc_ty_defns
             .iter()
-            .map(|defn| env.associated_ty_lookups[&(trait_id, defn.name.str)].id)
+            .map(|defn| env.associated_ty_lookups[&(trait_id, defn.name.lower())].id)
             .collect();
 
+        let methods = self
+            .method_defns
+            .iter()
+            .map(|defn| defn.lower_method(env, self.all_parameters()))
+            .collect::<LowerResult<_>>()?;
+
         Ok(rust_ir::TraitDatum {
             id: trait_id,
             binders: binders,
-            flags: self.flags.lower(),
+            flags: rust_ir::TraitFlags {
+                auto,
+                ..self.flags.lower()
+            },
             associated_ty_ids,
+            methods,
+        })
+    }
+}
+
+trait LowerMethod {
+    /// Lowers the method, which belongs to a trait or impl with the
+    /// parameters `outer_parameters`. As for associated types, the
+    /// parameters of the method come first.
+    fn lower_method(
+        &self,
+        empty_env: &Env,
+        outer_parameters: Vec<chalk_ir::ParameterKind<chalk_ir::Identifier>>,
+    ) -> LowerResult<rust_ir::MethodDatum>;
+}
+
+impl LowerMethod for MethodDefn {
+    fn lower_method(
+        &self,
+        empty_env: &Env,
+        outer_parameters: Vec<chalk_ir::ParameterKind<chalk_ir::Identifier>>,
+    ) -> LowerResult<rust_ir::MethodDatum> {
+        let mut parameter_kinds = self.all_parameters();
+        parameter_kinds.extend(outer_parameters);
+
+        let binders = empty_env.in_binders(parameter_kinds, |env| {
+            Ok(rust_ir::MethodDatumBound {
+                inputs: self
+                    .args
+                    .iter()
+                    .map(|ty| ty.lower(env))
+                    .collect::<LowerResult<_>>()?,
+                output: self.ret.lower(env)?,
+                where_clauses: self.lower_where_clauses(env)?,
+            })
+        })?;
+
+        Ok(rust_ir::MethodDatum {
+            name: self.name.lower(),
+            binders,
         })
     }
 }
 
 pub trait LowerGoal<A> {
-    fn lower(&self, arg: &A) -> LowerResult<Box<chalk_ir::Goal<ChalkIr>>>;
+    fn lower(&self, arg: &A) -> LowerResult<chalk_ir::Goal<ChalkIr>>;
 }
 
 impl LowerGoal<LoweredProgram> for Goal {
-    fn lower(&self, program: &LoweredProgram) -> LowerResult<Box<chalk_ir::Goal<ChalkIr>>> {
+    fn lower(&self, program: &LoweredProgram) -> LowerResult<chalk_ir::Goal<ChalkIr>> {
         let associated_ty_lookups: BTreeMap<_, _> = program
             .associated_ty_data
             .iter()
@@ -1206,7 +1701,7 @@ impl LowerGoal<LoweredProgram> for Goal {
                 let addl_parameter_kinds = datum.binders.binders[..num_addl_params].to_owned();
                 let lookup = AssociatedTyLookup {
                     id: associated_ty_id,
-                    addl_parameter_kinds,
+                    addl_parameter_kinds: addl_parameter_kinds.into(),
                 };
                 ((datum.trait_id, datum.name), lookup)
             })
@@ -1216,6 +1711,7 @@ impl LowerGoal<LoweredProgram> for Goal {
             type_ids: &program.type_ids,
             type_kinds: &program.type_kinds,
             associated_ty_lookups: &associated_ty_lookups,
+            lang_items: &program.lang_items,
             parameter_map: BTreeMap::new(),
         };
 
@@ -1224,7 +1720,7 @@ impl LowerGoal<LoweredProgram> for Goal {
 }
 
 impl<'k> LowerGoal<Env<'k>> for Goal {
-    fn lower(&self, env: &Env<'k>) -> LowerResult<Box<chalk_ir::Goal<ChalkIr>>> {
+    fn lower(&self, env: &Env<'k>) -> LowerResult<chalk_ir::Goal<ChalkIr>> {
         match self {
             Goal::ForAll(ids, g) => g.lower_quantified(env, chalk_ir::QuantifierKind::ForAll, ids),
             Goal::Exists(ids, g) => g.lower_quantified(env, chalk_ir::QuantifierKind::Exists, ids),
@@ -1238,24 +1734,24 @@ impl<'k> LowerGoal<Env<'k>> for Goal {
                     .flat_map(|h| h.lower_clause(env).apply_result())
                     .map(|result| result.map(|h| h.into_from_env_clause()))
                     .collect();
-                Ok(Box::new(chalk_ir::Goal::Implies(
+                Ok(chalk_ir::Goal::Implies(
                     where_clauses?,
-                    g.lower(env)?,
-                )))
+                    Arc::new(g.lower(env)?),
+                ))
             }
-            Goal::And(g1, g2) => Ok(Box::new(chalk_ir::Goal::And(
-                g1.lower(env)?,
-                g2.lower(env)?,
-            ))),
-            Goal::Not(g) => Ok(Box::new(chalk_ir::Goal::Not(g.lower(env)?))),
-            Goal::Compatible(g) => Ok(Box::new(g.lower(env)?.compatible())),
+            Goal::And(g1, g2) => Ok(chalk_ir::Goal::And(
+                Arc::new(g1.lower(env)?),
+                Arc::new(g2.lower(env)?),
+            )),
+            Goal::Not(g) => Ok(chalk_ir::Goal::Not(Arc::new(g.lower(env)?))),
+            Goal::Compatible(g) => Ok(g.lower(env)?.compatible()),
             Goal::Leaf(leaf) => {
                 // A where clause can lower to multiple leaf goals; wrap these in Goal::And.
                 let leaves = leaf.lower(env)?.into_iter().map(chalk_ir::Goal::Leaf);
                 let goal = leaves
-                    .fold1(|goal, leaf| chalk_ir::Goal::And(Box::new(goal), Box::new(leaf)))
+                    .fold1(|goal, leaf| chalk_ir::Goal::And(Arc::new(goal), Arc::new(leaf)))
                     .expect("at least one goal");
-                Ok(Box::new(goal))
+                Ok(goal)
             }
         }
     }
@@ -1267,7 +1763,7 @@ trait LowerQuantifiedGoal {
         env: &Env,
         quantifier_kind: chalk_ir::QuantifierKind,
         parameter_kinds: &[ParameterKind],
-    ) -> LowerResult<Box<chalk_ir::Goal<ChalkIr>>>;
+    ) -> LowerResult<chalk_ir::Goal<ChalkIr>>;
 }
 
 impl LowerQuantifiedGoal for Goal {
@@ -1276,17 +1772,14 @@ impl LowerQuantifiedGoal for Goal {
         env: &Env,
         quantifier_kind: chalk_ir::QuantifierKind,
         parameter_kinds: &[ParameterKind],
-    ) -> LowerResult<Box<chalk_ir::Goal<ChalkIr>>> {
+    ) -> LowerResult<chalk_ir::Goal<ChalkIr>> {
         if parameter_kinds.is_empty() {
             return self.lower(env);
         }
 
         let parameter_kinds = parameter_kinds.iter().map(|pk| pk.lower());
-        let subgoal = env.in_binders(parameter_kinds, |env| self.lower(env))?;
-        Ok(Box::new(chalk_ir::Goal::Quantified(
-            quantifier_kind,
-            subgoal,
-        )))
+        let subgoal = env.in_binders(parameter_kinds, |env| Ok(Arc::new(self.lower(env)?)))?;
+        Ok(chalk_ir::Goal::Quantified(quantifier_kind, subgoal))
     }
 }
 
@@ -1306,7 +1799,7 @@ impl<T> ApplyResult for LowerResult<Vec<T>> {
     }
 }
 
-trait Kinded {
+pub(crate) trait Kinded {
     fn kind(&self) -> Kind;
 }
 
@@ -0,0 +1,232 @@
+//! Shrinks a program that exhibits some failure (a wrong solution, a
+//! panic in the solver, ...) to a smaller program that still exhibits
+//! it, for use in bug reports.
+//!
+//! The minimizer repeatedly removes parts of the program -- items,
+//! then the where clauses, fields, associated types and conditions
+//! within the remaining items -- and keeps each removal for which the
+//! failure persists. The removals are tried in chunks, as in delta
+//! debugging, so that large programs shrink quickly. The result is
+//! minimal in the sense that no single part can be removed from it
+//! without the failure going away.
+
+use crate::db::ChalkDatabase;
+use crate::error::ChalkError;
+use crate::query::LoweringDatabase;
+use chalk_parse::ast;
+use chalk_solve::ext::GoalExt;
+use chalk_solve::{Solution, SolverChoice};
+use std::panic::{self, AssertUnwindSafe};
+
+/// The result of solving a goal in a candidate program, as passed to
+/// the `is_failure` callback of `minimize_repro`.
+#[derive(Debug)]
+pub enum ReproOutcome {
+    /// The program or the goal failed to lower, or the program failed
+    /// its checks (coherence, WF, ...).
+    Error(ChalkError),
+
+    /// The solver returned this solution.
+    Solution(Option<Solution>),
+
+    /// The solver panicked, with this message.
+    Panic(String),
+}
+
+/// Shrinks `program_text` to a smaller program in which solving
+/// `goal_text` still yields an outcome for which `is_failure` returns
+/// true, and returns that program as text. Candidate programs in which
+/// the goal no longer lowers (e.g., because they no longer define a
+/// type that it names) are given to `is_failure` as errors like any
+/// other outcome, so callers looking for a wrong solution or a panic
+/// should return false for `ReproOutcome::Error`.
+///
+/// Errors if `program_text` does not parse, or if it does not exhibit
+/// the failure to begin with.
+///
+/// Note that panics in the solver are still reported by the panic
+/// hook as they occur.
+pub fn minimize_repro(
+    program_text: &str,
+    goal_text: &str,
+    solver_choice: SolverChoice,
+    mut is_failure: impl FnMut(&ReproOutcome) -> bool,
+) -> Result<String, ChalkError> {
+    let program = chalk_parse::parse_program(program_text)?;
+    let mut still_fails =
+        |program: &ast::Program| is_failure(&solve_candidate(program, goal_text, solver_choice));
+
+    if !still_fails(&program) {
+        let error: Box<dyn std::error::Error> = "the program does not exhibit the failure".into();
+        return Err(error.into());
+    }
+
+    Ok(minimize_program(program, still_fails).to_string())
+}
+
+/// Lowers `program`, with its checks, and solves `goal_text` in it.
+fn solve_candidate(
+    program: &ast::Program,
+    goal_text: &str,
+    solver_choice: SolverChoice,
+) -> ReproOutcome {
+    let db = ChalkDatabase::with(&program.to_string(), solver_choice);
+    if let Err(error) = db.checked_program() {
+        return ReproOutcome::Error(error);
+    }
+
+    db.with_program(|_| {
+        let goal = match db.parse_and_lower_goal(goal_text) {
+            Ok(goal) => goal,
+            Err(error) => return ReproOutcome::Error(error),
+        };
+        let goal = goal.into_closed_goal();
+        match panic::catch_unwind(AssertUnwindSafe(|| db.solve(&goal))) {
+            Ok(solution) => ReproOutcome::Solution(solution),
+            Err(payload) => ReproOutcome::Panic(
+                payload
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                    .unwrap_or_default(),
+            ),
+        }
+    })
+}
+
+/// Shrinks `program`, for which `still_fails` returns true, to a
+/// smaller program for which it still does (see the module docs).
+pub fn minimize_program(
+    mut program: ast::Program,
+    mut still_fails: impl FnMut(&ast::Program) -> bool,
+) -> ast::Program {
+    // Removing something from one item may make it possible to remove
+    // another item (e.g., an impl that was only needed to satisfy a
+    // where clause), so we go until nothing changes.
+    loop {
+        let before = program.clone();
+
+        let mut items = program.items.clone();
+        shrink(&mut items, |items| {
+            still_fails(&ast::Program {
+                items: items.to_vec(),
+            })
+        });
+        program.items = items;
+
+        for index in 0..program.items.len() {
+            shrink_within(&mut program, index, where_clauses, &mut still_fails);
+            shrink_within(&mut program, index, fields, &mut still_fails);
+            shrink_within(&mut program, index, assoc_ty_defns, &mut still_fails);
+            shrink_within(&mut program, index, assoc_ty_values, &mut still_fails);
+            shrink_within(&mut program, index, method_defns, &mut still_fails);
+            shrink_within(&mut program, index, conditions, &mut still_fails);
+        }
+
+        if program == before {
+            return program;
+        }
+    }
+}
+
+/// Shrinks the vector that `select` returns for the item at `index`,
+/// if any, keeping the rest of `program` as is.
+fn shrink_within<T: Clone>(
+    program: &mut ast::Program,
+    index: usize,
+    select: fn(&mut ast::Item) -> Option<&mut Vec<T>>,
+    still_fails: &mut impl FnMut(&ast::Program) -> bool,
+) {
+    let mut elements = match select(&mut program.items[index]) {
+        Some(elements) if !elements.is_empty() => elements.clone(),
+        _ => return,
+    };
+
+    let mut candidate = program.clone();
+    shrink(&mut elements, |elements| {
+        *select(&mut candidate.items[index]).unwrap() = elements.to_vec();
+        still_fails(&candidate)
+    });
+
+    *select(&mut program.items[index]).unwrap() = elements;
+}
+
+/// Removes elements from `elements` for as long as `still_fails`
+/// returns true for what is left, trying to remove large chunks first.
+fn shrink<T: Clone>(elements: &mut Vec<T>, mut still_fails: impl FnMut(&[T]) -> bool) {
+    let mut chunk_size = (elements.len() / 2).max(1);
+    while !elements.is_empty() {
+        let mut removed_any = false;
+        let mut start = 0;
+        while start < elements.len() {
+            let end = (start + chunk_size).min(elements.len());
+            let candidate: Vec<T> = elements[..start]
+                .iter()
+                .chain(&elements[end..])
+                .cloned()
+                .collect();
+            if still_fails(&candidate) {
+                *elements = candidate;
+                removed_any = true;
+            } else {
+                start = end;
+            }
+        }
+
+        if !removed_any {
+            if chunk_size == 1 {
+                break;
+            }
+            chunk_size = (chunk_size / 2).max(1);
+        }
+    }
+}
+
+fn where_clauses(item: &mut ast::Item) -> Option<&mut Vec<ast::QuantifiedWhereClause>> {
+    match item {
+        ast::Item::StructDefn(defn) => Some(&mut defn.where_clauses),
+        ast::Item::TraitDefn(defn) => Some(&mut defn.where_clauses),
+        ast::Item::Impl(impl_) => Some(&mut impl_.where_clauses),
+        ast::Item::InherentImpl(impl_) => Some(&mut impl_.where_clauses),
+        ast::Item::DefaultImpl(_) | ast::Item::Clause(_) => None,
+    }
+}
+
+fn fields(item: &mut ast::Item) -> Option<&mut Vec<ast::Field>> {
+    match item {
+        ast::Item::StructDefn(defn) => Some(&mut defn.fields),
+        _ => None,
+    }
+}
+
+fn assoc_ty_defns(item: &mut ast::Item) -> Option<&mut Vec<ast::AssocTyDefn>> {
+    match item {
+        ast::Item::TraitDefn(defn) => Some(&mut defn.assoc_ty_defns),
+        _ => None,
+    }
+}
+
+fn assoc_ty_values(item: &mut ast::Item) -> Option<&mut Vec<ast::AssocTyValue>> {
+    match item {
+        ast::Item::Impl(impl_) => Some(&mut impl_.assoc_ty_values),
+        _ => None,
+    }
+}
+
+fn method_defns(item: &mut ast::Item) -> Option<&mut Vec<ast::MethodDefn>> {
+    match item {
+        ast::Item::TraitDefn(defn) => Some(&mut defn.method_defns),
+        ast::Item::Impl(impl_) => Some(&mut impl_.method_defns),
+        _ => None,
+    }
+}
+
+/// The type of `ast::Clause::conditions`.
+type Conditions = Vec<Box<ast::Goal>>;
+
+fn conditions(item: &mut ast::Item) -> Option<&mut Conditions> {
+    match item {
+        ast::Item::Clause(clause) => Some(&mut clause.conditions),
+        _ => None,
+    }
+}
@@ -7,11 +7,11 @@ use chalk_ir::{
     TypeKindId, TypeName,
 };
 use chalk_rust_ir::{
-    AssociatedTyDatum, AssociatedTyValue, AssociatedTyValueId, ImplDatum, ImplType, StructDatum,
-    TraitDatum, TypeKind,
+    AssociatedTyDatum, AssociatedTyValue, AssociatedTyValueId, ImplDatum, ImplType,
+    InherentImplDatum, InherentImplId, LangItem, StructDatum, TraitDatum, TypeKind, WellKnownItems,
 };
 use chalk_solve::split::Split;
-use chalk_solve::RustIrDatabase;
+use chalk_solve::{MissingData, ProgramItems, RustIrDatabase};
 use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::Arc;
@@ -30,6 +30,9 @@ pub struct Program {
     /// For each impl:
     pub impl_data: BTreeMap<ImplId, Arc<ImplDatum>>,
 
+    /// For each inherent impl:
+    pub inherent_impl_data: BTreeMap<InherentImplId, Arc<InherentImplDatum>>,
+
     /// For each associated ty value `type Foo = XXX` found in an impl:
     pub associated_ty_values: BTreeMap<AssociatedTyValueId, Arc<AssociatedTyValue>>,
 
@@ -41,6 +44,13 @@ pub struct Program {
 
     /// For each user-specified clause
     pub custom_clauses: Vec<ProgramClause<ChalkIr>>,
+
+    /// For each lang item (e.g., `#[lang(owned_box)]`), the struct or
+    /// trait that was declared as that item.
+    pub lang_items: BTreeMap<LangItem, TypeKindId>,
+
+    /// The traits declared as well-known traits (e.g., `#[lang(add)]`).
+    pub well_known_items: Arc<WellKnownItems>,
 }
 
 impl Program {
@@ -52,6 +62,16 @@ impl Program {
             .map(|(&impl_id, _)| impl_id)
             .collect()
     }
+
+    /// Returns the items that `chalk_solve::check_program` checks: all
+    /// traits and structs, and the impls declared in this crate.
+    pub fn program_items(&self) -> ProgramItems {
+        ProgramItems {
+            traits: self.trait_data.keys().cloned().collect(),
+            structs: self.struct_data.keys().cloned().collect(),
+            impls: self.local_impl_ids(),
+        }
+    }
 }
 
 impl tls::DebugContext for Program {
@@ -122,6 +142,44 @@ impl RustIrDatabase for Program {
         self.struct_data[&id].clone()
     }
 
+    fn try_associated_ty_data(&self, ty: TypeId) -> Result<Arc<AssociatedTyDatum>, MissingData> {
+        self.associated_ty_data
+            .get(&ty)
+            .cloned()
+            .ok_or(MissingData::AssociatedTy(ty))
+    }
+
+    fn try_trait_datum(&self, id: TraitId) -> Result<Arc<TraitDatum>, MissingData> {
+        self.trait_data
+            .get(&id)
+            .cloned()
+            .ok_or(MissingData::Trait(id))
+    }
+
+    fn try_struct_datum(&self, id: StructId) -> Result<Arc<StructDatum>, MissingData> {
+        self.struct_data
+            .get(&id)
+            .cloned()
+            .ok_or(MissingData::Struct(id))
+    }
+
+    fn try_impl_datum(&self, id: ImplId) -> Result<Arc<ImplDatum>, MissingData> {
+        self.impl_data
+            .get(&id)
+            .cloned()
+            .ok_or(MissingData::Impl(id))
+    }
+
+    fn try_associated_ty_value(
+        &self,
+        id: AssociatedTyValueId,
+    ) -> Result<Arc<AssociatedTyValue>, MissingData> {
+        self.associated_ty_values
+            .get(&id)
+            .cloned()
+            .ok_or(MissingData::AssociatedTyValue(id))
+    }
+
     fn impls_for_trait(&self, trait_id: TraitId, parameters: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
         self.impl_data
             .iter()
@@ -146,6 +204,10 @@ impl RustIrDatabase for Program {
             .collect()
     }
 
+    fn local_inherent_impls(&self) -> Vec<Arc<InherentImplDatum>> {
+        self.inherent_impl_data.values().cloned().collect()
+    }
+
     fn impl_provided_for(&self, auto_trait_id: TraitId, struct_id: StructId) -> bool {
         // Look for an impl like `impl Send for Foo` where `Foo` is
         // the struct.  See `push_auto_trait_impls` for more.
@@ -164,6 +226,14 @@ impl RustIrDatabase for Program {
         })
     }
 
+    fn lang_item(&self, item: LangItem) -> Option<TypeKindId> {
+        self.lang_items.get(&item).cloned()
+    }
+
+    fn well_known_items(&self) -> Arc<WellKnownItems> {
+        self.well_known_items.clone()
+    }
+
     fn type_name(&self, id: TypeKindId) -> Identifier {
         match self.type_kinds.get(&id) {
             Some(v) => v.name,
@@ -10,7 +10,8 @@ use chalk_ir::TraitId;
 use chalk_solve::clauses::builder::ClauseBuilder;
 use chalk_solve::clauses::program_clauses::ToProgramClauses;
 use chalk_solve::coherence::orphan;
-use chalk_solve::coherence::{CoherenceSolver, SpecializationPriorities};
+use chalk_solve::coherence::{CoherenceSolver, InherentImplChecker, SpecializationPriorities};
+use chalk_solve::conformance::ConformanceSolver;
 use chalk_solve::wf;
 use chalk_solve::RustIrDatabase;
 use chalk_solve::Solver;
@@ -24,18 +25,26 @@ pub trait LoweringDatabase: RustIrDatabase {
     #[salsa::input]
     fn program_text(&self) -> Arc<String>;
 
+    /// Items shared by many programs, such as the lang items of a test
+    /// suite; they are lowered before (and are visible to) the items
+    /// of `program_text`.
+    #[salsa::input]
+    fn program_prelude(&self) -> Arc<String>;
+
     #[salsa::input]
     fn solver_choice(&self) -> SolverChoice;
 
     fn program_ir(&self) -> Result<Arc<Program>, ChalkError>;
 
-    /// Performs coherence check and computes which impls specialize
-    /// one another (the "specialization priorities").
+    /// Performs coherence check (of both trait and inherent impls) and
+    /// computes which impls specialize one another (the
+    /// "specialization priorities").
     fn coherence(&self) -> Result<BTreeMap<TraitId, Arc<SpecializationPriorities>>, ChalkError>;
 
     fn orphan_check(&self) -> Result<(), ChalkError>;
 
-    /// The lowered IR, with coherence, orphan, and WF checks performed.
+    /// The lowered IR, with coherence, orphan, conformance, and WF
+    /// checks performed.
     fn checked_program(&self) -> Result<Arc<Program>, ChalkError>;
 
     /// The program as logic.
@@ -52,8 +61,9 @@ pub trait LoweringDatabase: RustIrDatabase {
 }
 
 fn program_ir(db: &impl LoweringDatabase) -> Result<Arc<Program>, ChalkError> {
-    let text = db.program_text();
-    Ok(Arc::new(chalk_parse::parse_program(&text)?.lower()?))
+    let prelude = chalk_parse::parse_program(&db.program_prelude())?;
+    let program = chalk_parse::parse_program(&db.program_text())?;
+    Ok(Arc::new([prelude, program].lower()?))
 }
 
 fn orphan_check(db: &impl LoweringDatabase) -> Result<(), ChalkError> {
@@ -84,6 +94,8 @@ fn coherence(
         .collect();
     let priorities_map = priorities_map?;
 
+    InherentImplChecker::new(db, db.solver_choice()).check()?;
+
     let () = db.orphan_check()?;
 
     Ok(priorities_map)
@@ -95,12 +107,22 @@ fn checked_program(db: &impl LoweringDatabase) -> Result<Arc<Program>, ChalkErro
     db.coherence()?;
 
     let () = tls::set_current_program(&program, || -> Result<(), ChalkError> {
+        let conformance = ConformanceSolver::new(db, db.solver_choice());
+
+        for &impl_id in program.impl_data.keys() {
+            conformance.verify_impl(impl_id)?;
+        }
+
         let solver = wf::WfSolver::new(db, db.solver_choice());
 
         for &id in program.struct_data.keys() {
             solver.verify_struct_decl(id)?;
         }
 
+        for &trait_id in program.trait_data.keys() {
+            solver.verify_auto_trait(trait_id)?;
+        }
+
         for &impl_id in program.impl_data.keys() {
             solver.verify_trait_impl(impl_id)?;
         }
@@ -122,20 +144,17 @@ fn environment(db: &impl LoweringDatabase) -> Result<Arc<ProgramEnvironment>, Ch
 
     let builder = &mut ClauseBuilder::new(db, &mut program_clauses);
 
-    program
-        .associated_ty_data
-        .values()
-        .for_each(|d| d.to_program_clauses(builder));
+    for datum in program.associated_ty_data.values() {
+        datum.to_program_clauses(builder)?;
+    }
 
-    program
-        .trait_data
-        .values()
-        .for_each(|d| d.to_program_clauses(builder));
+    for datum in program.trait_data.values() {
+        datum.to_program_clauses(builder)?;
+    }
 
-    program
-        .struct_data
-        .values()
-        .for_each(|d| d.to_program_clauses(builder));
+    for datum in program.struct_data.values() {
+        datum.to_program_clauses(builder)?;
+    }
 
     for (&auto_trait_id, _) in program
         .trait_data
@@ -143,7 +162,7 @@ fn environment(db: &impl LoweringDatabase) -> Result<Arc<ProgramEnvironment>, Ch
         .filter(|(_, auto_trait)| auto_trait.is_auto_trait())
     {
         for &struct_id in program.struct_data.keys() {
-            chalk_solve::clauses::push_auto_trait_impls(builder, auto_trait_id, struct_id);
+            chalk_solve::clauses::push_auto_trait_impls(builder, auto_trait_id, struct_id)?;
         }
     }
 
@@ -151,12 +170,11 @@ fn environment(db: &impl LoweringDatabase) -> Result<Arc<ProgramEnvironment>, Ch
         // If we encounter a negative impl, do not generate any rule. Negative impls
         // are currently just there to deactivate default impls for auto traits.
         if datum.is_positive() {
-            datum.to_program_clauses(builder);
-            datum
-                .associated_ty_value_ids
-                .iter()
-                .map(|&atv_id| db.associated_ty_value(atv_id))
-                .for_each(|atv| atv.to_program_clauses(builder));
+            datum.to_program_clauses(builder)?;
+            for &atv_id in &datum.associated_ty_value_ids {
+                db.try_associated_ty_value(atv_id)?
+                    .to_program_clauses(builder)?;
+            }
         }
     }
 
@@ -0,0 +1,61 @@
+//! Hash-consing of environments.
+//!
+//! The clauses of an environment are cloned into every goal solved in
+//! it. Interning environments through an `EnvironmentArena` ensures
+//! that structurally equal environments share a single allocation, so
+//! that comparing them usually stops at pointer equality, as `Arc`'s
+//! `PartialEq` compares the pointers before the contents.
+
+use crate::family::TypeFamily;
+use crate::{Environment, ProgramClause};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// A set of interned environments; see the module docs.
+#[derive(Debug)]
+pub struct EnvironmentArena<TF: TypeFamily> {
+    environments: HashSet<Arc<Vec<ProgramClause<TF>>>>,
+}
+
+impl<TF: TypeFamily> EnvironmentArena<TF> {
+    pub fn new() -> Self {
+        EnvironmentArena {
+            environments: HashSet::new(),
+        }
+    }
+
+    /// Returns the number of distinct environments interned so far.
+    pub fn len(&self) -> usize {
+        self.environments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.environments.is_empty()
+    }
+
+    /// Forgets the environments interned so far. The environments
+    /// handed out stay valid, but environments interned from now on no
+    /// longer share with them.
+    pub fn clear(&mut self) {
+        self.environments.clear();
+    }
+
+    /// Interns `environment`, returning a copy whose clauses are
+    /// shared with those of every equal environment interned before
+    /// (see `Environment::ptr_eq`).
+    pub fn intern(&mut self, environment: Environment<TF>) -> Environment<TF> {
+        if let Some(clauses) = self.environments.get(&environment.clauses) {
+            return Environment {
+                clauses: clauses.clone(),
+            };
+        }
+        self.environments.insert(environment.clauses.clone());
+        environment
+    }
+}
+
+impl<TF: TypeFamily> Default for EnvironmentArena<TF> {
+    fn default() -> Self {
+        EnvironmentArena::new()
+    }
+}
@@ -1,5 +1,6 @@
 use crate::*;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 /// The `Cast` trait is used to make annoying upcasts between
 /// logically equivalent types that imply wrappers. For example, one
@@ -125,6 +126,18 @@ impl<TF: TypeFamily> CastTo<DomainGoal<TF>> for Normalize<TF> {
     }
 }
 
+impl<TF: TypeFamily> CastTo<DomainGoal<TF>> for LayoutCompatible<TF> {
+    fn cast_to(self) -> DomainGoal<TF> {
+        DomainGoal::LayoutCompatible(self)
+    }
+}
+
+impl<TF: TypeFamily> CastTo<DomainGoal<TF>> for Upcast<TF> {
+    fn cast_to(self) -> DomainGoal<TF> {
+        DomainGoal::Upcast(self)
+    }
+}
+
 impl<TF: TypeFamily> CastTo<DomainGoal<TF>> for WellFormed<TF> {
     fn cast_to(self) -> DomainGoal<TF> {
         DomainGoal::WellFormed(self)
@@ -143,6 +156,12 @@ impl<TF: TypeFamily> CastTo<LeafGoal<TF>> for EqGoal<TF> {
     }
 }
 
+impl<TF: TypeFamily> CastTo<LeafGoal<TF>> for TypeOutlives<TF> {
+    fn cast_to(self) -> LeafGoal<TF> {
+        LeafGoal::TypeOutlives(self)
+    }
+}
+
 impl<T: CastTo<Goal<TF>>, TF: TypeFamily> CastTo<Goal<TF>> for Binders<T> {
     fn cast_to(self) -> Goal<TF> {
         if self.binders.is_empty() {
@@ -150,7 +169,7 @@ impl<T: CastTo<Goal<TF>>, TF: TypeFamily> CastTo<Goal<TF>> for Binders<T> {
         } else {
             Goal::Quantified(
                 QuantifierKind::ForAll,
-                self.map(|bound| Box::new(bound.cast())),
+                self.map(|bound| Arc::new(bound.cast())),
             )
         }
     }
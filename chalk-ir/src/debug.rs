@@ -65,6 +65,10 @@ impl Debug for TypeName {
             TypeName::TypeKindId(id) => write!(fmt, "{:?}", id),
             TypeName::Placeholder(index) => write!(fmt, "{:?}", index),
             TypeName::AssociatedType(assoc_ty) => write!(fmt, "{:?}", assoc_ty),
+            TypeName::Tuple(arity) => write!(fmt, "Tuple{}", arity),
+            TypeName::FnPtr => write!(fmt, "fn"),
+            TypeName::OpaqueConst(name) => write!(fmt, "const {}", name),
+            TypeName::Builtin(builtin) => write!(fmt, "{:?}", builtin),
             TypeName::Error => write!(fmt, "{{error}}"),
         }
     }
@@ -109,6 +113,20 @@ impl<TF: TypeFamily> Debug for Lifetime<TF> {
     }
 }
 
+impl Debug for BuiltinType {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        match self {
+            BuiltinType::Str => write!(fmt, "str"),
+            BuiltinType::Slice => write!(fmt, "slice"),
+            BuiltinType::Array => write!(fmt, "array"),
+            BuiltinType::Ref(Mutability::Not) => write!(fmt, "ref"),
+            BuiltinType::Ref(Mutability::Mut) => write!(fmt, "ref_mut"),
+            BuiltinType::Ptr(Mutability::Not) => write!(fmt, "ptr_const"),
+            BuiltinType::Ptr(Mutability::Mut) => write!(fmt, "ptr_mut"),
+        }
+    }
+}
+
 impl Debug for PlaceholderIndex {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         let PlaceholderIndex { ui, idx } = self;
@@ -118,7 +136,60 @@ impl Debug for PlaceholderIndex {
 
 impl<TF: TypeFamily> Debug for ApplicationTy<TF> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
-        write!(fmt, "{:?}{:?}", self.name, Angle(&self.parameters))
+        match self.name {
+            TypeName::Tuple(_) => {
+                write!(fmt, "(")?;
+                for (index, parameter) in self.parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(fmt, ", ")?;
+                    }
+                    write!(fmt, "{:?}", parameter)?;
+                }
+                if self.parameters.len() == 1 {
+                    write!(fmt, ",")?;
+                }
+                write!(fmt, ")")
+            }
+            TypeName::FnPtr => {
+                // `fn(A, B) -> C`, if the arguments are known to be a tuple
+                if let [args, output] = self.parameters.as_slice() {
+                    if let ParameterKind::Ty(args) = args.as_ref() {
+                        if let Ty::Apply(ApplicationTy {
+                            name: TypeName::Tuple(_),
+                            parameters,
+                        }) = args.lookup_ref()
+                        {
+                            write!(fmt, "fn(")?;
+                            for (index, parameter) in parameters.iter().enumerate() {
+                                if index > 0 {
+                                    write!(fmt, ", ")?;
+                                }
+                                write!(fmt, "{:?}", parameter)?;
+                            }
+                            return write!(fmt, ") -> {:?}", output);
+                        }
+                    }
+                }
+                write!(fmt, "{:?}{:?}", self.name, Angle(&self.parameters))
+            }
+            TypeName::Builtin(builtin) => match (builtin, self.parameters.as_slice()) {
+                (BuiltinType::Str, []) => write!(fmt, "str"),
+                (BuiltinType::Slice, [element]) => write!(fmt, "[{:?}]", element),
+                (BuiltinType::Array, [element, len]) => write!(fmt, "[{:?}; {:?}]", element, len),
+                (BuiltinType::Ref(Mutability::Not), [referent, lifetime]) => {
+                    write!(fmt, "&{:?} {:?}", lifetime, referent)
+                }
+                (BuiltinType::Ref(Mutability::Mut), [referent, lifetime]) => {
+                    write!(fmt, "&{:?} mut {:?}", lifetime, referent)
+                }
+                (BuiltinType::Ptr(Mutability::Not), [pointee]) => {
+                    write!(fmt, "*const {:?}", pointee)
+                }
+                (BuiltinType::Ptr(Mutability::Mut), [pointee]) => write!(fmt, "*mut {:?}", pointee),
+                _ => write!(fmt, "{:?}{:?}", self.name, Angle(&self.parameters)),
+            },
+            _ => write!(fmt, "{:?}{:?}", self.name, Angle(&self.parameters)),
+        }
     }
 }
 
@@ -189,6 +260,18 @@ impl<'a, T: Debug> Debug for Angle<'a, T> {
     }
 }
 
+impl<TF: TypeFamily> Debug for LayoutCompatible<TF> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(fmt, "LayoutCompatible({:?}, {:?})", self.a, self.b)
+    }
+}
+
+impl<TF: TypeFamily> Debug for Upcast<TF> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(fmt, "Upcast({:?} => {:?})", self.source, self.target)
+    }
+}
+
 impl<TF: TypeFamily> Debug for Normalize<TF> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         write!(fmt, "Normalize({:?} -> {:?})", self.projection, self.ty)
@@ -247,6 +330,9 @@ impl<TF: TypeFamily> Debug for DomainGoal<TF> {
             ),
             DomainGoal::Compatible(_) => write!(fmt, "Compatible"),
             DomainGoal::DownstreamType(n) => write!(fmt, "DownstreamType({:?})", n),
+            DomainGoal::Custom(n) => write!(fmt, "{:?}", n),
+            DomainGoal::LayoutCompatible(n) => write!(fmt, "{:?}", n),
+            DomainGoal::Upcast(n) => write!(fmt, "{:?}", n),
         }
     }
 }
@@ -256,16 +342,29 @@ impl<TF: TypeFamily> Debug for LeafGoal<TF> {
         match *self {
             LeafGoal::EqGoal(ref eq) => write!(fmt, "{:?}", eq),
             LeafGoal::DomainGoal(ref dom) => write!(fmt, "{:?}", dom),
+            LeafGoal::TypeOutlives(ref outlives) => write!(fmt, "{:?}", outlives),
         }
     }
 }
 
+impl<TF: TypeFamily> Debug for CustomGoal<TF> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "{}{:?}", self.name, Angle(&self.parameters))
+    }
+}
+
 impl<TF: TypeFamily> Debug for EqGoal<TF> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         write!(fmt, "({:?} = {:?})", self.a, self.b)
     }
 }
 
+impl<TF: TypeFamily> Debug for TypeOutlives<TF> {
+    fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
+        write!(fmt, "({:?}: {:?})", self.ty, self.lifetime)
+    }
+}
+
 impl<TF: TypeFamily> Debug for Goal<TF> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match *self {
@@ -391,6 +490,7 @@ impl<TF: TypeFamily> Debug for Constraint<TF> {
     fn fmt(&self, fmt: &mut Formatter) -> Result<(), Error> {
         match self {
             Constraint::LifetimeEq(a, b) => write!(fmt, "{:?} == {:?}", a, b),
+            Constraint::TypeOutlives(ty, lifetime) => write!(fmt, "{:?}: {:?}", ty, lifetime),
         }
     }
 }
@@ -1,8 +1,10 @@
 use crate::cast::CastTo;
 use crate::debug::Angle;
 use crate::fold::{Fold, Folder, ReflexiveFold};
+use crate::stable_hash::StableHash;
 use crate::tls;
 use crate::zip::Zip;
+use crate::CustomGoal;
 use crate::Lifetime;
 use crate::Parameter;
 use crate::ParameterKind;
@@ -28,6 +30,10 @@ use std::marker::PhantomData;
 /// (e.g., `SourceTF` and `TargetTF`) -- even if those type parameters
 /// wind up being mapped to the same underlying type families in the
 /// end.
+///
+/// The `StableHash` impls of the interned representations must hash
+/// their contents, not their identity, so that the hashes of goals
+/// are the same in every process (see the `stable_hash` module).
 pub trait TypeFamily: Debug + Copy + Eq + Ord + Hash {
     /// "Interned" representation of types. You can use the `Lookup`
     /// trait to convert this to a `Ty<Self>`.
@@ -39,7 +45,8 @@ pub trait TypeFamily: Debug + Copy + Eq + Ord + Hash {
         + ReflexiveFold<Self>
         + Zip<Self>
         + Lookup<Ty<Self>>
-        + CastTo<Parameter<Self>>;
+        + CastTo<Parameter<Self>>
+        + StableHash;
 
     /// "Interned" representation of lifetimes. You can use the
     /// `Lookup` trait to convert this to a `Lifetime<Self>`.
@@ -51,7 +58,13 @@ pub trait TypeFamily: Debug + Copy + Eq + Ord + Hash {
         + ReflexiveFold<Self>
         + Zip<Self>
         + Lookup<Lifetime<Self>>
-        + CastTo<Parameter<Self>>;
+        + CastTo<Parameter<Self>>
+        + StableHash;
+
+    /// Goals whose meaning is defined by the embedder rather than by
+    /// chalk (see `DomainGoal::Custom`). Chalk never inspects these
+    /// itself; it only folds and unifies them.
+    type CustomGoal: Debug + Clone + Eq + Ord + Hash + ReflexiveFold<Self> + Zip<Self> + StableHash;
 
     /// Prints the debug representation of a projection. To get good
     /// results, this requires inspecting TLS, and is difficult to
@@ -118,6 +131,7 @@ pub struct ChalkIr {}
 impl TypeFamily for ChalkIr {
     type Type = Ty<ChalkIr>;
     type Lifetime = Lifetime<ChalkIr>;
+    type CustomGoal = CustomGoal<ChalkIr>;
 
     fn debug_projection(
         projection: &ProjectionTy<ChalkIr>,
@@ -382,7 +382,13 @@ impl<TF: TypeFamily> Fold<TF> for ApplicationTy<TF> {
                 folder.fold_free_placeholder_ty(ui, binders)
             }
 
-            TypeName::TypeKindId(_) | TypeName::AssociatedType(_) | TypeName::Error => {
+            TypeName::TypeKindId(_)
+            | TypeName::AssociatedType(_)
+            | TypeName::Tuple(_)
+            | TypeName::FnPtr
+            | TypeName::OpaqueConst(_)
+            | TypeName::Builtin(_)
+            | TypeName::Error => {
                 let parameters = parameters.fold_with(folder, binders)?;
                 Ok(ApplicationTy { name, parameters }.cast().intern())
             }
@@ -390,6 +396,37 @@ impl<TF: TypeFamily> Fold<TF> for ApplicationTy<TF> {
     }
 }
 
+/// Folding an environment keeps its clauses shared if the folder
+/// leaves them unchanged, as it does for most environments (those
+/// without inference variables). New clauses are only allocated from
+/// the first clause the folder changes, and only the clauses before
+/// it are compared.
+impl<TF: TypeFamily> Fold<TF> for Environment<TF> {
+    type Result = Self;
+    fn fold_with(&self, folder: &mut dyn Folder<TF>, binders: usize) -> Fallible<Self::Result> {
+        let mut folded: Option<Vec<ProgramClause<TF>>> = None;
+        for (index, clause) in self.clauses.iter().enumerate() {
+            let clause_folded = clause.fold_with(folder, binders)?;
+            match &mut folded {
+                Some(clauses) => clauses.push(clause_folded),
+                None if clause_folded != *clause => {
+                    let mut clauses = Vec::with_capacity(self.clauses.len());
+                    clauses.extend(self.clauses[..index].iter().cloned());
+                    clauses.push(clause_folded);
+                    folded = Some(clauses);
+                }
+                None => {}
+            }
+        }
+        match folded {
+            Some(clauses) => Ok(Environment {
+                clauses: Arc::new(clauses),
+            }),
+            None => Ok(self.clone()),
+        }
+    }
+}
+
 impl<TF: TypeFamily> Fold<TF> for QuantifiedTy<TF> {
     type Result = Self;
     fn fold_with(&self, folder: &mut dyn Folder<TF>, binders: usize) -> Fallible<Self::Result> {
@@ -417,7 +454,7 @@ where
         } = *self;
         let value = self_value.fold_with(folder, binders + self_binders.len())?;
         Ok(Binders {
-            binders: self_binders.clone(),
+            binders: *self_binders,
             value: value,
         })
     }
@@ -541,6 +578,7 @@ where
         let ExClause {
             subst,
             ambiguous,
+            caveat,
             constraints,
             subgoals,
             current_time,
@@ -549,6 +587,7 @@ where
         Ok(ExClause {
             subst: subst.fold_with(folder, binders)?,
             ambiguous: *ambiguous,
+            caveat: *caveat,
             constraints: constraints.fold_with(folder, binders)?,
             subgoals: subgoals.fold_with(folder, binders)?,
             current_time: current_time.fold_with(folder, binders)?,
@@ -0,0 +1,115 @@
+//! Identifiers: the names of items, such as the `ConstEvaluatable` of a
+//! custom goal. Identifiers are interned in a string table shared by
+//! all threads, so they are compared and hashed as integers, and can
+//! be created on one thread and printed on another. Strings are never
+//! removed from the table.
+
+use lazy_static::lazy_static;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::RwLock;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    index: u32,
+}
+
+#[derive(Default)]
+struct StringTable {
+    indices: HashMap<&'static str, Identifier>,
+    strings: Vec<&'static str>,
+}
+
+lazy_static! {
+    static ref STRING_TABLE: RwLock<StringTable> = RwLock::new(StringTable::default());
+}
+
+/// Returns the identifier for `name`; shorthand for
+/// `Identifier::intern`.
+pub fn intern(name: &str) -> Identifier {
+    Identifier::intern(name)
+}
+
+impl Identifier {
+    /// Returns the identifier for `name`, adding `name` to the string
+    /// table if it is not there yet.
+    pub fn intern(name: &str) -> Identifier {
+        if let Some(&identifier) = STRING_TABLE.read().unwrap().indices.get(name) {
+            return identifier;
+        }
+
+        let mut table = STRING_TABLE.write().unwrap();
+        if let Some(&identifier) = table.indices.get(name) {
+            return identifier;
+        }
+        let identifier = Identifier {
+            index: table.strings.len() as u32,
+        };
+        let name: &'static str = Box::leak(name.to_owned().into_boxed_str());
+        table.strings.push(name);
+        table.indices.insert(name, identifier);
+        identifier
+    }
+
+    /// The string that this identifier was interned from.
+    pub fn as_str(self) -> &'static str {
+        STRING_TABLE.read().unwrap().strings[self.index as usize]
+    }
+}
+
+impl fmt::Debug for Identifier {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), fmt)
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), fmt)
+    }
+}
+
+/// Identifiers are ordered by their strings, so that orderings (e.g.,
+/// of the keys of a `BTreeMap`) do not depend on the order in which
+/// identifiers were interned.
+impl PartialOrd for Identifier {
+    fn partial_cmp(&self, other: &Identifier) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Identifier {
+    fn cmp(&self, other: &Identifier) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+/// Archived as its string rather than its index, which is only
+/// meaningful within the process that interned it.
+#[cfg(feature = "persist")]
+impl rkyv::Archive for Identifier {
+    type Archived = rkyv::string::ArchivedString;
+    type Resolver = rkyv::string::StringResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        rkyv::string::ArchivedString::resolve_from_str(self.as_str(), pos, resolver, out);
+    }
+}
+
+#[cfg(feature = "persist")]
+impl<S: rkyv::ser::Serializer + ?Sized> rkyv::Serialize<S> for Identifier {
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        rkyv::string::ArchivedString::serialize_from_str(self.as_str(), serializer)
+    }
+}
+
+#[cfg(feature = "persist")]
+impl<D: rkyv::Fallible + ?Sized> rkyv::Deserialize<Identifier, D> for rkyv::string::ArchivedString {
+    fn deserialize(&self, _: &mut D) -> Result<Identifier, D::Error> {
+        Ok(Identifier::intern(self.as_str()))
+    }
+}
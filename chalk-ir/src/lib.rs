@@ -2,15 +2,21 @@ use crate::cast::Cast;
 use crate::family::Lookup;
 use crate::fold::shift::Shift;
 use crate::fold::{
-    DefaultInferenceFolder, DefaultPlaceholderFolder, DefaultTypeFolder, Fold, Folder,
-    FreeVarFolder, Subst,
+    DefaultFreeVarFolder, DefaultInferenceFolder, DefaultPlaceholderFolder, DefaultTypeFolder,
+    Fold, Folder, FreeVarFolder, PlaceholderFolder, Subst,
 };
 use chalk_derive::Fold;
 use chalk_engine::fallible::*;
-use lalrpop_intern::InternedString;
 use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
 use std::iter;
 use std::marker::PhantomData;
+use std::sync::Arc;
+
+#[cfg(feature = "persist")]
+use crate::persist::{PersistDeserializer, PersistFamily, PersistSerializer, PersistValidator};
+#[cfg(feature = "persist")]
+use rkyv::{Archive, Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Void {}
@@ -40,7 +46,6 @@ macro_rules! impl_debugs {
 }
 
 extern crate chalk_engine;
-extern crate lalrpop_intern;
 
 #[macro_use]
 mod macros;
@@ -51,6 +56,7 @@ pub mod zip;
 #[macro_use]
 pub mod fold;
 
+pub mod arena;
 pub mod cast;
 
 pub mod family;
@@ -58,15 +64,42 @@ use family::{HasTypeFamily, TypeFamily};
 
 pub mod could_match;
 pub mod debug;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod stable_hash;
 pub mod tls;
 
-pub type Identifier = InternedString;
+mod identifier;
+pub use identifier::{intern, Identifier};
 
-#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+mod parameter_kinds;
+pub use parameter_kinds::ParameterKinds;
+
+#[derive(Clone, Eq, PartialOrd, Ord)]
 /// The set of assumptions we've made so far, and the current number of
 /// universal (forall) quantifiers we're within.
+///
+/// The clauses are shared, as an environment is cloned into every
+/// goal solved in it; see also `arena::EnvironmentArena`.
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct Environment<TF: TypeFamily> {
-    pub clauses: Vec<ProgramClause<TF>>,
+    pub clauses: Arc<Vec<ProgramClause<TF>>>,
+}
+
+/// Environments that share their clauses are equal without looking at
+/// the clauses, so comparing interned environments (which the solver
+/// uses throughout) comes down to comparing pointers.
+impl<TF: TypeFamily> PartialEq for Environment<TF> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || self.clauses == other.clauses
+    }
+}
+
+impl<TF: TypeFamily> Hash for Environment<TF> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.clauses.hash(state);
+    }
 }
 
 impl<TF: TypeFamily> HasTypeFamily for Environment<TF> {
@@ -75,21 +108,33 @@ impl<TF: TypeFamily> HasTypeFamily for Environment<TF> {
 
 impl<TF: TypeFamily> Environment<TF> {
     pub fn new() -> Self {
-        Environment { clauses: vec![] }
+        Environment {
+            clauses: Arc::new(vec![]),
+        }
     }
 
     pub fn add_clauses<I>(&self, clauses: I) -> Self
     where
         I: IntoIterator<Item = ProgramClause<TF>>,
     {
-        let mut env = self.clone();
-        let env_clauses: BTreeSet<_> = env.clauses.into_iter().chain(clauses).collect();
-        env.clauses = env_clauses.into_iter().collect();
-        env
+        let env_clauses: BTreeSet<_> = self.clauses.iter().cloned().chain(clauses).collect();
+        Environment {
+            clauses: Arc::new(env_clauses.into_iter().collect()),
+        }
+    }
+
+    /// Returns true if `self` and `other` share their clauses, as the
+    /// environments of the goals derived from one goal do (and as
+    /// equal environments interned in an `EnvironmentArena` do). This
+    /// implies that they are equal.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.clauses, &other.clauses)
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct InEnvironment<G: HasTypeFamily> {
     pub environment: Environment<G::TypeFamily>,
     pub goal: G,
@@ -116,6 +161,8 @@ impl<G: HasTypeFamily> InEnvironment<G> {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum TypeName {
     /// a type like `Vec<T>`
     TypeKindId(TypeKindId),
@@ -128,11 +175,132 @@ pub enum TypeName {
     /// an associated type like `Iterator::Item`; see `AssociatedType` for details
     AssociatedType(TypeId),
 
+    /// a tuple type like `(A, B)`, with the given arity; the element
+    /// types are the parameters of the `ApplicationTy`
+    Tuple(usize),
+
+    /// a function pointer type like `fn(A, B) -> C`, whose two
+    /// parameters are the tuple of the argument types, `(A, B)`, and
+    /// the return type `C`. Since the arguments form a single
+    /// parameter, function pointers of every arity share this name
+    /// (and the clauses generated for it).
+    FnPtr,
+
+    /// an unevaluated constant expression, such as the `{ N * 2 }` in
+    /// `[T; { N * 2 }]`, applied to the generic parameters that it
+    /// mentions. The identifier names the expression (like a `DefId`
+    /// in rustc). Chalk does not evaluate constants: it only knows
+    /// that syntactically identical expressions, applied to the same
+    /// parameters, are equal, and treats any two other constant
+    /// expressions as possibly equal (see the unifier).
+    OpaqueConst(Identifier),
+
+    /// a type constructor built into the language, like `[T]`, which
+    /// the program does not declare (see `BuiltinType`)
+    Builtin(BuiltinType),
+
     /// This can be used to represent an error, e.g. during name resolution of a type.
     /// Chalk itself will not produce this, just pass it through when given.
     Error,
 }
 
+/// The type constructors built into the language. Their properties,
+/// from which the solver generates their clauses (as for tuples), are
+/// given by the methods below, and the element type, if any, is always
+/// the first parameter.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub enum BuiltinType {
+    /// `str`, with no parameters.
+    Str,
+
+    /// `[T]`, whose parameter is the element type.
+    Slice,
+
+    /// `[T; N]`, whose parameters are the element type and the length.
+    /// Like the length of an array in rustc, the length is a constant
+    /// (see `TypeName::OpaqueConst`), or a type parameter standing for
+    /// one.
+    Array,
+
+    /// `&'a T` or `&'a mut T`, whose parameters are the referent type
+    /// and the lifetime (in that order, so that the referent comes
+    /// first like other element types).
+    Ref(Mutability),
+
+    /// `*const T` or `*mut T`, whose parameter is the pointee type.
+    Ptr(Mutability),
+}
+
+/// Whether a reference or raw pointer type permits mutation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub enum Mutability {
+    Not,
+    Mut,
+}
+
+impl BuiltinType {
+    /// The kinds of the parameters of the type constructor.
+    pub fn parameter_kinds(self) -> Vec<ParameterKind<()>> {
+        match self {
+            BuiltinType::Str => vec![],
+            BuiltinType::Slice | BuiltinType::Ptr(_) => vec![ParameterKind::Ty(())],
+            BuiltinType::Array => vec![ParameterKind::Ty(()), ParameterKind::Ty(())],
+            BuiltinType::Ref(_) => vec![ParameterKind::Ty(()), ParameterKind::Lifetime(())],
+        }
+    }
+
+    /// The number of parameters of the type constructor.
+    pub fn arity(self) -> usize {
+        self.parameter_kinds().len()
+    }
+
+    /// Whether the type implements `Sized`; `str` and slices are
+    /// unsized, whatever their element type.
+    pub fn is_sized(self) -> bool {
+        match self {
+            BuiltinType::Str | BuiltinType::Slice => false,
+            BuiltinType::Array | BuiltinType::Ref(_) | BuiltinType::Ptr(_) => true,
+        }
+    }
+
+    /// Whether the type implements `Copy` and `Clone` whatever its
+    /// element type, as shared references and raw pointers do.
+    pub fn is_copy(self) -> bool {
+        match self {
+            BuiltinType::Ref(Mutability::Not) | BuiltinType::Ptr(_) => true,
+            BuiltinType::Str
+            | BuiltinType::Slice
+            | BuiltinType::Array
+            | BuiltinType::Ref(Mutability::Mut) => false,
+        }
+    }
+
+    /// Whether the type implements `Unpin` whatever its element type,
+    /// as references and raw pointers do: moving one of them does not
+    /// move what it points to.
+    pub fn is_unpin(self) -> bool {
+        match self {
+            BuiltinType::Ref(_) | BuiltinType::Ptr(_) => true,
+            BuiltinType::Str | BuiltinType::Slice | BuiltinType::Array => false,
+        }
+    }
+
+    /// Whether the type implements `Copy` and `Clone` when its element
+    /// type does, as arrays do.
+    pub fn is_copy_if_element_is(self) -> bool {
+        match self {
+            BuiltinType::Array => true,
+            BuiltinType::Str | BuiltinType::Slice | BuiltinType::Ref(_) | BuiltinType::Ptr(_) => {
+                false
+            }
+        }
+    }
+}
+
 /// An universe index is how a universally quantified parameter is
 /// represented when it's binder is moved into the environment.
 /// An example chain of transformations would be:
@@ -141,6 +309,8 @@ pub enum TypeName {
 /// `Goal(!U1)` (the quantifier was moved to the environment and replaced with a universe index)
 /// See https://rust-lang.github.io/rustc-guide/borrow_check/region_inference.html#placeholders-and-universes for more.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct UniverseIndex {
     pub counter: usize,
 }
@@ -164,23 +334,35 @@ impl UniverseIndex {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct StructId(pub RawId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct TraitId(pub RawId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ImplId(pub RawId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ClauseId(pub RawId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct TypeId(pub RawId);
 
 impl_debugs!(ImplId, ClauseId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum TypeKindId {
     TypeId(TypeId),
     TraitId(TraitId),
@@ -201,6 +383,8 @@ impl_froms!(TypeKindId: TypeId, TraitId, StructId);
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[allow(non_camel_case_types)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct RawId {
     pub index: u32,
 }
@@ -212,6 +396,8 @@ pub enum TypeSort {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum Ty<TF: TypeFamily> {
     /// An "application" type is one that applies the set of type
     /// arguments to some base type. For example, `Vec<u32>` would be
@@ -327,6 +513,8 @@ impl<TF: TypeFamily> Ty<TF> {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct InferenceVar {
     index: u32,
 }
@@ -354,8 +542,22 @@ impl InferenceVar {
 /// for<'a...'z> X -- all binders are instantiated at once,
 /// and we use deBruijn indices within `self.ty`
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "persist",
+    derive(Archive, Serialize, Deserialize),
+    archive(
+        check_bytes,
+        bound(
+            archive = "TF: PersistFamily",
+            serialize = "TF: PersistFamily, __S: PersistSerializer",
+            deserialize = "TF: PersistFamily, __D: PersistDeserializer"
+        )
+    ),
+    archive_attr(check_bytes(bound = "__C: PersistValidator"))
+)]
 pub struct QuantifiedTy<TF: TypeFamily> {
     pub num_binders: usize,
+    #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))]
     pub ty: TF::Type,
 }
 
@@ -364,6 +566,8 @@ impl<TF: TypeFamily> HasTypeFamily for QuantifiedTy<TF> {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum Lifetime<TF: TypeFamily> {
     /// See Ty::Var(_).
     BoundVar(usize),
@@ -402,6 +606,8 @@ impl<TF: TypeFamily> Lifetime<TF> {
 /// Two indexes are required, the one of the universe itself
 /// and the relative index inside the universe.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct PlaceholderIndex {
     /// Index *of* the universe.
     pub ui: UniverseIndex,
@@ -425,6 +631,8 @@ impl PlaceholderIndex {
 
 // Fold derive intentionally omitted, folded through Ty
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ApplicationTy<TF: TypeFamily> {
     pub name: TypeName,
     pub parameters: Vec<Parameter<TF>>,
@@ -449,6 +657,8 @@ impl<TF: TypeFamily> ApplicationTy<TF> {
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum ParameterKind<T, L = T> {
     Ty(T),
     Lifetime(L),
@@ -512,7 +722,23 @@ impl<T, L> ParameterKind<T, L> {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub struct Parameter<TF: TypeFamily>(pub ParameterKind<TF::Type, TF::Lifetime>);
+#[cfg_attr(
+    feature = "persist",
+    derive(Archive, Serialize, Deserialize),
+    archive(
+        check_bytes,
+        bound(
+            archive = "TF: PersistFamily",
+            serialize = "TF: PersistFamily, __S: PersistSerializer",
+            deserialize = "TF: PersistFamily, __D: PersistDeserializer"
+        )
+    ),
+    archive_attr(check_bytes(bound = "__C: PersistValidator"))
+)]
+pub struct Parameter<TF: TypeFamily>(
+    #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))]
+    pub  ParameterKind<TF::Type, TF::Lifetime>,
+);
 
 impl<TF: TypeFamily> HasTypeFamily for Parameter<TF> {
     type TypeFamily = TF;
@@ -557,6 +783,8 @@ impl<TF: TypeFamily> Parameter<TF> {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ProjectionTy<TF: TypeFamily> {
     pub associated_ty_id: TypeId,
     pub parameters: Vec<Parameter<TF>>,
@@ -567,6 +795,8 @@ impl<TF: TypeFamily> HasTypeFamily for ProjectionTy<TF> {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct TraitRef<TF: TypeFamily> {
     pub trait_id: TraitId,
     pub parameters: Vec<Parameter<TF>>,
@@ -596,12 +826,16 @@ impl<TF: TypeFamily> TraitRef<TF> {
 
 /// Where clauses that can be written by a Rust programmer.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum WhereClause<TF: TypeFamily> {
     Implemented(TraitRef<TF>),
     ProjectionEq(ProjectionEq<TF>),
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum WellFormed<TF: TypeFamily> {
     /// A predicate which is true is some trait ref is well-formed.
     /// For example, given the following trait definitions:
@@ -636,6 +870,8 @@ impl<TF: TypeFamily> HasTypeFamily for WellFormed<TF> {
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum FromEnv<TF: TypeFamily> {
     /// A predicate which enables deriving everything which should be true if we *know* that
     /// some trait ref is well-formed. For example given the above trait definitions, we can use
@@ -672,6 +908,8 @@ impl<TF: TypeFamily> HasTypeFamily for FromEnv<TF> {
 /// logical statement. As much as possible, the Chalk solver should avoid
 /// decomposing this enum, and instead treat its values opaquely.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum DomainGoal<TF: TypeFamily> {
     Holds(WhereClause<TF>),
 
@@ -732,6 +970,20 @@ pub enum DomainGoal<TF: TypeFamily> {
     ///
     /// This makes a new type `T` available and makes `DownstreamType(T)` provable for that type.
     DownstreamType(TF::Type),
+
+    /// A goal that chalk does not model itself (e.g., rustc's
+    /// `ConstEvaluatable`). The embedder decides whether it holds; see
+    /// `RustIrDatabase::solve_custom_goal` in `chalk-solve`.
+    Custom(TF::CustomGoal),
+
+    /// Experimental: true if values of the two types have the same
+    /// layout, so that one can be transmuted into the other. See
+    /// `LayoutCompatible`.
+    LayoutCompatible(LayoutCompatible<TF>),
+
+    /// True if a value of the `dyn` type `source` may be coerced to
+    /// the `dyn` type `target` by trait upcasting. See `Upcast`.
+    Upcast(Upcast<TF>),
 }
 
 pub type QuantifiedWhereClause<TF> = Binders<WhereClause<TF>>;
@@ -798,22 +1050,78 @@ impl<TF: TypeFamily> DomainGoal<TF> {
 /// A goal that does not involve any logical connectives. Equality is treated
 /// specially by the logic (as with most first-order logics), since it interacts
 /// with unification etc.
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum LeafGoal<TF: TypeFamily> {
     EqGoal(EqGoal<TF>),
     DomainGoal(DomainGoal<TF>),
+    TypeOutlives(TypeOutlives<TF>),
 }
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct EqGoal<TF: TypeFamily> {
     pub a: Parameter<TF>,
     pub b: Parameter<TF>,
 }
 
+/// `T: 'a`, that every lifetime in the type `T` outlives `'a`. Like
+/// lifetime equality, this is not checked by the solver, which instead
+/// records it as a region constraint (`Constraint::TypeOutlives`) for
+/// the embedder to check.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub struct TypeOutlives<TF: TypeFamily> {
+    pub ty: TF::Type,
+    pub lifetime: TF::Lifetime,
+}
+
+/// The representation of embedder-defined goals in the `ChalkIr`
+/// family: an uninterpreted predicate `name` applied to some
+/// parameters, e.g. `ConstEvaluatable<T>`. Two custom goals unify if
+/// their names are equal and their parameters unify.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub struct CustomGoal<TF: TypeFamily> {
+    pub name: Identifier,
+    pub parameters: Vec<Parameter<TF>>,
+}
+
+/// Experimental (for prototyping safe transmute): proves that `a` and
+/// `b` have compatible layouts. The solver only generates clauses for
+/// these goals when the `layout` feature of `chalk-solve` is enabled.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub struct LayoutCompatible<TF: TypeFamily> {
+    pub a: TF::Type,
+    pub b: TF::Type,
+}
+
+/// Proves that `dyn A` may be upcast to `dyn B`, written `Upcast(dyn A
+/// => dyn B)`: that is, that every bound of `dyn B` is implied by the
+/// bounds of `dyn A`. The traits of `B` must be `A` or its supertraits,
+/// auto traits must be preserved (`dyn A + Send` may become `dyn B +
+/// Send`, but `dyn A` may not), and the parameters of the traits,
+/// including lifetimes, must agree.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
+pub struct Upcast<TF: TypeFamily> {
+    pub source: TF::Type,
+    pub target: TF::Type,
+}
+
 /// Proves that the given projection **normalizes** to the given
 /// type. A projection `T::Foo` normalizes to the type `U` if we can
 /// **match it to an impl** and that impl has a `type Foo = V` where
 /// `U = V`.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct Normalize<TF: TypeFamily> {
     pub projection: ProjectionTy<TF>,
     pub ty: TF::Type,
@@ -823,8 +1131,22 @@ pub struct Normalize<TF: TypeFamily> {
 /// `U`. Equality can be proven via normalization, but we can also
 /// prove that `T::Foo = V::Foo` if `T = V` without normalizing.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(
+    feature = "persist",
+    derive(Archive, Serialize, Deserialize),
+    archive(
+        check_bytes,
+        bound(
+            archive = "TF: PersistFamily",
+            serialize = "TF: PersistFamily, __S: PersistSerializer",
+            deserialize = "TF: PersistFamily, __D: PersistDeserializer"
+        )
+    ),
+    archive_attr(check_bytes(bound = "__C: PersistValidator"))
+)]
 pub struct ProjectionEq<TF: TypeFamily> {
     pub projection: ProjectionTy<TF>,
+    #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))]
     pub ty: TF::Type,
 }
 
@@ -836,8 +1158,10 @@ pub struct ProjectionEq<TF: TypeFamily> {
 /// (IOW, we use deBruijn indices, where binders are introduced in reverse order
 /// of `self.binders`.)
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct Binders<T> {
-    pub binders: Vec<ParameterKind<()>>,
+    pub binders: ParameterKinds,
     pub value: T,
 }
 
@@ -863,7 +1187,7 @@ impl<T> Binders<T> {
     {
         let value = op(&self.value);
         Binders {
-            binders: self.binders.clone(),
+            binders: self.binders,
             value,
         }
     }
@@ -941,14 +1265,14 @@ impl<V: IntoIterator> IntoIterator for Binders<V> {
 
 pub struct BindersIntoIterator<V: IntoIterator> {
     iter: <V as IntoIterator>::IntoIter,
-    binders: Vec<ParameterKind<()>>,
+    binders: ParameterKinds,
 }
 
 impl<V: IntoIterator> Iterator for BindersIntoIterator<V> {
     type Item = Binders<<V as IntoIterator>::Item>;
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|v| Binders {
-            binders: self.binders.clone(),
+            binders: self.binders,
             value: v,
         })
     }
@@ -958,12 +1282,16 @@ impl<V: IntoIterator> Iterator for BindersIntoIterator<V> {
 /// `conditions = cond_1 && cond_2 && ...` is the conjunction of the individual
 /// conditions.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ProgramClauseImplication<TF: TypeFamily> {
     pub consequence: DomainGoal<TF>,
     pub conditions: Vec<Goal<TF>>,
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum ProgramClause<TF: TypeFamily> {
     Implies(ProgramClauseImplication<TF>),
     ForAll(Binders<ProgramClauseImplication<TF>>),
@@ -993,11 +1321,98 @@ impl<TF: TypeFamily> ProgramClause<TF> {
 /// first appearance; the kind/universe of the variable is recorded in the
 /// `binders` field.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct Canonical<T> {
     pub value: T,
     pub binders: Vec<ParameterKind<UniverseIndex>>,
 }
 
+impl<T> Canonical<T> {
+    /// The universe of each canonical variable, in order.
+    pub fn binder_universes(&self) -> impl Iterator<Item = UniverseIndex> + '_ {
+        self.binders.iter().map(|pk| pk.into_inner())
+    }
+
+    /// The universes that the value mentions: those of its variables,
+    /// and those of the placeholders that appear in it. Embedders that
+    /// replay a canonical value (such as a solution) into their own
+    /// inference context use this to map each universe to one of
+    /// theirs.
+    pub fn universes<TF: TypeFamily>(&self) -> CanonicalUniverses
+    where
+        T: Fold<TF>,
+    {
+        CanonicalUniverses {
+            binders: self.binder_universes().collect(),
+            placeholders: placeholder_universes(&self.value),
+        }
+    }
+}
+
+/// The universes that a canonical value mentions; see
+/// `Canonical::universes`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CanonicalUniverses {
+    /// The universe of each canonical variable.
+    pub binders: Vec<UniverseIndex>,
+
+    /// The universes of the placeholders in the value, in increasing
+    /// order and without duplicates.
+    pub placeholders: Vec<UniverseIndex>,
+}
+
+impl CanonicalUniverses {
+    /// The greatest universe mentioned (the root universe if none
+    /// is).
+    pub fn max_universe(&self) -> UniverseIndex {
+        self.binders
+            .iter()
+            .chain(&self.placeholders)
+            .cloned()
+            .max()
+            .unwrap_or(UniverseIndex::ROOT)
+    }
+}
+
+/// The universes of the placeholders in `value`, in increasing order
+/// and without duplicates.
+pub fn placeholder_universes<TF: TypeFamily, T: Fold<TF>>(value: &T) -> Vec<UniverseIndex> {
+    let mut collector = PlaceholderUniverses(BTreeSet::new());
+    value.fold_with(&mut collector, 0).unwrap();
+    collector.0.into_iter().collect()
+}
+
+/// A folder that collects the universes of the placeholders that it
+/// finds, leaving them in place.
+struct PlaceholderUniverses(BTreeSet<UniverseIndex>);
+
+impl DefaultTypeFolder for PlaceholderUniverses {}
+
+impl DefaultFreeVarFolder for PlaceholderUniverses {}
+
+impl DefaultInferenceFolder for PlaceholderUniverses {}
+
+impl<TF: TypeFamily> PlaceholderFolder<TF> for PlaceholderUniverses {
+    fn fold_free_placeholder_ty(
+        &mut self,
+        placeholder: PlaceholderIndex,
+        _binders: usize,
+    ) -> Fallible<TF::Type> {
+        self.0.insert(placeholder.ui);
+        Ok(placeholder.to_ty::<TF>())
+    }
+
+    fn fold_free_placeholder_lifetime(
+        &mut self,
+        placeholder: PlaceholderIndex,
+        _binders: usize,
+    ) -> Fallible<TF::Lifetime> {
+        self.0.insert(placeholder.ui);
+        Ok(placeholder.to_lifetime::<TF>())
+    }
+}
+
 /// A "universe canonical" value. This is a wrapper around a
 /// `Canonical`, indicating that the universes within have been
 /// "renumbered" to start from 0 and collapse unimportant
@@ -1005,6 +1420,8 @@ pub struct Canonical<T> {
 ///
 /// To produce one of these values, use the `u_canonicalize` method.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct UCanonical<T> {
     pub canonical: Canonical<T>,
     pub universes: usize,
@@ -1023,13 +1440,37 @@ impl<T> UCanonical<T> {
 
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
 /// A general goal; this is the full range of questions you can pose to Chalk.
+#[cfg_attr(
+    feature = "persist",
+    derive(Archive, Serialize, Deserialize),
+    archive(
+        check_bytes,
+        bound(
+            archive = "TF: PersistFamily",
+            serialize = "TF: PersistFamily, __S: PersistSerializer",
+            deserialize = "TF: PersistFamily, __D: PersistDeserializer"
+        )
+    ),
+    archive_attr(check_bytes(bound = "__C: PersistValidator"))
+)]
 pub enum Goal<TF: TypeFamily> {
     /// Introduces a binding at depth 0, shifting other bindings up
     /// (deBruijn index).
-    Quantified(QuantifierKind, Binders<Box<Goal<TF>>>),
-    Implies(Vec<ProgramClause<TF>>, Box<Goal<TF>>),
-    And(Box<Goal<TF>>, Box<Goal<TF>>),
-    Not(Box<Goal<TF>>),
+    Quantified(
+        QuantifierKind,
+        #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))]
+        Binders<Arc<Goal<TF>>>,
+    ),
+    Implies(
+        #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))]
+        Vec<ProgramClause<TF>>,
+        #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))] Arc<Goal<TF>>,
+    ),
+    And(
+        #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))] Arc<Goal<TF>>,
+        #[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))] Arc<Goal<TF>>,
+    ),
+    Not(#[cfg_attr(feature = "persist", omit_bounds, archive_attr(omit_bounds))] Arc<Goal<TF>>),
     Leaf(LeafGoal<TF>),
 
     /// Indicates something that cannot be proven to be true or false
@@ -1049,19 +1490,19 @@ impl<TF: TypeFamily> HasTypeFamily for Goal<TF> {
 }
 
 impl<TF: TypeFamily> Goal<TF> {
-    pub fn quantify(self, kind: QuantifierKind, binders: Vec<ParameterKind<()>>) -> Goal<TF> {
+    pub fn quantify(self, kind: QuantifierKind, binders: impl Into<ParameterKinds>) -> Goal<TF> {
         Goal::Quantified(
             kind,
             Binders {
-                value: Box::new(self),
-                binders,
+                value: Arc::new(self),
+                binders: binders.into(),
             },
         )
     }
 
     /// Takes a goal `G` and turns it into `not { G }`
     pub fn negate(self) -> Self {
-        Goal::Not(Box::new(self))
+        Goal::Not(Arc::new(self))
     }
 
     /// Takes a goal `G` and turns it into `compatible { G }`
@@ -1071,11 +1512,11 @@ impl<TF: TypeFamily> Goal<TF> {
         Goal::Quantified(
             QuantifierKind::ForAll,
             Binders {
-                value: Box::new(self),
-                binders: Vec::new(),
+                value: Arc::new(self),
+                binders: ParameterKinds::EMPTY,
             }
             .with_fresh_type_var(|goal, ty| {
-                Box::new(Goal::Implies(
+                Arc::new(Goal::Implies(
                     vec![
                         DomainGoal::Compatible(()).cast(),
                         DomainGoal::DownstreamType(ty).cast(),
@@ -1087,11 +1528,13 @@ impl<TF: TypeFamily> Goal<TF> {
     }
 
     pub fn implied_by(self, predicates: Vec<ProgramClause<TF>>) -> Goal<TF> {
-        Goal::Implies(predicates, Box::new(self))
+        Goal::Implies(predicates, Arc::new(self))
     }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum QuantifierKind {
     ForAll,
     Exists,
@@ -1104,8 +1547,11 @@ pub enum QuantifierKind {
 /// for later checking. This allows for decoupling between type and region
 /// checking in the compiler.
 #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub enum Constraint<TF: TypeFamily> {
     LifetimeEq(TF::Lifetime, TF::Lifetime),
+    TypeOutlives(TF::Type, TF::Lifetime),
 }
 
 impl<TF: TypeFamily> HasTypeFamily for Constraint<TF> {
@@ -1114,6 +1560,8 @@ impl<TF: TypeFamily> HasTypeFamily for Constraint<TF> {
 
 /// A mapping of inference variables to instantiations thereof.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct Substitution<TF: TypeFamily> {
     /// Map free variable with given index to the value with the same
     /// index. Naturally, the kind of the variable must agree with
@@ -1181,6 +1629,8 @@ impl<'a, TF: TypeFamily> DefaultPlaceholderFolder for &'a Substitution<TF> {}
 /// and the constraints represents any region constraints that must
 /// additionally be solved.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Fold)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 pub struct ConstrainedSubst<TF: TypeFamily> {
     pub subst: Substitution<TF>, /* NB: The `is_trivial` routine relies on the fact that `subst` is folded first. */
     pub constraints: Vec<InEnvironment<Constraint<TF>>>,
@@ -0,0 +1,148 @@
+//! Parameter kind lists: the kinds of the parameters bound by a
+//! `Binders`. Clause generation clones the binders of a datum into
+//! each clause that it generates from the datum, and folding clones
+//! them into each value that it folds, so for impl-heavy programs the
+//! allocation of these lists used to dominate. They are therefore
+//! interned in a table shared by all threads, like identifiers: a
+//! `ParameterKinds` is a reference to the interned list, which is
+//! copied rather than cloned. There are few distinct lists in any
+//! program, and they are never removed from the table.
+
+use crate::ParameterKind;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::Deref;
+use std::sync::RwLock;
+
+/// An interned list of parameter kinds. It dereferences to the slice of
+/// kinds, and compares, hashes and orders as that slice does.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ParameterKinds {
+    kinds: &'static [ParameterKind<()>],
+}
+
+lazy_static! {
+    static ref PARAMETER_KINDS_TABLE: RwLock<HashSet<&'static [ParameterKind<()>]>> =
+        RwLock::new(HashSet::new());
+}
+
+impl ParameterKinds {
+    /// The empty list, which is not looked up in the table.
+    pub const EMPTY: ParameterKinds = ParameterKinds { kinds: &[] };
+
+    /// Returns the interned list with the same kinds as `kinds`, adding
+    /// it to the table if it is not there yet.
+    pub fn intern(kinds: &[ParameterKind<()>]) -> ParameterKinds {
+        if kinds.is_empty() {
+            return ParameterKinds::EMPTY;
+        }
+
+        if let Some(&kinds) = PARAMETER_KINDS_TABLE.read().unwrap().get(kinds) {
+            return ParameterKinds { kinds };
+        }
+
+        let mut table = PARAMETER_KINDS_TABLE.write().unwrap();
+        if let Some(&kinds) = table.get(kinds) {
+            return ParameterKinds { kinds };
+        }
+        let kinds: &'static [ParameterKind<()>] = Box::leak(kinds.to_vec().into_boxed_slice());
+        table.insert(kinds);
+        ParameterKinds { kinds }
+    }
+
+    /// Returns the list of `self` followed by `other`.
+    pub fn concat(self, other: &[ParameterKind<()>]) -> ParameterKinds {
+        if other.is_empty() {
+            return self;
+        }
+        ParameterKinds::intern(&[self.kinds, other].concat())
+    }
+}
+
+impl Default for ParameterKinds {
+    fn default() -> Self {
+        ParameterKinds::EMPTY
+    }
+}
+
+impl Deref for ParameterKinds {
+    type Target = [ParameterKind<()>];
+
+    fn deref(&self) -> &[ParameterKind<()>] {
+        self.kinds
+    }
+}
+
+impl<'a> IntoIterator for &'a ParameterKinds {
+    type Item = &'a ParameterKind<()>;
+    type IntoIter = std::slice::Iter<'a, ParameterKind<()>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.kinds.iter()
+    }
+}
+
+impl From<Vec<ParameterKind<()>>> for ParameterKinds {
+    fn from(kinds: Vec<ParameterKind<()>>) -> Self {
+        ParameterKinds::intern(&kinds)
+    }
+}
+
+impl From<&[ParameterKind<()>]> for ParameterKinds {
+    fn from(kinds: &[ParameterKind<()>]) -> Self {
+        ParameterKinds::intern(kinds)
+    }
+}
+
+impl FromIterator<ParameterKind<()>> for ParameterKinds {
+    fn from_iter<I: IntoIterator<Item = ParameterKind<()>>>(iter: I) -> Self {
+        let kinds: Vec<_> = iter.into_iter().collect();
+        ParameterKinds::intern(&kinds)
+    }
+}
+
+impl PartialEq<Vec<ParameterKind<()>>> for ParameterKinds {
+    fn eq(&self, other: &Vec<ParameterKind<()>>) -> bool {
+        self.kinds == &other[..]
+    }
+}
+
+impl fmt::Debug for ParameterKinds {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.kinds, fmt)
+    }
+}
+
+/// Archived as the list of kinds, which is interned again when it is
+/// loaded.
+#[cfg(feature = "persist")]
+impl rkyv::Archive for ParameterKinds {
+    type Archived = rkyv::vec::ArchivedVec<rkyv::Archived<ParameterKind<()>>>;
+    type Resolver = rkyv::vec::VecResolver;
+
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        rkyv::vec::ArchivedVec::resolve_from_slice(self.kinds, pos, resolver, out);
+    }
+}
+
+#[cfg(feature = "persist")]
+impl<S> rkyv::Serialize<S> for ParameterKinds
+where
+    S: rkyv::ser::Serializer + rkyv::ser::ScratchSpace + ?Sized,
+{
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        rkyv::vec::ArchivedVec::serialize_from_slice(self.kinds, serializer)
+    }
+}
+
+#[cfg(feature = "persist")]
+impl<D: rkyv::Fallible + ?Sized> rkyv::Deserialize<ParameterKinds, D>
+    for rkyv::vec::ArchivedVec<rkyv::Archived<ParameterKind<()>>>
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<ParameterKinds, D::Error> {
+        let kinds: Vec<ParameterKind<()>> = self.deserialize(deserializer)?;
+        Ok(ParameterKinds::intern(&kinds))
+    }
+}
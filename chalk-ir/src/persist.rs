@@ -0,0 +1,94 @@
+//! Support for archiving the IR with rkyv (see `chalk_solve::persist`),
+//! enabled by the `persist` feature.
+//!
+//! The IR is recursive through the interned types and lifetimes of its
+//! type family, and through the subgoals of goals. The derived rkyv
+//! impls cannot bound the fields that close those cycles (the bounds
+//! would never be proven), so the types with such fields instead
+//! require the family to be a `PersistFamily`, and the serializers,
+//! deserializers and validators to be able to handle every part of
+//! the IR.
+
+use crate::family::{ChalkIr, TypeFamily};
+use crate::{CustomGoal, Lifetime, Ty, Void};
+use rkyv::de::SharedDeserializeRegistry;
+use rkyv::ser::{ScratchSpace, Serializer, SharedSerializeRegistry};
+use rkyv::validation::{ArchiveContext, SharedContext};
+use rkyv::{Archive, CheckBytes, Deserialize, Fallible, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// A type family whose interned representations are the IR types
+/// themselves, as in `ChalkIr`, so that they can be archived
+/// structurally. Families that really intern their types cannot be
+/// archived.
+pub trait PersistFamily:
+    TypeFamily<Type = Ty<Self>, Lifetime = Lifetime<Self>, CustomGoal = CustomGoal<Self>> + 'static
+{
+}
+
+impl PersistFamily for ChalkIr {}
+
+/// The serializers that can archive the IR.
+pub trait PersistSerializer: Serializer + ScratchSpace + SharedSerializeRegistry {}
+
+impl<S: Serializer + ScratchSpace + SharedSerializeRegistry + ?Sized> PersistSerializer for S {}
+
+/// The deserializers that can load archived IR.
+pub trait PersistDeserializer: SharedDeserializeRegistry {}
+
+impl<D: SharedDeserializeRegistry + ?Sized> PersistDeserializer for D {}
+
+/// The validators that can check archived IR.
+pub trait PersistValidator:
+    ArchiveContext + SharedContext + Fallible<Error: Error + 'static>
+{
+}
+
+impl<C> PersistValidator for C where
+    C: ArchiveContext + SharedContext + Fallible<Error: Error + 'static> + ?Sized
+{
+}
+
+/// `Void` has no values, so it is archived as itself; the bytes of an
+/// archive never validate as a `Void`.
+impl Archive for Void {
+    type Archived = Void;
+    type Resolver = Void;
+
+    unsafe fn resolve(&self, _: usize, _: Void, _: *mut Void) {
+        match *self {}
+    }
+}
+
+impl<S: Fallible + ?Sized> Serialize<S> for Void {
+    fn serialize(&self, _: &mut S) -> Result<Void, S::Error> {
+        match *self {}
+    }
+}
+
+impl<D: Fallible + ?Sized> Deserialize<Void, D> for Void {
+    fn deserialize(&self, _: &mut D) -> Result<Void, D::Error> {
+        match *self {}
+    }
+}
+
+impl<C: ?Sized> CheckBytes<C> for Void {
+    type Error = VoidCheckError;
+
+    unsafe fn check_bytes<'a>(_: *const Void, _: &mut C) -> Result<&'a Void, VoidCheckError> {
+        Err(VoidCheckError)
+    }
+}
+
+/// The error for bytes that claim to be a `Void`.
+#[derive(Debug)]
+pub struct VoidCheckError;
+
+impl fmt::Display for VoidCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "archived `Void` value")
+    }
+}
+
+impl Error for VoidCheckError {}
@@ -0,0 +1,320 @@
+//! Hashing that is stable across processes and platforms, so that
+//! caches keyed by goals can be shared between processes (e.g., in
+//! distributed builds). The derived `Hash` impls are not suitable for
+//! this: identifiers hash by their index in the interner, which
+//! depends on the order in which strings were interned, and integers
+//! hash in native byte order and width.
+//!
+//! `StableHash` instead walks the structure, hashing identifiers by
+//! their text and ids by the index assigned to them by the program,
+//! and feeds everything to the hasher as little-endian 64-bit
+//! integers. Interned types and lifetimes are hashed through their
+//! type family, which must hash them by content (see `TypeFamily`).
+
+use crate::family::TypeFamily;
+use crate::*;
+
+/// A 64-bit FNV-1a hasher, with all integers written as little-endian
+/// `u64`s. Unlike the hashers in `std`, its output is specified, and
+/// so may be persisted.
+#[derive(Clone, Debug)]
+pub struct StableHasher {
+    state: u64,
+}
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher {
+            state: 0xcbf2_9ce4_8422_2325,
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state ^= u64::from(byte);
+            self.state = self.state.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    pub fn write_u64(&mut self, n: u64) {
+        self.write_bytes(&n.to_le_bytes());
+    }
+
+    pub fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    /// Writes the length of `s`, then its bytes.
+    pub fn write_str(&mut self, s: &str) {
+        self.write_usize(s.len());
+        self.write_bytes(s.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        StableHasher::new()
+    }
+}
+
+/// Types whose hash is stable across processes and platforms (see
+/// the module docs).
+pub trait StableHash {
+    fn stable_hash(&self, hasher: &mut StableHasher);
+
+    /// Convenience method: hashes `self` with a fresh hasher.
+    fn stable_hash_value(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.stable_hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Generates a StableHash impl that hashes each field of the struct in
+/// turn.
+macro_rules! struct_stable_hash {
+    (impl[$($param:tt)*] StableHash for $self:ty { $($field:tt),* $(,)* }) => {
+        impl<$($param)*> StableHash for $self {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                // Validate that we have indeed listed all fields
+                let Self { $($field: _),* } = self;
+                $(
+                    self.$field.stable_hash(hasher);
+                )*
+            }
+        }
+    };
+}
+
+/// Generates a StableHash impl that hashes the given tag of the
+/// variant, followed by its fields. The tags, rather than the order
+/// of the variants, determine the hash, so they must never be reused.
+macro_rules! enum_stable_hash {
+    (impl[$($param:tt)*] StableHash for $self:ty {
+        $($tag:literal => $variant:ident $(($($field:ident),*))?),* $(,)*
+    }) => {
+        impl<$($param)*> StableHash for $self {
+            fn stable_hash(&self, hasher: &mut StableHasher) {
+                match self {
+                    $(
+                        Self::$variant $(($($field),*))? => {
+                            hasher.write_usize($tag);
+                            $($($field.stable_hash(hasher);)*)?
+                        }
+                    )*
+                }
+            }
+        }
+    };
+}
+
+impl StableHash for u32 {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_u64(u64::from(*self));
+    }
+}
+
+impl StableHash for usize {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(*self);
+    }
+}
+
+impl StableHash for () {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for Void {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {
+        match *self {}
+    }
+}
+
+impl<T> StableHash for PhantomData<T> {
+    fn stable_hash(&self, _hasher: &mut StableHasher) {}
+}
+
+impl StableHash for Identifier {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_str(self.as_str());
+    }
+}
+
+impl<T: StableHash> StableHash for Vec<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.len());
+        for element in self {
+            element.stable_hash(hasher);
+        }
+    }
+}
+
+/// Hashed as the `Vec` of its kinds.
+impl StableHash for ParameterKinds {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(self.len());
+        for kind in self {
+            kind.stable_hash(hasher);
+        }
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Box<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+impl<T: StableHash + ?Sized> StableHash for Arc<T> {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        (**self).stable_hash(hasher);
+    }
+}
+
+struct_stable_hash!(impl[] StableHash for RawId { index });
+struct_stable_hash!(impl[] StableHash for StructId { 0 });
+struct_stable_hash!(impl[] StableHash for TraitId { 0 });
+struct_stable_hash!(impl[] StableHash for ImplId { 0 });
+struct_stable_hash!(impl[] StableHash for ClauseId { 0 });
+struct_stable_hash!(impl[] StableHash for TypeId { 0 });
+struct_stable_hash!(impl[] StableHash for UniverseIndex { counter });
+struct_stable_hash!(impl[] StableHash for PlaceholderIndex { ui, idx });
+struct_stable_hash!(impl[] StableHash for InferenceVar { index });
+
+enum_stable_hash!(impl[] StableHash for TypeKindId {
+    0 => TypeId(id),
+    1 => TraitId(id),
+    2 => StructId(id),
+});
+enum_stable_hash!(impl[] StableHash for TypeName {
+    0 => TypeKindId(id),
+    1 => Placeholder(placeholder),
+    2 => AssociatedType(id),
+    3 => Tuple(arity),
+    4 => Error,
+    5 => FnPtr,
+    6 => OpaqueConst(name),
+    7 => Builtin(builtin),
+});
+enum_stable_hash!(impl[] StableHash for BuiltinType {
+    0 => Str,
+    1 => Slice,
+    2 => Array,
+    3 => Ref(mutability),
+    4 => Ptr(mutability),
+});
+enum_stable_hash!(impl[] StableHash for Mutability {
+    0 => Not,
+    1 => Mut,
+});
+enum_stable_hash!(impl[] StableHash for QuantifierKind {
+    0 => ForAll,
+    1 => Exists,
+});
+
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for Ty<TF> {
+    0 => Apply(apply),
+    1 => Dyn(bounds),
+    2 => Opaque(bounds),
+    3 => Projection(projection),
+    4 => ForAll(quantified_ty),
+    5 => BoundVar(depth),
+    6 => InferenceVar(var),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for QuantifiedTy<TF> { num_binders, ty });
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for Lifetime<TF> {
+    0 => BoundVar(depth),
+    1 => InferenceVar(var),
+    2 => Placeholder(placeholder),
+    3 => Phantom(void, phantom),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for ApplicationTy<TF> { name, parameters });
+enum_stable_hash!(impl[T: StableHash, L: StableHash] StableHash for ParameterKind<T, L> {
+    0 => Ty(ty),
+    1 => Lifetime(lifetime),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for Parameter<TF> { 0 });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for ProjectionTy<TF> {
+    associated_ty_id,
+    parameters,
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for TraitRef<TF> { trait_id, parameters });
+
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for WhereClause<TF> {
+    0 => Implemented(trait_ref),
+    1 => ProjectionEq(projection_eq),
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for WellFormed<TF> {
+    0 => Trait(trait_ref),
+    1 => Ty(ty),
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for FromEnv<TF> {
+    0 => Trait(trait_ref),
+    1 => Ty(ty),
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for DomainGoal<TF> {
+    0 => Holds(where_clause),
+    1 => WellFormed(well_formed),
+    2 => FromEnv(from_env),
+    3 => Normalize(normalize),
+    4 => IsLocal(ty),
+    5 => IsUpstream(ty),
+    6 => IsFullyVisible(ty),
+    7 => LocalImplAllowed(trait_ref),
+    8 => Compatible(unit),
+    9 => DownstreamType(ty),
+    10 => Custom(custom_goal),
+    11 => LayoutCompatible(layout_compatible),
+    12 => Upcast(upcast),
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for LeafGoal<TF> {
+    0 => EqGoal(eq_goal),
+    1 => DomainGoal(domain_goal),
+    2 => TypeOutlives(type_outlives),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for EqGoal<TF> { a, b });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for TypeOutlives<TF> { ty, lifetime });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for CustomGoal<TF> { name, parameters });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for LayoutCompatible<TF> { a, b });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for Upcast<TF> { source, target });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for Normalize<TF> { projection, ty });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for ProjectionEq<TF> { projection, ty });
+
+struct_stable_hash!(impl[T: StableHash] StableHash for Binders<T> { binders, value });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for ProgramClauseImplication<TF> {
+    consequence,
+    conditions,
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for ProgramClause<TF> {
+    0 => Implies(implication),
+    1 => ForAll(implication),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for Environment<TF> { clauses });
+struct_stable_hash!(impl[G: HasTypeFamily + StableHash] StableHash for InEnvironment<G> {
+    environment,
+    goal,
+});
+struct_stable_hash!(impl[T: StableHash] StableHash for Canonical<T> { value, binders });
+struct_stable_hash!(impl[T: StableHash] StableHash for UCanonical<T> { canonical, universes });
+
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for Goal<TF> {
+    0 => Quantified(kind, subgoal),
+    1 => Implies(clauses, subgoal),
+    2 => And(subgoal1, subgoal2),
+    3 => Not(subgoal),
+    4 => Leaf(leaf),
+    5 => CannotProve(unit),
+});
+enum_stable_hash!(impl[TF: TypeFamily] StableHash for Constraint<TF> {
+    0 => LifetimeEq(a, b),
+    1 => TypeOutlives(ty, lifetime),
+});
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for Substitution<TF> { parameters });
+struct_stable_hash!(impl[TF: TypeFamily] StableHash for ConstrainedSubst<TF> {
+    subst,
+    constraints,
+});
@@ -198,6 +198,10 @@ struct_zip!(impl[TF: TypeFamily] Zip<TF> for ProjectionTy<TF> {
 struct_zip!(impl[TF: TypeFamily] Zip<TF> for Normalize<TF> { projection, ty });
 struct_zip!(impl[TF: TypeFamily] Zip<TF> for ProjectionEq<TF> { projection, ty });
 struct_zip!(impl[TF: TypeFamily] Zip<TF> for EqGoal<TF> { a, b });
+struct_zip!(impl[TF: TypeFamily] Zip<TF> for TypeOutlives<TF> { ty, lifetime });
+struct_zip!(impl[TF: TypeFamily] Zip<TF> for CustomGoal<TF> { name, parameters });
+struct_zip!(impl[TF: TypeFamily] Zip<TF> for LayoutCompatible<TF> { a, b });
+struct_zip!(impl[TF: TypeFamily] Zip<TF> for Upcast<TF> { source, target });
 struct_zip!(impl[TF: TypeFamily] Zip<TF> for ProgramClauseImplication<TF> {
     consequence,
     conditions
@@ -247,9 +251,12 @@ enum_zip!(impl<TF> for DomainGoal<TF> {
     IsFullyVisible,
     LocalImplAllowed,
     Compatible,
-    DownstreamType
+    DownstreamType,
+    Custom,
+    LayoutCompatible,
+    Upcast
 });
-enum_zip!(impl<TF> for LeafGoal<TF> { DomainGoal, EqGoal });
+enum_zip!(impl<TF> for LeafGoal<TF> { DomainGoal, EqGoal, TypeOutlives });
 enum_zip!(impl<TF> for ProgramClause<TF> { Implies, ForAll });
 
 // Annoyingly, Goal cannot use `enum_zip` because some variants have
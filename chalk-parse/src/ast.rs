@@ -23,6 +23,8 @@ pub enum Item {
     StructDefn(StructDefn),
     TraitDefn(TraitDefn),
     Impl(Impl),
+    InherentImpl(InherentImpl),
+    DefaultImpl(DefaultImpl),
     Clause(Clause),
 }
 
@@ -33,12 +35,23 @@ pub struct StructDefn {
     pub where_clauses: Vec<QuantifiedWhereClause>,
     pub fields: Vec<Field>,
     pub flags: StructFlags,
+    pub lang_item: Option<LangItem>,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct StructFlags {
     pub upstream: bool,
     pub fundamental: bool,
+    pub extern_type: bool,
+    pub scalar: Option<ScalarKind>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ScalarKind {
+    Bool,
+    Char,
+    Int,
+    Float,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -47,7 +60,9 @@ pub struct TraitDefn {
     pub parameter_kinds: Vec<ParameterKind>,
     pub where_clauses: Vec<QuantifiedWhereClause>,
     pub assoc_ty_defns: Vec<AssocTyDefn>,
+    pub method_defns: Vec<MethodDefn>,
     pub flags: TraitFlags,
+    pub lang_item: Option<LangItem>,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -57,6 +72,35 @@ pub struct TraitFlags {
     pub upstream: bool,
     pub fundamental: bool,
     pub non_enumerable: bool,
+    pub sealed: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LangItem {
+    OwnedBox,
+    Sized,
+    Unpin,
+    Drop,
+    FnOnce,
+    FnMut,
+    Fn,
+    Copy,
+    Clone,
+    Send,
+    Sync,
+    UnsafeCell,
+    Deref,
+    Index,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -65,6 +109,19 @@ pub struct AssocTyDefn {
     pub parameter_kinds: Vec<ParameterKind>,
     pub bounds: Vec<QuantifiedInlineBound>,
     pub where_clauses: Vec<QuantifiedWhereClause>,
+    pub default: Option<Ty>,
+}
+
+/// A method signature in a trait or trait impl, `fn foo<T>(Self, T)
+/// -> T where T: Clone;`. Methods have no bodies, and their arguments
+/// are given only by their types.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MethodDefn {
+    pub name: Identifier,
+    pub parameter_kinds: Vec<ParameterKind>,
+    pub args: Vec<Ty>,
+    pub ret: Ty,
+    pub where_clauses: Vec<QuantifiedWhereClause>,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -84,6 +141,7 @@ pub enum Parameter {
 pub enum InlineBound {
     TraitBound(TraitBound),
     ProjectionEqBound(ProjectionEqBound),
+    FnBound(FnBound),
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -101,10 +159,28 @@ pub struct TraitBound {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
-/// Represents a projection equality bound on e.g. a type or type parameter.
-/// Does not know anything about what it's binding.
+/// Represents the parenthesized sugar `Fn(A, B) -> C` for a bound on
+/// one of the fn-family traits, which stands for `Fn<(A, B)>` together
+/// with `Output = C` (or `Output = ()` if there is no `-> C`).
+pub struct FnBound {
+    pub trait_name: Identifier,
+    pub inputs: Vec<Ty>,
+    pub output: Option<Ty>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// Represents a trait bound with associated type bindings, like
+/// `Foo<A = X, B = Y>`, on e.g. a type or type parameter. Does not know
+/// anything about what it's binding.
 pub struct ProjectionEqBound {
     pub trait_bound: TraitBound,
+    /// At least one binding.
+    pub bindings: Vec<AssocTyBinding>,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+/// An associated type binding `Name<args> = value` in a bound.
+pub struct AssocTyBinding {
     pub name: Identifier,
     pub args: Vec<Parameter>,
     pub value: Ty,
@@ -132,9 +208,42 @@ pub struct Impl {
     pub polarity: Polarity,
     pub where_clauses: Vec<QuantifiedWhereClause>,
     pub assoc_ty_values: Vec<AssocTyValue>,
+    pub method_defns: Vec<MethodDefn>,
     pub impl_type: ImplType,
 }
 
+/// An inherent impl, `impl<T> Foo<T> { fn bar; const BAZ; }`, whose
+/// self type is a struct. Only the names of its items are recorded,
+/// for coherence.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct InherentImpl {
+    pub parameter_kinds: Vec<ParameterKind>,
+    pub self_ty: Ty,
+    pub where_clauses: Vec<QuantifiedWhereClause>,
+    pub items: Vec<InherentItem>,
+}
+
+/// A default impl, `impl Send for .. { }`: the legacy way of declaring
+/// `Send` an auto trait, like `#[auto]` does.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DefaultImpl {
+    pub trait_name: Identifier,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InherentItem {
+    Fn(Identifier),
+    Const(Identifier),
+}
+
+impl InherentItem {
+    pub fn name(&self) -> Identifier {
+        match self {
+            InherentItem::Fn(name) | InherentItem::Const(name) => *name,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum ImplType {
     Local,
@@ -171,6 +280,48 @@ pub enum Ty {
         lifetime_names: Vec<Identifier>,
         ty: Box<Ty>,
     },
+    Tuple {
+        types: Vec<Ty>,
+    },
+    /// `fn(A, B) -> C`; the return type is `()` if omitted.
+    FnPtr {
+        args: Vec<Ty>,
+        ret: Box<Ty>,
+    },
+    /// `const N<T>`, an unevaluated constant expression named `N`,
+    /// applied to the parameters it mentions.
+    OpaqueConst {
+        name: Identifier,
+        args: Vec<Parameter>,
+    },
+    /// `[T]`. (`str` is an ordinary name, `Ty::Id`, which refers to
+    /// the built-in type unless the program declares a type `str`.)
+    Slice {
+        ty: Box<Ty>,
+    },
+    /// `[T; N]`, where the length `N` is a constant or a type
+    /// parameter.
+    Array {
+        ty: Box<Ty>,
+        len: Box<Ty>,
+    },
+    /// `&'a T` or `&'a mut T`.
+    Ref {
+        lifetime: Lifetime,
+        mutability: Mutability,
+        ty: Box<Ty>,
+    },
+    /// `*const T` or `*mut T`.
+    Ptr {
+        mutability: Mutability,
+        ty: Box<Ty>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Mutability {
+    Not,
+    Mut,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
@@ -224,8 +375,18 @@ impl fmt::Display for Identifier {
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum WhereClause {
-    Implemented { trait_ref: TraitRef },
-    ProjectionEq { projection: ProjectionTy, ty: Ty },
+    Implemented {
+        trait_ref: TraitRef,
+    },
+    /// `T: Foo<A = X, B = Y>`.
+    ProjectionEq {
+        ty: Ty,
+        bound: ProjectionEqBound,
+    },
+    FnBound {
+        ty: Ty,
+        bound: Box<FnBound>,
+    },
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -242,6 +403,8 @@ pub enum DomainGoal {
     LocalImplAllowed { trait_ref: TraitRef },
     Compatible,
     DownstreamType { ty: Ty },
+    LayoutCompatible { a: Ty, b: Ty },
+    Upcast { source: Ty, target: Ty },
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -249,6 +412,7 @@ pub enum LeafGoal {
     DomainGoal { goal: DomainGoal },
     UnifyTys { a: Ty, b: Ty },
     UnifyLifetimes { a: Lifetime, b: Lifetime },
+    TypeOutlives { ty: Ty, lifetime: Lifetime },
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
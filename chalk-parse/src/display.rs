@@ -0,0 +1,629 @@
+//! `Display` impls that print the AST back out in the surface syntax,
+//! such that parsing the output yields the same AST (modulo spans and
+//! comments). This is used, e.g., to write out programs that have
+//! been transformed or minimized.
+
+use crate::ast::*;
+use std::fmt;
+
+/// Writes `elements`, separated by `separator`.
+fn write_separated<T: fmt::Display>(
+    f: &mut fmt::Formatter,
+    elements: &[T],
+    separator: &str,
+) -> fmt::Result {
+    for (index, element) in elements.iter().enumerate() {
+        if index > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{}", element)?;
+    }
+    Ok(())
+}
+
+/// Writes `<a, b, c>`, or nothing if `elements` is empty.
+fn write_angle<T: fmt::Display>(f: &mut fmt::Formatter, elements: &[T]) -> fmt::Result {
+    if elements.is_empty() {
+        return Ok(());
+    }
+    f.write_str("<")?;
+    write_separated(f, elements, ", ")?;
+    f.write_str(">")
+}
+
+/// Writes `forall<a, b> `, or nothing if `parameter_kinds` is empty.
+fn write_forall(f: &mut fmt::Formatter, parameter_kinds: &[ParameterKind]) -> fmt::Result {
+    if parameter_kinds.is_empty() {
+        return Ok(());
+    }
+    f.write_str("forall")?;
+    write_angle(f, parameter_kinds)?;
+    f.write_str(" ")
+}
+
+/// Writes ` where a, b`, or nothing if `where_clauses` is empty.
+fn write_where_clauses(
+    f: &mut fmt::Formatter,
+    where_clauses: &[QuantifiedWhereClause],
+) -> fmt::Result {
+    if where_clauses.is_empty() {
+        return Ok(());
+    }
+    f.write_str(" where ")?;
+    write_separated(f, where_clauses, ", ")
+}
+
+/// Writes `Trait<args>` for the trait ref, given the arguments other
+/// than the self type.
+fn write_trait(
+    f: &mut fmt::Formatter,
+    trait_name: &Identifier,
+    args_no_self: &[Parameter],
+) -> fmt::Result {
+    write!(f, "{}", trait_name)?;
+    write_angle(f, args_no_self)
+}
+
+/// Writes `Self: Trait<args>` (with `sep` in place of `:`).
+fn write_trait_ref(f: &mut fmt::Formatter, trait_ref: &TraitRef, sep: &str) -> fmt::Result {
+    write!(f, "{}{} ", TyAtom(self_ty(trait_ref)), sep)?;
+    write_trait(f, &trait_ref.trait_name, &trait_ref.args[1..])
+}
+
+/// The self type of `trait_ref`, which is always its first argument.
+fn self_ty(trait_ref: &TraitRef) -> &Ty {
+    match &trait_ref.args[0] {
+        Parameter::Ty(ty) => ty,
+        Parameter::Lifetime(_) => panic!("self type of `{}` is a lifetime", trait_ref.trait_name),
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Item::StructDefn(defn) => write!(f, "{}", defn),
+            Item::TraitDefn(defn) => write!(f, "{}", defn),
+            Item::Impl(impl_) => write!(f, "{}", impl_),
+            Item::InherentImpl(impl_) => write!(f, "{}", impl_),
+            Item::DefaultImpl(impl_) => write!(f, "impl {} for .. {{ }}", impl_.trait_name),
+            Item::Clause(clause) => {
+                f.write_str("forall")?;
+                write_angle(f, &clause.parameter_kinds)?;
+                write!(f, " {{ {}", clause.consequence)?;
+                if !clause.conditions.is_empty() {
+                    f.write_str(" if ")?;
+                    write_separated(
+                        f,
+                        &clause
+                            .conditions
+                            .iter()
+                            .map(|g| Goal1(g))
+                            .collect::<Vec<_>>(),
+                        ", ",
+                    )?;
+                }
+                f.write_str(" }")
+            }
+        }
+    }
+}
+
+impl fmt::Display for StructDefn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(lang_item) = self.lang_item {
+            write!(f, "#[lang({})] ", lang_item)?;
+        }
+        if let Some(scalar) = self.flags.scalar {
+            write!(f, "#[scalar({})] ", scalar)?;
+        }
+        if self.flags.upstream {
+            f.write_str("#[upstream] ")?;
+        }
+        if self.flags.fundamental {
+            f.write_str("#[fundamental] ")?;
+        }
+        if self.flags.extern_type {
+            return write!(f, "extern type {};", self.name);
+        }
+        write!(f, "struct {}", self.name)?;
+        write_angle(f, &self.parameter_kinds)?;
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(" {")?;
+        if !self.fields.is_empty() {
+            f.write_str(" ")?;
+            write_separated(f, &self.fields, ", ")?;
+        }
+        f.write_str(" }")
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.ty)
+    }
+}
+
+impl fmt::Display for TraitDefn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(lang_item) = self.lang_item {
+            write!(f, "#[lang({})] ", lang_item)?;
+        }
+        let TraitFlags {
+            auto,
+            marker,
+            upstream,
+            fundamental,
+            non_enumerable,
+            sealed,
+        } = self.flags;
+        for (flag, name) in &[
+            (auto, "auto"),
+            (marker, "marker"),
+            (upstream, "upstream"),
+            (fundamental, "fundamental"),
+            (non_enumerable, "non_enumerable"),
+            (sealed, "sealed"),
+        ] {
+            if *flag {
+                write!(f, "#[{}] ", name)?;
+            }
+        }
+        write!(f, "trait {}", self.name)?;
+        write_angle(f, &self.parameter_kinds)?;
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(" {")?;
+        for assoc_ty_defn in &self.assoc_ty_defns {
+            write!(f, " {}", assoc_ty_defn)?;
+        }
+        for method_defn in &self.method_defns {
+            write!(f, " {}", method_defn)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+impl fmt::Display for LangItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LangItem::OwnedBox => "owned_box",
+            LangItem::Sized => "sized",
+            LangItem::Unpin => "unpin",
+            LangItem::Drop => "drop",
+            LangItem::FnOnce => "fn_once",
+            LangItem::FnMut => "fn_mut",
+            LangItem::Fn => "fn",
+            LangItem::Copy => "copy",
+            LangItem::Clone => "clone",
+            LangItem::Send => "send",
+            LangItem::Sync => "sync",
+            LangItem::UnsafeCell => "unsafe_cell",
+            LangItem::Deref => "deref",
+            LangItem::Index => "index",
+            LangItem::Add => "add",
+            LangItem::Sub => "sub",
+            LangItem::Mul => "mul",
+            LangItem::Div => "div",
+            LangItem::Rem => "rem",
+            LangItem::BitAnd => "bitand",
+            LangItem::BitOr => "bitor",
+            LangItem::BitXor => "bitxor",
+            LangItem::Shl => "shl",
+            LangItem::Shr => "shr",
+        })
+    }
+}
+
+impl fmt::Display for ScalarKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ScalarKind::Bool => "bool",
+            ScalarKind::Char => "char",
+            ScalarKind::Int => "int",
+            ScalarKind::Float => "float",
+        })
+    }
+}
+
+impl fmt::Display for AssocTyDefn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "type {}", self.name)?;
+        write_angle(f, &self.parameter_kinds)?;
+        if !self.bounds.is_empty() {
+            f.write_str(": ")?;
+            write_separated(f, &self.bounds, " + ")?;
+        }
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(";")
+    }
+}
+
+impl fmt::Display for MethodDefn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fn {}", self.name)?;
+        write_angle(f, &self.parameter_kinds)?;
+        f.write_str("(")?;
+        write_separated(f, &self.args, ", ")?;
+        f.write_str(")")?;
+        match &self.ret {
+            Ty::Tuple { types } if types.is_empty() => {}
+            ret => write!(f, " -> {}", ret)?,
+        }
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(";")
+    }
+}
+
+impl fmt::Display for Impl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let ImplType::External = self.impl_type {
+            f.write_str("#[upstream] ")?;
+        }
+        f.write_str("impl")?;
+        write_angle(f, &self.parameter_kinds)?;
+        f.write_str(" ")?;
+        if let Polarity::Negative = self.polarity {
+            f.write_str("!")?;
+        }
+        write_trait(f, &self.trait_ref.trait_name, &self.trait_ref.args[1..])?;
+        write!(f, " for {}", self_ty(&self.trait_ref))?;
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(" {")?;
+        for assoc_ty_value in &self.assoc_ty_values {
+            write!(f, " {}", assoc_ty_value)?;
+        }
+        for method_defn in &self.method_defns {
+            write!(f, " {}", method_defn)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+impl fmt::Display for InherentImpl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("impl")?;
+        write_angle(f, &self.parameter_kinds)?;
+        write!(f, " {}", self.self_ty)?;
+        write_where_clauses(f, &self.where_clauses)?;
+        f.write_str(" {")?;
+        for item in &self.items {
+            match item {
+                InherentItem::Fn(name) => write!(f, " fn {};", name)?,
+                InherentItem::Const(name) => write!(f, " const {};", name)?,
+            }
+        }
+        f.write_str(" }")
+    }
+}
+
+impl fmt::Display for AssocTyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.default {
+            f.write_str("default ")?;
+        }
+        write!(f, "type {}", self.name)?;
+        write_angle(f, &self.parameter_kinds)?;
+        write!(f, " = {};", self.value)
+    }
+}
+
+impl fmt::Display for ParameterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterKind::Ty(name) | ParameterKind::Lifetime(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Parameter::Ty(ty) => write!(f, "{}", ty),
+            Parameter::Lifetime(lifetime) => write!(f, "{}", lifetime),
+        }
+    }
+}
+
+impl fmt::Display for Lifetime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lifetime::Id { name } => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ty::Id { name } => write!(f, "{}", name),
+            Ty::Dyn { bounds } => {
+                f.write_str("dyn ")?;
+                write_separated(f, bounds, " + ")
+            }
+            Ty::Opaque { bounds } => {
+                f.write_str("impl ")?;
+                write_separated(f, bounds, " + ")
+            }
+            Ty::Apply { name, args } => {
+                // `Foo<>` would parse as `Ty::Apply` with no arguments,
+                // whereas `Foo` parses as `Ty::Id`.
+                write!(f, "{}<", name)?;
+                write_separated(f, args, ", ")?;
+                f.write_str(">")
+            }
+            Ty::Projection { proj } => write!(f, "{}", proj),
+            Ty::ForAll { lifetime_names, ty } => {
+                f.write_str("for<")?;
+                write_separated(f, lifetime_names, ", ")?;
+                write!(f, "> {}", ty)
+            }
+            Ty::Tuple { types } => {
+                f.write_str("(")?;
+                write_separated(f, types, ", ")?;
+                if types.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Ty::FnPtr { args, ret } => {
+                f.write_str("fn(")?;
+                write_separated(f, args, ", ")?;
+                match &**ret {
+                    Ty::Tuple { types } if types.is_empty() => f.write_str(")"),
+                    ret => write!(f, ") -> {}", TyAtom(ret)),
+                }
+            }
+            Ty::OpaqueConst { name, args } => {
+                write!(f, "const {}", name)?;
+                if !args.is_empty() {
+                    f.write_str("<")?;
+                    write_separated(f, args, ", ")?;
+                    f.write_str(">")?;
+                }
+                Ok(())
+            }
+            Ty::Slice { ty } => write!(f, "[{}]", ty),
+            Ty::Array { ty, len } => write!(f, "[{}; {}]", ty, len),
+            Ty::Ref {
+                lifetime,
+                mutability: Mutability::Not,
+                ty,
+            } => write!(f, "&{} {}", lifetime, TyAtom(ty)),
+            Ty::Ref {
+                lifetime,
+                mutability: Mutability::Mut,
+                ty,
+            } => write!(f, "&{} mut {}", lifetime, TyAtom(ty)),
+            Ty::Ptr {
+                mutability: Mutability::Not,
+                ty,
+            } => write!(f, "*const {}", TyAtom(ty)),
+            Ty::Ptr {
+                mutability: Mutability::Mut,
+                ty,
+            } => write!(f, "*mut {}", TyAtom(ty)),
+        }
+    }
+}
+
+/// A type in a position where `dyn`, `impl` and `for` types must be
+/// parenthesized, such as the output of an `Fn` bound or a self type
+/// followed by `:`.
+struct TyAtom<'a>(&'a Ty);
+
+impl fmt::Display for TyAtom<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Ty::Dyn { .. } | Ty::Opaque { .. } | Ty::ForAll { .. } => write!(f, "({})", self.0),
+            ty => write!(f, "{}", ty),
+        }
+    }
+}
+
+impl fmt::Display for ProjectionTy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<")?;
+        write_trait_ref(f, &self.trait_ref, " as")?;
+        write!(f, ">::{}", self.name)?;
+        write_angle(f, &self.args)
+    }
+}
+
+impl fmt::Display for QuantifiedInlineBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_forall(f, &self.parameter_kinds)?;
+        write!(f, "{}", self.bound)
+    }
+}
+
+impl fmt::Display for InlineBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InlineBound::TraitBound(bound) => write!(f, "{}", bound),
+            InlineBound::ProjectionEqBound(bound) => write!(f, "{}", bound),
+            InlineBound::FnBound(bound) => write!(f, "{}", bound),
+        }
+    }
+}
+
+impl fmt::Display for TraitBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_trait(f, &self.trait_name, &self.args_no_self)
+    }
+}
+
+impl fmt::Display for ProjectionEqBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}<", self.trait_bound.trait_name)?;
+        for arg in &self.trait_bound.args_no_self {
+            write!(f, "{}, ", arg)?;
+        }
+        for (i, binding) in self.bindings.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", binding.name)?;
+            write_angle(f, &binding.args)?;
+            write!(f, " = {}", binding.value)?;
+        }
+        f.write_str(">")
+    }
+}
+
+impl fmt::Display for FnBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}(", self.trait_name)?;
+        write_separated(f, &self.inputs, ", ")?;
+        f.write_str(")")?;
+        if let Some(output) = &self.output {
+            write!(f, " -> {}", TyAtom(output))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for QuantifiedWhereClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_forall(f, &self.parameter_kinds)?;
+        write!(f, "{}", self.where_clause)
+    }
+}
+
+impl fmt::Display for WhereClause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WhereClause::Implemented { trait_ref } => write_trait_ref(f, trait_ref, ":"),
+            WhereClause::ProjectionEq { ty, bound } => write!(f, "{}: {}", TyAtom(ty), bound),
+            WhereClause::FnBound { ty, bound } => write!(f, "{}: {}", TyAtom(ty), bound),
+        }
+    }
+}
+
+impl fmt::Display for DomainGoal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DomainGoal::Holds { where_clause } => write!(f, "{}", where_clause),
+            DomainGoal::Normalize { projection, ty } => {
+                write!(f, "Normalize({} -> {})", projection, ty)
+            }
+            DomainGoal::TraitRefWellFormed { trait_ref } => {
+                f.write_str("WellFormed(")?;
+                write_trait_ref(f, trait_ref, ":")?;
+                f.write_str(")")
+            }
+            DomainGoal::TyWellFormed { ty } => write!(f, "WellFormed({})", ty),
+            DomainGoal::TyFromEnv { ty } => write!(f, "FromEnv({})", ty),
+            DomainGoal::TraitRefFromEnv { trait_ref } => {
+                f.write_str("FromEnv(")?;
+                write_trait_ref(f, trait_ref, ":")?;
+                f.write_str(")")
+            }
+            DomainGoal::IsLocal { ty } => write!(f, "IsLocal({})", ty),
+            DomainGoal::IsUpstream { ty } => write!(f, "IsUpstream({})", ty),
+            DomainGoal::IsFullyVisible { ty } => write!(f, "IsFullyVisible({})", ty),
+            DomainGoal::LocalImplAllowed { trait_ref } => {
+                f.write_str("LocalImplAllowed(")?;
+                write_trait_ref(f, trait_ref, ":")?;
+                f.write_str(")")
+            }
+            DomainGoal::Compatible => f.write_str("Compatible"),
+            DomainGoal::DownstreamType { ty } => write!(f, "DownstreamType({})", ty),
+            DomainGoal::LayoutCompatible { a, b } => write!(f, "LayoutCompatible({}, {})", a, b),
+            DomainGoal::Upcast { source, target } => write!(f, "Upcast({} => {})", source, target),
+        }
+    }
+}
+
+impl fmt::Display for LeafGoal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LeafGoal::DomainGoal { goal } => write!(f, "{}", goal),
+            LeafGoal::UnifyTys { a, b } => write!(f, "{} = {}", a, b),
+            LeafGoal::UnifyLifetimes { a, b } => write!(f, "{} = {}", a, b),
+            LeafGoal::TypeOutlives { ty, lifetime } => write!(f, "{}: {}", TyAtom(ty), lifetime),
+        }
+    }
+}
+
+/// A clause in the hypotheses of an `if` goal, which uses `:-` rather
+/// than the `if` of clauses in the program.
+struct InlineClause<'a>(&'a Clause);
+
+impl fmt::Display for InlineClause<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let clause = self.0;
+        if !clause.parameter_kinds.is_empty() {
+            write_forall(f, &clause.parameter_kinds)?;
+            f.write_str("{ ")?;
+        }
+        write!(f, "{}", clause.consequence)?;
+        if !clause.conditions.is_empty() {
+            f.write_str(" :- ")?;
+            write_separated(
+                f,
+                &clause
+                    .conditions
+                    .iter()
+                    .map(|g| Goal1(g))
+                    .collect::<Vec<_>>(),
+                ", ",
+            )?;
+        }
+        if !clause.parameter_kinds.is_empty() {
+            f.write_str(" }")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Goal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Goal::And(g1, g2) => write!(f, "{}, {}", Goal1(g1), g2),
+            goal => write!(f, "{}", Goal1(goal)),
+        }
+    }
+}
+
+/// A goal in a position where conjunctions must be parenthesized.
+struct Goal1<'a>(&'a Goal);
+
+impl fmt::Display for Goal1<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            // Unlike elsewhere, the `<>` are required here, even if
+            // there are no parameters.
+            Goal::ForAll(parameter_kinds, goal) => {
+                f.write_str("forall<")?;
+                write_separated(f, parameter_kinds, ", ")?;
+                write!(f, "> {{ {} }}", goal)
+            }
+            Goal::Exists(parameter_kinds, goal) => {
+                f.write_str("exists<")?;
+                write_separated(f, parameter_kinds, ", ")?;
+                write!(f, "> {{ {} }}", goal)
+            }
+            Goal::Implies(clauses, goal) => {
+                f.write_str("if (")?;
+                write_separated(
+                    f,
+                    &clauses.iter().map(InlineClause).collect::<Vec<_>>(),
+                    "; ",
+                )?;
+                write!(f, ") {{ {} }}", goal)
+            }
+            Goal::And(..) => write!(f, "({})", self.0),
+            Goal::Not(goal) => write!(f, "not {{ {} }}", goal),
+            Goal::Compatible(goal) => write!(f, "compatible {{ {} }}", goal),
+            Goal::Leaf(leaf) => write!(f, "{}", leaf),
+        }
+    }
+}
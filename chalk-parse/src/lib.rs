@@ -5,6 +5,7 @@ extern crate lalrpop_util;
 extern crate lalrpop_intern;
 
 pub mod ast;
+mod display;
 #[rustfmt::skip]
 lalrpop_mod!(pub parser);
 
@@ -7,13 +7,134 @@ use chalk_ir::cast::Cast;
 use chalk_ir::family::{ChalkIr, HasTypeFamily};
 use chalk_ir::fold::{shift::Shift, Fold, Folder};
 use chalk_ir::{
-    Binders, Identifier, ImplId, Lifetime, Parameter, ParameterKind, ProjectionEq, ProjectionTy,
-    QuantifiedWhereClause, RawId, StructId, TraitId, TraitRef, Ty, TypeId, TypeName, WhereClause,
+    Binders, Identifier, ImplId, Lifetime, Parameter, ParameterKind, ParameterKinds, ProjectionEq,
+    ProjectionTy, QuantifiedWhereClause, RawId, StructId, TraitId, TraitRef, Ty, TypeId, TypeName,
+    WhereClause,
 };
+use std::collections::BTreeMap;
 use std::iter;
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum LangItem {}
+/// Items that the solver has built-in knowledge of. The database
+/// tells us which struct or trait (if any) plays each role; see
+/// `RustIrDatabase::lang_item`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LangItem {
+    /// The `Box<T>` struct.
+    OwnedBox,
+
+    /// The `Sized` trait, which every type implements apart from
+    /// `dyn` types and extern types.
+    Sized,
+
+    /// The `Unpin` auto trait.
+    Unpin,
+
+    /// The `Drop` trait.
+    Drop,
+
+    /// The `FnOnce` trait, which declares the `Output` associated
+    /// type used by the `Fn(A) -> B` sugar.
+    FnOnce,
+
+    /// The `FnMut` trait.
+    FnMut,
+
+    /// The `Fn` trait.
+    Fn,
+
+    /// The `Copy` trait, which the scalar types implement (see
+    /// `StructFlags::scalar`).
+    Copy,
+
+    /// The `Clone` trait, which the scalar types implement too.
+    Clone,
+
+    /// The `Send` auto trait, which raw pointers do not implement, and
+    /// which `&T` implements only if `T: Sync`.
+    Send,
+
+    /// The `Sync` auto trait, which raw pointers and `UnsafeCell` do
+    /// not implement.
+    Sync,
+
+    /// The `UnsafeCell<T>` struct, the marker of interior mutability.
+    UnsafeCell,
+}
+
+/// Traits that the solver's built-in rules do not depend on, but that
+/// helpers built on top of the solver know the meaning of, such as the
+/// operator traits used by `chalk_solve::ext::binary_op_goal`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum WellKnownTrait {
+    /// The `Deref` trait, which declares the `Target` associated type.
+    Deref,
+
+    /// The `Index<Idx>` trait, which declares the `Output` associated
+    /// type.
+    Index,
+
+    /// The binary operator traits, like `Add<Rhs>`, each of which
+    /// declares the `Output` associated type.
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+/// The table of well-known traits, which the database fills in with
+/// the traits (if any) that play each role; see
+/// `RustIrDatabase::well_known_items`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WellKnownItems {
+    traits: BTreeMap<WellKnownTrait, TraitId>,
+    scalars: BTreeMap<StructId, ScalarKind>,
+}
+
+impl WellKnownItems {
+    pub fn new() -> Self {
+        WellKnownItems::default()
+    }
+
+    /// Records `trait_id` as the trait `item`, returning the trait
+    /// that was previously recorded for `item`, if any.
+    pub fn insert(&mut self, item: WellKnownTrait, trait_id: TraitId) -> Option<TraitId> {
+        self.traits.insert(item, trait_id)
+    }
+
+    pub fn get(&self, item: WellKnownTrait) -> Option<TraitId> {
+        self.traits.get(&item).cloned()
+    }
+
+    /// The traits recorded, with the roles they play.
+    pub fn traits(&self) -> impl Iterator<Item = (WellKnownTrait, TraitId)> + '_ {
+        self.traits
+            .iter()
+            .map(|(&item, &trait_id)| (item, trait_id))
+    }
+
+    /// Records `struct_id` as a scalar type of the given kind (see
+    /// `StructFlags::scalar`).
+    pub fn insert_scalar(&mut self, struct_id: StructId, kind: ScalarKind) {
+        self.scalars.insert(struct_id, kind);
+    }
+
+    pub fn scalar_kind(&self, struct_id: StructId) -> Option<ScalarKind> {
+        self.scalars.get(&struct_id).cloned()
+    }
+
+    /// The scalar types of the program, in order of their ids.
+    pub fn scalars(&self) -> impl Iterator<Item = (StructId, ScalarKind)> + '_ {
+        self.scalars
+            .iter()
+            .map(|(&struct_id, &kind)| (struct_id, kind))
+    }
+}
 
 /// Identifier for an "associated type value" found in some impl.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -25,6 +146,9 @@ pub struct ImplDatum {
     pub binders: Binders<ImplDatumBound>,
     pub impl_type: ImplType,
     pub associated_ty_value_ids: Vec<AssociatedTyValueId>,
+
+    /// The methods defined in the impl.
+    pub methods: Vec<MethodDatum>,
 }
 
 impl ImplDatum {
@@ -43,6 +167,29 @@ pub struct ImplDatumBound {
     pub where_clauses: Vec<QuantifiedWhereClause<ChalkIr>>,
 }
 
+/// Identifier for an inherent impl.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InherentImplId(pub RawId);
+
+/// An inherent impl, `impl<T> Foo<T> where .. { .. }`. The solver has
+/// no rules for these; they are only checked for coherence (see
+/// `chalk_solve::coherence::InherentImplChecker`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InherentImplDatum {
+    pub id: InherentImplId,
+    pub binders: Binders<InherentImplDatumBound>,
+
+    /// The names of the associated functions and constants defined by
+    /// the impl, which share a namespace.
+    pub items: Vec<Identifier>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InherentImplDatumBound {
+    pub self_ty: Ty<ChalkIr>,
+    pub where_clauses: Vec<QuantifiedWhereClause<ChalkIr>>,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ImplType {
     Local,
@@ -71,6 +218,14 @@ impl StructDatum {
     pub fn name(&self) -> TypeName {
         self.id.cast()
     }
+
+    pub fn is_extern_type(&self) -> bool {
+        self.flags.extern_type
+    }
+
+    pub fn scalar_kind(&self) -> Option<ScalarKind> {
+        self.flags.scalar
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -83,6 +238,30 @@ pub struct StructDatumBound {
 pub struct StructFlags {
     pub upstream: bool,
     pub fundamental: bool,
+
+    /// An extern type (`extern { type Foo; }`), whose size and
+    /// contents are unknown: it is not `Sized`, and implements auto
+    /// traits only through explicit impls.
+    pub extern_type: bool,
+
+    /// If this struct stands for one of the primitive scalar types
+    /// (like `u32` or `f64`), which kind of scalar it is. Scalars
+    /// implement `Copy` and `Clone` without any impls being declared;
+    /// the database lists them in `WellKnownItems::scalars`.
+    pub scalar: Option<ScalarKind>,
+}
+
+/// The kinds of primitive scalar types (see `StructFlags::scalar`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ScalarKind {
+    Bool,
+    Char,
+
+    /// The integer types, like `u32` and `isize`.
+    Int,
+
+    /// The floating-point types, `f32` and `f64`.
+    Float,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -98,6 +277,9 @@ pub struct TraitDatum {
 
     /// The id of each associated type defined in the trait.
     pub associated_ty_ids: Vec<TypeId>,
+
+    /// The methods declared in the trait.
+    pub methods: Vec<MethodDatum>,
 }
 
 impl TraitDatum {
@@ -108,6 +290,10 @@ impl TraitDatum {
     pub fn is_non_enumerable_trait(&self) -> bool {
         self.flags.non_enumerable
     }
+
+    pub fn is_sealed(&self) -> bool {
+        self.flags.sealed
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -123,11 +309,20 @@ pub struct TraitDatumBound {
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TraitFlags {
+    /// An auto trait is implemented by each type whose components
+    /// implement it, unless there is an explicit impl of the trait for
+    /// the type. In Rust, it is declared `auto trait Send { }`, or
+    /// with the legacy default impl `impl Send for .. { }`.
     pub auto: bool,
     pub marker: bool,
     pub upstream: bool,
     pub fundamental: bool,
     pub non_enumerable: bool,
+
+    /// A sealed trait can only be implemented in the crate that
+    /// declares it, so there are no downstream impls of it to account
+    /// for in coherence, and (if it is upstream) no local ones either.
+    pub sealed: bool,
 }
 
 /// An inline bound, e.g. `: Foo<K>` in `impl<K, T: Foo<K>> SomeType<T>`.
@@ -175,7 +370,7 @@ impl IntoWhereClauses for QuantifiedInlineBound {
             .into_where_clauses(self_ty)
             .into_iter()
             .map(|wc| Binders {
-                binders: self.binders.clone(),
+                binders: self.binders,
                 value: wc,
             })
             .collect()
@@ -212,6 +407,9 @@ impl TraitBound {
 #[has_type_family(ChalkIr)]
 pub struct ProjectionEqBound {
     pub trait_bound: TraitBound,
+    /// Usually an associated type of `trait_bound`, but for the
+    /// `Fn(A) -> B` sugar this is `FnOnce::Output`, even when the
+    /// bound is `Fn` or `FnMut`.
     pub associated_ty_id: TypeId,
     /// Does not include trait parameters.
     pub parameters: Vec<Parameter<ChalkIr>>,
@@ -220,22 +418,29 @@ pub struct ProjectionEqBound {
 
 impl ProjectionEqBound {
     fn into_where_clauses(&self, self_ty: Ty<ChalkIr>) -> Vec<WhereClause<ChalkIr>> {
-        let trait_ref = self.trait_bound.as_trait_ref(self_ty);
-
-        let mut parameters = self.parameters.clone();
-        parameters.extend(trait_ref.parameters.clone());
-
         vec![
-            WhereClause::Implemented(trait_ref),
-            WhereClause::ProjectionEq(ProjectionEq {
-                projection: ProjectionTy {
-                    associated_ty_id: self.associated_ty_id,
-                    parameters: parameters,
-                },
-                ty: self.value.clone(),
-            }),
+            WhereClause::Implemented(self.trait_bound.as_trait_ref(self_ty.clone())),
+            WhereClause::ProjectionEq(self.as_projection_eq(self_ty)),
         ]
     }
+
+    /// The binding alone, applied to `self_ty`: for `Foo<Item = U>`,
+    /// `ProjectionEq(<self_ty as Foo>::Item = U)`. A bound with several
+    /// bindings, like `Foo<A = X, B = Y>`, is represented by one
+    /// `ProjectionEqBound` per binding, and stands for the
+    /// `Implemented` where clause together with each of these.
+    pub fn as_projection_eq(&self, self_ty: Ty<ChalkIr>) -> ProjectionEq<ChalkIr> {
+        let trait_ref = self.trait_bound.as_trait_ref(self_ty);
+        let mut parameters = self.parameters.clone();
+        parameters.extend(trait_ref.parameters);
+        ProjectionEq {
+            projection: ProjectionTy {
+                associated_ty_id: self.associated_ty_id,
+                parameters,
+            },
+            ty: self.value.clone(),
+        }
+    }
 }
 
 pub trait Anonymize {
@@ -243,11 +448,11 @@ pub trait Anonymize {
     /// which *have* names (`ParameterKind<Identifier>`) to a list of
     /// "anonymous" generic parameters that just preserves their
     /// kinds (`ParameterKind<()>`). Often convenient in lowering.
-    fn anonymize(&self) -> Vec<ParameterKind<()>>;
+    fn anonymize(&self) -> ParameterKinds;
 }
 
 impl Anonymize for [ParameterKind<Identifier>] {
-    fn anonymize(&self) -> Vec<ParameterKind<()>> {
+    fn anonymize(&self) -> ParameterKinds {
         self.iter().map(|pk| pk.map(|_| ())).collect()
     }
 }
@@ -285,6 +490,9 @@ impl<'a> ToParameter for (&'a ParameterKind<()>, usize) {
 ///
 /// * The *parameters* `P0...Pm` are all in scope for this associated type.
 /// * The *bounds* `bounds` are things that the impl must prove to be true.
+///   In turn, code that is generic over `Foo` can assume them (along
+///   with the supertraits of the traits they name) for any type known
+///   to implement `Foo`, without restating them.
 /// * The *where clauses* `where_clauses` are things that the impl can *assume* to be true
 ///   (but which projectors must prove).
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -319,6 +527,11 @@ pub struct AssociatedTyDatumBound {
 
     /// Where clauses that must hold for the projection to be well-formed.
     pub where_clauses: Vec<QuantifiedWhereClause<ChalkIr>>,
+
+    /// The default value of the associated type, if the trait gives
+    /// one (`type Item = u32;`), which impls that do not give a value
+    /// of their own use.
+    pub default: Option<Ty<ChalkIr>>,
 }
 
 impl HasTypeFamily for AssociatedTyDatumBound {
@@ -359,6 +572,57 @@ impl AssociatedTyDatum {
             .flat_map(|b| b.into_where_clauses(self_ty.clone()))
             .collect()
     }
+
+    /// Returns the value that the impl `impl_id` (with datum
+    /// `impl_datum`) takes from the default of this associated type,
+    /// if there is one, as if the impl had given it itself. E.g., for
+    ///
+    /// ```ignore
+    /// trait Iterable { type Iter<'a> = Iter<'a, Self>; }
+    /// impl<T> Iterable for Vec<T> { }
+    /// ```
+    ///
+    /// this is `type Iter<'a> = Iter<'a, Vec<T>>` in the impl. It is up
+    /// to the caller to check that the impl does not give a value of
+    /// its own.
+    pub fn default_value_for(
+        &self,
+        impl_id: ImplId,
+        impl_datum: &ImplDatum,
+    ) -> Option<AssociatedTyValue> {
+        let default = self.binders.value.default.as_ref()?;
+        let trait_parameters = &impl_datum.binders.value.trait_ref.parameters;
+        let own_binders = &self.binders.binders[..self.binders.len() - trait_parameters.len()];
+
+        // The binders of the value are those of the associated type
+        // itself, followed by those of the impl, so the parameters of
+        // the default are the former, then those of the trait ref of
+        // the impl (shifted past the former).
+        let parameters: Vec<_> = own_binders
+            .iter()
+            .zip(0..)
+            .map(|p| p.to_parameter())
+            .chain(trait_parameters.shifted_in(own_binders.len()))
+            .collect();
+        let ty = Binders {
+            binders: self.binders.binders,
+            value: default.clone(),
+        }
+        .substitute(&parameters);
+
+        Some(AssociatedTyValue {
+            impl_id,
+            associated_ty_id: self.id,
+            value: Binders {
+                binders: own_binders
+                    .iter()
+                    .chain(&impl_datum.binders.binders)
+                    .cloned()
+                    .collect(),
+                value: AssociatedTyValueBound { ty },
+            },
+        })
+    }
 }
 
 /// Represents the *value* of an associated type that is assigned
@@ -420,6 +684,51 @@ impl HasTypeFamily for AssociatedTyValueBound {
     type TypeFamily = ChalkIr;
 }
 
+/// The signature of a method declared in a trait or defined in a trait
+/// impl. Only signatures are represented; there are no bodies.
+///
+/// ```ignore
+/// trait Foo<T> {
+///     fn bar<U>(Self, U) -> T where U: Clone; // <-- represents this line!
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodDatum {
+    /// Name of this method.
+    pub name: Identifier,
+
+    /// As for associated types, the binders are the parameters of the
+    /// method itself (`U` above), followed by those of the trait (resp.
+    /// impl) the method belongs to.
+    pub binders: Binders<MethodDatumBound>,
+}
+
+impl MethodDatum {
+    /// The parameters of the method itself, given the number of
+    /// parameters of the trait (resp. impl) that it belongs to.
+    pub fn own_parameters(&self, outer_parameters: usize) -> &[ParameterKind<()>] {
+        let binders = &self.binders.binders;
+        &binders[..binders.len() - outer_parameters]
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Fold)]
+#[has_type_family(ChalkIr)]
+pub struct MethodDatumBound {
+    /// The types of the arguments, including the receiver, if any.
+    pub inputs: Vec<Ty<ChalkIr>>,
+
+    /// The return type; `()` if none is written.
+    pub output: Ty<ChalkIr>,
+
+    /// Where clauses declared on the method, which callers must prove.
+    pub where_clauses: Vec<QuantifiedWhereClause<ChalkIr>>,
+}
+
+impl HasTypeFamily for MethodDatumBound {
+    type TypeFamily = ChalkIr;
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct TypeKind {
     pub sort: TypeSort,
@@ -0,0 +1,43 @@
+//! The supported API of chalk-solve for embedders: the items that an
+//! embedder needs to implement a database and solve goals against it,
+//! at paths that do not depend on how the crate is organized inside.
+//! An embedder that imports from here, rather than from the modules
+//! that define the items, is not broken when they move.
+//!
+//! The items are those re-exported below, together with the versions
+//! of `chalk_ir` and `chalk_rust_ir` that they are defined in terms of
+//! (as `ir` and `rust_ir`). Changes to them follow these rules:
+//!
+//! - An item that moves stays re-exported here under its old name.
+//!   An item that is renamed keeps its old name too, as a
+//!   `#[deprecated]` re-export, for at least one release.
+//! - Removing an item, or changing it in a way that breaks code using
+//!   it, bumps `API_VERSION`.
+//!
+//! The other public modules of the crate (`clauses`, `wf`, and so on)
+//! are not covered, and may change with any release.
+
+pub use chalk_ir as ir;
+pub use chalk_rust_ir as rust_ir;
+
+pub use crate::check::{check_program, ProgramItems, ProgramReport};
+pub use crate::clauses::ClauseOrigin;
+pub use crate::coherence::{
+    CoherenceError, CoherenceSolver, SpecializationGraph, SpecializationPriority,
+};
+pub use crate::coinductive_goal::{CoinductiveGoals, GoalKind};
+pub use crate::ext::{CanonicalExt, GoalExt, ImpliedByEnv};
+pub use crate::frozen::{FreezeError, FrozenProgram};
+pub use crate::infer::unify::UnificationError;
+pub use crate::solve::{
+    AmbiguityCause, CancellationToken, Cancelled, CannotProvePolicy, ClauseFilter, ClauseFilterFn,
+    GoalPass, GoalPipeline, Guidance, SlgOptions, Solution, SolveMode, Solver, SolverChoice,
+};
+pub use crate::{
+    CustomGoalSolution, IllKindedImpl, MissingData, RegionConstraintResolution,
+    RegionConstraintResolver, RustIrDatabase, SyncDatabase,
+};
+
+/// The version of the API of this module, bumped when one of its
+/// items is removed or changed in a way that breaks code using it.
+pub const API_VERSION: u32 = 3;
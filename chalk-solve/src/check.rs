@@ -0,0 +1,195 @@
+//! Checking a whole program at once: the coherence of the impls of
+//! each trait (overlap, orphan rules and inherent impls) and the
+//! well-formedness of each struct, auto trait and impl, which includes
+//! the object safety of the traits that they use as `dyn` types. Each
+//! item is checked on its own, so that one ill-formed item does not
+//! hide the errors of the others, and the errors are collected in a
+//! `ProgramReport`.
+
+use crate::coherence::orphan;
+use crate::coherence::{CoherenceError, CoherenceSolver, InherentImplChecker};
+use crate::wf::{WfError, WfSolver};
+use crate::{RustIrDatabase, SolverChoice};
+use chalk_ir::{ImplId, StructId, TraitId};
+use std::error::Error;
+use std::fmt;
+
+/// The items of the program to check. The database cannot list its
+/// items, so the embedder does; these are usually the items of the
+/// crate being compiled. (Inherent impls are not listed: they are
+/// those of `RustIrDatabase::local_inherent_impls`.)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramItems {
+    /// The traits, whose impls are checked for overlap (see
+    /// `RustIrDatabase::local_impls_to_coherence_check`), and which
+    /// are checked to be well-formed auto traits, if they are auto
+    /// traits.
+    pub traits: Vec<TraitId>,
+    pub structs: Vec<StructId>,
+
+    /// The trait impls, which are checked against the orphan rules and
+    /// for well-formedness.
+    pub impls: Vec<ImplId>,
+}
+
+/// The errors found by `check_program`, each with the item it was
+/// found in, in the order of the items in `ProgramItems`.
+#[derive(Debug, Default)]
+pub struct ProgramReport {
+    /// The traits whose impls overlap, or specialize one another in a
+    /// cycle (see `CoherenceSolver::specialization_priorities`).
+    pub coherence: Vec<(TraitId, CoherenceError)>,
+
+    /// The impls that violate the orphan rules.
+    pub orphans: Vec<(ImplId, CoherenceError)>,
+
+    /// The first item defined by two overlapping inherent impls (see
+    /// `InherentImplChecker`).
+    pub inherent: Option<CoherenceError>,
+
+    pub structs: Vec<(StructId, WfError)>,
+
+    /// The auto traits that are not well-formed (see
+    /// `WfSolver::verify_auto_trait`).
+    pub auto_traits: Vec<(TraitId, WfError)>,
+    pub impls: Vec<(ImplId, WfError)>,
+}
+
+impl ProgramReport {
+    /// True if no errors were found.
+    pub fn is_ok(&self) -> bool {
+        self.errors().next().is_none()
+    }
+
+    /// All the errors, in the order of the fields of the report.
+    pub fn errors(&self) -> impl Iterator<Item = &(dyn Error + 'static)> {
+        fn coherence(error: &CoherenceError) -> &(dyn Error + 'static) {
+            error
+        }
+        fn wf(error: &WfError) -> &(dyn Error + 'static) {
+            error
+        }
+
+        self.coherence
+            .iter()
+            .map(|(_, error)| coherence(error))
+            .chain(self.orphans.iter().map(|(_, error)| coherence(error)))
+            .chain(self.inherent.iter().map(coherence))
+            .chain(self.structs.iter().map(|(_, error)| wf(error)))
+            .chain(self.auto_traits.iter().map(|(_, error)| wf(error)))
+            .chain(self.impls.iter().map(|(_, error)| wf(error)))
+    }
+}
+
+/// One error per line.
+impl fmt::Display for ProgramReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in self.errors() {
+            writeln!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the coherence and well-formedness of the `items` of `db`.
+pub fn check_program<DB: RustIrDatabase>(
+    db: &DB,
+    solver_choice: SolverChoice,
+    items: &ProgramItems,
+) -> ProgramReport {
+    let checker = ProgramChecker::new(db, solver_choice);
+    ProgramReport {
+        coherence: errors(items.traits.iter().map(|&id| (id, checker.coherence(id)))),
+        orphans: errors(items.impls.iter().map(|&id| (id, checker.orphan(id)))),
+        inherent: checker.inherent().err(),
+        structs: errors(
+            items
+                .structs
+                .iter()
+                .map(|&id| (id, checker.struct_decl(id))),
+        ),
+        auto_traits: errors(items.traits.iter().map(|&id| (id, checker.auto_trait(id)))),
+        impls: errors(items.impls.iter().map(|&id| (id, checker.trait_impl(id)))),
+    }
+}
+
+/// Like `check_program`, but checks the items of each kind in
+/// parallel (using rayon). The report is the same.
+#[cfg(feature = "parallel")]
+pub fn check_program_parallel<DB: crate::SyncDatabase>(
+    db: &DB,
+    solver_choice: SolverChoice,
+    items: &ProgramItems,
+) -> ProgramReport {
+    use rayon::prelude::*;
+
+    fn par_errors<Id, E>(ids: &[Id], check: impl Fn(Id) -> Result<(), E> + Sync) -> Vec<(Id, E)>
+    where
+        Id: Copy + Send + Sync,
+        E: Send,
+    {
+        let results: Vec<_> = ids.par_iter().map(|&id| (id, check(id))).collect();
+        errors(results)
+    }
+
+    let checker = ProgramChecker::new(db, solver_choice);
+    ProgramReport {
+        coherence: par_errors(&items.traits, |id| checker.coherence(id)),
+        orphans: par_errors(&items.impls, |id| checker.orphan(id)),
+        inherent: checker.inherent().err(),
+        structs: par_errors(&items.structs, |id| checker.struct_decl(id)),
+        auto_traits: par_errors(&items.traits, |id| checker.auto_trait(id)),
+        impls: par_errors(&items.impls, |id| checker.trait_impl(id)),
+    }
+}
+
+/// The errors among the `results` of checking each item.
+fn errors<Id, E>(results: impl IntoIterator<Item = (Id, Result<(), E>)>) -> Vec<(Id, E)> {
+    results
+        .into_iter()
+        .filter_map(|(id, result)| result.err().map(|error| (id, error)))
+        .collect()
+}
+
+/// The checks of one item each, which `check_program` runs.
+struct ProgramChecker<'db, DB: RustIrDatabase> {
+    db: &'db DB,
+    solver_choice: SolverChoice,
+    wf: WfSolver<'db, DB>,
+}
+
+impl<'db, DB: RustIrDatabase> ProgramChecker<'db, DB> {
+    fn new(db: &'db DB, solver_choice: SolverChoice) -> Self {
+        ProgramChecker {
+            db,
+            solver_choice,
+            wf: WfSolver::new(db, solver_choice),
+        }
+    }
+
+    fn coherence(&self, trait_id: TraitId) -> Result<(), CoherenceError> {
+        CoherenceSolver::new(self.db, self.solver_choice, trait_id)
+            .specialization_priorities()
+            .map(|_| ())
+    }
+
+    fn orphan(&self, impl_id: ImplId) -> Result<(), CoherenceError> {
+        orphan::perform_orphan_check(self.db, self.solver_choice, impl_id)
+    }
+
+    fn inherent(&self) -> Result<(), CoherenceError> {
+        InherentImplChecker::new(self.db, self.solver_choice).check()
+    }
+
+    fn struct_decl(&self, struct_id: StructId) -> Result<(), WfError> {
+        self.wf.verify_struct_decl(struct_id)
+    }
+
+    fn auto_trait(&self, trait_id: TraitId) -> Result<(), WfError> {
+        self.wf.verify_auto_trait(trait_id)
+    }
+
+    fn trait_impl(&self, impl_id: ImplId) -> Result<(), WfError> {
+        self.wf.verify_trait_impl(impl_id)
+    }
+}
@@ -0,0 +1,173 @@
+//! Checks the program clauses that embedders supply directly (see
+//! `RustIrDatabase::custom_clauses`). Clauses derived from the Rust IR
+//! are built by chalk itself, but custom clauses are constructed by
+//! hand, and a mistake in their bound variables makes the solver
+//! misbehave silently -- e.g., a variable that refers past the binders
+//! of its clause ends up unified with whatever happens to be at that
+//! index when the clause is instantiated.
+//!
+//! `Solver` runs these checks on the custom clauses before the first
+//! goal it solves, in debug builds and when the `validate-clauses`
+//! feature is enabled; embedders can also call `validate_clauses`
+//! themselves.
+
+use crate::RustIrDatabase;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{
+    DefaultInferenceFolder, DefaultPlaceholderFolder, DefaultTypeFolder, Fold, FreeVarFolder,
+};
+use chalk_ir::*;
+use std::fmt;
+
+/// A custom program clause that failed validation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidClause {
+    /// The index of the clause within the clauses validated.
+    pub index: usize,
+    pub clause: Box<ProgramClause<ChalkIr>>,
+    pub error: ClauseError,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ClauseError {
+    /// A bound variable does not refer to any of the binders in
+    /// scope. `depth` is its index relative to the binders of the
+    /// clause itself, of which there are `binders`.
+    UnboundVar { depth: usize, binders: usize },
+
+    /// A bound variable is used as a type but refers to a lifetime
+    /// parameter of the clause, or vice versa. `depth` is the index
+    /// of that parameter.
+    IllKinded {
+        depth: usize,
+        expected: ParameterKind<()>,
+        found: ParameterKind<()>,
+    },
+}
+
+impl fmt::Display for InvalidClause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "custom clause #{} (`{:?}`) ", self.index, self.clause)?;
+        match &self.error {
+            ClauseError::UnboundVar { depth, binders } => write!(
+                f,
+                "refers to the bound variable ^{}, but the clause has only {} binder(s)",
+                depth, binders
+            ),
+            ClauseError::IllKinded {
+                depth,
+                expected,
+                found,
+            } => write!(
+                f,
+                "uses its parameter ^{} as a {}, but the parameter is a {}",
+                depth,
+                kind_name(*found),
+                kind_name(*expected)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidClause {}
+
+fn kind_name(kind: ParameterKind<()>) -> &'static str {
+    match kind {
+        ParameterKind::Ty(()) => "type",
+        ParameterKind::Lifetime(()) => "lifetime",
+    }
+}
+
+/// Validates the custom clauses of `db`; see `validate_clauses`.
+pub fn validate_custom_clauses(db: &dyn RustIrDatabase) -> Result<(), InvalidClause> {
+    validate_clauses(&db.custom_clauses())
+}
+
+/// Checks that each of `clauses` is closed: every bound variable in
+/// it refers to a binder in scope, and those that refer to the
+/// binders of the clause itself (i.e., the `forall<..>` at its top)
+/// have the kind that the binder declares. Returns the first clause
+/// that fails these checks.
+///
+/// The kinds of variables bound within the clause (by `forall` goals
+/// or `dyn` types) are not checked.
+pub fn validate_clauses(clauses: &[ProgramClause<ChalkIr>]) -> Result<(), InvalidClause> {
+    for (index, clause) in clauses.iter().enumerate() {
+        let result = match clause {
+            ProgramClause::Implies(implication) => validate_implication(implication, &[]),
+            ProgramClause::ForAll(implication) => {
+                validate_implication(&implication.value, &implication.binders)
+            }
+        };
+        if let Err(error) = result {
+            return Err(InvalidClause {
+                index,
+                clause: Box::new(clause.clone()),
+                error,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn validate_implication(
+    implication: &ProgramClauseImplication<ChalkIr>,
+    binders: &[ParameterKind<()>],
+) -> Result<(), ClauseError> {
+    let mut validator = BoundVarValidator {
+        binders,
+        error: None,
+    };
+    implication.fold_with(&mut validator, 0).unwrap();
+    validator.error.map_or(Ok(()), Err)
+}
+
+/// Records the first bound variable that escapes the binders of the
+/// value folded, or that does not match the kind of its binder.
+struct BoundVarValidator<'b> {
+    binders: &'b [ParameterKind<()>],
+    error: Option<ClauseError>,
+}
+
+impl BoundVarValidator<'_> {
+    fn check(&mut self, depth: usize, found: ParameterKind<()>) {
+        if self.error.is_some() {
+            return;
+        }
+        self.error = match self.binders.get(depth) {
+            None => Some(ClauseError::UnboundVar {
+                depth,
+                binders: self.binders.len(),
+            }),
+            Some(&expected) if expected != found => Some(ClauseError::IllKinded {
+                depth,
+                expected,
+                found,
+            }),
+            Some(_) => None,
+        };
+    }
+}
+
+impl DefaultTypeFolder for BoundVarValidator<'_> {}
+
+impl FreeVarFolder<ChalkIr> for BoundVarValidator<'_> {
+    fn fold_free_var_ty(&mut self, depth: usize, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        self.check(depth, ParameterKind::Ty(()));
+        Ok(Ty::BoundVar(depth + binders))
+    }
+
+    fn fold_free_var_lifetime(
+        &mut self,
+        depth: usize,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        self.check(depth, ParameterKind::Lifetime(()));
+        Ok(Lifetime::BoundVar(depth + binders))
+    }
+}
+
+impl DefaultInferenceFolder for BoundVarValidator<'_> {}
+
+impl DefaultPlaceholderFolder for BoundVarValidator<'_> {}
@@ -2,16 +2,34 @@ use self::builder::ClauseBuilder;
 use self::env_elaborator::elaborate_env_clauses;
 use self::program_clauses::ToProgramClauses;
 use crate::split::Split;
-use crate::RustIrDatabase;
+use crate::{CustomGoalSolution, IllKindedImpl, MissingData, RustIrDatabase};
+use chalk_engine::fallible::Fallible;
 use chalk_ir::cast::Cast;
 use chalk_ir::could_match::CouldMatch;
 use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::*;
 use chalk_ir::*;
-use rustc_hash::FxHashSet;
+use chalk_rust_ir::{ImplDatum, LangItem};
+use rustc_hash::FxHashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+pub(crate) use self::prune::{can_prune_well_formed, prune_well_formed};
+pub(crate) use self::reveal::program_clauses_revealing;
 
 pub mod builder;
+mod builtin_traits;
+mod builtin_types;
+mod dyn_ty;
 mod env_elaborator;
+mod fn_ptr;
+#[cfg(feature = "layout")]
+mod layout;
 pub mod program_clauses;
+mod prune;
+mod reveal;
+mod send_sync;
+mod upcast;
 
 /// For auto-traits, we generate a default rule for every struct,
 /// unless there is a manual impl for that struct given explicitly.
@@ -43,31 +61,59 @@ pub mod program_clauses;
 ///         Implemented(Box<Option<MyList<T>>>: Send).
 /// }
 /// ```
+///
+/// Projections in the field types are not normalized when we match
+/// against them, so each is replaced by a fresh variable that is
+/// related to it through a `ProjectionEq` condition. Given
+/// `struct Wrapper<T> { item: <T as Iterator>::Item }`, we generate:
+///
+/// ```notrust
+/// forall<T, U> {
+///     Implemented(Wrapper<T>: Send) :-
+///         ProjectionEq(<T as Iterator>::Item = U),
+///         Implemented(U: Send).
+/// }
+/// ```
 pub fn push_auto_trait_impls(
     builder: &mut ClauseBuilder<'_>,
     auto_trait_id: TraitId,
     struct_id: StructId,
-) {
+) -> Result<(), MissingData> {
     debug_heading!(
         "push_auto_trait_impls({:?}, {:?})",
         auto_trait_id,
         struct_id
     );
 
-    let struct_datum = &builder.db.struct_datum(struct_id);
+    let struct_datum = &builder.db.try_struct_datum(struct_id)?;
+    let auto_trait_datum = builder.db.try_trait_datum(auto_trait_id)?;
 
     // Must be an auto trait.
-    assert!(builder.db.trait_datum(auto_trait_id).is_auto_trait());
+    assert!(auto_trait_datum.is_auto_trait());
 
     // Auto traits never have generic parameters of their own (apart from `Self`).
-    assert_eq!(builder.db.trait_datum(auto_trait_id).binders.len(), 1);
+    assert_eq!(auto_trait_datum.binders.len(), 1);
 
     // If there is a `impl AutoTrait for Foo<..>` or `impl !AutoTrait
     // for Foo<..>`, where `Foo` is the struct we're looking at, then
     // we don't generate our own rules.
     if builder.db.impl_provided_for(auto_trait_id, struct_id) {
         debug!("impl provided");
-        return;
+        return Ok(());
+    }
+
+    // The contents of an extern type are unknown, so it implements
+    // auto traits only through explicit impls.
+    if struct_datum.is_extern_type() {
+        debug!("extern type");
+        return Ok(());
+    }
+
+    // `UnsafeCell` is not `Sync`, whatever its fields.
+    let rule = send_sync::send_sync_rule(builder.db, auto_trait_id, struct_id.cast());
+    if rule == Some(send_sync::SendSyncRule::Never) {
+        debug!("never implemented");
+        return Ok(());
     }
 
     let binders = struct_datum.binders.map_ref(|b| &b.fields);
@@ -84,32 +130,135 @@ pub fn push_auto_trait_impls(
             parameters: vec![self_ty.cast()],
         };
 
-        // forall<P0..Pn> { // generic parameters from struct
-        //   MyStruct<...>: MyAutoTrait :-
-        //      Field0: MyAutoTrait,
-        //      ...
-        //      FieldN: MyAutoTrait
-        // }
-        builder.push_clause(
-            auto_trait_ref,
-            fields.iter().map(|field_ty| TraitRef {
-                trait_id: auto_trait_id,
-                parameters: vec![field_ty.clone().cast()],
-            }),
-        );
+        // Replace the projections in the fields with new variables
+        // `U0..Um`, which are bound after the struct's parameters.
+        let first_var = builder.placeholders_in_scope().len();
+        let mut replacer = ProjectionReplacer {
+            first_var,
+            projections: vec![],
+        };
+        let fields: Vec<Ty<ChalkIr>> = fields
+            .iter()
+            .map(|field_ty| field_ty.fold_with(&mut replacer, 0).unwrap())
+            .collect();
+        let projections = replacer.projections;
+
+        let variables = Binders {
+            binders: vec![ParameterKind::Ty(()); projections.len()].into(),
+            value: PhantomData::<ChalkIr>,
+        };
+        builder.push_binders(&variables, |builder, PhantomData| {
+            let variables = &builder.placeholders_in_scope()[first_var..];
+
+            // forall<P0..Pn, U0..Um> { // generic parameters from struct
+            //   MyStruct<...>: MyAutoTrait :-
+            //      ProjectionEq(Projection0 = U0),
+            //      ...
+            //      ProjectionEq(ProjectionM = Um),
+            //      Field0: MyAutoTrait,
+            //      ...
+            //      FieldN: MyAutoTrait
+            // }
+            let conditions: Vec<Goal<ChalkIr>> = projections
+                .into_iter()
+                .zip(variables)
+                .map(|(projection, variable)| {
+                    WhereClause::ProjectionEq(ProjectionEq {
+                        projection,
+                        ty: variable.assert_ty_ref().clone(),
+                    })
+                    .cast()
+                })
+                .chain(fields.into_iter().map(|field_ty| {
+                    TraitRef {
+                        trait_id: auto_trait_id,
+                        parameters: vec![field_ty.cast()],
+                    }
+                    .cast()
+                }))
+                .collect();
+            builder.push_clause(auto_trait_ref, conditions);
+        });
     });
+    Ok(())
+}
+
+/// Replaces the projections that appear in a type (outside of any
+/// binders within it) with the bound variables `first_var..`, in
+/// order, recording the projections that were replaced.
+struct ProjectionReplacer {
+    first_var: usize,
+    projections: Vec<ProjectionTy<ChalkIr>>,
+}
+
+impl TypeFolder<ChalkIr> for ProjectionReplacer {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        match ty {
+            Ty::Projection(projection) if binders == 0 => {
+                let var = Ty::BoundVar(self.first_var + self.projections.len());
+                self.projections.push(projection.clone());
+                Ok(var)
+            }
+            _ => super_fold_ty(self, ty, binders),
+        }
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for ProjectionReplacer {}
+
+impl DefaultInferenceFolder for ProjectionReplacer {}
+
+impl DefaultPlaceholderFolder for ProjectionReplacer {}
+
+/// Where a program clause came from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ClauseOrigin {
+    /// One of the `RustIrDatabase::custom_clauses`.
+    Custom,
+
+    /// The declaration of a trait.
+    Trait(TraitId),
+
+    /// The declaration of a struct.
+    Struct(StructId),
+
+    /// The declaration of an associated type.
+    AssociatedTy(TypeId),
+
+    /// An impl, or one of its associated type values.
+    Impl(ImplId),
+
+    /// The environment in which the goal is proven.
+    Environment,
+
+    /// A rule built into the solver, such as the structural impls of
+    /// auto traits; the string names the rule.
+    BuiltIn(&'static str),
 }
 
 /// Given some goal `goal` that must be proven, along with
 /// its `environment`, figures out the program clauses that apply
-/// to this goal from the Rust program. So for example if the goal
-/// is `Implemented(T: Clone)`, then this function might return clauses
-/// derived from the trait `Clone` and its impls.
-pub(crate) fn program_clauses_for_goal<'db>(
-    db: &'db dyn RustIrDatabase,
+/// to this goal from the Rust program, each with its origin. So for
+/// example if the goal is `Implemented(T: Clone)`, then this function
+/// might return clauses derived from the trait `Clone` and its impls.
+/// If `goal` fails, the clauses with origin `Impl` name the impls
+/// whose where clauses could not be proven.
+///
+/// Fails if the database cannot supply an item needed to generate
+/// the clauses.
+pub fn program_clauses_with_origins(
+    db: &dyn RustIrDatabase,
     environment: &Environment<ChalkIr>,
     goal: &DomainGoal<ChalkIr>,
-) -> Vec<ProgramClause<ChalkIr>> {
+) -> Result<Vec<(ProgramClause<ChalkIr>, ClauseOrigin)>, MissingData> {
     debug_heading!(
         "program_clauses_for_goal(goal={:?}, environment={:?})",
         goal,
@@ -117,14 +266,110 @@ pub(crate) fn program_clauses_for_goal<'db>(
     );
 
     let mut vec = vec![];
+    let mut origins = vec![];
     vec.extend(db.custom_clauses());
-    program_clauses_that_could_match(db, environment, goal, &mut vec);
-    program_clauses_for_env(db, environment, &mut vec);
-    vec.retain(|c| c.could_match(goal));
+    origins.resize(vec.len(), ClauseOrigin::Custom);
+    program_clauses_that_could_match(db, environment, goal, &mut vec, &mut origins)?;
+    program_clauses_for_env(db, environment, &mut vec)?;
+    origins.resize(vec.len(), ClauseOrigin::Environment);
+
+    let vec: Vec<_> = vec
+        .into_iter()
+        .zip(origins)
+        .filter(|(c, _)| c.could_match(goal))
+        .collect();
 
     debug!("vec = {:#?}", vec);
 
-    vec
+    Ok(vec)
+}
+
+/// Like `program_clauses_with_origins`, but without leaving out the
+/// clauses that cannot match `goal`, so that diagnostics can say why
+/// they do not (see `Solver::rejected_candidates`). This includes
+/// all the custom clauses and the clauses of the environment,
+/// whatever they prove.
+pub(crate) fn unfiltered_program_clauses_with_origins(
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    goal: &DomainGoal<ChalkIr>,
+) -> Result<Vec<(ProgramClause<ChalkIr>, ClauseOrigin)>, MissingData> {
+    let mut vec = vec![];
+    let mut origins = vec![];
+    vec.extend(db.custom_clauses());
+    origins.resize(vec.len(), ClauseOrigin::Custom);
+    program_clauses_that_could_match(db, environment, goal, &mut vec, &mut origins)?;
+
+    // The database may have left out the impls that cannot match the
+    // goal (see `RustIrDatabase::impls_for_trait`), so ask again with
+    // parameters that any impl matches.
+    if let DomainGoal::Holds(WhereClause::Implemented(trait_ref)) = goal {
+        let unknown: Vec<Parameter<ChalkIr>> = (0..)
+            .map(InferenceVar::from)
+            .zip(&trait_ref.parameters)
+            .map(|(var, parameter)| match parameter.as_ref() {
+                ParameterKind::Ty(_) => var.to_ty::<ChalkIr>().cast(),
+                ParameterKind::Lifetime(_) => var.to_lifetime::<ChalkIr>().cast(),
+            })
+            .collect();
+        for (impl_id, impl_datum) in impls_for_trait(db, trait_ref.trait_id, &unknown)? {
+            if origins.contains(&ClauseOrigin::Impl(impl_id)) {
+                continue;
+            }
+            let builder = &mut ClauseBuilder::with_origins(db, &mut vec, &mut origins);
+            builder.with_origin(ClauseOrigin::Impl(impl_id), |builder| {
+                impl_datum.to_program_clauses(builder)
+            })?;
+        }
+    }
+
+    program_clauses_for_env(db, environment, &mut vec)?;
+    origins.resize(vec.len(), ClauseOrigin::Environment);
+    Ok(vec.into_iter().zip(origins).collect())
+}
+
+/// Returns the impls that `db.impls_for_trait` gives for a goal with
+/// the given trait and parameters, with their data. Impls whose trait
+/// ref does not have the kinds of `parameters` could not be unified
+/// with the goal, so they are left out (and reported through
+/// `RustIrDatabase::ill_kinded_impl`).
+pub(crate) fn impls_for_trait(
+    db: &dyn RustIrDatabase,
+    trait_id: TraitId,
+    parameters: &[Parameter<ChalkIr>],
+) -> Result<Vec<(ImplId, Arc<ImplDatum>)>, MissingData> {
+    let mut impls = vec![];
+    for impl_id in db.impls_for_trait(trait_id, parameters) {
+        let impl_datum = db.try_impl_datum(impl_id)?;
+        match check_kinds(parameters, &impl_datum.binders.value.trait_ref.parameters) {
+            Ok(()) => impls.push((impl_id, impl_datum)),
+            Err(error) => {
+                debug!("impls_for_trait: skipping {:?}: {}", impl_id, error);
+                db.ill_kinded_impl(impl_id, error);
+            }
+        }
+    }
+    Ok(impls)
+}
+
+fn check_kinds(
+    expected: &[Parameter<ChalkIr>],
+    found: &[Parameter<ChalkIr>],
+) -> Result<(), IllKindedImpl> {
+    if expected.len() != found.len() {
+        return Err(IllKindedImpl::ParameterCount {
+            expected: expected.len(),
+            found: found.len(),
+        });
+    }
+    match expected
+        .iter()
+        .zip(found)
+        .position(|(a, b)| a.is_ty() != b.is_ty())
+    {
+        Some(index) => Err(IllKindedImpl::ParameterKind { index }),
+        None => Ok(()),
+    }
 }
 
 /// Returns a set of program clauses that could possibly match
@@ -136,8 +381,9 @@ fn program_clauses_that_could_match(
     environment: &Environment<ChalkIr>,
     goal: &DomainGoal<ChalkIr>,
     clauses: &mut Vec<ProgramClause<ChalkIr>>,
-) {
-    let builder = &mut ClauseBuilder::new(db, clauses);
+    origins: &mut Vec<ClauseOrigin>,
+) -> Result<(), MissingData> {
+    let builder = &mut ClauseBuilder::with_origins(db, clauses, origins);
 
     match goal {
         DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => {
@@ -145,21 +391,108 @@ fn program_clauses_that_could_match(
 
             // This is needed for the coherence related impls, as well
             // as for the `Implemented(Foo) :- FromEnv(Foo)` rule.
-            db.trait_datum(trait_id).to_program_clauses(builder);
+            let trait_datum = db.try_trait_datum(trait_id)?;
+            builder.with_origin(ClauseOrigin::Trait(trait_id), |builder| {
+                trait_datum.to_program_clauses(builder)
+            })?;
+
+            for (impl_id, impl_datum) in impls_for_trait(db, trait_id, &trait_ref.parameters)? {
+                builder.with_origin(ClauseOrigin::Impl(impl_id), |builder| {
+                    impl_datum.to_program_clauses(builder)
+                })?;
+            }
+
+            // Function pointers implement the `Fn` traits (whatever
+            // the self type, so that it may be inferred) and the auto
+            // traits.
+            let self_is_fn_ptr = match trait_ref.parameters[0].assert_ty_ref() {
+                Ty::Apply(apply) => apply.name == TypeName::FnPtr,
+                Ty::InferenceVar(_) => true,
+                _ => false,
+            };
+            if self_is_fn_ptr {
+                builder.with_origin(ClauseOrigin::BuiltIn("fn pointer"), |builder| {
+                    fn_ptr::push_fn_ptr_impls(builder, trait_id)
+                })?;
+            }
+
+            // The scalar types implement `Copy` and `Clone` (again
+            // whatever the self type).
+            if db.lang_item(LangItem::Copy) == Some(trait_id.into())
+                || db.lang_item(LangItem::Clone) == Some(trait_id.into())
+            {
+                builder.with_origin(ClauseOrigin::BuiltIn("scalar"), |builder| {
+                    builtin_traits::push_scalar_impls(builder, trait_id)
+                });
+            }
 
-            for impl_id in db.impls_for_trait(trait_ref.trait_id, &trait_ref.parameters) {
-                db.impl_datum(impl_id).to_program_clauses(builder);
+            // `str`, slices, arrays, references and raw pointers have
+            // built-in impls of `Sized`, `Copy`, `Clone` and the auto
+            // traits.
+            if let Ty::Apply(apply) = trait_ref.parameters[0].assert_ty_ref() {
+                if let TypeName::Builtin(builtin) = apply.name {
+                    builder.with_origin(ClauseOrigin::BuiltIn("built-in type"), |builder| {
+                        builtin_types::push_builtin_type_impls(builder, trait_id, builtin)
+                    })?;
+                }
             }
 
             // If this is a `Foo: Send` (or any auto-trait), then add
-            // the automatic impls for `Foo`.
-            let trait_datum = db.trait_datum(trait_id);
+            // the automatic impls for `Foo`. `Box` is special: its
+            // impls are built-in (and cover some non-auto traits too).
+            let box_id = db.lang_item(LangItem::OwnedBox);
+            if let Ty::Apply(apply) = trait_ref.parameters[0].assert_ty_ref() {
+                if let TypeName::TypeKindId(TypeKindId::StructId(struct_id)) = apply.name {
+                    if box_id == Some(struct_id.into()) {
+                        builder.with_origin(ClauseOrigin::BuiltIn("Box"), |builder| {
+                            builtin_traits::push_owned_box_impls(builder, trait_id, struct_id)
+                        })?;
+                    }
+                }
+            }
+            // `Sized` is implemented by structs (except extern types)
+            // and by tuples of sized types.
+            if db.lang_item(LangItem::Sized) == Some(trait_id.into()) {
+                if let Ty::Apply(apply) = trait_ref.parameters[0].assert_ty_ref() {
+                    match apply.name {
+                        TypeName::TypeKindId(TypeKindId::StructId(struct_id)) => builder
+                            .with_origin(ClauseOrigin::BuiltIn("Sized"), |builder| {
+                                builtin_traits::push_sized_impls(builder, trait_id, struct_id)
+                            })?,
+                        TypeName::Tuple(arity) => {
+                            builder.with_origin(ClauseOrigin::BuiltIn("tuple"), |builder| {
+                                builtin_traits::push_tuple_auto_trait_impls(
+                                    builder, trait_id, arity,
+                                )
+                            })
+                        }
+                        _ => {}
+                    }
+                }
+            }
             if trait_datum.is_auto_trait() {
                 match trait_ref.parameters[0].assert_ty_ref() {
-                    Ty::Apply(apply) => {
-                        if let TypeName::TypeKindId(TypeKindId::StructId(struct_id)) = apply.name {
-                            push_auto_trait_impls(builder, trait_id, struct_id);
+                    Ty::Apply(apply) => match apply.name {
+                        TypeName::TypeKindId(TypeKindId::StructId(struct_id))
+                            if box_id != Some(struct_id.into()) =>
+                        {
+                            builder.with_origin(ClauseOrigin::BuiltIn("auto trait"), |builder| {
+                                push_auto_trait_impls(builder, trait_id, struct_id)
+                            })?
                         }
+                        TypeName::Tuple(arity) => {
+                            builder.with_origin(ClauseOrigin::BuiltIn("tuple"), |builder| {
+                                builtin_traits::push_tuple_auto_trait_impls(
+                                    builder, trait_id, arity,
+                                )
+                            })
+                        }
+                        _ => {}
+                    },
+                    Ty::Dyn(bounds) => {
+                        builder.with_origin(ClauseOrigin::BuiltIn("dyn"), |builder| {
+                            builtin_traits::push_dyn_auto_trait_impls(builder, trait_id, bounds)
+                        })
                     }
                     Ty::InferenceVar(_) => {
                         panic!("auto-traits should flounder if nothing is known")
@@ -197,6 +530,11 @@ fn program_clauses_that_could_match(
             // forall<'a> { ProjectionEq(<dyn Fn(&u8) as Fn<'a>>::Output, ()) },
             // ```
             //
+            // We also generate clauses for the supertraits of `Fn`,
+            // and for the bounds of its associated types (see the
+            // `dyn_ty` module). These are needed if the self type is
+            // a projection out of a `dyn` type, too.
+            //
             // FIXME. This is presently rather wasteful, in that we
             // don't check that the these program clauses we are
             // generating are actually relevant to the goal `goal`
@@ -210,44 +548,30 @@ fn program_clauses_that_could_match(
             // that goal, because they let us prove other things but
             // not `Clone`.
             let self_ty = trait_ref.self_type_parameter().unwrap(); // This cannot be None
-            match &self_ty {
-                Ty::Opaque(exists_qwcs) | Ty::Dyn(exists_qwcs) => {
-                    // In this arm, `self_ty` is the `dyn Fn(&u8)`,
-                    // and `exists_qwcs` is the `exists<T> { .. }`
-                    // clauses shown above.
-
-                    for exists_qwc in exists_qwcs.into_iter() {
-                        // Replace the `T` from `exists<T> { .. }` with `self_ty`,
-                        // yielding clases like
-                        //
-                        // ```
-                        // forall<'a> { Implemented(dyn Fn(&u8): Fn<(&'a u8)>) }
-                        // ```
-                        let qwc = exists_qwc.substitute(&[self_ty.clone().cast()]);
-
-                        builder.push_binders(&qwc, |builder, wc| {
-                            builder.push_fact(wc);
-                        });
-                    }
-                }
-                _ => {}
-            }
+            builder.with_origin(ClauseOrigin::BuiltIn("dyn"), |builder| {
+                dyn_ty::push_dyn_ty_clauses(builder, &self_ty)
+            })?;
 
-            // TODO sized, unsize_trait, builtin impls?
+            // TODO unsize_trait, builtin impls?
         }
         DomainGoal::Holds(WhereClause::ProjectionEq(projection_predicate)) => {
-            db.associated_ty_data(projection_predicate.projection.associated_ty_id)
-                .to_program_clauses(builder);
+            push_associated_ty_clauses(builder, projection_predicate.projection.associated_ty_id)?;
+
+            // If the self type is a `dyn` type, its bounds may say
+            // what the associated type is, e.g. `dyn Iterator<Item = u32>`.
+            let projection_ty = Ty::Projection(projection_predicate.projection.clone());
+            builder.with_origin(ClauseOrigin::BuiltIn("dyn"), |builder| {
+                dyn_ty::push_dyn_ty_clauses(builder, &projection_ty)
+            })?;
         }
         DomainGoal::WellFormed(WellFormed::Trait(trait_predicate)) => {
-            db.trait_datum(trait_predicate.trait_id)
-                .to_program_clauses(builder);
+            push_trait_clauses(builder, trait_predicate.trait_id)?
         }
         DomainGoal::WellFormed(WellFormed::Ty(ty))
         | DomainGoal::IsUpstream(ty)
-        | DomainGoal::DownstreamType(ty) => match_ty(builder, environment, ty),
+        | DomainGoal::DownstreamType(ty) => match_ty(builder, environment, ty)?,
         DomainGoal::IsFullyVisible(ty) | DomainGoal::IsLocal(ty) => {
-            match_ty(builder, environment, ty)
+            match_ty(builder, environment, ty)?
         }
         DomainGoal::FromEnv(_) => (), // Computed in the environment
         DomainGoal::Normalize(Normalize { projection, ty: _ }) => {
@@ -262,20 +586,60 @@ fn program_clauses_that_could_match(
             //     type Item = Bar; // <-- associated type value
             // }
             // ```
-            let associated_ty_datum = db.associated_ty_data(projection.associated_ty_id);
+            let associated_ty_datum = db.try_associated_ty_data(projection.associated_ty_id)?;
             let trait_id = associated_ty_datum.trait_id;
-            let trait_parameters = db.trait_parameters_from_projection(projection);
+            let trait_parameters = db.try_trait_parameters_from_projection(projection)?;
             push_program_clauses_for_associated_type_values_in_impls_of(
                 builder,
                 trait_id,
                 trait_parameters,
-            );
+            )?;
+            builder.with_origin(ClauseOrigin::BuiltIn("fn pointer"), |builder| {
+                fn_ptr::push_fn_ptr_output(builder, projection.associated_ty_id)
+            })?;
         }
-        DomainGoal::LocalImplAllowed(trait_ref) => db
-            .trait_datum(trait_ref.trait_id)
-            .to_program_clauses(builder),
+        DomainGoal::LocalImplAllowed(trait_ref) => push_trait_clauses(builder, trait_ref.trait_id)?,
         DomainGoal::Compatible(()) => (),
+        DomainGoal::Custom(custom_goal) => builder
+            .with_origin(ClauseOrigin::BuiltIn("custom goal"), |builder| {
+                push_custom_goal_clauses(builder, custom_goal)
+            }),
+        #[cfg(feature = "layout")]
+        DomainGoal::LayoutCompatible(layout_compatible) => builder
+            .with_origin(ClauseOrigin::BuiltIn("layout"), |builder| {
+                layout::push_layout_compatible_clauses(builder, layout_compatible)
+            })?,
+        #[cfg(not(feature = "layout"))]
+        DomainGoal::LayoutCompatible(_) => (),
+        DomainGoal::Upcast(upcast) => builder
+            .with_origin(ClauseOrigin::BuiltIn("upcast"), |builder| {
+                upcast::push_upcast_clauses(builder, upcast)
+            })?,
     };
+
+    Ok(())
+}
+
+fn push_trait_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    trait_id: TraitId,
+) -> Result<(), MissingData> {
+    let trait_datum = builder.db.try_trait_datum(trait_id)?;
+    builder.with_origin(ClauseOrigin::Trait(trait_id), |builder| {
+        trait_datum.to_program_clauses(builder)
+    })?;
+    Ok(())
+}
+
+fn push_associated_ty_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    associated_ty_id: TypeId,
+) -> Result<(), MissingData> {
+    let associated_ty_datum = builder.db.try_associated_ty_data(associated_ty_id)?;
+    builder.with_origin(ClauseOrigin::AssociatedTy(associated_ty_id), |builder| {
+        associated_ty_datum.to_program_clauses(builder)
+    })?;
+    Ok(())
 }
 
 /// Generate program clauses from the associated-type values
@@ -296,7 +660,7 @@ fn push_program_clauses_for_associated_type_values_in_impls_of(
     builder: &mut ClauseBuilder<'_>,
     trait_id: TraitId,
     trait_parameters: &[Parameter<ChalkIr>],
-) {
+) -> Result<(), MissingData> {
     debug_heading!(
         "push_program_clauses_for_associated_type_values_in_impls_of(\
          trait_id={:?}, \
@@ -305,20 +669,41 @@ fn push_program_clauses_for_associated_type_values_in_impls_of(
         trait_parameters,
     );
 
-    for impl_id in builder.db.impls_for_trait(trait_id, trait_parameters) {
-        let impl_datum = builder.db.impl_datum(impl_id);
+    let trait_datum = builder.db.try_trait_datum(trait_id)?;
+    for (impl_id, impl_datum) in impls_for_trait(builder.db, trait_id, trait_parameters)? {
         if !impl_datum.is_positive() {
             continue;
         }
 
         debug!("impl_id = {:?}", impl_id);
 
+        let mut defined = vec![];
         for &atv_id in &impl_datum.associated_ty_value_ids {
-            let atv = builder.db.associated_ty_value(atv_id);
+            let atv = builder.db.try_associated_ty_value(atv_id)?;
             debug!("atv_id = {:?} atv = {:#?}", atv_id, atv);
-            atv.to_program_clauses(builder);
+            builder.with_origin(ClauseOrigin::Impl(impl_id), |builder| {
+                atv.to_program_clauses(builder)
+            })?;
+            defined.push(atv.associated_ty_id);
+        }
+
+        // The associated types that the impl leaves out take their
+        // default values from the trait, if it has any.
+        for &associated_ty_id in &trait_datum.associated_ty_ids {
+            if defined.contains(&associated_ty_id) {
+                continue;
+            }
+            let associated_ty = builder.db.try_associated_ty_data(associated_ty_id)?;
+            if let Some(atv) = associated_ty.default_value_for(impl_id, &impl_datum) {
+                debug!("default atv = {:#?}", atv);
+                builder.with_origin(ClauseOrigin::Impl(impl_id), |builder| {
+                    atv.to_program_clauses(builder)
+                })?;
+            }
         }
     }
+
+    Ok(())
 }
 
 /// Examine `T` and push clauses that may be relevant to proving the
@@ -332,40 +717,154 @@ fn push_program_clauses_for_associated_type_values_in_impls_of(
 ///
 /// Note that the type `T` must not be an unbound inference variable;
 /// earlier parts of the logic should "flounder" in that case.
-fn match_ty(builder: &mut ClauseBuilder<'_>, environment: &Environment<ChalkIr>, ty: &Ty<ChalkIr>) {
+fn match_ty(
+    builder: &mut ClauseBuilder<'_>,
+    environment: &Environment<ChalkIr>,
+    ty: &Ty<ChalkIr>,
+) -> Result<(), MissingData> {
     match ty {
         Ty::Apply(application_ty) => match application_ty.name {
-            TypeName::TypeKindId(type_kind_id) => match_type_kind(builder, type_kind_id),
+            TypeName::TypeKindId(type_kind_id) => match_type_kind(builder, type_kind_id)?,
             TypeName::Placeholder(_) | TypeName::Error => {}
-            TypeName::AssociatedType(type_id) => builder
-                .db
-                .associated_ty_data(type_id)
-                .to_program_clauses(builder),
+            TypeName::Tuple(arity) => builder
+                .with_origin(ClauseOrigin::BuiltIn("tuple"), |builder| {
+                    push_tuple_clauses(builder, arity)
+                }),
+            TypeName::FnPtr => builder
+                .with_origin(ClauseOrigin::BuiltIn("fn pointer"), |builder| {
+                    fn_ptr::push_fn_ptr_clauses(builder)
+                }),
+            TypeName::Builtin(builtin) => builder
+                .with_origin(ClauseOrigin::BuiltIn("built-in type"), |builder| {
+                    builtin_types::push_builtin_type_clauses(builder, builtin)
+                }),
+            TypeName::OpaqueConst(name) => builder
+                .with_origin(ClauseOrigin::BuiltIn("const"), |builder| {
+                    push_opaque_const_clauses(builder, name, &application_ty.parameters)
+                }),
+            TypeName::AssociatedType(type_id) => push_associated_ty_clauses(builder, type_id)?,
         },
-        Ty::Projection(projection_ty) => builder
-            .db
-            .associated_ty_data(projection_ty.associated_ty_id)
-            .to_program_clauses(builder),
-        Ty::ForAll(quantified_ty) => match_ty(builder, environment, &quantified_ty.ty),
+        Ty::Projection(projection_ty) => {
+            push_associated_ty_clauses(builder, projection_ty.associated_ty_id)?
+        }
+        Ty::ForAll(quantified_ty) => match_ty(builder, environment, &quantified_ty.ty)?,
         Ty::BoundVar(_) => {}
         Ty::InferenceVar(_) => panic!("should have floundered"),
         Ty::Dyn(_) | Ty::Opaque(_) => {}
     }
+    Ok(())
 }
 
-fn match_type_kind(builder: &mut ClauseBuilder<'_>, type_kind_id: TypeKindId) {
+fn match_type_kind(
+    builder: &mut ClauseBuilder<'_>,
+    type_kind_id: TypeKindId,
+) -> Result<(), MissingData> {
     match type_kind_id {
-        TypeKindId::TypeId(type_id) => builder
-            .db
-            .associated_ty_data(type_id)
-            .to_program_clauses(builder),
-        TypeKindId::TraitId(trait_id) => {
-            builder.db.trait_datum(trait_id).to_program_clauses(builder)
+        TypeKindId::TypeId(type_id) => push_associated_ty_clauses(builder, type_id)?,
+        TypeKindId::TraitId(trait_id) => push_trait_clauses(builder, trait_id)?,
+        TypeKindId::StructId(struct_id) => {
+            let struct_datum = builder.db.try_struct_datum(struct_id)?;
+            builder.with_origin(ClauseOrigin::Struct(struct_id), |builder| {
+                struct_datum.to_program_clauses(builder)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Tuples have no declaration of their own, so we generate their
+/// clauses directly. A tuple is well-formed and fully visible if its
+/// element types are, and it is always upstream (it is defined in the
+/// standard library, and is not fundamental):
+///
+/// ```notrust
+/// forall<T..> { WellFormed((T..)) :- WellFormed(T).. }
+/// forall<T..> { IsFullyVisible((T..)) :- IsFullyVisible(T).. }
+/// forall<T..> { IsUpstream((T..)) }
+/// ```
+fn push_tuple_clauses(builder: &mut ClauseBuilder<'_>, arity: usize) {
+    let binders = Binders {
+        binders: vec![ParameterKind::Ty(()); arity].into(),
+        value: PhantomData::<ChalkIr>,
+    };
+    builder.push_binders(&binders, |builder, PhantomData| {
+        let parameters = builder.placeholders_in_scope().to_vec();
+        let tuple_ty: Ty<_> = ApplicationTy {
+            name: TypeName::Tuple(arity),
+            parameters: parameters.clone(),
         }
-        TypeKindId::StructId(struct_id) => builder
-            .db
-            .struct_datum(struct_id)
-            .to_program_clauses(builder),
+        .cast();
+        let element_tys = || parameters.iter().map(|p| p.assert_ty_ref().clone());
+
+        builder.push_clause(
+            WellFormed::Ty(tuple_ty.clone()),
+            element_tys().map(WellFormed::Ty),
+        );
+        builder.push_clause(
+            DomainGoal::IsFullyVisible(tuple_ty.clone()),
+            element_tys().map(DomainGoal::IsFullyVisible),
+        );
+        builder.push_fact(DomainGoal::IsUpstream(tuple_ty));
+    });
+}
+
+/// Opaque constants are well-formed and fully visible if the
+/// parameters they are applied to are; they are neither local nor
+/// upstream, as the expression they stand for may well mention types
+/// of both.
+///
+/// ```notrust
+/// forall<P..> { WellFormed(const N<P..>) :- WellFormed(P).. }
+/// forall<P..> { IsFullyVisible(const N<P..>) :- IsFullyVisible(P).. }
+/// ```
+///
+/// (Lifetime parameters are not mentioned in the conditions.)
+fn push_opaque_const_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    name: Identifier,
+    parameters: &[Parameter<ChalkIr>],
+) {
+    let binders = Binders {
+        binders: parameters
+            .iter()
+            .map(|p| match p.as_ref() {
+                ParameterKind::Ty(_) => ParameterKind::Ty(()),
+                ParameterKind::Lifetime(_) => ParameterKind::Lifetime(()),
+            })
+            .collect(),
+        value: PhantomData::<ChalkIr>,
+    };
+    builder.push_binders(&binders, |builder, PhantomData| {
+        let parameters = builder.placeholders_in_scope().to_vec();
+        let const_ty: Ty<_> = ApplicationTy {
+            name: TypeName::OpaqueConst(name),
+            parameters: parameters.clone(),
+        }
+        .cast();
+        let tys = || parameters.iter().filter_map(|p| p.as_ref().ty().cloned());
+
+        builder.push_clause(WellFormed::Ty(const_ty.clone()), tys().map(WellFormed::Ty));
+        builder.push_clause(
+            DomainGoal::IsFullyVisible(const_ty),
+            tys().map(DomainGoal::IsFullyVisible),
+        );
+    });
+}
+
+/// Asks the database whether the (inference-variable free) custom
+/// goal `goal` holds, and turns the answer into a program clause:
+///
+/// - if the goal holds subject to obligations `O..`, we push `goal :- O..`;
+/// - if the database cannot decide, we push `goal :- CannotProve`;
+/// - otherwise, we push nothing, and so the goal fails.
+fn push_custom_goal_clauses(builder: &mut ClauseBuilder<'_>, goal: &CustomGoal<ChalkIr>) {
+    let consequence = DomainGoal::Custom(goal.clone());
+    match builder.db.solve_custom_goal(goal) {
+        CustomGoalSolution::Holds(obligations) => builder.push_clause(consequence, obligations),
+        CustomGoalSolution::Ambiguous => {
+            builder.push_clause(consequence, Some(Goal::CannotProve(())))
+        }
+        CustomGoalSolution::NoSolution => {}
     }
 }
 
@@ -373,20 +872,35 @@ fn program_clauses_for_env<'db>(
     db: &'db dyn RustIrDatabase,
     environment: &Environment<ChalkIr>,
     clauses: &mut Vec<ProgramClause<ChalkIr>>,
-) {
-    let mut last_round = FxHashSet::default();
-    elaborate_env_clauses(db, &environment.clauses, &mut last_round);
+) -> Result<(), MissingData> {
+    let closure = elaborate_env(db, &environment.clauses)?;
+    clauses.extend(closure.into_keys());
+    Ok(())
+}
+
+/// Returns the clauses implied by the `FromEnv` facts in
+/// `env_clauses` (transitively), each with the declaration that it
+/// was derived from. The `env_clauses` themselves are not included,
+/// unless they are also implied by others.
+pub(crate) fn elaborate_env(
+    db: &dyn RustIrDatabase,
+    env_clauses: &Vec<ProgramClause<ChalkIr>>,
+) -> Result<FxHashMap<ProgramClause<ChalkIr>, ClauseOrigin>, MissingData> {
+    let mut last_round = FxHashMap::default();
+    elaborate_env_clauses(db, env_clauses, &mut last_round)?;
 
     let mut closure = last_round.clone();
-    let mut next_round = FxHashSet::default();
+    let mut next_round = FxHashMap::default();
     while !last_round.is_empty() {
-        elaborate_env_clauses(db, &last_round.drain().collect(), &mut next_round);
-        last_round.extend(
-            next_round
-                .drain()
-                .filter(|clause| closure.insert(clause.clone())),
-        );
+        let clauses = last_round.drain().map(|(clause, _)| clause).collect();
+        elaborate_env_clauses(db, &clauses, &mut next_round)?;
+        for (clause, origin) in next_round.drain() {
+            if !closure.contains_key(&clause) {
+                closure.insert(clause.clone(), origin);
+                last_round.insert(clause, origin);
+            }
+        }
     }
 
-    clauses.extend(closure.drain())
+    Ok(closure)
 }
@@ -1,4 +1,5 @@
 use crate::cast::{Cast, CastTo, Caster};
+use crate::clauses::ClauseOrigin;
 use crate::RustIrDatabase;
 use chalk_ir::family::{ChalkIr, HasTypeFamily};
 use chalk_ir::fold::Fold;
@@ -10,10 +11,15 @@ use std::marker::PhantomData;
 /// program clauses. It takes ownership of the output vector while it
 /// lasts, and offers methods like `push_clause` and so forth to
 /// append to it.
+///
+/// A builder created with `with_origins` also records, for each
+/// clause, where it came from (see `with_origin`).
 pub struct ClauseBuilder<'me> {
     pub db: &'me dyn RustIrDatabase,
     clauses: &'me mut Vec<ProgramClause<ChalkIr>>,
-    binders: Vec<ParameterKind<()>>,
+    origins: Option<&'me mut Vec<ClauseOrigin>>,
+    origin: Option<ClauseOrigin>,
+    binders: ParameterKinds,
     parameters: Vec<Parameter<ChalkIr>>,
 }
 
@@ -22,11 +28,35 @@ impl<'me> ClauseBuilder<'me> {
         Self {
             db,
             clauses,
-            binders: vec![],
+            origins: None,
+            origin: None,
+            binders: ParameterKinds::EMPTY,
             parameters: vec![],
         }
     }
 
+    /// Like `new`, but for each clause pushed onto `clauses`, the
+    /// origin in effect is pushed onto `origins`.
+    pub fn with_origins(
+        db: &'me dyn RustIrDatabase,
+        clauses: &'me mut Vec<ProgramClause<ChalkIr>>,
+        origins: &'me mut Vec<ClauseOrigin>,
+    ) -> Self {
+        Self {
+            origins: Some(origins),
+            ..Self::new(db, clauses)
+        }
+    }
+
+    /// Executes `op` with `origin` as the origin of the clauses that
+    /// it pushes, then restores the previous origin.
+    pub fn with_origin<R>(&mut self, origin: ClauseOrigin, op: impl FnOnce(&mut Self) -> R) -> R {
+        let old_origin = self.origin.replace(origin);
+        let result = op(self);
+        self.origin = old_origin;
+        result
+    }
+
     /// Pushes a "fact" `forall<..> { consequence }` into the set of
     /// program clauses, meaning something that we can assume to be
     /// true unconditionally. The `forall<..>` binders will be
@@ -53,11 +83,15 @@ impl<'me> ClauseBuilder<'me> {
             self.clauses.push(ProgramClause::Implies(clause));
         } else {
             self.clauses.push(ProgramClause::ForAll(Binders {
-                binders: self.binders.clone(),
+                binders: self.binders,
                 value: clause,
             }));
         }
 
+        if let Some(origins) = &mut self.origins {
+            origins.push(self.origin.expect("clause pushed without an origin"));
+        }
+
         debug!("pushed clause {:?}", self.clauses.last());
     }
 
@@ -68,17 +102,22 @@ impl<'me> ClauseBuilder<'me> {
 
     /// Executes `op` with the `binders` in-scope; `op` is invoked
     /// with the bound value `v` as a parameter. After `op` finishes,
-    /// the binders are popped from scope.
+    /// the binders are popped from scope, and its result is returned.
     ///
     /// The new binders are always pushed onto the end of the internal
     /// list of binders; this means that any extant values where were
     /// created referencing the *old* list of binders are still valid.
-    pub fn push_binders<V>(&mut self, binders: &Binders<V>, op: impl FnOnce(&mut Self, V::Result))
+    pub fn push_binders<V, R>(
+        &mut self,
+        binders: &Binders<V>,
+        op: impl FnOnce(&mut Self, V::Result) -> R,
+    ) -> R
     where
         V: Fold<ChalkIr> + HasTypeFamily<TypeFamily = ChalkIr>,
     {
-        let old_len = self.binders.len();
-        self.binders.extend(binders.binders.clone());
+        let old_binders = self.binders;
+        let old_len = old_binders.len();
+        self.binders = old_binders.concat(&binders.binders);
         self.parameters.extend(
             binders
                 .binders
@@ -88,21 +127,21 @@ impl<'me> ClauseBuilder<'me> {
         );
 
         let value = binders.substitute(&self.parameters[old_len..]);
-        op(self, value);
+        let result = op(self, value);
 
-        self.binders.truncate(old_len);
+        self.binders = old_binders;
         self.parameters.truncate(old_len);
+        result
     }
 
     /// Push a single binder, for a type, at the end of the binder
     /// list.  The indices of previously bound variables are
     /// unaffected and hence the context remains usable. Invokes `op`,
     /// passing a type representing this new type variable in as an
-    /// argument.
-    #[allow(dead_code)]
-    pub fn push_bound_ty(&mut self, op: impl FnOnce(&mut Self, Ty<ChalkIr>)) {
+    /// argument, and returns its result.
+    pub fn push_bound_ty<R>(&mut self, op: impl FnOnce(&mut Self, Ty<ChalkIr>) -> R) -> R {
         let binders = Binders {
-            binders: vec![ParameterKind::Ty(())],
+            binders: ParameterKinds::intern(&[ParameterKind::Ty(())]),
             value: PhantomData::<ChalkIr>,
         };
         self.push_binders(&binders, |this, PhantomData| {
@@ -113,6 +152,6 @@ impl<'me> ClauseBuilder<'me> {
                 .assert_ty_ref()
                 .clone();
             op(this, ty)
-        });
+        })
     }
 }
@@ -0,0 +1,201 @@
+use crate::clauses::builder::ClauseBuilder;
+use crate::MissingData;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::LangItem;
+use std::marker::PhantomData;
+
+/// Pushes the built-in impls of `trait_id` for `Box<T>` (i.e., for
+/// the struct declared as the `OwnedBox` lang item). These mirror the
+/// impls in the standard library, rather than being derived from
+/// whatever fields the program happened to declare for `Box`:
+///
+/// ```notrust
+/// forall<T> { Implemented(Box<T>: Unpin) }
+/// forall<T> { Implemented(Box<T>: Drop) }
+/// forall<T> { Implemented(Box<T>: AutoTrait) :- Implemented(T: AutoTrait) }
+/// ```
+///
+/// As with other auto traits, an explicit impl of an auto trait for
+/// `Box` suppresses the built-in rule.
+pub fn push_owned_box_impls(
+    builder: &mut ClauseBuilder<'_>,
+    trait_id: TraitId,
+    box_id: StructId,
+) -> Result<(), MissingData> {
+    debug_heading!("push_owned_box_impls({:?}, {:?})", trait_id, box_id);
+
+    let db = builder.db;
+    let is_lang_item = |item| db.lang_item(item) == Some(TypeKindId::TraitId(trait_id));
+    let trait_datum = db.try_trait_datum(trait_id)?;
+    if trait_datum.is_auto_trait() && db.impl_provided_for(trait_id, box_id) {
+        debug!("impl provided");
+        return Ok(());
+    }
+
+    builder.push_bound_ty(|builder, ty| {
+        let box_ty: Ty<_> = ApplicationTy {
+            name: box_id.cast(),
+            parameters: vec![ty.clone().cast()],
+        }
+        .cast();
+        let box_trait_ref = TraitRef {
+            trait_id,
+            parameters: vec![box_ty.cast()],
+        };
+
+        if is_lang_item(LangItem::Unpin) || is_lang_item(LangItem::Drop) {
+            builder.push_fact(box_trait_ref);
+        } else if trait_datum.is_auto_trait() {
+            builder.push_clause(
+                box_trait_ref,
+                Some(TraitRef {
+                    trait_id,
+                    parameters: vec![ty.cast()],
+                }),
+            );
+        }
+    });
+    Ok(())
+}
+
+/// Pushes the built-in impls of `Copy` or `Clone` (`trait_id`) for
+/// the scalar types (see `WellKnownItems::scalars`), so that programs
+/// need not declare an impl for each of `u32`, `f64` and so on:
+///
+/// ```notrust
+/// Implemented(u32: Copy)
+/// Implemented(f64: Copy)
+/// ```
+pub fn push_scalar_impls(builder: &mut ClauseBuilder<'_>, trait_id: TraitId) {
+    debug_heading!("push_scalar_impls({:?})", trait_id);
+
+    let well_known_items = builder.db.well_known_items();
+    for (struct_id, _) in well_known_items.scalars() {
+        let scalar_ty: Ty<_> = ApplicationTy {
+            name: struct_id.cast(),
+            parameters: vec![],
+        }
+        .cast();
+        builder.push_fact(TraitRef {
+            trait_id,
+            parameters: vec![scalar_ty.cast()],
+        });
+    }
+}
+
+/// Pushes the built-in impl of `Sized` (`sized_trait_id`) for the
+/// struct `struct_id`. Every struct is sized, as chalk has no unsized
+/// fields, apart from extern types, which have no impl at all:
+///
+/// ```notrust
+/// forall<T..> { Implemented(Foo<T..>: Sized) }
+/// ```
+pub fn push_sized_impls(
+    builder: &mut ClauseBuilder<'_>,
+    sized_trait_id: TraitId,
+    struct_id: StructId,
+) -> Result<(), MissingData> {
+    debug_heading!("push_sized_impls({:?}, {:?})", sized_trait_id, struct_id);
+
+    let struct_datum = builder.db.try_struct_datum(struct_id)?;
+    if struct_datum.is_extern_type() {
+        debug!("extern type");
+        return Ok(());
+    }
+
+    let binders = struct_datum.binders.map_ref(|b| &b.fields);
+    builder.push_binders(&binders, |builder, _| {
+        let self_ty: Ty<_> = ApplicationTy {
+            name: struct_id.cast(),
+            parameters: builder.placeholders_in_scope().to_vec(),
+        }
+        .cast();
+        builder.push_fact(TraitRef {
+            trait_id: sized_trait_id,
+            parameters: vec![self_ty.cast()],
+        });
+    });
+    Ok(())
+}
+
+/// Pushes the built-in impl of the auto trait `auto_trait_id` (or of
+/// `Sized`) for tuples of the given arity, which (like a struct)
+/// implements an auto trait if all of its element types do:
+///
+/// ```notrust
+/// forall<T..> { Implemented((T..): AutoTrait) :- Implemented(T: AutoTrait).. }
+/// ```
+pub fn push_tuple_auto_trait_impls(
+    builder: &mut ClauseBuilder<'_>,
+    auto_trait_id: TraitId,
+    arity: usize,
+) {
+    debug_heading!(
+        "push_tuple_auto_trait_impls({:?}, {:?})",
+        auto_trait_id,
+        arity
+    );
+
+    let binders = Binders {
+        binders: vec![ParameterKind::Ty(()); arity].into(),
+        value: PhantomData::<ChalkIr>,
+    };
+    builder.push_binders(&binders, |builder, PhantomData| {
+        let parameters = builder.placeholders_in_scope().to_vec();
+        let tuple_ty: Ty<_> = ApplicationTy {
+            name: TypeName::Tuple(arity),
+            parameters: parameters.clone(),
+        }
+        .cast();
+        builder.push_clause(
+            TraitRef {
+                trait_id: auto_trait_id,
+                parameters: vec![tuple_ty.cast()],
+            },
+            parameters.into_iter().map(|parameter| TraitRef {
+                trait_id: auto_trait_id,
+                parameters: vec![parameter],
+            }),
+        );
+    });
+}
+
+/// Pushes the built-in impl of the auto trait `auto_trait_id` for the
+/// `dyn` type with the given bounds. Unlike a struct or a tuple, an
+/// object type does not implement an auto trait because of what it
+/// contains (its hidden type is unknown), but only if the trait is
+/// named in its bounds:
+///
+/// ```notrust
+/// Implemented(dyn Foo + Send: Send)
+/// ```
+///
+/// whereas `dyn Foo: Send` does not hold (unless `Send` is a
+/// supertrait of `Foo`; see the `dyn_ty` module).
+pub fn push_dyn_auto_trait_impls(
+    builder: &mut ClauseBuilder<'_>,
+    auto_trait_id: TraitId,
+    bounds: &Binders<Vec<QuantifiedWhereClause<ChalkIr>>>,
+) {
+    debug_heading!(
+        "push_dyn_auto_trait_impls({:?}, {:?})",
+        auto_trait_id,
+        bounds
+    );
+
+    let in_bounds = bounds.value.iter().any(|bound| match &bound.value {
+        WhereClause::Implemented(trait_ref) => trait_ref.trait_id == auto_trait_id,
+        WhereClause::ProjectionEq(_) => false,
+    });
+    if !in_bounds {
+        return;
+    }
+
+    let dyn_ty = Ty::Dyn(bounds.clone());
+    builder.push_fact(TraitRef {
+        trait_id: auto_trait_id,
+        parameters: vec![dyn_ty.cast()],
+    });
+}
@@ -0,0 +1,145 @@
+//! Clauses for the type constructors built into the language, `str`,
+//! `[T]`, `[T; N]`, `&'a T` and `*const T` (and their `mut` forms; see
+//! `BuiltinType`). Like tuples, they have no declaration in the
+//! program, so their clauses are generated from the properties that
+//! `BuiltinType` gives for each of them.
+
+use crate::clauses::builder::ClauseBuilder;
+use crate::clauses::send_sync::{send_sync_rule, SendSyncRule};
+use crate::MissingData;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::LangItem;
+use std::marker::PhantomData;
+
+/// Pushes the clauses for the type itself, which (like a tuple) is
+/// well-formed and fully visible if its type parameters are, and is
+/// upstream. A reference is well-formed only if its referent also
+/// outlives its lifetime, which the embedder checks as a region
+/// constraint; a raw pointer has no such requirement:
+///
+/// ```notrust
+/// forall<T, N> { WellFormed([T; N]) :- WellFormed(T), WellFormed(N) }
+/// forall<'a, T> { WellFormed(&'a T) :- WellFormed(T), T: 'a }
+/// forall<T, N> { IsFullyVisible([T; N]) :- IsFullyVisible(T), IsFullyVisible(N) }
+/// forall<T, N> { IsUpstream([T; N]) }
+/// ```
+pub(super) fn push_builtin_type_clauses(builder: &mut ClauseBuilder<'_>, builtin: BuiltinType) {
+    push_builtin_type_binders(builder, builtin, |builder, builtin_ty, parameters| {
+        let tys = || parameters.iter().filter_map(|p| p.as_ref().ty().cloned());
+        let mut wf_conditions: Vec<Goal<ChalkIr>> =
+            tys().map(|ty| WellFormed::Ty(ty).cast()).collect();
+        if let BuiltinType::Ref(_) = builtin {
+            wf_conditions.push(
+                TypeOutlives {
+                    ty: parameters[0].assert_ty_ref().clone(),
+                    lifetime: *parameters[1].assert_lifetime_ref(),
+                }
+                .cast(),
+            );
+        }
+        builder.push_clause(WellFormed::Ty(builtin_ty.clone()), wf_conditions);
+        builder.push_clause(
+            DomainGoal::IsFullyVisible(builtin_ty.clone()),
+            tys().map(DomainGoal::IsFullyVisible),
+        );
+        builder.push_fact(DomainGoal::IsUpstream(builtin_ty));
+    });
+}
+
+/// Pushes the built-in impls of `trait_id` for the type, if it is
+/// `Sized` (and the type is sized), `Copy` or `Clone` (and the type
+/// is a shared reference or raw pointer, or an array, which is `Copy`
+/// if its element type is), `Unpin` (and the type is a reference
+/// or raw pointer, whatever it points to), or an auto trait, which the type
+/// implements if its element type does (apart from the exceptions
+/// for `Send` and `Sync` in the `send_sync` module):
+///
+/// ```notrust
+/// forall<T, N> { Implemented([T; N]: Sized) }
+/// forall<'a, T> { Implemented(&'a T: Copy) }                           // likewise `Clone`
+/// forall<T, N> { Implemented([T; N]: Copy) :- Implemented(T: Copy) }   // likewise `Clone`
+/// forall<'a, T> { Implemented(&'a T: Unpin) }                          // likewise `&'a mut T`, `*const T`
+/// forall<T> { Implemented([T]: AutoTrait) :- Implemented(T: AutoTrait) }
+/// forall<'a, T> { Implemented(&'a T: Send) :- Implemented(T: Sync) }
+/// Implemented(str: AutoTrait)
+/// ```
+pub(super) fn push_builtin_type_impls(
+    builder: &mut ClauseBuilder<'_>,
+    trait_id: TraitId,
+    builtin: BuiltinType,
+) -> Result<(), MissingData> {
+    debug_heading!("push_builtin_type_impls({:?}, {:?})", trait_id, builtin);
+
+    let db = builder.db;
+    let is_lang_item = |item| db.lang_item(item) == Some(TypeKindId::TraitId(trait_id));
+    let mut element_trait_id = trait_id;
+    let from_element = if is_lang_item(LangItem::Sized) {
+        if !builtin.is_sized() {
+            return Ok(());
+        }
+        false
+    } else if is_lang_item(LangItem::Copy) || is_lang_item(LangItem::Clone) {
+        if builtin.is_copy() {
+            false
+        } else if builtin.is_copy_if_element_is() {
+            true
+        } else {
+            return Ok(());
+        }
+    } else if is_lang_item(LangItem::Unpin) && builtin.is_unpin() {
+        false
+    } else if db.try_trait_datum(trait_id)?.is_auto_trait() {
+        match send_sync_rule(db, trait_id, TypeName::Builtin(builtin)) {
+            Some(SendSyncRule::Never) => return Ok(()),
+            Some(SendSyncRule::ElementImplements(item)) => {
+                if let Some(TypeKindId::TraitId(id)) = db.lang_item(item) {
+                    element_trait_id = id;
+                }
+            }
+            None => {}
+        }
+        true
+    } else {
+        return Ok(());
+    };
+
+    push_builtin_type_binders(builder, builtin, |builder, builtin_ty, parameters| {
+        let element = parameters.first().filter(|_| from_element);
+        builder.push_clause(
+            TraitRef {
+                trait_id,
+                parameters: vec![builtin_ty.cast()],
+            },
+            element.map(|element| TraitRef {
+                trait_id: element_trait_id,
+                parameters: vec![element.clone()],
+            }),
+        );
+    });
+    Ok(())
+}
+
+/// Executes `op` with the type `builtin<P..>`, and its parameters
+/// `P..`, bound by the binders of the clauses that it pushes.
+fn push_builtin_type_binders(
+    builder: &mut ClauseBuilder<'_>,
+    builtin: BuiltinType,
+    op: impl FnOnce(&mut ClauseBuilder<'_>, Ty<ChalkIr>, Vec<Parameter<ChalkIr>>),
+) {
+    let binders = Binders {
+        binders: builtin.parameter_kinds().into(),
+        value: PhantomData::<ChalkIr>,
+    };
+    builder.push_binders(&binders, |builder, PhantomData| {
+        let placeholders = builder.placeholders_in_scope();
+        let parameters = placeholders[placeholders.len() - builtin.arity()..].to_vec();
+        let builtin_ty: Ty<_> = ApplicationTy {
+            name: TypeName::Builtin(builtin),
+            parameters: parameters.clone(),
+        }
+        .cast();
+        op(builder, builtin_ty, parameters)
+    });
+}
@@ -0,0 +1,356 @@
+//! Clauses for `dyn Trait` and `impl Trait` types. Such a type is
+//! represented by its bounds, which are bound over the (hidden) self
+//! type:
+//!
+//! ```notrust
+//! dyn(exists<T> {
+//!     Implemented(T: Foo),
+//!     ProjectionEq(<T as Foo>::Item = u32),
+//! })
+//! ```
+//!
+//! The type satisfies its bounds, with itself substituted for `T`,
+//! and, through those, everything that the traits in its bounds
+//! guarantee about `Self`: their supertraits, and the bounds of their
+//! associated types.
+//!
+//! Not every where clause of a trait can be carried over to objects
+//! in this way. A supertrait bound like `trait Foo: Bar<Self>` uses
+//! `Self` somewhere other than as the self type; such a trait is not
+//! object safe in Rust, and we simply generate no clauses for these
+//! where clauses.
+
+use super::builder::ClauseBuilder;
+use crate::MissingData;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::cast::{Cast, Caster};
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::*;
+use chalk_ir::*;
+use chalk_rust_ir::{AssociatedTyDatum, AssociatedTyDatumBound, IntoWhereClauses};
+use rustc_hash::FxHashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Pushes the clauses for the `dyn` or `impl` type `ty`, if it is
+/// one, or for the self type of `ty` if it is a projection (so that
+/// we can prove things about `<dyn Foo as Foo>::Item`). Given
+///
+/// ```notrust
+/// trait Bar { }
+/// trait Foo where Self: Bar {
+///     type Item: Clone;
+/// }
+/// ```
+///
+/// and the type `dyn Foo`, we generate:
+///
+/// ```notrust
+/// Implemented(dyn Foo: Foo).
+/// Implemented(dyn Foo: Bar).
+/// forall<X> {
+///     Implemented(X: Clone) :- ProjectionEq(<dyn Foo as Foo>::Item = X)
+/// }
+/// ```
+///
+/// The projection in the last clause is routed through
+/// `ProjectionEq`, as for the structural impls of auto traits.
+pub(super) fn push_dyn_ty_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    ty: &Ty<ChalkIr>,
+) -> Result<(), MissingData> {
+    let self_ty = match ty {
+        Ty::Projection(projection) => {
+            let associated_ty = builder
+                .db
+                .try_associated_ty_data(projection.associated_ty_id)?;
+            let trait_datum = builder.db.try_trait_datum(associated_ty.trait_id)?;
+            let self_index = projection.parameters.len() - trait_datum.binders.len();
+            projection.parameters[self_index].assert_ty_ref().clone()
+        }
+        _ => ty.clone(),
+    };
+
+    let bounds = match &self_ty {
+        Ty::Dyn(bounds) | Ty::Opaque(bounds) => bounds,
+        _ => return Ok(()),
+    };
+
+    let object_traits = ObjectTraits::collect(builder, bounds)?;
+    let is_dyn = matches!(self_ty, Ty::Dyn(_));
+
+    for bound in bounds.into_iter() {
+        // The auto traits in the bounds of a `dyn` type are left to
+        // the auto trait rules (`push_dyn_auto_trait_impls`).
+        if let WhereClause::Implemented(trait_ref) = &bound.value.value {
+            if is_dyn && object_traits.is_auto_trait(trait_ref.trait_id) {
+                continue;
+            }
+        }
+
+        // Replace the `T` from `exists<T> { .. }` with `self_ty`,
+        // yielding clauses like
+        //
+        // ```
+        // forall<'a> { Implemented(dyn Fn(&u8): Fn<(&'a u8)>) }
+        // ```
+        let bound = bound.substitute(&[self_ty.clone().cast()]);
+        builder.push_binders(&bound, |builder, wc| {
+            builder.push_fact(wc.clone());
+            if let WhereClause::Implemented(trait_ref) = &wc {
+                object_traits.push_implied_clauses(builder, trait_ref, &mut vec![]);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// The data for the traits that an object type implements, directly
+/// or as supertraits, indexed by trait.
+struct ObjectTraits {
+    traits: FxHashMap<TraitId, ObjectTrait>,
+}
+
+struct ObjectTrait {
+    is_auto_trait: bool,
+
+    /// The where clauses of the trait that also hold for objects: the
+    /// supertrait bounds, such as `Self: Bar` or `<Self as Bar>::Item
+    /// = u32`, that do not use `Self` anywhere else.
+    super_bounds: Vec<Binders<QuantifiedWhereClause<ChalkIr>>>,
+
+    associated_tys: Vec<Arc<AssociatedTyDatum>>,
+}
+
+impl ObjectTraits {
+    /// Fetches the data for the traits in `bounds` and, transitively,
+    /// their supertraits.
+    fn collect(
+        builder: &ClauseBuilder<'_>,
+        bounds: &Binders<Vec<QuantifiedWhereClause<ChalkIr>>>,
+    ) -> Result<Self, MissingData> {
+        let db = builder.db;
+        let mut traits = FxHashMap::default();
+        let mut pending: Vec<TraitId> = bounds
+            .value
+            .iter()
+            .filter_map(|qwc| match &qwc.value {
+                WhereClause::Implemented(trait_ref) => Some(trait_ref.trait_id),
+                WhereClause::ProjectionEq(_) => None,
+            })
+            .collect();
+
+        while let Some(trait_id) = pending.pop() {
+            if traits.contains_key(&trait_id) {
+                continue;
+            }
+
+            let trait_datum = db.try_trait_datum(trait_id)?;
+            let mut super_bounds = vec![];
+            for where_clause in trait_datum
+                .binders
+                .map_ref(|b| &b.where_clauses)
+                .into_iter()
+            {
+                let where_clause = where_clause.map(|wc| wc.clone());
+                if !is_super_bound(builder, &where_clause)? {
+                    debug!("ObjectTraits: skipping where clause {:?}", where_clause);
+                    continue;
+                }
+                if let WhereClause::Implemented(trait_ref) = &where_clause.value.value {
+                    pending.push(trait_ref.trait_id);
+                }
+                super_bounds.push(where_clause);
+            }
+
+            let associated_tys = trait_datum
+                .associated_ty_ids
+                .iter()
+                .map(|&id| db.try_associated_ty_data(id))
+                .collect::<Result<_, _>>()?;
+
+            traits.insert(
+                trait_id,
+                ObjectTrait {
+                    is_auto_trait: trait_datum.is_auto_trait(),
+                    super_bounds,
+                    associated_tys,
+                },
+            );
+        }
+
+        Ok(ObjectTraits { traits })
+    }
+
+    fn is_auto_trait(&self, trait_id: TraitId) -> bool {
+        self.traits
+            .get(&trait_id)
+            .is_some_and(|object_trait| object_trait.is_auto_trait)
+    }
+
+    /// Pushes the clauses implied by `trait_ref`, whose self type is
+    /// the object type: the bounds of the associated types of the
+    /// trait, and the supertrait bounds (recursively). `stack` holds
+    /// the traits being visited, to guard against cyclic supertraits.
+    fn push_implied_clauses(
+        &self,
+        builder: &mut ClauseBuilder<'_>,
+        trait_ref: &TraitRef<ChalkIr>,
+        stack: &mut Vec<TraitId>,
+    ) {
+        if stack.contains(&trait_ref.trait_id) {
+            return;
+        }
+        let object_trait = match self.traits.get(&trait_ref.trait_id) {
+            Some(object_trait) => object_trait,
+            None => return,
+        };
+        stack.push(trait_ref.trait_id);
+
+        for associated_ty in &object_trait.associated_tys {
+            push_associated_ty_bounds(builder, associated_ty, &trait_ref.parameters);
+        }
+
+        for super_bound in &object_trait.super_bounds {
+            let super_bound = super_bound.substitute(&trait_ref.parameters);
+            builder.push_binders(&super_bound, |builder, wc| {
+                builder.push_fact(wc.clone());
+                if let WhereClause::Implemented(trait_ref) = &wc {
+                    self.push_implied_clauses(builder, trait_ref, stack);
+                }
+            });
+        }
+
+        stack.pop();
+    }
+}
+
+/// Pushes the bounds declared on `associated_ty`, for the trait
+/// parameters `trait_parameters`, routing the projection through
+/// `ProjectionEq`. Given `type Item<'a>: Clone where T: 'a` in
+/// `trait Foo<T>`, and the trait parameters `[dyn Foo<u32>, u32]`:
+///
+/// ```notrust
+/// forall<'a, X> {
+///     Implemented(X: Clone) :-
+///         ProjectionEq(<dyn Foo<u32> as Foo<u32>>::Item<'a> = X),
+///         u32: 'a
+/// }
+/// ```
+fn push_associated_ty_bounds(
+    builder: &mut ClauseBuilder<'_>,
+    associated_ty: &AssociatedTyDatum,
+    trait_parameters: &[Parameter<ChalkIr>],
+) {
+    let num_own = associated_ty.binders.len() - trait_parameters.len();
+    let own_binders = Binders {
+        binders: associated_ty.binders.binders[..num_own].into(),
+        value: PhantomData::<ChalkIr>,
+    };
+
+    builder.push_binders(&own_binders, |builder, PhantomData| {
+        let parameters: Vec<_> = builder.placeholders_in_scope()
+            [builder.placeholders_in_scope().len() - num_own..]
+            .iter()
+            .chain(trait_parameters)
+            .cloned()
+            .collect();
+        let AssociatedTyDatumBound {
+            bounds,
+            where_clauses,
+            default: _,
+        } = associated_ty.binders.substitute(&parameters);
+        let projection = ProjectionTy {
+            associated_ty_id: associated_ty.id,
+            parameters,
+        };
+
+        builder.push_bound_ty(|builder, ty| {
+            for bound in bounds.iter().flat_map(|b| b.into_where_clauses(ty.clone())) {
+                builder.push_binders(&bound, |builder, wc| {
+                    let projection_eq = ProjectionEq {
+                        projection: projection.clone(),
+                        ty: ty.clone(),
+                    };
+                    builder.push_clause(
+                        wc,
+                        Some(projection_eq.cast::<Goal<ChalkIr>>())
+                            .into_iter()
+                            .chain(where_clauses.iter().cloned().casted()),
+                    );
+                });
+            }
+        });
+    });
+}
+
+/// Returns true if `where_clause`, a where clause of some trait,
+/// holds for all objects of that trait, i.e., if it is a supertrait
+/// bound that mentions `Self` only as the self type.
+fn is_super_bound(
+    builder: &ClauseBuilder<'_>,
+    where_clause: &Binders<QuantifiedWhereClause<ChalkIr>>,
+) -> Result<bool, MissingData> {
+    // Within the quantified where clause, `Self` (the first parameter
+    // of the trait) is the first variable not bound by the clause
+    // itself.
+    let binders = where_clause.value.binders.len();
+    let is_self = |parameter: &Parameter<ChalkIr>| *parameter == Ty::BoundVar(binders).cast();
+
+    Ok(match &where_clause.value.value {
+        WhereClause::Implemented(trait_ref) => {
+            is_self(&trait_ref.parameters[0])
+                && !trait_ref.parameters[1..]
+                    .iter()
+                    .any(|p| mentions_self(p, binders))
+        }
+        WhereClause::ProjectionEq(ProjectionEq { projection, ty }) => {
+            let associated_ty = builder
+                .db
+                .try_associated_ty_data(projection.associated_ty_id)?;
+            let trait_datum = builder.db.try_trait_datum(associated_ty.trait_id)?;
+            let self_index = projection.parameters.len() - trait_datum.binders.len();
+            is_self(&projection.parameters[self_index])
+                && !projection
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .any(|(i, p)| i != self_index && mentions_self(p, binders))
+                && !mentions_self(ty, binders)
+        }
+    })
+}
+
+/// Returns true if `value`, which is under `binders` binders, refers
+/// to the free variable with index 0, i.e., to `Self`.
+fn mentions_self<T: Fold<ChalkIr>>(value: &T, binders: usize) -> bool {
+    let mut finder = SelfFinder { found: false };
+    value.fold_with(&mut finder, binders).unwrap();
+    finder.found
+}
+
+struct SelfFinder {
+    found: bool,
+}
+
+impl DefaultTypeFolder for SelfFinder {}
+
+impl FreeVarFolder<ChalkIr> for SelfFinder {
+    fn fold_free_var_ty(&mut self, depth: usize, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        self.found |= depth == 0;
+        Ok(Ty::BoundVar(depth + binders))
+    }
+
+    fn fold_free_var_lifetime(
+        &mut self,
+        depth: usize,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        Ok(Lifetime::BoundVar(depth + binders))
+    }
+}
+
+impl DefaultInferenceFolder for SelfFinder {}
+
+impl DefaultPlaceholderFolder for SelfFinder {}
@@ -1,15 +1,17 @@
-use super::program_clauses::ToProgramClauses;
+use super::{push_associated_ty_clauses, push_trait_clauses};
 use crate::clauses::builder::ClauseBuilder;
 use crate::clauses::match_type_kind;
+use crate::clauses::ClauseOrigin;
 use crate::DomainGoal;
 use crate::FromEnv;
+use crate::MissingData;
 use crate::ProgramClause;
 use crate::RustIrDatabase;
 use crate::Ty;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::ProjectionTy;
 use chalk_ir::TypeName;
-use rustc_hash::FxHashSet;
+use rustc_hash::FxHashMap;
 
 /// When proving a `FromEnv` goal, we elaborate all `FromEnv` goals
 /// found in the environment.
@@ -17,17 +19,22 @@ use rustc_hash::FxHashSet;
 /// For example, when `T: Clone` is in the environment, we can prove
 /// `T: Copy` by adding the clauses from `trait Clone`, which includes
 /// the rule `FromEnv(T: Copy) :- FromEnv(T: Clone)
+///
+/// Each clause is added to `out` along with the declaration it was
+/// derived from.
 pub(super) fn elaborate_env_clauses(
     db: &dyn RustIrDatabase,
     in_clauses: &Vec<ProgramClause<ChalkIr>>,
-    out: &mut FxHashSet<ProgramClause<ChalkIr>>,
-) {
+    out: &mut FxHashMap<ProgramClause<ChalkIr>, ClauseOrigin>,
+) -> Result<(), MissingData> {
     let mut this_round = vec![];
-    let mut visitor = EnvElaborator::new(db, &mut this_round);
+    let mut origins = vec![];
+    let mut visitor = EnvElaborator::new(db, &mut this_round, &mut origins);
     for clause in in_clauses {
-        visitor.visit_program_clause(&clause);
+        visitor.visit_program_clause(&clause)?;
     }
-    out.extend(this_round);
+    out.extend(this_round.into_iter().zip(origins));
+    Ok(())
 }
 
 struct EnvElaborator<'me> {
@@ -36,34 +43,42 @@ struct EnvElaborator<'me> {
 }
 
 impl<'me> EnvElaborator<'me> {
-    fn new(db: &'me dyn RustIrDatabase, out: &'me mut Vec<ProgramClause<ChalkIr>>) -> Self {
+    fn new(
+        db: &'me dyn RustIrDatabase,
+        out: &'me mut Vec<ProgramClause<ChalkIr>>,
+        origins: &'me mut Vec<ClauseOrigin>,
+    ) -> Self {
         EnvElaborator {
             db,
-            builder: ClauseBuilder::new(db, out),
+            builder: ClauseBuilder::with_origins(db, out, origins),
         }
     }
 
-    fn visit_projection_ty(&mut self, projection_ty: &ProjectionTy<ChalkIr>) {
-        self.db
-            .associated_ty_data(projection_ty.associated_ty_id)
-            .to_program_clauses(&mut self.builder);
+    fn visit_projection_ty(
+        &mut self,
+        projection_ty: &ProjectionTy<ChalkIr>,
+    ) -> Result<(), MissingData> {
+        push_associated_ty_clauses(&mut self.builder, projection_ty.associated_ty_id)
     }
 
-    fn visit_ty(&mut self, ty: &Ty<ChalkIr>) {
+    fn visit_ty(&mut self, ty: &Ty<ChalkIr>) -> Result<(), MissingData> {
         match ty {
             Ty::Apply(application_ty) => match application_ty.name {
                 TypeName::TypeKindId(type_kind_id) => {
-                    match_type_kind(&mut self.builder, type_kind_id)
+                    match_type_kind(&mut self.builder, type_kind_id)?
                 }
-                TypeName::Placeholder(_) | TypeName::Error => (),
+                TypeName::Placeholder(_)
+                | TypeName::Tuple(_)
+                | TypeName::FnPtr
+                | TypeName::OpaqueConst(_)
+                | TypeName::Builtin(_)
+                | TypeName::Error => (),
                 TypeName::AssociatedType(type_id) => {
-                    self.db
-                        .associated_ty_data(type_id)
-                        .to_program_clauses(&mut self.builder);
+                    push_associated_ty_clauses(&mut self.builder, type_id)?
                 }
             },
             Ty::Projection(projection_ty) => {
-                self.visit_projection_ty(projection_ty);
+                self.visit_projection_ty(projection_ty)?;
             }
 
             // FIXME(#203) -- We haven't fully figured out the implied
@@ -72,36 +87,35 @@ impl<'me> EnvElaborator<'me> {
 
             Ty::ForAll(_) | Ty::BoundVar(_) | Ty::InferenceVar(_) => (),
         }
+        Ok(())
     }
 
-    fn visit_from_env(&mut self, from_env: &FromEnv<ChalkIr>) {
+    fn visit_from_env(&mut self, from_env: &FromEnv<ChalkIr>) -> Result<(), MissingData> {
         match from_env {
             FromEnv::Trait(trait_ref) => {
-                let trait_datum = self.db.trait_datum(trait_ref.trait_id);
-
-                trait_datum.to_program_clauses(&mut self.builder);
+                push_trait_clauses(&mut self.builder, trait_ref.trait_id)?;
 
                 // If we know that `T: Iterator`, then we also know
                 // things about `<T as Iterator>::Item`, so push those
                 // implied bounds too:
+                let trait_datum = self.db.try_trait_datum(trait_ref.trait_id)?;
                 for &associated_ty_id in &trait_datum.associated_ty_ids {
-                    self.db
-                        .associated_ty_data(associated_ty_id)
-                        .to_program_clauses(&mut self.builder);
+                    push_associated_ty_clauses(&mut self.builder, associated_ty_id)?;
                 }
+                Ok(())
             }
             FromEnv::Ty(ty) => self.visit_ty(ty),
         }
     }
 
-    fn visit_domain_goal(&mut self, domain_goal: &DomainGoal<ChalkIr>) {
+    fn visit_domain_goal(&mut self, domain_goal: &DomainGoal<ChalkIr>) -> Result<(), MissingData> {
         match domain_goal {
             DomainGoal::FromEnv(from_env) => self.visit_from_env(from_env),
-            _ => {}
+            _ => Ok(()),
         }
     }
 
-    fn visit_program_clause(&mut self, clause: &ProgramClause<ChalkIr>) {
+    fn visit_program_clause(&mut self, clause: &ProgramClause<ChalkIr>) -> Result<(), MissingData> {
         match clause {
             ProgramClause::Implies(clause) => self.visit_domain_goal(&clause.consequence),
             ProgramClause::ForAll(clause) => self.visit_domain_goal(&clause.value.consequence),
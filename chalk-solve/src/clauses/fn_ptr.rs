@@ -0,0 +1,126 @@
+//! Clauses for function pointer types. A function pointer
+//! `fn(A, B) -> C` is represented as `fn<(A, B), C>` (see
+//! `TypeName::FnPtr`), so each rule below is a single clause over the
+//! argument tuple `Args` that covers function pointers of every arity,
+//! and unification of two function pointers is just unification of
+//! their argument tuples and return types.
+
+use crate::clauses::builder::ClauseBuilder;
+use crate::MissingData;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::LangItem;
+use std::marker::PhantomData;
+
+/// Pushes the clauses for the type itself, which (like a tuple) is
+/// well-formed and fully visible if its parts are, and is upstream:
+///
+/// ```notrust
+/// forall<Args, R> { WellFormed(fn<Args, R>) :- WellFormed(Args), WellFormed(R) }
+/// forall<Args, R> { IsFullyVisible(fn<Args, R>) :- IsFullyVisible(Args), IsFullyVisible(R) }
+/// forall<Args, R> { IsUpstream(fn<Args, R>) }
+/// ```
+pub(super) fn push_fn_ptr_clauses(builder: &mut ClauseBuilder<'_>) {
+    push_fn_ptr_binders(builder, |builder, fn_ptr_ty, args, output| {
+        builder.push_clause(
+            WellFormed::Ty(fn_ptr_ty.clone()),
+            vec![WellFormed::Ty(args.clone()), WellFormed::Ty(output.clone())],
+        );
+        builder.push_clause(
+            DomainGoal::IsFullyVisible(fn_ptr_ty.clone()),
+            vec![
+                DomainGoal::IsFullyVisible(args),
+                DomainGoal::IsFullyVisible(output),
+            ],
+        );
+        builder.push_fact(DomainGoal::IsUpstream(fn_ptr_ty));
+    });
+}
+
+/// Pushes the built-in impls of `trait_id` for function pointers, if
+/// it is one of the `Fn` traits, `Sized`, or an auto trait:
+///
+/// ```notrust
+/// forall<Args, R> { Implemented(fn<Args, R>: FnOnce<Args>) }   // likewise `FnMut`, `Fn`
+/// forall<Args, R> { Implemented(fn<Args, R>: Sized) }
+/// forall<Args, R> { Implemented(fn<Args, R>: AutoTrait) }
+/// ```
+pub(super) fn push_fn_ptr_impls(
+    builder: &mut ClauseBuilder<'_>,
+    trait_id: TraitId,
+) -> Result<(), MissingData> {
+    let db = builder.db;
+    let is_fn_trait = [LangItem::FnOnce, LangItem::FnMut, LangItem::Fn]
+        .iter()
+        .any(|&item| db.lang_item(item) == Some(TypeKindId::TraitId(trait_id)));
+    let is_sized = db.lang_item(LangItem::Sized) == Some(TypeKindId::TraitId(trait_id));
+    if !is_fn_trait && !is_sized && !db.try_trait_datum(trait_id)?.is_auto_trait() {
+        return Ok(());
+    }
+
+    push_fn_ptr_binders(builder, |builder, fn_ptr_ty, args, _| {
+        let mut parameters = vec![fn_ptr_ty.cast()];
+        if is_fn_trait {
+            parameters.push(args.cast());
+        }
+        builder.push_fact(TraitRef {
+            trait_id,
+            parameters,
+        });
+    });
+    Ok(())
+}
+
+/// Pushes the value of the `Output` type of `FnOnce` for function
+/// pointers, if `associated_ty_id` is that type:
+///
+/// ```notrust
+/// forall<Args, R> { Normalize(<fn<Args, R> as FnOnce<Args>>::Output -> R) }
+/// ```
+pub(super) fn push_fn_ptr_output(
+    builder: &mut ClauseBuilder<'_>,
+    associated_ty_id: TypeId,
+) -> Result<(), MissingData> {
+    let db = builder.db;
+    let datum = db.try_associated_ty_data(associated_ty_id)?;
+    if db.lang_item(LangItem::FnOnce) != Some(TypeKindId::TraitId(datum.trait_id))
+        || datum.name.to_string() != "Output"
+    {
+        return Ok(());
+    }
+
+    push_fn_ptr_binders(builder, |builder, fn_ptr_ty, args, output| {
+        builder.push_fact(Normalize {
+            projection: ProjectionTy {
+                associated_ty_id,
+                parameters: vec![fn_ptr_ty.cast(), args.cast()],
+            },
+            ty: output,
+        });
+    });
+    Ok(())
+}
+
+/// Executes `op` with the function pointer type `fn<Args, R>`, and its
+/// `Args` and `R`, bound by the binders of the clauses that it pushes.
+fn push_fn_ptr_binders(
+    builder: &mut ClauseBuilder<'_>,
+    op: impl FnOnce(&mut ClauseBuilder<'_>, Ty<ChalkIr>, Ty<ChalkIr>, Ty<ChalkIr>),
+) {
+    let binders = Binders {
+        binders: vec![ParameterKind::Ty(()); 2].into(),
+        value: PhantomData::<ChalkIr>,
+    };
+    builder.push_binders(&binders, |builder, PhantomData| {
+        let parameters = builder.placeholders_in_scope().to_vec();
+        let args = parameters[parameters.len() - 2].assert_ty_ref().clone();
+        let output = parameters[parameters.len() - 1].assert_ty_ref().clone();
+        let fn_ptr_ty: Ty<_> = ApplicationTy {
+            name: TypeName::FnPtr,
+            parameters: vec![args.clone().cast(), output.clone().cast()],
+        }
+        .cast();
+        op(builder, fn_ptr_ty, args, output)
+    });
+}
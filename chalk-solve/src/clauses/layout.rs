@@ -0,0 +1,126 @@
+//! Experimental clauses for `LayoutCompatible` goals, for prototyping
+//! safe transmute. Two types are layout compatible if:
+//!
+//! - they are the same type;
+//! - the database says so (see `RustIrDatabase::layout_facts`); or
+//! - both are structs or tuples with at least one field, they have
+//!   the same number of fields, and their fields are pairwise layout
+//!   compatible, in order.
+//!
+//! Structs and tuples without fields are treated as opaque: for
+//! example, `u32` and `u8` are both declared as `struct u32 { }` (and
+//! so on) in test programs, but are not layout compatible.
+
+use super::builder::ClauseBuilder;
+use crate::MissingData;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::ToParameter;
+use std::iter;
+
+/// Pushes the clauses that may be used to prove `goal`. Given
+///
+/// ```notrust
+/// struct Foo<T> { a: T, b: u32 }
+/// ```
+///
+/// and the goal `LayoutCompatible(Foo<u8>, (u8, u32))`, we generate:
+///
+/// ```notrust
+/// forall<T> { LayoutCompatible(T, T) }
+/// forall<T, U, V> {
+///     LayoutCompatible(Foo<T>, (U, V)) :-
+///         LayoutCompatible(T, U),
+///         LayoutCompatible(u32, V)
+/// }
+/// ```
+///
+/// along with the facts from the database.
+pub(super) fn push_layout_compatible_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    goal: &LayoutCompatible<ChalkIr>,
+) -> Result<(), MissingData> {
+    builder.push_bound_ty(|builder, ty| {
+        builder.push_fact(LayoutCompatible {
+            a: ty.clone(),
+            b: ty,
+        });
+    });
+
+    for LayoutCompatible { a, b } in builder.db.layout_facts() {
+        builder.push_fact(LayoutCompatible {
+            a: b.clone(),
+            b: a.clone(),
+        });
+        builder.push_fact(LayoutCompatible { a, b });
+    }
+
+    let shapes = (
+        layout_shape(builder, &goal.a)?,
+        layout_shape(builder, &goal.b)?,
+    );
+    if let (Some(shape_a), Some(shape_b)) = shapes {
+        builder.push_binders(&shape_a, |builder, a| {
+            builder.push_binders(&shape_b, |builder, b| {
+                if a.len() != b.len() {
+                    return;
+                }
+                builder.push_clause(
+                    LayoutCompatible {
+                        a: a[0].clone(),
+                        b: b[0].clone(),
+                    },
+                    a[1..].iter().zip(&b[1..]).map(|(a, b)| LayoutCompatible {
+                        a: a.clone(),
+                        b: b.clone(),
+                    }),
+                );
+            });
+        });
+    }
+
+    Ok(())
+}
+
+/// If `ty` is a struct or tuple with fields, returns its generic form
+/// followed by its field types, quantified over its parameters (e.g.,
+/// `forall<T> { [Foo<T>, T, u32] }` for the `Foo<u8>` above).
+fn layout_shape(
+    builder: &ClauseBuilder<'_>,
+    ty: &Ty<ChalkIr>,
+) -> Result<Option<Binders<Vec<Ty<ChalkIr>>>>, MissingData> {
+    let name = match ty {
+        Ty::Apply(apply) => apply.name,
+        _ => return Ok(None),
+    };
+
+    let (binders, fields) = match name {
+        TypeName::TypeKindId(TypeKindId::StructId(struct_id)) => {
+            let struct_datum = builder.db.try_struct_datum(struct_id)?;
+            (
+                struct_datum.binders.binders,
+                struct_datum.binders.value.fields.clone(),
+            )
+        }
+        TypeName::Tuple(arity) => {
+            let binders = vec![ParameterKind::Ty(()); arity].into();
+            let fields = (0..arity).map(Ty::BoundVar).collect();
+            (binders, fields)
+        }
+        _ => return Ok(None),
+    };
+    if fields.is_empty() {
+        return Ok(None);
+    }
+
+    let generic_ty: Ty<ChalkIr> = ApplicationTy {
+        name,
+        parameters: binders.iter().zip(0..).map(|p| p.to_parameter()).collect(),
+    }
+    .cast();
+    Ok(Some(Binders {
+        binders,
+        value: iter::once(generic_ty).chain(fields).collect(),
+    }))
+}
@@ -1,5 +1,6 @@
 use crate::clauses::builder::ClauseBuilder;
 use crate::split::Split;
+use crate::MissingData;
 use chalk_ir::cast::{Cast, Caster};
 use chalk_ir::*;
 use chalk_rust_ir::*;
@@ -7,9 +8,11 @@ use std::iter;
 
 /// Trait for lowering a given piece of rust-ir source (e.g., an impl
 /// or struct definition) into its associated "program clauses" --
-/// that is, into the lowered, logical rules that it defines.
+/// that is, into the lowered, logical rules that it defines. Any other
+/// items that are needed are looked up with the `try_` accessors of
+/// the database, so that missing ones are reported.
 pub trait ToProgramClauses {
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>);
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData>;
 }
 
 impl ToProgramClauses for ImplDatum {
@@ -26,13 +29,14 @@ impl ToProgramClauses for ImplDatum {
     /// generate nothing -- this is just a way to *opt out* from the
     /// default auto trait impls, it doesn't have any positive effect
     /// on its own.
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) {
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData> {
         if self.is_positive() {
             let binders = self.binders.map_ref(|b| (&b.trait_ref, &b.where_clauses));
             builder.push_binders(&binders, |builder, (trait_ref, where_clauses)| {
                 builder.push_clause(trait_ref, where_clauses);
             });
         }
+        Ok(())
     }
 }
 
@@ -62,9 +66,9 @@ impl ToProgramClauses for AssociatedTyValue {
     ///         Implemented(Iter<'a, T>: 'a).   // (2)
     /// }
     /// ```
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) {
-        let impl_datum = builder.db.impl_datum(self.impl_id);
-        let associated_ty = builder.db.associated_ty_data(self.associated_ty_id);
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData> {
+        let impl_datum = builder.db.try_impl_datum(self.impl_id)?;
+        let associated_ty = builder.db.try_associated_ty_data(self.associated_ty_id)?;
 
         builder.push_binders(&self.value, |builder, assoc_ty_value| {
             let all_parameters = builder.placeholders_in_scope().to_vec();
@@ -75,7 +79,10 @@ impl ToProgramClauses for AssociatedTyValue {
             // * `projection`: `<Vec<!T> as Iterable>::Iter<'!a>`
             let (impl_params, projection) = builder
                 .db
-                .impl_parameters_and_projection_from_associated_ty_value(&all_parameters, self);
+                .try_impl_parameters_and_projection_from_associated_ty_value(
+                    &all_parameters,
+                    self,
+                )?;
 
             // Assemble the full list of conditions for projection to be valid.
             // This comes in two parts, marked as (1) and (2) in doc above:
@@ -112,7 +119,8 @@ impl ToProgramClauses for AssociatedTyValue {
                 },
                 impl_where_clauses.chain(assoc_ty_where_clauses),
             );
-        });
+            Ok(())
+        })
     }
 }
 
@@ -165,7 +173,7 @@ impl ToProgramClauses for StructDatum {
     /// forall<T> { DownstreamType(Box<T>) :- DownstreamType(T). }
     /// ```
     ///
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) {
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData> {
         debug_heading!("StructDatum::to_program_clauses(self={:?})", self);
 
         let binders = self.binders.map_ref(|b| &b.where_clauses);
@@ -268,6 +276,7 @@ impl ToProgramClauses for StructDatum {
                 });
             }
         });
+        Ok(())
     }
 }
 
@@ -385,7 +394,13 @@ impl ToProgramClauses for TraitDatum {
     /// To implement fundamental traits, we simply just do not add the rule above that allows
     /// upstream types to implement upstream traits. Fundamental traits are not allowed to
     /// compatibly do that.
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) {
+    ///
+    /// A `#[sealed]` trait can only be implemented by the crate that declares it. We therefore
+    /// leave out the rules for potential downstream impls. If the trait is `#[upstream]`, we
+    /// also leave out the `LocalImplAllowed` rules, as no impl of it is allowed in the current
+    /// crate; otherwise, we leave out the rule above for compatible upstream impls, as all of
+    /// its impls are known.
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData> {
         let binders = self.binders.map_ref(|b| &b.where_clauses);
         builder.push_binders(&binders, |builder, where_clauses| {
             let parameters = builder.placeholders_in_scope().to_vec();
@@ -412,8 +427,14 @@ impl ToProgramClauses for TraitDatum {
             // conditions.
             let type_parameters: Vec<_> = trait_ref.type_parameters().collect();
 
-            // Add all cases for potential downstream impls that could exist
-            for i in 0..type_parameters.len() {
+            // Add all cases for potential downstream impls that could
+            // exist, unless the trait is sealed.
+            let downstream_params = if self.flags.sealed {
+                0
+            } else {
+                type_parameters.len()
+            };
+            for i in 0..downstream_params {
                 builder.push_clause(
                     trait_ref.clone(),
                     where_clauses
@@ -437,7 +458,7 @@ impl ToProgramClauses for TraitDatum {
             if !self.flags.upstream {
                 // Impls for traits declared locally always pass the impl rules
                 builder.push_fact(DomainGoal::LocalImplAllowed(trait_ref.clone()));
-            } else {
+            } else if !self.flags.sealed {
                 // Impls for remote traits must have a local type in the right place
                 for i in 0..type_parameters.len() {
                     builder.push_clause(
@@ -450,8 +471,10 @@ impl ToProgramClauses for TraitDatum {
             }
 
             // Fundamental traits can be reasoned about negatively without any ambiguity, so no
-            // need for this rule if the trait is fundamental.
-            if !self.flags.fundamental {
+            // need for this rule if the trait is fundamental. Nor is it needed for a local sealed
+            // trait, all of whose impls are in the current crate.
+            let local_sealed = self.flags.sealed && !self.flags.upstream;
+            if !self.flags.fundamental && !local_sealed {
                 builder.push_clause(
                     trait_ref.clone(),
                     where_clauses
@@ -494,6 +517,7 @@ impl ToProgramClauses for TraitDatum {
             // ```
             builder.push_clause(trait_ref.clone(), Some(trait_ref.clone().from_env()));
         });
+        Ok(())
     }
 }
 
@@ -566,7 +590,7 @@ impl ToProgramClauses for AssociatedTyDatum {
     ///     FromEnv(Self: Foo) :- FromEnv((Foo::Assoc)<Self, 'a,T>).
     /// }
     /// ```
-    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) {
+    fn to_program_clauses(&self, builder: &mut ClauseBuilder<'_>) -> Result<(), MissingData> {
         let binders = self.binders.map_ref(|b| (&b.where_clauses, &b.bounds));
         builder.push_binders(&binders, |builder, (where_clauses, bounds)| {
             let parameters = builder.placeholders_in_scope().to_vec();
@@ -577,7 +601,7 @@ impl ToProgramClauses for AssociatedTyDatum {
             };
 
             // Retrieve the trait ref embedding the associated type
-            let trait_ref = builder.db.trait_ref_from_projection(&projection);
+            let trait_ref = builder.db.try_trait_ref_from_projection(&projection)?;
 
             // Construct an application from the projection. So if we have `<T as Iterator>::Item`,
             // we would produce `(Iterator::Item)<T>`.
@@ -676,6 +700,7 @@ impl ToProgramClauses for AssociatedTyDatum {
                 //    }
                 builder.push_clause(projection_eq, Some(normalize));
             });
-        });
+            Ok(())
+        })
     }
 }
@@ -0,0 +1,230 @@
+//! Pruning of the `WellFormed` conditions of program clauses that the
+//! other conditions of the same clause already imply (see
+//! `SlgOptions::prune_well_formed`). For example, given
+//!
+//! ```notrust
+//! trait Eq { }
+//! trait Ord where Self: Eq { }
+//! struct Set<T> where T: Ord, T: Eq { }
+//! ```
+//!
+//! the clause `WF(Set<T>) :- WF(T: Ord), WF(T: Eq)` becomes
+//! `WF(Set<T>) :- WF(T: Ord)`, since the only way to prove
+//! `WF(T: Ord)` is through the clause `WF(T: Ord) :- Implemented(T:
+//! Ord), WF(T: Eq)`. (This relies on `WellFormed` goals being proven
+//! only by the clauses that chalk generates for them, and on those
+//! proofs not being cyclic; see `implied_by`.)
+
+use crate::{MissingData, RustIrDatabase};
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+
+/// The most `WellFormed` goals that a single condition is expanded
+/// into, beyond which it is not relied upon.
+const MAX_IMPLIED: usize = 64;
+
+/// Returns whether `WellFormed` goals are only proven by the clauses
+/// that chalk generates for them: the conditions of those clauses are
+/// then implied by the goals. A custom clause or a hypothesis of the
+/// environment could prove a `WellFormed` goal by other means.
+pub(crate) fn can_prune_well_formed(
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+) -> bool {
+    db.custom_clauses()
+        .iter()
+        .chain(environment.clauses.iter())
+        .all(|clause| match clause {
+            ProgramClause::Implies(implication) => !is_well_formed(&implication.consequence),
+            ProgramClause::ForAll(binders) => !is_well_formed(&binders.value.consequence),
+        })
+}
+
+fn is_well_formed(goal: &DomainGoal<ChalkIr>) -> bool {
+    matches!(goal, DomainGoal::WellFormed(_))
+}
+
+/// Removes the `WellFormed` conditions of `clause` that are implied
+/// by one of its other conditions, returning how many were removed.
+/// The caller must check `can_prune_well_formed` first.
+pub(crate) fn prune_well_formed(
+    db: &dyn RustIrDatabase,
+    clause: &mut ProgramClause<ChalkIr>,
+) -> Result<usize, MissingData> {
+    let implication = match clause {
+        ProgramClause::Implies(implication) => implication,
+        ProgramClause::ForAll(binders) => &mut binders.value,
+    };
+    let consequence_head = match &implication.consequence {
+        DomainGoal::WellFormed(well_formed) => Some(Head::of(well_formed)),
+        _ => None,
+    };
+    let conditions = &mut implication.conditions;
+
+    // Each condition is checked against the conditions kept so far
+    // (and those not yet checked), so of two identical conditions
+    // only one is removed.
+    let mut pruned = 0;
+    let mut i = 0;
+    while i < conditions.len() {
+        let implied = match as_well_formed(&conditions[i]) {
+            Some(well_formed) => {
+                let mut implied = false;
+                for (j, other) in conditions.iter().enumerate() {
+                    if let (true, Some(other)) = (j != i, as_well_formed(other)) {
+                        if other == well_formed
+                            || implied_by(db, other, consequence_head)?
+                                .is_some_and(|implied| implied.contains(well_formed))
+                        {
+                            implied = true;
+                            break;
+                        }
+                    }
+                }
+                implied
+            }
+            None => false,
+        };
+        if implied {
+            debug!("prune_well_formed: pruning {:?}", conditions[i]);
+            conditions.remove(i);
+            pruned += 1;
+        } else {
+            i += 1;
+        }
+    }
+    Ok(pruned)
+}
+
+fn as_well_formed(goal: &Goal<ChalkIr>) -> Option<&WellFormed<ChalkIr>> {
+    match goal {
+        Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::WellFormed(well_formed))) => Some(well_formed),
+        _ => None,
+    }
+}
+
+/// What a `WellFormed` goal is about, which determines the clauses
+/// that prove it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Head {
+    Trait(TraitId),
+    Struct(StructId),
+
+    /// A tuple or function pointer type, which is well-formed if its
+    /// component types are.
+    Structural,
+
+    /// Anything else, such as a type variable, whose clauses are not
+    /// known here.
+    Unknown,
+}
+
+impl Head {
+    fn of(well_formed: &WellFormed<ChalkIr>) -> Head {
+        match well_formed {
+            WellFormed::Trait(trait_ref) => Head::Trait(trait_ref.trait_id),
+            WellFormed::Ty(Ty::Apply(apply)) => match apply.name {
+                TypeName::TypeKindId(TypeKindId::StructId(struct_id)) => Head::Struct(struct_id),
+                TypeName::Tuple(_) | TypeName::FnPtr | TypeName::Builtin(_) => Head::Structural,
+                _ => Head::Unknown,
+            },
+            WellFormed::Ty(_) => Head::Unknown,
+        }
+    }
+}
+
+/// The `WellFormed` goals that any proof of `well_formed` must prove,
+/// directly or through one another, or `None` if that cannot be
+/// relied upon.
+///
+/// `WellFormed` goals are coinductive, so a goal may be proven by a
+/// cycle that skips the conditions it would otherwise need. Thus the
+/// goals reached are only relied upon if no two of them are about the
+/// same trait or struct (so that they cannot form a cycle) and none
+/// of them is about `consequence_head`, the head of the clause being
+/// pruned (whose conditions would be missing from a cycle through
+/// it).
+fn implied_by(
+    db: &dyn RustIrDatabase,
+    well_formed: &WellFormed<ChalkIr>,
+    consequence_head: Option<Head>,
+) -> Result<Option<Vec<WellFormed<ChalkIr>>>, MissingData> {
+    let mut implied = vec![];
+    let mut path = vec![];
+    let usable = visit(db, well_formed, consequence_head, &mut path, &mut implied)?;
+    Ok(if usable { Some(implied) } else { None })
+}
+
+/// Adds the goals that `well_formed` implies to `implied`, returning
+/// false if they cannot be relied upon (see `implied_by`). `path`
+/// holds the heads of the goals that led to `well_formed`.
+fn visit(
+    db: &dyn RustIrDatabase,
+    well_formed: &WellFormed<ChalkIr>,
+    consequence_head: Option<Head>,
+    path: &mut Vec<Head>,
+    implied: &mut Vec<WellFormed<ChalkIr>>,
+) -> Result<bool, MissingData> {
+    let head = Head::of(well_formed);
+    match head {
+        Head::Unknown => return Ok(false),
+        Head::Structural => {}
+        Head::Trait(_) | Head::Struct(_) => {
+            if Some(head) == consequence_head || path.contains(&head) {
+                return Ok(false);
+            }
+        }
+    }
+    if implied.len() >= MAX_IMPLIED {
+        return Ok(false);
+    }
+
+    path.push(head);
+    for next in directly_implied_by(db, well_formed)? {
+        if !visit(db, &next, consequence_head, path, implied)? {
+            return Ok(false);
+        }
+        if !implied.contains(&next) {
+            implied.push(next);
+        }
+    }
+    path.pop();
+    Ok(true)
+}
+
+/// The `WellFormed` conditions of the clauses that prove
+/// `well_formed` (see `ToProgramClauses` for `TraitDatum` and
+/// `StructDatum`, and the clauses for tuples and function pointers).
+/// Conditions under binders of their own are left out.
+fn directly_implied_by(
+    db: &dyn RustIrDatabase,
+    well_formed: &WellFormed<ChalkIr>,
+) -> Result<Vec<WellFormed<ChalkIr>>, MissingData> {
+    let where_clauses = match well_formed {
+        WellFormed::Trait(trait_ref) => db
+            .try_trait_datum(trait_ref.trait_id)?
+            .binders
+            .map_ref(|bound| &bound.where_clauses)
+            .substitute(&trait_ref.parameters),
+        WellFormed::Ty(Ty::Apply(apply)) => match apply.name {
+            TypeName::TypeKindId(TypeKindId::StructId(struct_id)) => db
+                .try_struct_datum(struct_id)?
+                .binders
+                .map_ref(|bound| &bound.where_clauses)
+                .substitute(&apply.parameters),
+            TypeName::Tuple(_) | TypeName::FnPtr | TypeName::Builtin(_) => {
+                return Ok(apply.type_parameters().map(WellFormed::Ty).collect())
+            }
+            _ => vec![],
+        },
+        WellFormed::Ty(_) => vec![],
+    };
+    Ok(where_clauses
+        .into_iter()
+        .filter(|qwc| qwc.binders.is_empty())
+        .filter_map(|qwc| match qwc.value {
+            WhereClause::Implemented(trait_ref) => Some(WellFormed::Trait(trait_ref)),
+            WhereClause::ProjectionEq(_) => None,
+        })
+        .collect())
+}
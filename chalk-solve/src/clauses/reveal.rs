@@ -0,0 +1,175 @@
+//! Clause assembly for `SolveMode::Revealing`, in which specialization
+//! is resolved: of the impls that apply to a goal, only the most
+//! specialized one is used.
+
+use crate::clauses::{impls_for_trait, program_clauses_with_origins, ClauseOrigin};
+use crate::split::Split;
+use crate::{MissingData, RustIrDatabase};
+use chalk_ir::cast::Caster;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::*;
+use chalk_rust_ir::ImplDatum;
+use itertools::Itertools;
+use std::sync::Arc;
+
+/// The clauses returned by `program_clauses_revealing`, and whether
+/// any of them got conditions to rule out more specialized impls.
+pub(crate) type RevealedClauses = (Vec<(ProgramClause<ChalkIr>, ClauseOrigin)>, bool);
+
+/// Like `program_clauses_with_origins`, but each clause from an impl (or
+/// from one of its associated type values) gets the extra condition
+/// that none of the more specialized impls among the candidates
+/// applies. So for these impls:
+///
+/// ```notrust
+/// impl<T> Foo for T where T: Bar { type Assoc = (); }
+/// impl Foo for u32 { type Assoc = u32; }
+/// ```
+///
+/// the clauses for `Normalize(<?X as Foo>::Assoc -> ?Y)` are:
+///
+/// ```notrust
+/// forall<T> {
+///     Normalize(<T as Foo>::Assoc -> ()) :-
+///         Implemented(T: Bar),
+///         not { exists<> { u32 = T } }
+/// }
+/// Normalize(<u32 as Foo>::Assoc -> u32)
+/// ```
+///
+/// An impl is more specialized than another if it has a higher
+/// specialization priority; two impls that apply to the same types
+/// always have different priorities.
+///
+/// Also returns whether any conditions were added, in which case the
+/// caller must make sure that the types of the goal are known, as
+/// `not` goals cannot be decided otherwise.
+pub(crate) fn program_clauses_revealing(
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    goal: &DomainGoal<ChalkIr>,
+) -> Result<RevealedClauses, MissingData> {
+    let clauses = program_clauses_with_origins(db, environment, goal)?;
+
+    // The impls that could supply the clauses for `goal`, with their
+    // priorities. For a `Normalize` goal, these are the impls that
+    // define the associated type (whatever its value).
+    let (trait_ref, associated_ty_id) = match goal {
+        DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => (trait_ref.clone(), None),
+        DomainGoal::Normalize(normalize) => (
+            db.try_trait_ref_from_projection(&normalize.projection)?,
+            Some(normalize.projection.associated_ty_id),
+        ),
+        _ => return Ok((clauses, false)),
+    };
+    let mut candidates = vec![];
+    for (impl_id, impl_datum) in impls_for_trait(db, trait_ref.trait_id, &trait_ref.parameters)? {
+        let priority = match db.impl_specialization_priority(impl_id) {
+            Some(priority) => priority,
+            None => continue,
+        };
+        if !impl_datum.is_positive() {
+            continue;
+        }
+        if let Some(associated_ty_id) = associated_ty_id {
+            let mut defines_value = false;
+            for &atv_id in &impl_datum.associated_ty_value_ids {
+                defines_value |=
+                    db.try_associated_ty_value(atv_id)?.associated_ty_id == associated_ty_id;
+            }
+            if !defines_value {
+                continue;
+            }
+        }
+        candidates.push((priority, impl_datum));
+    }
+
+    let mut result = Vec::with_capacity(clauses.len());
+    let mut specialized = false;
+    for (clause, origin) in clauses {
+        let priority = match origin {
+            ClauseOrigin::Impl(impl_id) => db.impl_specialization_priority(impl_id),
+            _ => None,
+        };
+        let more_specialized: Vec<&ImplDatum> = match priority {
+            Some(priority) => candidates
+                .iter()
+                .filter(|&&(other, _)| other > priority)
+                .map(|(_, impl_datum)| &**impl_datum)
+                .collect(),
+            None => vec![],
+        };
+        if more_specialized.is_empty() {
+            result.push((clause, origin));
+            continue;
+        }
+
+        specialized = true;
+        let clause = map_implication(clause, |implication| {
+            let trait_ref = match &implication.consequence {
+                DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => trait_ref.clone(),
+                DomainGoal::Normalize(normalize) => {
+                    db.try_trait_ref_from_projection(&normalize.projection)?
+                }
+                _ => return Ok(()),
+            };
+            implication.conditions.extend(
+                more_specialized
+                    .iter()
+                    .map(|impl_datum| impl_applies(impl_datum, &trait_ref).negate()),
+            );
+            Ok(())
+        })?;
+        result.push((clause, origin));
+    }
+    Ok((result, specialized))
+}
+
+/// Applies `op` to the implication of `clause`, under its binders (if
+/// any).
+fn map_implication(
+    clause: ProgramClause<ChalkIr>,
+    op: impl FnOnce(&mut ProgramClauseImplication<ChalkIr>) -> Result<(), MissingData>,
+) -> Result<ProgramClause<ChalkIr>, MissingData> {
+    match clause {
+        ProgramClause::Implies(mut implication) => {
+            op(&mut implication)?;
+            Ok(ProgramClause::Implies(implication))
+        }
+        ProgramClause::ForAll(mut binders) => {
+            op(&mut binders.value)?;
+            Ok(ProgramClause::ForAll(binders))
+        }
+    }
+}
+
+/// A goal that holds if `impl_datum` applies to `trait_ref`:
+///
+/// ```notrust
+/// exists<impl binders> { impl params = trait_ref params, impl WC }
+/// ```
+fn impl_applies(impl_datum: &ImplDatum, trait_ref: &TraitRef<ChalkIr>) -> Goal<ChalkIr> {
+    let len = impl_datum.binders.len();
+    let params_goals = impl_datum
+        .binders
+        .value
+        .trait_ref
+        .parameters
+        .iter()
+        .cloned()
+        .zip(trait_ref.parameters.iter().map(|p| p.shifted_in(len)))
+        .map(|(a, b)| Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })));
+    let wc_goals = impl_datum
+        .binders
+        .value
+        .where_clauses
+        .iter()
+        .cloned()
+        .casted();
+    params_goals
+        .chain(wc_goals)
+        .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+        .expect("Every trait takes at least one input type")
+        .quantify(QuantifierKind::Exists, impl_datum.binders.binders)
+}
@@ -0,0 +1,97 @@
+//! The built-in rules of `Send` and `Sync` that differ from the
+//! structural rule of auto traits (under which a type implements an
+//! auto trait if its fields, or element type, do). Raw pointers are
+//! neither `Send` nor `Sync`, since nothing is known about who else
+//! may access their pointee; `UnsafeCell` permits mutation through a
+//! shared reference, so it is not `Sync`; and a shared reference is
+//! `Send` only if its referent may be shared, i.e. is `Sync`.
+//!
+//! These rules only remove or change built-in impls: an explicit impl
+//! of `Send` or `Sync`, such as `impl Send for *const Foo { }`, still
+//! applies. (For `UnsafeCell`, as for any struct, an explicit impl
+//! replaces the built-in rule altogether.)
+
+use crate::RustIrDatabase;
+use chalk_ir::{BuiltinType, Mutability, TraitId, TypeKindId, TypeName};
+use chalk_rust_ir::LangItem;
+
+/// How a type constructor implements `Send` or `Sync`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(super) enum SendSyncRule {
+    /// The type does not implement the trait.
+    Never,
+
+    /// The type implements the trait if its element type implements
+    /// the given auto trait (instead of the trait itself).
+    ElementImplements(LangItem),
+}
+
+/// A type constructor with a rule in `SEND_SYNC_RULES`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Constructor {
+    Builtin(BuiltinType),
+    Struct(LangItem),
+}
+
+/// The type constructors that do not implement `Send` or `Sync`
+/// structurally, with the rule they follow instead.
+const SEND_SYNC_RULES: &[(Constructor, LangItem, SendSyncRule)] = &[
+    (
+        Constructor::Builtin(BuiltinType::Ptr(Mutability::Not)),
+        LangItem::Send,
+        SendSyncRule::Never,
+    ),
+    (
+        Constructor::Builtin(BuiltinType::Ptr(Mutability::Mut)),
+        LangItem::Send,
+        SendSyncRule::Never,
+    ),
+    (
+        Constructor::Builtin(BuiltinType::Ptr(Mutability::Not)),
+        LangItem::Sync,
+        SendSyncRule::Never,
+    ),
+    (
+        Constructor::Builtin(BuiltinType::Ptr(Mutability::Mut)),
+        LangItem::Sync,
+        SendSyncRule::Never,
+    ),
+    (
+        Constructor::Builtin(BuiltinType::Ref(Mutability::Not)),
+        LangItem::Send,
+        SendSyncRule::ElementImplements(LangItem::Sync),
+    ),
+    (
+        Constructor::Struct(LangItem::UnsafeCell),
+        LangItem::Sync,
+        SendSyncRule::Never,
+    ),
+];
+
+/// The rule by which the type constructor `name` implements the auto
+/// trait `auto_trait_id`, if that is `Send` or `Sync` and the rule is
+/// not the structural one. A rule that refers to a trait the program
+/// does not declare is ignored.
+pub(super) fn send_sync_rule(
+    db: &dyn RustIrDatabase,
+    auto_trait_id: TraitId,
+    name: TypeName,
+) -> Option<SendSyncRule> {
+    let is_lang_item = |item, id: TypeKindId| db.lang_item(item) == Some(id);
+    let is_constructor = |constructor| match (constructor, name) {
+        (Constructor::Builtin(builtin), TypeName::Builtin(name)) => builtin == name,
+        (Constructor::Struct(item), TypeName::TypeKindId(id)) => is_lang_item(item, id),
+        _ => false,
+    };
+
+    SEND_SYNC_RULES
+        .iter()
+        .find(|&&(constructor, trait_item, _)| {
+            is_lang_item(trait_item, auto_trait_id.into()) && is_constructor(constructor)
+        })
+        .map(|&(_, _, rule)| rule)
+        .filter(|rule| match rule {
+            SendSyncRule::Never => true,
+            SendSyncRule::ElementImplements(item) => db.lang_item(*item).is_some(),
+        })
+}
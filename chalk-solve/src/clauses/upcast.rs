@@ -0,0 +1,93 @@
+//! Clauses for `Upcast(dyn A => dyn B)` goals, for embedders that
+//! implement trait upcasting coercions.
+
+use super::builder::ClauseBuilder;
+use crate::MissingData;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+
+/// Pushes the clause that proves `goal`, if both of its types are
+/// `dyn` types. Given the goal `Upcast(dyn A + Send => dyn B<u32> +
+/// Send)`, we generate:
+///
+/// ```notrust
+/// Upcast(dyn A + Send => dyn B<u32> + Send) :-
+///     forall<T> { if (FromEnv(T: A), FromEnv(T: Send)) { FromEnv(T: B<u32>) } },
+///     forall<T> { if (FromEnv(T: A), FromEnv(T: Send)) { FromEnv(T: Send) } }
+/// ```
+///
+/// That is, every bound of the target must be implied by the bounds of
+/// the source. As `FromEnv` goals can only be proven from the
+/// environment, which is elaborated with the supertraits of the traits
+/// in it, this holds exactly when each trait in the target is a trait
+/// of the source or one of its supertraits. Bounds on associated types
+/// (`dyn Iterator<Item = u32>`) are proven as `ProjectionEq` goals.
+///
+/// No clause is generated if the database rejects the upcast from the
+/// principal (i.e., non-auto) trait of the source to that of the target
+/// (see `RustIrDatabase::upcast_vtable_compatible`).
+pub(super) fn push_upcast_clauses(
+    builder: &mut ClauseBuilder<'_>,
+    goal: &Upcast<ChalkIr>,
+) -> Result<(), MissingData> {
+    let (source_bounds, target_bounds) = match (&goal.source, &goal.target) {
+        (Ty::Dyn(source_bounds), Ty::Dyn(target_bounds)) => (source_bounds, target_bounds),
+        _ => return Ok(()),
+    };
+
+    if let (Some(source_trait), Some(target_trait)) = (
+        principal_trait(builder, source_bounds)?,
+        principal_trait(builder, target_bounds)?,
+    ) {
+        if source_trait != target_trait
+            && !builder
+                .db
+                .upcast_vtable_compatible(source_trait, target_trait)
+        {
+            debug!(
+                "push_upcast_clauses: {:?} cannot be upcast to {:?}",
+                source_trait, target_trait
+            );
+            return Ok(());
+        }
+    }
+
+    // Both sets of bounds are bound over the (hidden) self type,
+    // which becomes the `T` in `forall<T> { .. }`.
+    let hypotheses: Vec<ProgramClause<ChalkIr>> = source_bounds
+        .value
+        .iter()
+        .map(|bound| bound.clone().into_from_env_goal().cast())
+        .collect();
+    let conditions = target_bounds.value.iter().map(|bound| {
+        bound
+            .clone()
+            .into_from_env_goal()
+            .cast::<Goal<ChalkIr>>()
+            .implied_by(hypotheses.clone())
+            .quantify(QuantifierKind::ForAll, source_bounds.binders)
+    });
+
+    builder.push_clause(goal.clone(), conditions);
+    Ok(())
+}
+
+/// The first trait in `bounds` that is not an auto trait, if any.
+fn principal_trait(
+    builder: &ClauseBuilder<'_>,
+    bounds: &Binders<Vec<QuantifiedWhereClause<ChalkIr>>>,
+) -> Result<Option<TraitId>, MissingData> {
+    for bound in &bounds.value {
+        if let WhereClause::Implemented(trait_ref) = &bound.value {
+            if !builder
+                .db
+                .try_trait_datum(trait_ref.trait_id)?
+                .is_auto_trait()
+            {
+                return Ok(Some(trait_ref.trait_id));
+            }
+        }
+    }
+    Ok(None)
+}
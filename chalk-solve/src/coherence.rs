@@ -1,15 +1,24 @@
 use petgraph::prelude::*;
 
-use crate::solve::SolverChoice;
+use crate::clauses::ClauseOrigin;
+use crate::solve::{SolveMode, SolverChoice};
 use crate::RustIrDatabase;
-use chalk_ir::{self, Identifier, ImplId, TraitId};
+use chalk_ir::family::ChalkIr;
+use chalk_ir::{
+    self, Canonical, Identifier, ImplId, ProgramClause, QuantifiedWhereClause, Substitution,
+    TraitId,
+};
+use chalk_rust_ir::InherentImplId;
 use std::collections::BTreeMap;
 use std::fmt;
 use std::sync::Arc;
 
+mod inherent;
 pub mod orphan;
 mod solve;
 
+pub use self::inherent::InherentImplChecker;
+
 pub struct CoherenceSolver<'db, DB>
 where
     DB: RustIrDatabase,
@@ -21,25 +30,161 @@ where
 
 #[derive(Debug)]
 pub enum CoherenceError {
-    OverlappingImpls(Identifier),
+    /// Two impls of the named trait overlap; the `ImplOverlap`
+    /// explains why.
+    OverlappingImpls(Identifier, Box<ImplOverlap>),
     FailedOrphanCheck(Identifier),
+
+    /// Two inherent impls with overlapping self types both define an
+    /// item of the given name.
+    DuplicateInherentItem(Identifier, (InherentImplId, InherentImplId)),
+
+    /// The impls of the named trait specialize one another in a cycle
+    /// (each impl specializing the next, in some order), and so cannot
+    /// be given priorities.
+    SpecializationCycle(Identifier, Vec<ImplId>),
+}
+
+/// Why two impls were found to overlap, for diagnostics.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImplOverlap {
+    /// The impls that overlap.
+    pub impls: (ImplId, ImplId),
+
+    /// Values for the parameters of both impls (those of the first
+    /// impl, then those of the second) for which both impls apply, if
+    /// the solver found a unique such set of values. Parameters left
+    /// unconstrained are bound by the `Canonical`.
+    pub witness: Option<Canonical<Substitution<ChalkIr>>>,
+
+    /// The where clauses of both impls, with the `witness` applied (if
+    /// any), and the clauses that could prove them.
+    pub where_clauses: Vec<OverlapWhereClause>,
+
+    /// True if the impls apply to disjoint sets of types in this crate
+    /// as it is, and overlap only because that cannot be relied upon:
+    /// an upstream crate might add an impl (unless the trait is
+    /// `#[fundamental]`), or a downstream crate might add a type, that
+    /// satisfies the where clauses of both.
+    pub negative_reasoning: bool,
+}
+
+/// A where clause of one of two overlapping impls; see `ImplOverlap`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OverlapWhereClause {
+    pub where_clause: QuantifiedWhereClause<ChalkIr>,
+
+    /// The program clauses that could prove `where_clause`, each with
+    /// the declaration that it was derived from (e.g., the impl that
+    /// satisfies it).
+    pub clauses: Vec<(ProgramClause<ChalkIr>, ClauseOrigin)>,
 }
 
 impl fmt::Display for CoherenceError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            CoherenceError::OverlappingImpls(id) => {
+            CoherenceError::OverlappingImpls(id, _) => {
                 write!(f, "overlapping impls of trait {:?}", id)
             }
             CoherenceError::FailedOrphanCheck(id) => {
                 write!(f, "impl for trait {:?} violates the orphan rules", id)
             }
+            CoherenceError::DuplicateInherentItem(name, _) => write!(
+                f,
+                "duplicate definitions with name {:?} in overlapping inherent impls",
+                name
+            ),
+            CoherenceError::SpecializationCycle(id, _) => {
+                write!(
+                    f,
+                    "impls of trait {:?} specialize one another in a cycle",
+                    id
+                )
+            }
         }
     }
 }
 
 impl std::error::Error for CoherenceError {}
 
+/// The specialization graph of a trait: its impls (those checked for
+/// coherence), with an edge from each impl to every impl that
+/// specializes it. Impls that overlap no other impl have no edges.
+/// Every pair of impls is checked, so if `B` specializes `A` and `C`
+/// specializes `B`, there is an edge from `A` to `C` as well.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SpecializationGraph {
+    impls: Vec<ImplId>,
+    specializations: BTreeMap<ImplId, Vec<ImplId>>,
+}
+
+impl SpecializationGraph {
+    fn new(mut impls: Vec<ImplId>) -> Self {
+        impls.sort();
+        SpecializationGraph {
+            impls,
+            specializations: BTreeMap::new(),
+        }
+    }
+
+    fn add_edge(&mut self, less_special: ImplId, more_special: ImplId) {
+        let impls = self.specializations.entry(less_special).or_default();
+        if let Err(index) = impls.binary_search(&more_special) {
+            impls.insert(index, more_special);
+        }
+    }
+
+    /// The impls of the trait, in order.
+    pub fn impls(&self) -> &[ImplId] {
+        &self.impls
+    }
+
+    /// The impls that specialize `impl_id`, in order.
+    pub fn specializations(&self, impl_id: ImplId) -> &[ImplId] {
+        self.specializations
+            .get(&impl_id)
+            .map_or(&[], |impls| &impls[..])
+    }
+
+    /// The impls that `impl_id` specializes.
+    pub fn specialized_impls(&self, impl_id: ImplId) -> Vec<ImplId> {
+        self.edges()
+            .filter(|&(_, more_special)| more_special == impl_id)
+            .map(|(less_special, _)| less_special)
+            .collect()
+    }
+
+    /// The edges of the graph, as pairs `(less_special, more_special)`.
+    pub fn edges(&self) -> impl Iterator<Item = (ImplId, ImplId)> + '_ {
+        self.specializations
+            .iter()
+            .flat_map(|(&less_special, impls)| {
+                impls
+                    .iter()
+                    .map(move |&more_special| (less_special, more_special))
+            })
+    }
+
+    fn to_graph_map(&self) -> DiGraphMap<ImplId, ()> {
+        let mut graph = DiGraphMap::new();
+        for (less_special, more_special) in self.edges() {
+            graph.add_edge(less_special, more_special, ());
+        }
+        graph
+    }
+
+    /// The impls of a cycle of the graph, if it has one.
+    fn find_cycle(&self) -> Option<Vec<ImplId>> {
+        petgraph::algo::tarjan_scc(&self.to_graph_map())
+            .into_iter()
+            .find(|component| component.len() > 1)
+            .map(|mut component| {
+                component.sort();
+                component
+            })
+    }
+}
+
 /// Stores the specialization priorities for a set of impls.
 /// This basically encodes which impls specialize one another.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -53,11 +198,22 @@ impl SpecializationPriorities {
         self.map[&impl_id]
     }
 
-    /// Store the priority of an impl (used during construction).
-    /// Panics if we have already stored the priority for this impl.
-    fn insert(&mut self, impl_id: ImplId, p: SpecializationPriority) {
-        let old_value = self.map.insert(impl_id, p);
-        assert!(old_value.is_none());
+    /// Lookup the priority of an impl in the set, if it is there.
+    pub fn get(&self, impl_id: ImplId) -> Option<SpecializationPriority> {
+        self.map.get(&impl_id).cloned()
+    }
+
+    /// Store the priority of an impl (used during construction),
+    /// unless it already has a higher one. Returns true if it was
+    /// stored.
+    fn raise(&mut self, impl_id: ImplId, p: SpecializationPriority) -> bool {
+        match self.map.get(&impl_id) {
+            Some(&old_value) if old_value >= p => false,
+            _ => {
+                self.map.insert(impl_id, p);
+                true
+            }
+        }
     }
 }
 
@@ -71,11 +227,14 @@ impl<'db, DB> CoherenceSolver<'db, DB>
 where
     DB: RustIrDatabase,
 {
-    /// Constructs a new `CoherenceSolver`.
+    /// Constructs a new `CoherenceSolver`. The checks solve in the
+    /// regular mode whatever the mode of `solver_choice`, since
+    /// `SolveMode::Revealing` relies on the specialization priorities
+    /// that they compute.
     pub fn new(db: &'db DB, solver_choice: SolverChoice, trait_id: TraitId) -> Self {
         Self {
             db,
-            solver_choice,
+            solver_choice: solver_choice.with_solve_mode(SolveMode::Regular),
             trait_id,
         }
     }
@@ -83,9 +242,46 @@ where
     pub fn specialization_priorities(
         &self,
     ) -> Result<Arc<SpecializationPriorities>, CoherenceError> {
-        let mut result = SpecializationPriorities::default();
+        let graph = self.specialization_graph()?;
+        Ok(Arc::new(self.priorities_from_graph(&graph)))
+    }
 
-        let forest = self.build_specialization_forest()?;
+    /// Returns the specialization graph of the trait, from which
+    /// `specialization_priorities` derives the priorities, or an error
+    /// if impls overlap without one specializing the other, or
+    /// specialize one another in a cycle.
+    pub fn specialization_graph(&self) -> Result<SpecializationGraph, CoherenceError> {
+        let mut graph =
+            SpecializationGraph::new(self.db.local_impls_to_coherence_check(self.trait_id));
+
+        // Find all specializations (implemented in coherence/solve)
+        // Record them in the graph by adding an edge from the less special
+        // to the more special.
+        self.visit_specializations_of_trait(|less_special, more_special| {
+            graph.add_edge(less_special, more_special);
+        })?;
+
+        self.check_for_cycles(graph)
+    }
+
+    fn check_for_cycles(
+        &self,
+        graph: SpecializationGraph,
+    ) -> Result<SpecializationGraph, CoherenceError> {
+        match graph.find_cycle() {
+            Some(cycle) => {
+                let trait_name = self.db.type_name(self.trait_id.into());
+                Err(CoherenceError::SpecializationCycle(trait_name, cycle))
+            }
+            None => Ok(graph),
+        }
+    }
+
+    fn priorities_from_graph(&self, graph: &SpecializationGraph) -> SpecializationPriorities {
+        // The forest is built as a GraphMap, so that we never add
+        // multiple nodes with the same ImplId.
+        let forest = graph.to_graph_map().into_graph();
+        let mut result = SpecializationPriorities::default();
 
         // Visit every root in the forest & set specialization
         // priority for the tree that is the root of.
@@ -93,26 +289,33 @@ where
             self.set_priorities(root_idx, &forest, 0, &mut result);
         }
 
-        Ok(Arc::new(result))
+        result
     }
 
-    // Build the forest of specialization relationships.
-    fn build_specialization_forest(&self) -> Result<Graph<ImplId, ()>, CoherenceError> {
-        // The forest is returned as a graph but built as a GraphMap; this is
-        // so that we never add multiple nodes with the same ItemId.
-        let mut forest = DiGraphMap::new();
-
-        // Find all specializations (implemented in coherence/solve)
-        // Record them in the forest by adding an edge from the less special
-        // to the more special.
-        self.visit_specializations_of_trait(|less_special, more_special| {
-            forest.add_edge(less_special, more_special, ());
+    /// Like `specialization_priorities`, but checks the pairs of impls
+    /// for overlap in parallel (using rayon).
+    #[cfg(feature = "parallel")]
+    pub fn check_trait_coherence_parallel(
+        &self,
+    ) -> Result<Arc<SpecializationPriorities>, CoherenceError>
+    where
+        DB: crate::SyncDatabase,
+    {
+        let mut graph =
+            SpecializationGraph::new(self.db.local_impls_to_coherence_check(self.trait_id));
+        self.visit_specializations_of_trait_parallel(|less_special, more_special| {
+            graph.add_edge(less_special, more_special);
         })?;
-
-        Ok(forest.into_graph())
+        let graph = self.check_for_cycles(graph)?;
+        Ok(Arc::new(self.priorities_from_graph(&graph)))
     }
 
     // Recursively set priorities for those node and all of its children.
+    //
+    // A node may be reached along several paths, e.g. the last impl of a
+    // chain of three impls, which specializes both of the others. It gets
+    // the priority of the longest path, so that it is above every impl it
+    // specializes.
     fn set_priorities(
         &self,
         idx: NodeIndex,
@@ -125,7 +328,9 @@ where
             let impl_id = forest
                 .node_weight(idx)
                 .expect("index should be a valid index into graph");
-            map.insert(*impl_id, SpecializationPriority(p));
+            if !map.raise(*impl_id, SpecializationPriority(p)) {
+                return;
+            }
         }
 
         // Visit all children of this node, setting their priority to this + 1
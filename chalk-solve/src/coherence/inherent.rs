@@ -0,0 +1,102 @@
+//! Coherence for inherent impls. Unlike trait impls, inherent impls
+//! may overlap, but two overlapping inherent impls must not define an
+//! associated function or constant of the same name (rustc's E0592),
+//! as a path like `Foo::<T>::bar` could then refer to either.
+
+use crate::coherence::CoherenceError;
+use crate::ext::*;
+use crate::solve::{Solution, SolveMode, SolverChoice};
+use crate::RustIrDatabase;
+use chalk_ir::cast::*;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::*;
+use chalk_rust_ir::InherentImplDatum;
+use itertools::Itertools;
+use std::sync::Arc;
+
+/// Checks the `RustIrDatabase::local_inherent_impls` for duplicate
+/// items.
+pub struct InherentImplChecker<'db, DB>
+where
+    DB: RustIrDatabase,
+{
+    db: &'db DB,
+    solver_choice: SolverChoice,
+}
+
+impl<'db, DB> InherentImplChecker<'db, DB>
+where
+    DB: RustIrDatabase,
+{
+    pub fn new(db: &'db DB, solver_choice: SolverChoice) -> Self {
+        InherentImplChecker { db, solver_choice }
+    }
+
+    /// Reports the first item that is defined by two inherent impls
+    /// whose self types overlap. Impls are only tested for overlap if
+    /// they have an item name in common.
+    pub fn check(&self) -> Result<(), CoherenceError> {
+        let impls = self.db.local_inherent_impls();
+        for (lhs, rhs) in impls.iter().tuple_combinations() {
+            let duplicate = match lhs.items.iter().find(|name| rhs.items.contains(name)) {
+                Some(&name) => name,
+                None => continue,
+            };
+            if !self.disjoint(lhs, rhs) {
+                return Err(CoherenceError::DuplicateInherentItem(
+                    duplicate,
+                    (lhs.id, rhs.id),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Test if the self types of two inherent impls are disjoint, in the same way as
+    // `CoherenceSolver::disjoint` tests trait impls:
+    //
+    //  Impls:
+    //      impl<T> Foo<T> where T: Bar { fn baz; }
+    //      impl Foo<u32> { fn baz; }
+    //  Generates:
+    //      not { compatible { exists<T> { Foo<T> = Foo<u32>, T: Bar } } }
+    //
+    fn disjoint(&self, lhs: &Arc<InherentImplDatum>, rhs: &Arc<InherentImplDatum>) -> bool {
+        debug_heading!("inherent disjoint(lhs={:#?}, rhs={:#?})", lhs, rhs);
+
+        let lhs_len = lhs.binders.len();
+        let eq_goal = Goal::Leaf(LeafGoal::EqGoal(EqGoal {
+            a: lhs.binders.value.self_ty.clone().cast(),
+            b: rhs.binders.value.self_ty.shifted_in(lhs_len).cast(),
+        }));
+        let lhs_where_clauses = lhs.binders.value.where_clauses.iter().cloned();
+        let rhs_where_clauses = rhs
+            .binders
+            .value
+            .where_clauses
+            .iter()
+            .map(|wc| wc.shifted_in(lhs_len));
+        let binders = lhs.binders.binders.concat(&rhs.binders.binders);
+
+        let goal = Some(eq_goal)
+            .into_iter()
+            .chain(lhs_where_clauses.chain(rhs_where_clauses).casted())
+            .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+            .unwrap()
+            .quantify(QuantifierKind::Exists, binders)
+            .compatible()
+            .negate();
+
+        let solution = self
+            .solver_choice
+            .with_solve_mode(SolveMode::Intercrate)
+            .into_solver()
+            .solve(self.db, &goal.into_closed_goal());
+        let result = match solution {
+            Some(Solution::Unique(_)) => true,
+            Some(Solution::Ambig(_)) | None => false,
+        };
+        debug!("inherent disjoint: result = {:?}", result);
+        result
+    }
+}
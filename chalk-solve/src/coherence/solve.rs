@@ -1,13 +1,17 @@
-use crate::coherence::{CoherenceError, CoherenceSolver};
+use crate::clauses::{impls_for_trait, program_clauses_with_origins};
+use crate::coherence::{CoherenceError, CoherenceSolver, ImplOverlap, OverlapWhereClause};
 use crate::ext::*;
+use crate::infer::InferenceTable;
 use crate::RustIrDatabase;
-use crate::Solution;
+use crate::{Solution, SolveMode};
 use chalk_ir::cast::*;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::fold::shift::Shift;
+use chalk_ir::fold::Subst;
 use chalk_ir::*;
 use chalk_rust_ir::*;
 use itertools::Itertools;
+use std::sync::Arc;
 
 impl<'db, DB> CoherenceSolver<'db, DB>
 where
@@ -26,32 +30,88 @@ where
         // Iterate over every pair of impls for the same trait.
         let impls = self.db.local_impls_to_coherence_check(self.trait_id);
         for (l_id, r_id) in impls.into_iter().tuple_combinations() {
-            let lhs = &self.db.impl_datum(l_id);
-            let rhs = &self.db.impl_datum(r_id);
-
-            // Two negative impls never overlap.
-            if !lhs.is_positive() && !rhs.is_positive() {
-                continue;
+            if let Some((less_special, more_special)) = self.check_impl_pair(l_id, r_id)? {
+                record_specialization(less_special, more_special);
             }
+        }
 
-            // Check if the impls overlap, then if they do, check if one specializes
-            // the other. Note that specialization can only run one way - if both
-            // specialization checks return *either* true or false, that's an error.
-            if !self.disjoint(lhs, rhs) {
-                match (self.specializes(lhs, rhs), self.specializes(rhs, lhs)) {
-                    (true, false) => record_specialization(l_id, r_id),
-                    (false, true) => record_specialization(r_id, l_id),
-                    (_, _) => {
-                        let trait_name = self.db.type_name(self.trait_id.into());
-                        Err(CoherenceError::OverlappingImpls(trait_name))?;
-                    }
-                }
+        Ok(())
+    }
+
+    /// Like `visit_specializations_of_trait`, but checks the pairs of
+    /// impls in parallel. Each pair is an independent query (with its
+    /// own solver), so the only shared state is the (read-only)
+    /// database. The results are collected in the same order in which
+    /// the sequential version visits them, so the specializations
+    /// recorded and the error reported (if any) are deterministic.
+    #[cfg(feature = "parallel")]
+    pub(super) fn visit_specializations_of_trait_parallel(
+        &self,
+        mut record_specialization: impl FnMut(ImplId, ImplId),
+    ) -> Result<(), CoherenceError>
+    where
+        DB: crate::SyncDatabase,
+    {
+        use rayon::prelude::*;
+
+        let trait_datum = self.db.trait_datum(self.trait_id);
+        if trait_datum.flags.marker {
+            return Ok(());
+        }
+
+        let impls = self.db.local_impls_to_coherence_check(self.trait_id);
+        let pairs: Vec<(ImplId, ImplId)> = impls.into_iter().tuple_combinations().collect();
+        let results: Vec<_> = pairs
+            .into_par_iter()
+            .map(|(l_id, r_id)| self.check_impl_pair(l_id, r_id))
+            .collect();
+
+        for result in results {
+            if let Some((less_special, more_special)) = result? {
+                record_specialization(less_special, more_special);
             }
         }
 
         Ok(())
     }
 
+    /// Checks a single pair of impls. Returns `Ok(None)` if they are
+    /// disjoint and `Ok(Some((less_special, more_special)))` if one
+    /// specializes the other.
+    fn check_impl_pair(
+        &self,
+        l_id: ImplId,
+        r_id: ImplId,
+    ) -> Result<Option<(ImplId, ImplId)>, CoherenceError> {
+        let lhs = &self.db.impl_datum(l_id);
+        let rhs = &self.db.impl_datum(r_id);
+
+        // Two negative impls never overlap.
+        if !lhs.is_positive() && !rhs.is_positive() {
+            return Ok(None);
+        }
+
+        // Check if the impls overlap, then if they do, check if one specializes
+        // the other. Note that specialization can only run one way - if both
+        // specialization checks return *either* true or false, that's an error.
+        if self.disjoint(lhs, rhs) || self.disjoint_by_negative_impls(lhs, rhs) {
+            return Ok(None);
+        }
+
+        match (self.specializes(lhs, rhs), self.specializes(rhs, lhs)) {
+            (true, false) => Ok(Some((l_id, r_id))),
+            (false, true) => Ok(Some((r_id, l_id))),
+            (_, _) => {
+                let trait_name = self.db.type_name(self.trait_id.into());
+                let overlap = self.explain_overlap((l_id, r_id), lhs, rhs);
+                Err(CoherenceError::OverlappingImpls(
+                    trait_name,
+                    Box::new(overlap),
+                ))
+            }
+        }
+    }
+
     // Test if the set of types that these two impls apply to overlap. If the test succeeds, these
     // two impls are disjoint.
     //
@@ -88,61 +148,208 @@ where
     fn disjoint(&self, lhs: &ImplDatum, rhs: &ImplDatum) -> bool {
         debug_heading!("overlaps(lhs={:#?}, rhs={:#?})", lhs, rhs);
 
-        let lhs_len = lhs.binders.len();
+        let goal = overlap_goal(lhs, rhs).compatible().negate();
 
-        // Join the two impls' binders together
-        let mut binders = lhs.binders.binders.clone();
-        binders.extend(rhs.binders.binders.clone());
+        let canonical_goal = &goal.into_closed_goal();
+        let solution = self
+            .solver_choice
+            .with_solve_mode(SolveMode::Intercrate)
+            .into_solver()
+            .solve(self.db, canonical_goal);
+        let result = match solution {
+            // Goal was proven with a unique solution, so no impl was found that causes these two
+            // to overlap
+            Some(Solution::Unique(_)) => true,
+            // Goal was ambiguous, so there *may* be overlap
+            Some(Solution::Ambig(_)) |
+            // Goal cannot be proven, so there is some impl that causes overlap
+            None => false,
+        };
+        debug!("overlaps: result = {:?}", result);
+        result
+    }
 
-        // Upshift the rhs variables in params to account for the joined binders
-        let lhs_params = params(lhs).iter().cloned();
-        let rhs_params = params(rhs).iter().map(|param| param.shifted_in(lhs_len));
+    // Explains why two impls that are not disjoint overlap (see `ImplOverlap`).
+    //
+    // We solve the goal of `disjoint` before the `compatible` modality and negation are applied,
+    // as an ordinary query. If it has a unique solution, that gives the types for which both
+    // impls apply. If it has none, then the impls only overlap in some other compatible world,
+    // and it was the negative reasoning needed to rule that out that failed.
+    fn explain_overlap(
+        &self,
+        impls: (ImplId, ImplId),
+        lhs: &ImplDatum,
+        rhs: &ImplDatum,
+    ) -> ImplOverlap {
+        let binders = joined_binders(lhs, rhs);
+        let (canonical_goal, map) = overlap_goal(lhs, rhs).into_peeled_goal_with_map();
+        let solution = self
+            .solver_choice
+            .into_solver()
+            .solve(self.db, &canonical_goal);
+        debug!("explain_overlap: solution = {:?}", solution);
 
-        // Create an equality goal for every input type the trait, attempting
-        // to unify the inputs to both impls with one another
-        let params_goals = lhs_params
-            .zip(rhs_params)
-            .map(|(a, b)| Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })));
+        let negative_reasoning = solution.is_none();
+        let witness = match solution {
+            Some(Solution::Unique(constrained)) => {
+                let Canonical {
+                    binders: mut witness_binders,
+                    value,
+                } = constrained;
+                let values = map.existential_values(&value.subst);
+                let parameters = values
+                    .into_iter()
+                    .zip(&binders)
+                    .map(|(value, kind)| {
+                        // Parameters that do not appear in the goal can be anything.
+                        value.unwrap_or_else(|| {
+                            witness_binders.push(kind.map(|()| UniverseIndex::root()));
+                            (kind, witness_binders.len() - 1).to_parameter()
+                        })
+                    })
+                    .collect();
+                Some(Canonical {
+                    binders: witness_binders,
+                    value: Substitution { parameters },
+                })
+            }
+            Some(Solution::Ambig(_)) | None => None,
+        };
 
-        // Upshift the rhs variables in where clauses
-        let lhs_where_clauses = lhs.binders.value.where_clauses.iter().cloned();
-        let rhs_where_clauses = rhs
+        let where_clauses = joined_where_clauses(lhs, rhs)
+            .map(|where_clause| {
+                let where_clause = match &witness {
+                    Some(witness) => Subst::apply(&witness.value.parameters, &where_clause),
+                    None => where_clause,
+                };
+                // The clauses are only for diagnostics, so we make do
+                // without those that the database fails to supply.
+                let goal = DomainGoal::Holds(where_clause.value.clone());
+                let clauses = program_clauses_with_origins(self.db, &Environment::new(), &goal)
+                    .unwrap_or_default();
+                OverlapWhereClause {
+                    where_clause,
+                    clauses,
+                }
+            })
+            .collect();
+
+        ImplOverlap {
+            impls,
+            witness,
+            where_clauses,
+            negative_reasoning,
+        }
+    }
+
+    // Test if the two impls are disjoint because, wherever they overlap, one of their where
+    // clauses is known *not* to hold: it is covered by an explicit negative impl. Unlike the
+    // absence of a positive impl, a negative impl `impl !Bar for Baz` is a promise that `Baz: Bar`
+    // will never hold, in this crate or any other, so we can rely on it in every compatible world.
+    //
+    // We unify the trait refs of the two impls (giving up if they do not unify, or only do so
+    // modulo projections, in which case `disjoint` is the more precise test). Then, for each
+    // `Implemented` where clause of either impl, and each negative impl of its trait, we check
+    // that the negative impl applies for all values of the remaining variables, assuming the
+    // where clauses of both impls.
+    //
+    // Example:
+    //
+    //  Impls:
+    //      impl<T> Foo for T where T: Bar { }
+    //      impl<U> Foo for U where U: Baz { }
+    //      impl<V> !Baz for V where V: Bar { }
+    //  Unifying `T` and `U` and checking `U: Baz` against the negative impl generates:
+    //      forall<T> { if (T: Bar, T: Baz) { exists<V> { T = V, V: Bar } } }
+    //
+    fn disjoint_by_negative_impls(&self, lhs: &ImplDatum, rhs: &ImplDatum) -> bool {
+        debug_heading!("disjoint_by_negative_impls(lhs={:#?}, rhs={:#?})", lhs, rhs);
+
+        let mut infer = InferenceTable::new();
+        let environment = Environment::new();
+        let (lhs_trait_ref, lhs_where_clauses) = instantiate_impl(&mut infer, lhs);
+        let (rhs_trait_ref, rhs_where_clauses) = instantiate_impl(&mut infer, rhs);
+        match infer.unify(&environment, &lhs_trait_ref, &rhs_trait_ref) {
+            Ok(result) if result.goals.is_empty() && !result.ambiguous => {}
+            _ => return false,
+        }
+
+        let mut where_clauses = lhs_where_clauses;
+        where_clauses.extend(rhs_where_clauses);
+        let where_clauses = infer.canonicalize(&where_clauses).quantified;
+        let binders: Vec<_> = where_clauses
+            .binders
+            .iter()
+            .map(|pk| pk.map(|_| ()))
+            .collect();
+
+        let result = where_clauses.value.iter().any(|wc| {
+            let trait_ref = match wc {
+                Binders {
+                    binders,
+                    value: WhereClause::Implemented(trait_ref),
+                } if binders.is_empty() => trait_ref,
+                _ => return false,
+            };
+            impls_for_trait(self.db, trait_ref.trait_id, &trait_ref.parameters)
+                .unwrap_or_else(|missing| panic!("{}", missing))
+                .into_iter()
+                .map(|(_, impl_datum)| impl_datum)
+                .filter(|impl_datum| !impl_datum.is_positive())
+                .any(|negative| {
+                    self.negative_impl_applies(&negative, trait_ref, &where_clauses.value, &binders)
+                })
+        });
+        debug!("disjoint_by_negative_impls: result = {:?}", result);
+        result
+    }
+
+    // Test if the negative impl `negative` applies to `trait_ref` for all values of the variables
+    // in `binders`, assuming `hypotheses`:
+    //
+    //  forall<binders> { if (hypotheses) { exists<negative binders> { trait_ref = negative trait
+    //  ref, negative where clauses } } }
+    //
+    fn negative_impl_applies(
+        &self,
+        negative: &ImplDatum,
+        trait_ref: &TraitRef<ChalkIr>,
+        hypotheses: &[QuantifiedWhereClause<ChalkIr>],
+        binders: &[ParameterKind<()>],
+    ) -> bool {
+        let negative_len = negative.binders.len();
+        let params_goals = trait_ref
+            .parameters
+            .iter()
+            .map(|p| p.shifted_in(negative_len))
+            .zip(params(negative).iter().cloned())
+            .map(|(a, b)| Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })));
+        let wc_goals = negative
             .binders
             .value
             .where_clauses
             .iter()
-            .map(|wc| wc.shifted_in(lhs_len));
-
-        // Create a goal for each clause in both where clauses
-        let wc_goals = lhs_where_clauses
-            .chain(rhs_where_clauses)
-            .map(|wc| wc.cast());
+            .cloned()
+            .casted();
 
-        // Join all the goals we've created together with And, then quantify them
-        // over the joined binders. This is our query.
         let goal = params_goals
             .chain(wc_goals)
-            .fold1(|goal, leaf| Goal::And(Box::new(goal), Box::new(leaf)))
+            .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
             .expect("Every trait takes at least one input type")
-            .quantify(QuantifierKind::Exists, binders)
-            .compatible()
-            .negate();
+            .quantify(QuantifierKind::Exists, negative.binders.binders)
+            .implied_by(hypotheses.iter().cloned().casted().collect())
+            .quantify(QuantifierKind::ForAll, binders.to_vec());
 
         let canonical_goal = &goal.into_closed_goal();
-        let solution = self
+        let result = match self
             .solver_choice
             .into_solver()
-            .solve(self.db, canonical_goal);
-        let result = match solution {
-            // Goal was proven with a unique solution, so no impl was found that causes these two
-            // to overlap
-            Some(Solution::Unique(_)) => true,
-            // Goal was ambiguous, so there *may* be overlap
-            Some(Solution::Ambig(_)) |
-            // Goal cannot be proven, so there is some impl that causes overlap
+            .solve(self.db, canonical_goal)
+        {
+            Some(sol) => sol.is_unique(),
             None => false,
         };
-        debug!("overlaps: result = {:?}", result);
+        debug!("negative_impl_applies: result = {:?}", result);
         result
     }
 
@@ -174,11 +381,13 @@ where
             return false;
         }
 
-        let more_len = more_special.binders.len();
+        // The variables of `more_special` are bound outside of those of
+        // `less_special`, so they are shifted in under the latter.
+        let less_len = less_special.binders.len();
 
         // Create parameter equality goals.
-        let more_special_params = params(more_special).iter().cloned();
-        let less_special_params = params(less_special).iter().map(|p| p.shifted_in(more_len));
+        let more_special_params = params(more_special).iter().map(|p| p.shifted_in(less_len));
+        let less_special_params = params(less_special).iter().cloned();
         let params_goals = more_special_params
             .zip(less_special_params)
             .map(|(a, b)| Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })));
@@ -197,16 +406,17 @@ where
             .value
             .where_clauses
             .iter()
-            .map(|wc| wc.shifted_in(more_len).cast());
+            .cloned()
+            .casted();
 
         // Join all of the goals together.
         let goal = params_goals
             .chain(less_special_wc)
-            .fold1(|goal, leaf| Goal::And(Box::new(goal), Box::new(leaf)))
+            .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
             .expect("Every trait takes at least one input type")
-            .quantify(QuantifierKind::Exists, less_special.binders.binders.clone())
+            .quantify(QuantifierKind::Exists, less_special.binders.binders)
             .implied_by(more_special_wc)
-            .quantify(QuantifierKind::ForAll, more_special.binders.binders.clone());
+            .quantify(QuantifierKind::ForAll, more_special.binders.binders);
 
         let canonical_goal = &goal.into_closed_goal();
         let result = match self
@@ -224,6 +434,71 @@ where
     }
 }
 
+/// The where clauses of both impls, in terms of their joined binders.
+fn joined_where_clauses<'a>(
+    lhs: &'a ImplDatum,
+    rhs: &'a ImplDatum,
+) -> impl Iterator<Item = QuantifiedWhereClause<ChalkIr>> + 'a {
+    // Upshift the rhs variables in where clauses
+    let lhs_len = lhs.binders.len();
+    let lhs_where_clauses = lhs.binders.value.where_clauses.iter().cloned();
+    let rhs_where_clauses = rhs
+        .binders
+        .value
+        .where_clauses
+        .iter()
+        .map(move |wc| wc.shifted_in(lhs_len));
+    lhs_where_clauses.chain(rhs_where_clauses)
+}
+
+/// The goal that holds for the types to which both impls apply
+/// (see `disjoint`), quantified over the binders of both impls:
+///
+/// ```notrust
+/// exists<lhs binders, rhs binders> { lhs params = rhs params, lhs WC, rhs WC }
+/// ```
+fn overlap_goal(lhs: &ImplDatum, rhs: &ImplDatum) -> Goal<ChalkIr> {
+    let lhs_len = lhs.binders.len();
+
+    // Upshift the rhs variables in params to account for the joined binders
+    let lhs_params = params(lhs).iter().cloned();
+    let rhs_params = params(rhs).iter().map(|param| param.shifted_in(lhs_len));
+
+    // Create an equality goal for every input type the trait, attempting
+    // to unify the inputs to both impls with one another
+    let params_goals = lhs_params
+        .zip(rhs_params)
+        .map(|(a, b)| Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })));
+
+    // Create a goal for each clause in both where clauses
+    let wc_goals = joined_where_clauses(lhs, rhs).map(|wc| wc.cast());
+
+    // Join all the goals we've created together with And, then quantify them
+    // over the joined binders.
+    params_goals
+        .chain(wc_goals)
+        .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+        .expect("Every trait takes at least one input type")
+        .quantify(QuantifierKind::Exists, joined_binders(lhs, rhs))
+}
+
+/// The binders of both impls, those of `lhs` first.
+fn joined_binders(lhs: &ImplDatum, rhs: &ImplDatum) -> ParameterKinds {
+    lhs.binders.binders.concat(&rhs.binders.binders)
+}
+
 fn params(impl_datum: &ImplDatum) -> &[Parameter<ChalkIr>] {
     &impl_datum.binders.value.trait_ref.parameters
 }
+
+/// Instantiates the binders of `impl_datum` with fresh inference
+/// variables, returning its trait ref and where clauses.
+fn instantiate_impl(
+    infer: &mut InferenceTable,
+    impl_datum: &ImplDatum,
+) -> (TraitRef<ChalkIr>, Vec<QuantifiedWhereClause<ChalkIr>>) {
+    let bound = impl_datum
+        .binders
+        .map_ref(|b| (b.trait_ref.clone(), b.where_clauses.clone()));
+    infer.instantiate_binders_existentially(&bound)
+}
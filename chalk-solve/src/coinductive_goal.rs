@@ -2,6 +2,163 @@ use crate::RustIrDatabase;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::*;
 
+/// The shapes of goals that the solver treats as coinductive, for use
+/// with `SlgOptions::coinductive_goals`. By default, these are the
+/// goals `T: AutoTrait` and `WellFormed(T: Trait)` (see
+/// `IsCoinductive`); the other shapes are opt-in:
+///
+/// - `FromEnv(T: Trait)` and `FromEnv(T)`, whose clauses are
+///   elaborated from the environment. With implied bounds, proving
+///   one of these may cycle back to itself through the supertraits
+///   (e.g., when two traits each name the other as a supertrait).
+/// - `WellFormed(T)`, for types whose well-formedness (through their
+///   where clauses) depends on itself.
+/// - `Normalize(<T as Trait>::Item -> U)`.
+///
+/// ```
+/// # use chalk_solve::{CoinductiveGoals, SlgOptions, SolverChoice};
+/// let goals = CoinductiveGoals::default().from_env(true);
+/// let choice = SolverChoice::slg_with(SlgOptions::default().coinductive_goals(goals));
+/// ```
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct CoinductiveGoals {
+    auto_traits: bool,
+    well_formed_traits: bool,
+    well_formed_tys: bool,
+    from_env: bool,
+    normalize: bool,
+}
+
+impl CoinductiveGoals {
+    /// Sets whether `T: AutoTrait` is coinductive (default: true).
+    pub fn auto_traits(self, auto_traits: bool) -> Self {
+        CoinductiveGoals {
+            auto_traits,
+            ..self
+        }
+    }
+
+    /// Sets whether `WellFormed(T: Trait)` is coinductive (default:
+    /// true).
+    pub fn well_formed_traits(self, well_formed_traits: bool) -> Self {
+        CoinductiveGoals {
+            well_formed_traits,
+            ..self
+        }
+    }
+
+    /// Sets whether `WellFormed(T)` is coinductive (default: false).
+    pub fn well_formed_tys(self, well_formed_tys: bool) -> Self {
+        CoinductiveGoals {
+            well_formed_tys,
+            ..self
+        }
+    }
+
+    /// Sets whether `FromEnv(T: Trait)` and `FromEnv(T)` are
+    /// coinductive (default: false).
+    pub fn from_env(self, from_env: bool) -> Self {
+        CoinductiveGoals { from_env, ..self }
+    }
+
+    /// Sets whether `Normalize(<T as Trait>::Item -> U)` is
+    /// coinductive (default: false).
+    pub fn normalize(self, normalize: bool) -> Self {
+        CoinductiveGoals { normalize, ..self }
+    }
+}
+
+impl Default for CoinductiveGoals {
+    fn default() -> Self {
+        CoinductiveGoals {
+            auto_traits: true,
+            well_formed_traits: true,
+            well_formed_tys: false,
+            from_env: false,
+            normalize: false,
+        }
+    }
+}
+
+/// The kinds of goals that the solver distinguishes between when
+/// deciding whether a goal is coinductive (see `IsCoinductive`). An
+/// embedder that keeps caches of its own can key its handling of
+/// cycles on these: a cycle through a goal is only accepted if the
+/// goal is coinductive.
+///
+/// A goal `forall<..> { G }` has the kind of `G`.
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum GoalKind {
+    /// `T: Trait`, where `Trait` is an auto trait.
+    AutoTrait,
+
+    /// `T: Trait`, where `Trait` is not an auto trait.
+    Trait,
+
+    /// `ProjectionEq(<T as Trait>::Item = U)`.
+    ProjectionEq,
+
+    /// `Normalize(<T as Trait>::Item -> U)`.
+    Normalize,
+
+    /// `WellFormed(T: Trait)`.
+    WellFormedTrait,
+
+    /// `WellFormed(T)`.
+    WellFormedTy,
+
+    /// `FromEnv(T: Trait)` or `FromEnv(T)`.
+    FromEnv,
+
+    /// Any other domain goal, such as `IsLocal(T)`.
+    OtherDomainGoal,
+
+    /// A goal that is not a domain goal, such as `A, B`, `exists<T> {
+    /// G }` or `T = U`.
+    Compound,
+}
+
+impl GoalKind {
+    /// Classifies `goal`.
+    pub fn of(db: &dyn RustIrDatabase, goal: &Goal<ChalkIr>) -> GoalKind {
+        match goal {
+            Goal::Leaf(LeafGoal::DomainGoal(domain_goal)) => match domain_goal {
+                DomainGoal::Holds(WhereClause::Implemented(tr)) => {
+                    if db.trait_datum(tr.trait_id).is_auto_trait() {
+                        GoalKind::AutoTrait
+                    } else {
+                        GoalKind::Trait
+                    }
+                }
+                DomainGoal::Holds(WhereClause::ProjectionEq(..)) => GoalKind::ProjectionEq,
+                DomainGoal::WellFormed(WellFormed::Trait(..)) => GoalKind::WellFormedTrait,
+                DomainGoal::WellFormed(WellFormed::Ty(..)) => GoalKind::WellFormedTy,
+                DomainGoal::FromEnv(..) => GoalKind::FromEnv,
+                DomainGoal::Normalize(..) => GoalKind::Normalize,
+                _ => GoalKind::OtherDomainGoal,
+            },
+            Goal::Quantified(QuantifierKind::ForAll, goal) => GoalKind::of(db, &goal.value),
+            _ => GoalKind::Compound,
+        }
+    }
+
+    /// Whether goals of this kind are coinductive, given the shapes of
+    /// goals that are (see `SlgOptions::coinductive_goals`).
+    pub fn is_coinductive(self, goals: &CoinductiveGoals) -> bool {
+        match self {
+            GoalKind::AutoTrait => goals.auto_traits,
+            GoalKind::WellFormedTrait => goals.well_formed_traits,
+            GoalKind::WellFormedTy => goals.well_formed_tys,
+            GoalKind::FromEnv => goals.from_env,
+            GoalKind::Normalize => goals.normalize,
+            GoalKind::Trait
+            | GoalKind::ProjectionEq
+            | GoalKind::OtherDomainGoal
+            | GoalKind::Compound => false,
+        }
+    }
+}
+
 pub trait IsCoinductive {
     /// A goal G has coinductive semantics if proving G is allowed to
     /// assume G is true (very roughly speaking). In the case of
@@ -10,26 +167,19 @@ pub trait IsCoinductive {
     /// is any trait. The latter is needed for dealing with WF
     /// requirements and cyclic traits, which generates cycles in the
     /// proof tree which must not be rejected but instead must be
-    /// treated as a success.
-    fn is_coinductive(&self, db: &dyn RustIrDatabase) -> bool;
+    /// treated as a success. Which shapes count is determined by
+    /// `goals` (see `GoalKind::is_coinductive`).
+    fn is_coinductive(&self, db: &dyn RustIrDatabase, goals: &CoinductiveGoals) -> bool;
 }
 
 impl IsCoinductive for Goal<ChalkIr> {
-    fn is_coinductive(&self, db: &dyn RustIrDatabase) -> bool {
-        match self {
-            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(wca))) => match wca {
-                WhereClause::Implemented(tr) => db.trait_datum(tr.trait_id).is_auto_trait(),
-                WhereClause::ProjectionEq(..) => false,
-            },
-            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::WellFormed(WellFormed::Trait(..)))) => true,
-            Goal::Quantified(QuantifierKind::ForAll, goal) => goal.value.is_coinductive(db),
-            _ => false,
-        }
+    fn is_coinductive(&self, db: &dyn RustIrDatabase, goals: &CoinductiveGoals) -> bool {
+        GoalKind::of(db, self).is_coinductive(goals)
     }
 }
 
 impl IsCoinductive for UCanonical<InEnvironment<Goal<ChalkIr>>> {
-    fn is_coinductive(&self, db: &dyn RustIrDatabase) -> bool {
-        self.canonical.value.goal.is_coinductive(db)
+    fn is_coinductive(&self, db: &dyn RustIrDatabase, goals: &CoinductiveGoals) -> bool {
+        self.canonical.value.goal.is_coinductive(db, goals)
     }
 }
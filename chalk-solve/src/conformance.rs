@@ -0,0 +1,606 @@
+//! Checks that the items in a trait impl conform to the declarations
+//! in the trait. For associated type values, this means that:
+//!
+//! - every associated type declared in the trait has a value in the
+//!   impl, and the impl defines no others;
+//! - each value declares the same parameters as the associated type
+//!   (e.g., `type Item<'a>` in the trait requires `type Item<'a>` in
+//!   the impl);
+//! - each value satisfies the bounds declared on the associated type,
+//!   under the where clauses of the impl.
+//!
+//! For methods, it means that each method of the impl is declared in
+//! the trait, with the same parameters and signature, and with no where
+//! clauses beyond those that the declaration implies. Since methods
+//! have no bodies, we cannot tell which methods of the trait have
+//! defaults, so an impl need not define all of them.
+//!
+//! The WF check for impls also requires the bounds to hold, but it
+//! only reports that the impl as a whole is ill-formed; the errors
+//! here say which item is at fault.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::display::display;
+use crate::ext::*;
+use crate::solve::SolverChoice;
+use crate::split::Split;
+use crate::wf::FoldInputTypes;
+use crate::RustIrDatabase;
+use chalk_ir::cast::*;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::*;
+use chalk_rust_ir::*;
+use itertools::Itertools;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConformanceError {
+    /// The trait declares an associated type, without a default, that
+    /// the impl does not define.
+    MissingAssociatedTy {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        associated_ty_name: Identifier,
+    },
+
+    /// The impl defines a value for an associated type that is not
+    /// declared in the trait.
+    ExtraAssociatedTy {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        associated_ty_name: Identifier,
+    },
+
+    /// The parameters of an associated type value do not match those
+    /// of the declaration.
+    AssociatedTyParameters {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        associated_ty_name: Identifier,
+        expected: Vec<ParameterKind<()>>,
+        actual: Vec<ParameterKind<()>>,
+    },
+
+    /// An associated type value does not satisfy one of the bounds
+    /// declared on the associated type. The bound is given with the
+    /// value substituted for the associated type, and is quantified
+    /// over the parameters of the value and of the impl; it is
+    /// rendered with `display`, as the names of the items it mentions
+    /// are only known to the database.
+    UnsatisfiedAssociatedTyBound {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        associated_ty_name: Identifier,
+        bound: String,
+    },
+
+    /// The impl defines a method that is not declared in the trait.
+    ExtraMethod {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        method_name: Identifier,
+    },
+
+    /// The parameters of a method do not match those of the
+    /// declaration.
+    MethodParameters {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        method_name: Identifier,
+        expected: Vec<ParameterKind<()>>,
+        actual: Vec<ParameterKind<()>>,
+    },
+
+    /// The argument or return types of a method do not match those of
+    /// the declaration.
+    MethodSignature {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        method_name: Identifier,
+    },
+
+    /// A method has a where clause that the where clauses of the
+    /// declaration do not imply. The where clause is quantified over
+    /// the parameters of the method and of the impl, and rendered like
+    /// the bound of `UnsatisfiedAssociatedTyBound`.
+    StricterMethodRequirement {
+        impl_id: ImplId,
+        trait_name: Identifier,
+        method_name: Identifier,
+        where_clause: String,
+    },
+}
+
+impl fmt::Display for ConformanceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConformanceError::MissingAssociatedTy {
+                trait_name,
+                associated_ty_name,
+                ..
+            } => write!(
+                f,
+                "impl of trait {:?} is missing associated type {:?}",
+                trait_name, associated_ty_name
+            ),
+            ConformanceError::ExtraAssociatedTy {
+                trait_name,
+                associated_ty_name,
+                ..
+            } => write!(
+                f,
+                "associated type {:?} is not a member of trait {:?}",
+                associated_ty_name, trait_name
+            ),
+            ConformanceError::AssociatedTyParameters {
+                trait_name,
+                associated_ty_name,
+                expected,
+                actual,
+                ..
+            } => write!(
+                f,
+                "associated type {:?} in impl of trait {:?} has parameters {}, \
+                 but the trait declares {}",
+                associated_ty_name,
+                trait_name,
+                describe_parameters(actual),
+                describe_parameters(expected),
+            ),
+            ConformanceError::UnsatisfiedAssociatedTyBound {
+                trait_name,
+                associated_ty_name,
+                bound,
+                ..
+            } => write!(
+                f,
+                "associated type {:?} in impl of trait {:?} does not satisfy the bound `{}`",
+                associated_ty_name, trait_name, bound
+            ),
+            ConformanceError::ExtraMethod {
+                trait_name,
+                method_name,
+                ..
+            } => write!(
+                f,
+                "method {:?} is not a member of trait {:?}",
+                method_name, trait_name
+            ),
+            ConformanceError::MethodParameters {
+                trait_name,
+                method_name,
+                expected,
+                actual,
+                ..
+            } => write!(
+                f,
+                "method {:?} in impl of trait {:?} has parameters {}, \
+                 but the trait declares {}",
+                method_name,
+                trait_name,
+                describe_parameters(actual),
+                describe_parameters(expected),
+            ),
+            ConformanceError::MethodSignature {
+                trait_name,
+                method_name,
+                ..
+            } => write!(
+                f,
+                "method {:?} in impl of trait {:?} has a signature incompatible with the trait",
+                method_name, trait_name
+            ),
+            ConformanceError::StricterMethodRequirement {
+                trait_name,
+                method_name,
+                where_clause,
+                ..
+            } => write!(
+                f,
+                "method {:?} in impl of trait {:?} has the where clause `{}`, \
+                 which the trait does not require",
+                method_name, trait_name, where_clause
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ConformanceError {}
+
+fn describe_parameters(kinds: &[ParameterKind<()>]) -> String {
+    let kinds: Vec<_> = kinds
+        .iter()
+        .map(|kind| match kind {
+            ParameterKind::Ty(()) => "type",
+            ParameterKind::Lifetime(()) => "lifetime",
+        })
+        .collect();
+    format!("[{}]", kinds.join(", "))
+}
+
+pub struct ConformanceSolver<'db, DB: RustIrDatabase> {
+    db: &'db DB,
+    solver_choice: SolverChoice,
+}
+
+impl<'db, DB> ConformanceSolver<'db, DB>
+where
+    DB: RustIrDatabase,
+{
+    /// Constructs a new `ConformanceSolver`.
+    pub fn new(db: &'db DB, solver_choice: SolverChoice) -> Self {
+        Self { db, solver_choice }
+    }
+
+    /// Checks the impl `impl_id`, returning the first error found
+    /// (if any).
+    pub fn verify_impl(&self, impl_id: ImplId) -> Result<(), ConformanceError> {
+        match self.conformance_errors(impl_id).into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the impl `impl_id`, returning all errors found. Negative
+    /// impls define no items, and so always conform.
+    pub fn conformance_errors(&self, impl_id: ImplId) -> Vec<ConformanceError> {
+        let impl_datum = self.db.impl_datum(impl_id);
+        if !impl_datum.is_positive() {
+            return vec![];
+        }
+
+        let trait_id = impl_datum.trait_id();
+        let trait_datum = self.db.trait_datum(trait_id);
+        let trait_name = self.db.type_name(trait_id.into());
+        let mut errors = vec![];
+
+        let values: Vec<_> = impl_datum
+            .associated_ty_value_ids
+            .iter()
+            .map(|&id| self.db.associated_ty_value(id))
+            .collect();
+
+        for &associated_ty_id in &trait_datum.associated_ty_ids {
+            let associated_ty_datum = self.db.associated_ty_data(associated_ty_id);
+            if associated_ty_datum.binders.value.default.is_none()
+                && !values
+                    .iter()
+                    .any(|atv| atv.associated_ty_id == associated_ty_id)
+            {
+                errors.push(ConformanceError::MissingAssociatedTy {
+                    impl_id,
+                    trait_name,
+                    associated_ty_name: associated_ty_datum.name,
+                });
+            }
+        }
+
+        for atv in &values {
+            let associated_ty_datum = self.db.associated_ty_data(atv.associated_ty_id);
+            let associated_ty_name = associated_ty_datum.name;
+
+            if associated_ty_datum.trait_id != trait_id {
+                errors.push(ConformanceError::ExtraAssociatedTy {
+                    impl_id,
+                    trait_name,
+                    associated_ty_name,
+                });
+                continue;
+            }
+
+            // Both binder lists end with the parameters of the
+            // enclosing trait (resp. impl); compare what precedes them.
+            let declared = &associated_ty_datum.binders.binders;
+            let expected = &declared[..declared.len() - trait_datum.binders.len()];
+            let (_, actual) = self
+                .db
+                .split_associated_ty_value_parameters(&atv.value.binders, atv);
+            if expected != actual {
+                errors.push(ConformanceError::AssociatedTyParameters {
+                    impl_id,
+                    trait_name,
+                    associated_ty_name,
+                    expected: expected.to_vec(),
+                    actual: actual.to_vec(),
+                });
+                continue;
+            }
+
+            for bound in self.unsatisfied_bounds(&impl_datum, atv, &associated_ty_datum) {
+                errors.push(ConformanceError::UnsatisfiedAssociatedTyBound {
+                    impl_id,
+                    trait_name,
+                    associated_ty_name,
+                    bound: self.render(&Binders {
+                        binders: atv.value.binders,
+                        value: bound,
+                    }),
+                });
+            }
+        }
+
+        for method in &impl_datum.methods {
+            let method_name = method.name;
+            let trait_method = match trait_datum.methods.iter().find(|m| m.name == method_name) {
+                Some(trait_method) => trait_method,
+                None => {
+                    errors.push(ConformanceError::ExtraMethod {
+                        impl_id,
+                        trait_name,
+                        method_name,
+                    });
+                    continue;
+                }
+            };
+
+            let expected = trait_method.own_parameters(trait_datum.binders.len());
+            let actual = method.own_parameters(impl_datum.binders.len());
+            if expected != actual {
+                errors.push(ConformanceError::MethodParameters {
+                    impl_id,
+                    trait_name,
+                    method_name,
+                    expected: expected.to_vec(),
+                    actual: actual.to_vec(),
+                });
+                continue;
+            }
+
+            let (signature_matches, stricter) =
+                self.compare_method(&impl_datum, method, trait_method);
+            if !signature_matches {
+                errors.push(ConformanceError::MethodSignature {
+                    impl_id,
+                    trait_name,
+                    method_name,
+                });
+            }
+            for where_clause in stricter {
+                errors.push(ConformanceError::StricterMethodRequirement {
+                    impl_id,
+                    trait_name,
+                    method_name,
+                    where_clause: self.render(&Binders {
+                        binders: method.binders.binders,
+                        value: where_clause,
+                    }),
+                });
+            }
+        }
+
+        errors
+    }
+
+    fn render(&self, value: &Binders<QuantifiedWhereClause<ChalkIr>>) -> String {
+        display(self.db, value).to_string()
+    }
+
+    /// Compares the method `method` of the impl with its declaration
+    /// `trait_method` in the trait, returning whether their signatures
+    /// match, and the where clauses of `method` that the where clauses
+    /// of the declaration do not imply. Given
+    ///
+    /// ```ignore
+    /// trait Foo<T> {
+    ///     fn bar<U>(Self, U) -> T where U: Clone;
+    /// }
+    ///
+    /// impl<T> Foo<T> for Box<T> where T: Clone {
+    ///     fn bar<U>(Box<T>, U) -> T where U: Clone, U: Debug;
+    /// }
+    /// ```
+    ///
+    /// we prove, for the signature:
+    ///
+    /// ```ignore
+    /// forall<T> {
+    ///     if (FromEnv(T: Clone), FromEnv(Box<T>)) {
+    ///         forall<U> {
+    ///             if (FromEnv(U: Clone)) {
+    ///                 Box<T> = Box<T>, U = U, T = T
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// and, in the same way, separately for each where clause of the
+    /// method, `Implemented(U: Clone)` and `Implemented(U: Debug)`.
+    fn compare_method(
+        &self,
+        impl_datum: &ImplDatum,
+        method: &MethodDatum,
+        trait_method: &MethodDatum,
+    ) -> (bool, Vec<QuantifiedWhereClause<ChalkIr>>) {
+        // Both methods take the parameters of the method itself first,
+        // followed by those of the trait (resp. impl); the parameters
+        // of the trait are given by the trait ref of the impl.
+        let own_binders = method.own_parameters(impl_datum.binders.len());
+        let own_len = own_binders.len();
+        let parameters: Vec<_> = own_binders
+            .iter()
+            .zip(0..)
+            .map(|p| p.to_parameter())
+            .chain(
+                impl_datum
+                    .binders
+                    .value
+                    .trait_ref
+                    .parameters
+                    .shifted_in(own_len),
+            )
+            .collect();
+        let declared = trait_method.binders.substitute(&parameters);
+        let MethodDatumBound {
+            inputs,
+            output,
+            where_clauses,
+        } = &method.binders.value;
+
+        let method_hypotheses: Vec<ProgramClause<ChalkIr>> = declared
+            .where_clauses
+            .into_iter()
+            .map(|qwc| qwc.into_from_env_goal())
+            .casted()
+            .collect();
+        let impl_hypotheses = self.impl_hypotheses(impl_datum);
+        let holds = |goal: Goal<ChalkIr>| {
+            let goal = Goal::Implies(method_hypotheses.clone(), Arc::new(goal))
+                .quantify(QuantifierKind::ForAll, own_binders.to_vec());
+            let goal = Goal::Implies(impl_hypotheses.clone(), Arc::new(goal))
+                .quantify(QuantifierKind::ForAll, impl_datum.binders.binders);
+
+            debug!("method conformance goal: {:?}", goal);
+
+            self.holds(goal)
+        };
+
+        let signature_matches = inputs.len() == declared.inputs.len() && {
+            let goal = inputs
+                .iter()
+                .chain(Some(output))
+                .zip(declared.inputs.iter().chain(Some(&declared.output)))
+                .map(|(a, b)| -> Goal<ChalkIr> {
+                    EqGoal {
+                        a: a.clone().cast(),
+                        b: b.clone().cast(),
+                    }
+                    .cast()
+                })
+                .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+                .expect("at least one goal");
+            holds(goal)
+        };
+
+        let stricter = where_clauses
+            .iter()
+            .filter(|where_clause| {
+                let goal: Goal<ChalkIr> = (*where_clause).clone().cast();
+                !holds(goal)
+            })
+            .cloned()
+            .collect();
+
+        (signature_matches, stricter)
+    }
+
+    /// The hypotheses under which the items of an impl are checked: as
+    /// in the WF check, the types in the impl header are assumed to be
+    /// well-formed, and the impl's where clauses to hold.
+    fn impl_hypotheses(&self, impl_datum: &ImplDatum) -> Vec<ProgramClause<ChalkIr>> {
+        let mut header_input_types = Vec::new();
+        impl_datum
+            .binders
+            .value
+            .trait_ref
+            .fold(&mut header_input_types);
+        impl_datum
+            .binders
+            .value
+            .where_clauses
+            .iter()
+            .cloned()
+            .map(|qwc| qwc.into_from_env_goal())
+            .casted()
+            .chain(
+                header_input_types
+                    .into_iter()
+                    .map(|ty| DomainGoal::FromEnv(FromEnv::Ty(ty)))
+                    .casted(),
+            )
+            .collect()
+    }
+
+    /// Whether the closed goal `goal` has a unique solution.
+    fn holds(&self, goal: Goal<ChalkIr>) -> bool {
+        match self
+            .solver_choice
+            .into_solver()
+            .solve(self.db, &goal.into_closed_goal())
+        {
+            Some(sol) => sol.is_unique(),
+            None => false,
+        }
+    }
+
+    /// Returns the bounds of the associated type that `atv` fails to
+    /// satisfy. Given
+    ///
+    /// ```ignore
+    /// trait Foo {
+    ///     type Item<'a>: Clone where Self: 'a;
+    /// }
+    ///
+    /// impl<T> Foo for Box<T> where T: Clone {
+    ///     type Item<'a> = Box<&'a T>;
+    /// }
+    /// ```
+    ///
+    /// we prove, separately for each bound:
+    ///
+    /// ```ignore
+    /// forall<T> {
+    ///     if (FromEnv(T: Clone), FromEnv(Box<T>)) {
+    ///         forall<'a> {
+    ///             if (Box<T>: 'a) {
+    ///                 Implemented(Box<&'a T>: Clone)
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    fn unsatisfied_bounds(
+        &self,
+        impl_datum: &ImplDatum,
+        atv: &AssociatedTyValue,
+        associated_ty_datum: &AssociatedTyDatum,
+    ) -> Vec<QuantifiedWhereClause<ChalkIr>> {
+        let all_parameters: Vec<_> = atv
+            .value
+            .binders
+            .iter()
+            .zip(0..)
+            .map(|p| p.to_parameter())
+            .collect();
+        let (_, projection) = self
+            .db
+            .impl_parameters_and_projection_from_associated_ty_value(&all_parameters, atv);
+        let AssociatedTyValueBound { ty: value_ty } = atv.value.substitute(&all_parameters);
+        let AssociatedTyDatumBound {
+            bounds,
+            where_clauses,
+            default: _,
+        } = associated_ty_datum
+            .binders
+            .substitute(&projection.parameters);
+
+        let (_, value_binders) = self
+            .db
+            .split_associated_ty_value_parameters(&atv.value.binders, atv);
+        let value_hypotheses: Vec<ProgramClause<ChalkIr>> = where_clauses
+            .into_iter()
+            .map(|qwc| qwc.into_from_env_goal())
+            .casted()
+            .collect();
+
+        let impl_hypotheses = self.impl_hypotheses(impl_datum);
+
+        bounds
+            .iter()
+            .flat_map(|qb| qb.into_where_clauses(value_ty.clone()))
+            .filter(|bound| {
+                let goal: Goal<ChalkIr> = bound.clone().cast();
+                let goal = Goal::Implies(value_hypotheses.clone(), Arc::new(goal))
+                    .quantify(QuantifierKind::ForAll, value_binders.to_vec());
+                let goal = Goal::Implies(impl_hypotheses.clone(), Arc::new(goal))
+                    .quantify(QuantifierKind::ForAll, impl_datum.binders.binders);
+
+                debug!("conformance goal: {:?}", goal);
+
+                !self.holds(goal)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,696 @@
+//! Renders chalk IR in (roughly) the surface syntax used by the test
+//! DSL, e.g. `forall<T> { Implemented(T: Foo) }`, rather than the raw
+//! de Bruijn form produced by `Debug`. Bound variables are given
+//! names as their binders are entered, and items are printed using
+//! the names that the database knows them by.
+//!
+//! Use `display(db, &value)` to get something that implements
+//! `std::fmt::Display`.
+
+use crate::split::Split;
+use crate::RustIrDatabase;
+use chalk_ir::family::{ChalkIr, HasTypeFamily};
+use chalk_ir::*;
+use chalk_rust_ir::LangItem;
+use std::fmt::{self, Formatter};
+use std::sync::Arc;
+
+/// Returns a value that renders `value` using the names found in `db`.
+pub fn display<'a, T>(db: &'a dyn RustIrDatabase, value: &'a T) -> impl fmt::Display + 'a
+where
+    T: RenderAsRust + ?Sized,
+{
+    DisplayRenderAsRust { db, value }
+}
+
+struct DisplayRenderAsRust<'a, T: ?Sized> {
+    db: &'a dyn RustIrDatabase,
+    value: &'a T,
+}
+
+impl<T: RenderAsRust + ?Sized> fmt::Display for DisplayRenderAsRust<'_, T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut state = WriterState::new(self.db);
+        self.value.fmt(&mut state, f)
+    }
+}
+
+/// Tracks the names of the bound variables in scope while rendering.
+pub struct WriterState<'a> {
+    db: &'a dyn RustIrDatabase,
+
+    /// Names of the bound variables in scope; the innermost binder
+    /// is at the end, so `BoundVar(d)` is `names[names.len() - 1 - d]`.
+    names: Vec<String>,
+}
+
+impl<'a> WriterState<'a> {
+    fn new(db: &'a dyn RustIrDatabase) -> Self {
+        WriterState { db, names: vec![] }
+    }
+
+    /// Introduces names for `binders` (in order), returning them.
+    /// Within a set of binders, the first binder has index 0, so the
+    /// names are pushed in reverse.
+    fn push_binders(&mut self, binders: &[ParameterKind<()>]) -> Vec<String> {
+        let num_lts = self.names.iter().filter(|n| n.starts_with('\'')).count();
+        let num_tys =
+            self.names.len() - num_lts - self.names.iter().filter(|n| *n == "Self").count();
+        let mut next_ty = num_tys;
+        let mut next_lt = num_lts;
+        let names: Vec<String> = binders
+            .iter()
+            .map(|binder| match binder {
+                ParameterKind::Ty(()) => {
+                    next_ty += 1;
+                    ty_name(next_ty - 1)
+                }
+                ParameterKind::Lifetime(()) => {
+                    next_lt += 1;
+                    lifetime_name(next_lt - 1)
+                }
+            })
+            .collect();
+        self.names.extend(names.iter().rev().cloned());
+        names
+    }
+
+    fn pop_binders(&mut self, len: usize) {
+        let new_len = self.names.len() - len;
+        self.names.truncate(new_len);
+    }
+
+    fn bound_var(&self, depth: usize) -> Option<&str> {
+        if depth < self.names.len() {
+            Some(&self.names[self.names.len() - 1 - depth])
+        } else {
+            None
+        }
+    }
+
+    /// Renders `value` with `binders` in scope, printed as
+    /// `<keyword><A, B> ` (nothing is printed if there are no
+    /// binders).
+    fn with_binders(
+        &mut self,
+        f: &mut Formatter<'_>,
+        keyword: &str,
+        binders: &[ParameterKind<()>],
+        op: impl FnOnce(&mut Self, &mut Formatter<'_>) -> fmt::Result,
+    ) -> fmt::Result {
+        let names = self.push_binders(binders);
+        let result = if names.is_empty() {
+            op(self, f)
+        } else {
+            write!(f, "{}<{}> ", keyword, names.join(", ")).and_then(|()| op(self, f))
+        };
+        self.pop_binders(names.len());
+        result
+    }
+}
+
+fn ty_name(index: usize) -> String {
+    const NAMES: &[&str] = &["T", "U", "V", "W", "X", "Y", "Z"];
+    match NAMES.get(index) {
+        Some(name) => name.to_string(),
+        None => format!("T{}", index),
+    }
+}
+
+fn lifetime_name(index: usize) -> String {
+    if index < 26 {
+        format!("'{}", (b'a' + index as u8) as char)
+    } else {
+        format!("'a{}", index)
+    }
+}
+
+/// Types that can be rendered by `display`.
+pub trait RenderAsRust {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result;
+}
+
+impl<T: RenderAsRust + ?Sized> RenderAsRust for &T {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(s, f)
+    }
+}
+
+impl<T: RenderAsRust> RenderAsRust for Arc<T> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        (**self).fmt(s, f)
+    }
+}
+
+/// Renders `values` separated by `separator`.
+fn write_joined<T: RenderAsRust>(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    values: &[T],
+    separator: &str,
+) -> fmt::Result {
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            write!(f, "{}", separator)?;
+        }
+        value.fmt(s, f)?;
+    }
+    Ok(())
+}
+
+/// Renders `values` as `<A, B>` (or nothing, if there are none).
+fn write_angle<T: RenderAsRust>(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    values: &[T],
+) -> fmt::Result {
+    if values.is_empty() {
+        return Ok(());
+    }
+    write!(f, "<")?;
+    write_joined(s, f, values, ", ")?;
+    write!(f, ">")
+}
+
+impl RenderAsRust for TypeKindId {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match *self {
+            TypeKindId::TypeId(type_id) => {
+                let datum = s.db.associated_ty_data(type_id);
+                let trait_name = s.db.type_name(datum.trait_id.into());
+                write!(f, "{}::{}", trait_name, datum.name)
+            }
+            TypeKindId::TraitId(_) | TypeKindId::StructId(_) => {
+                write!(f, "{}", s.db.type_name(*self))
+            }
+        }
+    }
+}
+
+impl RenderAsRust for TypeName {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeName::TypeKindId(id) => id.fmt(s, f),
+            TypeName::Placeholder(index) => write!(f, "{:?}", index),
+            TypeName::AssociatedType(type_id) => {
+                write!(f, "(")?;
+                TypeKindId::TypeId(*type_id).fmt(s, f)?;
+                write!(f, ")")
+            }
+            TypeName::Tuple(arity) => write!(f, "Tuple{}", arity),
+            TypeName::FnPtr => write!(f, "fn"),
+            TypeName::OpaqueConst(name) => write!(f, "const {}", name),
+            TypeName::Builtin(builtin) => write!(f, "{:?}", builtin),
+            TypeName::Error => write!(f, "{{error}}"),
+        }
+    }
+}
+
+impl RenderAsRust for Ty<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::BoundVar(depth) => match s.bound_var(*depth) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "^{}", depth - s.names.len()),
+            },
+            Ty::InferenceVar(var) => write!(f, "{:?}", var),
+            Ty::Apply(apply) => apply.fmt(s, f),
+            Ty::Projection(projection) => projection.fmt(s, f),
+            Ty::Dyn(bounds) => {
+                write!(f, "dyn ")?;
+                write_inline_bounds(s, f, bounds)
+            }
+            Ty::Opaque(bounds) => {
+                write!(f, "impl ")?;
+                write_inline_bounds(s, f, bounds)
+            }
+            Ty::ForAll(quantified_ty) => {
+                let binders = vec![ParameterKind::Lifetime(()); quantified_ty.num_binders];
+                s.with_binders(f, "for", &binders, |s, f| quantified_ty.ty.fmt(s, f))
+            }
+        }
+    }
+}
+
+/// Renders `ty` where `dyn`, `impl` and `for` types must be
+/// parenthesized, such as the referent of a reference.
+fn write_ty_atom(s: &mut WriterState<'_>, f: &mut Formatter<'_>, ty: &Ty<ChalkIr>) -> fmt::Result {
+    match ty {
+        Ty::Dyn(_) | Ty::Opaque(_) | Ty::ForAll(_) => {
+            write!(f, "(")?;
+            ty.fmt(s, f)?;
+            write!(f, ")")
+        }
+        _ => ty.fmt(s, f),
+    }
+}
+
+/// Renders the bounds of a `dyn` or `impl` type as `Foo + Bar<X>`.
+/// The outer binder is the `Self` type, which is not written.
+fn write_inline_bounds(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    bounds: &Binders<Vec<QuantifiedWhereClause<ChalkIr>>>,
+) -> fmt::Result {
+    assert_eq!(bounds.binders.len(), 1);
+    s.names.push("Self".to_string());
+    let result = (|| {
+        let mut qwcs = bounds.value.iter().peekable();
+        let mut first = true;
+        while let Some(qwc) = qwcs.next() {
+            if !first {
+                write!(f, " + ")?;
+            }
+            first = false;
+
+            // `Fn(A) -> B` was lowered to `Fn<(A,)>` followed by the
+            // `Output` projection; render the pair using the sugar.
+            if let Some((trait_ref, output)) =
+                qwcs.peek().and_then(|next| fn_sugar(s.db, qwc, next))
+            {
+                qwcs.next();
+                s.with_binders(f, "forall", &qwc.binders, |s, f| {
+                    write_fn_sugar(s, f, trait_ref, output)
+                })?;
+                continue;
+            }
+
+            s.with_binders(f, "forall", &qwc.binders, |s, f| match &qwc.value {
+                WhereClause::Implemented(trait_ref) => {
+                    trait_ref.trait_id.fmt(s, f)?;
+                    write_angle(s, f, &trait_ref.parameters[1..])
+                }
+                WhereClause::ProjectionEq(projection_eq) => {
+                    let (datum, trait_params, other_params) =
+                        s.db.split_projection(&projection_eq.projection);
+                    datum.trait_id.fmt(s, f)?;
+                    write!(f, "<")?;
+                    for param in &trait_params[1..] {
+                        param.fmt(s, f)?;
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", datum.name)?;
+                    write_angle(s, f, other_params)?;
+                    write!(f, " = ")?;
+                    projection_eq.ty.fmt(s, f)?;
+                    write!(f, ">")
+                }
+            })?;
+        }
+        Ok(())
+    })();
+    s.names.pop();
+    result
+}
+
+/// If `implemented` is `Self: Fn<(A..)>` for one of the fn-family
+/// traits and `projection` is the matching `<Self as FnOnce<(A..)>>::Output = B`,
+/// returns the trait-ref and `B`, so that the pair can be rendered as
+/// `Fn(A..) -> B`.
+fn fn_sugar<'v>(
+    db: &dyn RustIrDatabase,
+    implemented: &'v QuantifiedWhereClause<ChalkIr>,
+    projection: &'v QuantifiedWhereClause<ChalkIr>,
+) -> Option<(&'v TraitRef<ChalkIr>, &'v Ty<ChalkIr>)> {
+    let trait_ref = match &implemented.value {
+        WhereClause::Implemented(trait_ref) => trait_ref,
+        WhereClause::ProjectionEq(_) => return None,
+    };
+    let projection_eq = match &projection.value {
+        WhereClause::ProjectionEq(projection_eq) => projection_eq,
+        WhereClause::Implemented(_) => return None,
+    };
+    let is_lang_item = |item, id: TraitId| db.lang_item(item) == Some(TypeKindId::TraitId(id));
+    let is_fn_trait = [LangItem::FnOnce, LangItem::FnMut, LangItem::Fn]
+        .iter()
+        .any(|&item| is_lang_item(item, trait_ref.trait_id));
+    let is_tuple = match trait_ref.parameters.get(1).map(|p| &p.0) {
+        Some(ParameterKind::Ty(Ty::Apply(apply))) => match apply.name {
+            TypeName::Tuple(_) => trait_ref.parameters.len() == 2,
+            _ => false,
+        },
+        _ => false,
+    };
+    let datum = db.associated_ty_data(projection_eq.projection.associated_ty_id);
+    if is_fn_trait
+        && is_tuple
+        && is_lang_item(LangItem::FnOnce, datum.trait_id)
+        && implemented.binders == projection.binders
+        && projection_eq.projection.parameters == trait_ref.parameters
+    {
+        Some((trait_ref, &projection_eq.ty))
+    } else {
+        None
+    }
+}
+
+/// Renders `Fn<(A, B)>` with output `C` as `Fn(A, B) -> C`, omitting
+/// the `-> C` when `C` is `()`.
+fn write_fn_sugar(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    trait_ref: &TraitRef<ChalkIr>,
+    output: &Ty<ChalkIr>,
+) -> fmt::Result {
+    trait_ref.trait_id.fmt(s, f)?;
+    let inputs = match trait_ref.parameters[1].assert_ty_ref() {
+        Ty::Apply(apply) => &apply.parameters,
+        _ => unreachable!(),
+    };
+    write!(f, "(")?;
+    write_joined(s, f, inputs, ", ")?;
+    write!(f, ")")?;
+    write_output(s, f, output)
+}
+
+/// Renders the ` -> C` of `Fn(A, B) -> C` or `fn(A, B) -> C`, or
+/// nothing if `C` is `()`.
+fn write_output(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    output: &Ty<ChalkIr>,
+) -> fmt::Result {
+    match output {
+        Ty::Apply(ApplicationTy {
+            name: TypeName::Tuple(0),
+            ..
+        }) => Ok(()),
+        _ => {
+            write!(f, " -> ")?;
+            output.fmt(s, f)
+        }
+    }
+}
+
+impl RenderAsRust for TraitId {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        TypeKindId::TraitId(*self).fmt(s, f)
+    }
+}
+
+impl RenderAsRust for ApplicationTy<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.name {
+            TypeName::Tuple(_) => {
+                write!(f, "(")?;
+                write_joined(s, f, &self.parameters, ", ")?;
+                if self.parameters.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            TypeName::FnPtr => match self.parameters[0].assert_ty_ref() {
+                Ty::Apply(ApplicationTy {
+                    name: TypeName::Tuple(_),
+                    parameters: inputs,
+                }) => {
+                    write!(f, "fn(")?;
+                    write_joined(s, f, inputs, ", ")?;
+                    write!(f, ")")?;
+                    write_output(s, f, self.parameters[1].assert_ty_ref())
+                }
+                _ => {
+                    self.name.fmt(s, f)?;
+                    write_angle(s, f, &self.parameters)
+                }
+            },
+            TypeName::Builtin(BuiltinType::Str) => write!(f, "str"),
+            TypeName::Builtin(BuiltinType::Slice) => {
+                write!(f, "[")?;
+                self.parameters[0].fmt(s, f)?;
+                write!(f, "]")
+            }
+            TypeName::Builtin(BuiltinType::Array) => {
+                write!(f, "[")?;
+                self.parameters[0].fmt(s, f)?;
+                write!(f, "; ")?;
+                self.parameters[1].fmt(s, f)?;
+                write!(f, "]")
+            }
+            TypeName::Builtin(BuiltinType::Ref(mutability)) => {
+                write!(f, "&")?;
+                self.parameters[1].fmt(s, f)?;
+                match mutability {
+                    Mutability::Not => write!(f, " ")?,
+                    Mutability::Mut => write!(f, " mut ")?,
+                }
+                write_ty_atom(s, f, self.parameters[0].assert_ty_ref())
+            }
+            TypeName::Builtin(BuiltinType::Ptr(mutability)) => {
+                match mutability {
+                    Mutability::Not => write!(f, "*const ")?,
+                    Mutability::Mut => write!(f, "*mut ")?,
+                }
+                write_ty_atom(s, f, self.parameters[0].assert_ty_ref())
+            }
+            _ => {
+                self.name.fmt(s, f)?;
+                write_angle(s, f, &self.parameters)
+            }
+        }
+    }
+}
+
+impl RenderAsRust for ProjectionTy<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        let (datum, trait_params, other_params) = s.db.split_projection(self);
+        write!(f, "<")?;
+        trait_params[0].fmt(s, f)?;
+        write!(f, " as ")?;
+        datum.trait_id.fmt(s, f)?;
+        write_angle(s, f, &trait_params[1..])?;
+        write!(f, ">::{}", datum.name)?;
+        write_angle(s, f, other_params)
+    }
+}
+
+impl RenderAsRust for Lifetime<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Lifetime::BoundVar(depth) => match s.bound_var(*depth) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "'^{}", depth - s.names.len()),
+            },
+            Lifetime::InferenceVar(var) => write!(f, "'{:?}", var),
+            Lifetime::Placeholder(index) => write!(f, "'{:?}", index),
+            Lifetime::Phantom(..) => unreachable!(),
+        }
+    }
+}
+
+impl RenderAsRust for Parameter<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ParameterKind::Ty(ty) => ty.fmt(s, f),
+            ParameterKind::Lifetime(lifetime) => lifetime.fmt(s, f),
+        }
+    }
+}
+
+impl RenderAsRust for TraitRef<ChalkIr> {
+    /// Renders as `P0: Trait<P1..Pn>`.
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        self.parameters[0].fmt(s, f)?;
+        write!(f, ": ")?;
+        self.trait_id.fmt(s, f)?;
+        write_angle(s, f, &self.parameters[1..])
+    }
+}
+
+impl RenderAsRust for WhereClause<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WhereClause::Implemented(trait_ref) => {
+                write!(f, "Implemented(")?;
+                trait_ref.fmt(s, f)?;
+                write!(f, ")")
+            }
+            WhereClause::ProjectionEq(projection_eq) => {
+                write!(f, "ProjectionEq(")?;
+                projection_eq.projection.fmt(s, f)?;
+                write!(f, " = ")?;
+                projection_eq.ty.fmt(s, f)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Renders `QuantifiedWhereClause`s, among others.
+impl<T: RenderAsRust> RenderAsRust for Binders<T> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        s.with_binders(f, "forall", &self.binders, |s, f| self.value.fmt(s, f))
+    }
+}
+
+impl RenderAsRust for CustomGoal<ChalkIr> {
+    /// Renders as `Name<P0..Pn>`.
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        write_angle(s, f, &self.parameters)
+    }
+}
+
+impl RenderAsRust for DomainGoal<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        let (name, arg): (&str, &dyn RenderAsRust) = match self {
+            DomainGoal::Holds(wc) => return wc.fmt(s, f),
+            DomainGoal::WellFormed(WellFormed::Trait(trait_ref)) => ("WellFormed", trait_ref),
+            DomainGoal::WellFormed(WellFormed::Ty(ty)) => ("WellFormed", ty),
+            DomainGoal::FromEnv(FromEnv::Trait(trait_ref)) => ("FromEnv", trait_ref),
+            DomainGoal::FromEnv(FromEnv::Ty(ty)) => ("FromEnv", ty),
+            DomainGoal::Normalize(normalize) => {
+                write!(f, "Normalize(")?;
+                normalize.projection.fmt(s, f)?;
+                write!(f, " -> ")?;
+                normalize.ty.fmt(s, f)?;
+                return write!(f, ")");
+            }
+            DomainGoal::IsLocal(ty) => ("IsLocal", ty),
+            DomainGoal::IsUpstream(ty) => ("IsUpstream", ty),
+            DomainGoal::IsFullyVisible(ty) => ("IsFullyVisible", ty),
+            DomainGoal::LocalImplAllowed(trait_ref) => ("LocalImplAllowed", trait_ref),
+            DomainGoal::Compatible(()) => return write!(f, "Compatible"),
+            DomainGoal::DownstreamType(ty) => ("DownstreamType", ty),
+            DomainGoal::Custom(custom_goal) => return custom_goal.fmt(s, f),
+            DomainGoal::LayoutCompatible(LayoutCompatible { a, b }) => {
+                write!(f, "LayoutCompatible(")?;
+                a.fmt(s, f)?;
+                write!(f, ", ")?;
+                b.fmt(s, f)?;
+                return write!(f, ")");
+            }
+            DomainGoal::Upcast(Upcast { source, target }) => {
+                write!(f, "Upcast(")?;
+                source.fmt(s, f)?;
+                write!(f, " => ")?;
+                target.fmt(s, f)?;
+                return write!(f, ")");
+            }
+        };
+        write!(f, "{}(", name)?;
+        arg.fmt(s, f)?;
+        write!(f, ")")
+    }
+}
+
+impl RenderAsRust for LeafGoal<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            LeafGoal::EqGoal(EqGoal { a, b }) => {
+                a.fmt(s, f)?;
+                write!(f, " = ")?;
+                b.fmt(s, f)
+            }
+            LeafGoal::DomainGoal(domain_goal) => domain_goal.fmt(s, f),
+            LeafGoal::TypeOutlives(TypeOutlives { ty, lifetime }) => {
+                write_ty_atom(s, f, ty)?;
+                write!(f, ": ")?;
+                lifetime.fmt(s, f)
+            }
+        }
+    }
+}
+
+/// Renders `goal`; if `operand` is true, the goal appears as an
+/// element of a comma-separated list (e.g., the left-hand side of
+/// `A, B`, or a condition of a clause) and so a conjunction must be
+/// parenthesized.
+fn write_goal(
+    s: &mut WriterState<'_>,
+    f: &mut Formatter<'_>,
+    goal: &Goal<ChalkIr>,
+    operand: bool,
+) -> fmt::Result {
+    match goal {
+        Goal::Quantified(kind, subgoal) => {
+            let keyword = match kind {
+                QuantifierKind::ForAll => "forall",
+                QuantifierKind::Exists => "exists",
+            };
+            let names = s.push_binders(&subgoal.binders);
+            write!(f, "{}<{}> {{ ", keyword, names.join(", "))?;
+            let result = write_goal(s, f, &subgoal.value, false);
+            s.pop_binders(names.len());
+            result?;
+            write!(f, " }}")
+        }
+        Goal::Implies(clauses, subgoal) => {
+            write!(f, "if (")?;
+            write_joined(s, f, clauses, "; ")?;
+            write!(f, ") {{ ")?;
+            write_goal(s, f, subgoal, false)?;
+            write!(f, " }}")
+        }
+        Goal::And(left, right) => {
+            if operand {
+                write!(f, "(")?;
+            }
+            write_goal(s, f, left, true)?;
+            write!(f, ", ")?;
+            write_goal(s, f, right, false)?;
+            if operand {
+                write!(f, ")")?;
+            }
+            Ok(())
+        }
+        Goal::Not(subgoal) => {
+            write!(f, "not {{ ")?;
+            write_goal(s, f, subgoal, false)?;
+            write!(f, " }}")
+        }
+        Goal::Leaf(leaf) => leaf.fmt(s, f),
+        Goal::CannotProve(()) => write!(f, "CannotProve"),
+    }
+}
+
+impl RenderAsRust for Goal<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        write_goal(s, f, self, false)
+    }
+}
+
+impl RenderAsRust for ProgramClauseImplication<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        self.consequence.fmt(s, f)?;
+        for (index, condition) in self.conditions.iter().enumerate() {
+            write!(f, "{}", if index == 0 { " :- " } else { ", " })?;
+            write_goal(s, f, condition, true)?;
+        }
+        Ok(())
+    }
+}
+
+impl RenderAsRust for ProgramClause<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramClause::Implies(implication) => implication.fmt(s, f),
+            ProgramClause::ForAll(implication) => {
+                let names = s.push_binders(&implication.binders);
+                write!(f, "forall<{}> {{ ", names.join(", "))?;
+                let result = implication.value.fmt(s, f);
+                s.pop_binders(names.len());
+                result?;
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+impl RenderAsRust for Environment<ChalkIr> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Env(")?;
+        write_joined(s, f, &self.clauses, "; ")?;
+        write!(f, ")")
+    }
+}
+
+impl<T: RenderAsRust + HasTypeFamily<TypeFamily = ChalkIr>> RenderAsRust for InEnvironment<T> {
+    fn fmt(&self, s: &mut WriterState<'_>, f: &mut Formatter<'_>) -> fmt::Result {
+        if !self.environment.clauses.is_empty() {
+            self.environment.fmt(s, f)?;
+            write!(f, " |- ")?;
+        }
+        self.goal.fmt(s, f)
+    }
+}
@@ -1,7 +1,22 @@
-use crate::infer::InferenceTable;
+use crate::clauses::{elaborate_env, program_clauses_with_origins, ClauseOrigin};
+use crate::infer::{InferenceTable, ParameterEnaVariableExt};
+use crate::wf::FoldInputTypes;
+use crate::{GoalPipeline, MissingData, RustIrDatabase, Solution, Solver, SolverChoice};
+use chalk_engine::fallible::{Fallible, NoSolution};
+use chalk_ir::cast::Cast;
+use chalk_ir::could_match::CouldMatch;
 use chalk_ir::family::ChalkIr;
-use chalk_ir::fold::Fold;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::fold::{
+    self, DefaultFreeVarFolder, DefaultInferenceFolder, DefaultPlaceholderFolder, Fold, Subst,
+    TypeFolder,
+};
+use chalk_ir::stable_hash::StableHash;
 use chalk_ir::*;
+use chalk_rust_ir::{IntoWhereClauses, QuantifiedInlineBound, ToParameter, WellKnownTrait};
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
 
 pub trait CanonicalExt<T> {
     fn map<OP, U>(self, op: OP) -> Canonical<U::Result>
@@ -44,9 +59,163 @@ impl<T> CanonicalExt<T> for Canonical<T> {
     }
 }
 
+/// Relates the binders of a goal to the variables of the canonical
+/// goal that `GoalExt::into_peeled_goal_with_map` peels it into, so
+/// that a solution to the canonical goal can be applied back to the
+/// goal the caller started from.
+///
+/// Binders are numbered in the order in which they are peeled:
+/// outermost first, counting every binder of a quantifier, with the
+/// `exists` and `forall` binders numbered separately. Note that this is
+/// the order of the goal in prenex form (see `GoalExt::into_prenex`).
+/// For example, in `exists<T, U> { forall<V> { exists<W> { .. } } }`,
+/// the existentials are `T`, `U` and `W` (0 to 2), and the universal
+/// `V` is 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CanonicalizationMap {
+    /// For each existential, the index of its canonical variable.
+    existentials: Vec<Option<usize>>,
+
+    /// For each universal, its placeholder in the canonical goal.
+    universals: Vec<Option<Parameter<ChalkIr>>>,
+}
+
+impl CanonicalizationMap {
+    /// The number of `exists` binders that were peeled.
+    pub fn num_existentials(&self) -> usize {
+        self.existentials.len()
+    }
+
+    /// The number of `forall` binders that were peeled.
+    pub fn num_universals(&self) -> usize {
+        self.universals.len()
+    }
+
+    /// The canonical variable `?N` that the existential `index`
+    /// became, or `None` if it does not appear in the goal (in which
+    /// case no solution constrains it).
+    pub fn canonical_var(&self, index: usize) -> Option<usize> {
+        self.existentials[index]
+    }
+
+    /// The placeholder that the universal `index` became in the
+    /// canonical goal, or `None` if it does not appear in the goal.
+    pub fn placeholder(&self, index: usize) -> Option<&Parameter<ChalkIr>> {
+        self.universals[index].as_ref()
+    }
+
+    /// The universal that `placeholder` (a placeholder of the
+    /// canonical goal, e.g., one found in a solution) stands for.
+    pub fn universal_of(&self, placeholder: &Parameter<ChalkIr>) -> Option<usize> {
+        self.universals
+            .iter()
+            .position(|universal| universal.as_ref() == Some(placeholder))
+    }
+
+    /// Given the substitution of a solution to the canonical goal
+    /// (e.g., that of `Solution::Unique`), returns the value of each
+    /// existential, or `None` for those that do not appear in the goal.
+    /// The values are in terms of the binders of the solution (i.e., of
+    /// the `Canonical` that holds `subst`), and may contain
+    /// placeholders of the canonical goal (see `universal_of`).
+    pub fn existential_values(
+        &self,
+        subst: &Substitution<ChalkIr>,
+    ) -> Vec<Option<Parameter<ChalkIr>>> {
+        self.existentials
+            .iter()
+            .map(|var| var.map(|var| subst.parameters[var].clone()))
+            .collect()
+    }
+}
+
+/// A variable whose value, in a substitution that `check_universes`
+/// rejected, mentions a placeholder that the variable cannot name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniverseViolation {
+    /// The index of the variable.
+    pub var: usize,
+
+    /// The universe of the variable.
+    pub var_universe: UniverseIndex,
+
+    /// The universe of the placeholder, which the variable's universe
+    /// cannot see.
+    pub placeholder_universe: UniverseIndex,
+}
+
+/// Checks that `subst`, which gives a value to each variable of a
+/// canonical value with the universes `universes` (e.g., as an
+/// embedder replays a solution into its inference context, with the
+/// universes mapped to its own), respects them: the value of a
+/// variable must not mention a placeholder from a universe that the
+/// universe of the variable cannot see. An embedder that maps the
+/// universes of a solution wrongly would otherwise unify a variable
+/// with a type it cannot name, and accept goals that do not hold.
+///
+/// # Panics
+///
+/// Panics if `subst` does not have a value for each variable.
+pub fn check_universes(
+    universes: &CanonicalUniverses,
+    subst: &Substitution<ChalkIr>,
+) -> Result<(), UniverseViolation> {
+    assert_eq!(universes.binders.len(), subst.parameters.len());
+    for (var, (&var_universe, value)) in universes.binders.iter().zip(&subst.parameters).enumerate()
+    {
+        let unseen = placeholder_universes(value)
+            .into_iter()
+            .find(|&ui| !var_universe.can_see(ui));
+        if let Some(placeholder_universe) = unseen {
+            return Err(UniverseViolation {
+                var,
+                var_universe,
+                placeholder_universe,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn placeholder_parameter(
+    placeholder: PlaceholderIndex,
+    kind: ParameterKind<()>,
+) -> Parameter<ChalkIr> {
+    match kind {
+        ParameterKind::Ty(()) => placeholder.to_ty::<ChalkIr>().cast(),
+        ParameterKind::Lifetime(()) => placeholder.to_lifetime::<ChalkIr>().cast(),
+    }
+}
+
 pub trait GoalExt {
     fn into_peeled_goal(self) -> UCanonical<InEnvironment<Goal<ChalkIr>>>;
+    fn into_peeled_goal_with_map(
+        self,
+    ) -> (
+        UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        CanonicalizationMap,
+    );
     fn into_closed_goal(self) -> UCanonical<InEnvironment<Goal<ChalkIr>>>;
+    fn into_prenex(self) -> Goal<ChalkIr>;
+    fn implied_by_env(
+        &self,
+        db: &dyn RustIrDatabase,
+        environment: &Environment<ChalkIr>,
+    ) -> Result<ImpliedByEnv, MissingData>;
+}
+
+/// The result of `GoalExt::implied_by_env`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImpliedByEnv {
+    /// The goal holds: it is a fact of the environment (or follows
+    /// from one by a single rule).
+    Yes,
+
+    /// The goal does not hold: no clause could prove it.
+    No,
+
+    /// The quick check cannot tell; the goal has to be solved.
+    NeedsSolver,
 }
 
 impl GoalExt for Goal<ChalkIr> {
@@ -54,36 +223,106 @@ impl GoalExt for Goal<ChalkIr> {
     /// `forall<>` quantifiers (as well as implications) have been
     /// "peeled" and are converted into free universal or existential
     /// variables. Assumes that this goal is a "closed goal" which
-    /// does not -- at present -- contain any variables. Useful for
-    /// REPLs and tests but not much else.
+    /// does not -- at present -- contain any variables. If you need
+    /// to relate the solution back to the binders of this goal, use
+    /// `into_peeled_goal_with_map` instead.
+    ///
+    /// The goal is first rewritten by the built-in goal passes
+    /// (`GoalPipeline::STANDARD`), which put it into prenex form (see
+    /// `into_prenex`), so quantifiers buried under `And` or
+    /// implications are peeled as well. This way, goals that differ
+    /// only in where their binders are written wind up with the same
+    /// canonical form (and hence share tables).
     fn into_peeled_goal(self) -> UCanonical<InEnvironment<Goal<ChalkIr>>> {
+        self.into_peeled_goal_with_map().0
+    }
+
+    /// Like `into_peeled_goal`, but also returns the mapping from the
+    /// binders that were peeled to the variables of the canonical
+    /// goal. The goal is canonicalized (each existential becomes a
+    /// canonical variable `?N`, numbered in order of appearance) and
+    /// then u-canonicalized (the universes of the placeholders are
+    /// renumbered to be consecutive), so neither the numbering of the
+    /// existentials nor that of the universes matches the binders of
+    /// the original goal; the `CanonicalizationMap` records both.
+    fn into_peeled_goal_with_map(
+        self,
+    ) -> (
+        UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        CanonicalizationMap,
+    ) {
         let mut infer = InferenceTable::new();
+        let mut existentials = vec![];
+        let mut universals = vec![];
         let peeled_goal = {
-            let mut env_goal = InEnvironment::new(&Environment::new(), self);
+            let mut env_goal =
+                InEnvironment::new(&Environment::new(), GoalPipeline::STANDARD.run(&self));
             loop {
                 let InEnvironment { environment, goal } = env_goal;
                 match goal {
                     Goal::Quantified(QuantifierKind::ForAll, subgoal) => {
+                        let kinds = subgoal.binders;
                         let subgoal = infer.instantiate_binders_universally(&subgoal);
-                        env_goal = InEnvironment::new(&environment, *subgoal);
+                        let ui = infer.max_universe();
+                        universals.extend(
+                            kinds
+                                .iter()
+                                .cloned()
+                                .enumerate()
+                                .map(|(idx, kind)| (PlaceholderIndex { ui, idx }, kind)),
+                        );
+                        env_goal = InEnvironment::new(&environment, (*subgoal).clone());
                     }
 
                     Goal::Quantified(QuantifierKind::Exists, subgoal) => {
-                        let subgoal = infer.instantiate_binders_existentially(&subgoal);
-                        env_goal = InEnvironment::new(&environment, *subgoal);
+                        let ui = infer.max_universe();
+                        let binders: Vec<_> =
+                            subgoal.binders.iter().map(|pk| pk.map(|()| ui)).collect();
+                        let subst = infer.fresh_subst(&binders);
+                        let subgoal = Subst::apply(&subst.parameters, &subgoal.value);
+                        existentials.extend(subst.parameters);
+                        env_goal = InEnvironment::new(&environment, (*subgoal).clone());
                     }
 
                     Goal::Implies(wc, subgoal) => {
                         let new_environment = &environment.add_clauses(wc);
-                        env_goal = InEnvironment::new(&new_environment, *subgoal);
+                        env_goal = InEnvironment::new(&new_environment, (*subgoal).clone());
                     }
 
                     _ => break InEnvironment::new(&environment, goal),
                 }
             }
         };
-        let canonical = infer.canonicalize(&peeled_goal).quantified;
-        infer.u_canonicalize(&canonical).quantified
+        let canonicalized = infer.canonicalize(&peeled_goal);
+        let u_canonicalized = infer.u_canonicalize(&canonicalized.quantified);
+
+        let canonical_vars: Vec<_> = canonicalized
+            .free_vars
+            .into_iter()
+            .map(|var| var.to_parameter())
+            .collect();
+        let map = CanonicalizationMap {
+            existentials: existentials
+                .iter()
+                .map(|param| canonical_vars.iter().position(|var| var == param))
+                .collect(),
+            universals: universals
+                .into_iter()
+                .map(|(placeholder, kind)| {
+                    let ui = u_canonicalized
+                        .universes
+                        .canonical_universe_of(placeholder.ui)?;
+                    Some(placeholder_parameter(
+                        PlaceholderIndex {
+                            ui,
+                            idx: placeholder.idx,
+                        },
+                        kind,
+                    ))
+                })
+                .collect(),
+        };
+        (u_canonicalized.quantified, map)
     }
 
     /// Given a goal with no free variables (a "closed" goal), creates
@@ -101,4 +340,795 @@ impl GoalExt for Goal<ChalkIr> {
         let canonical_goal = infer.canonicalize(&env_goal).quantified;
         infer.u_canonicalize(&canonical_goal).quantified
     }
+
+    /// Floats `forall` and `exists` binders outward through `And`
+    /// and through the goal position of implications, producing a
+    /// goal in which all such quantifiers form a prefix. For example:
+    ///
+    /// ```notrust
+    /// A, exists<T> { if (C) { forall<U> { B } } }
+    /// ```
+    ///
+    /// becomes
+    ///
+    /// ```notrust
+    /// exists<T> { forall<U> { A, if (C) { B } } }
+    /// ```
+    ///
+    /// This is sound because the goals we move the binders across
+    /// cannot refer to the bound variables (we shift them in as
+    /// needed), and because the relative order of the quantifiers
+    /// within each subgoal is preserved, so no existential can wind
+    /// up seeing a universal that it could not see before. We never
+    /// float binders out of a `not { .. }`, since that would change
+    /// the meaning of the goal, but we do normalize its contents.
+    ///
+    /// When both sides of an `And` are quantified, an `exists` is
+    /// hoisted before a `forall`, so that `(forall<T> { A }), (exists<U>
+    /// { B })` becomes `exists<U> { forall<T> { A, B } }`: the
+    /// existential, which could not see `T` before, still cannot.
+    fn into_prenex(self) -> Goal<ChalkIr> {
+        match self {
+            Goal::Quantified(kind, subgoal) => {
+                let Binders { binders, value } = subgoal;
+                Goal::Quantified(
+                    kind,
+                    Binders {
+                        binders,
+                        value: Arc::new((*value).clone().into_prenex()),
+                    },
+                )
+            }
+
+            Goal::And(left, right) => match (
+                (*left).clone().into_prenex(),
+                (*right).clone().into_prenex(),
+            ) {
+                (
+                    left @ Goal::Quantified(QuantifierKind::ForAll, _),
+                    Goal::Quantified(QuantifierKind::Exists, right),
+                ) => {
+                    let left = left.shifted_in(right.binders.len());
+                    hoist(QuantifierKind::Exists, right, |right| {
+                        Goal::And(Arc::new(left), right)
+                    })
+                }
+                (Goal::Quantified(kind, left), right) => {
+                    let right = right.shifted_in(left.binders.len());
+                    hoist(kind, left, |left| Goal::And(left, Arc::new(right)))
+                }
+                (left, Goal::Quantified(kind, right)) => {
+                    let left = left.shifted_in(right.binders.len());
+                    hoist(kind, right, |right| Goal::And(Arc::new(left), right))
+                }
+                (left, right) => Goal::And(Arc::new(left), Arc::new(right)),
+            },
+
+            Goal::Implies(clauses, subgoal) => match (*subgoal).clone().into_prenex() {
+                Goal::Quantified(kind, subgoal) => {
+                    let clauses = clauses.shifted_in(subgoal.binders.len());
+                    hoist(kind, subgoal, |subgoal| Goal::Implies(clauses, subgoal))
+                }
+                subgoal => Goal::Implies(clauses, Arc::new(subgoal)),
+            },
+
+            Goal::Not(subgoal) => Goal::Not(Arc::new((*subgoal).clone().into_prenex())),
+
+            goal @ Goal::Leaf(_) | goal @ Goal::CannotProve(()) => goal,
+        }
+    }
+
+    /// A quick check, for tooling that wants to filter goals before
+    /// solving them, of whether this closed goal holds in
+    /// `environment` without any reasoning. Clauses are only matched
+    /// against the goal, never solved, so this is cheap, but often
+    /// inconclusive:
+    ///
+    /// - `Yes` if the goal is one of the facts of the environment
+    ///   (including the supertraits of its `FromEnv` facts), or a
+    ///   trait goal `T: Foo` whose `FromEnv(T: Foo)` is one;
+    /// - `No` if no clause of the program or the environment could
+    ///   match the goal at all (not counting the rule `Implemented(T:
+    ///   Foo) :- FromEnv(T: Foo)` when no clause of the environment
+    ///   could give `FromEnv(T: Foo)`, nor the rules that need
+    ///   `Compatible` outside of `compatible { .. }`);
+    /// - for `A, B`, `Yes` if both are `Yes` and `No` if either is
+    ///   `No`;
+    /// - `NeedsSolver` otherwise, and for goals that are not solved
+    ///   with clauses (custom goals, and `Compatible` ones) or that
+    ///   have binders of their own.
+    fn implied_by_env(
+        &self,
+        db: &dyn RustIrDatabase,
+        environment: &Environment<ChalkIr>,
+    ) -> Result<ImpliedByEnv, MissingData> {
+        let mut facts = vec![];
+        for clause in environment.clauses.iter() {
+            if let ProgramClause::Implies(implication) = clause {
+                if implication.conditions.is_empty() {
+                    facts.push(implication.consequence.clone());
+                }
+            }
+        }
+        for i in 0..facts.len() {
+            if let DomainGoal::FromEnv(FromEnv::Trait(trait_ref)) = &facts[i] {
+                let super_traits =
+                    super_traits(db, trait_ref.trait_id)?.substitute(&trait_ref.parameters);
+                facts.extend(
+                    super_traits
+                        .into_iter()
+                        .filter(|super_trait| super_trait.binders.is_empty())
+                        .map(|super_trait| DomainGoal::FromEnv(FromEnv::Trait(super_trait.value))),
+                );
+            }
+        }
+
+        // The clauses that could give `FromEnv` facts: those of the
+        // environment, and those that they imply.
+        let from_env_clauses: Vec<_> = elaborate_env(db, &environment.clauses)?
+            .into_keys()
+            .chain(environment.clauses.iter().cloned())
+            .collect();
+        implied_by_facts(db, environment, &facts, &from_env_clauses, self)
+    }
+}
+
+/// `GoalExt::implied_by_env` for `goal`, given the `facts` of
+/// `environment` and the clauses that could give `FromEnv` facts.
+fn implied_by_facts(
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    facts: &[DomainGoal<ChalkIr>],
+    from_env_clauses: &[ProgramClause<ChalkIr>],
+    goal: &Goal<ChalkIr>,
+) -> Result<ImpliedByEnv, MissingData> {
+    Ok(match goal {
+        Goal::And(left, right) => {
+            match (
+                implied_by_facts(db, environment, facts, from_env_clauses, left)?,
+                implied_by_facts(db, environment, facts, from_env_clauses, right)?,
+            ) {
+                (ImpliedByEnv::Yes, ImpliedByEnv::Yes) => ImpliedByEnv::Yes,
+                (ImpliedByEnv::No, _) | (_, ImpliedByEnv::No) => ImpliedByEnv::No,
+                _ => ImpliedByEnv::NeedsSolver,
+            }
+        }
+
+        Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })) if a == b => ImpliedByEnv::Yes,
+
+        Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Custom(_)))
+        | Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Compatible(()))) => ImpliedByEnv::NeedsSolver,
+
+        Goal::Leaf(LeafGoal::DomainGoal(goal)) => {
+            let from_env = match goal {
+                DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => {
+                    Some(DomainGoal::FromEnv(FromEnv::Trait(trait_ref.clone())))
+                }
+                _ => None,
+            };
+            if facts
+                .iter()
+                .any(|fact| fact == goal || Some(fact) == from_env.as_ref())
+            {
+                return Ok(ImpliedByEnv::Yes);
+            }
+
+            // The rule `Implemented(T: Foo) :- FromEnv(T: Foo)` of the
+            // trait needs a clause of the environment to go on, and the
+            // rules of the compatible modality need `Compatible`.
+            let from_env_impossible = match &from_env {
+                Some(from_env) => !from_env_clauses.iter().any(|c| c.could_match(from_env)),
+                None => false,
+            };
+            let compatible = facts.contains(&DomainGoal::Compatible(()));
+            let could_prove = program_clauses_with_origins(db, environment, goal)?
+                .iter()
+                .any(|(clause, origin)| {
+                    let conditions = &clause_implication(clause).conditions;
+                    let needs_compatible = conditions.iter().any(|condition| {
+                        matches!(
+                            condition,
+                            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Compatible(())))
+                        )
+                    });
+                    let needs_from_env = matches!(origin, ClauseOrigin::Trait(_))
+                        && conditions.iter().all(|condition| {
+                            matches!(
+                                condition,
+                                Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::FromEnv(_)))
+                            )
+                        });
+                    (compatible || !needs_compatible) && !(needs_from_env && from_env_impossible)
+                })
+                || environment.clauses.iter().any(|c| c.could_match(goal));
+            if could_prove {
+                ImpliedByEnv::NeedsSolver
+            } else {
+                ImpliedByEnv::No
+            }
+        }
+
+        Goal::Leaf(LeafGoal::EqGoal(_))
+        | Goal::Leaf(LeafGoal::TypeOutlives(_))
+        | Goal::Quantified(..)
+        | Goal::Implies(..)
+        | Goal::Not(_)
+        | Goal::CannotProve(()) => ImpliedByEnv::NeedsSolver,
+    })
+}
+
+fn clause_implication(clause: &ProgramClause<ChalkIr>) -> &ProgramClauseImplication<ChalkIr> {
+    match clause {
+        ProgramClause::Implies(implication) => implication,
+        ProgramClause::ForAll(binders) => &binders.value,
+    }
+}
+
+/// Rebuilds `kind<binders> { op(value) }`, re-normalizing the new body
+/// since `op` may have placed further quantifiers beside `value`.
+fn hoist(
+    kind: QuantifierKind,
+    binders: Binders<Arc<Goal<ChalkIr>>>,
+    op: impl FnOnce(Arc<Goal<ChalkIr>>) -> Goal<ChalkIr>,
+) -> Goal<ChalkIr> {
+    let Binders { binders, value } = binders;
+    Goal::Quantified(
+        kind,
+        Binders {
+            binders,
+            value: Arc::new(op(value).into_prenex()),
+        },
+    )
+}
+
+/// Solves each of the closed `goals` in `environment`, as issued when
+/// type-checking a function body, returning the results in the order
+/// of `goals` (`Err(NoSolution)` if a goal cannot be proven).
+///
+/// This is cheaper than canonicalizing each goal separately with
+/// `into_closed_goal`: the environment is canonicalized only once and
+/// then shared by all of the goals. The goals are solved smallest
+/// first, since smaller goals are often subgoals of larger ones (e.g.,
+/// `Foo: Clone` of `Vec<Foo>: Clone`), whose tables are then already
+/// complete when the larger goal needs them.
+///
+/// # Panics
+///
+/// Panics if `environment` or one of the `goals` contains inference
+/// variables or free bound variables.
+pub fn solve_batch(
+    solver: &mut Solver,
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    goals: &[Goal<ChalkIr>],
+) -> Vec<Fallible<Solution>> {
+    let mut infer = InferenceTable::new();
+    let environment = closed(&mut infer, environment);
+
+    let mut order: Vec<usize> = (0..goals.len()).collect();
+    order.sort_by_key(|&i| size(&goals[i]));
+
+    let mut solutions = vec![Err(NoSolution); goals.len()];
+    for i in order {
+        let goal = UCanonical {
+            canonical: Canonical {
+                value: InEnvironment {
+                    environment: environment.clone(),
+                    goal: closed(&mut infer, &goals[i]),
+                },
+                binders: vec![],
+            },
+            universes: 1,
+        };
+        solutions[i] = solver.solve(db, &goal).ok_or(NoSolution);
+    }
+    solutions
+}
+
+/// Returns true if `clause`, one of the clauses of `environment`, is
+/// redundant: the rest of the environment proves it without it. This
+/// is what a lint like "this bound is redundant" asks of each where
+/// clause of an item, given as `FromEnv` facts (as in
+/// `implied_env_of_impl`), or as the hypotheses of an `if` goal.
+///
+/// A `FromEnv(T: Foo)` clause is proven as `T: Foo`, and a
+/// `FromEnv(T)` one as `WellFormed(T)`; any other clause as its
+/// consequence, assuming its conditions. A clause whose conditions are
+/// not all domain goals is never redundant. The clause only counts as
+/// redundant if it holds without any lifetime constraints.
+///
+/// The goal is solved by a fresh solver of `SolverChoice::default()`,
+/// so a clause that is only proven through itself (e.g., by an impl
+/// like `impl<T> Foo for T where T: Foo`) is an inductive cycle that
+/// fails, and the clause is not redundant.
+///
+/// # Panics
+///
+/// Panics if `environment` or `clause` contains inference variables
+/// or free bound variables.
+pub fn is_redundant_clause(
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    clause: &ProgramClause<ChalkIr>,
+) -> Result<bool, MissingData> {
+    let implication = clause_implication(clause);
+    let mut hypotheses = vec![];
+    for condition in &implication.conditions {
+        match condition {
+            Goal::Leaf(LeafGoal::DomainGoal(condition)) => {
+                hypotheses.push(condition.clone().cast())
+            }
+            _ => return Ok(false),
+        }
+    }
+    let consequence: Goal<ChalkIr> = match &implication.consequence {
+        DomainGoal::FromEnv(FromEnv::Trait(trait_ref)) => trait_ref.clone().cast(),
+        DomainGoal::FromEnv(FromEnv::Ty(ty)) => WellFormed::Ty(ty.clone()).cast(),
+        consequence => consequence.clone().cast(),
+    };
+    let goal = if hypotheses.is_empty() {
+        consequence
+    } else {
+        Goal::Implies(hypotheses, Arc::new(consequence))
+    };
+    let goal = match clause {
+        ProgramClause::Implies(_) => goal,
+        ProgramClause::ForAll(binders) => Goal::Quantified(
+            QuantifierKind::ForAll,
+            Binders {
+                binders: binders.binders,
+                value: Arc::new(goal),
+            },
+        ),
+    };
+
+    let rest = Environment {
+        clauses: Arc::new(
+            environment
+                .clauses
+                .iter()
+                .filter(|c| *c != clause)
+                .cloned()
+                .collect(),
+        ),
+    };
+    let mut infer = InferenceTable::new();
+    let goal = closed(&mut infer, &InEnvironment::new(&rest, goal));
+    let goal = infer
+        .u_canonicalize(&Canonical {
+            value: goal,
+            binders: vec![],
+        })
+        .quantified;
+    let solution = SolverChoice::default().into_solver().try_solve(db, &goal)?;
+    Ok(match solution {
+        Some(Solution::Unique(constrained)) => constrained.value.constraints.is_empty(),
+        _ => false,
+    })
+}
+
+/// Returns a hash of `goal` that is stable across processes and
+/// platforms, so that it can be used as the key of a cache shared
+/// between processes. Two goals have the same hash if they are equal
+/// and were lowered from programs that assign the same ids to the
+/// items they mention; the text of identifiers is hashed, not their
+/// interned index. See `chalk_ir::stable_hash` for details.
+///
+/// The hash does not cover the mode of the solver, which a cache must
+/// also be keyed by; `Solver::cache_key` combines the two.
+pub fn stable_hash_goal(goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> u64 {
+    goal.stable_hash_value()
+}
+
+/// Clauses bound by the parameters of an impl, each with its origin
+/// (see `implied_env_of_impl`).
+pub type ImpliedEnv = Binders<Vec<(ProgramClause<ChalkIr>, ClauseOrigin)>>;
+
+/// Returns the clauses that the body of the impl `impl_id` may assume,
+/// each paired with its origin:
+///
+/// - the where clauses of the impl, as `FromEnv` facts;
+/// - the well-formedness of the types in the impl header (its implied
+///   bounds), as `FromEnv(Ty)` facts;
+/// - everything that those facts imply, e.g. the supertraits of the
+///   traits in the where clauses, or the where clauses of the structs
+///   in the header.
+///
+/// The first two have the origin `ClauseOrigin::Impl(impl_id)`; the
+/// others name the declaration they were derived from. These are the
+/// hypotheses under which the WF check of the impl proves its goals.
+///
+/// The clauses are bound by the parameters of the impl, given by the
+/// binders of the result. The hypotheses come first, in order, and
+/// the clauses implied by them follow, sorted.
+pub fn implied_env_of_impl(
+    db: &dyn RustIrDatabase,
+    impl_id: ImplId,
+) -> Result<ImpliedEnv, MissingData> {
+    let impl_datum = db.try_impl_datum(impl_id)?;
+    let impl_bound = &impl_datum.binders.value;
+
+    let mut header_input_types = vec![];
+    impl_bound.trait_ref.fold(&mut header_input_types);
+
+    let hypotheses: Vec<ProgramClause<ChalkIr>> = impl_bound
+        .where_clauses
+        .iter()
+        .cloned()
+        .map(|qwc| qwc.into_from_env_goal().cast())
+        .chain(
+            header_input_types
+                .into_iter()
+                .map(|ty| DomainGoal::FromEnv(FromEnv::Ty(ty)).cast()),
+        )
+        .collect();
+
+    let mut implied: Vec<_> = elaborate_env(db, &hypotheses)?
+        .into_iter()
+        .filter(|(clause, _)| !hypotheses.contains(clause))
+        .collect();
+    implied.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let clauses = hypotheses
+        .into_iter()
+        .map(|clause| (clause, ClauseOrigin::Impl(impl_id)))
+        .chain(implied)
+        .collect();
+    Ok(Binders {
+        binders: impl_datum.binders.binders,
+        value: clauses,
+    })
+}
+
+/// The supertraits of a trait, bound by the parameters of the trait
+/// (see `super_traits`). Each supertrait is bound in turn by the
+/// lifetimes of higher-ranked supertrait bounds on the way to it.
+pub type SuperTraits = Binders<Vec<Binders<TraitRef<ChalkIr>>>>;
+
+/// Returns the supertraits of `trait_id`: the traits named by its where
+/// clauses of the form `Self: Bar<..>`, and their supertraits in turn,
+/// in terms of the parameters of `trait_id`. Given
+///
+/// ```notrust
+/// trait Foo<T> where Self: Bar<Vec<T>>, forall<'a> Self: Baz<'a> { }
+/// trait Bar<U> where Self: Qux<U> { }
+/// ```
+///
+/// the supertraits of `Foo` are `Self: Bar<Vec<T>>`, `Self:
+/// Qux<Vec<T>>` and `forall<'a> { Self: Baz<'a> }`, bound by `Self` and
+/// `T`. The supertraits are listed in the order of a depth-first walk
+/// of the where clauses, without duplicates; a trait that is its own
+/// supertrait (through a cycle) is not walked again.
+///
+/// To find the supertraits of many traits, use a `TraitGraph`, which
+/// caches them.
+pub fn super_traits(
+    db: &dyn RustIrDatabase,
+    trait_id: TraitId,
+) -> Result<SuperTraits, MissingData> {
+    let trait_datum = db.try_trait_datum(trait_id)?;
+    let binders = &trait_datum.binders.binders;
+    let trait_ref = TraitRef {
+        trait_id,
+        parameters: binders.iter().zip(0..).map(|p| p.to_parameter()).collect(),
+    };
+
+    let mut super_traits = vec![];
+    push_super_traits(
+        db,
+        &Binders {
+            binders: ParameterKinds::EMPTY,
+            value: trait_ref,
+        },
+        &mut vec![],
+        &mut super_traits,
+    )?;
+    Ok(Binders {
+        binders: *binders,
+        value: super_traits,
+    })
+}
+
+/// Returns the goal that holds if `self_ty` satisfies all of `bounds`
+/// (e.g., the bounds of an associated type, instantiated for some
+/// type), or `None` if there are no bounds. A bound with associated
+/// type bindings is desugared as in where clauses: `Iterator<Item =
+/// u32>` becomes
+///
+/// ```notrust
+/// Implemented(self_ty: Iterator), ProjectionEq(<self_ty as Iterator>::Item = u32)
+/// ```
+///
+/// with the `Implemented` goal only included once, however many
+/// bindings the bound has.
+pub fn bounds_goal(
+    bounds: &[QuantifiedInlineBound],
+    self_ty: &Ty<ChalkIr>,
+) -> Option<Goal<ChalkIr>> {
+    let mut where_clauses: Vec<QuantifiedWhereClause<ChalkIr>> = vec![];
+    for wc in bounds
+        .iter()
+        .flat_map(|bound| bound.into_where_clauses(self_ty.clone()))
+    {
+        if !where_clauses.contains(&wc) {
+            where_clauses.push(wc);
+        }
+    }
+    where_clauses
+        .into_iter()
+        .map(|wc| wc.cast())
+        .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+}
+
+/// Pushes the supertraits of `trait_ref` onto `super_traits`. The
+/// binders of `trait_ref` are those of the higher-ranked bounds on the
+/// way to it; the variables bound beyond those are the parameters of
+/// the trait `super_traits` was invoked for. `stack` holds the traits
+/// being walked.
+fn push_super_traits(
+    db: &dyn RustIrDatabase,
+    trait_ref: &Binders<TraitRef<ChalkIr>>,
+    stack: &mut Vec<TraitId>,
+    super_traits: &mut Vec<Binders<TraitRef<ChalkIr>>>,
+) -> Result<(), MissingData> {
+    let trait_id = trait_ref.value.trait_id;
+    if stack.contains(&trait_id) {
+        return Ok(());
+    }
+    let trait_datum = db.try_trait_datum(trait_id)?;
+    stack.push(trait_id);
+
+    for where_clause in &trait_datum.binders.value.where_clauses {
+        // Within the where clause, `Self` (the first parameter of the
+        // trait) is the first variable not bound by the clause itself.
+        let own_binders = &where_clause.binders;
+        match &where_clause.value {
+            WhereClause::Implemented(super_ref)
+                if super_ref.parameters[0] == Ty::BoundVar(own_binders.len()).cast() => {}
+            _ => continue,
+        }
+
+        // Substituting the parameters of `trait_ref` shifts them over
+        // the binders of the where clause, so the variables bound by
+        // `trait_ref` come after those.
+        let where_clause = Binders {
+            binders: trait_datum.binders.binders,
+            value: where_clause.clone(),
+        }
+        .substitute(&trait_ref.value.parameters);
+        let super_ref = match where_clause.value {
+            WhereClause::Implemented(super_ref) => super_ref,
+            WhereClause::ProjectionEq(_) => unreachable!(),
+        };
+        let super_ref = Binders {
+            binders: own_binders
+                .iter()
+                .chain(&trait_ref.binders)
+                .cloned()
+                .collect(),
+            value: super_ref,
+        };
+
+        if !super_traits.contains(&super_ref) {
+            super_traits.push(super_ref.clone());
+        }
+        push_super_traits(db, &super_ref, stack, super_traits)?;
+    }
+
+    stack.pop();
+    Ok(())
 }
+
+/// Answers questions about how traits relate to one another, caching
+/// the supertraits of each trait (see `super_traits`).
+pub struct TraitGraph<'db> {
+    db: &'db dyn RustIrDatabase,
+    super_traits: FxHashMap<TraitId, Arc<SuperTraits>>,
+}
+
+impl<'db> TraitGraph<'db> {
+    pub fn new(db: &'db dyn RustIrDatabase) -> Self {
+        TraitGraph {
+            db,
+            super_traits: FxHashMap::default(),
+        }
+    }
+
+    /// The supertraits of `trait_id`; see `super_traits`.
+    pub fn super_traits(&mut self, trait_id: TraitId) -> Result<Arc<SuperTraits>, MissingData> {
+        if let Some(super_traits) = self.super_traits.get(&trait_id) {
+            return Ok(super_traits.clone());
+        }
+        let super_traits = Arc::new(super_traits(self.db, trait_id)?);
+        self.super_traits.insert(trait_id, super_traits.clone());
+        Ok(super_traits)
+    }
+
+    /// Returns true if `super_trait` is a supertrait of `trait_id` (for
+    /// some parameters).
+    pub fn is_super_trait(
+        &mut self,
+        trait_id: TraitId,
+        super_trait: TraitId,
+    ) -> Result<bool, MissingData> {
+        Ok(self
+            .super_traits(trait_id)?
+            .value
+            .iter()
+            .any(|super_ref| super_ref.value.trait_id == super_trait))
+    }
+}
+
+/// A binary operator, which is overloaded through one of the
+/// well-known traits (see `RustIrDatabase::well_known_items`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl BinOp {
+    /// Returns the trait through which the operator is overloaded.
+    pub fn well_known_trait(self) -> WellKnownTrait {
+        match self {
+            BinOp::Add => WellKnownTrait::Add,
+            BinOp::Sub => WellKnownTrait::Sub,
+            BinOp::Mul => WellKnownTrait::Mul,
+            BinOp::Div => WellKnownTrait::Div,
+            BinOp::Rem => WellKnownTrait::Rem,
+            BinOp::BitAnd => WellKnownTrait::BitAnd,
+            BinOp::BitOr => WellKnownTrait::BitOr,
+            BinOp::BitXor => WellKnownTrait::BitXor,
+            BinOp::Shl => WellKnownTrait::Shl,
+            BinOp::Shr => WellKnownTrait::Shr,
+        }
+    }
+}
+
+/// Returns the goal that holds if the operator `op` can be applied to
+/// operands of types `lhs` and `rhs`. For `BinOp::Add`, this is:
+///
+/// ```notrust
+/// exists<O> {
+///     Implemented(lhs: Add<rhs>),
+///     Normalize(<lhs as Add<rhs>>::Output -> O)
+/// }
+/// ```
+///
+/// where `O` is the type of the result. We use `Normalize` rather than
+/// `ProjectionEq` so that `O` is the type given by the impl (or the
+/// environment), and not the placeholder `(Add::Output)<lhs, rhs>`. Returns `None` if the
+/// database declares no trait for `op`, or if that trait has no
+/// `Output` associated type.
+pub fn binary_op_goal(
+    db: &dyn RustIrDatabase,
+    op: BinOp,
+    lhs: &Ty<ChalkIr>,
+    rhs: &Ty<ChalkIr>,
+) -> Option<Goal<ChalkIr>> {
+    // Within the `exists<O>`, `O` is `^0`.
+    let goal = binary_op_goal_with_output(
+        db,
+        op,
+        &lhs.shifted_in(1),
+        &rhs.shifted_in(1),
+        Ty::BoundVar(0),
+    )?;
+    Some(goal.quantify(QuantifierKind::Exists, vec![ParameterKind::Ty(())]))
+}
+
+/// Solves `binary_op_goal(db, op, lhs, rhs)` in `environment`. The
+/// substitution of the solution has a single entry, for the type of
+/// the result. Returns `None` if the goal cannot be proven, or could
+/// not be constructed.
+///
+/// # Panics
+///
+/// Panics if `environment`, `lhs` or `rhs` contains inference
+/// variables or free bound variables.
+pub fn resolve_binary_op(
+    solver: &mut Solver,
+    db: &dyn RustIrDatabase,
+    environment: &Environment<ChalkIr>,
+    op: BinOp,
+    lhs: &Ty<ChalkIr>,
+    rhs: &Ty<ChalkIr>,
+) -> Option<Solution> {
+    let mut infer = InferenceTable::new();
+    let environment = closed(&mut infer, environment);
+    let lhs = closed(&mut infer, lhs);
+    let rhs = closed(&mut infer, rhs);
+    let output = infer.new_variable(UniverseIndex::root()).to_ty();
+    let goal = binary_op_goal_with_output(db, op, &lhs, &rhs, output)?;
+    let canonical = infer
+        .canonicalize(&InEnvironment::new(&environment, goal))
+        .quantified;
+    let goal = infer.u_canonicalize(&canonical).quantified;
+    solver.solve(db, &goal)
+}
+
+/// The body of `binary_op_goal`, with `output` for the type of the
+/// result.
+fn binary_op_goal_with_output(
+    db: &dyn RustIrDatabase,
+    op: BinOp,
+    lhs: &Ty<ChalkIr>,
+    rhs: &Ty<ChalkIr>,
+    output: Ty<ChalkIr>,
+) -> Option<Goal<ChalkIr>> {
+    let trait_id = db.well_known_items().get(op.well_known_trait())?;
+    let output_id = db
+        .trait_datum(trait_id)
+        .associated_ty_ids
+        .iter()
+        .cloned()
+        .find(|&id| db.associated_ty_data(id).name.to_string() == "Output")?;
+
+    let parameters = vec![lhs.clone().cast(), rhs.clone().cast()];
+    let implemented = TraitRef {
+        trait_id,
+        parameters: parameters.clone(),
+    };
+    let normalize = Normalize {
+        projection: ProjectionTy {
+            associated_ty_id: output_id,
+            parameters,
+        },
+        ty: output,
+    };
+    Some(Goal::And(
+        Arc::new(implemented.cast()),
+        Arc::new(normalize.cast()),
+    ))
+}
+
+/// Canonicalizes `value`, which must not contain free variables.
+fn closed<T: Fold<ChalkIr>>(infer: &mut InferenceTable, value: &T) -> T::Result {
+    let canonical = infer.canonicalize(value).quantified;
+    assert!(
+        canonical.binders.is_empty(),
+        "expected a closed value, found {:?}",
+        value
+    );
+    canonical.value
+}
+
+/// Returns the number of types in `goal`, counting nested ones.
+pub(crate) fn size(goal: &Goal<ChalkIr>) -> usize {
+    let mut sizer = Sizer { size: 0 };
+    goal.fold_with(&mut sizer, 0).expect("Sizer is infallible");
+    sizer.size
+}
+
+struct Sizer {
+    size: usize,
+}
+
+impl TypeFolder<ChalkIr> for Sizer {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        self.size += 1;
+        fold::super_fold_ty(self, ty, binders)
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        fold::super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for Sizer {}
+
+impl DefaultInferenceFolder for Sizer {}
+
+impl DefaultPlaceholderFolder for Sizer {}
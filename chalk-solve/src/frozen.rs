@@ -0,0 +1,608 @@
+//! `FrozenProgram`, an immutable snapshot of a `RustIrDatabase`.
+//!
+//! An embedder whose items live in a database that may change (or that
+//! computes them lazily) can freeze the part of it that a solving
+//! session needs before handing it to the solver. Freezing reads every
+//! item reachable from a set of roots once, checks that the items agree
+//! with one another (e.g., that the impls listed for a trait are impls
+//! of that trait), and stores them in maps. The solver then runs against
+//! the snapshot, which answers each query without calling back into the
+//! embedder, and reports items that were not frozen as `MissingData`
+//! rather than panicking.
+
+use crate::coherence::SpecializationPriority;
+use crate::{MissingData, RegionConstraintResolver, RustIrDatabase};
+use chalk_engine::fallible::Fallible;
+use chalk_ir::cast::Cast;
+use chalk_ir::could_match::CouldMatch;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{
+    self, DefaultFreeVarFolder, DefaultInferenceFolder, DefaultPlaceholderFolder, Fold, TypeFolder,
+};
+use chalk_ir::*;
+use chalk_rust_ir::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Every lang item, all of which are roots of a freeze.
+const LANG_ITEMS: &[LangItem] = &[
+    LangItem::OwnedBox,
+    LangItem::Sized,
+    LangItem::Unpin,
+    LangItem::Drop,
+    LangItem::FnOnce,
+    LangItem::FnMut,
+    LangItem::Fn,
+    LangItem::Copy,
+    LangItem::Clone,
+    LangItem::Send,
+    LangItem::Sync,
+    LangItem::UnsafeCell,
+];
+
+/// A snapshot of the items of a `RustIrDatabase`, taken by
+/// `FrozenProgram::freeze`, which is itself a `RustIrDatabase`.
+///
+/// The snapshot answers the hooks through which the embedder takes part
+/// in solving (`solve_custom_goal`, `upcast_vtable_compatible`,
+/// `prefer_impl` and `ill_kinded_impl`) with their defaults, since they
+/// cannot be decided up front; the region constraint resolver, if any,
+/// is kept.
+#[derive(Clone)]
+pub struct FrozenProgram {
+    custom_clauses: Vec<ProgramClause<ChalkIr>>,
+    associated_ty_data: BTreeMap<TypeId, Arc<AssociatedTyDatum>>,
+    trait_data: BTreeMap<TraitId, Arc<TraitDatum>>,
+    struct_data: BTreeMap<StructId, Arc<StructDatum>>,
+    impl_data: BTreeMap<ImplId, Arc<ImplDatum>>,
+    associated_ty_values: BTreeMap<AssociatedTyValueId, Arc<AssociatedTyValue>>,
+
+    /// For each trait, all of its impls (as returned by
+    /// `impls_for_trait` for parameters that any impl matches).
+    impls_for_trait: BTreeMap<TraitId, Vec<ImplId>>,
+    local_impls_to_coherence_check: BTreeMap<TraitId, Vec<ImplId>>,
+
+    /// The pairs of an auto trait and a struct with an explicit impl of
+    /// the trait.
+    impl_provided_for: BTreeSet<(TraitId, StructId)>,
+    lang_items: BTreeMap<LangItem, TypeKindId>,
+    well_known_items: Arc<WellKnownItems>,
+
+    /// The names of the traits and structs.
+    type_names: BTreeMap<TypeKindId, Identifier>,
+    specialization_priorities: BTreeMap<ImplId, SpecializationPriority>,
+    local_inherent_impls: Vec<Arc<InherentImplDatum>>,
+    region_constraint_resolver: Option<Arc<RegionConstraintResolver>>,
+    #[cfg(feature = "layout")]
+    layout_facts: Vec<LayoutCompatible<ChalkIr>>,
+}
+
+impl FrozenProgram {
+    /// Snapshots the items of `db` that are reachable from `roots`.
+    ///
+    /// Besides the roots, the lang items, the well-known items, the
+    /// inherent impls and the items named by the custom clauses are
+    /// frozen, and from each item, the items it names:
+    ///
+    /// - a trait brings in its associated types and all of its impls;
+    /// - an impl, struct or associated type brings in the items named by
+    ///   its types and where clauses, and an associated type brings in
+    ///   its trait.
+    ///
+    /// Only the consequences of custom clauses are searched for traits,
+    /// so a trait that is named only by their conditions must be given
+    /// as a root. Fails if `db` is missing an item, or if its items do
+    /// not agree with one another.
+    pub fn freeze(
+        db: &dyn RustIrDatabase,
+        roots: impl IntoIterator<Item = TypeKindId>,
+    ) -> Result<FrozenProgram, FreezeError> {
+        let mut ids = IdCollector::default();
+        ids.ids.extend(roots);
+        ids.ids
+            .extend(LANG_ITEMS.iter().filter_map(|&item| db.lang_item(item)));
+        let well_known_items = db.well_known_items();
+        ids.ids.extend(
+            well_known_items
+                .traits()
+                .map(|(_, trait_id)| TypeKindId::TraitId(trait_id)),
+        );
+        ids.ids.extend(
+            well_known_items
+                .scalars()
+                .map(|(struct_id, _)| TypeKindId::StructId(struct_id)),
+        );
+        let custom_clauses = db.custom_clauses();
+        for clause in &custom_clauses {
+            let implication = match clause {
+                ProgramClause::Implies(implication) => implication,
+                ProgramClause::ForAll(binders) => &binders.value,
+            };
+            if let DomainGoal::Holds(where_clause) = &implication.consequence {
+                ids.where_clause(where_clause);
+            }
+            ids.visit(clause);
+        }
+        let local_inherent_impls = db.local_inherent_impls();
+        for inherent_impl in &local_inherent_impls {
+            ids.visit(&inherent_impl.binders.value.self_ty);
+            ids.where_clauses(&inherent_impl.binders.value.where_clauses);
+        }
+        #[cfg(feature = "layout")]
+        let layout_facts = db.layout_facts();
+        #[cfg(feature = "layout")]
+        ids.visit(&layout_facts);
+
+        let mut program = FrozenProgram {
+            custom_clauses,
+            associated_ty_data: BTreeMap::new(),
+            trait_data: BTreeMap::new(),
+            struct_data: BTreeMap::new(),
+            impl_data: BTreeMap::new(),
+            associated_ty_values: BTreeMap::new(),
+            impls_for_trait: BTreeMap::new(),
+            local_impls_to_coherence_check: BTreeMap::new(),
+            impl_provided_for: BTreeSet::new(),
+            lang_items: LANG_ITEMS
+                .iter()
+                .filter_map(|&item| Some((item, db.lang_item(item)?)))
+                .collect(),
+            well_known_items,
+            type_names: BTreeMap::new(),
+            specialization_priorities: BTreeMap::new(),
+            local_inherent_impls,
+            region_constraint_resolver: db.region_constraint_resolver(),
+            #[cfg(feature = "layout")]
+            layout_facts,
+        };
+
+        let mut seen = BTreeSet::new();
+        while let Some(id) = ids.ids.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            match id {
+                TypeKindId::TraitId(trait_id) => {
+                    program.type_names.insert(id, db.type_name(id));
+                    program.freeze_trait(db, trait_id, &mut ids)?
+                }
+                TypeKindId::StructId(struct_id) => {
+                    program.type_names.insert(id, db.type_name(id));
+                    let struct_datum = db.try_struct_datum(struct_id)?;
+                    ids.visit(&struct_datum.binders.value.fields);
+                    ids.where_clauses(&struct_datum.binders.value.where_clauses);
+                    program.struct_data.insert(struct_id, struct_datum);
+                }
+                TypeKindId::TypeId(associated_ty_id) => {
+                    let associated_ty = db.try_associated_ty_data(associated_ty_id)?;
+                    ids.ids.push(TypeKindId::TraitId(associated_ty.trait_id));
+                    for bound in &associated_ty.binders.value.bounds {
+                        ids.inline_bound(&bound.value);
+                    }
+                    ids.where_clauses(&associated_ty.binders.value.where_clauses);
+                    ids.visit(&associated_ty.binders.value.default);
+                    program
+                        .associated_ty_data
+                        .insert(associated_ty_id, associated_ty);
+                }
+            }
+        }
+
+        for (&trait_id, trait_datum) in &program.trait_data {
+            if !trait_datum.is_auto_trait() {
+                continue;
+            }
+            for &struct_id in program.struct_data.keys() {
+                if db.impl_provided_for(trait_id, struct_id) {
+                    program.impl_provided_for.insert((trait_id, struct_id));
+                }
+            }
+        }
+
+        program.validate()?;
+        Ok(program)
+    }
+
+    /// Freezes the trait `trait_id`, its associated types, and its
+    /// impls, with their associated type values.
+    fn freeze_trait(
+        &mut self,
+        db: &dyn RustIrDatabase,
+        trait_id: TraitId,
+        ids: &mut IdCollector,
+    ) -> Result<(), FreezeError> {
+        let trait_datum = db.try_trait_datum(trait_id)?;
+        ids.where_clauses(&trait_datum.binders.value.where_clauses);
+        ids.ids.extend(
+            trait_datum
+                .associated_ty_ids
+                .iter()
+                .map(|&id| TypeKindId::TypeId(id)),
+        );
+
+        // Ask for the impls with parameters that any impl matches, so
+        // that a database that filters them by the parameters returns
+        // all of them.
+        let unknown: Vec<Parameter<ChalkIr>> = (0..)
+            .map(InferenceVar::from)
+            .zip(&trait_datum.binders.binders)
+            .map(|(var, kind)| match kind {
+                ParameterKind::Ty(()) => var.to_ty::<ChalkIr>().cast(),
+                ParameterKind::Lifetime(()) => var.to_lifetime::<ChalkIr>().cast(),
+            })
+            .collect();
+        let impl_ids = db.impls_for_trait(trait_id, &unknown);
+        let local_impl_ids = db.local_impls_to_coherence_check(trait_id);
+        for &impl_id in impl_ids.iter().chain(&local_impl_ids) {
+            if self.impl_data.contains_key(&impl_id) {
+                continue;
+            }
+            let impl_datum = db.try_impl_datum(impl_id)?;
+            ids.visit(&impl_datum.binders.value.trait_ref.parameters);
+            ids.where_clauses(&impl_datum.binders.value.where_clauses);
+            for &value_id in &impl_datum.associated_ty_value_ids {
+                let value = db.try_associated_ty_value(value_id)?;
+                ids.visit(&value.value.value.ty);
+                self.associated_ty_values.insert(value_id, value);
+            }
+            if let Some(priority) = db.impl_specialization_priority(impl_id) {
+                self.specialization_priorities.insert(impl_id, priority);
+            }
+            self.impl_data.insert(impl_id, impl_datum);
+        }
+
+        self.trait_data.insert(trait_id, trait_datum);
+        self.impls_for_trait.insert(trait_id, impl_ids);
+        self.local_impls_to_coherence_check
+            .insert(trait_id, local_impl_ids);
+        Ok(())
+    }
+
+    /// Checks that the frozen items agree with one another.
+    fn validate(&self) -> Result<(), FreezeError> {
+        for (&trait_id, trait_datum) in &self.trait_data {
+            if trait_datum.id != trait_id {
+                return Err(FreezeError::WrongId(TypeKindId::TraitId(trait_id)));
+            }
+            for &associated_ty_id in &trait_datum.associated_ty_ids {
+                if self.associated_ty_data[&associated_ty_id].trait_id != trait_id {
+                    return Err(FreezeError::AssociatedTyOfOtherTrait {
+                        trait_id,
+                        associated_ty_id,
+                    });
+                }
+            }
+        }
+
+        for (&associated_ty_id, associated_ty) in &self.associated_ty_data {
+            if associated_ty.id != associated_ty_id {
+                return Err(FreezeError::WrongId(TypeKindId::TypeId(associated_ty_id)));
+            }
+            let trait_id = associated_ty.trait_id;
+            if !self.trait_data[&trait_id]
+                .associated_ty_ids
+                .contains(&associated_ty_id)
+            {
+                return Err(FreezeError::AssociatedTyOfOtherTrait {
+                    trait_id,
+                    associated_ty_id,
+                });
+            }
+        }
+
+        for (&struct_id, struct_datum) in &self.struct_data {
+            if struct_datum.id != struct_id {
+                return Err(FreezeError::WrongId(TypeKindId::StructId(struct_id)));
+            }
+        }
+
+        let impl_lists = self
+            .impls_for_trait
+            .iter()
+            .chain(&self.local_impls_to_coherence_check);
+        for (&trait_id, impl_ids) in impl_lists {
+            for &impl_id in impl_ids {
+                if self.impl_data[&impl_id].trait_id() != trait_id {
+                    return Err(FreezeError::ImplOfOtherTrait { trait_id, impl_id });
+                }
+            }
+        }
+
+        for (&impl_id, impl_datum) in &self.impl_data {
+            let trait_datum = &self.trait_data[&impl_datum.trait_id()];
+            for &value_id in &impl_datum.associated_ty_value_ids {
+                let value = &self.associated_ty_values[&value_id];
+                if value.impl_id != impl_id
+                    || !trait_datum
+                        .associated_ty_ids
+                        .contains(&value.associated_ty_id)
+                {
+                    return Err(FreezeError::WrongAssociatedTyValue { impl_id, value_id });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for FrozenProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrozenProgram")
+            .field("traits", &self.trait_data.len())
+            .field("structs", &self.struct_data.len())
+            .field("impls", &self.impl_data.len())
+            .finish()
+    }
+}
+
+impl RustIrDatabase for FrozenProgram {
+    fn custom_clauses(&self) -> Vec<ProgramClause<ChalkIr>> {
+        self.custom_clauses.clone()
+    }
+
+    fn associated_ty_data(&self, ty: TypeId) -> Arc<AssociatedTyDatum> {
+        self.associated_ty_data[&ty].clone()
+    }
+
+    fn trait_datum(&self, trait_id: TraitId) -> Arc<TraitDatum> {
+        self.trait_data[&trait_id].clone()
+    }
+
+    fn struct_datum(&self, struct_id: StructId) -> Arc<StructDatum> {
+        self.struct_data[&struct_id].clone()
+    }
+
+    fn impl_datum(&self, impl_id: ImplId) -> Arc<ImplDatum> {
+        self.impl_data[&impl_id].clone()
+    }
+
+    fn associated_ty_value(&self, id: AssociatedTyValueId) -> Arc<AssociatedTyValue> {
+        self.associated_ty_values[&id].clone()
+    }
+
+    fn try_associated_ty_data(&self, ty: TypeId) -> Result<Arc<AssociatedTyDatum>, MissingData> {
+        self.associated_ty_data
+            .get(&ty)
+            .cloned()
+            .ok_or(MissingData::AssociatedTy(ty))
+    }
+
+    fn try_trait_datum(&self, trait_id: TraitId) -> Result<Arc<TraitDatum>, MissingData> {
+        self.trait_data
+            .get(&trait_id)
+            .cloned()
+            .ok_or(MissingData::Trait(trait_id))
+    }
+
+    fn try_struct_datum(&self, struct_id: StructId) -> Result<Arc<StructDatum>, MissingData> {
+        self.struct_data
+            .get(&struct_id)
+            .cloned()
+            .ok_or(MissingData::Struct(struct_id))
+    }
+
+    fn try_impl_datum(&self, impl_id: ImplId) -> Result<Arc<ImplDatum>, MissingData> {
+        self.impl_data
+            .get(&impl_id)
+            .cloned()
+            .ok_or(MissingData::Impl(impl_id))
+    }
+
+    fn try_associated_ty_value(
+        &self,
+        id: AssociatedTyValueId,
+    ) -> Result<Arc<AssociatedTyValue>, MissingData> {
+        self.associated_ty_values
+            .get(&id)
+            .cloned()
+            .ok_or(MissingData::AssociatedTyValue(id))
+    }
+
+    fn impls_for_trait(&self, trait_id: TraitId, parameters: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
+        let impl_ids = match self.impls_for_trait.get(&trait_id) {
+            Some(impl_ids) => impl_ids,
+            None => return vec![],
+        };
+        impl_ids
+            .iter()
+            .cloned()
+            .filter(|impl_id| {
+                let trait_ref = &self.impl_data[impl_id].binders.value.trait_ref;
+                trait_ref.parameters.len() != parameters.len()
+                    || <[_] as CouldMatch<[_]>>::could_match(parameters, &trait_ref.parameters)
+            })
+            .collect()
+    }
+
+    fn local_impls_to_coherence_check(&self, trait_id: TraitId) -> Vec<ImplId> {
+        self.local_impls_to_coherence_check
+            .get(&trait_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn impl_provided_for(&self, auto_trait_id: TraitId, struct_id: StructId) -> bool {
+        self.impl_provided_for.contains(&(auto_trait_id, struct_id))
+    }
+
+    fn lang_item(&self, item: LangItem) -> Option<TypeKindId> {
+        self.lang_items.get(&item).cloned()
+    }
+
+    fn well_known_items(&self) -> Arc<WellKnownItems> {
+        self.well_known_items.clone()
+    }
+
+    fn type_name(&self, id: TypeKindId) -> Identifier {
+        match self.type_names.get(&id) {
+            Some(&name) => name,
+            None => panic!("no type with id `{:?}`", id),
+        }
+    }
+
+    fn region_constraint_resolver(&self) -> Option<Arc<RegionConstraintResolver>> {
+        self.region_constraint_resolver.clone()
+    }
+
+    fn impl_specialization_priority(&self, impl_id: ImplId) -> Option<SpecializationPriority> {
+        self.specialization_priorities.get(&impl_id).cloned()
+    }
+
+    fn local_inherent_impls(&self) -> Vec<Arc<InherentImplDatum>> {
+        self.local_inherent_impls.clone()
+    }
+
+    #[cfg(feature = "layout")]
+    fn layout_facts(&self) -> Vec<LayoutCompatible<ChalkIr>> {
+        self.layout_facts.clone()
+    }
+}
+
+/// Why `FrozenProgram::freeze` failed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum FreezeError {
+    /// The database could not provide an item.
+    Missing(MissingData),
+
+    /// The datum that the database gave for the item has another id.
+    WrongId(TypeKindId),
+
+    /// The database listed the impl among those of `trait_id`, but it
+    /// is an impl of another trait.
+    ImplOfOtherTrait { trait_id: TraitId, impl_id: ImplId },
+
+    /// The trait and the associated type disagree on whether the
+    /// trait declares it.
+    AssociatedTyOfOtherTrait {
+        trait_id: TraitId,
+        associated_ty_id: TypeId,
+    },
+
+    /// The impl lists the associated type value, but the value belongs
+    /// to another impl, or to an associated type of another trait.
+    WrongAssociatedTyValue {
+        impl_id: ImplId,
+        value_id: AssociatedTyValueId,
+    },
+}
+
+impl From<MissingData> for FreezeError {
+    fn from(missing: MissingData) -> Self {
+        FreezeError::Missing(missing)
+    }
+}
+
+impl fmt::Display for FreezeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreezeError::Missing(missing) => missing.fmt(f),
+            FreezeError::WrongId(id) => write!(f, "the datum of {:?} has another id", id),
+            FreezeError::ImplOfOtherTrait { trait_id, impl_id } => write!(
+                f,
+                "impl {:?} is listed for trait {:?}, but implements another trait",
+                impl_id, trait_id
+            ),
+            FreezeError::AssociatedTyOfOtherTrait {
+                trait_id,
+                associated_ty_id,
+            } => write!(
+                f,
+                "trait {:?} and associated type {:?} disagree on whether the trait declares it",
+                trait_id, associated_ty_id
+            ),
+            FreezeError::WrongAssociatedTyValue { impl_id, value_id } => write!(
+                f,
+                "impl {:?} lists associated type value {:?}, which is not one of its values",
+                impl_id, value_id
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FreezeError {}
+
+/// Collects the ids of the items named by the values it visits, which
+/// remain to be frozen.
+#[derive(Default)]
+struct IdCollector {
+    ids: Vec<TypeKindId>,
+}
+
+impl IdCollector {
+    fn visit<T: Fold<ChalkIr>>(&mut self, value: &T) {
+        value.fold_with(self, 0).expect("IdCollector is infallible");
+    }
+
+    fn where_clauses(&mut self, where_clauses: &[QuantifiedWhereClause<ChalkIr>]) {
+        for where_clause in where_clauses {
+            self.where_clause(&where_clause.value);
+            self.visit(where_clause);
+        }
+    }
+
+    fn where_clause(&mut self, where_clause: &WhereClause<ChalkIr>) {
+        match where_clause {
+            WhereClause::Implemented(trait_ref) => {
+                self.ids.push(TypeKindId::TraitId(trait_ref.trait_id))
+            }
+            WhereClause::ProjectionEq(eq) => self
+                .ids
+                .push(TypeKindId::TypeId(eq.projection.associated_ty_id)),
+        }
+    }
+
+    fn inline_bound(&mut self, bound: &InlineBound) {
+        match bound {
+            InlineBound::TraitBound(bound) => {
+                self.ids.push(TypeKindId::TraitId(bound.trait_id));
+                self.visit(&bound.args_no_self);
+            }
+            InlineBound::ProjectionEqBound(bound) => {
+                self.ids
+                    .push(TypeKindId::TraitId(bound.trait_bound.trait_id));
+                self.ids.push(TypeKindId::TypeId(bound.associated_ty_id));
+                self.visit(&bound.trait_bound.args_no_self);
+                self.visit(&bound.parameters);
+                self.visit(&bound.value);
+            }
+        }
+    }
+}
+
+impl TypeFolder<ChalkIr> for IdCollector {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        match ty {
+            Ty::Apply(apply) => match apply.name {
+                TypeName::TypeKindId(id) => self.ids.push(id),
+                TypeName::AssociatedType(id) => self.ids.push(TypeKindId::TypeId(id)),
+                _ => {}
+            },
+            Ty::Dyn(bounds) | Ty::Opaque(bounds) => {
+                for bound in &bounds.value {
+                    self.where_clause(&bound.value);
+                }
+            }
+            Ty::Projection(projection) => self
+                .ids
+                .push(TypeKindId::TypeId(projection.associated_ty_id)),
+            _ => {}
+        }
+        fold::super_fold_ty(self, ty, binders)
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        fold::super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for IdCollector {}
+
+impl DefaultInferenceFolder for IdCollector {}
+
+impl DefaultPlaceholderFolder for IdCollector {}
@@ -1,6 +1,8 @@
+use crate::RegionConstraintResolver;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::*;
 use chalk_ir::{cast::Cast, fold::Fold};
+use std::sync::Arc;
 
 pub(crate) mod canonicalize;
 pub(crate) mod instantiate;
@@ -18,6 +20,10 @@ pub(crate) struct InferenceTable {
     unify: ena::unify::InPlaceUnificationTable<EnaVariable>,
     vars: Vec<EnaVariable>,
     max_universe: UniverseIndex,
+
+    /// Decides the region constraints that unification gives rise to,
+    /// if set (see `RustIrDatabase::region_constraint_resolver`).
+    region_constraint_resolver: Option<Arc<RegionConstraintResolver>>,
 }
 
 pub(crate) struct InferenceSnapshot {
@@ -35,9 +41,19 @@ impl InferenceTable {
             unify: ena::unify::UnificationTable::new(),
             vars: vec![],
             max_universe: UniverseIndex::root(),
+            region_constraint_resolver: None,
         }
     }
 
+    /// Sets the callback that decides the region constraints that
+    /// unification gives rise to; without one, they are all kept.
+    pub(crate) fn set_region_constraint_resolver(
+        &mut self,
+        resolver: Option<Arc<RegionConstraintResolver>>,
+    ) {
+        self.region_constraint_resolver = resolver;
+    }
+
     /// Creates a new inference table, pre-populated with
     /// `num_universes` fresh universes. Instantiates the canonical
     /// value `canonical` within those universes (which must not
@@ -247,7 +247,9 @@ fn lifetime_constraint_indirect() {
     // '!1.
     let t_a = ty!(apply (item 0) (lifetime (placeholder 1)));
     let t_b = ty!(apply (item 0) (lifetime (infer 1)));
-    let UnificationResult { goals, constraints } = table.unify(&environment0, &t_a, &t_b).unwrap();
+    let UnificationResult {
+        goals, constraints, ..
+    } = table.unify(&environment0, &t_a, &t_b).unwrap();
     assert!(goals.is_empty());
     assert!(constraints.is_empty());
 
@@ -257,7 +259,9 @@ fn lifetime_constraint_indirect() {
     // we will replace `'!1` with a new variable `'?2` and introduce a
     // (likely unsatisfiable) constraint relating them.
     let t_c = ty!(infer 0);
-    let UnificationResult { goals, constraints } = table.unify(&environment0, &t_c, &t_b).unwrap();
+    let UnificationResult {
+        goals, constraints, ..
+    } = table.unify(&environment0, &t_c, &t_b).unwrap();
     assert!(goals.is_empty());
     assert_eq!(constraints.len(), 1);
     assert_eq!(
@@ -265,3 +269,53 @@ fn lifetime_constraint_indirect() {
         "InEnvironment { environment: Env([]), goal: \'?2 == \'!1_0 }",
     );
 }
+
+#[test]
+fn unification_errors() {
+    let mut table = InferenceTable::new();
+    let environment0 = Environment::new();
+    let a = table.new_variable(U0).to_ty();
+    let mut explain = |a: &Ty<ChalkIr>, b: &Ty<ChalkIr>| {
+        table
+            .unify_explaining(&environment0, a, b)
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert_eq!(
+        explain(&ty!(apply (item 0)), &ty!(apply (item 1))),
+        "expected `TypeId(0)`, found `TypeId(1)`"
+    );
+    assert_eq!(
+        explain(
+            &ty!(apply (item 0) (apply (item 1))),
+            &ty!(apply (item 0) (apply (item 1)) (apply (item 1)))
+        ),
+        "expected 1 generic arguments for `TypeId(0)`, found 2"
+    );
+    assert_eq!(
+        explain(
+            &ty!(apply (item 0) (apply (item 1))),
+            &ty!(apply (item 0) (lifetime (placeholder 1)))
+        ),
+        "expected a type as generic argument 0 of `TypeId(0)`, found a lifetime"
+    );
+    assert_eq!(
+        explain(&a, &ty!(apply (placeholder 1))),
+        "`!1_0` would have to outlive its universe, as a variable of U0 cannot name it"
+    );
+    assert_eq!(
+        explain(&a, &ty!(apply (item 0) (expr a))),
+        "`?0` would have to be `TypeId(0)<?0>`, which contains it, \
+         as generic argument 0 of `TypeId(0)<?0>`"
+    );
+    assert_eq!(
+        explain(
+            &a,
+            &ty!(apply (item 0) (apply (item 1)) (apply (item 1) (expr a)))
+        ),
+        "`?0` would have to be `TypeId(0)<TypeId(1), TypeId(1)<?0>>`, which contains it, \
+         as generic argument 0 of `TypeId(1)<?0>`, \
+         which is generic argument 1 of `TypeId(0)<TypeId(1), TypeId(1)<?0>>`"
+    );
+}
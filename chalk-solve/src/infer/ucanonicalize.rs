@@ -164,10 +164,19 @@ impl UniverseMap {
         }
     }
 
+    /// Like `map_universe_to_canonical`, but returns `None` if
+    /// `universe` does not appear in the u-canonical value.
+    pub(crate) fn canonical_universe_of(&self, universe: UniverseIndex) -> Option<UniverseIndex> {
+        self.universes
+            .binary_search(&universe)
+            .ok()
+            .map(|counter| UniverseIndex { counter })
+    }
+
     /// Given a "canonical universe" -- one found in the
     /// `u_canonicalize` result -- returns the original universe that
     /// it corresponded to.
-    fn map_universe_from_canonical(&self, universe: UniverseIndex) -> UniverseIndex {
+    pub(crate) fn map_universe_from_canonical(&self, universe: UniverseIndex) -> UniverseIndex {
         if universe.counter < self.universes.len() {
             self.universes[universe.counter]
         } else {
@@ -1,10 +1,13 @@
+use crate::RegionConstraintResolution;
 use chalk_engine::fallible::*;
 use chalk_ir::cast::Cast;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::fold::{
-    DefaultFreeVarFolder, DefaultTypeFolder, Fold, InferenceFolder, PlaceholderFolder,
+    DefaultFreeVarFolder, DefaultPlaceholderFolder, DefaultTypeFolder, Fold, InferenceFolder,
+    PlaceholderFolder,
 };
 use chalk_ir::zip::{Zip, Zipper};
+use std::fmt;
 
 use super::var::*;
 use super::*;
@@ -16,6 +19,20 @@ impl InferenceTable {
         a: &T,
         b: &T,
     ) -> Fallible<UnificationResult>
+    where
+        T: ?Sized + Zip<ChalkIr>,
+    {
+        self.unify_explaining(environment, a, b)
+            .map_err(|_| NoSolution)
+    }
+
+    /// Like `unify`, but says why unification failed, if it did.
+    pub(crate) fn unify_explaining<T>(
+        &mut self,
+        environment: &Environment<ChalkIr>,
+        a: &T,
+        b: &T,
+    ) -> Result<UnificationResult, UnificationError>
     where
         T: ?Sized + Zip<ChalkIr>,
     {
@@ -37,6 +54,82 @@ impl InferenceTable {
             }
         }
     }
+
+    /// Records the constraint `ty: lifetime`, unless `ty` has no
+    /// lifetimes (or parameters that could stand for some) and so
+    /// trivially outlives any lifetime, or the region constraint
+    /// resolver of the table decides it then and there.
+    pub(crate) fn type_outlives(
+        &mut self,
+        environment: &Environment<ChalkIr>,
+        ty: &Ty<ChalkIr>,
+        lifetime: &Lifetime<ChalkIr>,
+    ) -> Fallible<UnificationResult> {
+        let ty = self.normalize_deep(ty);
+        debug_heading!("type_outlives(ty={:?}, lifetime={:?})", ty, lifetime);
+        let mut unifier = Unifier::new(self, environment);
+        if !outlives_trivially(&ty) {
+            unifier.push_constraint(Constraint::TypeOutlives(ty, *lifetime))?;
+        }
+        Ok(UnificationResult {
+            goals: unifier.goals,
+            constraints: unifier.constraints,
+            ambiguous: unifier.ambiguous,
+        })
+    }
+}
+
+impl InferenceTable {
+    /// True if `ty`, which must be normalized (see `normalize_deep`),
+    /// contains `var` or a variable unified with it.
+    fn occurs_in(&mut self, var: EnaVariable, ty: &Ty<ChalkIr>) -> bool {
+        ty.fold_with(&mut OccursIn { table: self, var }, 0).is_err()
+    }
+}
+
+/// The folder of `InferenceTable::occurs_in`, which fails at `var`.
+struct OccursIn<'t> {
+    table: &'t mut InferenceTable,
+    var: EnaVariable,
+}
+
+impl<'t> DefaultTypeFolder for OccursIn<'t> {}
+
+impl<'t> DefaultPlaceholderFolder for OccursIn<'t> {}
+
+impl<'t> DefaultFreeVarFolder for OccursIn<'t> {}
+
+impl<'t> InferenceFolder<ChalkIr> for OccursIn<'t> {
+    fn fold_inference_ty(&mut self, var: InferenceVar, _binders: usize) -> Fallible<Ty<ChalkIr>> {
+        if self.table.unify.unioned(EnaVariable::from(var), self.var) {
+            Err(NoSolution)
+        } else {
+            Ok(var.to_ty::<ChalkIr>())
+        }
+    }
+
+    fn fold_inference_lifetime(
+        &mut self,
+        var: InferenceVar,
+        _binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        Ok(var.to_lifetime::<ChalkIr>())
+    }
+}
+
+/// True if `ty` is built only of named types, with no lifetimes,
+/// placeholders or variables, like `Vec<u32>`.
+fn outlives_trivially(ty: &Ty<ChalkIr>) -> bool {
+    match ty {
+        Ty::Apply(apply) => match apply.name {
+            TypeName::Placeholder(_) => false,
+            _ => apply
+                .parameters
+                .iter()
+                .all(|parameter| parameter.as_ref().ty().is_some_and(outlives_trivially)),
+        },
+        _ => false,
+    }
 }
 
 struct Unifier<'t> {
@@ -44,14 +137,151 @@ struct Unifier<'t> {
     environment: &'t Environment<ChalkIr>,
     goals: Vec<InEnvironment<DomainGoal<ChalkIr>>>,
     constraints: Vec<InEnvironment<Constraint<ChalkIr>>>,
+    ambiguous: bool,
+
+    /// Why unification failed, recorded where the failure is found
+    /// (see `fail`), since `Zipper` can only return `NoSolution`.
+    error: Option<UnificationError>,
 }
 
 #[derive(Debug)]
 pub(crate) struct UnificationResult {
     pub(crate) goals: Vec<InEnvironment<DomainGoal<ChalkIr>>>,
     pub(crate) constraints: Vec<InEnvironment<Constraint<ChalkIr>>>,
+
+    /// True if unification equated two opaque constants that may or
+    /// may not be equal (see `TypeName::OpaqueConst`); the types can
+    /// then only be said to be equal ambiguously.
+    pub(crate) ambiguous: bool,
 }
 
+/// Why two values could not be unified, for diagnostics such as
+/// `Solver::rejected_candidates`. The `Display` impl words it for
+/// users.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnificationError {
+    /// The same type constructor, applied to generic arguments of
+    /// different kinds or to different numbers of them, e.g.
+    /// `Foo<u32>` and `Foo<u32, u32>`. The kinds are those of the
+    /// arguments of the first and of the second type.
+    KindMismatch {
+        name: TypeName,
+        expected: Vec<ParameterKind<()>>,
+        actual: Vec<ParameterKind<()>>,
+    },
+
+    /// Types with different constructors, e.g. `Foo` and `Bar`, or
+    /// `Foo` and `dyn Bar`.
+    ConstructorMismatch(Ty<ChalkIr>, Ty<ChalkIr>),
+
+    /// An inference variable would have to be bound to a type that
+    /// names `placeholder`, which its universe cannot see, e.g. in
+    /// `exists<T> { forall<U> { T = U } }`.
+    UniverseError {
+        placeholder: PlaceholderIndex,
+        universe: UniverseIndex,
+    },
+
+    /// The inference variable `var` would have to be bound to `ty`,
+    /// which contains it, e.g. in `exists<T> { T = Vec<T> }`. The
+    /// `path` leads from `ty` down to `var`: each step is a type along
+    /// the way and the index of its generic argument that holds the
+    /// rest (for `Vec<Box<T>>`, `(Vec<Box<T>>, 0)` and `(Box<T>, 0)`).
+    /// It stops short if `var` occurs other than as a generic argument,
+    /// e.g. in the bounds of a `dyn` type.
+    OccursCheck {
+        var: InferenceVar,
+        ty: Ty<ChalkIr>,
+        path: Vec<(Ty<ChalkIr>, usize)>,
+    },
+
+    /// The region constraint resolver decided that the constraint
+    /// does not hold (see `RustIrDatabase::region_constraint_resolver`).
+    RegionConstraint(InEnvironment<Constraint<ChalkIr>>),
+
+    /// The values differ other than in their types, e.g. two trait
+    /// refs of different traits.
+    Mismatch,
+}
+
+impl fmt::Display for UnificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnificationError::KindMismatch {
+                name,
+                expected,
+                actual,
+            } if expected.len() != actual.len() => write!(
+                f,
+                "expected {} generic arguments for `{:?}`, found {}",
+                expected.len(),
+                name,
+                actual.len()
+            ),
+            UnificationError::KindMismatch {
+                name,
+                expected,
+                actual,
+            } => {
+                let (index, (a, b)) = expected
+                    .iter()
+                    .zip(actual)
+                    .enumerate()
+                    .find(|(_, (a, b))| a != b)
+                    .expect("kind mismatch with the same kinds");
+                let kind = |kind: &ParameterKind<()>| match kind {
+                    ParameterKind::Ty(()) => "a type",
+                    ParameterKind::Lifetime(()) => "a lifetime",
+                };
+                write!(
+                    f,
+                    "expected {} as generic argument {} of `{:?}`, found {}",
+                    kind(a),
+                    index,
+                    name,
+                    kind(b)
+                )
+            }
+            UnificationError::ConstructorMismatch(a, b) => {
+                write!(f, "expected `{:?}`, found `{:?}`", a, b)
+            }
+            UnificationError::UniverseError {
+                placeholder,
+                universe,
+            } => write!(
+                f,
+                "`{:?}` would have to outlive its universe, as a variable of {:?} cannot name it",
+                placeholder.to_ty::<ChalkIr>(),
+                universe
+            ),
+            UnificationError::OccursCheck { var, ty, path } => {
+                write!(
+                    f,
+                    "`{:?}` would have to be `{:?}`, which contains it",
+                    var, ty
+                )?;
+                // Innermost first: `?0` is argument 0 of `Box<?0>`,
+                // which is argument 0 of `Vec<Box<?0>>`.
+                for (index, (ty, argument)) in path.iter().rev().enumerate() {
+                    let separator = if index == 0 { ", as" } else { ", which is" };
+                    write!(
+                        f,
+                        "{} generic argument {} of `{:?}`",
+                        separator, argument, ty
+                    )?;
+                }
+                Ok(())
+            }
+            UnificationError::RegionConstraint(constraint) => {
+                write!(f, "the region constraint `{:?}` does not hold", constraint)
+            }
+            UnificationError::Mismatch => write!(f, "the values differ"),
+        }
+    }
+}
+
+impl std::error::Error for UnificationError {}
+
 impl<'t> Unifier<'t> {
     fn new(table: &'t mut InferenceTable, environment: &'t Environment<ChalkIr>) -> Self {
         Unifier {
@@ -59,20 +289,25 @@ impl<'t> Unifier<'t> {
             table: table,
             goals: vec![],
             constraints: vec![],
+            ambiguous: false,
+            error: None,
         }
     }
 
     /// The main entry point for the `Unifier` type and really the
     /// only type meant to be called externally. Performs a
     /// unification of `a` and `b` and returns the Unification Result.
-    fn unify<T>(mut self, a: &T, b: &T) -> Fallible<UnificationResult>
+    fn unify<T>(mut self, a: &T, b: &T) -> Result<UnificationResult, UnificationError>
     where
         T: ?Sized + Zip<ChalkIr>,
     {
-        Zip::zip_with(&mut self, a, b)?;
+        if let Err(NoSolution) = Zip::zip_with(&mut self, a, b) {
+            return Err(self.error.unwrap_or(UnificationError::Mismatch));
+        }
         Ok(UnificationResult {
             goals: self.goals,
             constraints: self.constraints,
+            ambiguous: self.ambiguous,
         })
     }
 
@@ -83,9 +318,17 @@ impl<'t> Unifier<'t> {
         T: Zip<ChalkIr> + Fold<ChalkIr>,
     {
         let sub_unifier = Unifier::new(self.table, &self.environment);
-        let UnificationResult { goals, constraints } = sub_unifier.unify(&ty1, &ty2)?;
+        let UnificationResult {
+            goals,
+            constraints,
+            ambiguous,
+        } = match sub_unifier.unify(&ty1, &ty2) {
+            Ok(result) => result,
+            Err(error) => return self.fail(error),
+        };
         self.goals.extend(goals);
         self.constraints.extend(constraints);
+        self.ambiguous |= ambiguous;
         Ok(())
     }
 
@@ -143,28 +386,60 @@ impl<'t> Unifier<'t> {
                 self.unify_forall_other(quantified_ty, other_ty)
             }
 
+            (
+                &Ty::Apply(
+                    ref const1 @ ApplicationTy {
+                        name: TypeName::OpaqueConst(_),
+                        ..
+                    },
+                ),
+                &Ty::Apply(
+                    ref const2 @ ApplicationTy {
+                        name: TypeName::OpaqueConst(_),
+                        ..
+                    },
+                ),
+            ) => self.unify_opaque_consts(const1, const2),
+
             (&Ty::Apply(ref apply1), &Ty::Apply(ref apply2)) => {
                 // Cannot unify (e.g.) some struct type `Foo` and some struct type `Bar`
                 if apply1.name != apply2.name {
-                    return Err(NoSolution);
+                    return self.fail(UnificationError::ConstructorMismatch(a.clone(), b.clone()));
+                }
+
+                // Nor `Foo<T>` with `Foo<'a>`, or with `Foo<T, U>`
+                let kinds = |apply: &ApplicationTy<ChalkIr>| -> Vec<ParameterKind<()>> {
+                    apply
+                        .parameters
+                        .iter()
+                        .map(|parameter| match parameter.as_ref() {
+                            ParameterKind::Ty(_) => ParameterKind::Ty(()),
+                            ParameterKind::Lifetime(_) => ParameterKind::Lifetime(()),
+                        })
+                        .collect()
+                };
+                let (kinds1, kinds2) = (kinds(apply1), kinds(apply2));
+                if kinds1 != kinds2 {
+                    return self.fail(UnificationError::KindMismatch {
+                        name: apply1.name,
+                        expected: kinds1,
+                        actual: kinds2,
+                    });
                 }
 
                 Zip::zip_with(self, &apply1.parameters, &apply2.parameters)
             }
 
             // Cannot unify (e.g.) some struct type `Foo` and an `impl Trait` type
-            (&Ty::Apply(_), &Ty::Opaque(_)) | (&Ty::Opaque(_), &Ty::Apply(_)) => {
-                return Err(NoSolution);
-            }
-
-            // Cannot unify (e.g.) some struct type `Foo` and a `dyn Trait` type
-            (&Ty::Apply(_), &Ty::Dyn(_)) | (&Ty::Dyn(_), &Ty::Apply(_)) => {
-                return Err(NoSolution);
-            }
-
-            // Cannot unify (e.g.) some `dyn Trait` and some `impl Trait` type
-            (&Ty::Dyn(..), &Ty::Opaque(..)) | (&Ty::Opaque(..), &Ty::Dyn(..)) => {
-                return Err(NoSolution);
+            (&Ty::Apply(_), &Ty::Opaque(_))
+            | (&Ty::Opaque(_), &Ty::Apply(_))
+            // Nor some struct type `Foo` and a `dyn Trait` type
+            | (&Ty::Apply(_), &Ty::Dyn(_))
+            | (&Ty::Dyn(_), &Ty::Apply(_))
+            // Nor some `dyn Trait` and some `impl Trait` type
+            | (&Ty::Dyn(..), &Ty::Opaque(..))
+            | (&Ty::Opaque(..), &Ty::Dyn(..)) => {
+                self.fail(UnificationError::ConstructorMismatch(a.clone(), b.clone()))
             }
 
             (&Ty::Opaque(ref qwc1), &Ty::Opaque(ref qwc2))
@@ -193,6 +468,41 @@ impl<'t> Unifier<'t> {
         }
     }
 
+    /// Unifies two opaque constants. Syntactically identical constant
+    /// expressions (the same expression, applied to parameters that
+    /// unify) are equal; for any others, we cannot tell without
+    /// evaluating them, so the result is ambiguous (rather than an
+    /// error).
+    fn unify_opaque_consts(
+        &mut self,
+        const1: &ApplicationTy<ChalkIr>,
+        const2: &ApplicationTy<ChalkIr>,
+    ) -> Fallible<()> {
+        if const1.name == const2.name {
+            let snapshot = self.table.snapshot();
+            let (goals, constraints) = (self.goals.len(), self.constraints.len());
+            match Zip::zip_with(self, &const1.parameters, &const2.parameters) {
+                Ok(()) => {
+                    self.table.commit(snapshot);
+                    return Ok(());
+                }
+                Err(NoSolution) => {
+                    self.table.rollback_to(snapshot);
+                    self.goals.truncate(goals);
+                    self.constraints.truncate(constraints);
+                    self.error = None;
+                }
+            }
+        }
+
+        debug!(
+            "unify_opaque_consts: {:?} and {:?} may or may not be equal",
+            const1, const2
+        );
+        self.ambiguous = true;
+        Ok(())
+    }
+
     fn unify_forall_tys(
         &mut self,
         ty1: &QuantifiedTy<ChalkIr>,
@@ -287,7 +597,15 @@ impl<'t> Unifier<'t> {
         // as the variable is unified.
         let universe_index = self.table.universe_of_unbound_var(var);
 
-        let ty1 = ty.fold_with(&mut OccursCheck::new(self, var, universe_index), 0)?;
+        let mut occurs_check = OccursCheck::new(self, var, universe_index);
+        let ty1 = match ty.fold_with(&mut occurs_check, 0) {
+            Ok(ty1) => ty1,
+            Err(NoSolution) if occurs_check.cycle => {
+                let error = self.occurs_check_error(var, ty);
+                return self.fail(error);
+            }
+            Err(NoSolution) => return Err(NoSolution),
+        };
 
         self.table
             .unify
@@ -298,6 +616,45 @@ impl<'t> Unifier<'t> {
         Ok(())
     }
 
+    /// The `OccursCheck` error for binding `var` to `ty`, which
+    /// contains it: finds the path by which it does, descending into
+    /// the first generic argument that contains `var` each time.
+    fn occurs_check_error(&mut self, var: EnaVariable, ty: &Ty<ChalkIr>) -> UnificationError {
+        let ty = self.table.normalize_deep(ty);
+        let mut path = vec![];
+        let mut current = &ty;
+        loop {
+            let parameters = match current {
+                Ty::Apply(apply) => &apply.parameters,
+                Ty::Projection(proj) => &proj.parameters,
+                _ => break,
+            };
+            let table = &mut *self.table;
+            let argument = parameters
+                .iter()
+                .enumerate()
+                .find_map(|(index, parameter)| {
+                    parameter
+                        .as_ref()
+                        .ty()
+                        .filter(|parameter| table.occurs_in(var, parameter))
+                        .map(|parameter| (index, parameter))
+                });
+            match argument {
+                Some((index, parameter)) => {
+                    path.push((current.clone(), index));
+                    current = parameter;
+                }
+                None => break,
+            }
+        }
+        UnificationError::OccursCheck {
+            var: var.into(),
+            ty,
+            path,
+        }
+    }
+
     fn unify_lifetime_lifetime(
         &mut self,
         a: &Lifetime<ChalkIr>,
@@ -343,13 +700,13 @@ impl<'t> Unifier<'t> {
                         "unify_lifetime_lifetime: {:?} in {:?} cannot see {:?}; pushing constraint",
                         var, var_ui, idx.ui
                     );
-                    Ok(self.push_lifetime_eq_constraint(*a, *b))
+                    self.push_constraint(Constraint::LifetimeEq(*a, *b))
                 }
             }
 
             (&Lifetime::Placeholder(_), &Lifetime::Placeholder(_)) => {
                 if a != b {
-                    Ok(self.push_lifetime_eq_constraint(*a, *b))
+                    self.push_constraint(Constraint::LifetimeEq(*a, *b))
                 } else {
                     Ok(())
                 }
@@ -364,11 +721,35 @@ impl<'t> Unifier<'t> {
         }
     }
 
-    fn push_lifetime_eq_constraint(&mut self, a: Lifetime<ChalkIr>, b: Lifetime<ChalkIr>) {
-        self.constraints.push(InEnvironment::new(
-            self.environment,
-            Constraint::LifetimeEq(a, b),
-        ));
+    /// Records `constraint`, unless the region constraint resolver of
+    /// the table (if any) decides it then and there.
+    fn push_constraint(&mut self, constraint: Constraint<ChalkIr>) -> Fallible<()> {
+        let constraint = InEnvironment::new(self.environment, constraint);
+        let resolution = match &self.table.region_constraint_resolver {
+            Some(resolver) => resolver(&constraint),
+            None => RegionConstraintResolution::Deferred,
+        };
+        debug!("push_constraint: {:?} is {:?}", constraint, resolution);
+        match resolution {
+            RegionConstraintResolution::Holds => Ok(()),
+            RegionConstraintResolution::Fails => {
+                self.fail(UnificationError::RegionConstraint(constraint))
+            }
+            RegionConstraintResolution::Deferred => {
+                self.constraints.push(constraint);
+                Ok(())
+            }
+        }
+    }
+
+    /// Fails with `error`, unless a failure further in was recorded
+    /// already.
+    fn fail<T>(&mut self, error: UnificationError) -> Fallible<T> {
+        debug!("unification failed: {:?}", error);
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+        Err(NoSolution)
     }
 }
 
@@ -381,11 +762,27 @@ impl<'t> Zipper<ChalkIr> for Unifier<'t> {
         self.unify_lifetime_lifetime(a, b)
     }
 
-    fn zip_binders<T>(&mut self, _: &Binders<T>, _: &Binders<T>) -> Fallible<()>
+    fn zip_binders<T>(&mut self, a: &Binders<T>, b: &Binders<T>) -> Fallible<()>
     where
         T: Zip<ChalkIr> + Fold<ChalkIr, Result = T>,
     {
-        panic!("cannot unify things with binders (other than types)")
+        // This arises when unifying `dyn` and `impl` types, whose
+        // bounds are bound over the hidden self type. As in
+        // `unify_forall_tys`, we require `for<A..> exists<B..> a == b`,
+        // but here we check the converse too.
+        debug!("zip_binders({:?}, {:?})", a, b);
+
+        {
+            let a_universal = self.table.instantiate_binders_universally(a);
+            let b_existential = self.table.instantiate_binders_existentially(b);
+            Zip::zip_with(self, &a_universal, &b_existential)?;
+        }
+
+        {
+            let b_universal = self.table.instantiate_binders_universally(b);
+            let a_existential = self.table.instantiate_binders_existentially(a);
+            Zip::zip_with(self, &a_existential, &b_universal)
+        }
     }
 }
 
@@ -393,6 +790,10 @@ struct OccursCheck<'u, 't: 'u> {
     unifier: &'u mut Unifier<'t>,
     var: EnaVariable,
     universe_index: UniverseIndex,
+
+    /// True if the check failed because the type contains `var`, whose
+    /// error the caller makes (see `Unifier::occurs_check_error`).
+    cycle: bool,
 }
 
 impl<'u, 't> OccursCheck<'u, 't> {
@@ -401,6 +802,7 @@ impl<'u, 't> OccursCheck<'u, 't> {
             unifier,
             var,
             universe_index,
+            cycle: false,
         }
     }
 }
@@ -414,7 +816,10 @@ impl<'u, 't> PlaceholderFolder<ChalkIr> for OccursCheck<'u, 't> {
         _binders: usize,
     ) -> Fallible<Ty<ChalkIr>> {
         if self.universe_index < universe.ui {
-            Err(NoSolution)
+            self.unifier.fail(UnificationError::UniverseError {
+                placeholder: universe,
+                universe: self.universe_index,
+            })
         } else {
             Ok(universe.to_ty::<ChalkIr>()) // no need to shift, not relative to depth
         }
@@ -440,8 +845,10 @@ impl<'u, 't> PlaceholderFolder<ChalkIr> for OccursCheck<'u, 't> {
             // exists<'x> forall<'b> ?T = Foo<'x>, where 'x = 'b
 
             let tick_x = self.unifier.table.new_variable(self.universe_index);
-            self.unifier
-                .push_lifetime_eq_constraint(tick_x.to_lifetime(), ui.to_lifetime::<ChalkIr>());
+            self.unifier.push_constraint(Constraint::LifetimeEq(
+                tick_x.to_lifetime(),
+                ui.to_lifetime::<ChalkIr>(),
+            ))?;
             Ok(tick_x.to_lifetime())
         } else {
             // If the `ui` is higher than `self.universe_index`, then we can name
@@ -468,6 +875,7 @@ impl<'u, 't> InferenceFolder<ChalkIr> for OccursCheck<'u, 't> {
             // become the value of).
             InferenceValue::Unbound(ui) => {
                 if self.unifier.table.unify.unioned(var, self.var) {
+                    self.cycle = true;
                     return Err(NoSolution);
                 }
 
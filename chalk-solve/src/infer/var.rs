@@ -43,6 +43,12 @@ impl From<InferenceVar> for EnaVariable {
     }
 }
 
+impl From<EnaVariable> for InferenceVar {
+    fn from(var: EnaVariable) -> InferenceVar {
+        var.0
+    }
+}
+
 impl EnaVariable {
     /// Convert this inference variable into a type. When using this
     /// method, naturally you should know from context that the kind
@@ -0,0 +1,243 @@
+//! Solving goals that refer to the inference variables of the
+//! embedder. `Solver::solve` takes goals in canonical form, which
+//! leaves it to the embedder to canonicalize its goals and to map the
+//! solutions back onto its own variables. Instead, the embedder can
+//! implement `InferenceSource` for its inference table and hand the
+//! goal to `Solver::solve_with_inference_source` as it is: chalk
+//! canonicalizes it on the fly, and expresses the solution in terms of
+//! the embedder's variables.
+
+use crate::infer::ucanonicalize::UniverseMap;
+use crate::infer::{InferenceTable, ParameterEnaVariableExt};
+use crate::solve::{Guidance, Solution, Solver};
+use crate::RustIrDatabase;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::fold::{
+    DefaultFreeVarFolder, DefaultPlaceholderFolder, DefaultTypeFolder, Fold, InferenceFolder, Subst,
+};
+use chalk_ir::*;
+
+/// The inference table of the embedder, as seen by chalk. The
+/// inference variables in goals given to
+/// `Solver::solve_with_inference_source` are those of this table.
+pub trait InferenceSource {
+    /// The value of `var`, if it has one. The value may itself refer
+    /// to other inference variables.
+    fn probe_var(&self, var: InferenceVar) -> Option<Parameter<ChalkIr>>;
+
+    /// The universe of `var`, which has no value.
+    fn universe_of(&self, var: InferenceVar) -> UniverseIndex;
+
+    /// Creates a new inference variable, of the given kind and in the
+    /// given universe. Chalk uses these for the parts of a solution
+    /// that the goal leaves unconstrained.
+    fn new_variable(&mut self, kind: ParameterKind<UniverseIndex>) -> InferenceVar;
+
+    /// Unifies `var` with `value` (see `InferenceSubst::apply`).
+    fn unify(
+        &mut self,
+        var: ParameterKind<InferenceVar>,
+        value: &Parameter<ChalkIr>,
+    ) -> Fallible<()>;
+}
+
+/// A solution to a goal given to `Solver::solve_with_inference_source`;
+/// the counterpart of `Solution`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InferenceSolution {
+    /// The goal holds, for the values in the substitution (and provided
+    /// that its lifetime constraints hold).
+    Unique(InferenceSubst),
+
+    /// The goal may hold; see `Solution::Ambig`.
+    Ambig(InferenceGuidance),
+}
+
+/// The counterpart of `Guidance` for `InferenceSolution`; the
+/// substitutions have no lifetime constraints.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InferenceGuidance {
+    Definite(InferenceSubst),
+    Suggested(InferenceSubst),
+    Unknown,
+}
+
+/// Values for the inference variables of a goal, in terms of the
+/// variables of the `InferenceSource`. The values may refer to new
+/// variables created by `InferenceSource::new_variable`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InferenceSubst {
+    /// The value of each variable that had no value when the goal was
+    /// solved, in order of appearance in the goal.
+    pub values: Vec<(ParameterKind<InferenceVar>, Parameter<ChalkIr>)>,
+
+    pub constraints: Vec<InEnvironment<Constraint<ChalkIr>>>,
+}
+
+impl InferenceSubst {
+    /// Unifies each variable with its value, in `source`.
+    pub fn apply(&self, source: &mut dyn InferenceSource) -> Fallible<()> {
+        for (var, value) in &self.values {
+            source.unify(*var, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Solver {
+    /// Attempts to solve `goal`, whose inference variables are those
+    /// of `source` (see the module docs). The solution is computed as
+    /// by `solve` and translated back; nothing is unified in `source`
+    /// until `InferenceSubst::apply` is invoked, so the embedder can
+    /// choose whether to act on (e.g.) suggested guidance.
+    pub fn solve_with_inference_source(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        source: &mut dyn InferenceSource,
+        goal: &InEnvironment<Goal<ChalkIr>>,
+    ) -> Option<InferenceSolution> {
+        // Replace the variables of `source` with variables of our own,
+        // resolving those that have values along the way, and then
+        // canonicalize as usual.
+        let mut infer = InferenceTable::new();
+        let mut importer = Importer {
+            source: &*source,
+            infer: &mut infer,
+            vars: vec![],
+        };
+        let goal = goal.fold_with(&mut importer, 0).unwrap();
+        let imported_vars = importer.vars;
+        let canonicalized = infer.canonicalize(&goal);
+        let u_canonicalized = infer.u_canonicalize(&canonicalized.quantified);
+
+        // The variable of `source` for each canonical variable.
+        let vars: Vec<_> = canonicalized
+            .free_vars
+            .into_iter()
+            .map(|var| {
+                let param = var.to_parameter();
+                imported_vars
+                    .iter()
+                    .find(|(_, imported)| *imported == param)
+                    .map(|&(var, _)| var)
+                    .unwrap()
+            })
+            .collect();
+
+        let solution = self.solve(program, &u_canonicalized.quantified)?;
+        let universes = &u_canonicalized.universes;
+        let mut export_subst = |subst: &Canonical<ConstrainedSubst<ChalkIr>>| {
+            let ConstrainedSubst { subst, constraints } = export(source, universes, subst);
+            InferenceSubst {
+                values: vars.iter().cloned().zip(subst.parameters).collect(),
+                constraints,
+            }
+        };
+        let unconstrained = |subst: &Canonical<Substitution<ChalkIr>>| Canonical {
+            binders: subst.binders.clone(),
+            value: ConstrainedSubst {
+                subst: subst.value.clone(),
+                constraints: vec![],
+            },
+        };
+        Some(match solution {
+            Solution::Unique(subst) => InferenceSolution::Unique(export_subst(&subst)),
+            Solution::Ambig(Guidance::Definite(subst)) => InferenceSolution::Ambig(
+                InferenceGuidance::Definite(export_subst(&unconstrained(&subst))),
+            ),
+            Solution::Ambig(Guidance::Suggested(subst)) => InferenceSolution::Ambig(
+                InferenceGuidance::Suggested(export_subst(&unconstrained(&subst))),
+            ),
+            Solution::Ambig(Guidance::Unknown) => {
+                InferenceSolution::Ambig(InferenceGuidance::Unknown)
+            }
+        })
+    }
+}
+
+/// Maps `canonical`, which is in terms of the canonical universes of
+/// a goal, back to the universes of `source`, replacing its bound
+/// variables with new variables of `source`.
+fn export(
+    source: &mut dyn InferenceSource,
+    universes: &UniverseMap,
+    canonical: &Canonical<ConstrainedSubst<ChalkIr>>,
+) -> ConstrainedSubst<ChalkIr> {
+    let parameters: Vec<Parameter<ChalkIr>> = canonical
+        .binders
+        .iter()
+        .map(|kind| {
+            let kind = kind.map(|ui| universes.map_universe_from_canonical(ui));
+            let var = source.new_variable(kind);
+            match kind {
+                ParameterKind::Ty(_) => var.to_ty::<ChalkIr>().cast(),
+                ParameterKind::Lifetime(_) => var.to_lifetime::<ChalkIr>().cast(),
+            }
+        })
+        .collect();
+    let value = universes.map_from_canonical(&canonical.value);
+    Subst::apply(&parameters, &value)
+}
+
+/// Replaces the inference variables of `source` with their values,
+/// or, for those that have none, with variables of `infer` (recorded
+/// in `vars`).
+struct Importer<'a> {
+    source: &'a dyn InferenceSource,
+    infer: &'a mut InferenceTable,
+    vars: Vec<(ParameterKind<InferenceVar>, Parameter<ChalkIr>)>,
+}
+
+impl Importer<'_> {
+    fn import_var(&mut self, var: ParameterKind<InferenceVar>) -> Parameter<ChalkIr> {
+        if let Some((_, imported)) = self.vars.iter().find(|(v, _)| *v == var) {
+            return imported.clone();
+        }
+        let universe = self.source.universe_of(*var.as_ref().into_inner());
+        let imported = var
+            .map(|_| self.infer.new_variable(universe))
+            .to_parameter();
+        self.vars.push((var, imported.clone()));
+        imported
+    }
+}
+
+impl DefaultTypeFolder for Importer<'_> {}
+
+impl InferenceFolder<ChalkIr> for Importer<'_> {
+    fn fold_inference_ty(&mut self, var: InferenceVar, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        match self.source.probe_var(var) {
+            Some(value) => Ok(value
+                .assert_ty_ref()
+                .fold_with(self, 0)?
+                .shifted_in(binders)),
+            None => Ok(self
+                .import_var(ParameterKind::Ty(var))
+                .assert_ty_ref()
+                .clone()),
+        }
+    }
+
+    fn fold_inference_lifetime(
+        &mut self,
+        var: InferenceVar,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        match self.source.probe_var(var) {
+            Some(value) => Ok(value
+                .assert_lifetime_ref()
+                .fold_with(self, 0)?
+                .shifted_in(binders)),
+            None => Ok(*self
+                .import_var(ParameterKind::Lifetime(var))
+                .assert_lifetime_ref()),
+        }
+    }
+}
+
+impl DefaultFreeVarFolder for Importer<'_> {}
+
+impl DefaultPlaceholderFolder for Importer<'_> {}
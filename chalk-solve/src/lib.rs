@@ -1,19 +1,33 @@
+use crate::coherence::SpecializationPriority;
 use chalk_ir::family::ChalkIr;
 use chalk_ir::*;
 use chalk_rust_ir::*;
+use std::fmt;
 use std::fmt::Debug;
 use std::sync::Arc;
 
 #[macro_use]
 extern crate chalk_macros;
 
+pub mod api;
+pub mod check;
+pub mod clause_validation;
 pub mod clauses;
 pub mod coherence;
 mod coinductive_goal;
+pub mod conformance;
+pub mod display;
 pub mod ext;
+pub mod frozen;
 mod infer;
+pub mod inference_source;
+pub mod object_safety;
+#[cfg(feature = "persist")]
+pub mod persist;
+pub mod salsa_bridge;
 mod solve;
 pub mod split;
+pub mod variance;
 pub mod wf;
 
 pub trait RustIrDatabase: Debug {
@@ -36,6 +50,39 @@ pub trait RustIrDatabase: Debug {
     /// Returns the `AssociatedTyValue` with the given id.
     fn associated_ty_value(&self, id: AssociatedTyValueId) -> Arc<AssociatedTyValue>;
 
+    /// Fallible variant of `associated_ty_data`. The default
+    /// implementation defers to the panicking accessor; databases
+    /// that may not have every item available (e.g., because they
+    /// load items lazily from crate metadata) should override this
+    /// (and the other `try_` methods) to report `MissingData`
+    /// instead.
+    fn try_associated_ty_data(&self, ty: TypeId) -> Result<Arc<AssociatedTyDatum>, MissingData> {
+        Ok(self.associated_ty_data(ty))
+    }
+
+    /// Fallible variant of `trait_datum`.
+    fn try_trait_datum(&self, trait_id: TraitId) -> Result<Arc<TraitDatum>, MissingData> {
+        Ok(self.trait_datum(trait_id))
+    }
+
+    /// Fallible variant of `struct_datum`.
+    fn try_struct_datum(&self, struct_id: StructId) -> Result<Arc<StructDatum>, MissingData> {
+        Ok(self.struct_datum(struct_id))
+    }
+
+    /// Fallible variant of `impl_datum`.
+    fn try_impl_datum(&self, impl_id: ImplId) -> Result<Arc<ImplDatum>, MissingData> {
+        Ok(self.impl_datum(impl_id))
+    }
+
+    /// Fallible variant of `associated_ty_value`.
+    fn try_associated_ty_value(
+        &self,
+        id: AssociatedTyValueId,
+    ) -> Result<Arc<AssociatedTyValue>, MissingData> {
+        Ok(self.associated_ty_value(id))
+    }
+
     /// Returns a list of potentially relevant impls for a given
     /// trait-id; we also supply the type parameters that we are
     /// trying to match (if known: these parameters may contain
@@ -63,12 +110,267 @@ pub trait RustIrDatabase: Debug {
     /// user gave).
     fn impl_provided_for(&self, auto_trait_id: TraitId, struct_id: StructId) -> bool;
 
+    /// Returns the struct or trait that was declared to be the given
+    /// lang item, if any. Lang items are types and traits that the
+    /// solver has built-in rules for (e.g., `Box<T>`).
+    fn lang_item(&self, item: LangItem) -> Option<TypeKindId>;
+
+    /// Returns the table of well-known traits, such as the operator
+    /// traits. The solver itself has no built-in rules for these; they
+    /// are used by helpers like `ext::binary_op_goal`.
+    fn well_known_items(&self) -> Arc<WellKnownItems>;
+
     /// Returns the name for the type with the given id.
     fn type_name(&self, id: TypeKindId) -> Identifier;
+
+    /// Decides whether the embedder-defined goal `goal` holds. The
+    /// goal contains no inference variables (goals that would are
+    /// treated as floundered), but may contain placeholders. The
+    /// default implementation knows of no custom goals, and so
+    /// returns `CustomGoalSolution::NoSolution`.
+    fn solve_custom_goal(&self, goal: &CustomGoal<ChalkIr>) -> CustomGoalSolution {
+        debug!("solve_custom_goal({:?}): no solution", goal);
+        CustomGoalSolution::NoSolution
+    }
+
+    /// Returns true if the embedder supports upcasting trait objects of
+    /// the trait `source` to trait objects of `target`, one of its
+    /// supertraits, e.g. because the vtable of `source` embeds one for
+    /// `target`. Used by `Upcast` goals, which check the bounds of the
+    /// two types themselves. The default allows every upcast.
+    fn upcast_vtable_compatible(&self, source: TraitId, target: TraitId) -> bool {
+        debug!(
+            "upcast_vtable_compatible({:?}, {:?}): allowed",
+            source, target
+        );
+        true
+    }
+
+    /// Picks the impl to proceed with, among `candidates`, for a goal
+    /// that is ambiguous between them. Only consulted by tooling that
+    /// opts in, through `Solver::solve_preferring_impls`. The default
+    /// prefers none of them.
+    fn prefer_impl(&self, candidates: &[ImplId]) -> Option<ImplId> {
+        debug!("prefer_impl({:?}): none", candidates);
+        None
+    }
+
+    /// Called when one of the impls that `impls_for_trait` returned
+    /// for a goal has a trait ref whose parameters are not of the
+    /// kinds of those of the goal (e.g., a type where the goal has a
+    /// lifetime), which means that the database is inconsistent. The
+    /// solver skips the impl; this gives the embedder a chance to
+    /// report it. It may be called more than once for the same impl.
+    /// The default only logs it.
+    fn ill_kinded_impl(&self, impl_id: ImplId, error: IllKindedImpl) {
+        debug!("ill_kinded_impl({:?}): {}", impl_id, error);
+    }
+
+    /// Returns the callback with which the solver decides region
+    /// constraints as soon as unification gives rise to them, using
+    /// the embedder's region inference state, rather than returning
+    /// them with its answers. Constraints that the callback finds
+    /// cannot hold rule out the clause or answer that would require
+    /// them, so fewer candidates remain. The constraints are in terms
+    /// of the solver's own placeholders and inference variables.
+    ///
+    /// Answers are cached, so the callback must decide a constraint
+    /// the same way each time. The default registers no callback,
+    /// and so every constraint is returned.
+    fn region_constraint_resolver(&self) -> Option<Arc<RegionConstraintResolver>> {
+        None
+    }
+
+    /// Returns the specialization priority of the impl `impl_id` among
+    /// the impls of its trait (see
+    /// `CoherenceSolver::specialization_priorities`), if known. Used in
+    /// `SolveMode::Revealing` to pick the most specialized of the impls
+    /// that apply. The default knows of none, so that every impl that
+    /// applies is used.
+    fn impl_specialization_priority(&self, impl_id: ImplId) -> Option<SpecializationPriority> {
+        debug!("impl_specialization_priority({:?}): unknown", impl_id);
+        None
+    }
+
+    /// Returns the inherent impls declared in this crate, which
+    /// `InherentImplChecker` checks for coherence. The default returns
+    /// none, for databases that leave that check to someone else.
+    fn local_inherent_impls(&self) -> Vec<Arc<InherentImplDatum>> {
+        vec![]
+    }
+
+    /// Experimental: returns the `LayoutCompatible` facts that cannot
+    /// be derived from the structure of the types, such as
+    /// `LayoutCompatible(u32, i32)`. Each fact is also used with its
+    /// types swapped. The types must not contain free variables.
+    #[cfg(feature = "layout")]
+    fn layout_facts(&self) -> Vec<LayoutCompatible<ChalkIr>> {
+        vec![]
+    }
+}
+
+/// A `RustIrDatabase` that can be shared between threads, as the
+/// parallel APIs (e.g., `CoherenceSolver::check_trait_coherence_parallel`)
+/// require. Implemented for every database that is `Sync`.
+///
+/// The data that a database hands out (`Arc<TraitDatum>` and so on)
+/// and the solutions that a `Solver` returns are always `Send` and
+/// `Sync`, and a `Solver` is `Send`, so that each worker thread can
+/// own a solver of its own. (A single `Solver` cannot be shared, as
+/// solving requires `&mut self`.)
+pub trait SyncDatabase: RustIrDatabase + Sync {}
+
+impl<DB: RustIrDatabase + Sync + ?Sized> SyncDatabase for DB {}
+
+/// Checks, at compile time, the thread-safety guarantees described
+/// on `SyncDatabase`.
+#[allow(dead_code)]
+fn assert_thread_safety() {
+    fn send_sync<T: Send + Sync>() {}
+    fn send<T: Send>() {}
+
+    send_sync::<Arc<TraitDatum>>();
+    send_sync::<Arc<StructDatum>>();
+    send_sync::<Arc<ImplDatum>>();
+    send_sync::<Arc<AssociatedTyDatum>>();
+    send_sync::<Arc<AssociatedTyValue>>();
+    send_sync::<Arc<InherentImplDatum>>();
+    send_sync::<Arc<WellKnownItems>>();
+    send_sync::<ProgramClause<ChalkIr>>();
+    send_sync::<UCanonical<InEnvironment<Goal<ChalkIr>>>>();
+    send_sync::<Canonical<ConstrainedSubst<ChalkIr>>>();
+    send_sync::<Solution>();
+    send_sync::<MissingData>();
+    send_sync::<SolverChoice>();
+    send_sync::<CancellationToken>();
+    send_sync::<StackWatch<UCanonical<InEnvironment<Goal<ChalkIr>>>>>();
+    send::<Solver>();
+}
+
+/// The answer to a `DomainGoal::Custom` goal, as given by
+/// `RustIrDatabase::solve_custom_goal`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CustomGoalSolution {
+    /// The goal holds, provided that each of the given obligations
+    /// (if any) holds as well. The obligations are solved as ordinary
+    /// subgoals.
+    Holds(Vec<Goal<ChalkIr>>),
+
+    /// The goal does not hold.
+    NoSolution,
+
+    /// The embedder cannot decide whether the goal holds.
+    Ambiguous,
+}
+
+/// The callback of `RustIrDatabase::region_constraint_resolver`,
+/// given a region constraint as unification gives rise to it.
+pub type RegionConstraintResolver =
+    dyn Fn(&InEnvironment<Constraint<ChalkIr>>) -> RegionConstraintResolution + Send + Sync;
+
+/// What the solver does with a region constraint, as decided by a
+/// `RegionConstraintResolver`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegionConstraintResolution {
+    /// The constraint holds, and is dropped.
+    Holds,
+
+    /// The constraint cannot hold, and so unification fails.
+    Fails,
+
+    /// The constraint is kept, and returned with the answer (as
+    /// happens without a callback).
+    Deferred,
+}
+
+/// An item that a `RustIrDatabase` was asked for but could not
+/// provide. Returned by the `try_` accessors, and surfaced by
+/// `Solver::try_solve` when clause generation needs such an item.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MissingData {
+    AssociatedTy(TypeId),
+    Trait(TraitId),
+    Struct(StructId),
+    Impl(ImplId),
+    AssociatedTyValue(AssociatedTyValueId),
+}
+
+impl fmt::Display for MissingData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MissingData::AssociatedTy(id) => write!(f, "missing data for associated type {:?}", id),
+            MissingData::Trait(id) => write!(f, "missing data for trait {:?}", id),
+            MissingData::Struct(id) => write!(f, "missing data for struct {:?}", id),
+            MissingData::Impl(id) => write!(f, "missing data for impl {:?}", id),
+            MissingData::AssociatedTyValue(id) => {
+                write!(f, "missing data for associated type value {:?}", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MissingData {}
+
+/// How the trait ref of an impl that `RustIrDatabase::impls_for_trait`
+/// returned fails to match the kinds of the parameters of the goal
+/// (see `RustIrDatabase::ill_kinded_impl`).
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IllKindedImpl {
+    /// The trait ref has `found` parameters, the goal `expected`.
+    ParameterCount { expected: usize, found: usize },
+
+    /// The parameter at `index` is a type in one of them and a
+    /// lifetime in the other.
+    ParameterKind { index: usize },
+}
+
+impl fmt::Display for IllKindedImpl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IllKindedImpl::ParameterCount { expected, found } => write!(
+                f,
+                "impl has {} trait parameters, expected {}",
+                found, expected
+            ),
+            IllKindedImpl::ParameterKind { index } => {
+                write!(f, "trait parameter {} of impl has the wrong kind", index)
+            }
+        }
+    }
 }
 
+#[cfg(feature = "parallel")]
+pub use check::check_program_parallel;
+pub use check::{check_program, ProgramItems, ProgramReport};
+pub use coinductive_goal::{CoinductiveGoals, GoalKind, IsCoinductive};
+pub use infer::unify::UnificationError;
+pub use solve::AmbiguityCause;
+pub use solve::CancellationToken;
+pub use solve::Cancelled;
+pub use solve::Candidate;
+pub use solve::CannotProvePolicy;
+pub use solve::ClauseFilter;
+pub use solve::ClauseFilterFn;
+pub use solve::FolderPass;
+pub use solve::GoalPass;
+pub use solve::GoalPipeline;
 pub use solve::Guidance;
+pub use solve::ImplConfidence;
+pub use solve::ImplLocality;
+pub use solve::ImplSuggestion;
+pub use solve::MemoryReport;
+pub use solve::Overflow;
+pub use solve::OverflowAction;
+pub use solve::OverflowHandler;
+pub use solve::Prenex;
+pub use solve::ReentrantSolver;
+pub use solve::SlgOptions;
 pub use solve::Solution;
+pub use solve::SolveMode;
+pub use solve::SolveStats;
 pub use solve::Solver;
 pub use solve::SolverChoice;
+pub use solve::SolverSnapshot;
+pub use solve::StackWatch;
 pub use solve::TestSolver;
+pub use solve::ToolingSolution;
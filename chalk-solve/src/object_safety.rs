@@ -0,0 +1,135 @@
+//! Object safety: whether `dyn Trait` is a type. A trait is object
+//! safe if each of its methods could be called through a vtable, and
+//! if its supertraits are object safe as well. Here, that rules out:
+//!
+//! - traits with the supertrait bound `Self: Sized`, since objects are
+//!   unsized;
+//! - methods with type parameters of their own (e.g., `fn foo<T>(Self,
+//!   T)`), since a vtable has one entry per method.
+//!
+//! As in Rust, methods with a `where Self: Sized` clause are exempt,
+//! since they cannot be called on objects anyway. Other uses of `Self`
+//! in method signatures or supertrait bounds are not checked (see the
+//! `dyn_ty` clauses for how the latter are treated).
+
+use std::fmt;
+
+use crate::RustIrDatabase;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::*;
+use rustc_hash::FxHashSet;
+
+/// A reason why a trait is not object safe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ObjectSafetyViolation {
+    /// The trait, which may be a supertrait of the one checked, has
+    /// the bound `Self: Sized`.
+    SizedSelf { trait_name: Identifier },
+
+    /// A method of the trait, which may be a supertrait of the one
+    /// checked, has type parameters.
+    GenericMethod {
+        trait_name: Identifier,
+        method_name: Identifier,
+    },
+}
+
+impl fmt::Display for ObjectSafetyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectSafetyViolation::SizedSelf { trait_name } => {
+                write!(f, "trait {:?} requires `Self: Sized`", trait_name)
+            }
+            ObjectSafetyViolation::GenericMethod {
+                trait_name,
+                method_name,
+            } => write!(
+                f,
+                "method {:?} of trait {:?} has type parameters",
+                method_name, trait_name
+            ),
+        }
+    }
+}
+
+/// Returns true if `dyn Trait` is a type, for the trait `trait_id`.
+pub fn is_object_safe(db: &dyn RustIrDatabase, trait_id: TraitId) -> bool {
+    object_safety_violations(db, trait_id).is_empty()
+}
+
+/// Returns the reasons why the trait `trait_id` is not object safe,
+/// including those of its supertraits, or nothing if it is.
+pub fn object_safety_violations(
+    db: &dyn RustIrDatabase,
+    trait_id: TraitId,
+) -> Vec<ObjectSafetyViolation> {
+    let mut violations = vec![];
+    let mut visited = FxHashSet::default();
+    let mut pending = vec![trait_id];
+
+    while let Some(trait_id) = pending.pop() {
+        if !visited.insert(trait_id) {
+            continue;
+        }
+
+        let trait_datum = db.trait_datum(trait_id);
+        let trait_name = db.type_name(trait_id.into());
+        let where_clauses = &trait_datum.binders.value.where_clauses;
+
+        if where_clauses.iter().any(|wc| is_sized_self(db, wc, 0)) {
+            violations.push(ObjectSafetyViolation::SizedSelf { trait_name });
+        }
+
+        for method in &trait_datum.methods {
+            let own_parameters = method.own_parameters(trait_datum.binders.len());
+            let is_generic = own_parameters.contains(&ParameterKind::Ty(()));
+            let requires_sized = method
+                .binders
+                .value
+                .where_clauses
+                .iter()
+                .any(|wc| is_sized_self(db, wc, own_parameters.len()));
+            if is_generic && !requires_sized {
+                violations.push(ObjectSafetyViolation::GenericMethod {
+                    trait_name,
+                    method_name: method.name,
+                });
+            }
+        }
+
+        pending.extend(where_clauses.iter().filter_map(|wc| match &wc.value {
+            WhereClause::Implemented(trait_ref) if is_self(&trait_ref.parameters[0], wc, 0) => {
+                Some(trait_ref.trait_id)
+            }
+            _ => None,
+        }));
+    }
+
+    violations
+}
+
+/// True for the where clause `Self: Sized`, where `Self` is the bound
+/// variable `self_index` outside of the where clause's own binders.
+fn is_sized_self(
+    db: &dyn RustIrDatabase,
+    where_clause: &QuantifiedWhereClause<ChalkIr>,
+    self_index: usize,
+) -> bool {
+    match &where_clause.value {
+        WhereClause::Implemented(trait_ref) => {
+            db.lang_item(LangItem::Sized) == Some(trait_ref.trait_id.into())
+                && is_self(&trait_ref.parameters[0], where_clause, self_index)
+        }
+        WhereClause::ProjectionEq(_) => false,
+    }
+}
+
+fn is_self(
+    parameter: &Parameter<ChalkIr>,
+    where_clause: &QuantifiedWhereClause<ChalkIr>,
+    self_index: usize,
+) -> bool {
+    *parameter == Ty::BoundVar(where_clause.binders.len() + self_index).cast()
+}
@@ -0,0 +1,267 @@
+//! Persisting solutions between processes. A `PersistentCache` maps
+//! the cache keys of goals (see `Solver::cache_key`) to their
+//! solutions, and can be written out as bytes and loaded again later,
+//! e.g. by the next run of a build.
+//!
+//! The solutions refer to items by the ids assigned to them by the
+//! program, so a cache is only valid for the program it was computed
+//! against. The user therefore supplies a *revision* of the program
+//! when saving a cache, and the same revision must be given when
+//! loading it; a cache for any other revision is rejected rather than
+//! yielding solutions for the wrong program. The bytes also record the
+//! version of the format, and a checksum, so that caches written by
+//! other versions of chalk and corrupted caches are rejected too.
+//!
+//! The solutions are stored as an rkyv archive, which is validated
+//! once when the cache is loaded and then read in place: an
+//! `ArchivedCache` borrows the bytes, and looking up a key only
+//! deserializes the solution that is found, if any.
+
+use crate::solve::{Solution, Solver};
+use crate::RustIrDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::stable_hash::StableHasher;
+use chalk_ir::*;
+use rkyv::collections::ArchivedBTreeMap;
+use rkyv::de::deserializers::SharedDeserializeMap;
+use rkyv::{AlignedVec, Archived, Deserialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use crate::solve::{ArchivedGuidance, ArchivedSolution};
+
+/// The bytes with which every persisted cache starts.
+const MAGIC: &[u8; 8] = b"chalkslv";
+
+/// The version of the format; bumped whenever the encoding of
+/// solutions (or the meaning of cache keys) changes.
+pub const FORMAT_VERSION: u64 = 3;
+
+/// The length of the header: the magic bytes, the version, the
+/// revision and the checksum. The archive follows it, and so is as
+/// aligned as the bytes are.
+const HEADER_LEN: usize = 32;
+
+/// The alignment that the bytes of a cache must have to be loaded,
+/// which is that of an `AlignedVec`.
+pub const ALIGNMENT: usize = AlignedVec::ALIGNMENT;
+
+/// Solutions keyed by `Solver::cache_key`, which may be persisted
+/// (see the module docs).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PersistentCache {
+    solutions: BTreeMap<u64, Solution>,
+}
+
+/// A persisted cache, read in place from the bytes that it was loaded
+/// from (see the module docs).
+pub struct ArchivedCache<'a> {
+    solutions: &'a ArchivedBTreeMap<Archived<u64>, ArchivedSolution>,
+}
+
+/// Why a persisted cache could not be loaded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PersistError {
+    /// The bytes are not a persisted cache at all.
+    NotACache,
+
+    /// The cache was written in another version of the format.
+    UnsupportedVersion(u64),
+
+    /// The checksum does not match the contents of the cache.
+    ChecksumMismatch,
+
+    /// The cache was computed against another revision of the
+    /// program.
+    StaleRevision { expected: u64, found: u64 },
+
+    /// The bytes are not aligned to `ALIGNMENT`, so the archive cannot
+    /// be read in place.
+    Unaligned,
+
+    /// The checksum matches, but the archive does not validate.
+    Malformed,
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::NotACache => write!(f, "not a persisted solution cache"),
+            PersistError::UnsupportedVersion(version) => {
+                write!(f, "unsupported cache format version {}", version)
+            }
+            PersistError::ChecksumMismatch => write!(f, "cache checksum mismatch"),
+            PersistError::StaleRevision { expected, found } => write!(
+                f,
+                "cache is for program revision {}, not {}",
+                found, expected
+            ),
+            PersistError::Unaligned => {
+                write!(f, "cache bytes are not aligned to {} bytes", ALIGNMENT)
+            }
+            PersistError::Malformed => write!(f, "malformed cache contents"),
+        }
+    }
+}
+
+impl std::error::Error for PersistError {}
+
+impl PersistentCache {
+    pub fn new() -> Self {
+        PersistentCache::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.solutions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solutions.is_empty()
+    }
+
+    pub fn get(&self, key: u64) -> Option<&Solution> {
+        self.solutions.get(&key)
+    }
+
+    pub fn insert(&mut self, key: u64, solution: Solution) {
+        self.solutions.insert(key, solution);
+    }
+
+    /// Returns the cached solution for `goal`, if there is one, and
+    /// otherwise solves `goal` with `solver` and caches the solution.
+    /// Goals without solutions are not cached.
+    pub fn solve(
+        &mut self,
+        solver: &mut Solver,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<Solution> {
+        let key = solver.cache_key(goal);
+        if let Some(solution) = self.get(key) {
+            return Some(solution.clone());
+        }
+        let solution = solver.solve(program, goal)?;
+        self.insert(key, solution.clone());
+        Some(solution)
+    }
+
+    /// Writes the cache out, for the program at revision `revision`.
+    /// The bytes are aligned, so that they can be loaded in place with
+    /// `ArchivedCache::from_bytes`.
+    pub fn to_bytes(&self, revision: u64) -> AlignedVec {
+        let archive = rkyv::to_bytes::<_, 4096>(&self.solutions)
+            .expect("archiving solutions into memory cannot fail");
+        let mut bytes = AlignedVec::with_capacity(HEADER_LEN + archive.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&revision.to_le_bytes());
+        bytes.extend_from_slice(&checksum(revision, &archive).to_le_bytes());
+        bytes.extend_from_slice(&archive);
+        bytes
+    }
+}
+
+impl<'a> ArchivedCache<'a> {
+    /// Loads a cache written by `PersistentCache::to_bytes`, checking
+    /// that it was written for the program at revision `revision` and
+    /// validating the archive. The bytes must be aligned to
+    /// `ALIGNMENT`, as those of an `AlignedVec` are.
+    pub fn from_bytes(bytes: &'a [u8], revision: u64) -> Result<Self, PersistError> {
+        if !bytes.starts_with(MAGIC) {
+            return Err(PersistError::NotACache);
+        }
+        let version = read_u64(&bytes[8..]).ok_or(PersistError::Malformed)?;
+        if version != FORMAT_VERSION {
+            return Err(PersistError::UnsupportedVersion(version));
+        }
+
+        if bytes.len() < HEADER_LEN {
+            return Err(PersistError::ChecksumMismatch);
+        }
+        let found = read_u64(&bytes[16..]).unwrap();
+        let stored_checksum = read_u64(&bytes[24..]).unwrap();
+        let archive = &bytes[HEADER_LEN..];
+        if stored_checksum != checksum(found, archive) {
+            return Err(PersistError::ChecksumMismatch);
+        }
+        if found != revision {
+            return Err(PersistError::StaleRevision {
+                expected: revision,
+                found,
+            });
+        }
+
+        if !(archive.as_ptr() as usize).is_multiple_of(ALIGNMENT) {
+            return Err(PersistError::Unaligned);
+        }
+        let solutions = rkyv::check_archived_root::<BTreeMap<u64, Solution>>(archive)
+            .map_err(|_| PersistError::Malformed)?;
+        Ok(ArchivedCache { solutions })
+    }
+
+    pub fn len(&self) -> usize {
+        self.solutions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solutions.len() == 0
+    }
+
+    /// The archived solution for `key`, read in place.
+    pub fn get(&self, key: u64) -> Option<&'a ArchivedSolution> {
+        self.solutions.get(&Archived::<u64>::new(key))
+    }
+
+    /// The solution for `key`, deserialized from the archive.
+    pub fn solution(&self, key: u64) -> Option<Solution> {
+        self.get(key).map(deserialize)
+    }
+
+    /// Returns the persisted solution for `goal`, if there is one, and
+    /// otherwise solves `goal` with `solver`.
+    pub fn solve(
+        &self,
+        solver: &mut Solver,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<Solution> {
+        match self.solution(solver.cache_key(goal)) {
+            Some(solution) => Some(solution),
+            None => solver.solve(program, goal),
+        }
+    }
+
+    /// Deserializes every solution into a `PersistentCache`, e.g. to
+    /// add to the cache and write it out again.
+    pub fn to_cache(&self) -> PersistentCache {
+        let solutions = self
+            .solutions
+            .iter()
+            .map(|(key, solution)| (key.value(), deserialize(solution)))
+            .collect();
+        PersistentCache { solutions }
+    }
+}
+
+fn deserialize(solution: &ArchivedSolution) -> Solution {
+    solution
+        .deserialize(&mut SharedDeserializeMap::new())
+        .expect("validated solutions deserialize")
+}
+
+/// Reads the little-endian `u64` at the start of `bytes`, if there is
+/// one.
+fn read_u64(bytes: &[u8]) -> Option<u64> {
+    let mut n = [0; 8];
+    n.copy_from_slice(bytes.get(..8)?);
+    Some(u64::from_le_bytes(n))
+}
+
+/// The checksum of a cache, which covers the revision as well as the
+/// archive.
+fn checksum(revision: u64, archive: &[u8]) -> u64 {
+    let mut hasher = StableHasher::new();
+    hasher.write_bytes(&revision.to_le_bytes());
+    hasher.write_bytes(archive);
+    hasher.finish()
+}
@@ -0,0 +1,92 @@
+//! Helpers for embedding chalk in an incremental, salsa-style
+//! database, where every computation is a query that is memoized
+//! against a revision of the inputs and re-run when they change.
+//!
+//! # Implementing `RustIrDatabase`
+//!
+//! The datum accessors (`trait_datum`, `impl_datum` and so on) map
+//! naturally onto queries: make each of them a query keyed by the id
+//! that returns the `Arc`, and have the `RustIrDatabase` method call
+//! the query. The `Arc` is then shared between all the callers within
+//! a revision, and cloning it is cheap. Likewise, `impls_for_trait`
+//! should be a query keyed by the trait id (ignoring the parameters,
+//! which are only a hint) returning an `Arc<Vec<ImplId>>`.
+//!
+//! Chalk does not report which data a solution depended on, so the
+//! solutions themselves cannot be invalidated selectively: anything
+//! memoized across revisions must be keyed by the revision of the
+//! whole program, which is what the types here are for.
+//!
+//! # Solving goals
+//!
+//! There are two ways to expose solving as a query:
+//!
+//! - `solve` is a pure function of the program, the solver choice and
+//!   the goal, so it can be the body of a query keyed by `SolveKey`.
+//!   Every call starts from an empty solver, so nothing is shared
+//!   between goals.
+//! - A `SolverCache` keeps one solver per revision, so that goals
+//!   solved in the same revision share their tables. It is meant to
+//!   live in a query that is re-run in every revision (a "volatile"
+//!   query, in salsa's terms), or outside of the database altogether.
+
+use crate::solve::{Solution, Solver, SolverChoice};
+use crate::RustIrDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use std::sync::Mutex;
+
+/// The key of a query that solves a goal against a revision of the
+/// program (see `solve`).
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SolveKey {
+    pub revision: u64,
+    pub goal: UCanonical<InEnvironment<Goal<ChalkIr>>>,
+}
+
+/// Solves `goal` with a solver of its own, so that the solution only
+/// depends on the arguments (and the data of `program`).
+pub fn solve(
+    program: &dyn RustIrDatabase,
+    choice: SolverChoice,
+    goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+) -> Option<Solution> {
+    choice.into_solver().solve(program, goal)
+}
+
+/// A solver shared by the goals solved against one revision of the
+/// program, which is replaced by a fresh one as soon as a goal is
+/// solved against another revision.
+#[derive(Debug)]
+pub struct SolverCache {
+    choice: SolverChoice,
+    state: Mutex<(u64, Solver)>,
+}
+
+impl SolverCache {
+    pub fn new(choice: SolverChoice) -> Self {
+        SolverCache {
+            choice,
+            state: Mutex::new((0, choice.into_solver())),
+        }
+    }
+
+    /// Solves `key.goal` against the program at `key.revision`. The
+    /// tables of earlier revisions are discarded first, so a solution
+    /// never reflects data of another revision (provided that the
+    /// embedder bumps the revision whenever the program changes).
+    pub fn solve(&self, program: &dyn RustIrDatabase, key: &SolveKey) -> Option<Solution> {
+        let mut state = self.state.lock().unwrap();
+        let (revision, solver) = &mut *state;
+        if *revision != key.revision {
+            *revision = key.revision;
+            *solver = self.choice.into_solver();
+        }
+        solver.solve(program, &key.goal)
+    }
+
+    /// The revision that the current tables were computed against.
+    pub fn revision(&self) -> u64 {
+        self.state.lock().unwrap().0
+    }
+}
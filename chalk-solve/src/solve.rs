@@ -1,14 +1,42 @@
-use crate::solve::slg::SlgContext;
-use crate::RustIrDatabase;
-use chalk_engine::forest::Forest;
+use crate::clause_validation::validate_custom_clauses;
+use crate::clauses::ClauseOrigin;
+use crate::coinductive_goal::{CoinductiveGoals, IsCoinductive};
+use crate::solve::slg::{combine_solutions, SlgContext};
+use crate::{MissingData, RustIrDatabase};
+use chalk_engine::forest::{Forest, ForestSnapshot};
+use chalk_engine::profile::TableCost;
+pub use chalk_engine::watch::StackWatch;
+use chalk_ir::cast::Cast;
 use chalk_ir::family::ChalkIr;
+use chalk_ir::stable_hash::{StableHash, StableHasher};
 use chalk_ir::*;
+#[cfg(feature = "persist")]
+use rkyv::{Archive, Deserialize, Serialize};
 use std::fmt;
 
+mod candidate;
+mod empty_env;
+mod failures;
+mod identity;
+mod memory;
+mod normalizations;
+mod pipeline;
+mod prewarm;
+mod reentrant;
 mod slg;
+mod suggest;
 mod truncate;
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+pub use self::candidate::{Candidate, ToolingSolution};
+pub use self::memory::MemoryReport;
+pub use self::pipeline::{FolderPass, GoalPass, GoalPipeline, Prenex};
+pub use self::prewarm::{CancellationToken, Cancelled};
+pub use self::reentrant::{Overflow, OverflowAction, OverflowHandler, ReentrantSolver};
+pub use self::suggest::{ImplLocality, ImplSuggestion};
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 /// A (possible) solution for a proposed goal.
 pub enum Solution {
     /// The goal indeed holds, and there is a unique value for all existential
@@ -23,7 +51,9 @@ pub enum Solution {
     Ambig(Guidance),
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "persist", derive(Archive, Serialize, Deserialize))]
+#[cfg_attr(feature = "persist", archive(check_bytes))]
 /// When a goal holds ambiguously (e.g., because there are multiple possible
 /// solutions), we issue a set of *guidance* back to type inference.
 pub enum Guidance {
@@ -41,6 +71,23 @@ pub enum Guidance {
     Unknown,
 }
 
+/// Why a goal came out ambiguous, as returned by
+/// `Solver::solve_with_ambiguity_cause`. The guidance alone does not
+/// tell these apart, but rustc reports them differently: as "type
+/// annotations needed" (E0282) and as "cannot satisfy" with the
+/// candidates listed (E0283), respectively.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AmbiguityCause {
+    /// Not enough is known about the types involved to decide the
+    /// goal: e.g., the self type of a trait goal is still an inference
+    /// variable, or the goal overflowed.
+    InsufficientTypeInfo,
+
+    /// Several candidates (impls, where clauses, ...) apply, each
+    /// giving an answer of its own.
+    MultipleCandidates,
+}
+
 impl Solution {
     pub fn is_unique(&self) -> bool {
         match *self {
@@ -48,6 +95,78 @@ impl Solution {
             _ => false,
         }
     }
+
+    /// Combines `self` and `other`, two solutions to `root_goal` (e.g.,
+    /// from answers that an embedder enumerated itself), into one that
+    /// covers both, the way the solver combines the answers to a goal:
+    ///
+    /// - Equal solutions combine to themselves.
+    /// - Two `Unique` solutions that differ only in their lifetimes
+    ///   combine to a `Unique` one if `options` has
+    ///   `unique_modulo_lifetimes` set (with the constraints of both).
+    /// - Otherwise, the result is ambiguous, and its guidance
+    ///   generalizes the substitutions of both by anti-unification:
+    ///   e.g., `?0 := Vec<u32>` and `?0 := Vec<i32>` give the definite
+    ///   guidance `?0 := Vec<?X>`. The guidance is `Suggested` if
+    ///   either side's is, and `Unknown` if either side's is, or if
+    ///   nothing is left of the substitutions once generalized.
+    ///
+    /// The other options do not affect the result.
+    pub fn combine(
+        self,
+        other: Solution,
+        root_goal: &Canonical<InEnvironment<Goal<ChalkIr>>>,
+        options: SlgOptions,
+    ) -> Solution {
+        combine_solutions(root_goal, self, other, options.unique_modulo_lifetimes)
+    }
+
+    /// The universes that the substitution of the solution mentions
+    /// (see `Canonical::universes`), or `None` if it has no
+    /// substitution (ambiguous, without guidance). The universes are
+    /// those of the canonical goal the solution answers.
+    pub fn universes(&self) -> Option<CanonicalUniverses> {
+        match self {
+            Solution::Unique(constrained) => Some(constrained.universes()),
+            Solution::Ambig(Guidance::Definite(subst))
+            | Solution::Ambig(Guidance::Suggested(subst)) => Some(subst.universes()),
+            Solution::Ambig(Guidance::Unknown) => None,
+        }
+    }
+}
+
+/// Hashes the solution by its structure, like the goals it answers
+/// (see `chalk_ir::stable_hash`), so that solutions may be persisted
+/// alongside them.
+impl StableHash for Solution {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Solution::Unique(constrained) => {
+                hasher.write_usize(0);
+                constrained.stable_hash(hasher);
+            }
+            Solution::Ambig(guidance) => {
+                hasher.write_usize(1);
+                guidance.stable_hash(hasher);
+            }
+        }
+    }
+}
+
+impl StableHash for Guidance {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        match self {
+            Guidance::Definite(subst) => {
+                hasher.write_usize(0);
+                subst.stable_hash(hasher);
+            }
+            Guidance::Suggested(subst) => {
+                hasher.write_usize(1);
+                subst.stable_hash(hasher);
+            }
+            Guidance::Unknown => hasher.write_usize(2),
+        }
+    }
 }
 
 impl fmt::Display for Solution {
@@ -65,40 +184,664 @@ impl fmt::Display for Solution {
     }
 }
 
+/// A simplified answer to the question "does this type implement
+/// this trait?", as returned by `Solver::has_impl`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImplConfidence {
+    /// The trait is definitely implemented.
+    Yes,
+
+    /// The trait is definitely not implemented.
+    No,
+
+    /// We could not decide either way (e.g., because of overflow or
+    /// because several impls may apply). The guidance is whatever
+    /// the solver was able to offer.
+    Maybe(Guidance),
+}
+
+impl From<Option<Solution>> for ImplConfidence {
+    fn from(solution: Option<Solution>) -> Self {
+        match solution {
+            Some(Solution::Unique(_)) => ImplConfidence::Yes,
+            Some(Solution::Ambig(guidance)) => ImplConfidence::Maybe(guidance),
+            None => ImplConfidence::No,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
 pub enum SolverChoice {
     /// Run the SLG solver, producing a Solution.
-    SLG { max_size: usize },
+    ///
+    /// - `max_size` bounds the size of subgoals and answers; larger
+    ///   ones are truncated.
+    /// - `max_universes` bounds the number of universes a goal may
+    ///   create through nested `forall` binders. Once the limit is
+    ///   reached, the body of any further `forall` is given up on, so
+    ///   the goal comes out ambiguous (whatever the `cannot_prove`
+    ///   policy) rather than running away.
+    /// - `unique_modulo_lifetimes`, if set, makes a goal with several
+    ///   answers that differ only in their lifetimes come out as
+    ///   `Unique` rather than ambiguous (see
+    ///   `SlgOptions::unique_modulo_lifetimes`).
+    /// - `coinductive_goals` are the shapes of goals treated as
+    ///   coinductive.
+    /// - `solve_mode` says whether the solver answers ordinary,
+    ///   coherence or post-monomorphization queries (see `SolveMode`).
+    /// - `prune_well_formed`, if set, removes the `WellFormed`
+    ///   conditions of program clauses that their other conditions
+    ///   imply (see `SlgOptions::prune_well_formed`).
+    /// - `suggest_identity_impls`, if set, suggests the answer of an
+    ///   identity impl like `impl<T> Borrow<T> for T` for trait goals
+    ///   that are otherwise ambiguous without guidance (see
+    ///   `SlgOptions::suggest_identity_impls`).
+    /// - `max_failed_leaves` bounds the number of failing leaves of a
+    ///   goal that `Solver::solve_with_failures` reports (see
+    ///   `SlgOptions::max_failed_leaves`).
+    /// - `cannot_prove` says whether a `CannotProve` subgoal makes an
+    ///   answer ambiguous or is taken to hold (see `CannotProvePolicy`).
+    /// - `prefer_cached_subgoals`, if set, proves the conjuncts whose
+    ///   answers are already cached before the others (see
+    ///   `SlgOptions::prefer_cached_subgoals`).
+    /// - `clause_filter`, if set, is asked about each program clause
+    ///   that could prove a goal, and may drop it (see `ClauseFilter`).
+    /// - `goal_pipeline` is the passes that rewrite each goal before it
+    ///   is solved (see `GoalPipeline`).
+    SLG {
+        max_size: usize,
+        max_universes: usize,
+        unique_modulo_lifetimes: bool,
+        coinductive_goals: CoinductiveGoals,
+        solve_mode: SolveMode,
+        prune_well_formed: bool,
+        suggest_identity_impls: bool,
+        max_failed_leaves: usize,
+        cannot_prove: CannotProvePolicy,
+        prefer_cached_subgoals: bool,
+        clause_filter: Option<ClauseFilter>,
+        goal_pipeline: GoalPipeline,
+    },
 }
 
 impl SolverChoice {
-    /// Returns the default SLG parameters.
-    fn slg() -> Self {
-        SolverChoice::SLG { max_size: 10 }
+    /// Returns the SLG parameters with the given `max_size` and the
+    /// defaults for everything else.
+    pub fn slg(max_size: usize) -> Self {
+        SolverChoice::slg_with(SlgOptions::default().max_size(max_size))
     }
 
-    /// Creates a solver state.
-    pub fn into_solver(self) -> Solver {
+    /// Returns the SLG parameters given by `options`.
+    pub fn slg_with(options: SlgOptions) -> Self {
+        let SlgOptions {
+            max_size,
+            max_universes,
+            unique_modulo_lifetimes,
+            coinductive_goals,
+            solve_mode,
+            prune_well_formed,
+            suggest_identity_impls,
+            max_failed_leaves,
+            cannot_prove,
+            prefer_cached_subgoals,
+            clause_filter,
+            goal_pipeline,
+        } = options;
+        SolverChoice::SLG {
+            max_size,
+            max_universes,
+            unique_modulo_lifetimes,
+            coinductive_goals,
+            solve_mode,
+            prune_well_formed,
+            suggest_identity_impls,
+            max_failed_leaves,
+            cannot_prove,
+            prefer_cached_subgoals,
+            clause_filter,
+            goal_pipeline,
+        }
+    }
+
+    /// Returns the mode in which solvers created from this choice
+    /// answer queries.
+    pub fn solve_mode(self) -> SolveMode {
         match self {
-            SolverChoice::SLG { max_size } => Solver {
-                forest: Forest::new(SlgContext::new(max_size)),
+            SolverChoice::SLG { solve_mode, .. } => solve_mode,
+        }
+    }
+
+    /// Returns the same choice, but with the given mode. The
+    /// coherence checks use this to get an intercrate solver from
+    /// the solver choice of the program.
+    pub fn with_solve_mode(self, mode: SolveMode) -> Self {
+        match self {
+            SolverChoice::SLG {
+                max_size,
+                max_universes,
+                unique_modulo_lifetimes,
+                coinductive_goals,
+                prune_well_formed,
+                suggest_identity_impls,
+                max_failed_leaves,
+                cannot_prove,
+                prefer_cached_subgoals,
+                clause_filter,
+                goal_pipeline,
+                ..
+            } => SolverChoice::SLG {
+                max_size,
+                max_universes,
+                unique_modulo_lifetimes,
+                coinductive_goals,
+                solve_mode: mode,
+                prune_well_formed,
+                suggest_identity_impls,
+                max_failed_leaves,
+                cannot_prove,
+                prefer_cached_subgoals,
+                clause_filter,
+                goal_pipeline,
             },
         }
     }
+
+    /// The options of this choice, as `SolverChoice::slg_with` takes
+    /// them.
+    pub(crate) fn slg_options(self) -> SlgOptions {
+        match self {
+            SolverChoice::SLG {
+                max_size,
+                max_universes,
+                unique_modulo_lifetimes,
+                coinductive_goals,
+                solve_mode,
+                prune_well_formed,
+                suggest_identity_impls,
+                max_failed_leaves,
+                cannot_prove,
+                prefer_cached_subgoals,
+                clause_filter,
+                goal_pipeline,
+            } => SlgOptions {
+                max_size,
+                max_universes,
+                unique_modulo_lifetimes,
+                coinductive_goals,
+                solve_mode,
+                prune_well_formed,
+                suggest_identity_impls,
+                max_failed_leaves,
+                cannot_prove,
+                prefer_cached_subgoals,
+                clause_filter,
+                goal_pipeline,
+            },
+        }
+    }
+
+    /// Creates a solver state.
+    pub fn into_solver(self) -> Solver {
+        let options = self.slg_options();
+        Solver {
+            forest: Forest::new(SlgContext::new(&options)),
+            clauses_validated: false,
+            empty_env_cache: Default::default(),
+            suggest_identity_impls: options.suggest_identity_impls,
+            max_failed_leaves: options.max_failed_leaves,
+            goal_pipeline: options.goal_pipeline,
+        }
+    }
 }
 
 impl Default for SolverChoice {
     fn default() -> Self {
-        SolverChoice::slg()
+        SolverChoice::slg_with(SlgOptions::default())
+    }
+}
+
+/// The tunables of the SLG solver, for use with
+/// `SolverChoice::slg_with`. Start from the defaults and override
+/// what you need:
+///
+/// ```
+/// # use chalk_solve::{SlgOptions, SolverChoice};
+/// let choice = SolverChoice::slg_with(SlgOptions::default().max_size(20));
+/// ```
+///
+/// See `SolverChoice::SLG` for the meaning of each option.
+#[derive(Copy, Clone, Debug, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub struct SlgOptions {
+    max_size: usize,
+    max_universes: usize,
+    unique_modulo_lifetimes: bool,
+    coinductive_goals: CoinductiveGoals,
+    solve_mode: SolveMode,
+    prune_well_formed: bool,
+    suggest_identity_impls: bool,
+    max_failed_leaves: usize,
+    cannot_prove: CannotProvePolicy,
+    prefer_cached_subgoals: bool,
+    clause_filter: Option<ClauseFilter>,
+    goal_pipeline: GoalPipeline,
+}
+
+impl SlgOptions {
+    /// Sets the maximum size of subgoals and answers (default: 10).
+    pub fn max_size(self, max_size: usize) -> Self {
+        SlgOptions { max_size, ..self }
+    }
+
+    /// Sets the maximum number of universes in a goal (default: 32).
+    pub fn max_universes(self, max_universes: usize) -> Self {
+        SlgOptions {
+            max_universes,
+            ..self
+        }
+    }
+
+    /// Sets whether several answers that differ only in their
+    /// lifetimes (in the substitution or in the lifetime constraints)
+    /// are collapsed into a `Unique` solution (default: false). The
+    /// solution requires the lifetime constraints of *all* of the
+    /// answers, which is stronger than necessary (any one answer
+    /// would do) but is what type inference wants: it can commit to
+    /// the types, and the region checker deals with the lifetimes, as
+    /// in rustc.
+    pub fn unique_modulo_lifetimes(self, unique_modulo_lifetimes: bool) -> Self {
+        SlgOptions {
+            unique_modulo_lifetimes,
+            ..self
+        }
+    }
+
+    /// Sets the shapes of goals that are treated as coinductive
+    /// (default: `CoinductiveGoals::default()`).
+    pub fn coinductive_goals(self, coinductive_goals: CoinductiveGoals) -> Self {
+        SlgOptions {
+            coinductive_goals,
+            ..self
+        }
+    }
+
+    /// Sets whether the solver answers ordinary, coherence or
+    /// post-monomorphization queries (default: `SolveMode::Regular`).
+    pub fn solve_mode(self, solve_mode: SolveMode) -> Self {
+        SlgOptions { solve_mode, ..self }
+    }
+
+    /// Sets whether the `WellFormed` conditions of program clauses
+    /// that are implied by their other conditions are removed
+    /// (default: false). For example, with `trait Ord where Self: Eq`,
+    /// the clause `WF(Set<T>) :- WF(T: Ord), WF(T: Eq)` for a struct
+    /// `Set<T> where T: Ord, T: Eq` loses its second condition, since
+    /// `WF(T: Ord)` can only be proven through `WF(T: Eq)`. That is
+    /// not so if custom clauses or the environment give `WellFormed`
+    /// hypotheses, so nothing is pruned then. `SolveStats` counts the
+    /// conditions pruned.
+    pub fn prune_well_formed(self, prune_well_formed: bool) -> Self {
+        SlgOptions {
+            prune_well_formed,
+            ..self
+        }
+    }
+
+    /// Sets whether a trait goal that several candidates make
+    /// ambiguous, with no guidance, is given the answer of an identity
+    /// impl (one like `impl<T> Borrow<T> for T` or `impl<T> From<T>
+    /// for T`) as `Guidance::Suggested` (default: false). E.g., given
+    /// also `impl<T> Borrow<T> for Box<T>`, `Box<u32>: Borrow<?X>`
+    /// comes out with the suggestion `?X := Box<u32>`, which is what
+    /// rustc would infer. This is a heuristic for the convenience of
+    /// type inference, and the suggestion may well be wrong.
+    pub fn suggest_identity_impls(self, suggest_identity_impls: bool) -> Self {
+        SlgOptions {
+            suggest_identity_impls,
+            ..self
+        }
+    }
+
+    /// Sets the number of failing leaves of a goal that
+    /// `Solver::solve_with_failures` reports at most (default: 1). With
+    /// the default, it stops at the first failing conjunct of `A, B,
+    /// C`; with more, it goes on through the others, so that all the
+    /// unsatisfied bounds can be reported at once.
+    pub fn max_failed_leaves(self, max_failed_leaves: usize) -> Self {
+        SlgOptions {
+            max_failed_leaves,
+            ..self
+        }
+    }
+
+    /// Sets what becomes of an answer that depends on a `CannotProve`
+    /// subgoal (default: `CannotProvePolicy::Ambiguous`).
+    pub fn cannot_prove(self, cannot_prove: CannotProvePolicy) -> Self {
+        SlgOptions {
+            cannot_prove,
+            ..self
+        }
+    }
+
+    /// Sets whether a conjunct whose answers are all cached already
+    /// is proven before the others (default: false). One without
+    /// answers then fails the goal before any work goes into the
+    /// others, and one with few answers pins down the variables that
+    /// the others mention, so fewer subgoals are created. Finding the
+    /// cached conjuncts costs a lookup of each of them, each time a
+    /// conjunct is selected.
+    pub fn prefer_cached_subgoals(self, prefer_cached_subgoals: bool) -> Self {
+        SlgOptions {
+            prefer_cached_subgoals,
+            ..self
+        }
+    }
+
+    /// Sets the filter that program clauses must pass to be used
+    /// (default: none, so all of them are).
+    pub fn clause_filter(self, clause_filter: Option<ClauseFilter>) -> Self {
+        SlgOptions {
+            clause_filter,
+            ..self
+        }
+    }
+
+    /// Sets the passes that rewrite each goal before it is solved
+    /// (default: none, `GoalPipeline::EMPTY`).
+    pub fn goal_pipeline(self, goal_pipeline: GoalPipeline) -> Self {
+        SlgOptions {
+            goal_pipeline,
+            ..self
+        }
+    }
+}
+
+impl Default for SlgOptions {
+    fn default() -> Self {
+        SlgOptions {
+            max_size: 10,
+            max_universes: 32,
+            unique_modulo_lifetimes: false,
+            coinductive_goals: CoinductiveGoals::default(),
+            solve_mode: SolveMode::Regular,
+            prune_well_formed: false,
+            suggest_identity_impls: false,
+            max_failed_leaves: 1,
+            cannot_prove: CannotProvePolicy::Ambiguous,
+            prefer_cached_subgoals: false,
+            clause_filter: None,
+            goal_pipeline: GoalPipeline::EMPTY,
+        }
+    }
+}
+
+/// What becomes of an answer with a `CannotProve` subgoal, e.g. one
+/// of the rules for impls that an upstream crate may yet add. Which
+/// is right depends on the client: a coherence check must not conclude
+/// that impls overlap from a goal it could not decide, while type
+/// checking would rather go on with the types it has and report the
+/// goal later.
+#[derive(Copy, Clone, Debug, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum CannotProvePolicy {
+    /// The answer is ambiguous, as are the solutions drawn from it.
+    #[default]
+    Ambiguous,
+
+    /// The subgoal is taken to hold, so the answer can still make a
+    /// `Unique` solution. The solution carries a caveat that it was
+    /// made so (see `Solver::solve_with_caveat`).
+    Holds,
+}
+
+/// A callback that decides, for each program clause that could prove
+/// a goal, whether the solver may use it, given the goal, the clause
+/// and where it came from. Dropping clauses lets tooling explore
+/// counterfactual programs (e.g., what if this impl did not exist)
+/// without lowering the program again:
+///
+/// ```
+/// # use chalk_ir::family::ChalkIr;
+/// # use chalk_ir::{DomainGoal, ImplId, ProgramClause, RawId};
+/// # use chalk_solve::clauses::ClauseOrigin;
+/// # use chalk_solve::{ClauseFilter, SlgOptions, SolverChoice};
+/// # let impl_id = ImplId(RawId { index: 0 });
+/// let without_impl = move |_: &DomainGoal<ChalkIr>, _: &ProgramClause<ChalkIr>, origin| {
+///     origin != ClauseOrigin::Impl(impl_id)
+/// };
+/// let filter = ClauseFilter::new(Box::leak(Box::new(without_impl)));
+/// let choice = SolverChoice::slg_with(SlgOptions::default().clause_filter(Some(filter)));
+/// ```
+///
+/// The filter is called with the clauses of each table as it is
+/// created; as tables are kept, a solver must not be given a filter
+/// that changes its mind. Clauses of the environment are filtered
+/// too, with the origin `ClauseOrigin::Environment`. In
+/// `SolveMode::Revealing`, a dropped impl still keeps the impls that
+/// it specializes from being used.
+///
+/// The callback is `'static` so that `SolverChoice` stays `Copy`;
+/// two filters are equal if they are the same callback.
+#[derive(Copy, Clone)]
+pub struct ClauseFilter(&'static ClauseFilterFn);
+
+/// The callback of a `ClauseFilter`: true if the clause may be used.
+pub type ClauseFilterFn =
+    dyn Fn(&DomainGoal<ChalkIr>, &ProgramClause<ChalkIr>, ClauseOrigin) -> bool + Send + Sync;
+
+impl ClauseFilter {
+    pub fn new(filter: &'static ClauseFilterFn) -> Self {
+        ClauseFilter(filter)
+    }
+
+    /// True if `clause`, from `origin`, may be used to prove `goal`.
+    pub fn keeps(
+        &self,
+        goal: &DomainGoal<ChalkIr>,
+        clause: &ProgramClause<ChalkIr>,
+        origin: ClauseOrigin,
+    ) -> bool {
+        (self.0)(goal, clause, origin)
+    }
+
+    fn address(&self) -> usize {
+        self.0 as *const ClauseFilterFn as *const () as usize
+    }
+}
+
+impl fmt::Debug for ClauseFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ClauseFilter({:#x})", self.address())
+    }
+}
+
+impl PartialEq for ClauseFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.address() == other.address()
+    }
+}
+
+impl Eq for ClauseFilter {}
+
+impl PartialOrd for ClauseFilter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ClauseFilter {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.address().cmp(&other.address())
+    }
+}
+
+impl std::hash::Hash for ClauseFilter {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.address().hash(state)
     }
 }
 
+/// The kind of queries a solver answers. The answers of one mode are
+/// not valid in the other, so each `Solver` has a single mode, and
+/// anything that caches answers outside of a solver must key them by
+/// the mode as well as by the goal (see `Solver::cache_key`).
+#[derive(Copy, Clone, Debug, Default, PartialOrd, Ord, PartialEq, Eq, Hash)]
+pub enum SolveMode {
+    /// Ordinary trait solving, e.g., for type checking: the program is
+    /// taken to be the whole world.
+    #[default]
+    Regular,
+
+    /// Solving for coherence ("intercrate" mode). The `Compatible`
+    /// fact holds, which switches on the rules of the compatible
+    /// modality: a goal that impls in downstream or future upstream
+    /// crates could make true comes out ambiguous rather than false.
+    Intercrate,
+
+    /// Solving after monomorphization ("reveal all"), e.g., for code
+    /// generation: specialization is resolved, so an impl is only used
+    /// if no more specialized impl applies (see
+    /// `RustIrDatabase::impl_specialization_priority`). This lets an
+    /// associated type that a specializing impl overrides be
+    /// normalized. The goals are expected to be fully known; the check
+    /// that no more specialized impl applies flounders otherwise.
+    ///
+    /// Revealing the hidden types of opaque (`impl Trait`) types is
+    /// not supported: the opaque types of the IR are anonymous and
+    /// carry no hidden type.
+    Revealing,
+}
+
+impl StableHash for SolveMode {
+    fn stable_hash(&self, hasher: &mut StableHasher) {
+        hasher.write_usize(match self {
+            SolveMode::Regular => 0,
+            SolveMode::Intercrate => 1,
+            SolveMode::Revealing => 2,
+        });
+    }
+}
+
+/// The number of subgoals listed in `SolveStats::hottest_subgoals`.
+const NUM_HOTTEST_SUBGOALS: usize = 10;
+
+/// Statistics about the work a `Solver` has done so far, as returned
+/// by `Solver::stats`. The `Display` impl renders them for users,
+/// with the hottest subgoals as a table.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SolveStats {
+    /// The number of tables (i.e., distinct subgoals) created.
+    pub num_tables: usize,
+
+    /// The largest number of universes reached while solving. This
+    /// never exceeds the `max_universes` of the `SolverChoice`.
+    pub max_universes: usize,
+
+    /// The number of subgoals that were not proven because an
+    /// identical subgoal had to be proven for the same clause anyway.
+    pub duplicate_subgoals: usize,
+
+    /// The number of times a `Normalize` subgoal was put off so that
+    /// another subgoal of the same clause could be proven first.
+    pub deferred_projections: usize,
+
+    /// The number of `WellFormed` conditions removed from program
+    /// clauses (see `SlgOptions::prune_well_formed`). Clauses are
+    /// generated anew for each subgoal, so a condition is counted
+    /// each time it is removed.
+    pub pruned_well_formed: usize,
+
+    /// The number of times program clauses were assembled for a
+    /// subgoal. This is at most `num_tables`: the clauses for a table
+    /// are assembled once, when it is created, however many times a
+    /// cycle through it is iterated.
+    pub clause_sets: usize,
+
+    /// Unlike the other statistics, which cover all the goals solved
+    /// so far, these are for the last root goal only: the (at most
+    /// ten) subgoals it spent the most time on, most expensive first
+    /// (see `TableCost`). This shows which bound made a slow goal
+    /// slow.
+    pub hottest_subgoals: Vec<TableCost<UCanonical<InEnvironment<Goal<ChalkIr>>>>>,
+}
+
+impl fmt::Display for SolveStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} tables, {} universes, {} duplicate subgoals, {} deferred projections, \
+             {} pruned WF conditions, {} clause sets",
+            self.num_tables,
+            self.max_universes,
+            self.duplicate_subgoals,
+            self.deferred_projections,
+            self.pruned_well_formed,
+            self.clause_sets
+        )?;
+        write!(
+            f,
+            "{:>12} {:>8} {:>11}  subgoal",
+            "time", "strands", "activations"
+        )?;
+        for subgoal in &self.hottest_subgoals {
+            let InEnvironment { environment, goal } = &subgoal.goal.canonical.value;
+            write!(
+                f,
+                "\n{:>12} {:>8} {:>11}  {:?}",
+                format!("{:?}", subgoal.time),
+                subgoal.strands,
+                subgoal.activations,
+                goal,
+            )?;
+            if !environment.clauses.is_empty() {
+                write!(f, " in {:?}", environment)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A solution together with what `Solver::solve_with_ambiguity_cause`
+/// and `Solver::solve_with_caveat` report about it.
+#[derive(Clone, Debug)]
+struct DetailedSolution {
+    solution: Solution,
+    ambiguity_cause: Option<AmbiguityCause>,
+    caveat: bool,
+}
+
+/// A snapshot of the cached state of a `Solver`; see
+/// `Solver::snapshot`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SolverSnapshot {
+    forest: ForestSnapshot,
+    empty_env_cache_len: usize,
+}
+
 /// Finds the solution to "goals", or trait queries -- i.e., figures
 /// out what sets of types implement which traits. Also, between
 /// queries, this struct stores the cached state from previous solver
 /// attempts, which can then be re-used later.
 pub struct Solver {
     forest: Forest<SlgContext>,
+
+    /// Whether the custom clauses of the program have been validated
+    /// (see `validate_clauses`).
+    clauses_validated: bool,
+
+    /// The solutions of the `T: Trait` goals without variables in the
+    /// empty environment solved so far (see `solve_in_empty_env`).
+    empty_env_cache: empty_env::EmptyEnvCache,
+
+    /// Whether ambiguous trait goals are given the answer of an
+    /// identity impl as guidance (see `identity_guidance`).
+    suggest_identity_impls: bool,
+
+    /// The number of failing leaves that `solve_with_failures` reports
+    /// at most.
+    max_failed_leaves: usize,
+
+    /// The passes run on each root goal before it is solved (see
+    /// `intern_goal`).
+    goal_pipeline: GoalPipeline,
 }
 
 impl Solver {
@@ -120,13 +863,150 @@ impl Solver {
     /// - `None` is the goal cannot be proven.
     /// - `Some(solution)` if we succeeded in finding *some* answers,
     ///   although `solution` may reflect ambiguity and unknowns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` cannot supply an item needed to solve the
+    /// goal; see `try_solve`.
     pub fn solve(
         &mut self,
         program: &dyn RustIrDatabase,
         goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
     ) -> Option<Solution> {
+        self.try_solve(program, goal)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Like `solve`, but for databases that may not have every item
+    /// available: if solving `goal` requires an item that one of the
+    /// `try_` accessors of `program` fails to supply, returns the
+    /// `MissingData` error instead of panicking.
+    ///
+    /// The cached state of the solver is discarded when this
+    /// happens, since it may contain tables that were computed
+    /// without the missing item.
+    pub fn try_solve(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<Solution>, MissingData> {
+        self.try_solve_detailed(program, goal)
+            .map(|solution| solution.map(|solved| solved.solution))
+    }
+
+    /// Like `solve`, but if the solution is ambiguous, also says why
+    /// (see `AmbiguityCause`), so that diagnostics can tell a goal
+    /// that needs more type information from one that several
+    /// candidates apply to.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` cannot supply an item needed to solve the
+    /// goal; see `try_solve_with_ambiguity_cause`.
+    pub fn solve_with_ambiguity_cause(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<(Solution, Option<AmbiguityCause>)> {
+        self.try_solve_with_ambiguity_cause(program, goal)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Like `solve_with_ambiguity_cause`, but returns a `MissingData`
+    /// error rather than panicking if `program` cannot supply an item
+    /// needed to solve `goal` (see `try_solve`).
+    pub fn try_solve_with_ambiguity_cause(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<(Solution, Option<AmbiguityCause>)>, MissingData> {
+        self.try_solve_detailed(program, goal)
+            .map(|solution| solution.map(|solved| (solved.solution, solved.ambiguity_cause)))
+    }
+
+    /// Like `solve`, but also says whether a `Unique` solution holds
+    /// only because a `CannotProve` subgoal was taken to hold (see
+    /// `CannotProvePolicy::Holds`). Such a solution is good enough to
+    /// go on with, but the goal should be checked again once more is
+    /// known. With the default policy, the flag is always false.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` cannot supply an item needed to solve the
+    /// goal; see `try_solve_with_caveat`.
+    pub fn solve_with_caveat(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<(Solution, bool)> {
+        self.try_solve_with_caveat(program, goal)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Like `solve_with_caveat`, but returns a `MissingData` error
+    /// rather than panicking if `program` cannot supply an item needed
+    /// to solve `goal` (see `try_solve`).
+    pub fn try_solve_with_caveat(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<(Solution, bool)>, MissingData> {
+        self.try_solve_detailed(program, goal)
+            .map(|solution| solution.map(|solved| (solved.solution, solved.caveat)))
+    }
+
+    fn try_solve_detailed(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<DetailedSolution>, MissingData> {
+        let empty_env_trait_ref = empty_env::empty_env_trait_ref(goal);
+        if let Some(solution) = empty_env_trait_ref.and_then(|t| self.empty_env_cache.get(t)) {
+            return Ok(solution.clone());
+        }
+
+        self.validate_clauses(program);
+        let interned_goal = self.intern_goal(goal);
         let ops = self.forest.context().ops(program);
-        self.forest.solve(&ops, goal)
+        let solution = self.forest.solve(&ops, &interned_goal);
+        match ops.missing_data() {
+            None => {
+                let mut solution = solution.map(|solution| {
+                    let (ambiguity_cause, caveat) = if solution.is_unique() {
+                        (None, ops.caveat())
+                    } else {
+                        (ops.ambiguity_cause(), false)
+                    };
+                    DetailedSolution {
+                        solution,
+                        ambiguity_cause,
+                        caveat,
+                    }
+                });
+                if let (
+                    true,
+                    Some(DetailedSolution {
+                        solution: Solution::Ambig(guidance @ Guidance::Unknown),
+                        ambiguity_cause: Some(AmbiguityCause::MultipleCandidates),
+                        ..
+                    }),
+                ) = (self.suggest_identity_impls, &mut solution)
+                {
+                    if let Some(suggested) = self.identity_guidance(program, goal)? {
+                        *guidance = suggested;
+                    }
+                }
+                if let Some(trait_ref) = empty_env_trait_ref {
+                    self.empty_env_cache
+                        .insert(trait_ref.clone(), solution.clone());
+                }
+                Ok(solution)
+            }
+            Some(missing) => {
+                self.reset();
+                Err(missing)
+            }
+        }
     }
 
     /// Attempts to solve the given goal, which must be in canonical
@@ -151,14 +1031,202 @@ impl Solver {
     ///
     /// - `true` all solutions were processed with the function.
     /// - `false` the function returned `false` and solutions were interrupted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` cannot supply an item needed to solve the
+    /// goal; see `try_solve_multiple`.
     pub fn solve_multiple(
         &mut self,
         program: &dyn RustIrDatabase,
         goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
         f: impl FnMut(Canonical<ConstrainedSubst<ChalkIr>>, bool) -> bool,
     ) -> bool {
+        self.try_solve_multiple(program, goal, f)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Like `solve_multiple`, but returns a `MissingData` error rather
+    /// than panicking if `program` cannot supply an item needed to
+    /// solve `goal` (see `try_solve`). The solutions passed to `f`
+    /// before the item was found missing may be incomplete, and the
+    /// cached state of the solver is discarded.
+    pub fn try_solve_multiple(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+        mut f: impl FnMut(Canonical<ConstrainedSubst<ChalkIr>>, bool) -> bool,
+    ) -> Result<bool, MissingData> {
+        self.validate_clauses(program);
+        let goal = self.intern_goal(goal);
         let ops = self.forest.context().ops(program);
-        self.forest.solve_multiple(&ops, goal, f)
+        // A goal that flounders for want of an item yields ambiguous
+        // answers for as long as we ask, so stop at the first one.
+        let all_processed = self.forest.solve_multiple(&ops, &goal, |subst, has_next| {
+            ops.missing_data().is_none() && f(subst, has_next)
+        });
+        match ops.missing_data() {
+            None => Ok(all_processed),
+            Some(missing) => {
+                self.reset();
+                Err(missing)
+            }
+        }
+    }
+
+    /// In debug builds, and with the `validate-clauses` feature,
+    /// checks the custom clauses of `program` the first time it is
+    /// called (the clauses must be the same for every query anyway).
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the clauses is invalid; see
+    /// `clause_validation::validate_clauses`.
+    fn validate_clauses(&mut self, program: &dyn RustIrDatabase) {
+        if !cfg!(any(debug_assertions, feature = "validate-clauses")) || self.clauses_validated {
+            return;
+        }
+        if let Err(invalid) = validate_custom_clauses(program) {
+            panic!("{}", invalid);
+        }
+        self.clauses_validated = true;
+    }
+
+    /// Drops all the tables, along with the environments interned for
+    /// them.
+    fn reset(&mut self) {
+        self.forest.reset();
+        self.forest.context().clear_environments();
+    }
+
+    /// Returns a copy of `goal`, rewritten by the goal pipeline, whose
+    /// environment is shared with those of previously solved goals.
+    fn intern_goal(
+        &mut self,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> UCanonical<InEnvironment<Goal<ChalkIr>>> {
+        let mut goal = goal.clone();
+        let InEnvironment {
+            environment,
+            goal: root,
+        } = &mut goal.canonical.value;
+        *root = self.goal_pipeline.run(root);
+        *environment = self
+            .forest
+            .context()
+            .intern_environment(environment.clone());
+        goal
+    }
+
+    /// Convenience wrapper around `solve` that answers whether `ty`
+    /// implements the trait `trait_id` with the additional trait
+    /// parameters `args` (i.e., whether `ty: Trait<args..>` holds in
+    /// the empty environment). The goal is constructed internally, and
+    /// takes the fast path of `solve_in_empty_env`.
+    ///
+    /// `ty` and `args` must not contain free variables (which debug
+    /// builds check).
+    pub fn has_impl(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        ty: Ty<ChalkIr>,
+        trait_id: TraitId,
+        args: Vec<Parameter<ChalkIr>>,
+    ) -> ImplConfidence {
+        let trait_ref = TraitRef {
+            trait_id,
+            parameters: Some(ty.cast()).into_iter().chain(args).collect(),
+        };
+        self.solve_in_empty_env(program, &trait_ref).into()
+    }
+
+    /// Returns the mode in which this solver answers queries.
+    pub fn solve_mode(&self) -> SolveMode {
+        self.forest.context().solve_mode()
+    }
+
+    /// Returns true if this solver treats `goal` as coinductive, i.e.,
+    /// if a cycle through `goal` is accepted as a proof of it rather
+    /// than rejected (see `IsCoinductive`). An embedder that caches
+    /// answers of its own can use this to handle cycles in the same
+    /// way as the solver.
+    pub fn is_coinductive(
+        &self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> bool {
+        goal.is_coinductive(program, &self.forest.context().coinductive_goals())
+    }
+
+    /// Returns the key under which the solution of `goal` by this
+    /// solver may be cached outside of it, e.g. in a cache shared
+    /// between solvers or processes. This is the stable hash of the
+    /// goal (see `ext::stable_hash_goal`) combined with the mode of
+    /// the solver, so that the answers to coherence queries are never
+    /// mistaken for those of ordinary queries, or vice versa.
+    pub fn cache_key(&self, goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.solve_mode().stable_hash(&mut hasher);
+        goal.stable_hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns statistics about the goals solved so far.
+    pub fn stats(&self) -> SolveStats {
+        SolveStats {
+            num_tables: self.forest.num_tables(),
+            max_universes: self.forest.context().universes_seen(),
+            duplicate_subgoals: self.forest.context().duplicate_subgoals(),
+            deferred_projections: self.forest.context().deferred_projections(),
+            pruned_well_formed: self.forest.context().pruned_well_formed(),
+            clause_sets: self.forest.context().clause_sets(),
+            hottest_subgoals: self.forest.hottest_tables(NUM_HOTTEST_SUBGOALS),
+        }
+    }
+
+    /// Returns the approximate memory retained by each of the tables
+    /// created so far, largest first. This is meant for debugging
+    /// memory blowups, and walks all the tables, so it is not cheap.
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            tables: self.forest.table_memory(),
+        }
+    }
+
+    /// Returns a view of the subgoals the solver is working on, which
+    /// another thread can read while the solver solves: e.g., a
+    /// watchdog that reports what a goal is stuck on once a deadline
+    /// has passed, without cancelling it. The solver keeps the view up
+    /// to date from the first call on, which costs a lock and a clone
+    /// of the subgoal each time a table is pushed on its stack.
+    pub fn watch_stack(&mut self) -> StackWatch<UCanonical<InEnvironment<Goal<ChalkIr>>>> {
+        self.forest.watch_stack()
+    }
+
+    /// Takes a snapshot of the cached state of the solver, to which
+    /// `rollback_to_snapshot` can later return. This is meant for
+    /// solving goals speculatively -- e.g., while probing a candidate
+    /// that may not be chosen in the end -- without leaving their
+    /// tables behind. Snapshots can be nested, and must be taken and
+    /// rolled back to between calls to `solve`.
+    pub fn snapshot(&self) -> SolverSnapshot {
+        SolverSnapshot {
+            forest: self.forest.snapshot(),
+            empty_env_cache_len: self.empty_env_cache.len(),
+        }
+    }
+
+    /// Drops the tables created since `snapshot` was taken, so that
+    /// the goals solved since then are solved afresh when they come up
+    /// again. Answers found since then for goals that were already
+    /// cached at the snapshot are kept, since they are true regardless
+    /// (see `Forest::rollback_to`). The interned environments are
+    /// forgotten, as those of the dropped tables cannot be told apart
+    /// from the others.
+    pub fn rollback_to_snapshot(&mut self, snapshot: SolverSnapshot) {
+        self.forest.rollback_to(snapshot.forest);
+        self.forest.context().clear_environments();
+        self.empty_env_cache.truncate(snapshot.empty_env_cache_len);
     }
 
     pub fn into_test(self) -> TestSolver {
@@ -223,4 +1291,16 @@ impl TestSolver {
         let ops = self.forest.context().ops(program);
         self.forest.num_cached_answers_for_goal(&ops, goal)
     }
+
+    /// Returns the number of tables the solver has created so far.
+    /// Used only in testing.
+    pub fn num_tables(&self) -> usize {
+        self.forest.num_tables()
+    }
+
+    /// Renders the solver's tables, and the dependencies between them,
+    /// as a graph in the DOT language. Meant for debugging.
+    pub fn tables_dot(&self) -> String {
+        self.forest.to_dot()
+    }
 }
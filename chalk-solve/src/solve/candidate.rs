@@ -0,0 +1,301 @@
+//! The candidates by which a trait goal can be proven (see
+//! `Solver::candidates`) or why others cannot (see
+//! `Solver::rejected_candidates`), and solving with the impls that the
+//! database prefers, for tooling (see `Solver::solve_preferring_impls`).
+
+use crate::clauses::{
+    program_clauses_with_origins, unfiltered_program_clauses_with_origins, ClauseOrigin,
+};
+use crate::infer::unify::UnificationError;
+use crate::infer::InferenceTable;
+use crate::solve::{Solution, Solver};
+use crate::{MissingData, RustIrDatabase};
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::*;
+use itertools::Itertools;
+use std::sync::Arc;
+
+/// The ways in which a trait goal `T: Foo` can be proven, mirroring
+/// the kinds of candidates that rustc selects between. The variants
+/// are declared in order of preference: a where clause in the
+/// environment is preferred over an impl, as in rustc.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Candidate {
+    /// A where clause in the environment, through the rule
+    /// `Implemented(T: Foo) :- FromEnv(T: Foo)`.
+    ParamEnv,
+
+    /// An impl of the trait.
+    Impl(ImplId),
+
+    /// The structural impl of an auto trait, for a struct, a tuple or
+    /// `Box`.
+    AutoImpl,
+
+    /// The bounds of a `dyn` type, which implements its own traits.
+    Object,
+
+    /// Any other rule built into the solver, such as the impls of the
+    /// `Fn` traits for function pointers; the string names the rule
+    /// (see `ClauseOrigin::BuiltIn`).
+    Builtin(&'static str),
+
+    /// One of the `RustIrDatabase::custom_clauses`.
+    Custom,
+}
+
+/// The result of `Solver::solve_preferring_impls`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ToolingSolution {
+    /// The solution found by `solve`.
+    Exact(Solution),
+
+    /// `solve` found the goal to be ambiguous between several impls,
+    /// and the database preferred `impl_id` (see
+    /// `RustIrDatabase::prefer_impl`); `solution` is the solution of
+    /// the goal by that impl alone. This is only a heuristic: nothing
+    /// in the program says that the preferred impl is the one that
+    /// applies.
+    Heuristic { impl_id: ImplId, solution: Solution },
+}
+
+impl ToolingSolution {
+    pub fn solution(&self) -> &Solution {
+        match self {
+            ToolingSolution::Exact(solution) | ToolingSolution::Heuristic { solution, .. } => {
+                solution
+            }
+        }
+    }
+
+    pub fn is_heuristic(&self) -> bool {
+        match self {
+            ToolingSolution::Exact(_) => false,
+            ToolingSolution::Heuristic { .. } => true,
+        }
+    }
+}
+
+impl Candidate {
+    /// The candidate for a clause of the given origin that proves
+    /// `Implemented(T: trait_id)`, if the clause is one that can.
+    pub fn from_origin(
+        program: &dyn RustIrDatabase,
+        trait_id: TraitId,
+        origin: ClauseOrigin,
+    ) -> Option<Candidate> {
+        match origin {
+            ClauseOrigin::Custom => Some(Candidate::Custom),
+            ClauseOrigin::Trait(_) | ClauseOrigin::Environment => Some(Candidate::ParamEnv),
+            ClauseOrigin::Impl(impl_id) => Some(Candidate::Impl(impl_id)),
+            ClauseOrigin::BuiltIn("dyn") => Some(Candidate::Object),
+            ClauseOrigin::BuiltIn("auto trait") => Some(Candidate::AutoImpl),
+            ClauseOrigin::BuiltIn(name @ "tuple") | ClauseOrigin::BuiltIn(name @ "Box") => {
+                if program.trait_datum(trait_id).is_auto_trait() {
+                    Some(Candidate::AutoImpl)
+                } else {
+                    Some(Candidate::Builtin(name))
+                }
+            }
+            ClauseOrigin::BuiltIn(name) => Some(Candidate::Builtin(name)),
+            ClauseOrigin::Struct(_) | ClauseOrigin::AssociatedTy(_) => None,
+        }
+    }
+}
+
+impl Solver {
+    /// Explains how the trait goal `goal` can be proven: each candidate
+    /// that proves the goal on its own, with the solution it yields
+    /// (in terms of the variables of `goal`). The candidates are sorted
+    /// in order of preference, and otherwise listed in the order in
+    /// which their clauses are generated, so the result is
+    /// deterministic.
+    ///
+    /// A candidate may rely on the others to prove its own conditions;
+    /// only the first step of the proof is attributed to it. Candidates
+    /// for which `solve` finds no solution are omitted.
+    pub fn candidates(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<TraitRef<ChalkIr>>>,
+    ) -> Result<Vec<(Candidate, Solution)>, MissingData> {
+        let InEnvironment {
+            environment,
+            goal: trait_ref,
+        } = &goal.canonical.value;
+        let domain_goal: DomainGoal<_> = trait_ref.clone().cast();
+        let clauses = program_clauses_with_origins(program, environment, &domain_goal)?;
+
+        let mut candidates = vec![];
+        for (clause, origin) in clauses {
+            let candidate = match Candidate::from_origin(program, trait_ref.trait_id, origin) {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            let candidate_goal = UCanonical {
+                canonical: Canonical {
+                    binders: goal.canonical.binders.clone(),
+                    value: InEnvironment::new(environment, clause_goal(trait_ref, &clause)),
+                },
+                universes: goal.universes,
+            };
+            if let Some(solution) = self.try_solve(program, &candidate_goal)? {
+                candidates.push((candidate, solution));
+            }
+        }
+        candidates.sort_by_key(|&(candidate, _)| candidate);
+
+        // A where clause in the environment is often given by both
+        // the environment itself and the trait's `FromEnv` rule.
+        candidates.dedup();
+        Ok(candidates)
+    }
+
+    /// Explains why the candidates of the trait goal `goal` that do
+    /// not match it are ruled out: each candidate whose clause has a
+    /// head that does not unify with the goal, with the reason (see
+    /// `UnificationError`). E.g., for `Vec<u32>: Clone`, `impl Clone
+    /// for Foo` is given with the mismatch of `Vec<u32>` and `Foo`.
+    /// The candidates are in the same order as for `candidates`.
+    ///
+    /// Candidates that match the goal, but whose conditions do not
+    /// hold, are left out; `solve_with_failures` can tell which
+    /// conditions those are.
+    pub fn rejected_candidates(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<TraitRef<ChalkIr>>>,
+    ) -> Result<Vec<(Candidate, UnificationError)>, MissingData> {
+        let InEnvironment {
+            environment,
+            goal: trait_ref,
+        } = &goal.canonical.value;
+        let domain_goal: DomainGoal<_> = trait_ref.clone().cast();
+        let clauses = unfiltered_program_clauses_with_origins(program, environment, &domain_goal)?;
+
+        let mut rejected = vec![];
+        for (clause, origin) in clauses {
+            let candidate = match Candidate::from_origin(program, trait_ref.trait_id, origin) {
+                Some(candidate) => candidate,
+                None => continue,
+            };
+            let (mut infer, _, InEnvironment { environment, goal }) =
+                InferenceTable::from_canonical(goal.universes, &goal.canonical);
+            let implication = match &clause {
+                ProgramClause::Implies(implication) => implication.clone(),
+                ProgramClause::ForAll(binders) => infer.instantiate_binders_existentially(binders),
+            };
+            let head = match implication.consequence {
+                DomainGoal::Holds(WhereClause::Implemented(head))
+                    if head.trait_id == goal.trait_id =>
+                {
+                    head
+                }
+                _ => continue,
+            };
+            if let Err(error) = infer.unify_explaining(&environment, &goal, &head) {
+                rejected.push((candidate, error));
+            }
+        }
+        rejected.sort_by_key(|(candidate, _)| *candidate);
+        rejected.dedup();
+        Ok(rejected)
+    }
+}
+
+impl Solver {
+    /// Like `solve`, but for tooling (such as IDE completion) that
+    /// would rather make progress with *some* impl than stop at an
+    /// ambiguity. If `goal` is a trait goal (whose variables are bound
+    /// by the canonical binders, as by `GoalExt::into_peeled_goal`)
+    /// that is ambiguous because several impls apply, the database is asked to pick one of them
+    /// (see `RustIrDatabase::prefer_impl`), and the solution by that
+    /// impl is returned, marked as heuristic.
+    pub fn solve_preferring_impls(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<ToolingSolution> {
+        let solution = self.solve(program, goal)?;
+        if solution.is_unique() {
+            return Some(ToolingSolution::Exact(solution));
+        }
+        let trait_ref = match &goal.canonical.value.goal {
+            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+                trait_ref,
+            )))) => trait_ref,
+            _ => return Some(ToolingSolution::Exact(solution)),
+        };
+
+        let trait_goal = UCanonical {
+            canonical: Canonical {
+                binders: goal.canonical.binders.clone(),
+                value: InEnvironment::new(&goal.canonical.value.environment, trait_ref.clone()),
+            },
+            universes: goal.universes,
+        };
+        let mut impls: Vec<(ImplId, Solution)> = self
+            .candidates(program, &trait_goal)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+            .into_iter()
+            .filter_map(|(candidate, solution)| match candidate {
+                Candidate::Impl(impl_id) => Some((impl_id, solution)),
+                _ => None,
+            })
+            .collect();
+        if impls.len() < 2 {
+            return Some(ToolingSolution::Exact(solution));
+        }
+
+        let impl_ids: Vec<ImplId> = impls.iter().map(|&(impl_id, _)| impl_id).collect();
+        let preferred = program.prefer_impl(&impl_ids);
+        match impls
+            .iter()
+            .position(|&(impl_id, _)| Some(impl_id) == preferred)
+        {
+            Some(index) => {
+                let (impl_id, solution) = impls.swap_remove(index);
+                Some(ToolingSolution::Heuristic { impl_id, solution })
+            }
+            None => Some(ToolingSolution::Exact(solution)),
+        }
+    }
+}
+
+/// The goal that holds if `clause` proves `trait_ref`: given the
+/// clause `forall<X..> { Implemented(P..) :- C.. }`, that is
+///
+/// ```notrust
+/// exists<X..> { trait_ref.parameters = P.., C.. }
+/// ```
+fn clause_goal(trait_ref: &TraitRef<ChalkIr>, clause: &ProgramClause<ChalkIr>) -> Goal<ChalkIr> {
+    let (binders, implication, shift) = match clause {
+        ProgramClause::Implies(implication) => (ParameterKinds::EMPTY, implication, 0),
+        ProgramClause::ForAll(binders) => (binders.binders, &binders.value, 1),
+    };
+    let head = match &implication.consequence {
+        DomainGoal::Holds(WhereClause::Implemented(head)) => head,
+        consequence => panic!("clause for `{:?}` proves `{:?}`", trait_ref, consequence),
+    };
+    let goal = trait_ref
+        .parameters
+        .iter()
+        .zip(&head.parameters)
+        .map(|(a, b)| {
+            EqGoal {
+                a: a.shifted_in(shift),
+                b: b.clone(),
+            }
+            .cast()
+        })
+        .chain(implication.conditions.iter().cloned())
+        .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
+        .unwrap();
+    if binders.is_empty() {
+        goal
+    } else {
+        goal.quantify(QuantifierKind::Exists, binders)
+    }
+}
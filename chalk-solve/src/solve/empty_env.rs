@@ -0,0 +1,106 @@
+//! A fast path for `T: Trait` goals without variables in the empty
+//! environment, such as const evaluation poses in great numbers (e.g.,
+//! `u32: Copy`). Their solutions are cached by trait ref, so that
+//! answering one again skips canonicalizing and interning the goal and
+//! looking up its table.
+
+use crate::ext::GoalExt;
+use crate::solve::{DetailedSolution, Solution, Solver};
+use crate::RustIrDatabase;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use rustc_hash::FxHashMap;
+
+type CachedSolution = Option<DetailedSolution>;
+
+/// The solutions found for goals that take the fast path, by trait
+/// ref. Like the tables of the forest, the entries added since a
+/// snapshot are dropped when the solver is rolled back to it, so the
+/// order in which they were added is recorded.
+#[derive(Default)]
+pub(super) struct EmptyEnvCache {
+    solutions: FxHashMap<TraitRef<ChalkIr>, CachedSolution>,
+    added: Vec<TraitRef<ChalkIr>>,
+}
+
+impl EmptyEnvCache {
+    pub(super) fn get(&self, trait_ref: &TraitRef<ChalkIr>) -> Option<&CachedSolution> {
+        self.solutions.get(trait_ref)
+    }
+
+    pub(super) fn insert(&mut self, trait_ref: TraitRef<ChalkIr>, solution: CachedSolution) {
+        if self.solutions.insert(trait_ref.clone(), solution).is_none() {
+            self.added.push(trait_ref);
+        }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.added.len()
+    }
+
+    /// Drops the entries added after the first `len`.
+    pub(super) fn truncate(&mut self, len: usize) {
+        for trait_ref in self.added.drain(len..) {
+            self.solutions.remove(&trait_ref);
+        }
+    }
+}
+
+/// Returns the trait ref of `goal` if it is a `T: Trait` goal without
+/// variables (neither canonical ones nor placeholders) in the empty
+/// environment.
+pub(super) fn empty_env_trait_ref(
+    goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+) -> Option<&TraitRef<ChalkIr>> {
+    if !goal.canonical.binders.is_empty()
+        || goal.universes != 1
+        || !goal.canonical.value.environment.clauses.is_empty()
+    {
+        return None;
+    }
+    match &goal.canonical.value.goal {
+        Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+            trait_ref,
+        )))) => Some(trait_ref),
+        _ => None,
+    }
+}
+
+impl Solver {
+    /// Solves `trait_ref` in the empty environment. The trait ref must
+    /// not contain any variables: the goal is then canonical as it is,
+    /// so it is built directly rather than canonicalized (debug builds
+    /// check that it is). Goals solved this way (or through `solve`,
+    /// if they have this shape) are answered from a cache when they
+    /// come up again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `program` cannot supply an item needed to solve the
+    /// goal; see `try_solve`.
+    pub fn solve_in_empty_env(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        trait_ref: &TraitRef<ChalkIr>,
+    ) -> Option<Solution> {
+        if let Some(solution) = self.empty_env_cache.get(trait_ref) {
+            return solution.as_ref().map(|solved| solved.solution.clone());
+        }
+
+        let goal: Goal<ChalkIr> = trait_ref.clone().cast();
+        let goal = UCanonical {
+            canonical: Canonical {
+                value: InEnvironment::new(&Environment::new(), goal),
+                binders: vec![],
+            },
+            universes: 1,
+        };
+        debug_assert_eq!(
+            goal,
+            goal.canonical.value.goal.clone().into_closed_goal(),
+            "`solve_in_empty_env` given a trait ref with variables"
+        );
+        self.solve(program, &goal)
+    }
+}
@@ -0,0 +1,79 @@
+//! Reporting the failing leaves of a goal, rather than the goal as a
+//! whole: for `A, B, C` where `B` and `C` do not hold, `B` (and, with
+//! `SlgOptions::max_failed_leaves`, also `C`), so that diagnostics can
+//! list the unsatisfied bounds.
+
+use crate::solve::{Solution, Solver};
+use crate::RustIrDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+
+type CanonicalGoal = UCanonical<InEnvironment<Goal<ChalkIr>>>;
+
+impl Solver {
+    /// Like `solve`, but if `goal` cannot be proven, returns the leaves
+    /// of its conjunctions (and implications) that cannot be proven on
+    /// their own, in order, and at most `max_failed_leaves` of them.
+    /// Each leaf has the binders of `goal` and the environment in which
+    /// it appears.
+    ///
+    /// When no leaf fails on its own (e.g., for `exists<T> { T = u32, T
+    /// = i32 }`, whose leaves conflict only through `T`), `goal` itself
+    /// is returned as the failure.
+    pub fn solve_with_failures(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &CanonicalGoal,
+    ) -> Result<Solution, Vec<CanonicalGoal>> {
+        if let Some(solution) = self.solve(program, goal) {
+            return Ok(solution);
+        }
+
+        let mut failed = vec![];
+        let InEnvironment {
+            environment,
+            goal: value,
+        } = &goal.canonical.value;
+        self.collect_failed_leaves(program, goal, environment, value, &mut failed);
+        if failed.is_empty() {
+            failed.push(goal.clone());
+        }
+        Err(failed)
+    }
+
+    fn collect_failed_leaves(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        root: &CanonicalGoal,
+        environment: &Environment<ChalkIr>,
+        goal: &Goal<ChalkIr>,
+        failed: &mut Vec<CanonicalGoal>,
+    ) {
+        if failed.len() >= self.max_failed_leaves {
+            return;
+        }
+
+        match goal {
+            Goal::And(left, right) => {
+                self.collect_failed_leaves(program, root, environment, left, failed);
+                self.collect_failed_leaves(program, root, environment, right, failed);
+            }
+            Goal::Implies(hypotheses, goal) => {
+                let environment = environment.add_clauses(hypotheses.iter().cloned());
+                self.collect_failed_leaves(program, root, &environment, goal, failed);
+            }
+            _ => {
+                let leaf = UCanonical {
+                    canonical: Canonical {
+                        binders: root.canonical.binders.clone(),
+                        value: InEnvironment::new(environment, goal.clone()),
+                    },
+                    universes: root.universes,
+                };
+                if self.solve(program, &leaf).is_none() {
+                    failed.push(leaf);
+                }
+            }
+        }
+    }
+}
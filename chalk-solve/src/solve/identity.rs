@@ -0,0 +1,89 @@
+//! Guidance for trait goals made ambiguous by an identity impl, such
+//! as `impl<T> Borrow<T> for T` or `impl<T> From<T> for T` (see
+//! `SlgOptions::suggest_identity_impls`). Given also `impl<T>
+//! Borrow<T> for Box<T>`, `Box<u32>: Borrow<?X>` has the answers
+//! `?X := Box<u32>` and `?X := u32`, and so no guidance; rustc would
+//! pick the first.
+
+use crate::ext::*;
+use crate::solve::{Candidate, Guidance, Solution, Solver};
+use crate::{MissingData, RustIrDatabase};
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::ImplDatum;
+
+impl Solver {
+    /// Returns the guidance that the identity impl gives for `goal`,
+    /// as `Guidance::Suggested`, if `goal` is a trait goal with
+    /// variables that exactly one identity impl proves. As in rustc,
+    /// no impl is picked while the self type is still unknown.
+    pub(super) fn identity_guidance(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<Guidance>, MissingData> {
+        if goal.canonical.binders.is_empty() {
+            return Ok(None);
+        }
+        let trait_ref = match &goal.canonical.value.goal {
+            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+                trait_ref,
+            )))) => trait_ref,
+            _ => return Ok(None),
+        };
+        if let Some(Ty::BoundVar(_)) = trait_ref.self_type_parameter() {
+            return Ok(None);
+        }
+
+        let trait_goal = UCanonical {
+            canonical: Canonical {
+                binders: goal.canonical.binders.clone(),
+                value: InEnvironment::new(&goal.canonical.value.environment, trait_ref.clone()),
+            },
+            universes: goal.universes,
+        };
+        let mut identity_solutions = vec![];
+        for (candidate, solution) in self.candidates(program, &trait_goal)? {
+            if let Candidate::Impl(impl_id) = candidate {
+                if is_identity_impl(&*program.try_impl_datum(impl_id)?) {
+                    identity_solutions.push(solution);
+                }
+            }
+        }
+        if identity_solutions.len() != 1 {
+            return Ok(None);
+        }
+
+        Ok(match identity_solutions.pop().unwrap() {
+            Solution::Unique(constrained) => {
+                Some(Guidance::Suggested(constrained.map(|cs| cs.subst)))
+            }
+            Solution::Ambig(Guidance::Definite(subst))
+            | Solution::Ambig(Guidance::Suggested(subst)) => Some(Guidance::Suggested(subst)),
+            Solution::Ambig(Guidance::Unknown) => None,
+        })
+    }
+}
+
+/// Whether `impl_datum` is a positive impl of a trait with type
+/// parameters besides `Self` for a single type parameter of the impl,
+/// which it uses for all of them (e.g., `impl<T> Borrow<T> for T`).
+/// Its where clauses, if any, do not matter.
+fn is_identity_impl(impl_datum: &ImplDatum) -> bool {
+    if !impl_datum.is_positive() {
+        return false;
+    }
+    let mut tys = impl_datum.binders.value.trait_ref.type_parameters();
+    let self_ty = match tys.next() {
+        Some(Ty::BoundVar(depth)) => depth,
+        _ => return false,
+    };
+    let mut others = 0;
+    for ty in tys {
+        match ty {
+            Ty::BoundVar(depth) if depth == self_ty => others += 1,
+            _ => return false,
+        }
+    }
+    others > 0
+}
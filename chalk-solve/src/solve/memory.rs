@@ -0,0 +1,103 @@
+//! Approximate memory accounting for the solver (see
+//! `Solver::memory_report`).
+
+use chalk_engine::fallible::Fallible;
+use chalk_engine::memory::TableMemory;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{self, *};
+use chalk_ir::*;
+use std::fmt;
+use std::mem;
+
+/// The approximate memory retained by the tables of a solver, largest
+/// first. The `Display` impl renders it as a table, one row per table
+/// of the solver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub tables: Vec<TableMemory<UCanonical<InEnvironment<Goal<ChalkIr>>>>>,
+}
+
+impl MemoryReport {
+    /// Total bytes retained by all tables.
+    pub fn total_bytes(&self) -> usize {
+        self.tables.iter().map(|table| table.total_bytes()).sum()
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>10} {:>10} {:>8} {:>10} {:>8} {:>10}  goal",
+            "total", "goal", "answers", "bytes", "strands", "bytes"
+        )?;
+        for table in &self.tables {
+            let InEnvironment { environment, goal } = &table.goal.canonical.value;
+            write!(
+                f,
+                "{:>10} {:>10} {:>8} {:>10} {:>8} {:>10}  {:?}",
+                table.total_bytes(),
+                table.goal_bytes,
+                table.num_answers,
+                table.answer_bytes,
+                table.num_strands,
+                table.strand_bytes,
+                goal,
+            )?;
+            if !environment.clauses.is_empty() {
+                write!(f, " in {:?}", environment)?;
+            }
+            writeln!(f)?;
+        }
+        write!(
+            f,
+            "{} bytes in {} tables",
+            self.total_bytes(),
+            self.tables.len()
+        )
+    }
+}
+
+/// Approximates the number of bytes retained by `value`: its own
+/// size, plus the size of each type and lifetime within it. This
+/// undercounts the nodes of goals and the capacity of vectors, but is
+/// good enough to compare tables.
+pub(super) fn approx_size<T: Fold<ChalkIr>>(value: &T) -> usize {
+    let mut counter = NodeCounter {
+        tys: 0,
+        lifetimes: 0,
+    };
+    value
+        .fold_with(&mut counter, 0)
+        .expect("NodeCounter is infallible");
+    mem::size_of_val(value)
+        + counter.tys * mem::size_of::<Ty<ChalkIr>>()
+        + counter.lifetimes * mem::size_of::<Lifetime<ChalkIr>>()
+}
+
+struct NodeCounter {
+    tys: usize,
+    lifetimes: usize,
+}
+
+impl TypeFolder<ChalkIr> for NodeCounter {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        self.tys += 1;
+        fold::super_fold_ty(self, ty, binders)
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        self.lifetimes += 1;
+        fold::super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for NodeCounter {}
+
+impl DefaultInferenceFolder for NodeCounter {}
+
+impl DefaultPlaceholderFolder for NodeCounter {}
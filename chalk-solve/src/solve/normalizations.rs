@@ -0,0 +1,180 @@
+//! The associated type values chosen in a solution (see
+//! `Solver::normalizations`), for tooling that wants to show (e.g.)
+//! that `<Vec<u32> as IntoIterator>::Item` came out as `u32`.
+
+use crate::infer::InferenceTable;
+use crate::solve::{Solution, Solver};
+use crate::RustIrDatabase;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::fold::{self, *};
+use chalk_ir::*;
+
+impl Solver {
+    /// Solves `goal` and, if it has a unique solution, returns the
+    /// value of each associated type that the goal refers to under
+    /// that solution: the projections of its `Normalize` and
+    /// `ProjectionEq` goals, and the projection types that appear in
+    /// it, each with the type it normalizes to. The result is in terms
+    /// of the binders of the solution.
+    ///
+    /// Projections that do not normalize to a single type (e.g.,
+    /// because they mention variables bound within the goal, or
+    /// because the solution leaves their value open) are left out.
+    pub fn normalizations(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<Canonical<Vec<Normalize<ChalkIr>>>> {
+        let constrained = match self.solve(program, goal)? {
+            Solution::Unique(constrained) => constrained,
+            Solution::Ambig(_) => return None,
+        };
+        let binders = constrained.binders;
+        let subst = constrained.value.subst;
+        let InEnvironment { environment, goal } =
+            Subst::apply(&subst.parameters, &goal.canonical.value);
+
+        let mut projections = vec![];
+        collect_projections(&goal, 0, &mut projections);
+
+        let normalizations = projections
+            .into_iter()
+            .filter_map(|projection| {
+                let ty = self.normalize(program, &binders, &environment, &projection)?;
+                Some(Normalize { projection, ty })
+            })
+            .collect();
+        Some(Canonical {
+            binders,
+            value: normalizations,
+        })
+    }
+
+    /// Solves `Normalize(projection -> ?U)`, where `projection` is in
+    /// terms of `binders`, returning the value of `?U` if it is unique
+    /// and does not constrain the binders.
+    fn normalize(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        binders: &[ParameterKind<UniverseIndex>],
+        environment: &Environment<ChalkIr>,
+        projection: &ProjectionTy<ChalkIr>,
+    ) -> Option<Ty<ChalkIr>> {
+        // `?U` is bound by one more canonical binder, in the root
+        // universe (it can only name what `projection` names).
+        let num_binders = binders.len();
+        let goal: Goal<ChalkIr> = DomainGoal::Normalize(Normalize {
+            projection: projection.clone(),
+            ty: Ty::BoundVar(num_binders),
+        })
+        .cast();
+        let canonical = Canonical {
+            binders: binders
+                .iter()
+                .cloned()
+                .chain(Some(ParameterKind::Ty(UniverseIndex::ROOT)))
+                .collect(),
+            value: InEnvironment::new(environment, goal),
+        };
+        let u_canonicalized = InferenceTable::new().u_canonicalize(&canonical);
+
+        let subst = match self.solve(program, &u_canonicalized.quantified)? {
+            Solution::Unique(constrained) => constrained,
+            Solution::Ambig(_) => return None,
+        };
+        let (bound, value) = subst.value.subst.parameters.split_at(num_binders);
+        let bound = Substitution {
+            parameters: bound.to_vec(),
+        };
+        if subst.binders.len() != num_binders || !bound.is_identity_subst() {
+            return None;
+        }
+        Some(
+            u_canonicalized
+                .universes
+                .map_from_canonical(value[0].assert_ty_ref()),
+        )
+    }
+}
+
+/// Pushes the projections that `goal` (under `depth` binders) refers
+/// to onto `projections`, shifted out of those binders, skipping those
+/// that mention variables bound within the goal and duplicates.
+/// Negated goals are skipped, as proving them does not normalize
+/// anything.
+fn collect_projections(
+    goal: &Goal<ChalkIr>,
+    depth: usize,
+    projections: &mut Vec<ProjectionTy<ChalkIr>>,
+) {
+    match goal {
+        Goal::Quantified(_, subgoal) => collect_projections(&subgoal.value, depth + 1, projections),
+        Goal::Implies(_, subgoal) => collect_projections(subgoal, depth, projections),
+        Goal::And(left, right) => {
+            collect_projections(left, depth, projections);
+            collect_projections(right, depth, projections);
+        }
+        Goal::Not(_) | Goal::CannotProve(()) => {}
+        Goal::Leaf(leaf) => {
+            let mut collector = ProjectionCollector {
+                projections: vec![],
+            };
+            match leaf {
+                LeafGoal::DomainGoal(DomainGoal::Normalize(normalize)) => {
+                    collector.push(&normalize.projection, depth);
+                }
+                LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::ProjectionEq(eq))) => {
+                    collector.push(&eq.projection, depth);
+                }
+                _ => {}
+            }
+            leaf.fold_with(&mut collector, depth)
+                .expect("ProjectionCollector is infallible");
+            for projection in collector.projections {
+                if !projections.contains(&projection) {
+                    projections.push(projection);
+                }
+            }
+        }
+    }
+}
+
+struct ProjectionCollector {
+    projections: Vec<ProjectionTy<ChalkIr>>,
+}
+
+impl ProjectionCollector {
+    fn push(&mut self, projection: &ProjectionTy<ChalkIr>, binders: usize) {
+        if let Ok(projection) = projection.shifted_out(binders) {
+            if !self.projections.contains(&projection) {
+                self.projections.push(projection);
+            }
+        }
+    }
+}
+
+impl TypeFolder<ChalkIr> for ProjectionCollector {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        if let Ty::Projection(projection) = ty {
+            self.push(projection, binders);
+        }
+        fold::super_fold_ty(self, ty, binders)
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        fold::super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for ProjectionCollector {}
+
+impl DefaultInferenceFolder for ProjectionCollector {}
+
+impl DefaultPlaceholderFolder for ProjectionCollector {}
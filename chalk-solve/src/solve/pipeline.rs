@@ -0,0 +1,205 @@
+//! The goal pipeline: the passes that rewrite a root goal before the
+//! solver creates a table for it. Lowerings that want to run before
+//! solving (and in a defined order relative to one another) are
+//! registered as passes of the `GoalPipeline` of a `SolverChoice`,
+//! rather than each being applied by hand wherever goals are made.
+
+use crate::ext::GoalExt;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{Fold, Folder};
+use chalk_ir::Goal;
+use std::fmt;
+use std::iter;
+
+/// A rewriting of goals, run by a `GoalPipeline`. A pass must not
+/// change what the goal means: the rewritten goal must have the same
+/// solutions, and the same free variables. (The free variables of a
+/// root goal are the variables of its canonical form, including the
+/// `exists` and `forall` binders that were peeled off it; see
+/// `GoalExt::into_peeled_goal`.)
+///
+/// Passes that only rewrite the types and lifetimes in a goal are
+/// best written as a `Folder`, and wrapped in a `FolderPass`.
+pub trait GoalPass: Send + Sync {
+    /// The name of the pass, which identifies it: pipelines are
+    /// compared by the names of their passes.
+    fn name(&self) -> &'static str;
+
+    /// Returns the rewritten `goal`.
+    fn run(&self, goal: &Goal<ChalkIr>) -> Goal<ChalkIr>;
+}
+
+/// The pass that puts goals into prenex form (see
+/// `GoalExt::into_prenex`), so that goals which differ only in where
+/// their binders are written share their tables.
+#[derive(Copy, Clone, Debug)]
+pub struct Prenex;
+
+impl GoalPass for Prenex {
+    fn name(&self) -> &'static str {
+        "prenex"
+    }
+
+    fn run(&self, goal: &Goal<ChalkIr>) -> Goal<ChalkIr> {
+        goal.clone().into_prenex()
+    }
+}
+
+/// A pass that folds the goal with the folder that `make_folder`
+/// returns (a fresh one for each goal, so that folders may keep
+/// state).
+///
+/// # Panics
+///
+/// `run` panics if the folder fails: a pass has no way to report an
+/// error, so its folder must not have one.
+pub struct FolderPass<F> {
+    name: &'static str,
+    make_folder: F,
+}
+
+impl<F> FolderPass<F> {
+    pub fn new(name: &'static str, make_folder: F) -> Self {
+        FolderPass { name, make_folder }
+    }
+}
+
+impl<F, Fo> GoalPass for FolderPass<F>
+where
+    F: Fn() -> Fo + Send + Sync,
+    Fo: Folder<ChalkIr>,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn run(&self, goal: &Goal<ChalkIr>) -> Goal<ChalkIr> {
+        let mut folder = (self.make_folder)();
+        goal.fold_with(&mut folder, 0)
+            .unwrap_or_else(|_| panic!("goal pass `{}` failed on {:?}", self.name, goal))
+    }
+}
+
+/// The passes that a solver runs, in order, on each goal it is asked
+/// to solve. By default there are none: the built-in passes
+/// (`GoalPipeline::STANDARD`) are run by `GoalExt::into_peeled_goal`,
+/// before it peels the goal, and running them again on the peeled
+/// goal can change how it interacts with the solver's limits (e.g.,
+/// prenexing moves more of a goal under a `forall`, where the
+/// universe limit may cut it off). Passes are added with `then`:
+///
+/// ```
+/// # use chalk_ir::family::ChalkIr;
+/// # use chalk_ir::Goal;
+/// # use chalk_solve::{GoalPass, GoalPipeline, SlgOptions, SolverChoice};
+/// struct Identity;
+///
+/// impl GoalPass for Identity {
+///     fn name(&self) -> &'static str {
+///         "identity"
+///     }
+///
+///     fn run(&self, goal: &Goal<ChalkIr>) -> Goal<ChalkIr> {
+///         goal.clone()
+///     }
+/// }
+///
+/// let pipeline = GoalPipeline::STANDARD.then(&Identity);
+/// let choice = SolverChoice::slg_with(SlgOptions::default().goal_pipeline(pipeline));
+/// ```
+///
+/// The passes are `'static` so that `SolverChoice` stays `Copy`; two
+/// pipelines are equal if they run passes of the same names in the
+/// same order. As with `ClauseFilter`, the answers of a solver are
+/// cached, so its passes must always rewrite a goal the same way.
+#[derive(Copy, Clone)]
+pub struct GoalPipeline {
+    passes: &'static [&'static dyn GoalPass],
+}
+
+impl GoalPipeline {
+    /// The built-in passes, which `GoalExt::into_peeled_goal` runs:
+    /// `Prenex`.
+    pub const STANDARD: GoalPipeline = GoalPipeline { passes: &[&Prenex] };
+
+    /// The pipeline that leaves goals as they are.
+    pub const EMPTY: GoalPipeline = GoalPipeline { passes: &[] };
+
+    /// The pipeline that runs `passes`, in order.
+    pub fn new(passes: &'static [&'static dyn GoalPass]) -> Self {
+        GoalPipeline { passes }
+    }
+
+    /// The pipeline that runs the passes of `self` and then `pass`.
+    /// The new list of passes is leaked, so pipelines are best built
+    /// once, when the solver choice is.
+    pub fn then(self, pass: &'static dyn GoalPass) -> Self {
+        let passes: Vec<_> = self
+            .passes
+            .iter()
+            .cloned()
+            .chain(iter::once(pass))
+            .collect();
+        GoalPipeline {
+            passes: Box::leak(passes.into_boxed_slice()),
+        }
+    }
+
+    pub fn passes(&self) -> &'static [&'static dyn GoalPass] {
+        self.passes
+    }
+
+    /// Runs the passes on `goal`.
+    pub fn run(&self, goal: &Goal<ChalkIr>) -> Goal<ChalkIr> {
+        let mut goal = goal.clone();
+        for pass in self.passes {
+            goal = pass.run(&goal);
+            debug!("GoalPipeline: after `{}`: {:?}", pass.name(), goal);
+        }
+        goal
+    }
+
+    fn names(&self) -> impl Iterator<Item = &'static str> {
+        self.passes.iter().map(|pass| pass.name())
+    }
+}
+
+impl Default for GoalPipeline {
+    fn default() -> Self {
+        GoalPipeline::EMPTY
+    }
+}
+
+impl fmt::Debug for GoalPipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+impl PartialEq for GoalPipeline {
+    fn eq(&self, other: &Self) -> bool {
+        self.names().eq(other.names())
+    }
+}
+
+impl Eq for GoalPipeline {}
+
+impl PartialOrd for GoalPipeline {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GoalPipeline {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.names().cmp(other.names())
+    }
+}
+
+impl std::hash::Hash for GoalPipeline {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        for name in self.names() {
+            name.hash(state);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Solving goals ahead of time (see `Solver::prewarm`), so that the
+//! queries an embedder knows will come up repeatedly -- e.g., `T:
+//! Sized` for each of the types of a crate -- find their tables
+//! already complete.
+
+use crate::ext;
+use crate::solve::Solver;
+use crate::RustIrDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Asks a `Solver::prewarm` to stop early. Clones share the same flag,
+/// so the prewarming can be cancelled from another thread than the
+/// one doing it (e.g., when the user starts typing again).
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels every prewarming that uses this token (or a clone of
+    /// it). This cannot be undone.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Returned by `Solver::prewarm` if it was cancelled before it solved
+/// all of the goals.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cancelled {
+    /// The number of goals that were solved before the cancellation.
+    pub solved: usize,
+}
+
+impl Solver {
+    /// Solves each of `goals` and keeps the results in the cached
+    /// state of the solver, so that solving them again later (or goals
+    /// that have them as subgoals) is cheap. The solutions themselves
+    /// are not returned.
+    ///
+    /// The goals are solved smallest first, as in `ext::solve_batch`:
+    /// smaller goals are often subgoals of larger ones, whose tables
+    /// are then already complete when the larger goal needs them.
+    ///
+    /// `cancel` is checked before each goal; once it is cancelled, the
+    /// remaining goals are skipped and `Err(Cancelled)` is returned.
+    /// The goals solved up to then stay cached. A goal that needs an
+    /// item `program` cannot supply is skipped, as with `try_solve`
+    /// (which also discards the cached state).
+    ///
+    /// Since a `Solver` is `Send`, this may be run on a background
+    /// thread, with the solver handed back to the thread that answers
+    /// queries once it is done.
+    pub fn prewarm(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goals: &[UCanonical<InEnvironment<Goal<ChalkIr>>>],
+        cancel: &CancellationToken,
+    ) -> Result<(), Cancelled> {
+        let mut order: Vec<usize> = (0..goals.len()).collect();
+        order.sort_by_key(|&i| ext::size(&goals[i].canonical.value.goal));
+
+        for (solved, i) in order.into_iter().enumerate() {
+            if cancel.is_cancelled() {
+                return Err(Cancelled { solved });
+            }
+            let _ = self.try_solve(program, &goals[i]);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,209 @@
+//! A solver that databases can query from within their own callbacks
+//! (see `ReentrantSolver`).
+
+use super::{Guidance, Solution, Solver, SolverChoice};
+use crate::RustIrDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::rc::Rc;
+
+/// The number of queries that may be nested inside one another
+/// before `ReentrantSolver::solve` gives up (default).
+const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// A solver that may be used re-entrantly: a database can call
+/// `solve` on it from within one of its callbacks (e.g., to expand
+/// trait aliases in `impls_for_trait`), while an outer `solve` on the
+/// same `ReentrantSolver` is still in progress.
+///
+/// An SLG forest cannot be entered again while a query is running on
+/// it, so each level of nesting gets a solver of its own. These
+/// solvers are kept, with their caches, for the lifetime of the
+/// `ReentrantSolver`: all queries at the top level share one cache,
+/// all queries nested directly inside them another, and so on.
+///
+/// A nested query for a goal that one of the enclosing queries is
+/// still solving would depend on its own answer. Such cycles yield an
+/// ambiguous solution rather than recursing forever. So does nesting
+/// deeper than `max_depth`, unless the embedder decides otherwise
+/// (see `on_overflow`).
+///
+/// The solver is meant to be used from a single thread, so it is not
+/// `Sync`.
+pub struct ReentrantSolver {
+    choice: SolverChoice,
+    max_depth: Cell<usize>,
+
+    /// The solver for each level of nesting, created on demand.
+    levels: RefCell<Vec<Rc<RefCell<Solver>>>>,
+
+    /// The goals of the queries in progress, outermost first.
+    active: RefCell<Vec<UCanonical<InEnvironment<Goal<ChalkIr>>>>>,
+
+    /// Decides what to do when `max_depth` is reached; see
+    /// `on_overflow`.
+    overflow_handler: Option<Box<OverflowHandler>>,
+
+    /// Set when the overflow handler aborts, until the outermost query
+    /// returns.
+    aborted: Cell<Option<Overflow>>,
+}
+
+/// The callback of `ReentrantSolver::on_overflow`, given the depth
+/// reached and the goal that would nest deeper.
+pub type OverflowHandler =
+    dyn Fn(usize, &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> OverflowAction;
+
+/// What a `ReentrantSolver` does with a query that would nest deeper
+/// than its `max_depth`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowAction {
+    /// The query is not solved, and comes out as ambiguous (the
+    /// default).
+    Ambiguous,
+
+    /// `max_depth` is raised by the given amount, and the query is
+    /// solved after all.
+    IncreaseDepth(usize),
+
+    /// The query comes out as ambiguous, and so does every enclosing
+    /// one, with `try_solve` reporting the overflow.
+    Abort,
+}
+
+/// The error of `ReentrantSolver::try_solve` when the overflow handler
+/// aborted: queries were nested `depth` deep.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Overflow {
+    pub depth: usize,
+}
+
+impl fmt::Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "queries nested {} deep", self.depth)
+    }
+}
+
+impl ReentrantSolver {
+    pub fn new(choice: SolverChoice) -> Self {
+        ReentrantSolver {
+            choice,
+            max_depth: Cell::new(DEFAULT_MAX_DEPTH),
+            levels: RefCell::new(vec![]),
+            active: RefCell::new(vec![]),
+            overflow_handler: None,
+            aborted: Cell::new(None),
+        }
+    }
+
+    /// Sets the number of queries that may be nested inside one
+    /// another (default: 8).
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        self.max_depth.set(max_depth);
+        self
+    }
+
+    /// Sets the callback that decides what to do with a query that
+    /// would nest deeper than `max_depth` (see `OverflowAction`). By
+    /// default, such queries come out as ambiguous.
+    pub fn on_overflow(
+        self,
+        handler: impl Fn(usize, &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> OverflowAction + 'static,
+    ) -> Self {
+        ReentrantSolver {
+            overflow_handler: Some(Box::new(handler)),
+            ..self
+        }
+    }
+
+    /// Returns the number of queries currently in progress (0 outside
+    /// of any query).
+    pub fn depth(&self) -> usize {
+        self.active.borrow().len()
+    }
+
+    /// Solves `goal`, as `Solver::solve` does. May be called while
+    /// another query on this solver is in progress.
+    ///
+    /// Returns `Some(Solution::Ambig(Guidance::Unknown))` without
+    /// solving anything if `goal` is already being solved by an
+    /// enclosing query, or if `max_depth` queries are in progress (and
+    /// the overflow handler does not raise it).
+    pub fn solve(
+        &self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Option<Solution> {
+        self.try_solve(program, goal)
+            .unwrap_or(Some(Solution::Ambig(Guidance::Unknown)))
+    }
+
+    /// Like `solve`, but returns an error if the overflow handler
+    /// aborted this query or one nested inside it. The caches of the
+    /// solver are then discarded once the outermost query returns, as
+    /// they may hold answers that were cut off; the solver can be used
+    /// again afterwards.
+    pub fn try_solve(
+        &self,
+        program: &dyn RustIrDatabase,
+        goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>,
+    ) -> Result<Option<Solution>, Overflow> {
+        let depth = self.depth();
+        if let Some(overflow) = self.aborted.get() {
+            return Err(overflow);
+        }
+        if self.active.borrow().contains(goal) {
+            debug!("ReentrantSolver::solve: cycle at depth {}", depth);
+            return Ok(Some(Solution::Ambig(Guidance::Unknown)));
+        }
+        if depth >= self.max_depth.get() {
+            debug!("ReentrantSolver::solve: overflow at depth {}", depth);
+            let action = match &self.overflow_handler {
+                Some(handler) => handler(depth, goal),
+                None => OverflowAction::Ambiguous,
+            };
+            match action {
+                OverflowAction::Ambiguous => return Ok(Some(Solution::Ambig(Guidance::Unknown))),
+                OverflowAction::IncreaseDepth(extra) => {
+                    self.max_depth.set(self.max_depth.get() + extra.max(1));
+                }
+                OverflowAction::Abort => {
+                    let overflow = Overflow { depth };
+                    self.aborted.set(Some(overflow));
+                    return Err(overflow);
+                }
+            }
+        }
+
+        let solver = {
+            let mut levels = self.levels.borrow_mut();
+            if levels.len() == depth {
+                levels.push(Rc::new(RefCell::new(self.choice.into_solver())));
+            }
+            levels[depth].clone()
+        };
+
+        self.active.borrow_mut().push(goal.clone());
+        let solution = solver.borrow_mut().solve(program, goal);
+        self.active.borrow_mut().pop();
+
+        match self.aborted.get() {
+            None => Ok(solution),
+            Some(overflow) => {
+                if depth == 0 {
+                    self.aborted.set(None);
+                    self.levels.borrow_mut().clear();
+                }
+                Err(overflow)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ReentrantSolver {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "ReentrantSolver {{ depth: {} }}", self.depth())
+    }
+}
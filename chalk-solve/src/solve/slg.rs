@@ -1,13 +1,21 @@
-use crate::clauses::program_clauses_for_goal;
-use crate::coinductive_goal::IsCoinductive;
+use crate::clauses::{
+    can_prune_well_formed, program_clauses_revealing, program_clauses_with_origins,
+    prune_well_formed, ClauseOrigin,
+};
+use crate::coinductive_goal::{CoinductiveGoals, IsCoinductive};
 use crate::infer::ucanonicalize::{UCanonicalized, UniverseMap};
 use crate::infer::unify::UnificationResult;
 use crate::infer::InferenceTable;
+use crate::solve::memory::approx_size;
 use crate::solve::truncate::{self, Truncated};
-use crate::solve::Solution;
-use crate::RustIrDatabase;
+use crate::solve::{
+    AmbiguityCause, CannotProvePolicy, ClauseFilter, SlgOptions, Solution, SolveMode,
+};
+use crate::split::Split;
+use crate::{MissingData, RustIrDatabase};
 use chalk_engine::context::Floundered;
 use chalk_engine::fallible::Fallible;
+use chalk_ir::arena::EnvironmentArena;
 use chalk_ir::cast::Cast;
 use chalk_ir::cast::Caster;
 use chalk_ir::could_match::CouldMatch;
@@ -17,26 +25,152 @@ use chalk_ir::*;
 use chalk_engine::context;
 use chalk_engine::hh::HhGoal;
 use chalk_engine::{Answer, ExClause, Literal};
+use chalk_rust_ir::LangItem;
 
+use std::cell::Cell;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 mod aggregate;
 mod resolvent;
 
+pub(crate) use self::aggregate::combine_solutions;
+
 #[derive(Clone, Debug)]
 pub(crate) struct SlgContext {
     max_size: usize,
+    max_universes: usize,
+    unique_modulo_lifetimes: bool,
+    coinductive_goals: CoinductiveGoals,
+    solve_mode: SolveMode,
+    prune_well_formed: bool,
+    cannot_prove: CannotProvePolicy,
+    prefer_cached_subgoals: bool,
+    clause_filter: Option<ClauseFilter>,
+
+    /// Shared with the inference tables, which update the counters as
+    /// they go.
+    counters: Arc<SlgCounters>,
+
+    /// The environments of the goals being solved, interned so that
+    /// equal environments share their clauses and compare by pointer.
+    /// Shared with the inference tables, which intern the environments
+    /// that `add_clauses` makes.
+    environments: Arc<Mutex<EnvironmentArena<ChalkIr>>>,
+}
+
+/// Counters about the work done while solving, which the inference
+/// tables update (see `SolveStats`).
+#[derive(Debug, Default)]
+pub(crate) struct SlgCounters {
+    /// The largest number of universes any inference table has
+    /// reached.
+    universes_seen: AtomicUsize,
+
+    /// The number of subgoals dropped because another subgoal of the
+    /// same ex-clause was identical.
+    duplicate_subgoals: AtomicUsize,
+
+    /// The number of times a `Normalize` subgoal was passed over, so
+    /// that another subgoal would be selected first.
+    deferred_projections: AtomicUsize,
+
+    /// The number of `WellFormed` conditions pruned from program
+    /// clauses.
+    pruned_well_formed: AtomicUsize,
+
+    /// The number of times program clauses were assembled for a
+    /// domain goal.
+    clause_sets: AtomicUsize,
 }
 
 impl SlgContext {
-    pub(crate) fn new(max_size: usize) -> SlgContext {
-        SlgContext { max_size }
+    pub(crate) fn new(options: &SlgOptions) -> SlgContext {
+        SlgContext {
+            max_size: options.max_size,
+            max_universes: options.max_universes,
+            unique_modulo_lifetimes: options.unique_modulo_lifetimes,
+            coinductive_goals: options.coinductive_goals,
+            solve_mode: options.solve_mode,
+            prune_well_formed: options.prune_well_formed,
+            cannot_prove: options.cannot_prove,
+            prefer_cached_subgoals: options.prefer_cached_subgoals,
+            clause_filter: options.clause_filter,
+            counters: Arc::new(SlgCounters::default()),
+            environments: Arc::new(Mutex::new(EnvironmentArena::new())),
+        }
+    }
+
+    /// Returns a copy of `environment` that shares its clauses with
+    /// every equal environment interned so far.
+    pub(crate) fn intern_environment(
+        &self,
+        environment: Environment<ChalkIr>,
+    ) -> Environment<ChalkIr> {
+        self.environments.lock().unwrap().intern(environment)
+    }
+
+    /// Forgets the interned environments; called whenever the tables
+    /// that hold them are dropped.
+    pub(crate) fn clear_environments(&self) {
+        self.environments.lock().unwrap().clear();
+    }
+
+    pub(crate) fn solve_mode(&self) -> SolveMode {
+        self.solve_mode
+    }
+
+    pub(crate) fn coinductive_goals(&self) -> CoinductiveGoals {
+        self.coinductive_goals
+    }
+
+    /// Returns the largest number of universes created while solving
+    /// so far.
+    pub(crate) fn universes_seen(&self) -> usize {
+        self.counters.universes_seen.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of subgoals dropped as duplicates so far.
+    pub(crate) fn duplicate_subgoals(&self) -> usize {
+        self.counters.duplicate_subgoals.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times a `Normalize` subgoal was deferred
+    /// so far.
+    pub(crate) fn deferred_projections(&self) -> usize {
+        self.counters.deferred_projections.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of `WellFormed` conditions pruned from
+    /// program clauses so far.
+    pub(crate) fn pruned_well_formed(&self) -> usize {
+        self.counters.pruned_well_formed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of times program clauses were assembled so
+    /// far.
+    pub(crate) fn clause_sets(&self) -> usize {
+        self.counters.clause_sets.load(Ordering::Relaxed)
     }
 
     pub(crate) fn ops<'p>(&self, program: &'p dyn RustIrDatabase) -> SlgContextOps<'p> {
         SlgContextOps {
             program,
             max_size: self.max_size,
+            max_universes: self.max_universes,
+            unique_modulo_lifetimes: self.unique_modulo_lifetimes,
+            coinductive_goals: self.coinductive_goals,
+            solve_mode: self.solve_mode,
+            prune_well_formed: self.prune_well_formed,
+            cannot_prove: self.cannot_prove,
+            prefer_cached_subgoals: self.prefer_cached_subgoals,
+            clause_filter: self.clause_filter,
+            counters: self.counters.clone(),
+            environments: self.environments.clone(),
+            missing_data: Cell::new(None),
+            ambiguity_cause: Cell::new(None),
+            caveat: Cell::new(false),
         }
     }
 }
@@ -45,10 +179,63 @@ impl SlgContext {
 pub(crate) struct SlgContextOps<'me> {
     program: &'me dyn RustIrDatabase,
     max_size: usize,
+    max_universes: usize,
+    unique_modulo_lifetimes: bool,
+    coinductive_goals: CoinductiveGoals,
+    solve_mode: SolveMode,
+    prune_well_formed: bool,
+    cannot_prove: CannotProvePolicy,
+    prefer_cached_subgoals: bool,
+    clause_filter: Option<ClauseFilter>,
+    counters: Arc<SlgCounters>,
+    environments: Arc<Mutex<EnvironmentArena<ChalkIr>>>,
+
+    /// Set if clause generation needed an item that `program` could
+    /// not supply. The goal that needed it is treated as floundered,
+    /// and the solver reports the error once the engine returns.
+    missing_data: Cell<Option<MissingData>>,
+
+    /// Why the last solution made was ambiguous, if it was.
+    ambiguity_cause: Cell<Option<AmbiguityCause>>,
+
+    /// Whether the last solution made was `Unique` only because a
+    /// `CannotProve` subgoal was taken to hold.
+    caveat: Cell<bool>,
+}
+
+impl SlgContextOps<'_> {
+    /// Returns the first item that `program` failed to supply while
+    /// solving, if any.
+    pub(crate) fn missing_data(&self) -> Option<MissingData> {
+        self.missing_data.get()
+    }
+
+    /// Returns why the solution to the goal was ambiguous, if it was.
+    pub(crate) fn ambiguity_cause(&self) -> Option<AmbiguityCause> {
+        self.ambiguity_cause.get()
+    }
+
+    /// Returns whether the solution to the goal relies on a
+    /// `CannotProve` subgoal being taken to hold.
+    pub(crate) fn caveat(&self) -> bool {
+        self.caveat.get()
+    }
+
+    fn record_missing_data(&self, missing: MissingData) -> Floundered {
+        debug!("program_clauses: {}", missing);
+        if self.missing_data.get().is_none() {
+            self.missing_data.set(Some(missing));
+        }
+        Floundered
+    }
 }
 
 pub struct TruncatingInferenceTable {
     max_size: usize,
+    max_universes: usize,
+    cannot_prove: CannotProvePolicy,
+    counters: Arc<SlgCounters>,
+    environments: Arc<Mutex<EnvironmentArena<ChalkIr>>>,
     infer: InferenceTable,
 }
 
@@ -63,7 +250,7 @@ impl context::Context for SlgContext {
     type Environment = Environment<ChalkIr>;
     type DomainGoal = DomainGoal<ChalkIr>;
     type Goal = Goal<ChalkIr>;
-    type BindersGoal = Binders<Box<Goal<ChalkIr>>>;
+    type BindersGoal = Binders<Arc<Goal<ChalkIr>>>;
     type Parameter = Parameter<ChalkIr>;
     type ProgramClause = ProgramClause<ChalkIr>;
     type ProgramClauses = Vec<ProgramClause<ChalkIr>>;
@@ -113,6 +300,18 @@ impl context::Context for SlgContext {
         u_canon.universes
     }
 
+    fn approx_goal_size(u_canon: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> usize {
+        approx_size(&u_canon.canonical)
+    }
+
+    fn approx_subst_size(subst: &Canonical<ConstrainedSubst<ChalkIr>>) -> usize {
+        approx_size(subst)
+    }
+
+    fn approx_ex_clause_size(ex_clause: &Canonical<ExClause<SlgContext>>) -> usize {
+        approx_size(ex_clause)
+    }
+
     fn map_goal_from_canonical(
         map: &UniverseMap,
         value: &Canonical<InEnvironment<Goal<ChalkIr>>>,
@@ -130,7 +329,7 @@ impl context::Context for SlgContext {
 
 impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
     fn is_coinductive(&self, goal: &UCanonical<InEnvironment<Goal<ChalkIr>>>) -> bool {
-        goal.is_coinductive(self.program)
+        goal.is_coinductive(self.program, &self.coinductive_goals)
     }
 
     fn identity_constrained_subst(
@@ -146,18 +345,49 @@ impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
             .quantified
     }
 
+    /// The engine calls this once per table, when it creates the
+    /// table's initial strands; iterating a cycle to a fixed point
+    /// reuses those strands, so the clauses need no cache of their own
+    /// (see `SolveStats::clause_sets`).
     fn program_clauses(
         &self,
         environment: &Environment<ChalkIr>,
         goal: &DomainGoal<ChalkIr>,
         infer: &mut TruncatingInferenceTable,
     ) -> Result<Vec<ProgramClause<ChalkIr>>, Floundered> {
+        // Custom goals are decided by the database, which must not
+        // see inference variables.
+        let normalized_goal;
+        let goal = match goal {
+            DomainGoal::Custom(_) => {
+                normalized_goal = infer.infer.normalize_deep(goal);
+                if !infer
+                    .infer
+                    .canonicalize(&normalized_goal)
+                    .quantified
+                    .binders
+                    .is_empty()
+                {
+                    return Err(Floundered);
+                }
+                &normalized_goal
+            }
+            _ => goal,
+        };
+
         // Look for floundering goals:
         match goal {
-            // Check for a goal like `?T: Foo` where `Foo` is not enumerable.
+            // Check for a goal like `?T: Foo` where `Foo` is not enumerable
+            // (`Sized` never is).
             DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => {
-                let trait_datum = self.program.trait_datum(trait_ref.trait_id);
-                if trait_datum.is_non_enumerable_trait() || trait_datum.is_auto_trait() {
+                let trait_datum = self
+                    .program
+                    .try_trait_datum(trait_ref.trait_id)
+                    .map_err(|missing| self.record_missing_data(missing))?;
+                let is_sized = self.program.lang_item(LangItem::Sized)
+                    == Some(TypeKindId::TraitId(trait_ref.trait_id));
+                if trait_datum.is_non_enumerable_trait() || trait_datum.is_auto_trait() || is_sized
+                {
                     let self_ty = trait_ref.self_type_parameter().unwrap();
                     if let Some(v) = self_ty.inference_var() {
                         if !infer.infer.var_is_bound(v) {
@@ -176,20 +406,94 @@ impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
                 _ => {}
             },
 
+            // We can only pick the structural rule once we know both
+            // types.
+            DomainGoal::LayoutCompatible(LayoutCompatible { a, b }) => {
+                if let (Ty::InferenceVar(_), _) | (_, Ty::InferenceVar(_)) = (a, b) {
+                    return Err(Floundered);
+                }
+            }
+
+            // Likewise, the clauses for upcasting are specific to the
+            // bounds of both `dyn` types.
+            DomainGoal::Upcast(Upcast { source, target }) => {
+                if let (Ty::InferenceVar(_), _) | (_, Ty::InferenceVar(_)) = (source, target) {
+                    return Err(Floundered);
+                }
+            }
+
             _ => {}
         }
 
-        let mut clauses: Vec<_> = program_clauses_for_goal(self.program, environment, goal);
+        self.counters.clause_sets.fetch_add(1, Ordering::Relaxed);
+        let mut clauses: Vec<_> = match self.solve_mode {
+            // In revealing mode, only the most specialized impl is used,
+            // which can only be decided once the types are known.
+            SolveMode::Revealing => {
+                let (clauses, specialized) =
+                    program_clauses_revealing(self.program, environment, goal)
+                        .map_err(|missing| self.record_missing_data(missing))?;
+                if specialized {
+                    let trait_ref = match goal {
+                        DomainGoal::Normalize(normalize) => self
+                            .program
+                            .try_trait_ref_from_projection(&normalize.projection)
+                            .map_err(|missing| self.record_missing_data(missing))?,
+                        DomainGoal::Holds(WhereClause::Implemented(trait_ref)) => trait_ref.clone(),
+                        _ => unreachable!("specialized clauses for {:?}", goal),
+                    };
+                    let trait_ref = infer.infer.normalize_deep(&trait_ref);
+                    if !infer
+                        .infer
+                        .canonicalize(&trait_ref)
+                        .quantified
+                        .binders
+                        .is_empty()
+                    {
+                        return Err(Floundered);
+                    }
+                }
+                clauses
+            }
+            _ => program_clauses_with_origins(self.program, environment, goal)
+                .map_err(|missing| self.record_missing_data(missing))?,
+        };
+
+        if self.prune_well_formed && can_prune_well_formed(self.program, environment) {
+            let mut pruned = 0;
+            for (clause, _) in &mut clauses {
+                pruned += prune_well_formed(self.program, clause)
+                    .map_err(|missing| self.record_missing_data(missing))?;
+            }
+            self.counters
+                .pruned_well_formed
+                .fetch_add(pruned, Ordering::Relaxed);
+        }
+
+        // In intercrate mode, the compatible modality is always on.
+        if let (SolveMode::Intercrate, DomainGoal::Compatible(())) = (self.solve_mode, goal) {
+            clauses.push((
+                ProgramClause::Implies(ProgramClauseImplication {
+                    consequence: DomainGoal::Compatible(()),
+                    conditions: vec![],
+                }),
+                ClauseOrigin::BuiltIn("compatible"),
+            ));
+        }
 
         clauses.extend(
             environment
                 .clauses
                 .iter()
                 .filter(|&env_clause| env_clause.could_match(goal))
-                .cloned(),
+                .map(|env_clause| (env_clause.clone(), ClauseOrigin::Environment)),
         );
 
-        Ok(clauses)
+        if let Some(clause_filter) = self.clause_filter {
+            clauses.retain(|(clause, origin)| clause_filter.keeps(goal, clause, *origin));
+        }
+
+        Ok(clauses.into_iter().map(|(clause, _)| clause).collect())
     }
 
     fn instantiate_ucanonical_goal<R>(
@@ -202,9 +506,17 @@ impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
             Goal<ChalkIr>,
         ) -> R,
     ) -> R {
-        let (infer, subst, InEnvironment { environment, goal }) =
+        let (mut infer, subst, InEnvironment { environment, goal }) =
             InferenceTable::from_canonical(arg.universes, &arg.canonical);
-        let infer_table = TruncatingInferenceTable::new(self.max_size, infer);
+        infer.set_region_constraint_resolver(self.program.region_constraint_resolver());
+        let infer_table = TruncatingInferenceTable::new(
+            self.max_size,
+            self.max_universes,
+            self.cannot_prove,
+            self.counters.clone(),
+            self.environments.clone(),
+            infer,
+        );
         op(infer_table, subst, environment, goal)
     }
 
@@ -214,9 +526,17 @@ impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
         canonical_ex_clause: &Canonical<ExClause<SlgContext>>,
         op: impl FnOnce(TruncatingInferenceTable, ExClause<SlgContext>) -> R,
     ) -> R {
-        let (infer, _subst, ex_cluse) =
+        let (mut infer, _subst, ex_cluse) =
             InferenceTable::from_canonical(num_universes, canonical_ex_clause);
-        let infer_table = TruncatingInferenceTable::new(self.max_size, infer);
+        infer.set_region_constraint_resolver(self.program.region_constraint_resolver());
+        let infer_table = TruncatingInferenceTable::new(
+            self.max_size,
+            self.max_universes,
+            self.cannot_prove,
+            self.counters.clone(),
+            self.environments.clone(),
+            infer,
+        );
         op(infer_table, ex_cluse)
     }
 
@@ -227,11 +547,38 @@ impl<'me> context::ContextOps<SlgContext> for SlgContextOps<'me> {
         let Answer { subst, .. } = answer;
         subst
     }
+
+    fn prefer_cached_subgoals(&self) -> bool {
+        self.prefer_cached_subgoals
+    }
 }
 
 impl TruncatingInferenceTable {
-    fn new(max_size: usize, infer: InferenceTable) -> Self {
-        Self { max_size, infer }
+    fn new(
+        max_size: usize,
+        max_universes: usize,
+        cannot_prove: CannotProvePolicy,
+        counters: Arc<SlgCounters>,
+        environments: Arc<Mutex<EnvironmentArena<ChalkIr>>>,
+        infer: InferenceTable,
+    ) -> Self {
+        let table = Self {
+            max_size,
+            max_universes,
+            cannot_prove,
+            counters,
+            environments,
+            infer,
+        };
+        table.record_universes();
+        table
+    }
+
+    fn record_universes(&self) {
+        let universes = self.infer.max_universe().counter + 1;
+        self.counters
+            .universes_seen
+            .fetch_max(universes, Ordering::Relaxed);
     }
 }
 
@@ -265,11 +612,14 @@ impl context::InferenceTable<SlgContext> for TruncatingInferenceTable {
         match goal {
             Goal::Quantified(QuantifierKind::ForAll, binders_goal) => HhGoal::ForAll(binders_goal),
             Goal::Quantified(QuantifierKind::Exists, binders_goal) => HhGoal::Exists(binders_goal),
-            Goal::Implies(dg, subgoal) => HhGoal::Implies(dg, *subgoal),
-            Goal::And(g1, g2) => HhGoal::And(*g1, *g2),
-            Goal::Not(g1) => HhGoal::Not(*g1),
+            Goal::Implies(dg, subgoal) => HhGoal::Implies(dg, (*subgoal).clone()),
+            Goal::And(g1, g2) => HhGoal::And((*g1).clone(), (*g2).clone()),
+            Goal::Not(g1) => HhGoal::Not((*g1).clone()),
             Goal::Leaf(LeafGoal::EqGoal(EqGoal { a, b })) => HhGoal::Unify((), a, b),
             Goal::Leaf(LeafGoal::DomainGoal(domain_goal)) => HhGoal::DomainGoal(domain_goal),
+            Goal::Leaf(LeafGoal::TypeOutlives(TypeOutlives { ty, lifetime })) => {
+                HhGoal::Outlives(ty.cast(), lifetime.cast())
+            }
             Goal::CannotProve(()) => HhGoal::CannotProve,
         }
     }
@@ -280,39 +630,95 @@ impl context::InferenceTable<SlgContext> for TruncatingInferenceTable {
         env: &Environment<ChalkIr>,
         clauses: Vec<ProgramClause<ChalkIr>>,
     ) -> Environment<ChalkIr> {
-        Environment::add_clauses(env, clauses)
+        let env = Environment::add_clauses(env, clauses);
+        self.environments.lock().unwrap().intern(env)
     }
 
     fn into_goal(&self, domain_goal: DomainGoal<ChalkIr>) -> Goal<ChalkIr> {
         domain_goal.cast()
     }
 
+    // Used by: logic
+    fn dedup_subgoals(&mut self, ex_clause: &mut ExClause<SlgContext>) {
+        // Compare the subgoals with the values of the inference
+        // variables applied, which is what canonicalizing them as
+        // goals of their own would see.
+        let mut seen: Vec<Literal<SlgContext>> = vec![];
+        let before = ex_clause.subgoals.len();
+        let infer = &mut self.infer;
+        ex_clause.subgoals.retain(|subgoal| {
+            let subgoal = infer.normalize_deep(subgoal);
+            if seen.contains(&subgoal) {
+                debug!("dedup_subgoals: dropping duplicate {:?}", subgoal);
+                false
+            } else {
+                seen.push(subgoal);
+                true
+            }
+        });
+        let removed = before - ex_clause.subgoals.len();
+        self.counters
+            .duplicate_subgoals
+            .fetch_add(removed, Ordering::Relaxed);
+    }
+
     // Used by: logic
     fn next_subgoal_index(&mut self, ex_clause: &ExClause<SlgContext>) -> usize {
-        // For now, we always pick the last subgoal in the
-        // list.
+        // We pick the last subgoal in the list, except that we put
+        // off `Normalize` goals while there are other positive
+        // subgoals: proving those first tends to constrain the
+        // inference variables that the projections mention, which
+        // otherwise have to be enumerated. (`ProjectionEq` goals are
+        // not put off, as the order in which their two ways of being
+        // proven yield answers is significant.)
         //
         // FIXME(rust-lang-nursery/chalk#80) -- we should be more
         // selective. For example, we don't want to pick a
         // negative literal that will flounder, and we don't want
         // to pick things like `?T: Sized` if we can help it.
-        ex_clause.subgoals.len() - 1
+        let last = ex_clause.subgoals.len() - 1;
+        if !is_normalize_literal(&ex_clause.subgoals[last]) {
+            return last;
+        }
+        match ex_clause.subgoals.iter().rposition(|subgoal| {
+            matches!(subgoal, Literal::Positive(_)) && !is_normalize_literal(subgoal)
+        }) {
+            Some(index) => {
+                self.counters
+                    .deferred_projections
+                    .fetch_add(1, Ordering::Relaxed);
+                index
+            }
+            None => last,
+        }
     }
 }
 
 impl context::UnificationOps<SlgContext> for TruncatingInferenceTable {
     fn instantiate_binders_universally(
         &mut self,
-        arg: &Binders<Box<Goal<ChalkIr>>>,
-    ) -> Goal<ChalkIr> {
-        *self.infer.instantiate_binders_universally(arg)
+        arg: &Binders<Arc<Goal<ChalkIr>>>,
+    ) -> Option<Goal<ChalkIr>> {
+        // Instantiating the binders creates a new universe; if that
+        // would take us past the limit, give up on the body instead
+        // (which makes the goal ambiguous).
+        if self.infer.max_universe().counter + 1 >= self.max_universes {
+            debug!(
+                "instantiate_binders_universally: universe limit {} reached",
+                self.max_universes
+            );
+            return None;
+        }
+        let goal = (*self.infer.instantiate_binders_universally(arg)).clone();
+        self.record_universes();
+        Some(goal)
     }
 
     fn instantiate_binders_existentially(
         &mut self,
-        arg: &Binders<Box<Goal<ChalkIr>>>,
+        arg: &Binders<Arc<Goal<ChalkIr>>>,
     ) -> Goal<ChalkIr> {
-        *self.infer.instantiate_binders_existentially(arg)
+        (*self.infer.instantiate_binders_existentially(arg)).clone()
     }
 
     fn debug_ex_clause<'v>(&mut self, value: &'v ExClause<SlgContext>) -> Box<dyn Debug + 'v> {
@@ -366,6 +772,35 @@ impl context::UnificationOps<SlgContext> for TruncatingInferenceTable {
         let result = self.infer.unify(environment, a, b)?;
         Ok(into_ex_clause(result, ex_clause))
     }
+
+    fn outlives_into_ex_clause(
+        &mut self,
+        environment: &Environment<ChalkIr>,
+        a: &Parameter<ChalkIr>,
+        b: &Parameter<ChalkIr>,
+        ex_clause: &mut ExClause<SlgContext>,
+    ) -> Fallible<()> {
+        let result =
+            self.infer
+                .type_outlives(environment, a.assert_ty_ref(), b.assert_lifetime_ref())?;
+        Ok(into_ex_clause(result, ex_clause))
+    }
+
+    fn cannot_prove_holds(&self) -> bool {
+        self.cannot_prove == CannotProvePolicy::Holds
+    }
+}
+
+/// True if `literal` is a positive normalization goal, such as
+/// `Normalize(<T as Iterator>::Item -> U)`.
+fn is_normalize_literal(literal: &Literal<SlgContext>) -> bool {
+    matches!(
+        literal,
+        Literal::Positive(InEnvironment {
+            goal: Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Normalize(_))),
+            ..
+        })
+    )
 }
 
 /// Helper function
@@ -374,6 +809,7 @@ fn into_ex_clause(result: UnificationResult, ex_clause: &mut ExClause<SlgContext
         .subgoals
         .extend(result.goals.into_iter().casted().map(Literal::Positive));
     ex_clause.constraints.extend(result.constraints);
+    ex_clause.ambiguous |= result.ambiguous;
 }
 
 trait SubstitutionExt {
@@ -3,9 +3,14 @@ use crate::infer::InferenceTable;
 use crate::solve::slg::SlgContext;
 use crate::solve::slg::SlgContextOps;
 use crate::solve::slg::SubstitutionExt;
-use crate::solve::{Guidance, Solution};
+use crate::solve::{AmbiguityCause, Guidance, Solution};
+use chalk_engine::fallible::Fallible;
 use chalk_ir::cast::Cast;
 use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{
+    self, DefaultFreeVarFolder, DefaultInferenceFolder, DefaultPlaceholderFolder, Fold, Subst,
+    TypeFolder,
+};
 use chalk_ir::*;
 
 use chalk_engine::context;
@@ -24,13 +29,51 @@ impl<'me> context::AggregateOps<SlgContext> for SlgContextOps<'me> {
         if answers.peek_answer().is_none() {
             return None;
         }
-        let Answer { subst, ambiguous } = answers.next_answer().unwrap();
+        let Answer {
+            subst,
+            ambiguous,
+            caveat,
+        } = answers.next_answer().unwrap();
 
         // Exactly 1 unconditional answer?
         if answers.peek_answer().is_none() && !ambiguous {
+            self.caveat.set(caveat);
             return Some(Solution::Unique(subst));
         }
 
+        // Several unconditional answers that differ only in their
+        // lifetimes? If requested, we collapse them into one. Either
+        // way, the answers we drew are merged into the guidance below.
+        let mut drawn = vec![];
+        if self.unique_modulo_lifetimes && !ambiguous {
+            while drawn.len() < MAX_ANSWERS_MODULO_LIFETIMES {
+                match answers.next_answer() {
+                    Some(answer) => drawn.push(answer),
+                    None => break,
+                }
+            }
+            if answers.peek_answer().is_none() {
+                if let Some(merged) = merge_modulo_lifetimes(root_goal, &subst, &drawn) {
+                    self.caveat
+                        .set(caveat || drawn.iter().any(|answer| answer.caveat));
+                    return Some(Solution::Unique(merged));
+                }
+            }
+        }
+
+        // An answer that is itself ambiguous means that the goal could
+        // not be decided with the types known so far (a floundered goal
+        // yields such answers forever); otherwise there are several
+        // answers, from different candidates.
+        let cause = if ambiguous {
+            AmbiguityCause::InsufficientTypeInfo
+        } else {
+            AmbiguityCause::MultipleCandidates
+        };
+        self.ambiguity_cause.set(Some(cause));
+
+        let mut drawn = drawn.into_iter();
+
         // Otherwise, we either have >1 answer, or else we have
         // ambiguity.  Either way, we are only going to be giving back
         // **guidance**, and with guidance, the caller doesn't get
@@ -42,7 +85,8 @@ impl<'me> context::AggregateOps<SlgContext> for SlgContextOps<'me> {
         // but different region constraints. We should collapse those
         // cases into an `OR` region constraint at some point, but I
         // leave that for future work. This is basically
-        // rust-lang/rust#21974.
+        // rust-lang/rust#21974. (With `unique_modulo_lifetimes`, we
+        // approximate the `OR` with an `AND` above.)
         let mut subst = subst.map(|cs| cs.subst);
 
         // Extract answers and merge them into `subst`. Stop once we have
@@ -52,13 +96,18 @@ impl<'me> context::AggregateOps<SlgContext> for SlgContextOps<'me> {
                 break Guidance::Unknown;
             }
 
+            if let Some(answer1) = drawn.next() {
+                subst = merge_into_guidance(root_goal, subst, &answer1.subst.value.subst);
+                continue;
+            }
+
             if !answers.any_future_answer(|ref mut new_subst| new_subst.may_invalidate(&subst)) {
                 break Guidance::Definite(subst);
             }
 
             match answers.next_answer() {
                 Some(answer1) => {
-                    subst = merge_into_guidance(root_goal, subst, &answer1.subst);
+                    subst = merge_into_guidance(root_goal, subst, &answer1.subst.value.subst);
                 }
 
                 None => {
@@ -71,8 +120,173 @@ impl<'me> context::AggregateOps<SlgContext> for SlgContextOps<'me> {
     }
 }
 
+/// Combines two solutions to `root_goal` into one that covers both,
+/// merging them as `make_solution` merges answers (see
+/// `Solution::combine`).
+pub(crate) fn combine_solutions(
+    root_goal: &Canonical<InEnvironment<Goal<ChalkIr>>>,
+    solution0: Solution,
+    solution1: Solution,
+    unique_modulo_lifetimes: bool,
+) -> Solution {
+    if solution0 == solution1 {
+        return solution0;
+    }
+
+    let (guidance0, guidance1) = match (solution0, solution1) {
+        (Solution::Unique(subst0), Solution::Unique(subst1)) => {
+            if unique_modulo_lifetimes {
+                let answer1 = Answer {
+                    subst: subst1.clone(),
+                    ambiguous: false,
+                    caveat: false,
+                };
+                if let Some(merged) = merge_modulo_lifetimes(root_goal, &subst0, &[answer1]) {
+                    return Solution::Unique(merged);
+                }
+            }
+            (
+                into_guidance(Solution::Unique(subst0)),
+                into_guidance(Solution::Unique(subst1)),
+            )
+        }
+        (solution0, solution1) => (into_guidance(solution0), into_guidance(solution1)),
+    };
+
+    // The guidance is only as strong as the weaker of the two.
+    let (suggested, subst0, subst1) = match (guidance0, guidance1) {
+        (Guidance::Unknown, _) | (_, Guidance::Unknown) => {
+            return Solution::Ambig(Guidance::Unknown);
+        }
+        (Guidance::Definite(subst0), Guidance::Definite(subst1)) => (false, subst0, subst1),
+        (Guidance::Definite(subst0), Guidance::Suggested(subst1))
+        | (Guidance::Suggested(subst0), Guidance::Definite(subst1))
+        | (Guidance::Suggested(subst0), Guidance::Suggested(subst1)) => (true, subst0, subst1),
+    };
+
+    if subst0.value.is_empty() || is_trivial(&subst0) {
+        return Solution::Ambig(Guidance::Unknown);
+    }
+    let merged = merge_into_guidance(root_goal, subst0, &subst1.value);
+    if is_trivial(&merged) {
+        Solution::Ambig(Guidance::Unknown)
+    } else if suggested {
+        Solution::Ambig(Guidance::Suggested(merged))
+    } else {
+        Solution::Ambig(Guidance::Definite(merged))
+    }
+}
+
+/// The guidance that `solution` gives, without its lifetime
+/// constraints.
+fn into_guidance(solution: Solution) -> Guidance {
+    match solution {
+        Solution::Unique(subst) => Guidance::Definite(subst.map(|cs| cs.subst)),
+        Solution::Ambig(guidance) => guidance,
+    }
+}
+
+/// The most answers `merge_modulo_lifetimes` is willing to look at; a
+/// goal with more answers than this is reported as ambiguous.
+const MAX_ANSWERS_MODULO_LIFETIMES: usize = 8;
+
+/// Given the first answer to `root_goal`, and the remaining answers,
+/// returns a single answer that implies all of them, if they are all
+/// unconditional and their substitutions differ only in lifetimes.
+/// The substitution of the first answer is used, with the constraints
+/// of all the answers, plus the constraints that equate the lifetimes
+/// of the other answers with those of the first. E.g., the answers
+/// `?0 := &'a u32` and `?0 := &'b u32` become `?0 := &'a u32` with the
+/// constraint `'a == 'b`.
+fn merge_modulo_lifetimes(
+    root_goal: &Canonical<InEnvironment<Goal<ChalkIr>>>,
+    first: &Canonical<ConstrainedSubst<ChalkIr>>,
+    rest: &[Answer<SlgContext>],
+) -> Option<Canonical<ConstrainedSubst<ChalkIr>>> {
+    if rest.iter().any(|answer| answer.ambiguous) {
+        return None;
+    }
+    let erased = erase_lifetimes(first);
+    if rest
+        .iter()
+        .any(|answer| erase_lifetimes(&answer.subst) != erased)
+    {
+        return None;
+    }
+
+    let mut infer = InferenceTable::new();
+    let ConstrainedSubst {
+        subst,
+        mut constraints,
+    } = infer.instantiate_canonical(first);
+
+    // The root goal's environment, in terms of the answer.
+    let environment = Subst::apply(&subst.parameters, &root_goal.value.environment);
+
+    for answer in rest {
+        let other = infer.instantiate_canonical(&answer.subst);
+        let result = infer
+            .unify(&environment, &subst.parameters, &other.subst.parameters)
+            .ok()?;
+        if !result.goals.is_empty() || result.ambiguous {
+            return None;
+        }
+        for constraint in result.constraints.into_iter().chain(other.constraints) {
+            if !constraints.contains(&constraint) {
+                constraints.push(constraint);
+            }
+        }
+    }
+
+    Some(
+        infer
+            .canonicalize(&ConstrainedSubst { subst, constraints })
+            .quantified,
+    )
+}
+
+/// Returns the substitution of `answer` with all of its lifetimes
+/// replaced by the same placeholder, for comparing answers modulo
+/// lifetimes.
+fn erase_lifetimes(
+    answer: &Canonical<ConstrainedSubst<ChalkIr>>,
+) -> Canonical<Substitution<ChalkIr>> {
+    let mut infer = InferenceTable::new();
+    let subst = infer.instantiate_canonical(answer).subst;
+    let erased = subst
+        .fold_with(&mut LifetimeEraser, 0)
+        .expect("LifetimeEraser is infallible");
+    infer.canonicalize(&erased).quantified
+}
+
+struct LifetimeEraser;
+
+impl TypeFolder<ChalkIr> for LifetimeEraser {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        fold::super_fold_ty(self, ty, binders)
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        _lifetime: &Lifetime<ChalkIr>,
+        _binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        Ok(PlaceholderIndex {
+            ui: UniverseIndex::root(),
+            idx: usize::MAX,
+        }
+        .to_lifetime::<ChalkIr>())
+    }
+}
+
+impl DefaultFreeVarFolder for LifetimeEraser {}
+
+impl DefaultInferenceFolder for LifetimeEraser {}
+
+impl DefaultPlaceholderFolder for LifetimeEraser {}
+
 /// Given a current substitution used as guidance for `root_goal`, and
-/// a new possible answer to `root_goal`, returns a new set of
+/// the substitution of a new possible answer to `root_goal`, returns a new set of
 /// guidance that encompasses both of them. This is often more general
 /// than the old guidance. For example, if we had a guidance of `?0 =
 /// u32` and the new answer is `?0 = i32`, then the guidance would
@@ -80,16 +294,9 @@ impl<'me> context::AggregateOps<SlgContext> for SlgContextOps<'me> {
 fn merge_into_guidance(
     root_goal: &Canonical<InEnvironment<Goal<ChalkIr>>>,
     guidance: Canonical<Substitution<ChalkIr>>,
-    answer: &Canonical<ConstrainedSubst<ChalkIr>>,
+    subst1: &Substitution<ChalkIr>,
 ) -> Canonical<Substitution<ChalkIr>> {
     let mut infer = InferenceTable::new();
-    let Canonical {
-        value: ConstrainedSubst {
-            subst: subst1,
-            constraints: _,
-        },
-        binders: _,
-    } = answer;
 
     // Collect the types that the two substitutions have in
     // common.
@@ -381,3 +588,66 @@ fn vec_x_vs_vec_y() {
     // But this `var 0` is from `infer.
     assert_eq!(ty!(apply (item 0) (infer 0)), ty);
 }
+
+/// A root goal with one type variable, and the solution `?0 := ty`.
+#[cfg(test)]
+fn root_goal_and_unique(ty: Ty<ChalkIr>) -> (Canonical<InEnvironment<Goal<ChalkIr>>>, Solution) {
+    let root_goal = Canonical {
+        binders: vec![ParameterKind::Ty(UniverseIndex::root())],
+        value: InEnvironment::new(&Environment::new(), Goal::CannotProve(())),
+    };
+    let solution = Solution::Unique(Canonical {
+        binders: vec![],
+        value: ConstrainedSubst {
+            subst: Substitution {
+                parameters: vec![ty.cast()],
+            },
+            constraints: vec![],
+        },
+    });
+    (root_goal, solution)
+}
+
+/// Test combining `?0 := Vec<i32>` with `?0 := Vec<u32>`, and with
+/// itself
+#[test]
+fn combine_vec_i32_and_vec_u32() {
+    let (root_goal, vec_i32) = root_goal_and_unique(ty!(apply (item 0) (apply (item 1))));
+    let (_, vec_u32) = root_goal_and_unique(ty!(apply (item 0) (apply (item 2))));
+
+    assert_eq!(
+        combine_solutions(&root_goal, vec_i32.clone(), vec_i32.clone(), false),
+        vec_i32
+    );
+    assert_eq!(
+        combine_solutions(&root_goal, vec_i32.clone(), vec_u32, false),
+        Solution::Ambig(Guidance::Definite(Canonical {
+            binders: vec![ParameterKind::Ty(UniverseIndex::root())],
+            value: Substitution {
+                parameters: vec![ty!(apply (item 0) (bound 0)).cast()],
+            },
+        }))
+    );
+    assert_eq!(
+        combine_solutions(
+            &root_goal,
+            vec_i32,
+            Solution::Ambig(Guidance::Unknown),
+            false
+        ),
+        Solution::Ambig(Guidance::Unknown)
+    );
+}
+
+/// Test combining `?0 := Vec<i32>` with `?0 := u32`, which leaves no
+/// guidance
+#[test]
+fn combine_vec_i32_and_u32() {
+    let (root_goal, vec_i32) = root_goal_and_unique(ty!(apply (item 0) (apply (item 1))));
+    let (_, u32) = root_goal_and_unique(ty!(apply (item 2)));
+
+    assert_eq!(
+        combine_solutions(&root_goal, vec_i32, u32, false),
+        Solution::Ambig(Guidance::Unknown)
+    );
+}
@@ -100,6 +100,7 @@ impl context::ResolventOps<SlgContext> for TruncatingInferenceTable {
         let mut ex_clause = ExClause {
             subst: subst.clone(),
             ambiguous: false,
+            caveat: false,
             constraints: vec![],
             subgoals: vec![],
             current_time: TimeStamp::default(),
@@ -113,7 +114,7 @@ impl context::ResolventOps<SlgContext> for TruncatingInferenceTable {
         ex_clause
             .subgoals
             .extend(conditions.into_iter().map(|c| match c {
-                Goal::Not(c) => Literal::Negative(InEnvironment::new(environment, *c)),
+                Goal::Not(c) => Literal::Negative(InEnvironment::new(environment, (*c).clone())),
                 c => Literal::Positive(InEnvironment::new(environment, c)),
             }));
 
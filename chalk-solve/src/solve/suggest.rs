@@ -0,0 +1,232 @@
+//! Suggesting impls that would make a failing goal hold, for
+//! diagnostics like "consider implementing `Clone` for `Foo`" (see
+//! `Solver::suggest_impls`).
+
+use crate::clauses::{program_clauses_with_origins, ClauseOrigin};
+use crate::ext::GoalExt;
+use crate::infer::InferenceTable;
+use crate::solve::Solver;
+use crate::{MissingData, RustIrDatabase};
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+type CanonicalGoal = UCanonical<InEnvironment<Goal<ChalkIr>>>;
+
+/// How far `Solver::suggest_impls` follows the conditions of impls:
+/// e.g., with a depth of 1, from `Vec<Foo>: Clone` through `impl<T>
+/// Clone for Vec<T> where T: Clone` to `Foo: Clone`, but no further.
+const MAX_SUGGESTION_DEPTH: usize = 4;
+
+/// An impl that could make a goal hold if it were added to the
+/// program, as found by `Solver::suggest_impls`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ImplSuggestion {
+    /// The trait ref that the impl would implement, e.g. `Foo: Clone`.
+    /// It contains no variables or placeholders.
+    pub trait_ref: TraitRef<ChalkIr>,
+
+    /// Why the orphan rules allow the impl to be added.
+    pub locality: ImplLocality,
+
+    /// The impls whose conditions require `trait_ref`, each of them
+    /// applying to the trait ref that the previous one requires: for
+    /// `Vec<Foo>: Clone`, the impl of `Clone` for `Vec<T>`, which
+    /// requires `Foo: Clone`. Empty if the goal itself requires
+    /// `trait_ref`.
+    pub required_by: Vec<ImplId>,
+}
+
+/// What makes an impl local to the crate, and so allowed by the
+/// orphan rules. The variants are declared in the order in which
+/// suggestions are ranked.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ImplLocality {
+    /// Both the trait and the self type are declared in this crate.
+    LocalTraitAndType,
+
+    /// The self type is declared in this crate.
+    LocalType,
+
+    /// The trait is declared in this crate.
+    LocalTrait,
+
+    /// Neither is, but the orphan rules allow the impl anyway, e.g.
+    /// for `impl Foreign<Local> for Foreign`, or for `Box<Local>`.
+    Other,
+}
+
+impl Solver {
+    /// Suggests impls that would make `goal` hold, if it does not: for
+    /// each leaf of `goal` that fails (see `solve_with_failures`) and
+    /// is a trait goal, either an impl of that trait ref itself, or,
+    /// if impls of the trait apply to it but their conditions fail, an
+    /// impl for the trait goals among those conditions (and so on,
+    /// through at most a few impls).
+    ///
+    /// Only impls that the orphan rules allow are suggested, and only
+    /// for trait refs without variables or placeholders (for those, a
+    /// where clause is usually wanted instead). The suggestions are
+    /// ranked by their `ImplLocality`, and then in the order in which
+    /// they were found; none is suggested twice.
+    pub fn suggest_impls(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &CanonicalGoal,
+    ) -> Result<Vec<ImplSuggestion>, MissingData> {
+        let mut suggestions = vec![];
+        let mut visited = HashSet::new();
+        self.suggest_impls_for_goal(program, goal, &[], &mut visited, &mut suggestions)?;
+        suggestions.sort_by_key(|suggestion: &ImplSuggestion| suggestion.locality);
+        Ok(suggestions)
+    }
+
+    fn suggest_impls_for_goal(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        goal: &CanonicalGoal,
+        required_by: &[ImplId],
+        visited: &mut HashSet<TraitRef<ChalkIr>>,
+        suggestions: &mut Vec<ImplSuggestion>,
+    ) -> Result<(), MissingData> {
+        let failed = match self.solve_with_failures(program, goal) {
+            Ok(_) => return Ok(()),
+            Err(failed) => failed,
+        };
+
+        for leaf in failed {
+            let InEnvironment { environment, goal } = &leaf.canonical.value;
+            let trait_ref = match goal {
+                Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+                    trait_ref,
+                )))) if is_closed(trait_ref) => trait_ref,
+                _ => continue,
+            };
+            if !visited.insert(trait_ref.clone()) {
+                continue;
+            }
+
+            // If impls of the trait apply to the trait ref, it is their
+            // conditions that fail, and adding another impl would
+            // overlap them.
+            let impls = self.impls_applying_to(program, &leaf, environment, trait_ref)?;
+            if impls.is_empty() {
+                if let Some(locality) = self.impl_locality(program, trait_ref) {
+                    suggestions.push(ImplSuggestion {
+                        trait_ref: trait_ref.clone(),
+                        locality,
+                        required_by: required_by.to_vec(),
+                    });
+                }
+            } else if required_by.len() < MAX_SUGGESTION_DEPTH {
+                for (impl_id, conditions) in impls {
+                    let conditions = match conditions {
+                        Some(conditions) => conditions,
+                        None => continue,
+                    };
+                    let required_by: Vec<_> =
+                        required_by.iter().cloned().chain(Some(impl_id)).collect();
+                    self.suggest_impls_for_goal(
+                        program,
+                        &conditions,
+                        &required_by,
+                        visited,
+                        suggestions,
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The impls whose heads unify with `trait_ref`, each with its
+    /// conditions (under the unifying substitution), as a goal of its
+    /// own, if it has any.
+    fn impls_applying_to(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        leaf: &CanonicalGoal,
+        environment: &Environment<ChalkIr>,
+        trait_ref: &TraitRef<ChalkIr>,
+    ) -> Result<Vec<(ImplId, Option<CanonicalGoal>)>, MissingData> {
+        let domain_goal: DomainGoal<_> = trait_ref.clone().cast();
+        let mut impls = vec![];
+        for (clause, origin) in program_clauses_with_origins(program, environment, &domain_goal)? {
+            let impl_id = match origin {
+                ClauseOrigin::Impl(impl_id) => impl_id,
+                _ => continue,
+            };
+            let (mut infer, _, InEnvironment { environment, .. }) =
+                InferenceTable::from_canonical(leaf.universes, &leaf.canonical);
+            let implication = match clause {
+                ProgramClause::Implies(implication) => implication,
+                ProgramClause::ForAll(binders) => infer.instantiate_binders_existentially(&binders),
+            };
+            let head = match &implication.consequence {
+                DomainGoal::Holds(WhereClause::Implemented(head)) => head,
+                _ => continue,
+            };
+            let unified = match infer.unify(&environment, trait_ref, head) {
+                Ok(unified) => unified,
+                Err(_) => continue,
+            };
+            let conditions = unified
+                .goals
+                .into_iter()
+                .map(|goal| goal.goal.cast())
+                .chain(implication.conditions.iter().cloned())
+                .fold(None, |goal, condition| match goal {
+                    None => Some(condition),
+                    Some(goal) => Some(Goal::And(Arc::new(goal), Arc::new(condition))),
+                });
+            let conditions = conditions.map(|conditions| {
+                let canonicalized =
+                    infer.canonicalize(&InEnvironment::new(&environment, conditions));
+                infer.u_canonicalize(&canonicalized.quantified).quantified
+            });
+            impls.push((impl_id, conditions));
+        }
+        Ok(impls)
+    }
+
+    /// Why the orphan rules allow an impl for `trait_ref`, or `None` if
+    /// they do not.
+    fn impl_locality(
+        &mut self,
+        program: &dyn RustIrDatabase,
+        trait_ref: &TraitRef<ChalkIr>,
+    ) -> Option<ImplLocality> {
+        let allowed: Goal<ChalkIr> = DomainGoal::LocalImplAllowed(trait_ref.clone()).cast();
+        self.solve(program, &allowed.into_closed_goal())?;
+
+        let local_trait = !program.trait_datum(trait_ref.trait_id).flags.upstream;
+        let local_type = match trait_ref.self_type_parameter() {
+            Some(Ty::Apply(ApplicationTy {
+                name: TypeName::TypeKindId(TypeKindId::StructId(struct_id)),
+                ..
+            })) => !program.struct_datum(struct_id).flags.upstream,
+            _ => false,
+        };
+        Some(match (local_trait, local_type) {
+            (true, true) => ImplLocality::LocalTraitAndType,
+            (false, true) => ImplLocality::LocalType,
+            (true, false) => ImplLocality::LocalTrait,
+            (false, false) => ImplLocality::Other,
+        })
+    }
+}
+
+/// True if `trait_ref` names no variables or placeholders, and so
+/// could be the trait ref of an impl without parameters.
+fn is_closed(trait_ref: &TraitRef<ChalkIr>) -> bool {
+    placeholder_universes(trait_ref).is_empty()
+        && trait_ref
+            .parameters
+            .iter()
+            .all(|parameter| match parameter.as_ref() {
+                ParameterKind::Ty(ty) => !ty.needs_shift(),
+                ParameterKind::Lifetime(lifetime) => !lifetime.needs_shift(),
+            })
+}
@@ -103,7 +103,7 @@ forall<T> {
 fn cycley_slg(b: &mut Bencher) {
     run_bench(
         CYCLEY,
-        SolverChoice::SLG { max_size: 20 },
+        SolverChoice::slg(20),
         CYCLEY_GOAL,
         b,
         "Unique",
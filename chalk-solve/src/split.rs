@@ -1,9 +1,28 @@
-use crate::RustIrDatabase;
+//! Helpers for splitting the parameters of associated types (and of
+//! their values in impls) between the surrounding trait (or impl) and
+//! the associated type itself. Embedders should use these rather than
+//! redo the index arithmetic; the parameter order is easy to get
+//! backwards.
+//!
+//! Each helper has a `try_` variant, which looks up the items it needs
+//! with the `try_` accessors of the database and reports `MissingData`
+//! rather than panicking (see `RustIrDatabase::try_associated_ty_data`).
+
+use crate::{MissingData, RustIrDatabase};
 use chalk_ir::family::ChalkIr;
 use chalk_ir::*;
 use chalk_rust_ir::*;
 use std::sync::Arc;
 
+/// The datum of the associated type of a projection, and the parameters
+/// of the projection that come from the trait and from the associated
+/// type itself (see `Split::split_projection`).
+pub type SplitProjection<'p> = (
+    Arc<AssociatedTyDatum>,
+    &'p [Parameter<ChalkIr>],
+    &'p [Parameter<ChalkIr>],
+);
+
 /// Methods for splitting up the projections for associated types from
 /// the surrounding context.
 pub trait Split: RustIrDatabase {
@@ -13,24 +32,72 @@ pub trait Split: RustIrDatabase {
     /// have `(Iterator::Item)<F>`, this would return `([F], [])`,
     /// since `Iterator::Item` is not generic and hence doesn't have
     /// any type parameters itself.
-    fn split_projection<'p>(
+    fn split_projection<'p>(&self, projection: &'p ProjectionTy<ChalkIr>) -> SplitProjection<'p> {
+        self.try_split_projection(projection)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Fallible variant of `split_projection`.
+    fn try_split_projection<'p>(
         &self,
         projection: &'p ProjectionTy<ChalkIr>,
-    ) -> (
-        Arc<AssociatedTyDatum>,
-        &'p [Parameter<ChalkIr>],
-        &'p [Parameter<ChalkIr>],
-    ) {
+    ) -> Result<SplitProjection<'p>, MissingData> {
         let ProjectionTy {
             associated_ty_id,
             ref parameters,
         } = *projection;
-        let associated_ty_data = &self.associated_ty_data(associated_ty_id);
-        let trait_datum = &self.trait_datum(associated_ty_data.trait_id);
+        let associated_ty_data = self.try_associated_ty_data(associated_ty_id)?;
+        let (trait_params, other_params) =
+            self.try_split_associated_ty_parameters(parameters, &associated_ty_data)?;
+        Ok((associated_ty_data, trait_params, other_params))
+    }
+
+    /// Given the full set of parameters (or binders) for the
+    /// associated type `associated_ty`, splits them into those for the
+    /// *trait* and those for the *associated type itself*. The
+    /// parameters are in the same order as the binders of
+    /// `AssociatedTyDatum`, i.e., the associated type's own come
+    /// first:
+    ///
+    /// ```notrust
+    /// [ P0..Pn, Pn..Pm ]
+    ///           ^^^^^^ trait parameters
+    /// ```
+    ///
+    /// So for `trait Iterable<T> { type Iter<'a>; }`, given `['x, Y,
+    /// Z]` (the parameters of `<Y as Iterable<Z>>::Iter<'x>`), this
+    /// returns `([Y, Z], ['x])`.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, panics if `parameters` does not have one
+    /// element per binder of `associated_ty`.
+    fn split_associated_ty_parameters<'p, P>(
+        &self,
+        parameters: &'p [P],
+        associated_ty: &AssociatedTyDatum,
+    ) -> (&'p [P], &'p [P]) {
+        self.try_split_associated_ty_parameters(parameters, associated_ty)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Fallible variant of `split_associated_ty_parameters`.
+    fn try_split_associated_ty_parameters<'p, P>(
+        &self,
+        parameters: &'p [P],
+        associated_ty: &AssociatedTyDatum,
+    ) -> Result<(&'p [P], &'p [P]), MissingData> {
+        debug_assert_eq!(
+            parameters.len(),
+            associated_ty.binders.len(),
+            "wrong number of parameters for associated type {:?}",
+            associated_ty.name,
+        );
+        let trait_datum = self.try_trait_datum(associated_ty.trait_id)?;
         let trait_num_params = trait_datum.binders.len();
         let split_point = parameters.len() - trait_num_params;
         let (other_params, trait_params) = parameters.split_at(split_point);
-        (associated_ty_data.clone(), trait_params, other_params)
+        Ok((trait_params, other_params))
     }
 
     /// Given a projection `<P0 as Trait<P1..Pn>>::Item<Pn..Pm>`,
@@ -44,6 +111,15 @@ pub trait Split: RustIrDatabase {
         trait_params
     }
 
+    /// Fallible variant of `trait_parameters_from_projection`.
+    fn try_trait_parameters_from_projection<'p>(
+        &self,
+        projection: &'p ProjectionTy<ChalkIr>,
+    ) -> Result<&'p [Parameter<ChalkIr>], MissingData> {
+        let (_, trait_params, _) = self.try_split_projection(projection)?;
+        Ok(trait_params)
+    }
+
     /// Given a projection `<P0 as Trait<P1..Pn>>::Item<Pn..Pm>`,
     /// returns the trait parameters `[P0..Pn]` (see
     /// `split_projection`).
@@ -51,11 +127,20 @@ pub trait Split: RustIrDatabase {
         &self,
         projection: &'p ProjectionTy<ChalkIr>,
     ) -> TraitRef<ChalkIr> {
-        let (associated_ty_data, trait_params, _) = self.split_projection(&projection);
-        TraitRef {
+        self.try_trait_ref_from_projection(projection)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Fallible variant of `trait_ref_from_projection`.
+    fn try_trait_ref_from_projection(
+        &self,
+        projection: &ProjectionTy<ChalkIr>,
+    ) -> Result<TraitRef<ChalkIr>, MissingData> {
+        let (associated_ty_data, trait_params, _) = self.try_split_projection(projection)?;
+        Ok(TraitRef {
             trait_id: associated_ty_data.trait_id,
             parameters: trait_params.to_owned(),
-        }
+        })
     }
 
     /// Given the full set of parameters (or binders) for an
@@ -81,14 +166,37 @@ pub trait Split: RustIrDatabase {
     ///
     /// * the parameters for the impl (`[Y]`, in our example)
     /// * the parameters for the associated type value (`['a]`, in our example)
+    ///
+    /// # Panics
+    ///
+    /// Panics if `parameters` has fewer elements than the impl has
+    /// binders and, in debug builds, if it does not have one element
+    /// per binder of `associated_ty_value` (which includes those of
+    /// the impl).
     fn split_associated_ty_value_parameters<'p, P>(
         &self,
         parameters: &'p [P],
         associated_ty_value: &AssociatedTyValue,
     ) -> (&'p [P], &'p [P]) {
-        let impl_datum = self.impl_datum(associated_ty_value.impl_id);
+        self.try_split_associated_ty_value_parameters(parameters, associated_ty_value)
+            .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Fallible variant of `split_associated_ty_value_parameters`.
+    fn try_split_associated_ty_value_parameters<'p, P>(
+        &self,
+        parameters: &'p [P],
+        associated_ty_value: &AssociatedTyValue,
+    ) -> Result<(&'p [P], &'p [P]), MissingData> {
+        let impl_datum = self.try_impl_datum(associated_ty_value.impl_id)?;
         let impl_params_len = impl_datum.binders.len();
         assert!(parameters.len() >= impl_params_len);
+        debug_assert_eq!(
+            parameters.len(),
+            associated_ty_value.value.len(),
+            "wrong number of parameters for associated type value {:?}",
+            associated_ty_value.associated_ty_id,
+        );
 
         // the impl parameters are a suffix
         //
@@ -96,7 +204,7 @@ pub trait Split: RustIrDatabase {
         //           ^^^^^^^ impl parameters
         let split_point = parameters.len() - impl_params_len;
         let (other_params, impl_params) = parameters.split_at(split_point);
-        (impl_params, other_params)
+        Ok((impl_params, other_params))
     }
 
     /// Given the full set of parameters for an associated type *value*
@@ -124,17 +232,31 @@ pub trait Split: RustIrDatabase {
         parameters: &'p [Parameter<ChalkIr>],
         associated_ty_value: &AssociatedTyValue,
     ) -> (&'p [Parameter<ChalkIr>], ProjectionTy<ChalkIr>) {
+        self.try_impl_parameters_and_projection_from_associated_ty_value(
+            parameters,
+            associated_ty_value,
+        )
+        .unwrap_or_else(|missing| panic!("{}", missing))
+    }
+
+    /// Fallible variant of
+    /// `impl_parameters_and_projection_from_associated_ty_value`.
+    fn try_impl_parameters_and_projection_from_associated_ty_value<'p>(
+        &self,
+        parameters: &'p [Parameter<ChalkIr>],
+        associated_ty_value: &AssociatedTyValue,
+    ) -> Result<(&'p [Parameter<ChalkIr>], ProjectionTy<ChalkIr>), MissingData> {
         debug_heading!(
             "impl_parameters_and_projection_from_associated_ty_value(parameters={:?})",
             parameters,
         );
 
-        let impl_datum = self.impl_datum(associated_ty_value.impl_id);
+        let impl_datum = self.try_impl_datum(associated_ty_value.impl_id)?;
 
         // Get the trait ref from the impl -- so in our example above
         // this would be `Box<!T>: Foo`.
         let (impl_parameters, atv_parameters) =
-            self.split_associated_ty_value_parameters(&parameters, associated_ty_value);
+            self.try_split_associated_ty_value_parameters(parameters, associated_ty_value)?;
         let trait_ref = {
             let impl_trait_ref = impl_datum.binders.map_ref(|b| &b.trait_ref);
             debug!("impl_trait_ref: {:?}", impl_trait_ref);
@@ -159,7 +281,7 @@ pub trait Split: RustIrDatabase {
         debug!("trait_ref: {:?}", trait_ref);
         debug!("projection: {:?}", projection);
 
-        (impl_parameters, projection)
+        Ok((impl_parameters, projection))
     }
 }
 
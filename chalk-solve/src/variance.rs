@@ -0,0 +1,258 @@
+//! Variance inference for the parameters of structs, so that the
+//! database need not provide variances itself (an embedder can also
+//! use this to cross-check the variances it computed).
+//!
+//! The variance of a parameter is determined by where it appears in
+//! the fields of the struct, as in rustc: each field is in a covariant
+//! position, and the variance of a position within a field follows
+//! from the type around it. Since structs may refer to one another
+//! (and to themselves), the variances are computed as a fixed point:
+//! every parameter starts out bivariant (unused) and is lowered, using
+//! the current variances of the other structs, until nothing changes.
+//! Where clauses do not affect variance.
+
+use crate::RustIrDatabase;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::{DefaultInferenceFolder, DefaultPlaceholderFolder, DefaultTypeFolder};
+use chalk_ir::fold::{Fold, FreeVarFolder};
+use chalk_ir::*;
+use std::collections::BTreeMap;
+
+/// How the subtyping of a type relates to that of one of its
+/// parameters.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variance {
+    /// `T<A> <: T<B>` iff `A <: B`, e.g. the `T` of `Box<T>`.
+    Covariant,
+
+    /// `T<A> <: T<B>` iff `B <: A`, e.g. the argument types of `fn`.
+    Contravariant,
+
+    /// `T<A> <: T<B>` iff `A == B`, e.g. the parameters of a trait
+    /// object type or of a projection.
+    Invariant,
+
+    /// `T<A> <: T<B>` for all `A` and `B`: the parameter is not used.
+    Bivariant,
+}
+
+impl Variance {
+    /// The variance of a position with variance `v` within a position
+    /// of variance `self`.
+    pub fn xform(self, v: Variance) -> Variance {
+        match (self, v) {
+            (Variance::Covariant, v) => v,
+            (Variance::Contravariant, v) => v.flip(),
+            (Variance::Invariant, _) => Variance::Invariant,
+            (Variance::Bivariant, _) => Variance::Bivariant,
+        }
+    }
+
+    /// The variance of a parameter that is used both with variance
+    /// `self` and with variance `v`.
+    pub fn glb(self, v: Variance) -> Variance {
+        match (self, v) {
+            (Variance::Bivariant, v) | (v, Variance::Bivariant) => v,
+            (a, b) if a == b => a,
+            _ => Variance::Invariant,
+        }
+    }
+
+    fn flip(self) -> Variance {
+        match self {
+            Variance::Covariant => Variance::Contravariant,
+            Variance::Contravariant => Variance::Covariant,
+            v => v,
+        }
+    }
+}
+
+/// Computes the variance of each parameter (types and lifetimes alike,
+/// in order) of each of `structs`, and of the structs that their
+/// fields refer to, directly or not.
+pub fn struct_variances(
+    db: &dyn RustIrDatabase,
+    structs: impl IntoIterator<Item = StructId>,
+) -> BTreeMap<StructId, Vec<Variance>> {
+    let mut variances = BTreeMap::new();
+    let mut worklist: Vec<StructId> = structs.into_iter().collect();
+    while let Some(struct_id) = worklist.pop() {
+        if variances.contains_key(&struct_id) {
+            continue;
+        }
+        let datum = db.struct_datum(struct_id);
+        variances.insert(
+            struct_id,
+            vec![Variance::Bivariant; datum.binders.binders.len()],
+        );
+        for field in &datum.binders.value.fields {
+            collect_structs(field, &mut worklist);
+        }
+    }
+
+    let data: Vec<_> = variances
+        .keys()
+        .map(|&struct_id| (struct_id, db.struct_datum(struct_id)))
+        .collect();
+    loop {
+        let mut changed = false;
+        for (struct_id, datum) in &data {
+            let mut walker = VarianceWalker {
+                variances: &variances,
+                params: variances[struct_id].clone(),
+            };
+            for field in &datum.binders.value.fields {
+                walker.walk_ty(field, Variance::Covariant, 0);
+            }
+            if walker.params != variances[struct_id] {
+                variances.insert(*struct_id, walker.params);
+                changed = true;
+            }
+        }
+        if !changed {
+            return variances;
+        }
+    }
+}
+
+/// Pushes the structs mentioned by `ty` onto `structs`.
+fn collect_structs(ty: &Ty<ChalkIr>, structs: &mut Vec<StructId>) {
+    match ty {
+        Ty::Apply(apply) => {
+            if let TypeName::TypeKindId(TypeKindId::StructId(id)) = apply.name {
+                structs.push(id);
+            }
+            for parameter in &apply.parameters {
+                if let ParameterKind::Ty(ty) = &parameter.0 {
+                    collect_structs(ty, structs);
+                }
+            }
+        }
+        Ty::ForAll(quantified) => collect_structs(&quantified.ty, structs),
+        // Anything within these is invariant, whatever the variances
+        // of the structs there.
+        Ty::Dyn(_) | Ty::Opaque(_) | Ty::Projection(_) => {}
+        Ty::BoundVar(_) | Ty::InferenceVar(_) => {}
+    }
+}
+
+/// Lowers the variances of the parameters of one struct, `params`, to
+/// account for their uses in a field.
+struct VarianceWalker<'v> {
+    variances: &'v BTreeMap<StructId, Vec<Variance>>,
+    params: Vec<Variance>,
+}
+
+impl VarianceWalker<'_> {
+    /// Walks `ty`, which is in a position of variance `variance`,
+    /// under `binders` binders of its own.
+    fn walk_ty(&mut self, ty: &Ty<ChalkIr>, variance: Variance, binders: usize) {
+        match ty {
+            Ty::Apply(apply) => {
+                let variances: Vec<Variance> = match apply.name {
+                    TypeName::TypeKindId(TypeKindId::StructId(id)) => self.variances[&id].clone(),
+                    TypeName::Tuple(_) => vec![Variance::Covariant; apply.parameters.len()],
+                    // `fn(A..) -> B`: the tuple of arguments, then the
+                    // return type.
+                    TypeName::FnPtr => vec![Variance::Contravariant, Variance::Covariant],
+                    // `&'a mut T` and `*mut T`: the referent type is
+                    // invariant, and the lifetime covariant.
+                    TypeName::Builtin(BuiltinType::Ref(Mutability::Mut)) => {
+                        vec![Variance::Invariant, Variance::Covariant]
+                    }
+                    TypeName::Builtin(BuiltinType::Ptr(Mutability::Mut)) => {
+                        vec![Variance::Invariant]
+                    }
+                    // `&'a T`, `*const T`: covariant in everything.
+                    TypeName::Builtin(BuiltinType::Ref(Mutability::Not))
+                    | TypeName::Builtin(BuiltinType::Ptr(Mutability::Not)) => {
+                        vec![Variance::Covariant; apply.parameters.len()]
+                    }
+                    // `[T]` and `[T; N]`: the element type, then the
+                    // length (which is invariant).
+                    TypeName::Builtin(_) => {
+                        let mut variances = vec![Variance::Invariant; apply.parameters.len()];
+                        if let Some(element) = variances.first_mut() {
+                            *element = Variance::Covariant;
+                        }
+                        variances
+                    }
+                    _ => vec![Variance::Invariant; apply.parameters.len()],
+                };
+                for (parameter, &v) in apply.parameters.iter().zip(&variances) {
+                    self.walk_parameter(parameter, variance.xform(v), binders);
+                }
+            }
+            Ty::ForAll(quantified) => {
+                self.walk_ty(&quantified.ty, variance, binders + quantified.num_binders)
+            }
+            Ty::BoundVar(depth) => self.use_var(*depth, variance, binders),
+            Ty::Dyn(_) | Ty::Opaque(_) | Ty::Projection(_) => {
+                self.walk_invariant(ty, variance, binders)
+            }
+            Ty::InferenceVar(_) => {}
+        }
+    }
+
+    fn walk_parameter(
+        &mut self,
+        parameter: &Parameter<ChalkIr>,
+        variance: Variance,
+        binders: usize,
+    ) {
+        match &parameter.0 {
+            ParameterKind::Ty(ty) => self.walk_ty(ty, variance, binders),
+            ParameterKind::Lifetime(Lifetime::BoundVar(depth)) => {
+                self.use_var(*depth, variance, binders)
+            }
+            ParameterKind::Lifetime(_) => {}
+        }
+    }
+
+    /// Makes each parameter mentioned in `value` invariant (unless
+    /// `value` is in a bivariant position).
+    fn walk_invariant<T: Fold<ChalkIr>>(&mut self, value: &T, variance: Variance, binders: usize) {
+        let mut finder = VarFinder { vars: vec![] };
+        value.fold_with(&mut finder, binders).unwrap();
+        for depth in finder.vars {
+            self.use_var(depth, variance.xform(Variance::Invariant), 0);
+        }
+    }
+
+    /// Records a use of the variable with index `depth`, under
+    /// `binders` binders, in a position of variance `variance`.
+    fn use_var(&mut self, depth: usize, variance: Variance, binders: usize) {
+        if depth >= binders {
+            let param = &mut self.params[depth - binders];
+            *param = param.glb(variance);
+        }
+    }
+}
+
+/// Collects the free variables of a value.
+struct VarFinder {
+    vars: Vec<usize>,
+}
+
+impl DefaultTypeFolder for VarFinder {}
+
+impl FreeVarFolder<ChalkIr> for VarFinder {
+    fn fold_free_var_ty(&mut self, depth: usize, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        self.vars.push(depth);
+        Ok(Ty::BoundVar(depth + binders))
+    }
+
+    fn fold_free_var_lifetime(
+        &mut self,
+        depth: usize,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        self.vars.push(depth);
+        Ok(Lifetime::BoundVar(depth + binders))
+    }
+}
+
+impl DefaultInferenceFolder for VarFinder {}
+
+impl DefaultPlaceholderFolder for VarFinder {}
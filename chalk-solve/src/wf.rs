@@ -1,6 +1,8 @@
 use std::fmt;
+use std::sync::Arc;
 
 use crate::ext::*;
+use crate::object_safety::{self, ObjectSafetyViolation};
 use crate::solve::SolverChoice;
 use crate::split::Split;
 use crate::RustIrDatabase;
@@ -14,6 +16,38 @@ use itertools::Itertools;
 pub enum WfError {
     IllFormedTypeDecl(chalk_ir::Identifier),
     IllFormedTraitImpl(chalk_ir::Identifier),
+    IllFormedAutoTrait(chalk_ir::Identifier, Vec<AutoTraitViolation>),
+
+    /// A declaration uses `dyn Trait` for a trait that is not object
+    /// safe.
+    NotObjectSafe(chalk_ir::Identifier, Vec<ObjectSafetyViolation>),
+}
+
+/// A way in which the declaration of an auto trait is ill-formed (see
+/// `WfSolver::verify_auto_trait`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AutoTraitViolation {
+    /// The trait has parameters other than `Self`.
+    Parameters,
+
+    /// The trait defines associated types.
+    AssociatedTypes,
+
+    /// The trait has a where clause that is not a bound `Self:
+    /// AutoTrait` on another auto trait.
+    WhereClause(QuantifiedWhereClause<ChalkIr>),
+}
+
+impl fmt::Display for AutoTraitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AutoTraitViolation::Parameters => write!(f, "it has parameters"),
+            AutoTraitViolation::AssociatedTypes => write!(f, "it defines associated types"),
+            AutoTraitViolation::WhereClause(where_clause) => {
+                write!(f, "it has the where clause `{:?}`", where_clause)
+            }
+        }
+    }
 }
 
 impl fmt::Display for WfError {
@@ -29,6 +63,26 @@ impl fmt::Display for WfError {
                 "trait impl for {:?} does not meet well-formedness requirements",
                 id
             ),
+            WfError::IllFormedAutoTrait(id, violations) => {
+                write!(f, "auto trait {:?} is ill-formed: ", id)?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
+            WfError::NotObjectSafe(id, violations) => {
+                write!(f, "trait {:?} cannot be made into an object: ", id)?;
+                for (i, violation) in violations.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{}", violation)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -41,7 +95,7 @@ pub struct WfSolver<'db, DB: RustIrDatabase> {
 }
 
 /// A trait for retrieving all types appearing in some Chalk construction.
-trait FoldInputTypes {
+pub(crate) trait FoldInputTypes {
     fn fold(&self, accumulator: &mut Vec<Ty<ChalkIr>>);
 }
 
@@ -120,6 +174,21 @@ impl<T: FoldInputTypes> FoldInputTypes for Binders<T> {
     }
 }
 
+/// The hypotheses that the type parameters among `binders` are
+/// well-formed, as a type parameter always is where it is in scope.
+/// This lets structural types over them, like `(T, U)` or `&'a T`, be
+/// proven well-formed (though not, say, `T: 'a`, which is left to the
+/// embedder as a region constraint).
+fn type_parameters_well_formed(
+    binders: &[ParameterKind<()>],
+) -> impl Iterator<Item = ProgramClause<ChalkIr>> + '_ {
+    binders
+        .iter()
+        .enumerate()
+        .filter(|(_, kind)| matches!(kind, ParameterKind::Ty(())))
+        .map(|(index, _)| DomainGoal::WellFormed(WellFormed::Ty(Ty::BoundVar(index))).cast())
+}
+
 impl<'db, DB> WfSolver<'db, DB>
 where
     DB: RustIrDatabase,
@@ -141,6 +210,8 @@ where
             .where_clauses
             .fold(&mut input_types);
 
+        self.verify_object_tys(&input_types)?;
+
         if input_types.is_empty() {
             return Ok(());
         }
@@ -150,7 +221,7 @@ where
             .map(|ty| DomainGoal::WellFormed(WellFormed::Ty(ty)))
             .casted();
         let goal = goals
-            .fold1(|goal, leaf| Goal::And(Box::new(goal), Box::new(leaf)))
+            .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
             .expect("at least one goal");
 
         let hypotheses = struct_datum
@@ -161,12 +232,13 @@ where
             .cloned()
             .map(|wc| wc.map(|bound| bound.into_from_env_goal()))
             .casted()
+            .chain(type_parameters_well_formed(&struct_datum.binders.binders))
             .collect();
 
         // We ask that the above input types are well-formed provided that all the where-clauses
-        // on the struct definition hold.
-        let goal = Goal::Implies(hypotheses, Box::new(goal))
-            .quantify(QuantifierKind::ForAll, struct_datum.binders.binders.clone());
+        // on the struct definition hold (and its type parameters are well-formed).
+        let goal = Goal::Implies(hypotheses, Arc::new(goal))
+            .quantify(QuantifierKind::ForAll, struct_datum.binders.binders);
 
         let is_legal = match self
             .solver_choice
@@ -185,6 +257,78 @@ where
         }
     }
 
+    /// Checks that the traits of the `dyn` types among `tys` are
+    /// object safe.
+    fn verify_object_tys(&self, tys: &[Ty<ChalkIr>]) -> Result<(), WfError> {
+        for ty in tys {
+            let bounds = match ty {
+                Ty::Dyn(bounds) => bounds,
+                _ => continue,
+            };
+            for bound in &bounds.value {
+                if let WhereClause::Implemented(trait_ref) = &bound.value {
+                    let violations =
+                        object_safety::object_safety_violations(self.db, trait_ref.trait_id);
+                    if !violations.is_empty() {
+                        let name = self.db.type_name(trait_ref.trait_id.into());
+                        return Err(WfError::NotObjectSafe(name, violations));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the trait, if it is an auto trait, has the shape
+    /// that the clauses for auto traits assume: no parameters besides
+    /// `Self`, no associated types, and no where clauses except for
+    /// supertrait bounds on other auto traits (`Self: Send`). All the
+    /// violations found are reported.
+    pub fn verify_auto_trait(&self, trait_id: TraitId) -> Result<(), WfError> {
+        let trait_datum = self.db.trait_datum(trait_id);
+        if !trait_datum.is_auto_trait() {
+            return Ok(());
+        }
+
+        let mut violations = vec![];
+        if trait_datum.binders.len() > 1 {
+            violations.push(AutoTraitViolation::Parameters);
+        }
+        if !trait_datum.associated_ty_ids.is_empty() {
+            violations.push(AutoTraitViolation::AssociatedTypes);
+        }
+        violations.extend(
+            trait_datum
+                .binders
+                .value
+                .where_clauses
+                .iter()
+                .filter(|where_clause| !self.is_auto_trait_super_bound(where_clause))
+                .cloned()
+                .map(AutoTraitViolation::WhereClause),
+        );
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            let name = self.db.type_name(trait_id.into());
+            Err(WfError::IllFormedAutoTrait(name, violations))
+        }
+    }
+
+    /// True for a where clause `Self: AutoTrait` of a trait, where
+    /// `Self` is the first of the trait's parameters.
+    fn is_auto_trait_super_bound(&self, where_clause: &QuantifiedWhereClause<ChalkIr>) -> bool {
+        match &where_clause.value {
+            WhereClause::Implemented(trait_ref) => {
+                where_clause.binders.is_empty()
+                    && trait_ref.parameters == [Ty::BoundVar(0).cast()]
+                    && self.db.trait_datum(trait_ref.trait_id).is_auto_trait()
+            }
+            WhereClause::ProjectionEq(_) => false,
+        }
+    }
+
     pub fn verify_trait_impl(&self, impl_id: ImplId) -> Result<(), WfError> {
         let impl_datum = self.db.impl_datum(impl_id);
 
@@ -223,10 +367,37 @@ where
         let trait_ref = &impl_datum.binders.value.trait_ref;
         trait_ref.fold(&mut header_input_types);
 
-        let assoc_ty_goals = impl_datum
+        self.verify_object_tys(&input_types)?;
+        self.verify_object_tys(&header_input_types)?;
+
+        // The associated type values include the defaults from the
+        // trait for the associated types that the impl leaves out: they
+        // must meet the bounds for this impl in particular.
+        let assoc_ty_values: Vec<_> = impl_datum
             .associated_ty_value_ids
             .iter()
-            .filter_map(|&id| self.compute_assoc_ty_goal(id));
+            .map(|&id| self.db.associated_ty_value(id))
+            .collect();
+        let trait_datum = self.db.trait_datum(impl_datum.trait_id());
+        let default_assoc_ty_values: Vec<_> = trait_datum
+            .associated_ty_ids
+            .iter()
+            .filter(|&&id| {
+                !assoc_ty_values
+                    .iter()
+                    .any(|value| value.associated_ty_id == id)
+            })
+            .filter_map(|&id| {
+                self.db
+                    .associated_ty_data(id)
+                    .default_value_for(impl_id, &impl_datum)
+            })
+            .collect();
+        let assoc_ty_goals = assoc_ty_values
+            .iter()
+            .map(|value| &**value)
+            .chain(&default_assoc_ty_values)
+            .filter_map(|value| self.compute_assoc_ty_goal(value));
 
         // Things to prove well-formed: input types of the where-clauses, projection types
         // appearing in the header, associated type values, and of course the trait ref.
@@ -238,12 +409,12 @@ where
             .chain(Some(trait_ref_wf).cast());
 
         let goal = goals
-            .fold1(|goal, leaf| Goal::And(Box::new(goal), Box::new(leaf)))
+            .fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))
             .expect("at least one goal");
 
         // Assumptions: types appearing in the header which are not projection types are
-        // assumed to be well-formed, and where clauses declared on the impl are assumed
-        // to hold.
+        // assumed to be well-formed, as are the type parameters of the impl, and where
+        // clauses declared on the impl are assumed to hold.
         let hypotheses = impl_datum
             .binders
             .value
@@ -258,10 +429,11 @@ where
                     .map(|ty| DomainGoal::FromEnv(FromEnv::Ty(ty)))
                     .casted(),
             )
+            .chain(type_parameters_well_formed(&impl_datum.binders.binders))
             .collect();
 
-        let goal = Goal::Implies(hypotheses, Box::new(goal))
-            .quantify(QuantifierKind::ForAll, impl_datum.binders.binders.clone());
+        let goal = Goal::Implies(hypotheses, Arc::new(goal))
+            .quantify(QuantifierKind::ForAll, impl_datum.binders.binders);
 
         debug!("WF trait goal: {:?}", goal);
 
@@ -310,9 +482,7 @@ where
     ///     forall<'a> { WellFormed(Box<&'a T>) },
     /// }
     /// ```
-    fn compute_assoc_ty_goal(&self, assoc_ty_id: AssociatedTyValueId) -> Option<Goal<ChalkIr>> {
-        let assoc_ty = &self.db.associated_ty_value(assoc_ty_id);
-
+    fn compute_assoc_ty_goal(&self, assoc_ty: &AssociatedTyValue) -> Option<Goal<ChalkIr>> {
         // The substitutions for the binders on this associated type
         // value. These would be placeholders like `'!a` and `!T`, in
         // our example above.
@@ -364,6 +534,7 @@ where
         let AssociatedTyDatumBound {
             bounds: defn_bounds,
             where_clauses: defn_where_clauses,
+            default: _,
         } = assoc_ty_datum.binders.substitute(&projection.parameters);
 
         // Check that the `value_ty` meets the bounds from the trait.
@@ -382,10 +553,7 @@ where
 
         // Concatenate the WF goals of inner types + the requirements from trait
         let goals = wf_goals.chain(bound_goals);
-        let goal = match goals.fold1(|goal, leaf| Goal::And(Box::new(goal), Box::new(leaf))) {
-            Some(goal) => goal,
-            None => return None,
-        };
+        let goal = goals.fold1(|goal, leaf| Goal::And(Arc::new(goal), Arc::new(leaf)))?;
 
         // Add where clauses from the associated ty definition. We must
         // substitute parameters here, like we did with the bounds above.
@@ -396,7 +564,7 @@ where
             .casted()
             .collect();
 
-        let goal = Goal::Implies(hypotheses, Box::new(goal));
+        let goal = Goal::Implies(hypotheses, Arc::new(goal));
 
         // Create a composed goal that is universally quantified over
         // the parameters from the associated type value (e.g.,
@@ -8,8 +8,12 @@ use std::process::exit;
 use chalk_integration::db::ChalkDatabase;
 use chalk_integration::lowering::*;
 use chalk_integration::query::LoweringDatabase;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::{Goal, InEnvironment, LeafGoal};
+use chalk_solve::clauses::{program_clauses_with_origins, ClauseOrigin};
+use chalk_solve::display::display;
 use chalk_solve::ext::*;
-use chalk_solve::SolverChoice;
+use chalk_solve::{SlgOptions, SolverChoice};
 use docopt::Docopt;
 use rustyline::error::ReadlineError;
 
@@ -27,6 +31,7 @@ Options:
   --program=PATH      Specifies the path to the `.chalk` file containing traits/impls.
   --goal=GOAL         Specifies a goal to evaluate (may be given more than once).
   --overflow-depth=N  Specifies the overflow depth [default: 10].
+  --max-universes=N   Specifies the maximum number of universes in a goal [default: 32].
   --no-cache          Disable caching.
   --multiple          Ouput multiple answers instead of ambiguous solution.
 ";
@@ -37,6 +42,7 @@ struct Args {
     flag_program: Option<String>,
     flag_goal: Vec<String>,
     flag_overflow_depth: usize,
+    flag_max_universes: usize,
     flag_no_cache: bool,
     flag_multiple: bool,
 }
@@ -97,7 +103,41 @@ impl LoadedProgram {
         } else {
             match self.db.solve(&peeled_goal) {
                 Some(v) => println!("{}\n", v),
-                None => println!("No possible solution.\n"),
+                None => {
+                    println!("No possible solution.");
+                    self.explain_failure(&peeled_goal.canonical.value)?;
+                    println!();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Prints the coherence and well-formedness errors of the program,
+    /// all of them rather than just the first.
+    fn check(&self) -> Result<()> {
+        let report = self.db.check_program()?;
+        if report.is_ok() {
+            println!("No errors found.");
+        } else {
+            print!("{}", report);
+        }
+        Ok(())
+    }
+
+    /// For a goal that has no solution, prints the impls that could
+    /// have proven it: it must be their conditions that failed.
+    fn explain_failure(&self, goal: &InEnvironment<Goal<ChalkIr>>) -> Result<()> {
+        if let Goal::Leaf(LeafGoal::DomainGoal(domain_goal)) = &goal.goal {
+            for (clause, origin) in
+                program_clauses_with_origins(&self.db, &goal.environment, domain_goal)?
+            {
+                if let ClauseOrigin::Impl(_) = origin {
+                    println!(
+                        "note: the conditions of this impl could not be proven: {}",
+                        display(&self.db, &clause)
+                    );
+                }
             }
         }
         Ok(())
@@ -115,6 +155,10 @@ fn run() -> Result<()> {
         eprintln!("error: overflow depth must be at least 1");
         exit(1);
     }
+    if args.flag_max_universes == 0 {
+        eprintln!("error: max universes must be at least 1");
+        exit(1);
+    }
 
     // Load the .chalk file, if given.
     let mut prog = None;
@@ -215,6 +259,13 @@ fn process(
             Some(level) => std::env::set_var("CHALK_DEBUG", level),
             None => println!("debug <level> set debug level to <level>"),
         }
+    } else if command == "check" {
+        // Check the whole program, which need not pass the checks that
+        // the other commands require.
+        let prog = prog
+            .as_ref()
+            .ok_or("no program currently loaded; type 'help' to see available commands")?;
+        prog.check()?;
     } else {
         // The command is either "print", "lowered", or a goal.
 
@@ -229,8 +280,15 @@ fn process(
                 // Print out the loaded program.
                 "print" => println!("{}", prog.text),
 
-                // TODO: Write a line of documentation here.
-                "lowered" => println!("{:#?}", prog.db.environment()),
+                // Print out the program clauses of the loaded program.
+                "lowered" => {
+                    for clause in &prog.db.environment()?.program_clauses {
+                        println!("{}", display(&prog.db, clause));
+                    }
+                }
+
+                // Print out the memory retained by the solver's tables.
+                "memory" => println!("{}", prog.db.memory_report()),
 
                 // Assume this is a goal.
                 // TODO: Print out "type 'help' to see available commands" if it
@@ -262,6 +320,8 @@ fn help() {
     println!("  load <file>   load program from <file>");
     println!("  print         print the current program");
     println!("  lowered       print the lowered program");
+    println!("  memory        print the memory retained by the solver");
+    println!("  check         print the coherence and well-formedness errors");
     println!("  <goal>        attempt to solve <goal>");
     println!("  debug <level> set debug level to <level>");
 }
@@ -280,9 +340,11 @@ fn read_program(rl: &mut rustyline::Editor<()>) -> Result<String> {
 
 impl Args {
     fn solver_choice(&self) -> SolverChoice {
-        SolverChoice::SLG {
-            max_size: self.flag_overflow_depth,
-        }
+        SolverChoice::slg_with(
+            SlgOptions::default()
+                .max_size(self.flag_overflow_depth)
+                .max_universes(self.flag_max_universes),
+        )
     }
 }
 
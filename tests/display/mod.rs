@@ -0,0 +1,128 @@
+use chalk_integration::db::ChalkDatabase;
+use chalk_solve::display::display;
+use chalk_solve::SolverChoice;
+
+fn display_goals(program_text: &str, goals: &[(&str, &str)]) {
+    let db = ChalkDatabase::with(program_text, SolverChoice::default());
+    db.with_program(|_| {
+        for (goal_text, expected) in goals {
+            let goal = db.parse_and_lower_goal(goal_text).unwrap();
+            assert_eq!(display(&db, &*goal).to_string(), *expected);
+        }
+    });
+}
+
+#[test]
+fn quantifiers_and_implications() {
+    display_goals(
+        "
+            trait Foo { }
+            trait Bar<T> { }
+        ",
+        &[
+            (
+                "forall<T> { if (T: Foo) { exists<U> { U: Bar<T> } } }",
+                "forall<T> { if (FromEnv(T: Foo)) { exists<U> { Implemented(U: Bar<T>) } } }",
+            ),
+            (
+                "forall<T, 'a> { exists<U> { T: Bar<U>, U: Foo } }",
+                "forall<T, 'a> { exists<U> { Implemented(T: Bar<U>), Implemented(U: Foo) } }",
+            ),
+            (
+                "forall<T> { not { T: Foo } }",
+                "forall<T> { not { Implemented(T: Foo) } }",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn conjunction_precedence() {
+    display_goals(
+        "
+            trait Foo { }
+            struct A { }
+            struct B { }
+            struct C { }
+        ",
+        &[
+            (
+                "(A: Foo, B: Foo), C: Foo",
+                "(Implemented(A: Foo), Implemented(B: Foo)), Implemented(C: Foo)",
+            ),
+            (
+                "A: Foo, (B: Foo, C: Foo)",
+                "Implemented(A: Foo), Implemented(B: Foo), Implemented(C: Foo)",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn types() {
+    display_goals(
+        "
+            trait Iterator { type Item; }
+            trait Foo<T> { }
+            struct Vec<T> { }
+            struct u32 { }
+        ",
+        &[
+            (
+                "exists<T> { <Vec<T> as Iterator>::Item = u32 }",
+                "exists<T> { <Vec<T> as Iterator>::Item = u32 }",
+            ),
+            (
+                "forall<T> { Normalize(<T as Iterator>::Item -> Vec<T>) }",
+                "forall<T> { Normalize(<T as Iterator>::Item -> Vec<T>) }",
+            ),
+            (
+                "forall<'a> { dyn Foo<u32> + Iterator<Item = Vec<u32>>: Foo<u32> }",
+                // The projection bound is lowered to both `Self: Iterator`
+                // and the projection equality itself.
+                "forall<'a> { Implemented(dyn Foo<u32> + Iterator + Iterator<Item = Vec<u32>>: Foo<u32>) }",
+            ),
+            (
+                "for<'a> Vec<u32> = u32",
+                "for<'a> Vec<u32> = u32",
+            ),
+            (
+                "forall<T> { fn(T, Vec<T>) -> u32 = fn() }",
+                "forall<T> { fn(T, Vec<T>) -> u32 = fn() }",
+            ),
+            (
+                "forall<T> { [T] = [[T; const N]; T] }",
+                "forall<T> { [T] = [[T; const N]; T] }",
+            ),
+            (
+                "forall<'a, T> { &'a mut (dyn Iterator) = *const &'a T, &'a T: 'a }",
+                "forall<'a, T> { &'a mut (dyn Iterator) = *const &'a T, &'a T: 'a }",
+            ),
+        ],
+    );
+}
+
+#[test]
+fn fn_sugar() {
+    display_goals(
+        "
+            #[lang(fn_once)]
+            trait FnOnce<Args> { type Output; }
+            #[lang(fn)]
+            trait Fn<Args> { }
+            trait Foo { }
+            struct u32 { }
+        ",
+        &[
+            (
+                "dyn Fn(u32, (u32,)) -> u32 + Fn(): Foo",
+                "Implemented(dyn Fn(u32, (u32,)) -> u32 + Fn(): Foo)",
+            ),
+            (
+                "forall<F> { F: Fn(u32) -> () }",
+                // Outside of `dyn` types, the desugared form is shown.
+                "forall<T> { Implemented(T: Fn<(u32,)>), ProjectionEq(<T as FnOnce<(u32,)>>::Output = ()) }",
+            ),
+        ],
+    );
+}
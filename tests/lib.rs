@@ -1,6 +1,10 @@
+#![cfg_attr(feature = "bench", feature(test))]
+
 #[macro_use]
 mod test_util;
 #[macro_use]
 mod test;
 
+mod display;
 mod lowering;
+mod minimize;
@@ -66,6 +66,59 @@ fn auto_trait() {
     }
 }
 
+#[test]
+fn default_impl() {
+    lowering_error! {
+        program {
+            trait Foo<T> { }
+            impl Foo for .. { }
+        }
+        error_msg {
+            "auto trait `Foo` cannot have parameters"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                type Item;
+            }
+            impl Foo for .. { }
+        }
+        error_msg {
+            "auto trait `Foo` cannot define associated types"
+        }
+    }
+
+    lowering_error! {
+        program {
+            struct Foo { }
+            impl Foo for .. { }
+        }
+        error_msg {
+            "expected a trait, found `Foo`, which is not a trait"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Send { }
+            impl Send for .. { }
+            impl Send for .. { }
+        }
+        error_msg {
+            "duplicate default impl for trait `Send`"
+        }
+    }
+
+    lowering_success! {
+        program {
+            trait Send { }
+            impl Send for .. { }
+        }
+    }
+}
+
 #[test]
 fn negative_impl() {
     lowering_error! {
@@ -425,3 +478,83 @@ fn fundamental_multiple_type_parameters() {
         }
     }
 }
+
+#[test]
+fn duplicate_lang_item() {
+    lowering_error! {
+        program {
+            #[lang(owned_box)]
+            struct Box<T> { }
+
+            #[lang(owned_box)]
+            struct OtherBox<T> { }
+        }
+
+        error_msg {
+            "duplicate lang item `OwnedBox`"
+        }
+    }
+}
+
+#[test]
+fn duplicate_well_known_trait() {
+    lowering_error! {
+        program {
+            #[lang(add)]
+            trait Add<Rhs> { type Output; }
+
+            #[lang(add)]
+            trait Plus<Rhs> { type Output; }
+        }
+
+        error_msg {
+            "duplicate well-known trait `Add`"
+        }
+    }
+}
+
+#[test]
+fn owned_box_multiple_type_parameters() {
+    lowering_error! {
+        program {
+            #[lang(owned_box)]
+            struct Box<T, A> { }
+        }
+
+        error_msg {
+            "only a single parameter supported for fundamental type `Box`"
+        }
+    }
+}
+
+#[test]
+fn fn_sugar_requires_fn_trait() {
+    lowering_error! {
+        program {
+            #[lang(fn_once)]
+            trait FnOnce<Args> { type Output; }
+
+            trait Foo<Args> { }
+
+            struct S<F> where F: Foo(u32) -> u32 { }
+            struct u32 { }
+        }
+
+        error_msg {
+            "parenthesized parameters may only be used with a fn-family trait, not `Foo`"
+        }
+    }
+
+    lowering_error! {
+        program {
+            #[lang(fn)]
+            trait Fn<Args> { }
+
+            struct S<F> where F: Fn() { }
+        }
+
+        error_msg {
+            "no associated type `Output` defined in trait"
+        }
+    }
+}
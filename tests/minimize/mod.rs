@@ -0,0 +1,94 @@
+use chalk_integration::minimize::{minimize_repro, ReproOutcome};
+use chalk_solve::SolverChoice;
+
+/// Printing a parsed program and parsing the result again yields the
+/// same program (which we compare by printing it, as the spans differ).
+#[test]
+fn print_parse_roundtrip() {
+    let text = "
+        #[lang(owned_box)] #[upstream] struct Box<T> { value: T }
+        #[fundamental] struct Ref<'a, T> where T: Send { }
+        #[upstream] extern type Opaque;
+        #[scalar(int)] #[upstream] struct u32 { }
+        #[lang(copy)] trait Copy { }
+        #[lang(fn_once)] trait FnOnce<Args> { type Output; }
+        #[auto] #[upstream] trait Send { }
+        #[marker] #[non_enumerable] #[sealed] trait Marker { }
+        trait Iterator where Self: Marker, forall<'a> Self: Sized<'a> {
+            type Item: Clone + Into<u32> + Fn(u8) -> (dyn Send) where Self: Send;
+            type Gat<'a, U>: forall<'b> Fn(Ref<'b, U>) where U: Iterator<Item = u32>;
+        }
+        impl<T> Iterator for Box<T> where T: FnOnce<(u8,), Output = ()> {
+            type Item = for<'a> Ref<'a, T>;
+            default type Gat<'a, U> = (T, <T as Iterator>::Item, impl Send + Marker);
+        }
+        impl !Send for Ref<dyn Iterator<Item = u32> + Send, ()> { }
+        #[upstream] impl<T> Marker for Box<T> where T: Fn(u8, u16) -> u32 { }
+        #[lang(fn)] trait Fn<Args> { }
+        impl Marker for fn(u8, fn() -> (dyn Send)) -> (u8,) { }
+        impl<T> Marker for [([T], str); const N<T>] { }
+        impl<'a, T> Marker for (&'a mut *const T, *mut &'a T) { }
+        impl<T> Send for Ref<const Len<T>, const Zero> { }
+        impl<'a, T> Ref<'a, T> where T: Send { fn len; const MAX; }
+        impl Send for .. { }
+        trait Pair where forall<'a> Self: Iterator<Item = u32, Gat<'a, u8> = u16> { type Item: forall<'b> Iterator<Item = u8, Gat<'b, u16> = u32>; }
+        forall<T> { WellFormed(T: Iterator) if FromEnv(T), (T: Send, not { T = u8 }) }
+        forall { Compatible }
+        forall<T, 'a> {
+            LocalImplAllowed(T: Send) if
+                forall<> { IsLocal(T) },
+                exists<U> { Normalize(<T as Iterator>::Item -> U) },
+                if (FromEnv(T: Send) :- IsUpstream(T); forall<U> { T: Marker :- U = T }) { 'a = 'a },
+                compatible { DownstreamType(T) },
+                LayoutCompatible(T, Box<T>),
+                Upcast(dyn Iterator<Item = T> + Send => dyn Marker)
+        }
+    ";
+    let printed = chalk_parse::parse_program(text).unwrap().to_string();
+    let reprinted = chalk_parse::parse_program(&printed).unwrap().to_string();
+    assert_eq!(printed, reprinted);
+}
+
+#[test]
+fn minimize_unique_solution() {
+    let program_text = "
+        trait Bar { }
+        trait Baz { }
+        trait Unused { }
+        struct Foo { }
+        struct Other { field: Foo }
+        impl Bar for Foo where Foo: Baz { }
+        impl Baz for Foo { }
+        impl Baz for Other { }
+        impl Unused for Other { }
+        impl Bar for Other where Other: Unused { }
+    ";
+
+    // Pretend that `Foo: Bar` holding is the bug.
+    let minimized = minimize_repro(
+        program_text,
+        "Foo: Bar",
+        SolverChoice::default(),
+        |outcome| match outcome {
+            ReproOutcome::Solution(Some(solution)) => solution.is_unique(),
+            _ => false,
+        },
+    )
+    .unwrap();
+
+    assert_eq!(
+        minimized,
+        "trait Bar { }\nstruct Foo { }\nimpl Bar for Foo { }\n"
+    );
+}
+
+#[test]
+fn minimize_requires_failure() {
+    let result = minimize_repro(
+        "struct Foo { }",
+        "Foo = Foo",
+        SolverChoice::default(),
+        |_| false,
+    );
+    assert!(result.is_err());
+}
@@ -0,0 +1,55 @@
+//! Checks that `chalk_solve::api` is enough for an embedder: the
+//! goal below is written against the facade alone, and so is the
+//! `ForwardingDb` that it is solved in.
+
+use super::{DbHooks, ForwardingDb};
+use chalk_integration::db::ChalkDatabase;
+use chalk_solve::api::ir::cast::Cast;
+use chalk_solve::api::ir::family::ChalkIr;
+use chalk_solve::api::ir::*;
+use chalk_solve::api::{GoalExt, RustIrDatabase, SolverChoice};
+use std::sync::Arc;
+
+/// Hooks over a program without any items, overriding none of them.
+#[derive(Debug)]
+struct EmptyDb<'p> {
+    program: &'p dyn RustIrDatabase,
+}
+
+impl DbHooks for EmptyDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+}
+
+/// `exists<T> { T = () }` has the unique solution `T := ()`.
+#[test]
+fn minimal_embedder() {
+    let unit: Ty<ChalkIr> = ApplicationTy {
+        name: TypeName::Tuple(0),
+        parameters: vec![],
+    }
+    .cast();
+    let goal = Goal::Quantified(
+        QuantifierKind::Exists,
+        Binders {
+            binders: vec![ParameterKind::Ty(())].into(),
+            value: Arc::new(
+                EqGoal {
+                    a: Ty::BoundVar(0).cast(),
+                    b: unit.cast(),
+                }
+                .cast(),
+            ),
+        },
+    );
+
+    let db = ChalkDatabase::with("", SolverChoice::default());
+    db.with_program(|program| {
+        let solution = SolverChoice::default().into_solver().solve(
+            &ForwardingDb(&EmptyDb { program }),
+            &goal.into_closed_goal(),
+        );
+        assert!(solution.unwrap().is_unique());
+    });
+}
@@ -91,3 +91,427 @@ fn auto_trait_with_impls() {
         }
     }
 }
+
+/// The legacy syntax, `impl Send for .. { }`, makes `Send` an auto
+/// trait, whose default can be overridden (or removed) per type by
+/// explicit impls, as for `#[auto]` traits.
+#[test]
+fn default_impl() {
+    test! {
+        program {
+            trait Send { }
+            impl Send for .. { }
+
+            trait NotAuto { }
+
+            struct i32 { }
+            struct f32 { }
+            struct Vec<T> { }
+            struct Data<T> {
+                data: T
+            }
+
+            impl<T> Send for Vec<T> where T: Send { }
+            impl !Send for f32 { }
+        }
+
+        goal {
+            i32: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Data<i32>: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Data<f32>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Vec<f32>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            i32: NotAuto
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn owned_box_builtin_impls() {
+    test! {
+        program {
+            #[lang(unpin)]
+            #[auto] trait Unpin { }
+            #[auto] trait Send { }
+            #[lang(drop)]
+            trait Drop { }
+
+            // The (lack of) fields here does not matter: `Box` uses
+            // the built-in rules instead.
+            #[lang(owned_box)]
+            struct Box<T> { }
+
+            struct i32 { }
+            struct Pinned { }
+            impl !Unpin for Pinned { }
+            impl !Send for i32 { }
+        }
+
+        goal {
+            forall<T> { if (T: Send) { Box<T>: Send } }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Box<i32>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        // `Box<T>: Unpin` regardless of `T`.
+        goal {
+            Box<Pinned>: Unpin
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> { Box<T>: Drop }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            i32: Drop
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn tuple_auto_traits() {
+    test! {
+        program {
+            #[auto] trait Send { }
+
+            struct i32 { }
+            struct u32 { }
+            impl !Send for i32 { }
+        }
+
+        goal {
+            (u32, u32): Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            (u32, (i32,)): Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            (): Send
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+#[test]
+fn auto_trait_through_projection() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            trait Iterator { type Item; }
+
+            struct i32 { }
+            struct u32 { }
+            impl !Send for i32 { }
+
+            struct Ints { }
+            impl Iterator for Ints { type Item = i32; }
+
+            struct Uints { }
+            impl Iterator for Uints { type Item = u32; }
+
+            struct Wrapper<T> where T: Iterator {
+                item: <T as Iterator>::Item
+            }
+        }
+
+        goal {
+            Wrapper<Uints>: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Wrapper<Ints>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<T> {
+                if (T: Iterator<Item = u32>) {
+                    Wrapper<T>: Send
+                }
+            }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> {
+                if (T: Iterator; <T as Iterator>::Item: Send) {
+                    Wrapper<T>: Send
+                }
+            }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> {
+                if (T: Iterator) {
+                    Wrapper<T>: Send
+                }
+            }
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn extern_types() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            #[auto] trait Sync { }
+            #[lang(sized)] trait Sized { }
+
+            extern type Opaque;
+            extern type SendOpaque;
+            impl Send for SendOpaque { }
+
+            struct Foo { }
+            struct Wrapper<T> { value: T }
+            struct Ptr { opaque: Opaque }
+        }
+
+        // Extern types implement auto traits only through explicit
+        // impls, and are never `Sized`.
+        goal {
+            Opaque: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            SendOpaque: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            SendOpaque: Sync
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Opaque: Sized
+        } yields {
+            "No possible solution"
+        }
+
+        // Structs containing them are still subject to structural
+        // auto-trait reasoning.
+        goal {
+            Wrapper<SendOpaque>: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Ptr: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<T> { Wrapper<T>: Sized }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            (Foo, fn(Foo) -> Foo): Sized
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            (Foo, Opaque): Sized
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            exists<T> { T: Sized }
+        } yields {
+            "Ambiguous; no inference guidance"
+        }
+    }
+}
+
+/// An object type implements the auto traits named in its bounds (or
+/// implied by its supertraits), and no others: it has no fields to
+/// derive them from.
+#[test]
+fn dyn_auto_traits() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            #[auto] trait Sync { }
+            trait Foo { }
+            trait SendFoo where Self: Send { }
+
+            struct Wrapper<T> { field: T }
+        }
+
+        goal {
+            dyn Foo + Send: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            dyn Foo + Send: Sync
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            dyn SendFoo: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Wrapper<dyn Foo + Send>: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Wrapper<dyn Foo>: Send
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+/// Raw pointers are neither `Send` nor `Sync`, `UnsafeCell` is not
+/// `Sync`, and `&T` is `Send` if `T` is `Sync`; the other auto traits
+/// are structural for these types as usual.
+#[test]
+fn send_sync_exceptions() {
+    test! {
+        program {
+            #[lang(send)] #[auto] trait Send { }
+            #[lang(sync)] #[auto] trait Sync { }
+            #[auto] trait Other { }
+
+            struct u32 { }
+            struct Foo { }
+            #[lang(unsafe_cell)] struct UnsafeCell<T> { value: T }
+            struct Cell<T> { value: UnsafeCell<T> }
+            struct Ptr<T> { ptr: *const T }
+
+            impl Send for *const Foo { }
+        }
+
+        goal {
+            *const u32: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            *mut u32: Sync
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            *mut u32: Other
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Ptr<u32>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            *const Foo: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Cell<u32>: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Cell<u32>: Sync
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<'a> { &'a Cell<u32>: Send }
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<'a> { &'a mut Cell<u32>: Send }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { &'a u32: Send }
+        } yields {
+            "Unique"
+        }
+    }
+}
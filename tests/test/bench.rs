@@ -1,13 +1,17 @@
 //! Benchmarking tests.
 
+extern crate test;
 use self::test::Bencher;
 
-use chalk_ir;
+use chalk_integration::db::ChalkDatabase;
+use chalk_integration::query::LoweringDatabase;
 use chalk_solve::ext::*;
 use chalk_solve::SolverChoice;
 
-use super::{assert_result, parse_and_lower_goal, parse_and_lower_program_with_env};
+use super::assert_result;
 
+/// Solves the goal with a fresh solver each iteration, so that no
+/// tables are reused between iterations.
 fn run_bench(
     program_text: &str,
     solver_choice: SolverChoice,
@@ -15,19 +19,20 @@ fn run_bench(
     bencher: &mut Bencher,
     expected: &str,
 ) {
-    let (program, env) = parse_and_lower_program_with_env(program_text, solver_choice).unwrap();
-    chalk_ir::tls::set_current_program(&program, || {
-        let goal = parse_and_lower_goal(&program, goal_text).unwrap();
-        let peeled_goal = goal.into_peeled_goal();
-
+    let db = ChalkDatabase::with(program_text, solver_choice);
+    let peeled_goal = db
+        .parse_and_lower_goal(goal_text)
+        .unwrap()
+        .into_peeled_goal();
+    db.with_program(|program| {
         // Execute once to get an expected result.
-        let result = solver_choice.solve_root_goal(&env, &peeled_goal);
+        let result = solver_choice.into_solver().solve(program, &peeled_goal);
 
         // Check expectation.
         assert_result(&result, expected);
 
         // Then do it many times to measure time.
-        bencher.iter(|| solver_choice.solve_root_goal(&env, &peeled_goal));
+        bencher.iter(|| solver_choice.into_solver().solve(program, &peeled_goal));
     });
 }
 
@@ -95,11 +100,243 @@ forall<T> {
 
 #[bench]
 fn cycley_slg(b: &mut Bencher) {
+    run_bench(CYCLEY, SolverChoice::slg(20), CYCLEY_GOAL, b, "Unique");
+}
+
+/// `forall<T> { if (T: Clone) { Vec<T>: Clone, Vec<Vec<T>>: Clone, .. } }`,
+/// a long chain of `And` goals; solving it clones the remaining
+/// conjunction at every step, so it is sensitive to the cost of
+/// cloning goals.
+fn deep_and_goal(depth: usize) -> String {
+    let mut ty = "T".to_string();
+    let mut conjuncts = vec![];
+    for _ in 0..depth {
+        ty = format!("Vec<{}>", ty);
+        conjuncts.push(format!("{}: Clone", ty));
+    }
+    format!(
+        "forall<T> {{ if (T: Clone; T: Sized) {{ {} }} }}",
+        conjuncts.join(", ")
+    )
+}
+
+#[bench]
+fn deep_and_slg(b: &mut Bencher) {
     run_bench(
         CYCLEY,
-        SolverChoice::SLG { max_size: 20 },
-        CYCLEY_GOAL,
+        SolverChoice::slg(50),
+        &deep_and_goal(20),
         b,
         "Unique",
     );
 }
+
+/// `forall<T> { if (T: Sized; T: Clone; Vec<T>: Clone; ..) { Vec<..>: Clone, .. } }`:
+/// a goal in an environment with many clauses, which is carried into
+/// every subgoal (see `arena::EnvironmentArena`).
+fn clause_heavy_env_goal(depth: usize) -> String {
+    let mut ty = "T".to_string();
+    let mut clauses = vec!["T: Sized".to_string()];
+    for _ in 0..depth {
+        clauses.push(format!("{}: Clone", ty));
+        ty = format!("Vec<{}>", ty);
+    }
+    format!(
+        "forall<T> {{ if ({}) {{ {}: Clone, {}: Sized }} }}",
+        clauses.join("; "),
+        ty,
+        ty
+    )
+}
+
+#[bench]
+fn clause_heavy_env_slg(b: &mut Bencher) {
+    run_bench(
+        CYCLEY,
+        SolverChoice::slg(50),
+        &clause_heavy_env_goal(20),
+        b,
+        "Unique",
+    );
+}
+
+const EMPTY_ENV: &str = "
+trait Copy { }
+trait Clone { }
+trait Sized { }
+
+struct i32 { }
+struct u32 { }
+struct Rc<T> { }
+struct Box<T> { }
+struct Vec<T> { }
+
+impl Copy for i32 { }
+impl Copy for u32 { }
+impl<T> Clone for T where T: Copy { }
+impl<T> Clone for Rc<T> { }
+impl<T> Clone for Box<T> where T: Clone { }
+impl Sized for i32 { }
+impl Sized for u32 { }
+impl<T> Sized for Box<T> { }
+impl<T> Sized for Vec<T> where T: Sized { }
+";
+
+/// The goals posed by const evaluation: many tiny `T: Trait` goals
+/// without variables, in the empty environment.
+const EMPTY_ENV_GOALS: &[&str] = &[
+    "i32: Copy",
+    "u32: Clone",
+    "Rc<i32>: Clone",
+    "Box<u32>: Clone",
+    "Vec<i32>: Sized",
+    "Box<Vec<u32>>: Sized",
+];
+
+fn empty_env_trait_refs(db: &ChalkDatabase) -> Vec<chalk_ir::TraitRef<chalk_ir::family::ChalkIr>> {
+    use chalk_ir::{DomainGoal, Goal, LeafGoal, WhereClause};
+
+    EMPTY_ENV_GOALS
+        .iter()
+        .map(|text| match *db.parse_and_lower_goal(text).unwrap() {
+            Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+                trait_ref,
+            )))) => trait_ref,
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Solves the goals in the usual way, canonicalizing each of them
+/// first (the tables are already complete after the first iteration).
+#[bench]
+fn empty_env_goals_canonicalized(b: &mut Bencher) {
+    use chalk_ir::cast::Cast;
+
+    let db = ChalkDatabase::with(EMPTY_ENV, SolverChoice::default());
+    let trait_refs = empty_env_trait_refs(&db);
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        b.iter(|| {
+            for trait_ref in &trait_refs {
+                let goal: chalk_ir::Goal<_> = trait_ref.clone().cast();
+                assert!(solver.solve(program, &goal.into_closed_goal()).is_some());
+            }
+        });
+    });
+}
+
+/// Solves the same goals through `Solver::solve_in_empty_env`.
+#[bench]
+fn empty_env_goals_fast_path(b: &mut Bencher) {
+    let db = ChalkDatabase::with(EMPTY_ENV, SolverChoice::default());
+    let trait_refs = empty_env_trait_refs(&db);
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        b.iter(|| {
+            for trait_ref in &trait_refs {
+                assert!(solver.solve_in_empty_env(program, trait_ref).is_some());
+            }
+        });
+    });
+}
+
+/// Goals posed in sequence whose conjunctions end in a goal that the
+/// first one has already answered: once `Rc<u32>: Copy` is known not
+/// to hold, there is no need to look into the other conjuncts.
+const CACHED_SUBGOAL_GOALS: &[&str] = &[
+    "Rc<u32>: Copy",
+    "Box<Vec<Box<Vec<u32>>>>: Clone, Rc<u32>: Copy",
+    "Vec<Box<Vec<Box<u32>>>>: Sized, Rc<u32>: Copy",
+    "Rc<Vec<Box<u32>>>: Clone, Box<Vec<u32>>: Sized, Rc<u32>: Copy",
+];
+
+/// Solves `CACHED_SUBGOAL_GOALS` with a fresh solver each iteration,
+/// with or without `SlgOptions::prefer_cached_subgoals`.
+fn run_cached_subgoals_bench(prefer_cached: bool, b: &mut Bencher) {
+    use chalk_solve::SlgOptions;
+
+    let db = ChalkDatabase::with(EMPTY_ENV, SolverChoice::default());
+    let goals: Vec<_> = CACHED_SUBGOAL_GOALS
+        .iter()
+        .map(|text| db.parse_and_lower_goal(text).unwrap().into_peeled_goal())
+        .collect();
+    let solver_choice =
+        SolverChoice::slg_with(SlgOptions::default().prefer_cached_subgoals(prefer_cached));
+    db.with_program(|program| {
+        b.iter(|| {
+            let mut solver = solver_choice.into_solver();
+            for goal in &goals {
+                assert!(solver.solve(program, goal).is_none());
+            }
+        });
+    });
+}
+
+#[bench]
+fn cached_subgoals_in_order(b: &mut Bencher) {
+    run_cached_subgoals_bench(false, b);
+}
+
+#[bench]
+fn cached_subgoals_first(b: &mut Bencher) {
+    run_cached_subgoals_bench(true, b);
+}
+
+/// An impl-heavy program: `structs` structs, each with an impl of
+/// `Foo` and one of `Bar<D>`, with several parameters and where
+/// clauses. The binders of the impls are carried into each clause
+/// generated from them.
+fn impl_heavy_program(structs: usize) -> String {
+    let mut program = String::from("trait Foo { }\ntrait Bar<U> { }\n");
+    for i in 0..structs {
+        program += &format!(
+            "struct S{i}<A, B, C> {{ }}
+             impl<A, B, C> Foo for S{i}<A, B, C> where A: Foo, B: Bar<C> {{ }}
+             impl<A, B, C, D> Bar<D> for S{i}<A, B, C> where D: Foo {{ }}\n",
+            i = i
+        );
+    }
+    program
+}
+
+/// Generates the clauses for `?T: Foo`, which every impl of `Foo`
+/// could match.
+#[bench]
+fn clause_generation_impl_heavy(b: &mut Bencher) {
+    use chalk_ir::cast::Cast;
+    use chalk_ir::family::ChalkIr;
+    use chalk_ir::{DomainGoal, Environment, InferenceVar, TraitRef, TypeKindId};
+    use chalk_solve::clauses::program_clauses_with_origins;
+
+    let db = ChalkDatabase::with(&impl_heavy_program(200), SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let trait_id = match program.type_ids[&chalk_ir::intern("Foo")] {
+        TypeKindId::TraitId(trait_id) => trait_id,
+        _ => unreachable!(),
+    };
+    let goal: DomainGoal<ChalkIr> = TraitRef {
+        trait_id,
+        parameters: vec![InferenceVar::from(0).to_ty::<ChalkIr>().cast()],
+    }
+    .cast();
+    let environment = Environment::new();
+    b.iter(|| program_clauses_with_origins(&*program, &environment, &goal).unwrap());
+}
+
+/// Folds the where clauses of every impl of the impl-heavy program,
+/// each of which has binders of its own.
+#[bench]
+fn fold_impl_binders(b: &mut Bencher) {
+    use chalk_ir::fold::shift::Shift;
+
+    let db = ChalkDatabase::with(&impl_heavy_program(200), SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    b.iter(|| {
+        program
+            .impl_data
+            .values()
+            .map(|impl_datum| impl_datum.binders.value.where_clauses.shifted_in(1))
+            .count()
+    });
+}
@@ -0,0 +1,335 @@
+//! Tests for the built-in types `str`, `[T]` and `[T; N]`.
+
+use super::*;
+
+#[test]
+fn builtin_types_sized() {
+    test! {
+        program {
+            #[lang(sized)] trait Sized { }
+            struct u32 { }
+        }
+
+        goal {
+            forall<T, N> { [T; N]: Sized }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            [u32]: Sized
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            str: Sized
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn builtin_types_copy_clone() {
+    test! {
+        program {
+            #[lang(copy)] trait Copy { }
+            #[lang(clone)] trait Clone { }
+            #[scalar(int)] struct u32 { }
+            struct Foo { }
+        }
+
+        goal {
+            [u32; const Len]: Copy
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<N> { [u32; N]: Clone }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            [Foo; const Len]: Clone
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            [u32]: Copy
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            str: Clone
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn builtin_types_auto_traits() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            struct NotSend { }
+            impl !Send for NotSend { }
+            struct u32 { }
+        }
+
+        goal {
+            str: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            [[u32]; const Len]: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            [NotSend]: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<N> { [NotSend; N]: Send }
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn builtin_types_wf_and_unification() {
+    test! {
+        program {
+            trait Clone { }
+            struct Set<T> where T: Clone { }
+            struct u32 { }
+            impl Clone for u32 { }
+        }
+
+        goal {
+            WellFormed([Set<u32>; const Len])
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            WellFormed([Set<str>])
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            IsUpstream(str)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            exists<T, N> { [T; N] = [[u32]; const Len] }
+        } yields {
+            "Unique; substitution [?0 := [u32], ?1 := const Len], lifetime constraints []"
+        }
+
+        goal {
+            exists<T> { [T] = [u32; const Len] }
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+/// A program may declare a type named `str` of its own, which shadows
+/// the built-in one.
+#[test]
+fn builtin_str_shadowed() {
+    test! {
+        program {
+            #[lang(sized)] trait Sized { }
+            struct str { }
+        }
+
+        goal {
+            str: Sized
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+/// A reference is well-formed only if its referent outlives its
+/// lifetime, which is left to the embedder as a region constraint; a
+/// raw pointer is well-formed whatever its pointee.
+#[test]
+fn references_and_pointers_wf() {
+    test! {
+        program {
+            struct u32 { }
+            struct Ref<'a, T> { }
+        }
+
+        goal {
+            forall<'a, T> { if (WellFormed(T)) { WellFormed(&'a T) } }
+        } yields {
+            "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([WellFormed(!1_1)]), goal: !1_1: '!1_0 }]"
+        }
+
+        goal {
+            forall<'a, 'b, T> { WellFormed(&'a mut Ref<'b, T>) }
+        } yields {
+            "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([]), goal: Ref<'!1_1, !1_2>: '!1_0 }]"
+        }
+
+        goal {
+            forall<'a> { WellFormed(&'a u32) }
+        } yields {
+            "Unique; substitution [], lifetime constraints []"
+        }
+
+        goal {
+            forall<T> { if (WellFormed(T)) { WellFormed(*const T) } }
+        } yields {
+            "Unique; substitution [], lifetime constraints []"
+        }
+
+        goal {
+            forall<'a, T> { T: 'a }
+        } yields {
+            "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([]), goal: !1_1: '!1_0 }]"
+        }
+    }
+}
+
+#[test]
+fn references_and_pointers_traits() {
+    test! {
+        program {
+            #[lang(sized)] trait Sized { }
+            #[lang(copy)] trait Copy { }
+            #[lang(clone)] trait Clone { }
+            #[auto] trait Send { }
+            struct NotSend { }
+            impl !Send for NotSend { }
+            struct Foo { }
+        }
+
+        goal {
+            forall<'a> { &'a str: Sized }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { &'a Foo: Copy }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { &'a mut Foo: Clone }
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            *mut Foo: Copy
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { &'a mut NotSend: Send }
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            *const Foo: Send
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+/// References and raw pointers are `Unpin` even when what they point
+/// to is not, as moving them does not move it.
+#[test]
+fn references_and_pointers_unpin() {
+    test! {
+        program {
+            #[lang(unpin)] #[auto] trait Unpin { }
+            #[lang(sized)] trait Sized { }
+            struct Pinned { }
+            impl !Unpin for Pinned { }
+            struct Pin<P> { }
+        }
+
+        goal {
+            forall<'a, T> { &'a T: Unpin }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { &'a mut Pinned: Unpin }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a> { Pin<&'a mut Pinned>: Unpin }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> { *mut T: Unpin }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            [Pinned]: Unpin
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn references_and_pointers_unification() {
+    test! {
+        program {
+            struct u32 { }
+        }
+
+        goal {
+            forall<'a> { exists<'b, T> { &'a u32 = &'b T } }
+        } yields {
+            "Unique; substitution [?0 := u32, ?1 := '!1_0], lifetime constraints []"
+        }
+
+        goal {
+            forall<'a> { exists<T> { &'a mut u32 = &'a T } }
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            exists<T> { *const T = *mut u32 }
+        } yields {
+            "No possible solution"
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! Tests for `SlgOptions::prefer_cached_subgoals`.
+
+use super::*;
+use chalk_solve::SlgOptions;
+
+const PROGRAM: &str = "
+    trait Foo { }
+    trait Bar { }
+    trait Baz { }
+    struct u32 { }
+    struct i32 { }
+    struct Vec<T> { }
+    struct Box<T> { }
+    impl Foo for u32 { }
+    impl Foo for i32 { }
+    impl<T> Foo for Vec<T> where T: Foo { }
+    impl<T> Foo for Box<T> where T: Foo { }
+    impl Bar for i32 { }
+    impl Baz for u32 { }
+    impl Baz for i32 { }
+";
+
+/// Solves `goals` in turn with one solver, returning their solutions
+/// and the number of tables created in all.
+fn solve_all(prefer_cached: bool, goals: &[&str]) -> (Vec<Option<String>>, usize) {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let mut solver =
+        SolverChoice::slg_with(SlgOptions::default().prefer_cached_subgoals(prefer_cached))
+            .into_solver();
+    let solutions = goals
+        .iter()
+        .map(|goal| {
+            let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+            db.with_program(|program| {
+                solver
+                    .solve(program, &goal)
+                    .map(|solution| solution.to_string())
+            })
+        })
+        .collect();
+    (solutions, solver.stats().num_tables)
+}
+
+/// `u32: Bar` is known not to hold, so the conjunction fails without
+/// `Vec<Box<u32>>: Foo` being looked into.
+#[test]
+fn cached_failure_first() {
+    let goals = ["u32: Bar", "Vec<Box<u32>>: Foo, u32: Bar"];
+    let (solutions, tables) = solve_all(false, &goals);
+    let (cached_solutions, cached_tables) = solve_all(true, &goals);
+    assert_eq!(solutions, vec![None, None]);
+    assert_eq!(cached_solutions, solutions);
+    assert!(cached_tables < tables, "{} >= {}", cached_tables, tables);
+}
+
+/// `?T: Bar` has a single answer, which pins down `?T` before `?T: Baz`
+/// is looked into; otherwise both answers to `?T: Baz` are tried
+/// against `Bar`.
+#[test]
+fn cached_answer_first() {
+    let goals = ["exists<T> { T: Bar }", "exists<T> { T: Baz, T: Bar }"];
+    let (solutions, tables) = solve_all(false, &goals);
+    let (cached_solutions, cached_tables) = solve_all(true, &goals);
+    assert_eq!(
+        solutions,
+        vec![
+            Some("Unique; substitution [?0 := i32], lifetime constraints []".to_string()),
+            Some("Unique; substitution [?0 := i32], lifetime constraints []".to_string()),
+        ]
+    );
+    assert_eq!(cached_solutions, solutions);
+    assert!(cached_tables < tables, "{} >= {}", cached_tables, tables);
+}
+
+/// The order in which conjuncts are proven does not change the
+/// solutions.
+#[test]
+fn same_solutions() {
+    for goal in &[
+        "exists<T> { T: Bar, Vec<T>: Foo }",
+        "exists<T> { T: Foo }",
+        "i32: Bar, Vec<Box<i32>>: Foo",
+    ] {
+        assert_eq!(solve_all(true, &[goal]).0, solve_all(false, &[goal]).0);
+    }
+}
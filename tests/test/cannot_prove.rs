@@ -0,0 +1,108 @@
+//! Tests for `CannotProvePolicy` and `Solver::solve_with_caveat`.
+
+use super::*;
+use chalk_solve::{CannotProvePolicy, SlgOptions};
+
+/// In a `compatible` goal, `u32: Bar` rests on a `CannotProve`
+/// subgoal, as an upstream crate may yet add the impl.
+const PROGRAM: &str = "
+    trait Foo { }
+    #[upstream] trait Bar { }
+    #[upstream] struct u32 { }
+    struct Baz { }
+    impl Foo for u32 { }
+";
+
+/// Solves the goal of `text` with the given options, returning the
+/// solution as a string along with its caveat.
+fn solve_with(text: &str, options: SlgOptions) -> Option<(String, bool)> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(text).unwrap().into_closed_goal();
+    let mut solver = SolverChoice::slg_with(options).into_solver();
+    db.with_program(|program| solver.solve_with_caveat(program, &goal))
+        .map(|(solution, caveat)| (solution.to_string(), caveat))
+}
+
+fn solve(text: &str, cannot_prove: CannotProvePolicy) -> Option<(String, bool)> {
+    solve_with(text, SlgOptions::default().cannot_prove(cannot_prove))
+}
+
+#[test]
+fn ambiguous_by_default() {
+    assert_eq!(
+        solve(
+            "compatible { u32: Foo, u32: Bar }",
+            CannotProvePolicy::Ambiguous
+        ),
+        Some(("Ambiguous; no inference guidance".to_string(), false))
+    );
+}
+
+#[test]
+fn holds_with_caveat() {
+    assert_eq!(
+        solve(
+            "compatible { u32: Foo, u32: Bar }",
+            CannotProvePolicy::Holds
+        ),
+        Some((
+            "Unique; substitution [], lifetime constraints []".to_string(),
+            true
+        ))
+    );
+    assert_eq!(
+        solve(
+            "compatible { exists<T> { T = u32, T: Bar } }",
+            CannotProvePolicy::Holds
+        ),
+        Some((
+            "Unique; substitution [], lifetime constraints []".to_string(),
+            true
+        ))
+    );
+}
+
+/// The other conjuncts must still hold, and a goal that does not
+/// depend on `CannotProve` carries no caveat.
+#[test]
+fn holds_only_for_cannot_prove() {
+    assert_eq!(
+        solve(
+            "compatible { u32: Bar, Baz: Foo }",
+            CannotProvePolicy::Holds
+        ),
+        None
+    );
+    assert_eq!(
+        solve("u32: Foo", CannotProvePolicy::Holds),
+        Some((
+            "Unique; substitution [], lifetime constraints []".to_string(),
+            false
+        ))
+    );
+}
+
+/// An answer that holds only with a caveat does not disprove a
+/// negated goal, so the negation is ambiguous rather than failing.
+#[test]
+fn negation_of_caveat_is_ambiguous() {
+    for &policy in &[CannotProvePolicy::Ambiguous, CannotProvePolicy::Holds] {
+        assert_eq!(
+            solve("compatible { not { u32: Bar } }", policy),
+            Some(("Ambiguous; no inference guidance".to_string(), false))
+        );
+    }
+}
+
+/// Running out of universes is a resource limit, which leaves the goal
+/// ambiguous whatever the policy.
+#[test]
+fn universe_limit_is_ambiguous() {
+    for &policy in &[CannotProvePolicy::Ambiguous, CannotProvePolicy::Holds] {
+        let options = SlgOptions::default().max_universes(1).cannot_prove(policy);
+        assert_eq!(
+            solve_with("u32: Foo, forall<T> { T: Foo }", options),
+            Some(("Ambiguous; no inference guidance".to_string(), false))
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Tests for `chalk_solve::check_program`, which reports the errors of
+//! all items rather than the first.
+
+use chalk_integration::db::ChalkDatabase;
+use chalk_solve::SolverChoice;
+
+#[test]
+fn every_error_is_reported() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Hash { }
+            trait Generic {
+                fn bar<T>(Self, T);
+            }
+            #[upstream] trait Upstream { }
+            #[upstream] struct Bar { }
+            struct Set<K> where K: Hash { }
+
+            impl<T> Foo for T where T: Hash { }
+            impl<T> Foo for T where T: Generic { }
+            impl Upstream for Bar { }
+
+            struct MyType<K> {
+                value: Set<K>
+            }
+            struct Stuff {
+                field: dyn Generic
+            }
+        ",
+        SolverChoice::default(),
+    );
+
+    let report = db.check_program().unwrap();
+    assert!(!report.is_ok());
+    assert_eq!(report.coherence.len(), 1);
+    assert_eq!(report.orphans.len(), 1);
+    assert!(report.inherent.is_none());
+    assert_eq!(report.structs.len(), 2);
+    assert!(report.auto_traits.is_empty());
+    assert!(report.impls.is_empty());
+    assert_eq!(
+        report.to_string(),
+        "overlapping impls of trait \"Foo\"\n\
+         impl for trait \"Upstream\" violates the orphan rules\n\
+         type declaration \"MyType\" does not meet well-formedness requirements\n\
+         trait \"Generic\" cannot be made into an object: \
+         method \"bar\" of trait \"Generic\" has type parameters\n"
+    );
+}
+
+#[test]
+fn well_formed_program() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl Foo for Bar { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+        ",
+        SolverChoice::default(),
+    );
+
+    let report = db.check_program().unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.to_string(), "");
+}
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_check_matches_sequential() {
+    use chalk_integration::query::LoweringDatabase;
+
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Hash { }
+            trait Eq { }
+            #[upstream] trait Upstream { }
+            #[upstream] struct Bar { }
+            #[upstream] struct Baz { }
+            impl<T> Foo for T where T: Hash { }
+            impl<T> Foo for T where T: Eq { }
+            impl Upstream for Baz { }
+            impl Upstream for Bar { }
+        ",
+        SolverChoice::default(),
+    );
+
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let items = program.program_items();
+        let sequential = chalk_solve::check_program(&*program, SolverChoice::default(), &items);
+        let parallel =
+            chalk_solve::check_program_parallel(&*program, SolverChoice::default(), &items);
+        assert_eq!(sequential.to_string(), parallel.to_string());
+        assert_eq!(parallel.coherence.len(), 1);
+        assert_eq!(parallel.orphans.len(), 2);
+    });
+}
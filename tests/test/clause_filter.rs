@@ -0,0 +1,76 @@
+//! Tests for `SlgOptions::clause_filter`.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::{DomainGoal, ImplId, ProgramClause};
+use chalk_solve::clauses::ClauseOrigin;
+use chalk_solve::{ClauseFilter, SlgOptions};
+
+const PROGRAM: &str = "
+    trait Foo { }
+    struct u32 { }
+    struct i32 { }
+    impl Foo for u32 { }
+    impl Foo for i32 { }
+";
+
+/// The impls of the program, in the order they are declared.
+fn impls(program: &Program) -> Vec<ImplId> {
+    program.impl_data.keys().cloned().collect()
+}
+
+/// Solves the goal of `text` with the clause filter that `filter`
+/// returns for the program.
+fn solve(
+    text: &str,
+    filter: impl FnOnce(&Program) -> Option<ClauseFilter>,
+) -> Option<chalk_solve::Solution> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    let options = SlgOptions::default().clause_filter(filter(&program));
+    SolverChoice::slg_with(options)
+        .into_solver()
+        .solve(&*program, &goal)
+}
+
+fn without(origin: ClauseOrigin) -> Option<ClauseFilter> {
+    let filter = move |_: &DomainGoal<ChalkIr>, _: &ProgramClause<ChalkIr>, clause_origin| {
+        clause_origin != origin
+    };
+    Some(ClauseFilter::new(Box::leak(Box::new(filter))))
+}
+
+#[test]
+fn dropped_impl() {
+    let goal = "exists<T> { T: Foo }";
+    assert!(!solve(goal, |_| None).unwrap().is_unique());
+
+    // Without `impl Foo for u32`, only `i32` is left.
+    let without_u32 = |program: &Program| without(ClauseOrigin::Impl(impls(program)[0]));
+    assert!(solve(goal, without_u32).unwrap().is_unique());
+    assert!(solve("u32: Foo", without_u32).is_none());
+    assert!(solve("i32: Foo", without_u32).is_some());
+}
+
+#[test]
+fn dropped_environment() {
+    let goal = "forall<T> { if (T: Foo) { T: Foo } }";
+    assert!(solve(goal, |_| None).is_some());
+    assert!(solve(goal, |_| without(ClauseOrigin::Environment)).is_none());
+}
+
+#[test]
+fn filter_sees_goal() {
+    // Only the clauses for `WellFormed` goals are dropped.
+    let filter = |_: &Program| {
+        let filter = |goal: &DomainGoal<ChalkIr>, _: &ProgramClause<ChalkIr>, _| {
+            !matches!(goal, DomainGoal::WellFormed(_))
+        };
+        Some(ClauseFilter::new(Box::leak(Box::new(filter))))
+    };
+    assert!(solve("u32: Foo", filter).is_some());
+    assert!(solve("WellFormed(u32)", filter).is_none());
+    assert!(solve("WellFormed(u32)", |_| None).is_some());
+}
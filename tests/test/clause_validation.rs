@@ -0,0 +1,76 @@
+//! Tests for the validation of custom program clauses.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::*;
+use chalk_solve::clause_validation::{validate_clauses, validate_custom_clauses, ClauseError};
+
+/// The program `forall<T> { Vec<T>: Foo }`, with the binders of its
+/// custom clause replaced by `binders`.
+fn program_with_binders(binders: Vec<ParameterKind<()>>) -> Program {
+    let db = ChalkDatabase::with(
+        "struct Vec<T> { } trait Foo { } forall<T> { Vec<T>: Foo }",
+        SolverChoice::default(),
+    );
+    let mut program = (*db.program_ir().unwrap()).clone();
+    match &mut program.custom_clauses[0] {
+        ProgramClause::ForAll(implication) => implication.binders = binders.into(),
+        clause => panic!("unexpected clause {:?}", clause),
+    }
+    program
+}
+
+#[test]
+fn valid_custom_clauses() {
+    let program = program_with_binders(vec![ParameterKind::Ty(())]);
+    assert!(validate_custom_clauses(&program).is_ok());
+    assert!(validate_clauses(&[]).is_ok());
+}
+
+#[test]
+fn unbound_var_in_custom_clause() {
+    let program = program_with_binders(vec![]);
+    let invalid = validate_custom_clauses(&program).unwrap_err();
+    assert_eq!(invalid.index, 0);
+    assert_eq!(
+        invalid.error,
+        ClauseError::UnboundVar {
+            depth: 0,
+            binders: 0
+        }
+    );
+}
+
+#[test]
+fn ill_kinded_custom_clause() {
+    let program = program_with_binders(vec![ParameterKind::Lifetime(())]);
+    let invalid = validate_custom_clauses(&program).unwrap_err();
+    assert_eq!(
+        invalid.error,
+        ClauseError::IllKinded {
+            depth: 0,
+            expected: ParameterKind::Lifetime(()),
+            found: ParameterKind::Ty(()),
+        }
+    );
+    assert!(invalid
+        .to_string()
+        .ends_with("uses its parameter ^0 as a type, but the parameter is a lifetime"));
+}
+
+#[test]
+#[cfg(any(debug_assertions, feature = "validate-clauses"))]
+#[should_panic(expected = "refers to the bound variable ^0, but the clause has only 0 binder(s)")]
+fn solver_rejects_invalid_custom_clauses() {
+    let db = ChalkDatabase::with(
+        "struct Vec<T> { } trait Foo { } forall<T> { Vec<T>: Foo }",
+        SolverChoice::default(),
+    );
+    let goal = db.with_program(|_| {
+        db.parse_and_lower_goal("exists<T> { Vec<T>: Foo }")
+            .unwrap()
+            .into_closed_goal()
+    });
+    let program = program_with_binders(vec![]);
+    SolverChoice::default().into_solver().solve(&program, &goal);
+}
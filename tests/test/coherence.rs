@@ -75,6 +75,36 @@ fn two_blanket_impls_open_ended() {
     }
 }
 
+#[test]
+fn two_blanket_impls_disjoint_by_negative_impl() {
+    lowering_success! {
+        program {
+            trait Foo { }
+            trait Bar { }
+            trait Baz { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for T where T: Baz { }
+            impl<T> !Baz for T where T: Bar { }
+        }
+    }
+
+    // The negative impl does not cover all of the overlap.
+    lowering_error! {
+        program {
+            trait Foo { }
+            trait Bar { }
+            trait Baz { }
+            trait Qux { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for T where T: Baz { }
+            impl<T> !Baz for T where T: Qux { }
+        }
+        error_msg {
+            "overlapping impls of trait \"Foo\""
+        }
+    }
+}
+
 #[test]
 fn multiple_nonoverlapping_impls() {
     lowering_success! {
@@ -287,6 +317,102 @@ fn fundamental_traits() {
     }
 }
 
+#[test]
+fn sealed_traits() {
+    // Without `#[sealed]`, a downstream type could implement both `Bar`
+    // and `Baz` (see `two_blanket_impls_open_ended`).
+    lowering_success! {
+        program {
+            trait Foo { }
+            #[sealed] trait Bar { }
+            trait Baz { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for T where T: Baz { }
+        }
+    }
+
+    // A type of our own may still implement both.
+    lowering_error! {
+        program {
+            trait Foo { }
+            #[sealed] trait Bar { }
+            trait Baz { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for T where T: Baz { }
+            struct Quux { }
+            impl Bar for Quux { }
+            impl Baz for Quux { }
+        } error_msg {
+            "overlapping impls of trait \"Foo\""
+        }
+    }
+
+    // A sealed upstream trait cannot be implemented here at all.
+    lowering_error! {
+        program {
+            #[upstream] #[sealed] trait Foo { }
+            struct Bar { }
+            impl Foo for Bar { }
+        } error_msg {
+            "impl for trait \"Foo\" violates the orphan rules"
+        }
+    }
+}
+
+#[test]
+fn inherent_impls() {
+    // Inherent impls may overlap, as long as they do not define items
+    // of the same name.
+    lowering_success! {
+        program {
+            struct Foo<T> { }
+            struct Bar { }
+            impl<T> Foo<T> { fn len; }
+            impl Foo<Bar> { fn len_bar; const LEN; }
+        }
+    }
+
+    lowering_error! {
+        program {
+            struct Foo<T> { }
+            struct Bar { }
+            impl<T> Foo<T> { fn len; }
+            impl Foo<Bar> { const len; }
+        } error_msg {
+            "duplicate definitions with name \"len\" in overlapping inherent impls"
+        }
+    }
+
+    // Items of the same name are fine if the self types are disjoint,
+    // including through their where clauses.
+    lowering_success! {
+        program {
+            struct Foo<T> { }
+            struct Bar { }
+            struct Baz { }
+            trait Quux { }
+            impl Quux for Baz { }
+            impl Foo<Bar> { fn len; }
+            impl Foo<Baz> { fn len; }
+            impl<T> Foo<T> where T: Quux { fn new; }
+            impl Foo<Bar> { fn new; }
+        }
+    }
+
+    // ... but not if a downstream crate could make them overlap.
+    lowering_error! {
+        program {
+            struct Foo<T> { }
+            trait Quux { }
+            trait Corge { }
+            impl<T> Foo<T> where T: Quux { fn new; }
+            impl<T> Foo<T> where T: Corge { fn new; }
+        } error_msg {
+            "duplicate definitions with name \"new\" in overlapping inherent impls"
+        }
+    }
+}
+
 #[test]
 fn orphan_check() {
     // These tests are largely adapted from the compile-fail coherence-*.rs tests from rustc
@@ -459,3 +585,266 @@ fn orphan_check() {
         }
     }
 }
+
+#[cfg(feature = "parallel")]
+#[test]
+fn parallel_coherence_matches_sequential() {
+    use chalk_solve::coherence::CoherenceSolver;
+    use chalk_solve::SolverChoice;
+
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            trait Baz { }
+            struct Vec<T> { }
+            struct i32 { }
+            struct u32 { }
+
+            impl<T> Foo for Vec<T> where T: Foo { }
+            impl Foo for Vec<i32> { }
+            impl Foo for i32 { }
+            impl Foo for u32 { }
+
+            impl<T> Bar for Vec<T> where T: Foo { }
+            impl<T> Bar for Vec<T> where T: Baz { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        for &trait_id in program.trait_data.keys() {
+            let solver = CoherenceSolver::new(&*program, SolverChoice::default(), trait_id);
+            let sequential = solver.specialization_priorities();
+            let parallel = solver.check_trait_coherence_parallel();
+            match (sequential, parallel) {
+                (Ok(sequential), Ok(parallel)) => assert_eq!(sequential, parallel),
+                (Err(sequential), Err(parallel)) => {
+                    assert_eq!(sequential.to_string(), parallel.to_string())
+                }
+                (sequential, parallel) => panic!("{:?} vs {:?}", sequential, parallel),
+            }
+        }
+    });
+}
+
+#[test]
+fn overlap_explained() {
+    use chalk_integration::program::Program;
+    use chalk_ir::{ImplId, TraitId, TypeKindId};
+    use chalk_solve::clauses::ClauseOrigin;
+    use chalk_solve::coherence::{CoherenceError, CoherenceSolver, ImplOverlap};
+    use chalk_solve::SolverChoice;
+
+    fn trait_id(program: &Program, name: &str) -> TraitId {
+        match program.type_ids[&chalk_ir::intern(name)] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => unreachable!(),
+        }
+    }
+
+    fn impls_of(program: &Program, trait_id: TraitId) -> Vec<ImplId> {
+        program
+            .impl_data
+            .iter()
+            .filter(|(_, datum)| datum.binders.value.trait_ref.trait_id == trait_id)
+            .map(|(&impl_id, _)| impl_id)
+            .collect()
+    }
+
+    fn explain(program: &Program, trait_name: &str) -> ImplOverlap {
+        let solver = CoherenceSolver::new(
+            program,
+            SolverChoice::default(),
+            trait_id(program, trait_name),
+        );
+        match solver.specialization_priorities() {
+            Err(CoherenceError::OverlappingImpls(_, overlap)) => *overlap,
+            result => panic!("expected overlapping impls, got {:?}", result),
+        }
+    }
+
+    // Both impls apply to `Quux`, thanks to the impls of `Bar` and `Baz`.
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            trait Baz { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for T where T: Baz { }
+            struct Quux { }
+            impl Bar for Quux { }
+            impl Baz for Quux { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let overlap = explain(&program, "Foo");
+        assert!(!overlap.negative_reasoning);
+        assert_eq!(
+            format!("{:?}", overlap.witness.unwrap().value),
+            "[?0 := Quux, ?1 := Quux]"
+        );
+
+        let bar_impls = impls_of(&program, trait_id(&program, "Bar"));
+        let baz_impls = impls_of(&program, trait_id(&program, "Baz"));
+        let origins: Vec<Vec<ClauseOrigin>> = overlap
+            .where_clauses
+            .iter()
+            .map(|wc| wc.clauses.iter().map(|&(_, origin)| origin).collect())
+            .collect();
+        assert_eq!(origins.len(), 2);
+        assert!(origins[0].contains(&ClauseOrigin::Impl(bar_impls[0])));
+        assert!(origins[1].contains(&ClauseOrigin::Impl(baz_impls[0])));
+    });
+
+    // The impls are disjoint unless an upstream crate implements
+    // `Sized` for `str`, which it may do as `Sized` is not fundamental.
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            #[upstream] trait Sized { }
+            #[upstream] struct str { }
+            trait Bar { }
+            impl Bar for str { }
+            impl<T> Bar for T where T: Sized { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let overlap = explain(&program, "Bar");
+        assert!(overlap.negative_reasoning);
+        assert_eq!(overlap.witness, None);
+        assert_eq!(overlap.where_clauses.len(), 1);
+    });
+}
+
+/// An impl for `Vec<T>` is more specialized than the blanket impl, and
+/// so gets the higher priority.
+#[test]
+fn specialization_priority_of_specializing_impl() {
+    use chalk_solve::coherence::CoherenceSolver;
+    use chalk_solve::SolverChoice;
+
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Vec<T> { }
+            impl<T> Foo for T { }
+            impl<T> Foo for Vec<T> { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let foo = *program.trait_data.keys().next().unwrap();
+        let priorities = CoherenceSolver::new(&*program, SolverChoice::default(), foo)
+            .specialization_priorities()
+            .unwrap();
+        let impls: Vec<_> = program.impl_data.keys().cloned().collect();
+        assert!(priorities.priority(impls[0]) < priorities.priority(impls[1]));
+    });
+}
+
+/// More specialized impls get higher priorities, including along a
+/// chain of impls that each specialize the previous ones.
+#[test]
+fn specialization_priorities() {
+    use chalk_ir::TypeKindId;
+    use chalk_solve::coherence::CoherenceSolver;
+    use chalk_solve::SolverChoice;
+
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            struct Vec<T> { }
+            struct u32 { }
+            impl<T> Bar for T { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for Vec<T> where T: Bar { }
+            impl Foo for Vec<u32> { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let foo = match program.type_ids[&chalk_ir::intern("Foo")] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => unreachable!(),
+        };
+        let priorities = CoherenceSolver::new(&*program, SolverChoice::default(), foo)
+            .specialization_priorities()
+            .unwrap();
+        let mut impls: Vec<_> = program
+            .impl_data
+            .iter()
+            .filter(|(_, datum)| datum.trait_id() == foo)
+            .map(|(&impl_id, _)| impl_id)
+            .collect();
+        impls.sort();
+        assert!(priorities.priority(impls[0]) < priorities.priority(impls[1]));
+        assert!(priorities.priority(impls[1]) < priorities.priority(impls[2]));
+    });
+}
+
+/// The specialization graph has an edge from each impl to every impl
+/// that specializes it, including along a chain; impls that overlap
+/// no other impl have none.
+#[test]
+fn specialization_graph() {
+    use chalk_ir::TypeKindId;
+    use chalk_solve::coherence::CoherenceSolver;
+    use chalk_solve::SolverChoice;
+
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            struct Vec<T> { }
+            struct u32 { }
+            impl<T> Bar for T { }
+            impl<T> Foo for T where T: Bar { }
+            impl<T> Foo for Vec<T> where T: Bar { }
+            impl Foo for Vec<u32> { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let trait_id = |name| match program.type_ids[&chalk_ir::intern(name)] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => unreachable!(),
+        };
+        let impls_of = |trait_id| {
+            let mut impls: Vec<_> = program
+                .impl_data
+                .iter()
+                .filter(|(_, datum)| datum.trait_id() == trait_id)
+                .map(|(&impl_id, _)| impl_id)
+                .collect();
+            impls.sort();
+            impls
+        };
+
+        let foo = trait_id("Foo");
+        let impls = impls_of(foo);
+        let graph = CoherenceSolver::new(&*program, SolverChoice::default(), foo)
+            .specialization_graph()
+            .unwrap();
+        assert_eq!(graph.impls(), &impls[..]);
+        assert_eq!(graph.specializations(impls[0]), &[impls[1], impls[2]]);
+        assert_eq!(graph.specializations(impls[1]), &[impls[2]]);
+        assert_eq!(graph.specializations(impls[2]), &[]);
+        assert_eq!(graph.specialized_impls(impls[2]), vec![impls[0], impls[1]]);
+        assert_eq!(graph.edges().count(), 3);
+
+        let bar = trait_id("Bar");
+        let graph = CoherenceSolver::new(&*program, SolverChoice::default(), bar)
+            .specialization_graph()
+            .unwrap();
+        assert_eq!(graph.impls(), &impls_of(bar)[..]);
+        assert_eq!(graph.edges().count(), 0);
+    });
+}
@@ -360,3 +360,43 @@ fn local_impl_allowed_for_traits() {
         goal { forall<'a, T> { LocalImplAllowed(Upstream: UpstreamTrait<'a, Upstream, Local, T>) } } yields { "Unique" }
     }
 }
+
+#[test]
+fn intercrate_solve_mode() {
+    use chalk_solve::{SlgOptions, SolveMode};
+
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            #[upstream] struct Upstream { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let regular = SolverChoice::default();
+        let intercrate =
+            SolverChoice::slg_with(SlgOptions::default().solve_mode(SolveMode::Intercrate));
+        assert_eq!(regular.with_solve_mode(SolveMode::Intercrate), intercrate);
+
+        // In intercrate mode, an upstream crate may add the impl.
+        let goal = db
+            .parse_and_lower_goal("Upstream: Foo")
+            .unwrap()
+            .into_closed_goal();
+        let mut regular_solver = regular.into_solver();
+        let mut intercrate_solver = intercrate.into_solver();
+        assert_eq!(regular_solver.solve(program, &goal), None);
+        assert_eq!(
+            intercrate_solver.solve(program, &goal),
+            Some(Solution::Ambig(chalk_solve::Guidance::Unknown))
+        );
+
+        // The answers are cached under different keys.
+        assert_eq!(intercrate_solver.solve_mode(), SolveMode::Intercrate);
+        assert_ne!(
+            regular_solver.cache_key(&goal),
+            intercrate_solver.cache_key(&goal)
+        );
+    });
+}
@@ -1,6 +1,7 @@
 //! Tests targeting coinduction specifically
 
 use super::*;
+use chalk_solve::{CoinductiveGoals, GoalKind, SlgOptions};
 
 #[test]
 fn coinductive_semantics() {
@@ -80,3 +81,72 @@ fn mixed_semantics() {
         }
     }
 }
+
+/// With mutually recursive supertraits, the implied bounds elaborated
+/// from the environment cycle: `FromEnv(T: B)` holds if `FromEnv(T: A)`
+/// does, and vice versa. This cycle is only accepted when `FromEnv`
+/// goals are coinductive.
+#[test]
+fn coinductive_from_env() {
+    let program_text = "
+        trait A where Self: B { }
+        trait B where Self: A { }
+        trait C { }
+    ";
+    let goal_text = "forall<T> { if (FromEnv(T: A) :- FromEnv(T: C)) { FromEnv(T: A) } }";
+
+    let solve = |goals: CoinductiveGoals| {
+        let choice = SolverChoice::slg_with(SlgOptions::default().coinductive_goals(goals));
+        let db = ChalkDatabase::with(program_text, choice);
+        db.with_program(|_| {
+            let goal = db
+                .parse_and_lower_goal(goal_text)
+                .unwrap()
+                .into_closed_goal();
+            let mut solver = choice.into_solver();
+            solver.solve(&db, &goal)
+        })
+    };
+
+    assert!(solve(CoinductiveGoals::default()).is_none());
+    assert!(solve(CoinductiveGoals::default().from_env(true)).is_some_and(|s| s.is_unique()));
+}
+
+#[test]
+fn goal_kinds() {
+    let db = ChalkDatabase::with(
+        "
+            #[auto] trait Send { }
+            trait Clone { }
+            trait Iterator { type Item; }
+            struct Foo { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let kind_of = |text: &str| {
+            let goal = db.parse_and_lower_goal(text).unwrap();
+            GoalKind::of(program, &goal)
+        };
+        assert_eq!(kind_of("Foo: Send"), GoalKind::AutoTrait);
+        assert_eq!(kind_of("forall<T> { T: Clone }"), GoalKind::Trait);
+        assert_eq!(kind_of("WellFormed(Foo: Clone)"), GoalKind::WellFormedTrait);
+        assert_eq!(
+            kind_of("exists<U> { Normalize(<Foo as Iterator>::Item -> U) }"),
+            GoalKind::Compound
+        );
+
+        let goal = db
+            .parse_and_lower_goal("forall<T> { FromEnv(T: Clone) }")
+            .unwrap()
+            .into_closed_goal();
+        assert!(!SolverChoice::default()
+            .into_solver()
+            .is_coinductive(program, &goal));
+        let choice = SolverChoice::slg_with(
+            SlgOptions::default().coinductive_goals(CoinductiveGoals::default().from_env(true)),
+        );
+        assert!(choice.into_solver().is_coinductive(program, &goal));
+    });
+}
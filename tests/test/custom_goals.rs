@@ -0,0 +1,122 @@
+//! Tests for embedder-defined goals (`DomainGoal::Custom`), which are
+//! decided by `RustIrDatabase::solve_custom_goal`.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{CustomGoalSolution, Guidance, RustIrDatabase};
+
+/// Wraps a program, deciding `ConstEvaluatable<T>` as follows:
+///
+/// - `ConstEvaluatable<Foo>` holds;
+/// - `ConstEvaluatable<Vec<T>>` holds if `T: Clone`;
+/// - `ConstEvaluatable<Bar>` is ambiguous;
+/// - nothing else holds.
+#[derive(Debug)]
+struct ConstEvaluatableDb<'p> {
+    program: &'p Program,
+}
+
+impl ConstEvaluatableDb<'_> {
+    fn type_kind_id(&self, name: &str) -> TypeKindId {
+        self.program.type_ids[&chalk_ir::intern(name)]
+    }
+
+    fn ty(&self, name: &str, parameters: Vec<Parameter<ChalkIr>>) -> Ty<ChalkIr> {
+        ApplicationTy {
+            name: TypeName::TypeKindId(self.type_kind_id(name)),
+            parameters,
+        }
+        .cast()
+    }
+
+    fn const_evaluatable(&self, ty: Ty<ChalkIr>) -> Goal<ChalkIr> {
+        DomainGoal::Custom(CustomGoal {
+            name: chalk_ir::intern("ConstEvaluatable"),
+            parameters: vec![ty.cast()],
+        })
+        .cast()
+    }
+}
+
+impl DbHooks for ConstEvaluatableDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn solve_custom_goal(&self, goal: &CustomGoal<ChalkIr>) -> CustomGoalSolution {
+        assert_eq!(goal.name, chalk_ir::intern("ConstEvaluatable"));
+        let apply = match goal.parameters[0].assert_ty_ref() {
+            Ty::Apply(apply) => apply,
+            _ => return CustomGoalSolution::NoSolution,
+        };
+        match apply.name {
+            TypeName::TypeKindId(id) if id == self.type_kind_id("Foo") => {
+                CustomGoalSolution::Holds(vec![])
+            }
+            TypeName::TypeKindId(id) if id == self.type_kind_id("Bar") => {
+                CustomGoalSolution::Ambiguous
+            }
+            TypeName::TypeKindId(id) if id == self.type_kind_id("Vec") => {
+                let clone = match self.type_kind_id("Clone") {
+                    TypeKindId::TraitId(trait_id) => trait_id,
+                    _ => panic!("`Clone` is not a trait"),
+                };
+                CustomGoalSolution::Holds(vec![TraitRef {
+                    trait_id: clone,
+                    parameters: apply.parameters.clone(),
+                }
+                .cast()])
+            }
+            _ => CustomGoalSolution::NoSolution,
+        }
+    }
+}
+
+#[test]
+fn custom_goals() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            struct Baz { }
+            struct Vec<T> { }
+            impl Clone for Foo { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let db = ConstEvaluatableDb { program };
+        let foo = db.ty("Foo", vec![]);
+        let bar = db.ty("Bar", vec![]);
+        let baz = db.ty("Baz", vec![]);
+        let solve = |goal: Goal<ChalkIr>| {
+            SolverChoice::default()
+                .into_solver()
+                .solve(&ForwardingDb(&db), &goal.into_closed_goal())
+        };
+
+        assert!(solve(db.const_evaluatable(foo.clone()))
+            .unwrap()
+            .is_unique());
+        assert!(solve(db.const_evaluatable(db.ty("Vec", vec![foo.cast()])))
+            .unwrap()
+            .is_unique());
+        assert_eq!(
+            solve(db.const_evaluatable(db.ty("Vec", vec![bar.clone().cast()]))),
+            None
+        );
+        assert_eq!(solve(db.const_evaluatable(baz)), None);
+        assert!(!solve(db.const_evaluatable(bar)).unwrap().is_unique());
+
+        // The database never sees inference variables; such goals flounder.
+        let exists = db
+            .const_evaluatable(Ty::BoundVar(0))
+            .quantify(QuantifierKind::Exists, vec![ParameterKind::Ty(())]);
+        assert_eq!(solve(exists), Some(Solution::Ambig(Guidance::Unknown)));
+    });
+}
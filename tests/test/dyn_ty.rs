@@ -0,0 +1,149 @@
+//! Tests for `dyn Trait` types, which implement their own traits and,
+//! through them, their supertraits.
+
+use super::*;
+
+#[test]
+fn dyn_implements_own_traits() {
+    test! {
+        program {
+            trait Foo { }
+            trait Bar { }
+            trait Baz<T> { }
+            struct u32 { }
+        }
+
+        goal {
+            dyn Foo: Foo
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo + Bar: Bar
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo: Bar
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            dyn Baz<u32>: Baz<u32>
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            exists<T> { dyn Baz<u32>: Baz<T> }
+        } yields {
+            "Unique; substitution [?0 := u32]"
+        }
+    }
+}
+
+#[test]
+fn dyn_supertraits() {
+    test! {
+        program {
+            trait Base { }
+            trait Middle<T> where Self: Base { }
+            trait Foo where Self: Middle<u32> { }
+            trait Other { }
+            struct u32 { }
+        }
+
+        goal {
+            dyn Foo: Middle<u32>
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo: Base
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo: Other
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn dyn_supertraits_with_illegal_self() {
+    test! {
+        program {
+            trait Eq<T> { }
+            trait Bar { }
+            trait Foo where Self: Eq<Self>, Self: Bar { }
+        }
+
+        // `Self` may only appear as the self type of a supertrait
+        // bound, so this bound does not carry over to objects...
+        goal {
+            dyn Foo: Eq<dyn Foo>
+        } yields {
+            "No possible solution"
+        }
+
+        // ...but the other bounds still do.
+        goal {
+            dyn Foo: Bar
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+#[test]
+fn dyn_associated_ty_bounds() {
+    test! {
+        program {
+            trait Clone { }
+            trait Iterator { type Item: Clone; }
+            trait Collection where Self: Iterator { }
+            struct u32 { }
+            impl Clone for u32 { }
+        }
+
+        goal {
+            <dyn Iterator<Item = u32> as Iterator>::Item = u32
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            <dyn Iterator as Iterator>::Item: Clone
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> {
+                <dyn Iterator<Item = T> as Iterator>::Item: Clone
+            }
+        } yields {
+            "Unique"
+        }
+
+        // The bounds of the associated types of supertraits hold too.
+        goal {
+            <dyn Collection as Iterator>::Item: Clone
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Iterator: Clone
+        } yields {
+            "No possible solution"
+        }
+    }
+}
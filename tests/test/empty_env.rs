@@ -0,0 +1,118 @@
+//! Tests for the fast path of `T: Trait` goals without variables in
+//! the empty environment (`Solver::solve_in_empty_env`).
+
+use super::*;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+
+const PROGRAM: &str = "
+    trait Copy { }
+    trait Clone { }
+    struct u32 { }
+    struct Vec<T> { }
+    struct Foo { }
+    impl Copy for u32 { }
+    impl<T> Clone for T where T: Copy { }
+    impl<T> Clone for Vec<T> where T: Clone { }
+";
+
+fn trait_ref(db: &ChalkDatabase, text: &str) -> TraitRef<ChalkIr> {
+    match *db.parse_and_lower_goal(text).unwrap() {
+        Goal::Leaf(LeafGoal::DomainGoal(DomainGoal::Holds(WhereClause::Implemented(
+            trait_ref,
+        )))) => trait_ref,
+        goal => panic!("not a trait goal: {:?}", goal),
+    }
+}
+
+/// The fast path gives the same solutions as solving the goal in the
+/// usual way.
+#[test]
+fn same_solutions() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    for &text in &[
+        "u32: Copy",
+        "Vec<u32>: Clone",
+        "Foo: Clone",
+        "Vec<Foo>: Copy",
+    ] {
+        let trait_ref = trait_ref(&db, text);
+        let goal: Goal<ChalkIr> = trait_ref.clone().cast();
+        let goal = goal.into_closed_goal();
+        db.with_program(|program| {
+            let expected = SolverChoice::default().into_solver().solve(program, &goal);
+            let mut solver = SolverChoice::default().into_solver();
+            assert_eq!(
+                solver.solve_in_empty_env(program, &trait_ref),
+                expected,
+                "{}",
+                text
+            );
+            assert_eq!(solver.solve(program, &goal), expected, "{}", text);
+        });
+    }
+}
+
+/// A goal solved once is answered from the cache afterwards, without
+/// creating tables; rolling back to a snapshot drops the entries added
+/// since, like the tables.
+#[test]
+fn cached_until_rollback() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let u32_ref = trait_ref(&db, "u32: Clone");
+    let vec_ref = trait_ref(&db, "Vec<Vec<u32>>: Clone");
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        assert!(solver
+            .solve_in_empty_env(program, &u32_ref)
+            .unwrap()
+            .is_unique());
+        let before = solver.num_tables();
+        let snapshot = solver.snapshot();
+
+        assert!(solver
+            .solve_in_empty_env(program, &vec_ref)
+            .unwrap()
+            .is_unique());
+        let after = solver.num_tables();
+        assert!(after > before);
+        let goal: Goal<ChalkIr> = vec_ref.clone().cast();
+        assert!(solver
+            .solve(program, &goal.into_closed_goal())
+            .unwrap()
+            .is_unique());
+        assert_eq!(solver.num_tables(), after);
+
+        solver.rollback_to_snapshot(snapshot);
+        assert_eq!(solver.num_tables(), before);
+        assert!(solver
+            .solve_in_empty_env(program, &u32_ref)
+            .unwrap()
+            .is_unique());
+        assert_eq!(solver.num_tables(), before);
+        assert!(solver
+            .solve_in_empty_env(program, &vec_ref)
+            .unwrap()
+            .is_unique());
+        assert_eq!(solver.num_tables(), after);
+    });
+}
+
+/// Goals of other shapes are solved as usual.
+#[test]
+fn other_goals_not_cached() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db
+        .parse_and_lower_goal("exists<T> { Vec<T>: Clone }")
+        .unwrap()
+        .into_peeled_goal();
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        let snapshot = solver.snapshot();
+        let solution = solver.solve(program, &goal);
+        solver.rollback_to_snapshot(snapshot);
+        assert_eq!(solver.solve(program, &goal), solution);
+        assert!(solver.num_tables() > 0);
+    });
+}
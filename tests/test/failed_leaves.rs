@@ -0,0 +1,74 @@
+//! Tests for `Solver::solve_with_failures`.
+
+use super::*;
+use chalk_solve::SlgOptions;
+
+const PROGRAM: &str = "
+    trait Foo { }
+    trait Bar { }
+    struct u32 { }
+    struct i32 { }
+    impl Foo for u32 { }
+";
+
+/// Solves the goal of `text`, returning the failing leaves as
+/// strings, or `Ok` if it can be proven.
+fn failures(text: &str, max_failed_leaves: usize) -> Result<(), Vec<String>> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    let mut solver =
+        SolverChoice::slg_with(SlgOptions::default().max_failed_leaves(max_failed_leaves))
+            .into_solver();
+    db.with_program(|program| {
+        solver
+            .solve_with_failures(program, &goal)
+            .map(|_| ())
+            .map_err(|leaves| {
+                leaves
+                    .iter()
+                    .map(|leaf| format!("{:?}", leaf.canonical.value.goal))
+                    .collect()
+            })
+    })
+}
+
+#[test]
+fn first_failure_by_default() {
+    assert_eq!(
+        failures("u32: Foo, u32: Bar, i32: Foo", 1),
+        Err(vec!["Implemented(u32: Bar)".to_string()])
+    );
+}
+
+#[test]
+fn all_failures() {
+    assert_eq!(
+        failures("u32: Foo, u32: Bar, i32: Foo", 10),
+        Err(vec![
+            "Implemented(u32: Bar)".to_string(),
+            "Implemented(i32: Foo)".to_string(),
+        ])
+    );
+
+    // Under hypotheses.
+    assert_eq!(
+        failures("forall<T> { if (T: Foo) { T: Foo, T: Bar } }", 10),
+        Err(vec!["Implemented(!1_0: Bar)".to_string()])
+    );
+}
+
+#[test]
+fn success() {
+    assert_eq!(failures("u32: Foo", 10), Ok(()));
+    assert_eq!(failures("exists<T> { T: Foo }", 10), Ok(()));
+}
+
+/// Neither `T = u32` nor `T = i32` fails on its own, so the goal as a
+/// whole is reported.
+#[test]
+fn no_failing_leaf() {
+    assert_eq!(
+        failures("exists<T> { T = u32, T = i32 }", 10),
+        Err(vec!["((^0 = u32), (^0 = i32))".to_string()])
+    );
+}
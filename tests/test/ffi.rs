@@ -0,0 +1,119 @@
+//! Tests for the C ABI of `chalk_integration::ffi`, called as a C
+//! caller would.
+
+use chalk_integration::db::ChalkDatabase;
+use chalk_integration::ffi::*;
+use chalk_integration::query::LoweringDatabase;
+use chalk_solve::SolverChoice;
+use std::ffi::{CStr, CString};
+use std::panic;
+use std::ptr;
+
+/// Poses `goal` to `program` and returns the JSON of the solution.
+fn solve(program: *const ChalkProgram, goal: &str) -> String {
+    let goal = CString::new(goal).unwrap();
+    unsafe {
+        let json = chalk_program_solve(program, goal.as_ptr());
+        let result = CStr::from_ptr(json).to_str().unwrap().to_string();
+        chalk_string_free(json);
+        result
+    }
+}
+
+fn load(program: &str) -> *mut ChalkProgram {
+    let program = CString::new(program).unwrap();
+    let handle = unsafe { chalk_program_load(program.as_ptr()) };
+    assert!(!handle.is_null());
+    handle
+}
+
+#[test]
+fn solve_goals() {
+    assert_eq!(chalk_ffi_version(), FFI_VERSION);
+
+    let program = load(
+        "
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl Clone for Foo { }
+            impl Clone for Bar { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        ",
+    );
+    assert_eq!(
+        solve(program, "Vec<Foo>: Clone"),
+        r#"{"status":"unique","solution":"Unique; substitution [], lifetime constraints []"}"#
+    );
+    assert_eq!(
+        solve(program, "exists<T> { Vec<T>: Clone }"),
+        r#"{"status":"ambiguous","solution":"Ambiguous; no inference guidance"}"#
+    );
+    assert_eq!(
+        solve(program, "Vec<u32>: Clone"),
+        r#"{"status":"error","error":"invalid type name `u32`"}"#
+    );
+    assert_eq!(
+        solve(program, "forall<T> { T: Clone }"),
+        r#"{"status":"no-solution"}"#
+    );
+
+    // The quotes and newlines of the message are escaped.
+    assert_eq!(
+        solve(program, "Foo: \"Clone"),
+        r#"{"status":"error","error":"parse error: InvalidToken { location: 5 }\nposition: `Foo: \"Clone`\n                ^\n"}"#
+    );
+    unsafe { chalk_program_free(program) };
+}
+
+#[test]
+fn invalid_programs_and_arguments() {
+    // Errors in the program are reported when a goal is posed.
+    let program = load("trait Foo { } impl Foo for Bar { }");
+    assert_eq!(
+        solve(program, "Bar: Foo"),
+        r#"{"status":"error","error":"invalid type name `Bar`"}"#
+    );
+    unsafe { chalk_program_free(program) };
+
+    unsafe {
+        assert!(chalk_program_load(ptr::null()).is_null());
+        assert!(chalk_program_solve(ptr::null(), ptr::null()).is_null());
+        chalk_program_free(ptr::null_mut());
+        chalk_string_free(ptr::null_mut());
+    }
+    let program = load("trait Foo { }");
+    let json = unsafe { chalk_program_solve(program, ptr::null()) };
+    assert_eq!(
+        unsafe { CStr::from_ptr(json) }.to_str().unwrap(),
+        r#"{"status":"error","error":"the goal is null or not UTF-8"}"#
+    );
+    unsafe {
+        chalk_string_free(json);
+        chalk_program_free(program);
+    }
+}
+
+/// A panic while solving poisons the lock of the solver; the next goal
+/// gets a fresh solver rather than the poisoned one.
+#[test]
+fn solve_after_panic() {
+    let db = ChalkDatabase::with(
+        "trait Foo { } struct Bar { } impl Foo for Bar { }",
+        SolverChoice::default(),
+    );
+    let solver = db.solver();
+    let result = panic::catch_unwind(|| {
+        let _guard = solver.lock().unwrap();
+        panic!("panicked while solving");
+    });
+    assert!(result.is_err());
+    assert!(solver.is_poisoned());
+
+    assert_eq!(
+        solve_to_json(&db, "Bar: Foo"),
+        r#"{"status":"unique","solution":"Unique; substitution [], lifetime constraints []"}"#
+    );
+    assert!(!solver.is_poisoned());
+}
@@ -0,0 +1,140 @@
+//! Tests for function pointer types, `fn(A, B) -> C`.
+
+use super::*;
+
+#[test]
+fn fn_ptr_implements_fn_traits() {
+    test! {
+        program {
+            #[lang(fn_once)] trait FnOnce<Args> { type Output; }
+            #[lang(fn_mut)] trait FnMut<Args> where Self: FnOnce<Args> { }
+            #[lang(fn)] trait Fn<Args> where Self: FnMut<Args> { }
+            trait Clone { }
+            struct u32 { }
+            struct Foo { }
+        }
+
+        goal {
+            fn(u32) -> Foo: Fn<(u32,)>
+        } yields {
+            "Unique"
+        }
+
+        // Every arity is covered by the same clauses.
+        goal {
+            fn(u32, Foo, u32, Foo, u32): FnOnce<(u32, Foo, u32, Foo, u32)>
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            fn(): FnMut<()>
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            fn(u32): Fn<(Foo,)>
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            fn(u32): Clone
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<T> { fn(T) -> T: Fn<(T,)> }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            exists<A> { fn(u32, Foo): Fn<A> }
+        } yields {
+            "Unique; substitution [?0 := (u32, Foo)], lifetime constraints []"
+        }
+
+        goal {
+            exists<T> { Normalize(<fn(u32) -> Foo as FnOnce<(u32,)>>::Output -> T) }
+        } yields {
+            "Unique; substitution [?0 := Foo], lifetime constraints []"
+        }
+
+        goal {
+            fn(u32) -> Foo: Fn(u32) -> Foo
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            fn(u32) -> Foo: Fn(u32) -> u32
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn fn_ptr_unification() {
+    test! {
+        program {
+            struct u32 { }
+            struct Foo { }
+        }
+
+        goal {
+            exists<T, U> { fn(T, u32) -> U = fn(Foo, u32) }
+        } yields {
+            "Unique; substitution [?0 := Foo, ?1 := ()], lifetime constraints []"
+        }
+
+        goal {
+            exists<T> { fn(T) = fn(u32, u32) }
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn fn_ptr_wf_and_auto_traits() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            trait Clone { }
+            struct NotSend { }
+            impl !Send for NotSend { }
+            struct Set<T> where T: Clone { }
+            struct u32 { }
+            impl Clone for u32 { }
+        }
+
+        goal {
+            WellFormed(fn(Set<u32>) -> u32)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            WellFormed(fn(u32) -> Set<NotSend>)
+        } yields {
+            "No possible solution"
+        }
+
+        // Function pointers hold no data.
+        goal {
+            fn(NotSend) -> NotSend: Send
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            IsUpstream(fn(u32))
+        } yields {
+            "Unique"
+        }
+    }
+}
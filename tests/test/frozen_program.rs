@@ -0,0 +1,111 @@
+//! Tests for `FrozenProgram`.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::frozen::{FreezeError, FrozenProgram};
+use chalk_solve::{MissingData, RustIrDatabase};
+use std::sync::Arc;
+
+const PROGRAM: &str = "
+    trait Foo { type Assoc; }
+    trait Bar { }
+    #[auto] trait Send { }
+    struct u32 { }
+    struct Vec<T> { }
+    struct Wrapper<T> { field: Vec<T> }
+    struct Unnamed { }
+    impl Foo for u32 { type Assoc = Vec<u32>; }
+    impl<T> Foo for Vec<T> where T: Foo { type Assoc = T; }
+    impl !Send for u32 { }
+";
+
+fn type_id(program: &Program, name: &str) -> TypeKindId {
+    program.type_ids[&intern(name)]
+}
+
+/// Freezes the program, starting from the trait `Foo` alone.
+fn freeze_from_foo(program: &Program) -> FrozenProgram {
+    FrozenProgram::freeze(program, vec![type_id(program, "Foo")]).unwrap()
+}
+
+#[test]
+fn same_solutions() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let frozen = freeze_from_foo(&program);
+    let goals = [
+        "Vec<Vec<u32>>: Foo",
+        "exists<T> { T: Foo }",
+        "exists<T> { Normalize(<Vec<u32> as Foo>::Assoc -> T) }",
+        "Wrapper<u32>: Foo",
+        "Vec<u32>: Send",
+        "WellFormed(Vec<u32>)",
+    ];
+    for goal in &goals {
+        let goal = db.parse_and_lower_goal(goal).unwrap().into_closed_goal();
+        let mut solver = SolverChoice::default().into_solver();
+        assert_eq!(
+            solver.solve(&*program, &goal),
+            solver.solve(&frozen, &goal),
+            "{:?}",
+            goal
+        );
+    }
+}
+
+/// Items that nothing reachable names are left out, and reported as
+/// missing.
+#[test]
+fn unreachable_items_missing() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let frozen = freeze_from_foo(&program);
+    let unnamed = match type_id(&program, "Unnamed") {
+        TypeKindId::StructId(struct_id) => struct_id,
+        _ => unreachable!(),
+    };
+    let goal = db
+        .parse_and_lower_goal("WellFormed(Unnamed)")
+        .unwrap()
+        .into_closed_goal();
+    let result = SolverChoice::default()
+        .into_solver()
+        .try_solve(&frozen, &goal);
+    assert_eq!(result, Err(MissingData::Struct(unnamed)));
+    assert!(frozen
+        .try_trait_datum(TraitId(RawId { index: 999 }))
+        .is_err());
+
+    let frozen = FrozenProgram::freeze(&*program, vec![type_id(&program, "Unnamed")]).unwrap();
+    assert!(frozen.try_struct_datum(unnamed).is_ok());
+}
+
+/// A database that lists every impl for every trait.
+#[derive(Debug)]
+struct AllImpls(Arc<Program>);
+
+impl DbHooks for AllImpls {
+    fn program(&self) -> &dyn RustIrDatabase {
+        &*self.0
+    }
+
+    fn impls_for_trait(&self, _: TraitId, _: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
+        self.0.impl_data.keys().cloned().collect()
+    }
+}
+
+#[test]
+fn impl_of_other_trait() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let foo = type_id(&program, "Foo");
+    let error = FrozenProgram::freeze(&ForwardingDb(&AllImpls(program)), vec![foo]).unwrap_err();
+    match error {
+        FreezeError::ImplOfOtherTrait { trait_id, .. } => {
+            assert_eq!(TypeKindId::TraitId(trait_id), foo)
+        }
+        _ => panic!("unexpected error: {}", error),
+    }
+}
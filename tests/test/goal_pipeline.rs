@@ -0,0 +1,100 @@
+//! Tests for `SlgOptions::goal_pipeline`.
+
+use super::*;
+use chalk_engine::fallible::Fallible;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::*;
+use chalk_ir::*;
+use chalk_solve::{FolderPass, GoalPipeline, SlgOptions, Solution};
+
+const PROGRAM: &str = "
+    trait Foo { }
+    struct A { }
+    struct B { }
+    struct C { }
+    impl Foo for B { }
+";
+
+/// Replaces the struct `from` (which has no parameters) with `to`.
+struct Rename {
+    from: TypeKindId,
+    to: TypeKindId,
+}
+
+impl TypeFolder<ChalkIr> for Rename {
+    fn fold_ty(&mut self, ty: &Ty<ChalkIr>, binders: usize) -> Fallible<Ty<ChalkIr>> {
+        match ty {
+            Ty::Apply(apply) if apply.name == TypeName::TypeKindId(self.from) => {
+                Ok(ApplicationTy {
+                    name: TypeName::TypeKindId(self.to),
+                    parameters: vec![],
+                }
+                .cast())
+            }
+            _ => super_fold_ty(self, ty, binders),
+        }
+    }
+
+    fn fold_lifetime(
+        &mut self,
+        lifetime: &Lifetime<ChalkIr>,
+        binders: usize,
+    ) -> Fallible<Lifetime<ChalkIr>> {
+        super_fold_lifetime(self, lifetime, binders)
+    }
+}
+
+impl DefaultFreeVarFolder for Rename {}
+
+impl DefaultInferenceFolder for Rename {}
+
+impl DefaultPlaceholderFolder for Rename {}
+
+/// Solves `goal` with the standard passes followed by the renamings
+/// `renames`, in order.
+fn solve(goal: &str, renames: &[(&str, &str)]) -> Option<Solution> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let mut pipeline = GoalPipeline::STANDARD;
+    for &(from, to) in renames {
+        let from = program.type_ids[&intern(from)];
+        let to = program.type_ids[&intern(to)];
+        let pass = FolderPass::new("rename", move || Rename { from, to });
+        pipeline = pipeline.then(Box::leak(Box::new(pass)));
+    }
+    let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+    SolverChoice::slg_with(SlgOptions::default().goal_pipeline(pipeline))
+        .into_solver()
+        .solve(&*program, &goal)
+}
+
+#[test]
+fn passes_rewrite_goals() {
+    assert!(solve("A: Foo", &[]).is_none());
+    assert!(solve("A: Foo", &[("A", "B")]).is_some());
+    assert!(solve("exists<T> { if (T: Foo) { A: Foo } }", &[("A", "B")]).is_some());
+}
+
+/// Passes run in the order they were added to the pipeline.
+#[test]
+fn passes_run_in_order() {
+    assert!(solve("A: Foo", &[("A", "B"), ("B", "C")]).is_none());
+    assert!(solve("A: Foo", &[("B", "C"), ("A", "B")]).is_some());
+}
+
+#[test]
+fn pipeline_equality() {
+    assert_eq!(GoalPipeline::default(), GoalPipeline::EMPTY);
+    assert_eq!(format!("{:?}", GoalPipeline::STANDARD), r#"["prenex"]"#);
+
+    let id = TypeKindId::StructId(StructId(RawId { index: 0 }));
+    let pass = FolderPass::new("identity", move || Rename { from: id, to: id });
+    let extended = GoalPipeline::STANDARD.then(Box::leak(Box::new(pass)));
+    assert_ne!(extended, GoalPipeline::STANDARD);
+    assert_eq!(format!("{:?}", extended), r#"["prenex", "identity"]"#);
+    assert_ne!(
+        SolverChoice::slg_with(SlgOptions::default().goal_pipeline(extended)),
+        SolverChoice::default()
+    );
+}
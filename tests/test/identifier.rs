@@ -0,0 +1,32 @@
+//! Tests for `chalk_ir::Identifier`.
+
+use chalk_ir::{intern, Identifier};
+use std::thread;
+
+#[test]
+fn interned_once() {
+    let foo = intern("Foo");
+    assert_eq!(foo, Identifier::intern("Foo"));
+    assert_ne!(foo, intern("Bar"));
+    assert_eq!(foo.as_str(), "Foo");
+    assert_eq!(format!("{:?} {}", foo, foo), "\"Foo\" Foo");
+}
+
+/// Identifiers interned on one thread can be used on another.
+#[test]
+fn shared_between_threads() {
+    let foo = thread::spawn(|| intern("SharedBetweenThreads"))
+        .join()
+        .unwrap();
+    assert_eq!(foo, intern("SharedBetweenThreads"));
+    assert_eq!(foo.to_string(), "SharedBetweenThreads");
+}
+
+/// Identifiers are ordered by their strings, whatever the order in
+/// which they were interned.
+#[test]
+fn ordered_by_string() {
+    let b = intern("OrderedB");
+    let a = intern("OrderedA");
+    assert!(a < b);
+}
@@ -0,0 +1,63 @@
+//! Tests for `SlgOptions::suggest_identity_impls`.
+
+use super::*;
+use chalk_solve::SlgOptions;
+
+const PROGRAM: &str = "
+    trait Borrow<T> { }
+    trait Into<T> { }
+    struct u32 { }
+    struct i32 { }
+    struct Box<T> { }
+    impl<T> Borrow<T> for T { }
+    impl<T> Borrow<T> for Box<T> { }
+    impl Into<i32> for u32 { }
+    impl Into<u32> for u32 { }
+";
+
+fn assert_solves(suggest: bool, goal: &str, expected: &str) {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.checked_program().unwrap();
+    chalk_ir::tls::set_current_program(&program, || {
+        let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+        let solution =
+            SolverChoice::slg_with(SlgOptions::default().suggest_identity_impls(suggest))
+                .into_solver()
+                .solve(&db, &goal);
+        assert_result(&solution, expected);
+    });
+}
+
+/// The answer of the identity impl is suggested, where the solver
+/// alone gives no guidance.
+#[test]
+fn identity_suggested() {
+    let goal = "exists<T> { Box<u32>: Borrow<T> }";
+    assert_solves(false, goal, "Ambiguous; no inference guidance");
+    assert_solves(
+        true,
+        goal,
+        "Ambiguous; suggested substitution [?0 := Box<u32>]",
+    );
+}
+
+/// Goals without an identity impl, those that are not ambiguous and
+/// those whose self type is unknown are unaffected.
+#[test]
+fn other_goals_unaffected() {
+    assert_solves(
+        true,
+        "exists<T> { u32: Into<T> }",
+        "Ambiguous; no inference guidance",
+    );
+    assert_solves(
+        true,
+        "exists<T> { u32: Borrow<T> }",
+        "Unique; substitution [?0 := u32]",
+    );
+    assert_solves(
+        true,
+        "exists<T> { T: Borrow<u32> }",
+        "Ambiguous; no inference guidance",
+    );
+}
@@ -0,0 +1,91 @@
+//! Tests for the impls of `RustIrDatabase::impls_for_trait` whose
+//! trait refs do not have the kinds of the goal's parameters (see
+//! `RustIrDatabase::ill_kinded_impl`).
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{IllKindedImpl, RustIrDatabase};
+use std::cell::RefCell;
+
+/// Wraps a program, returning every impl of the program from
+/// `impls_for_trait` (whatever the trait), and recording the impls
+/// reported as ill-kinded.
+#[derive(Debug)]
+struct AllImplsDb<'p> {
+    program: &'p Program,
+    reported: RefCell<Vec<(ImplId, IllKindedImpl)>>,
+}
+
+impl DbHooks for AllImplsDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn impls_for_trait(&self, _: TraitId, _: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
+        self.program.impl_data.keys().cloned().collect()
+    }
+
+    fn ill_kinded_impl(&self, impl_id: ImplId, error: IllKindedImpl) {
+        self.reported.borrow_mut().push((impl_id, error));
+    }
+}
+
+/// The impls of `Marker` and `Outlives` are skipped, and reported,
+/// when solving a goal of `Convert`; the impl of `Convert` is used.
+#[test]
+fn skipped_and_reported() {
+    let db = ChalkDatabase::with(
+        "
+            trait Convert<T> { }
+            trait Marker { }
+            trait Outlives<'a> { }
+            struct u32 { }
+            struct i32 { }
+            impl Convert<i32> for u32 { }
+            impl Marker for u32 { }
+            impl<'a> Outlives<'a> for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let all_impls = AllImplsDb {
+            program,
+            reported: RefCell::new(vec![]),
+        };
+        let goal = db
+            .parse_and_lower_goal("exists<T> { u32: Convert<T> }")
+            .unwrap()
+            .into_peeled_goal();
+        let solution = SolverChoice::default()
+            .into_solver()
+            .solve(&ForwardingDb(&all_impls), &goal)
+            .unwrap();
+        assert!(solution.is_unique());
+
+        let mut reported: Vec<IllKindedImpl> = all_impls
+            .reported
+            .borrow()
+            .iter()
+            .map(|&(_, error)| error)
+            .collect();
+        reported.sort();
+        reported.dedup();
+        assert_eq!(
+            reported,
+            vec![
+                IllKindedImpl::ParameterCount {
+                    expected: 2,
+                    found: 1
+                },
+                IllKindedImpl::ParameterKind { index: 1 },
+            ]
+        );
+        assert_eq!(
+            reported[1].to_string(),
+            "trait parameter 1 of impl has the wrong kind"
+        );
+    });
+}
@@ -157,3 +157,167 @@ fn higher_ranked_implied_bounds() {
         }
     }
 }
+
+#[test]
+fn implied_env_of_impl() {
+    use chalk_solve::clauses::ClauseOrigin;
+
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            trait Copy where Self: Clone { }
+            trait Hash { }
+            trait Foo { }
+            struct HashSet<K> where K: Hash { }
+            impl<K> Foo for HashSet<K> where K: Copy { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let impl_id = *program.impl_data.keys().next().unwrap();
+        let env = chalk_solve::ext::implied_env_of_impl(program, impl_id).unwrap();
+        assert_eq!(env.binders.len(), 1);
+        let clauses: Vec<_> = env
+            .value
+            .iter()
+            .map(|(clause, origin)| (format!("{:?}", clause), *origin))
+            .collect();
+
+        // The where clause and the implied bounds of the header.
+        assert_eq!(
+            clauses[0],
+            ("FromEnv(^0: Copy)".to_string(), ClauseOrigin::Impl(impl_id))
+        );
+        assert_eq!(
+            clauses[1],
+            (
+                "FromEnv(HashSet<^0>)".to_string(),
+                ClauseOrigin::Impl(impl_id)
+            )
+        );
+
+        // What they imply, through `trait Copy` and `struct HashSet`.
+        let implied = |text: &str| {
+            clauses
+                .iter()
+                .find(|(clause, _)| clause == text)
+                .map(|&(_, origin)| origin)
+        };
+        assert!(matches!(
+            implied("for<type> FromEnv(^0: Clone) :- FromEnv(^0: Copy)"),
+            Some(ClauseOrigin::Trait(_))
+        ));
+        assert!(matches!(
+            implied("for<type> FromEnv(^0: Hash) :- FromEnv(HashSet<^0>)"),
+            Some(ClauseOrigin::Struct(_))
+        ));
+    });
+}
+
+#[test]
+fn super_traits() {
+    use chalk_integration::program::Program;
+    use chalk_ir::{TraitId, TypeKindId};
+    use chalk_solve::ext::{super_traits, TraitGraph};
+
+    fn trait_id(program: &Program, name: &str) -> TraitId {
+        match program.type_ids[&chalk_ir::intern(name)] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => unreachable!(),
+        }
+    }
+
+    let db = ChalkDatabase::with(
+        "
+            trait Foo<T> where Self: Bar<Vec<T>>, forall<'a> Self: Baz<'a>, T: Qux<T> { }
+            trait Bar<U> where Self: Qux<U> { }
+            trait Baz<'a> where Self: Bar<Ref<'a>> { }
+            trait Qux<U> { }
+            trait Ping where Self: Pong { }
+            trait Pong where Self: Ping { }
+            struct Vec<T> { }
+            struct Ref<'a> { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let foo = super_traits(program, trait_id(program, "Foo")).unwrap();
+        assert_eq!(foo.binders.len(), 2);
+        let foo: Vec<_> = foo.value.iter().map(|b| format!("{:?}", b)).collect();
+        assert_eq!(
+            foo,
+            vec![
+                "^0 as Bar<Vec<^1>>",
+                "^0 as Qux<Vec<^1>>",
+                "for<lifetime> ^1 as Baz<'^0>",
+                "for<lifetime> ^1 as Bar<Ref<'^0>>",
+                "for<lifetime> ^1 as Qux<Ref<'^0>>",
+            ]
+        );
+
+        // Cyclic supertraits are walked once.
+        let mut graph = TraitGraph::new(program);
+        let (ping, pong) = (trait_id(program, "Ping"), trait_id(program, "Pong"));
+        assert_eq!(graph.super_traits(ping).unwrap().value.len(), 2);
+        assert!(graph.is_super_trait(ping, pong).unwrap());
+        assert!(graph.is_super_trait(ping, ping).unwrap());
+        assert!(!graph
+            .is_super_trait(trait_id(program, "Qux"), ping)
+            .unwrap());
+    });
+}
+
+#[test]
+fn assoc_ty_bounds_elaborated() {
+    test! {
+        program {
+            trait Debug { }
+            trait Display where Self: Debug { }
+            trait Bar<X> { }
+            trait Iterator { type Item: Display; }
+            trait IntoIterator { type IntoIter: Iterator; }
+            trait Collection where Self: Iterator { }
+            trait Foo<X> { type Item: Bar<X>; }
+            struct u32 { }
+        }
+
+        // The bounds of `Item` hold for any `T: Iterator`, along with
+        // their supertraits.
+        goal {
+            forall<T> { if (T: Iterator) { <T as Iterator>::Item: Debug } }
+        } yields {
+            "Unique; substitution []"
+        }
+
+        goal {
+            forall<T> { if (T: Iterator) { exists<U> { T: Iterator<Item = U>, U: Debug } } }
+        } yields {
+            "Unique; substitution [?0 := (Iterator::Item)<!1_0>]"
+        }
+
+        goal {
+            forall<T> {
+                if (T: IntoIterator) {
+                    <<T as IntoIterator>::IntoIter as Iterator>::Item: Display
+                }
+            }
+        } yields {
+            "Unique; substitution []"
+        }
+
+        // `T: Iterator` need not be in the environment itself.
+        goal {
+            forall<T> { if (T: Collection) { <T as Iterator>::Item: Display } }
+        } yields {
+            "Unique; substitution []"
+        }
+
+        goal {
+            forall<T> { if (T: Foo<u32>) { exists<U> { <T as Foo<U>>::Item: Bar<U> } } }
+        } yields {
+            "Unique; substitution [?0 := u32]"
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Tests for `GoalExt::implied_by_env`.
+
+use super::*;
+use chalk_ir::InEnvironment;
+use chalk_solve::ext::ImpliedByEnv;
+
+const PROGRAM: &str = "
+    trait Clone { }
+    trait Copy where Self: Clone { }
+    trait Debug { }
+    trait Display { }
+    struct u32 { }
+    struct Foo { }
+    impl Clone for u32 { }
+    impl Display for u32 { }
+";
+
+/// Checks the goal of `text` in its environment: the goal is peeled,
+/// so `forall<T> { if (..) { .. } }` gives a goal about a placeholder
+/// in an environment with the hypotheses.
+fn check(text: &str) -> ImpliedByEnv {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    let InEnvironment { environment, goal } = &goal.canonical.value;
+    db.with_program(|program| goal.implied_by_env(program, environment))
+        .unwrap()
+}
+
+#[test]
+fn facts_of_the_environment() {
+    assert_eq!(
+        check("forall<T> { if (T: Debug) { T: Debug } }"),
+        ImpliedByEnv::Yes
+    );
+
+    // Through the supertraits of the hypotheses.
+    assert_eq!(
+        check("forall<T> { if (T: Copy) { T: Copy, T: Clone } }"),
+        ImpliedByEnv::Yes
+    );
+}
+
+#[test]
+fn no_clause_matches() {
+    assert_eq!(check("Foo: Debug"), ImpliedByEnv::No);
+    assert_eq!(check("u32: Copy"), ImpliedByEnv::No);
+    assert_eq!(
+        check("forall<T> { if (T: Copy) { T: Debug } }"),
+        ImpliedByEnv::No
+    );
+    assert_eq!(
+        check("forall<T> { if (T: Copy) { T: Clone, Foo: Debug } }"),
+        ImpliedByEnv::No
+    );
+}
+
+#[test]
+fn needs_solver() {
+    // Impls have to be solved...
+    assert_eq!(check("u32: Clone"), ImpliedByEnv::NeedsSolver);
+    assert_eq!(check("u32: Display"), ImpliedByEnv::NeedsSolver);
+
+    // ...and so do goals with binders of their own...
+    assert_eq!(
+        check("forall<T> { if (T: Debug) { exists<U> { U: Debug } } }"),
+        ImpliedByEnv::NeedsSolver
+    );
+
+    // ...and hypotheses that are not facts.
+    assert_eq!(
+        check("forall<T> { if (forall<U> { U: Debug }) { T: Debug } }"),
+        ImpliedByEnv::NeedsSolver
+    );
+}
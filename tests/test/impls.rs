@@ -2,6 +2,10 @@
 //! and other core logic functions.
 
 use super::*;
+use chalk_ir::cast::Cast;
+use chalk_solve::clauses::{program_clauses_with_origins, ClauseOrigin};
+use chalk_solve::{AmbiguityCause, Candidate, ImplConfidence, MissingData};
+use std::sync::Arc;
 
 #[test]
 fn prove_clone() {
@@ -639,3 +643,538 @@ fn clauses_in_if_goals() {
         }
     }
 }
+
+#[test]
+fn has_impl() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            trait Convert<T> { }
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl Clone for Foo { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+            impl Convert<Bar> for Foo { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    let type_kind_id = |name: &str| program.type_ids[&chalk_ir::intern(name)];
+    let ty = |name: &str, parameters: Vec<chalk_ir::Parameter<_>>| -> chalk_ir::Ty<_> {
+        chalk_ir::ApplicationTy {
+            name: chalk_ir::TypeName::TypeKindId(type_kind_id(name)),
+            parameters,
+        }
+        .cast()
+    };
+    let trait_id = |name: &str| match type_kind_id(name) {
+        chalk_ir::TypeKindId::TraitId(trait_id) => trait_id,
+        _ => panic!("`{}` is not a trait", name),
+    };
+
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        let foo = ty("Foo", vec![]);
+        let bar = ty("Bar", vec![]);
+        let clone = trait_id("Clone");
+        let convert = trait_id("Convert");
+
+        assert_eq!(
+            solver.has_impl(program, foo.clone(), clone, vec![]),
+            ImplConfidence::Yes
+        );
+        assert_eq!(
+            solver.has_impl(program, ty("Vec", vec![foo.clone().cast()]), clone, vec![]),
+            ImplConfidence::Yes
+        );
+        assert_eq!(
+            solver.has_impl(program, ty("Vec", vec![bar.clone().cast()]), clone, vec![]),
+            ImplConfidence::No
+        );
+        assert_eq!(
+            solver.has_impl(program, foo.clone(), convert, vec![bar.clone().cast()]),
+            ImplConfidence::Yes
+        );
+        assert_eq!(
+            solver.has_impl(program, bar, convert, vec![foo.cast()]),
+            ImplConfidence::No
+        );
+    });
+}
+
+#[test]
+fn try_solve_missing_data() {
+    let db = ChalkDatabase::with(
+        "
+            struct Foo { }
+            struct Vec<T> { }
+        ",
+        SolverChoice::default(),
+    );
+    let goal = db
+        .parse_and_lower_goal("WellFormed(Vec<Foo>)")
+        .unwrap()
+        .into_closed_goal();
+
+    db.with_program(|program| {
+        let vec_id = match program.type_ids[&chalk_ir::intern("Vec")] {
+            chalk_ir::TypeKindId::StructId(struct_id) => struct_id,
+            _ => panic!("`Vec` is not a struct"),
+        };
+        let mut partial = program.clone();
+        partial.struct_data.remove(&vec_id);
+
+        let mut solver = SolverChoice::default().into_solver();
+        assert_eq!(
+            solver.try_solve(&partial, &goal),
+            Err(MissingData::Struct(vec_id))
+        );
+
+        // The failed attempt must not leave anything cached behind.
+        assert!(solver
+            .try_solve(program, &goal)
+            .unwrap()
+            .unwrap()
+            .is_unique());
+    });
+}
+
+/// The detailed solutions report missing items as `try_solve` does.
+#[test]
+fn try_solve_detailed_missing_data() {
+    let db = ChalkDatabase::with(
+        "
+            struct Foo { }
+            struct Vec<T> { }
+        ",
+        SolverChoice::default(),
+    );
+    let goal = db
+        .parse_and_lower_goal("WellFormed(Vec<Foo>)")
+        .unwrap()
+        .into_closed_goal();
+
+    db.with_program(|program| {
+        let vec_id = match program.type_ids[&chalk_ir::intern("Vec")] {
+            chalk_ir::TypeKindId::StructId(struct_id) => struct_id,
+            _ => panic!("`Vec` is not a struct"),
+        };
+        let mut partial = program.clone();
+        partial.struct_data.remove(&vec_id);
+
+        let mut solver = SolverChoice::default().into_solver();
+        assert_eq!(
+            solver.try_solve_with_caveat(&partial, &goal),
+            Err(MissingData::Struct(vec_id))
+        );
+        assert_eq!(
+            solver.try_solve_with_ambiguity_cause(&partial, &goal),
+            Err(MissingData::Struct(vec_id))
+        );
+
+        let (solution, caveat) = solver
+            .try_solve_with_caveat(program, &goal)
+            .unwrap()
+            .unwrap();
+        assert!(solution.is_unique());
+        assert!(!caveat);
+        let (solution, cause) = solver
+            .try_solve_with_ambiguity_cause(program, &goal)
+            .unwrap()
+            .unwrap();
+        assert!(solution.is_unique());
+        assert_eq!(cause, None);
+    });
+}
+
+/// The built-in clauses, like those of auto traits, report missing
+/// items too, and so does `try_solve_multiple`.
+#[test]
+fn try_solve_missing_data_in_builtin_clauses() {
+    let db = ChalkDatabase::with(
+        "
+            #[auto] trait Send { }
+            struct Foo { }
+            struct Vec<T> { }
+        ",
+        SolverChoice::default(),
+    );
+    let goal = db
+        .parse_and_lower_goal("Vec<Foo>: Send")
+        .unwrap()
+        .into_closed_goal();
+
+    db.with_program(|program| {
+        let vec_id = match program.type_ids[&chalk_ir::intern("Vec")] {
+            chalk_ir::TypeKindId::StructId(struct_id) => struct_id,
+            _ => panic!("`Vec` is not a struct"),
+        };
+        let mut partial = program.clone();
+        partial.struct_data.remove(&vec_id);
+
+        let mut solver = SolverChoice::default().into_solver();
+        assert_eq!(
+            solver.try_solve(&partial, &goal),
+            Err(MissingData::Struct(vec_id))
+        );
+        assert_eq!(
+            solver.try_solve_multiple(&partial, &goal, |_, _| true),
+            Err(MissingData::Struct(vec_id))
+        );
+
+        let mut solutions = 0;
+        assert_eq!(
+            solver.try_solve_multiple(program, &goal, |_, _| {
+                solutions += 1;
+                true
+            }),
+            Ok(true)
+        );
+        assert_eq!(solutions, 1);
+    });
+}
+
+#[test]
+fn clause_origins() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            struct Foo { }
+            struct Vec<T> { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let goal = db.parse_and_lower_goal("Vec<Foo>: Clone").unwrap();
+        assert!(db.solve(&goal.clone().into_closed_goal()).is_none());
+        let goal = match *goal {
+            chalk_ir::Goal::Leaf(chalk_ir::LeafGoal::DomainGoal(goal)) => goal,
+            _ => panic!("not a domain goal"),
+        };
+
+        // The impl matches, so its where clause must be what failed.
+        let impl_id = *program.impl_data.keys().next().unwrap();
+        let origins: Vec<_> =
+            program_clauses_with_origins(program, &chalk_ir::Environment::new(), &goal)
+                .unwrap()
+                .into_iter()
+                .map(|(_, origin)| origin)
+                .collect();
+        assert!(
+            origins.contains(&ClauseOrigin::Impl(impl_id)),
+            "{:?}",
+            origins
+        );
+        let trait_id = *program.trait_data.keys().next().unwrap();
+        assert!(origins.iter().all(|&origin| {
+            origin == ClauseOrigin::Impl(impl_id) || origin == ClauseOrigin::Trait(trait_id)
+        }));
+    });
+}
+
+#[test]
+fn candidates() {
+    let db = ChalkDatabase::with(
+        "
+            #[auto] trait Send { }
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+            impl Clone for Foo { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        let mut candidates = |text: &str| {
+            let (environment, goal) = match *db.parse_and_lower_goal(text).unwrap() {
+                chalk_ir::Goal::Implies(clauses, goal) => {
+                    (chalk_ir::Environment::new().add_clauses(clauses), goal)
+                }
+                goal => (chalk_ir::Environment::new(), Arc::new(goal)),
+            };
+            let trait_ref = match &*goal {
+                chalk_ir::Goal::Leaf(chalk_ir::LeafGoal::DomainGoal(
+                    chalk_ir::DomainGoal::Holds(chalk_ir::WhereClause::Implemented(trait_ref)),
+                )) => trait_ref.clone(),
+                _ => panic!("not a trait goal"),
+            };
+            let goal = chalk_ir::UCanonical {
+                canonical: chalk_ir::Canonical {
+                    binders: vec![],
+                    value: chalk_ir::InEnvironment::new(&environment, trait_ref),
+                },
+                universes: 1,
+            };
+            solver
+                .candidates(program, &goal)
+                .unwrap()
+                .into_iter()
+                .map(|(candidate, solution)| (candidate, solution.is_unique()))
+                .collect::<Vec<_>>()
+        };
+
+        let foo_impl = program
+            .impl_data
+            .iter()
+            .find(|(_, datum)| datum.binders.binders.is_empty())
+            .map(|(&id, _)| id)
+            .unwrap();
+        assert_eq!(
+            candidates("Foo: Clone"),
+            vec![(Candidate::Impl(foo_impl), true)]
+        );
+
+        // The where clause is preferred over the impl.
+        assert_eq!(
+            candidates("if (Foo: Clone) { Foo: Clone }"),
+            vec![
+                (Candidate::ParamEnv, true),
+                (Candidate::Impl(foo_impl), true)
+            ]
+        );
+
+        // The impl for `Vec<T>` matches, but does not apply.
+        assert_eq!(candidates("Vec<Vec<Bar>>: Clone"), vec![]);
+
+        assert_eq!(
+            candidates("Vec<Foo>: Send"),
+            vec![(Candidate::AutoImpl, true)]
+        );
+        assert_eq!(
+            candidates("dyn Clone: Clone"),
+            vec![(Candidate::Object, true)]
+        );
+        assert_eq!(
+            candidates("fn(Foo) -> Foo: Send"),
+            vec![(Candidate::Builtin("fn pointer"), true)]
+        );
+    });
+}
+
+#[test]
+fn rejected_candidates() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+            impl Clone for Foo { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        let mut rejected = |text: &str| {
+            let trait_ref = match *db.parse_and_lower_goal(text).unwrap() {
+                chalk_ir::Goal::Leaf(chalk_ir::LeafGoal::DomainGoal(
+                    chalk_ir::DomainGoal::Holds(chalk_ir::WhereClause::Implemented(trait_ref)),
+                )) => trait_ref,
+                _ => panic!("not a trait goal"),
+            };
+            let goal = chalk_ir::UCanonical {
+                canonical: chalk_ir::Canonical {
+                    binders: vec![],
+                    value: chalk_ir::InEnvironment::new(&chalk_ir::Environment::new(), trait_ref),
+                },
+                universes: 1,
+            };
+            solver
+                .rejected_candidates(program, &goal)
+                .unwrap()
+                .into_iter()
+                .map(|(_, error)| error.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            rejected("Foo: Clone"),
+            vec!["expected `Foo`, found `Vec<?0>`"]
+        );
+
+        // Both impls are rejected.
+        assert_eq!(
+            rejected("Bar: Clone"),
+            vec![
+                "expected `Bar`, found `Vec<?0>`",
+                "expected `Bar`, found `Foo`"
+            ]
+        );
+
+        // The impl for `Vec<T>` matches, even though it does not apply.
+        assert_eq!(
+            rejected("Vec<Bar>: Clone"),
+            vec!["expected `Vec<Bar>`, found `Foo`"]
+        );
+    });
+}
+
+#[test]
+fn solve_batch() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            struct Vec<T> { }
+            impl Clone for Foo { }
+            impl<T> Clone for Vec<T> where T: Clone { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let goals: Vec<_> = ["Vec<Vec<Foo>>: Clone", "Foo: Clone", "Vec<Bar>: Clone"]
+            .iter()
+            .map(|text| *db.parse_and_lower_goal(text).unwrap())
+            .collect();
+        let mut solver = SolverChoice::default().into_solver();
+        let solutions = chalk_solve::ext::solve_batch(
+            &mut solver,
+            program,
+            &chalk_ir::Environment::new(),
+            &goals,
+        );
+        assert_eq!(solutions.len(), 3);
+        assert!(solutions[0].as_ref().unwrap().is_unique());
+        assert!(solutions[1].as_ref().unwrap().is_unique());
+        assert!(solutions[2].is_err());
+    });
+}
+
+/// The items of a test may refer to those of the prelude, which are
+/// lowered first.
+#[test]
+fn prelude() {
+    test! {
+        prelude PRELUDE,
+        program {
+            struct Foo { }
+            struct Bar { }
+            impl Clone for Foo { }
+            impl Copy for Foo { }
+            impl !Send for Bar { }
+        }
+
+        goal {
+            Box<Foo>: Copy
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Foo: Copy
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            (Foo, Box<Foo>): Sized
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Box<Bar>: Send
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<T> { if (T: Copy) { T: Clone } }
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+#[test]
+fn scalar_impls() {
+    test! {
+        prelude PRELUDE,
+        program {
+            struct Foo { }
+            struct Wrapper<T> { }
+            impl<T> Clone for Wrapper<T> where T: Clone { }
+        }
+
+        goal {
+            u32: Copy
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            f64: Clone
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Wrapper<bool>: Clone
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Foo: Clone
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            u32: Fn<()>
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            exists<T> { Wrapper<T>: Clone }
+        } yields {
+            "Ambiguous; no inference guidance"
+        }
+    }
+}
+
+#[test]
+fn ambiguity_causes() {
+    let db = ChalkDatabase::with(
+        "
+            #[auto] trait Send { }
+            trait Clone { }
+            struct Foo { }
+            struct Bar { }
+            impl Clone for Foo { }
+            impl Clone for Bar { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let cause = |text: &str| {
+            let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+            let mut solver = SolverChoice::default().into_solver();
+            let (_, cause) = solver.solve_with_ambiguity_cause(program, &goal).unwrap();
+            cause
+        };
+
+        assert_eq!(cause("Foo: Clone"), None);
+        assert_eq!(
+            cause("exists<T> { T: Clone }"),
+            Some(AmbiguityCause::MultipleCandidates)
+        );
+        assert_eq!(
+            cause("exists<T> { T: Send }"),
+            Some(AmbiguityCause::InsufficientTypeInfo)
+        );
+    });
+}
@@ -0,0 +1,118 @@
+//! Tests for solving goals with the inference variables of the embedder.
+
+use super::*;
+use chalk_engine::fallible::{Fallible, NoSolution};
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::Subst;
+use chalk_ir::*;
+use chalk_solve::inference_source::{InferenceSolution, InferenceSource};
+
+/// A minimal inference table, whose variables live in the root universe
+/// and are never unified with one another.
+#[derive(Default)]
+struct Table {
+    values: Vec<Option<Parameter<ChalkIr>>>,
+}
+
+impl Table {
+    fn new_ty_var(&mut self, value: Option<Ty<ChalkIr>>) -> Ty<ChalkIr> {
+        self.values.push(value.map(|ty| ty.cast()));
+        InferenceVar::from(self.values.len() as u32 - 1).to_ty::<ChalkIr>()
+    }
+}
+
+impl InferenceSource for Table {
+    fn probe_var(&self, var: InferenceVar) -> Option<Parameter<ChalkIr>> {
+        self.values[var.index() as usize].clone()
+    }
+
+    fn universe_of(&self, _var: InferenceVar) -> UniverseIndex {
+        UniverseIndex::root()
+    }
+
+    fn new_variable(&mut self, _kind: ParameterKind<UniverseIndex>) -> InferenceVar {
+        self.values.push(None);
+        InferenceVar::from(self.values.len() as u32 - 1)
+    }
+
+    fn unify(
+        &mut self,
+        var: ParameterKind<InferenceVar>,
+        value: &Parameter<ChalkIr>,
+    ) -> Fallible<()> {
+        let slot = &mut self.values[var.into_inner().index() as usize];
+        match slot {
+            Some(old) if old != value => Err(NoSolution),
+            _ => {
+                *slot = Some(value.clone());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[test]
+fn solve_with_inference_source() {
+    let db = ChalkDatabase::with(
+        "
+            trait Same<X> { }
+            impl<X> Same<X> for X { }
+            struct Vec<T> { }
+            struct u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|program| {
+        // `A: Same<Vec<B>>`, with `A` and `B` replaced by `a` and `b`.
+        let goal = |a: Ty<ChalkIr>, b: Ty<ChalkIr>| {
+            let goal = match *db
+                .parse_and_lower_goal("exists<A, B> { A: Same<Vec<B>> }")
+                .unwrap()
+            {
+                Goal::Quantified(QuantifierKind::Exists, goal) => goal,
+                goal => panic!("unexpected goal {:?}", goal),
+            };
+            let goal = Subst::apply(&[a.cast(), b.cast()], &goal.value);
+            InEnvironment::new(&Environment::new(), (*goal).clone())
+        };
+        let mut solver = SolverChoice::default().into_solver();
+
+        // `B` is known to be `u32`, so `A` must be `Vec<u32>`.
+        let mut table = Table::default();
+        let u32_ty: Ty<ChalkIr> = ApplicationTy {
+            name: TypeName::TypeKindId(program.type_ids[&chalk_ir::intern("u32")]),
+            parameters: vec![],
+        }
+        .cast();
+        let a = table.new_ty_var(None);
+        let b = table.new_ty_var(Some(u32_ty));
+        let solution = solver
+            .solve_with_inference_source(program, &mut table, &goal(a, b))
+            .unwrap();
+        let subst = match solution {
+            InferenceSolution::Unique(subst) => subst,
+            solution => panic!("unexpected solution {:?}", solution),
+        };
+        assert_eq!(format!("{:?}", subst.values), "[(Ty(?0), Vec<u32>)]");
+        subst.apply(&mut table).unwrap();
+        assert_eq!(format!("{:?}", table.values[0]), "Some(Vec<u32>)");
+
+        // With `B` unknown, the solution refers to a new variable for it.
+        let mut table = Table::default();
+        let a = table.new_ty_var(None);
+        let b = table.new_ty_var(None);
+        let solution = solver
+            .solve_with_inference_source(program, &mut table, &goal(a, b))
+            .unwrap();
+        let subst = match solution {
+            InferenceSolution::Unique(subst) => subst,
+            solution => panic!("unexpected solution {:?}", solution),
+        };
+        assert_eq!(table.values.len(), 3);
+        assert_eq!(
+            format!("{:?}", subst.values),
+            "[(Ty(?0), Vec<?2>), (Ty(?1), ?2)]"
+        );
+    });
+}
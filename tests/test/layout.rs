@@ -0,0 +1,143 @@
+//! Tests for the experimental `LayoutCompatible` goals (enabled by the
+//! `layout` feature).
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::RustIrDatabase;
+
+#[test]
+fn layout_compatible_structs() {
+    test! {
+        program {
+            struct u8 { }
+            struct u32 { }
+            struct Pair<A, B> { a: A, b: B }
+            struct Header { len: u32, tag: u8 }
+            struct Wrapper<T> { value: T }
+        }
+
+        goal {
+            LayoutCompatible(u32, u32)
+        } yields {
+            "Unique"
+        }
+
+        // Types without fields are opaque.
+        goal {
+            LayoutCompatible(u32, u8)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            LayoutCompatible(Header, Pair<u32, u8>)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            LayoutCompatible(Header, Pair<u8, u32>)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            LayoutCompatible(Header, (u32, u8))
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            LayoutCompatible(Wrapper<(u32, u8)>, (Header,))
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            LayoutCompatible(Header, (u32, u8, u8))
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            forall<T> {
+                LayoutCompatible(Wrapper<T>, (T,))
+            }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            exists<T> {
+                LayoutCompatible(Header, T)
+            }
+        } yields {
+            "Ambiguous; no inference guidance"
+        }
+    }
+}
+
+/// Adds the fact `LayoutCompatible(u32, i32)` to a program.
+#[derive(Debug)]
+struct SignednessDb<'p> {
+    program: &'p Program,
+}
+
+impl SignednessDb<'_> {
+    fn ty(&self, name: &str, parameters: Vec<Parameter<ChalkIr>>) -> Ty<ChalkIr> {
+        ApplicationTy {
+            name: TypeName::TypeKindId(self.program.type_ids[&chalk_ir::intern(name)]),
+            parameters,
+        }
+        .cast()
+    }
+}
+
+impl DbHooks for SignednessDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn layout_facts(&self) -> Vec<LayoutCompatible<ChalkIr>> {
+        vec![LayoutCompatible {
+            a: self.ty("u32", vec![]),
+            b: self.ty("i32", vec![]),
+        }]
+    }
+}
+
+#[test]
+fn layout_facts() {
+    let db = ChalkDatabase::with(
+        "
+            struct u32 { }
+            struct i32 { }
+            struct Wrapper<T> { value: T }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let db = SignednessDb { program };
+        let solve = |a: Ty<ChalkIr>, b: Ty<ChalkIr>| {
+            let goal: Goal<ChalkIr> = LayoutCompatible { a, b }.cast();
+            SolverChoice::default()
+                .into_solver()
+                .solve(&ForwardingDb(&db), &goal.into_closed_goal())
+        };
+        let u32 = db.ty("u32", vec![]);
+        let i32 = db.ty("i32", vec![]);
+
+        assert!(solve(u32.clone(), i32.clone()).unwrap().is_unique());
+        assert!(solve(i32.clone(), u32.clone()).unwrap().is_unique());
+        assert!(solve(
+            db.ty("Wrapper", vec![i32.cast()]),
+            db.ty("Wrapper", vec![u32.cast()])
+        )
+        .unwrap()
+        .is_unique());
+    });
+}
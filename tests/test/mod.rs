@@ -1,8 +1,15 @@
 use chalk_integration::db::ChalkDatabase;
 use chalk_integration::query::LoweringDatabase;
 use chalk_ir;
+use chalk_solve::api::ir::family::ChalkIr;
+use chalk_solve::api::{ir, rust_ir};
+use chalk_solve::api::{
+    CustomGoalSolution, IllKindedImpl, RegionConstraintResolver, RustIrDatabase,
+    SpecializationPriority,
+};
 use chalk_solve::ext::*;
 use chalk_solve::{Solution, SolverChoice};
+use std::sync::Arc;
 
 #[cfg(feature = "bench")]
 mod bench;
@@ -48,23 +55,44 @@ enum TestGoal {
     First(Vec<&'static str>),
 }
 
+/// Items that many tests need; a test can start from them with
+/// `test! { prelude PRELUDE, program { .. } .. }`, and only list the
+/// items of its own.
+const PRELUDE: &str = stringify! {
+    #[lang(sized)] trait Sized { }
+    #[lang(fn_once)] trait FnOnce<Args> { type Output; }
+    #[lang(fn_mut)] trait FnMut<Args> where Self: FnOnce<Args> { }
+    #[lang(fn)] trait Fn<Args> where Self: FnMut<Args> { }
+    #[lang(owned_box)] struct Box<T> { }
+    #[auto] trait Send { }
+    #[lang(clone)] trait Clone { }
+    #[lang(copy)] trait Copy where Self: Clone { }
+    #[scalar(bool)] struct bool { }
+    #[scalar(int)] struct u32 { }
+    #[scalar(float)] struct f64 { }
+};
+
 macro_rules! test {
     (program $program:tt $($goals:tt)*) => {
-        test!(@program[$program]
+        test!(prelude "", program $program $($goals)*)
+    };
+
+    (prelude $prelude:expr, program $program:tt $($goals:tt)*) => {
+        test!(@program[$prelude; $program]
               @parsed_goals[]
               @unparsed_goals[$($goals)*])
     };
 
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[]) => {
-        solve_goal(stringify!($program), vec![$($parsed_goals),*])
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[]) => {
+        solve_goal($prelude, stringify!($program), vec![$($parsed_goals),*])
     };
 
     // goal { G } yields { "Y" } -- test both solvers behave the same (the default)
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt yields { $expected:expr }
         $($unparsed_goals:tt)*
     ]) => {
-        test!(@program[$program]
+        test!(@program[$prelude; $program]
               @parsed_goals[
                   $($parsed_goals)*
                       (stringify!($goal), SolverChoice::default(), TestGoal::Aggregated($expected))
@@ -74,11 +102,11 @@ macro_rules! test {
 
     // goal { G } yields_all { "Y1", "Y2", ... , "YN" } -- test both solvers gets exactly N same answers in
     // the same order
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt yields_all { $($expected:expr),* }
         $($unparsed_goals:tt)*
     ]) => {
-        test!(@program[$program]
+        test!(@program[$prelude; $program]
               @parsed_goals[
                   $($parsed_goals)*
                       (stringify!($goal), SolverChoice::default(), TestGoal::All(vec![$($expected),*]))
@@ -87,11 +115,11 @@ macro_rules! test {
     };
 
     // goal { G } yields_first { "Y1", "Y2", ... , "YN" } -- test both solvers gets at least N same first answers
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt yields_first { $($expected:expr),* }
         $($unparsed_goals:tt)*
     ]) => {
-        test!(@program[$program]
+        test!(@program[$prelude; $program]
               @parsed_goals[
                   $($parsed_goals)*
                       (stringify!($goal), SolverChoice::default(), TestGoal::First(vec![$($expected),*]))
@@ -106,33 +134,34 @@ macro_rules! test {
     // to distinguish the case where there are other goals to come
     // (this rule) for the last goal in the list (next rule). There
     // might be a more elegant fix than copy-and-paste but this works.
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt $(yields[$($C:expr),+] { $expected:expr })*
             goal $($unparsed_goals:tt)*
     ]) => {
-        test!(@program[$program]
+        test!(@program[$prelude; $program]
               @parsed_goals[$($parsed_goals)*
-                            $($((stringify!($goal), $C, $expected))+)+]
+                            $($((stringify!($goal), $C, TestGoal::Aggregated($expected)))+)+]
               @unparsed_goals[goal $($unparsed_goals)*])
     };
 
     // same as above, but for the final goal in the list.
-    (@program[$program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
+    (@program[$prelude:expr; $program:tt] @parsed_goals[$($parsed_goals:tt)*] @unparsed_goals[
         goal $goal:tt $(yields[$($C:expr),+] { $expected:expr })*
     ]) => {
-        test!(@program[$program]
+        test!(@program[$prelude; $program]
               @parsed_goals[$($parsed_goals)*
-                            $($((stringify!($goal), $C, $expected))+)+]
+                            $($((stringify!($goal), $C, TestGoal::Aggregated($expected)))+)+]
               @unparsed_goals[])
     };
 }
 
-fn solve_goal(program_text: &str, goals: Vec<(&str, SolverChoice, TestGoal)>) {
+fn solve_goal(prelude: &str, program_text: &str, goals: Vec<(&str, SolverChoice, TestGoal)>) {
     println!("program {}", program_text);
     assert!(program_text.starts_with("{"));
     assert!(program_text.ends_with("}"));
 
-    let mut db = ChalkDatabase::with(
+    let mut db = ChalkDatabase::with_prelude(
+        prelude,
         &program_text[1..program_text.len() - 1],
         SolverChoice::default(),
     );
@@ -158,6 +187,16 @@ fn solve_goal(program_text: &str, goals: Vec<(&str, SolverChoice, TestGoal)>) {
             match expected {
                 TestGoal::Aggregated(expected) => {
                     let result = db.solve(&peeled_goal);
+                    if cfg!(feature = "check-cache") {
+                        check_cache(
+                            &db,
+                            &program,
+                            program_text,
+                            goal_text,
+                            &peeled_goal,
+                            &result,
+                        );
+                    }
                     assert_result(&result, expected);
                 }
                 TestGoal::All(expected) => {
@@ -196,13 +235,224 @@ fn solve_goal(program_text: &str, goals: Vec<(&str, SolverChoice, TestGoal)>) {
     }
 }
 
+/// Differential check of the solver's caching, enabled for the
+/// `yields` goals of `test!` with the `check-cache` feature: `result`,
+/// which the solver of `db` found with the tables left behind by the
+/// goals before, must be the same as the result of a fresh solver, and
+/// as the result of solving the goal again now that its own tables are
+/// complete. Caching interacts
+/// in subtle ways with cycles and coinduction, and a difference means
+/// that some cached answer was unsound (or incomplete).
+fn check_cache(
+    db: &ChalkDatabase,
+    program: &chalk_integration::program::Program,
+    program_text: &str,
+    goal_text: &str,
+    goal: &chalk_ir::UCanonical<chalk_ir::InEnvironment<chalk_ir::Goal<chalk_ir::family::ChalkIr>>>,
+    result: &Option<Solution>,
+) {
+    let uncached = db.solver_choice().into_solver().solve(program, goal);
+    let resolved = db.solve(goal);
+    if uncached != *result || resolved != *result {
+        panic!(
+            "caching changed the solution of goal {}\n\
+             with cached tables: {:?}\n\
+             with a fresh solver: {:?}\n\
+             when solved again: {:?}\n\
+             program {}",
+            goal_text, result, uncached, resolved, program_text
+        );
+    }
+}
+
+/// The hooks of `RustIrDatabase` that tests override, each defaulting
+/// to the answer of `program`. A test implements this for a type
+/// holding the program and whatever state its hooks need, and solves
+/// in a `ForwardingDb` around it.
+trait DbHooks: std::fmt::Debug {
+    /// The database that answers every query not overridden.
+    fn program(&self) -> &dyn RustIrDatabase;
+
+    fn impls_for_trait(
+        &self,
+        trait_id: ir::TraitId,
+        parameters: &[ir::Parameter<ChalkIr>],
+    ) -> Vec<ir::ImplId> {
+        self.program().impls_for_trait(trait_id, parameters)
+    }
+
+    fn solve_custom_goal(&self, goal: &ir::CustomGoal<ChalkIr>) -> CustomGoalSolution {
+        self.program().solve_custom_goal(goal)
+    }
+
+    fn upcast_vtable_compatible(&self, source: ir::TraitId, target: ir::TraitId) -> bool {
+        self.program().upcast_vtable_compatible(source, target)
+    }
+
+    fn prefer_impl(&self, candidates: &[ir::ImplId]) -> Option<ir::ImplId> {
+        self.program().prefer_impl(candidates)
+    }
+
+    fn ill_kinded_impl(&self, impl_id: ir::ImplId, error: IllKindedImpl) {
+        self.program().ill_kinded_impl(impl_id, error)
+    }
+
+    fn region_constraint_resolver(&self) -> Option<Arc<RegionConstraintResolver>> {
+        self.program().region_constraint_resolver()
+    }
+
+    #[cfg(feature = "layout")]
+    fn layout_facts(&self) -> Vec<ir::LayoutCompatible<ChalkIr>> {
+        self.program().layout_facts()
+    }
+}
+
+/// A database that forwards every query to the program of its hooks,
+/// except for the hooks themselves (see `DbHooks`). It is written
+/// against `chalk_solve::api` alone, which `api::minimal_embedder`
+/// relies on.
+#[derive(Debug)]
+struct ForwardingDb<'h, H: DbHooks>(&'h H);
+
+impl<H: DbHooks> RustIrDatabase for ForwardingDb<'_, H> {
+    fn custom_clauses(&self) -> Vec<ir::ProgramClause<ChalkIr>> {
+        self.0.program().custom_clauses()
+    }
+
+    fn associated_ty_data(&self, ty: ir::TypeId) -> Arc<rust_ir::AssociatedTyDatum> {
+        self.0.program().associated_ty_data(ty)
+    }
+
+    fn trait_datum(&self, trait_id: ir::TraitId) -> Arc<rust_ir::TraitDatum> {
+        self.0.program().trait_datum(trait_id)
+    }
+
+    fn struct_datum(&self, struct_id: ir::StructId) -> Arc<rust_ir::StructDatum> {
+        self.0.program().struct_datum(struct_id)
+    }
+
+    fn impl_datum(&self, impl_id: ir::ImplId) -> Arc<rust_ir::ImplDatum> {
+        self.0.program().impl_datum(impl_id)
+    }
+
+    fn associated_ty_value(
+        &self,
+        id: rust_ir::AssociatedTyValueId,
+    ) -> Arc<rust_ir::AssociatedTyValue> {
+        self.0.program().associated_ty_value(id)
+    }
+
+    fn impls_for_trait(
+        &self,
+        trait_id: ir::TraitId,
+        parameters: &[ir::Parameter<ChalkIr>],
+    ) -> Vec<ir::ImplId> {
+        self.0.impls_for_trait(trait_id, parameters)
+    }
+
+    fn local_impls_to_coherence_check(&self, trait_id: ir::TraitId) -> Vec<ir::ImplId> {
+        self.0.program().local_impls_to_coherence_check(trait_id)
+    }
+
+    fn impl_provided_for(&self, auto_trait_id: ir::TraitId, struct_id: ir::StructId) -> bool {
+        self.0.program().impl_provided_for(auto_trait_id, struct_id)
+    }
+
+    fn lang_item(&self, item: rust_ir::LangItem) -> Option<ir::TypeKindId> {
+        self.0.program().lang_item(item)
+    }
+
+    fn well_known_items(&self) -> Arc<rust_ir::WellKnownItems> {
+        self.0.program().well_known_items()
+    }
+
+    fn type_name(&self, id: ir::TypeKindId) -> ir::Identifier {
+        self.0.program().type_name(id)
+    }
+
+    fn solve_custom_goal(&self, goal: &ir::CustomGoal<ChalkIr>) -> CustomGoalSolution {
+        self.0.solve_custom_goal(goal)
+    }
+
+    fn upcast_vtable_compatible(&self, source: ir::TraitId, target: ir::TraitId) -> bool {
+        self.0.upcast_vtable_compatible(source, target)
+    }
+
+    fn prefer_impl(&self, candidates: &[ir::ImplId]) -> Option<ir::ImplId> {
+        self.0.prefer_impl(candidates)
+    }
+
+    fn ill_kinded_impl(&self, impl_id: ir::ImplId, error: IllKindedImpl) {
+        self.0.ill_kinded_impl(impl_id, error)
+    }
+
+    fn region_constraint_resolver(&self) -> Option<Arc<RegionConstraintResolver>> {
+        self.0.region_constraint_resolver()
+    }
+
+    fn impl_specialization_priority(&self, impl_id: ir::ImplId) -> Option<SpecializationPriority> {
+        self.0.program().impl_specialization_priority(impl_id)
+    }
+
+    fn local_inherent_impls(&self) -> Vec<Arc<rust_ir::InherentImplDatum>> {
+        self.0.program().local_inherent_impls()
+    }
+
+    #[cfg(feature = "layout")]
+    fn layout_facts(&self) -> Vec<ir::LayoutCompatible<ChalkIr>> {
+        self.0.layout_facts()
+    }
+}
+
+mod api;
 mod auto_traits;
+mod builtin_types;
+mod cached_subgoals;
+mod cannot_prove;
+mod check_program;
+mod clause_filter;
+mod clause_validation;
 mod coherence_goals;
 mod coinduction;
+mod custom_goals;
 mod cycle;
+mod dyn_ty;
+mod empty_env;
+mod failed_leaves;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod fn_ptr;
+mod frozen_program;
+mod goal_pipeline;
+mod identifier;
+mod identity_guidance;
+mod ill_kinded_impls;
 mod implied_bounds;
+mod implied_by_env;
 mod impls;
+mod inference_source;
+#[cfg(feature = "layout")]
+mod layout;
 mod negation;
+mod operators;
+#[cfg(feature = "persist")]
+mod persist;
+mod prewarm;
+mod program_builder;
 mod projection;
+mod prune_well_formed;
+mod redundant_clauses;
+mod reentrant;
+mod region_constraints;
+mod reveal;
+mod salsa_bridge;
+mod split;
+mod stable_hash;
+mod stack_watch;
+mod suggest_impls;
+mod tooling;
 mod unify;
+mod universes;
+mod upcast;
+mod variance;
 mod wf_goals;
@@ -0,0 +1,62 @@
+//! Tests for the operator helpers in `ext`, which find the operator
+//! traits through `RustIrDatabase::well_known_items`.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::ext::{resolve_binary_op, BinOp};
+
+fn ty(program: &Program, name: &str) -> Ty<ChalkIr> {
+    ApplicationTy {
+        name: TypeName::TypeKindId(program.type_ids[&chalk_ir::intern(name)]),
+        parameters: vec![],
+    }
+    .cast()
+}
+
+#[test]
+fn resolve_binary_op_output() {
+    let db = ChalkDatabase::with(
+        "
+            #[lang(add)]
+            trait Add<Rhs> { type Output; }
+
+            #[lang(mul)]
+            trait Mul<Rhs> { }
+
+            struct Meters { }
+            struct Feet { }
+            struct Length { }
+
+            impl Add<Meters> for Meters { type Output = Meters; }
+            impl Add<Feet> for Meters { type Output = Length; }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let meters = ty(program, "Meters");
+        let feet = ty(program, "Feet");
+        let env = Environment::new();
+        let mut solver = SolverChoice::default().into_solver();
+        let mut resolve = |op, lhs: &Ty<ChalkIr>, rhs: &Ty<ChalkIr>| {
+            resolve_binary_op(&mut solver, program, &env, op, lhs, rhs).map(|s| s.to_string())
+        };
+
+        assert_eq!(
+            resolve(BinOp::Add, &meters, &meters).unwrap(),
+            "Unique; substitution [?0 := Meters], lifetime constraints []"
+        );
+        assert_eq!(
+            resolve(BinOp::Add, &meters, &feet).unwrap(),
+            "Unique; substitution [?0 := Length], lifetime constraints []"
+        );
+        assert_eq!(resolve(BinOp::Add, &feet, &meters), None);
+
+        // `Mul` has no `Output`, and no trait is declared for `Sub`.
+        assert_eq!(resolve(BinOp::Mul, &meters, &meters), None);
+        assert_eq!(resolve(BinOp::Sub, &meters, &meters), None);
+    });
+}
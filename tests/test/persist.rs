@@ -0,0 +1,103 @@
+//! Tests for persisting solutions (enabled by the `persist` feature).
+
+use super::*;
+use chalk_solve::persist::{
+    ArchivedCache, ArchivedSolution, PersistError, PersistentCache, ALIGNMENT, FORMAT_VERSION,
+};
+
+const PROGRAM: &str = "
+    trait Clone { }
+    trait Iterator { type Item; }
+    struct Foo { }
+    struct Bar { }
+    struct Ref<'a, T> { }
+    struct Vec<T> { }
+    impl Clone for Foo { }
+    impl<T> Iterator for Vec<T> { type Item = T; }
+";
+
+const GOALS: &[&str] = &[
+    "Foo: Clone",
+    "exists<T> { T: Clone }",
+    "exists<T> { <Vec<T> as Iterator>::Item = Foo }",
+    "exists<T, U> { Vec<T> = U }",
+    "forall<'a> { exists<'b> { Ref<'a, Foo> = Ref<'b, Foo> } }",
+];
+
+#[test]
+fn persist_roundtrip() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    db.with_program(|program| {
+        let goals: Vec<_> = GOALS
+            .iter()
+            .map(|goal| db.parse_and_lower_goal(goal).unwrap().into_closed_goal())
+            .collect();
+
+        let mut solver = SolverChoice::default().into_solver();
+        let mut cache = PersistentCache::new();
+        let solutions: Vec<_> = goals
+            .iter()
+            .map(|goal| cache.solve(&mut solver, program, goal).unwrap())
+            .collect();
+        assert_eq!(cache.len(), GOALS.len());
+
+        let bytes = cache.to_bytes(7);
+        let loaded = ArchivedCache::from_bytes(&bytes, 7).unwrap();
+        assert_eq!(loaded.len(), GOALS.len());
+        assert_eq!(loaded.to_cache(), cache);
+
+        // A fresh solver finds the solutions in the loaded cache, which
+        // are read in place and only deserialized on request.
+        let solver = SolverChoice::default().into_solver();
+        for (goal, solution) in goals.iter().zip(&solutions) {
+            let key = solver.cache_key(goal);
+            assert!(loaded.get(key).is_some());
+            assert_eq!(loaded.solution(key).as_ref(), Some(solution));
+        }
+        let key = solver.cache_key(&goals[0]);
+        assert!(matches!(loaded.get(key), Some(ArchivedSolution::Unique(_))));
+
+        assert_eq!(
+            ArchivedCache::from_bytes(&bytes, 8).err(),
+            Some(PersistError::StaleRevision {
+                expected: 8,
+                found: 7
+            })
+        );
+
+        let mut corrupt = bytes.clone();
+        let middle = corrupt.len() / 2;
+        corrupt[middle] ^= 1;
+        assert_eq!(
+            ArchivedCache::from_bytes(&corrupt, 7).err(),
+            Some(PersistError::ChecksumMismatch)
+        );
+        assert_eq!(
+            ArchivedCache::from_bytes(&bytes[..bytes.len() - 1], 7).err(),
+            Some(PersistError::ChecksumMismatch)
+        );
+
+        let mut version = bytes.clone();
+        version[8] += 1;
+        assert_eq!(
+            ArchivedCache::from_bytes(&version, 7).err(),
+            Some(PersistError::UnsupportedVersion(FORMAT_VERSION + 1))
+        );
+
+        assert_eq!(
+            ArchivedCache::from_bytes(b"{\"solutions\": []}", 7).err(),
+            Some(PersistError::NotACache)
+        );
+
+        // The archive is read in place, so it must be aligned.
+        let mut shifted = vec![0; bytes.len() + 2];
+        let start = (1..=2)
+            .find(|start| !(shifted.as_ptr() as usize + start).is_multiple_of(ALIGNMENT))
+            .unwrap();
+        shifted[start..start + bytes.len()].copy_from_slice(&bytes);
+        assert_eq!(
+            ArchivedCache::from_bytes(&shifted[start..start + bytes.len()], 7).err(),
+            Some(PersistError::Unaligned)
+        );
+    });
+}
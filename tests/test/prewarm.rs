@@ -0,0 +1,84 @@
+//! Tests for `Solver::prewarm`.
+
+use super::*;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{CancellationToken, Cancelled};
+
+const PROGRAM: &str = "
+    trait Sized { }
+    struct u32 { }
+    struct Vec<T> { }
+    struct Foo { }
+    impl Sized for u32 { }
+    impl<T> Sized for Vec<T> where T: Sized { }
+";
+
+fn goals(db: &ChalkDatabase) -> Vec<UCanonical<InEnvironment<Goal<ChalkIr>>>> {
+    ["Vec<Vec<u32>>: Sized", "u32: Sized", "Foo: Sized"]
+        .iter()
+        .map(|goal| db.parse_and_lower_goal(goal).unwrap().into_closed_goal())
+        .collect()
+}
+
+#[test]
+fn prewarm_caches_goals() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goals = goals(&db);
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        assert_eq!(
+            solver.prewarm(program, &goals, &CancellationToken::new()),
+            Ok(())
+        );
+        let num_tables = solver.num_tables();
+        assert!(num_tables > 0);
+
+        assert!(solver.solve(program, &goals[0]).unwrap().is_unique());
+        assert!(solver.solve(program, &goals[1]).unwrap().is_unique());
+        assert_eq!(solver.solve(program, &goals[2]), None);
+        assert_eq!(solver.num_tables(), num_tables);
+    });
+}
+
+#[test]
+fn prewarm_cancelled() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goals = goals(&db);
+    db.with_program(|program| {
+        let cancel = CancellationToken::new();
+        cancel.clone().cancel();
+        assert!(cancel.is_cancelled());
+
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        assert_eq!(
+            solver.prewarm(program, &goals, &cancel),
+            Err(Cancelled { solved: 0 })
+        );
+        assert_eq!(solver.num_tables(), 0);
+    });
+}
+
+/// The solver can be prewarmed on another thread and handed back.
+#[test]
+fn prewarm_on_background_thread() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let goals = goals(&db);
+    let cancel = CancellationToken::new();
+
+    let solver = std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let mut solver = SolverChoice::default().into_solver();
+                assert_eq!(solver.prewarm(&*program, &goals, &cancel), Ok(()));
+                solver
+            })
+            .join()
+            .unwrap()
+    });
+    let mut solver = solver.into_test();
+    let num_tables = solver.num_tables();
+    assert!(solver.solve(&*program, &goals[0]).unwrap().is_unique());
+    assert_eq!(solver.num_tables(), num_tables);
+}
@@ -0,0 +1,265 @@
+//! Tests for `chalk_integration::builder::ProgramBuilder`.
+
+use super::*;
+use chalk_integration::builder::ProgramBuilder;
+use chalk_integration::program::Program;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_rust_ir::*;
+
+fn struct_flags() -> StructFlags {
+    StructFlags {
+        upstream: false,
+        fundamental: false,
+        extern_type: false,
+        scalar: None,
+    }
+}
+
+fn trait_flags() -> TraitFlags {
+    TraitFlags {
+        auto: false,
+        marker: false,
+        upstream: false,
+        fundamental: false,
+        non_enumerable: false,
+        sealed: false,
+    }
+}
+
+fn apply(id: impl Into<TypeKindId>, parameters: Vec<Ty<ChalkIr>>) -> Ty<ChalkIr> {
+    Ty::Apply(ApplicationTy {
+        name: TypeName::TypeKindId(id.into()),
+        parameters: parameters.into_iter().map(|ty| ty.cast()).collect(),
+    })
+}
+
+fn trait_ref(trait_id: TraitId, self_ty: Ty<ChalkIr>) -> TraitRef<ChalkIr> {
+    TraitRef {
+        trait_id,
+        parameters: vec![self_ty.cast()],
+    }
+}
+
+/// Builds the program
+///
+/// ```notrust
+/// struct u32 { }
+/// struct Vec<T> { }
+/// trait Foo { }
+/// impl Foo for u32 { }
+/// impl<T> Foo for Vec<T> where T: Foo { }
+/// ```
+fn build_foo_program() -> (Program, StructId, StructId, TraitId) {
+    let mut builder = ProgramBuilder::new();
+    let empty_struct = || StructDatumBound {
+        fields: vec![],
+        where_clauses: vec![],
+    };
+    let u32 = builder.add_struct("u32", vec![], empty_struct(), struct_flags());
+    let vec = builder.add_struct(
+        "Vec",
+        vec![ParameterKind::Ty(())],
+        empty_struct(),
+        struct_flags(),
+    );
+    let foo_trait = builder.add_trait(
+        "Foo",
+        vec![],
+        TraitDatumBound {
+            where_clauses: vec![],
+        },
+        trait_flags(),
+    );
+    builder.add_impl(
+        vec![],
+        ImplDatumBound {
+            trait_ref: trait_ref(foo_trait, apply(u32, vec![])),
+            where_clauses: vec![],
+        },
+        Polarity::Positive,
+        ImplType::Local,
+    );
+    builder.add_impl(
+        vec![ParameterKind::Ty(())],
+        ImplDatumBound {
+            trait_ref: trait_ref(foo_trait, apply(vec, vec![Ty::BoundVar(0)])),
+            where_clauses: vec![Binders {
+                binders: ParameterKinds::EMPTY,
+                value: WhereClause::Implemented(trait_ref(foo_trait, Ty::BoundVar(0))),
+            }],
+        },
+        Polarity::Positive,
+        ImplType::Local,
+    );
+    (builder.build().unwrap(), u32, vec, foo_trait)
+}
+
+/// The program built is the one that lowering its text gives.
+#[test]
+fn same_as_lowered() {
+    let (program, _, _, _) = build_foo_program();
+    let db = ChalkDatabase::with(
+        "
+            struct u32 { }
+            struct Vec<T> { }
+            trait Foo { }
+            impl Foo for u32 { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+        ",
+        SolverChoice::default(),
+    );
+    assert_eq!(program, *db.program_ir().unwrap());
+}
+
+#[test]
+fn solve() {
+    let (program, u32, vec, foo_trait) = build_foo_program();
+    let goal = |self_ty| {
+        let goal: Goal<ChalkIr> = trait_ref(foo_trait, self_ty).cast();
+        goal.into_closed_goal()
+    };
+    let mut solver = SolverChoice::default().into_solver();
+    let vec_u32 = apply(vec, vec![apply(u32, vec![])]);
+    assert!(solver
+        .solve(&program, &goal(apply(vec, vec![vec_u32])))
+        .unwrap()
+        .is_unique());
+    assert_eq!(solver.solve(&program, &goal(apply(vec, vec![]))), None);
+}
+
+/// An associated type and its value in an impl:
+///
+/// ```notrust
+/// trait Iterator { type Item; }
+/// impl Iterator for u32 { type Item = u32; }
+/// ```
+#[test]
+fn associated_types() {
+    let mut builder = ProgramBuilder::new();
+    let u32 = builder.declare_struct("u32", vec![]);
+    let iterator = builder.declare_trait("Iterator", vec![]);
+    let item = builder.add_associated_ty(
+        iterator,
+        "Item",
+        vec![],
+        AssociatedTyDatumBound {
+            bounds: vec![],
+            where_clauses: vec![],
+            default: None,
+        },
+    );
+    builder.define_trait(
+        iterator,
+        TraitDatumBound {
+            where_clauses: vec![],
+        },
+        trait_flags(),
+    );
+    builder.define_struct(
+        u32,
+        StructDatumBound {
+            fields: vec![],
+            where_clauses: vec![],
+        },
+        struct_flags(),
+    );
+    let impl_id = builder.add_impl(
+        vec![],
+        ImplDatumBound {
+            trait_ref: trait_ref(iterator, apply(u32, vec![])),
+            where_clauses: vec![],
+        },
+        Polarity::Positive,
+        ImplType::Local,
+    );
+    builder.add_associated_ty_value(impl_id, item, vec![], apply(u32, vec![]));
+    let program = builder.build().unwrap();
+    assert_eq!(program.trait_data[&iterator].associated_ty_ids, vec![item]);
+
+    let normalize = Normalize {
+        projection: ProjectionTy {
+            associated_ty_id: item,
+            parameters: vec![apply(u32, vec![]).cast()],
+        },
+        ty: apply(u32, vec![]),
+    };
+    let goal: Goal<ChalkIr> = normalize.cast();
+    assert!(SolverChoice::default()
+        .into_solver()
+        .solve(&program, &goal.into_closed_goal())
+        .unwrap()
+        .is_unique());
+}
+
+fn build_error(op: impl FnOnce(&mut ProgramBuilder)) -> String {
+    let mut builder = ProgramBuilder::new();
+    op(&mut builder);
+    builder.build().unwrap_err().to_string()
+}
+
+#[test]
+fn invalid_programs() {
+    let empty_struct = || StructDatumBound {
+        fields: vec![],
+        where_clauses: vec![],
+    };
+
+    assert_eq!(
+        build_error(|builder| {
+            builder.declare_struct("Foo", vec![]);
+        }),
+        "`Foo` is declared but never defined"
+    );
+
+    assert_eq!(
+        build_error(|builder| {
+            builder.add_struct("Foo", vec![], empty_struct(), struct_flags());
+            builder.add_struct("Foo", vec![], empty_struct(), struct_flags());
+        }),
+        "duplicate type name `Foo`"
+    );
+
+    assert_eq!(
+        build_error(|builder| {
+            let vec = builder.declare_struct("Vec", vec![ParameterKind::Ty(())]);
+            let bound = StructDatumBound {
+                fields: vec![apply(vec, vec![])],
+                where_clauses: vec![],
+            };
+            builder.define_struct(vec, bound, struct_flags());
+        }),
+        "`Vec` takes 1 type parameters, not 0"
+    );
+
+    assert_eq!(
+        build_error(|builder| {
+            let bound = StructDatumBound {
+                fields: vec![Ty::BoundVar(1)],
+                where_clauses: vec![],
+            };
+            builder.add_struct("Foo", vec![ParameterKind::Ty(())], bound, struct_flags());
+        }),
+        "bound variable `^1` is not bound by any binder"
+    );
+
+    assert_eq!(
+        build_error(|builder| {
+            let u32 = builder.add_struct("u32", vec![], empty_struct(), struct_flags());
+            builder.add_impl(
+                vec![],
+                ImplDatumBound {
+                    trait_ref: TraitRef {
+                        trait_id: TraitId(u32.0),
+                        parameters: vec![apply(u32, vec![]).cast()],
+                    },
+                    where_clauses: vec![],
+                },
+                Polarity::Positive,
+                ImplType::Local,
+            );
+        }),
+        "no item with id `TraitId(0)`"
+    );
+}
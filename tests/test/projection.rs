@@ -1,6 +1,10 @@
 //! Tests related to projection of associated types and normalization.
 
 use super::*;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::RustIrDatabase;
 
 #[test]
 fn normalize_basic() {
@@ -189,6 +193,82 @@ fn normalize_gat1() {
     }
 }
 
+/// An impl that leaves out an associated type takes the default from
+/// the trait, in terms of its own parameters.
+#[test]
+fn normalize_default() {
+    test! {
+        program {
+            struct u32 { }
+            struct Vec<T> { }
+            struct Pair<A, B> { }
+            struct Ref<'a, T> { }
+
+            trait Iterator {
+                type Item = u32;
+            }
+
+            impl Iterator for u32 { }
+
+            impl<T> Iterator for Vec<T> {
+                type Item = T;
+            }
+
+            trait Iterable {
+                type Iter<'a> = Ref<'a, Self>;
+            }
+
+            impl<T> Iterable for Vec<T> { }
+
+            trait Convert<T> {
+                type Out = Pair<Self, T>;
+            }
+
+            impl<U> Convert<U> for Vec<U> { }
+        }
+
+        goal {
+            exists<U> {
+                Normalize(<u32 as Iterator>::Item -> U)
+            }
+        } yields {
+            "Unique; substitution [?0 := u32], lifetime constraints []"
+        }
+
+        goal {
+            forall<T> {
+                exists<U> {
+                    Normalize(<Vec<T> as Iterator>::Item -> U)
+                }
+            }
+        } yields {
+            "Unique; substitution [?0 := !1_0], lifetime constraints []"
+        }
+
+        goal {
+            forall<T> {
+                forall<'a> {
+                    exists<U> {
+                        Normalize(<Vec<T> as Iterable>::Iter<'a> -> U)
+                    }
+                }
+            }
+        } yields {
+            "Unique; substitution [?0 := Ref<'!2_0, Vec<!1_0>>], lifetime constraints []"
+        }
+
+        goal {
+            forall<T> {
+                exists<U> {
+                    Normalize(<Vec<T> as Convert<T>>::Out -> U)
+                }
+            }
+        } yields {
+            "Unique; substitution [?0 := Pair<Vec<!1_0>, !1_0>], lifetime constraints []"
+        }
+    }
+}
+
 #[test]
 fn normalize_gat2() {
     test! {
@@ -667,3 +747,220 @@ fn gat_unify_with_implied_wc() {
         }
     }
 }
+
+#[test]
+fn fn_trait_sugar() {
+    test! {
+        program {
+            #[lang(fn_once)]
+            trait FnOnce<Args> { type Output; }
+
+            #[lang(fn)]
+            trait Fn<Args> { }
+
+            struct u32 { }
+            struct Closure { }
+
+            impl FnOnce<(u32,)> for Closure { type Output = u32; }
+            impl Fn<(u32,)> for Closure { }
+        }
+
+        goal {
+            Closure: Fn(u32) -> u32
+        } yields {
+            "Unique"
+        }
+
+        // Without `-> T`, the output is `()`.
+        goal {
+            Closure: Fn(u32)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Closure: Fn()
+        } yields {
+            "No possible solution"
+        }
+
+        // The sugar in a where clause desugars to the tupled form.
+        goal {
+            forall<F> {
+                if (F: Fn(u32, u32) -> u32) {
+                    F: Fn<(u32, u32)>, <F as FnOnce<(u32, u32)>>::Output = u32
+                }
+            }
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+#[test]
+fn normalizations() {
+    let db = ChalkDatabase::with(
+        "
+            trait Iterator { type Item; }
+            trait Clone { }
+            struct Vec<T> { }
+            struct u32 { }
+            impl<T> Iterator for Vec<T> { type Item = T; }
+            impl Clone for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let normalizations = |text: &str| {
+            let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+            SolverChoice::default()
+                .into_solver()
+                .normalizations(program, &goal)
+                .map(|normalizations| {
+                    (
+                        normalizations.binders.len(),
+                        format!("{:?}", normalizations.value),
+                    )
+                })
+        };
+
+        assert_eq!(
+            normalizations("exists<T> { Normalize(<Vec<u32> as Iterator>::Item -> T) }").unwrap(),
+            (
+                0,
+                "[Normalize(<Vec<u32> as Iterator>::Item -> u32)]".to_string()
+            )
+        );
+        assert_eq!(
+            normalizations("exists<T> { T = Vec<u32>, <T as Iterator>::Item: Clone }").unwrap(),
+            (
+                0,
+                "[Normalize(<Vec<u32> as Iterator>::Item -> u32)]".to_string()
+            )
+        );
+
+        // The projection under the `forall` names its variable.
+        assert_eq!(
+            normalizations("forall<T> { <Vec<T> as Iterator>::Item = T }").unwrap(),
+            (0, "[]".to_string())
+        );
+        assert_eq!(
+            normalizations("exists<T> { <Vec<T> as Iterator>::Item = T }").unwrap(),
+            (
+                1,
+                "[Normalize(<Vec<^0> as Iterator>::Item -> ^0)]".to_string()
+            )
+        );
+        assert_eq!(normalizations("exists<T> { T: Clone, T = Vec<T> }"), None);
+    });
+}
+
+#[test]
+fn multiple_assoc_bindings() {
+    test! {
+        program {
+            struct u32 { }
+            struct bool { }
+            trait Foo { type A; type B; }
+            trait Bar { }
+            trait Baz { type Item: Foo<A = u32, B = bool>; }
+            struct S { }
+            struct R { }
+            impl Foo for S { type A = u32; type B = bool; }
+            impl Foo for R { type A = u32; type B = u32; }
+            impl<T> Bar for T where T: Foo<A = u32, B = bool> { }
+        }
+
+        goal {
+            S: Bar
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            R: Bar
+        } yields {
+            "No possible solution"
+        }
+
+        // The bound implies the trait as well as each binding.
+        goal {
+            forall<T> { if (T: Foo<A = u32, B = bool>) { T: Bar } }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> { if (T: Foo<A = u32, B = bool>) { T: Foo } }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            dyn Foo<A = u32, B = bool>: Bar
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> { if (T: Baz) { <T as Baz>::Item: Bar } }
+        } yields {
+            "Unique"
+        }
+    }
+}
+
+#[test]
+fn bounds_goal() {
+    let db = ChalkDatabase::with(
+        "
+            struct u32 { }
+            struct bool { }
+            trait Foo { type A; type B; }
+            trait Baz { type Item: Foo<A = u32, B = bool>; }
+            struct S { }
+            struct R { }
+            impl Foo for S { type A = u32; type B = bool; }
+            impl Foo for R { type A = u32; type B = u32; }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let id = |name: &str| program.type_ids[&chalk_ir::intern(name)];
+        let trait_id = match id("Baz") {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => panic!("`Baz` is not a trait"),
+        };
+        let item = program.trait_datum(trait_id).associated_ty_ids[0];
+        let bounds = &program.associated_ty_data(item).binders.value.bounds;
+        let ty = |name: &str| -> Ty<ChalkIr> {
+            ApplicationTy {
+                name: TypeName::TypeKindId(id(name)),
+                parameters: vec![],
+            }
+            .cast()
+        };
+
+        // The bounds of `Item` (bound by `Self`) mention no variables
+        // apart from the self type we supply.
+        let goal = chalk_solve::ext::bounds_goal(bounds, &ty("S")).unwrap();
+        assert_eq!(
+            format!("{:?}", goal),
+            "((Implemented(S: Foo), ProjectionEq(<S as Foo>::A = u32)), \
+             ProjectionEq(<S as Foo>::B = bool))"
+        );
+        let solve = |goal: Goal<ChalkIr>| {
+            SolverChoice::default()
+                .into_solver()
+                .solve(program, &goal.into_closed_goal())
+        };
+        assert!(solve(goal).unwrap().is_unique());
+        assert_eq!(
+            solve(chalk_solve::ext::bounds_goal(bounds, &ty("R")).unwrap()),
+            None
+        );
+        assert!(chalk_solve::ext::bounds_goal(&[], &ty("S")).is_none());
+    });
+}
@@ -0,0 +1,84 @@
+//! Tests for `SlgOptions::prune_well_formed`.
+
+use super::*;
+use chalk_solve::SlgOptions;
+
+const PROGRAM: &str = "
+    trait Eq { }
+    trait Ord where Self: Eq { }
+    trait Copy { }
+    trait A where Self: B, Self: Copy { }
+    trait B where Self: A { }
+    struct u32 { }
+    struct Foo { }
+    struct Set<T> where T: Ord, T: Eq { }
+    struct Pair<T> where T: A, T: Copy { }
+    impl Eq for u32 { }
+    impl Ord for u32 { }
+    impl Copy for u32 { }
+    impl A for u32 { }
+    impl B for u32 { }
+    impl Eq for Foo { }
+";
+
+/// Solves `goal` with or without pruning, returning the solution and
+/// the number of conditions pruned.
+fn solve(prune: bool, goal: &str) -> (Option<Solution>, usize) {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+    let mut solver =
+        SolverChoice::slg_with(SlgOptions::default().prune_well_formed(prune)).into_solver();
+    let solution = solver.solve(&db, &goal);
+    (solution, solver.stats().pruned_well_formed)
+}
+
+/// `WF(T: Eq)` is pruned from the clause for `WF(Set<T>)`, since
+/// `WF(T: Ord)` implies it.
+#[test]
+fn implied_condition_pruned() {
+    let (solution, pruned) = solve(true, "WellFormed(Set<u32>)");
+    assert!(solution.unwrap().is_unique());
+    assert_eq!(pruned, 1);
+
+    let (_, pruned) = solve(false, "WellFormed(Set<u32>)");
+    assert_eq!(pruned, 0);
+}
+
+/// Pruning does not change the solutions.
+#[test]
+fn same_solutions() {
+    for goal in &[
+        "WellFormed(Set<u32>)",
+        "WellFormed(Set<Foo>)",
+        "WellFormed(Pair<u32>)",
+        "WellFormed(Pair<Foo>)",
+        "WellFormed(u32: A)",
+        "WellFormed(Foo: Ord)",
+        "exists<T> { WellFormed(Set<T>) }",
+        "forall<T> { if (T: Ord) { WellFormed(Set<T>) } }",
+    ] {
+        assert_eq!(solve(true, goal).0, solve(false, goal).0, "{}", goal);
+    }
+}
+
+/// `WF(T: A)` and `WF(T: B)` can be proven through each other (as
+/// `WellFormed` goals are coinductive), so neither is relied upon to
+/// imply `WF(T: Copy)`.
+#[test]
+fn cyclic_conditions_kept() {
+    for goal in &["WellFormed(u32: A)", "WellFormed(Pair<u32>)"] {
+        let (solution, pruned) = solve(true, goal);
+        assert!(solution.unwrap().is_unique(), "{}", goal);
+        assert_eq!(pruned, 0, "{}", goal);
+    }
+}
+
+/// Nothing is pruned when a hypothesis could prove a `WellFormed`
+/// goal by other means.
+#[test]
+fn not_pruned_with_well_formed_hypotheses() {
+    let goal = "forall<T> { if (WellFormed(T: Ord)) { WellFormed(Set<T>) } }";
+    let (with, pruned) = solve(true, goal);
+    assert_eq!(pruned, 0);
+    assert_eq!(with, solve(false, goal).0);
+}
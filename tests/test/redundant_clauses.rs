@@ -0,0 +1,59 @@
+//! Tests for `ext::is_redundant_clause`.
+
+use super::*;
+use chalk_ir::InEnvironment;
+use chalk_solve::ext::is_redundant_clause;
+
+const PROGRAM: &str = "
+    trait Clone { }
+    trait Copy where Self: Clone { }
+    trait Debug { }
+    trait Loop { }
+    struct u32 { }
+    impl Clone for u32 { }
+    impl<T> Loop for T where T: Loop { }
+";
+
+/// Returns the hypotheses of the `if` in `forall<T> { if (..) { .. } }`
+/// that are redundant.
+fn redundant(text: &str) -> Vec<String> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    let InEnvironment { environment, .. } = &goal.canonical.value;
+    db.with_program(|program| {
+        environment
+            .clauses
+            .iter()
+            .filter(|clause| is_redundant_clause(program, environment, clause).unwrap())
+            .map(|clause| format!("{:?}", clause))
+            .collect()
+    })
+}
+
+#[test]
+fn implied_by_supertrait() {
+    assert_eq!(
+        redundant("forall<T> { if (T: Copy; T: Clone) { T: Debug } }"),
+        vec!["FromEnv(!1_0: Clone)"]
+    );
+}
+
+#[test]
+fn not_redundant() {
+    assert!(redundant("forall<T> { if (T: Copy; T: Debug) { T: Debug } }").is_empty());
+}
+
+#[test]
+fn implied_by_impl() {
+    assert_eq!(
+        redundant("forall<T> { if (u32: Clone; T: Debug) { T: Debug } }"),
+        vec!["FromEnv(u32: Clone)"]
+    );
+}
+
+/// `T: Loop` could only be proven through the impl, which needs
+/// `T: Loop` again.
+#[test]
+fn cycle_not_redundant() {
+    assert!(redundant("forall<T> { if (T: Loop) { T: Debug } }").is_empty());
+}
@@ -0,0 +1,211 @@
+//! Tests for `ReentrantSolver`, which databases can query from within
+//! their own callbacks.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{Overflow, OverflowAction, ReentrantSolver, RustIrDatabase};
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Wraps a program, only offering the impls of a trait `Gated` for
+/// the types `T` where the goal `gate(T)` holds. The database decides
+/// this with nested queries on `solver`.
+#[derive(Debug)]
+struct GatedDb<'p> {
+    program: &'p Program,
+    solver: ReentrantSolver,
+    gated: TraitId,
+    gate: fn(&GatedDb<'p>, Ty<ChalkIr>) -> Goal<ChalkIr>,
+    max_depth_seen: Cell<usize>,
+}
+
+impl<'p> GatedDb<'p> {
+    fn new(
+        program: &'p Program,
+        gated: &str,
+        gate: fn(&GatedDb<'p>, Ty<ChalkIr>) -> Goal<ChalkIr>,
+    ) -> Self {
+        let mut db = GatedDb {
+            program,
+            solver: ReentrantSolver::new(SolverChoice::default()),
+            gated: TraitId(RawId { index: 0 }),
+            gate,
+            max_depth_seen: Cell::new(0),
+        };
+        db.gated = db.trait_id(gated);
+        db
+    }
+
+    fn trait_id(&self, name: &str) -> TraitId {
+        match self.program.type_ids[&chalk_ir::intern(name)] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => panic!("`{}` is not a trait", name),
+        }
+    }
+
+    fn ty(&self, name: &str) -> Ty<ChalkIr> {
+        ApplicationTy {
+            name: TypeName::TypeKindId(self.program.type_ids[&chalk_ir::intern(name)]),
+            parameters: vec![],
+        }
+        .cast()
+    }
+
+    fn implemented(&self, ty: Ty<ChalkIr>, trait_name: &str) -> Goal<ChalkIr> {
+        TraitRef {
+            trait_id: self.trait_id(trait_name),
+            parameters: vec![ty.cast()],
+        }
+        .cast()
+    }
+
+    fn solve(&self, goal: Goal<ChalkIr>) -> Option<Solution> {
+        self.solver
+            .solve(&ForwardingDb(self), &goal.into_closed_goal())
+    }
+}
+
+impl DbHooks for GatedDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn impls_for_trait(&self, trait_id: TraitId, parameters: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
+        let impls = self.program.impls_for_trait(trait_id, parameters);
+        if trait_id != self.gated {
+            return impls;
+        }
+
+        // Only types without variables are gated in these tests.
+        let self_ty = parameters[0].assert_ty_ref().clone();
+        self.max_depth_seen
+            .set(self.max_depth_seen.get().max(self.solver.depth()));
+        match self.solve((self.gate)(self, self_ty)) {
+            Some(Solution::Unique(_)) => impls,
+            _ => vec![],
+        }
+    }
+}
+
+const PROGRAM: &str = "
+    trait Foo { }
+    trait Enabled { }
+    struct A { }
+    struct B { }
+    impl Foo for A { }
+    impl Foo for B { }
+    impl Enabled for A { }
+";
+
+#[test]
+fn nested_queries() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    db.with_program(|program| {
+        let db = GatedDb::new(program, "Foo", |db, ty| db.implemented(ty, "Enabled"));
+
+        assert!(db
+            .solve(db.implemented(db.ty("A"), "Foo"))
+            .unwrap()
+            .is_unique());
+        assert_eq!(db.solve(db.implemented(db.ty("B"), "Foo")), None);
+
+        // The gate was checked from inside the outer query.
+        assert_eq!(db.max_depth_seen.get(), 1);
+        assert_eq!(db.solver.depth(), 0);
+    });
+}
+
+#[test]
+fn nested_query_cycle() {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    db.with_program(|program| {
+        // Whether `A: Foo` has impls depends on whether `A: Foo`
+        // holds. The nested query is cut off as ambiguous, so the
+        // impls are not offered.
+        let db = GatedDb::new(program, "Foo", |db, ty| db.implemented(ty, "Foo"));
+
+        assert_eq!(db.solve(db.implemented(db.ty("A"), "Foo")), None);
+        assert_eq!(db.max_depth_seen.get(), 1);
+        assert_eq!(db.solver.depth(), 0);
+    });
+}
+
+const CHAIN_PROGRAM: &str = "
+    trait Foo { }
+    trait Enabled { }
+    struct A { }
+    struct B { }
+    struct C { }
+    impl Foo for A { }
+    impl Foo for B { }
+    impl Foo for C { }
+    impl Enabled for C { }
+";
+
+/// `A: Foo` is gated on `B: Foo`, which is gated on `C: Foo`, which is
+/// gated on `C: Enabled`: solving `A: Foo` nests four queries.
+fn chain_gate(db: &GatedDb<'_>, ty: Ty<ChalkIr>) -> Goal<ChalkIr> {
+    if ty == db.ty("A") {
+        db.implemented(db.ty("B"), "Foo")
+    } else if ty == db.ty("B") {
+        db.implemented(db.ty("C"), "Foo")
+    } else {
+        db.implemented(ty, "Enabled")
+    }
+}
+
+#[test]
+fn nested_query_overflow() {
+    let db = ChalkDatabase::with(CHAIN_PROGRAM, SolverChoice::default());
+    db.with_program(|program| {
+        // By default, the innermost query is cut off as ambiguous.
+        let mut db = GatedDb::new(program, "Foo", chain_gate);
+        db.solver = ReentrantSolver::new(SolverChoice::default()).max_depth(2);
+        assert_eq!(db.solve(db.implemented(db.ty("A"), "Foo")), None);
+        assert!(db
+            .solve(db.implemented(db.ty("C"), "Foo"))
+            .unwrap()
+            .is_unique());
+
+        // The handler can raise the limit instead.
+        let overflows = Rc::new(Cell::new(0));
+        let counter = overflows.clone();
+        let mut db = GatedDb::new(program, "Foo", chain_gate);
+        db.solver = ReentrantSolver::new(SolverChoice::default())
+            .max_depth(2)
+            .on_overflow(move |depth, _| {
+                assert_eq!(depth, 2);
+                counter.set(counter.get() + 1);
+                OverflowAction::IncreaseDepth(2)
+            });
+        assert!(db
+            .solve(db.implemented(db.ty("A"), "Foo"))
+            .unwrap()
+            .is_unique());
+        assert_eq!(overflows.get(), 1);
+        assert_eq!(db.max_depth_seen.get(), 3);
+
+        // Or abort the outermost query, after which the solver is
+        // usable again.
+        let mut db = GatedDb::new(program, "Foo", chain_gate);
+        db.solver = ReentrantSolver::new(SolverChoice::default())
+            .max_depth(2)
+            .on_overflow(|_, _| OverflowAction::Abort);
+        let goal = db.implemented(db.ty("A"), "Foo").into_closed_goal();
+        assert_eq!(
+            db.solver.try_solve(&ForwardingDb(&db), &goal),
+            Err(Overflow { depth: 2 })
+        );
+        assert_eq!(db.solver.depth(), 0);
+        let goal = db.implemented(db.ty("C"), "Foo").into_closed_goal();
+        assert!(db
+            .solver
+            .try_solve(&ForwardingDb(&db), &goal)
+            .unwrap()
+            .unwrap()
+            .is_unique());
+    });
+}
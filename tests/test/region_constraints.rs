@@ -0,0 +1,130 @@
+//! Tests for `RustIrDatabase::region_constraint_resolver`, which
+//! decides region constraints as soon as they arise.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{RegionConstraintResolution, RegionConstraintResolver, RustIrDatabase};
+use std::sync::Arc;
+
+/// Wraps a program, deciding every region constraint as `resolution`.
+#[derive(Debug)]
+struct RegionDb<'p> {
+    program: &'p Program,
+    resolution: RegionConstraintResolution,
+}
+
+impl DbHooks for RegionDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn region_constraint_resolver(&self) -> Option<Arc<RegionConstraintResolver>> {
+        let resolution = self.resolution;
+        Some(Arc::new(move |_: &InEnvironment<Constraint<ChalkIr>>| {
+            resolution
+        }))
+    }
+}
+
+/// Solves the goal of `text` with every region constraint decided as
+/// `resolution`, or with none decided if it is `None`, and checks the
+/// solution against `expected`.
+fn check(text: &str, resolution: Option<RegionConstraintResolution>, expected: &str) {
+    let db = ChalkDatabase::with(
+        "
+            struct Ref<'a> { }
+            struct A { }
+            struct B { }
+            trait Same<'a> { }
+            trait Pick<'a, T> { }
+            impl<'x> Same<'x> for Ref<'x> { }
+            impl<'x> Pick<'x, A> for Ref<'x> { }
+            impl<'x, 'y> Pick<'y, B> for Ref<'x> { }
+        ",
+        SolverChoice::default(),
+    );
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        let solution = match resolution {
+            Some(resolution) => solver.solve(
+                &ForwardingDb(&RegionDb {
+                    program,
+                    resolution,
+                }),
+                &goal,
+            ),
+            None => solver.solve(program, &goal),
+        };
+        assert_result(&solution, expected);
+    })
+}
+
+#[test]
+fn constraints_resolved() {
+    let goal = "forall<'a, 'b> { Ref<'a>: Same<'b> }";
+    check(
+        goal,
+        None,
+        "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_1 == '!1_0 }]",
+    );
+    check(
+        goal,
+        Some(RegionConstraintResolution::Deferred),
+        "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([]), goal: '!1_1 == '!1_0 }]",
+    );
+    check(goal, Some(RegionConstraintResolution::Holds), "Unique");
+    check(
+        goal,
+        Some(RegionConstraintResolution::Fails),
+        "No possible solution",
+    );
+}
+
+/// The impl for `A` would require `'a == 'b`; once that is known not
+/// to hold, only the impl for `B` remains.
+#[test]
+fn candidates_pruned() {
+    let goal = "forall<'a, 'b> { exists<T> { Ref<'a>: Pick<'b, T> } }";
+    check(goal, None, "Ambiguous; no inference guidance");
+    check(
+        goal,
+        Some(RegionConstraintResolution::Fails),
+        "Unique; substitution [?0 := B], lifetime constraints []",
+    );
+}
+
+/// The field type `&'a T` is well-formed only if `T: 'a`, which the WF
+/// check of the struct leaves to the resolver.
+#[test]
+fn reference_field_outlives() {
+    use chalk_solve::wf::{WfError, WfSolver};
+
+    let db = ChalkDatabase::with(
+        "
+            struct Foo<'a, T> { x: &'a T }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|program| {
+        let foo = match program.type_ids[&chalk_ir::intern("Foo")] {
+            TypeKindId::StructId(struct_id) => struct_id,
+            _ => unreachable!(),
+        };
+        let verify = |resolution| {
+            let db = RegionDb {
+                program,
+                resolution,
+            };
+            WfSolver::new(&ForwardingDb(&db), SolverChoice::default()).verify_struct_decl(foo)
+        };
+        assert!(verify(RegionConstraintResolution::Holds).is_ok());
+        assert!(verify(RegionConstraintResolution::Deferred).is_ok());
+        assert!(matches!(
+            verify(RegionConstraintResolution::Fails),
+            Err(WfError::IllFormedTypeDecl(_))
+        ));
+    })
+}
@@ -0,0 +1,125 @@
+//! Tests for solving in `SolveMode::Revealing`.
+
+use super::*;
+use chalk_solve::{SlgOptions, SolveMode};
+
+fn revealing() -> SolverChoice {
+    SolverChoice::slg_with(SlgOptions::default().solve_mode(SolveMode::Revealing))
+}
+
+/// In revealing mode, the associated type values of the most
+/// specialized impl are used, whereas ordinarily every impl that
+/// applies is.
+#[test]
+fn most_specialized_impl() {
+    test! {
+        program {
+            trait Bar { }
+            trait Foo { type Assoc; }
+            struct u32 { }
+            struct Vec<T> { }
+            struct Baz { }
+            impl<T> Bar for T { }
+            impl<T> Foo for T where T: Bar { type Assoc = (); }
+            impl<T> Foo for Vec<T> where T: Bar { type Assoc = T; }
+            impl Foo for Vec<u32> { type Assoc = u32; }
+        }
+
+        goal {
+            Normalize(<Baz as Foo>::Assoc -> ())
+        } yields[revealing()] {
+            "Unique"
+        }
+
+        goal {
+            Normalize(<Baz as Foo>::Assoc -> Baz)
+        } yields[revealing()] {
+            "No possible solution"
+        }
+
+        goal {
+            Normalize(<Vec<Baz> as Foo>::Assoc -> Baz)
+        } yields[revealing()] {
+            "Unique"
+        }
+
+        goal {
+            Normalize(<Vec<Baz> as Foo>::Assoc -> ())
+        } yields[revealing()] {
+            "No possible solution"
+        }
+
+        goal {
+            Normalize(<Vec<u32> as Foo>::Assoc -> u32)
+        } yields[revealing()] {
+            "Unique"
+        }
+
+        goal {
+            Normalize(<Vec<u32> as Foo>::Assoc -> Baz)
+        } yields[revealing()] {
+            "No possible solution"
+        }
+
+        goal {
+            exists<U> { Normalize(<Vec<u32> as Foo>::Assoc -> U) }
+        } yields[SolverChoice::default()] {
+            "Ambiguous"
+        } yields[revealing()] {
+            "Unique; substitution [?0 := u32]"
+        }
+    }
+}
+
+#[test]
+fn implemented() {
+    test! {
+        program {
+            trait Bar { }
+            trait Foo { type Assoc; }
+            struct u32 { }
+            struct Vec<T> { }
+            struct Baz { }
+            impl<T> Bar for T { }
+            impl<T> Foo for T where T: Bar { type Assoc = (); }
+            impl<T> Foo for Vec<T> where T: Bar { type Assoc = T; }
+            impl Foo for Vec<u32> { type Assoc = u32; }
+        }
+
+        goal {
+            Vec<u32>: Foo
+        } yields[revealing()] {
+            "Unique"
+        }
+
+        goal {
+            Baz: Foo
+        } yields[revealing()] {
+            "Unique"
+        }
+    }
+}
+
+/// Whether a more specialized impl applies can only be decided once
+/// the types are known.
+#[test]
+fn unknown_types() {
+    test! {
+        program {
+            trait Bar { }
+            trait Foo { type Assoc; }
+            struct u32 { }
+            struct Vec<T> { }
+            impl<T> Bar for T { }
+            impl<T> Foo for T where T: Bar { type Assoc = (); }
+            impl<T> Foo for Vec<T> where T: Bar { type Assoc = T; }
+            impl Foo for Vec<u32> { type Assoc = u32; }
+        }
+
+        goal {
+            exists<T, U> { Normalize(<Vec<T> as Foo>::Assoc -> U) }
+        } yields[revealing()] {
+            "Ambiguous"
+        }
+    }
+}
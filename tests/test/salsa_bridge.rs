@@ -0,0 +1,56 @@
+//! Tests for the helpers in `chalk_solve::salsa_bridge`.
+
+use super::*;
+use chalk_solve::salsa_bridge::{self, SolveKey, SolverCache};
+use std::sync::Arc;
+
+const PROGRAM: &str = "
+    trait Clone { }
+    struct Foo { }
+    struct Bar { }
+    impl Clone for Foo { }
+";
+
+const EDITED_PROGRAM: &str = "
+    trait Clone { }
+    struct Foo { }
+    struct Bar { }
+    impl Clone for Foo { }
+    impl Clone for Bar { }
+";
+
+#[test]
+fn solver_cache_revisions() {
+    let mut db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let cache = SolverCache::new(SolverChoice::default());
+    let key = |db: &ChalkDatabase, revision| SolveKey {
+        revision,
+        goal: db
+            .parse_and_lower_goal("Bar: Clone")
+            .unwrap()
+            .into_closed_goal(),
+    };
+
+    let first = key(&db, 1);
+    db.with_program(|_| {
+        assert_eq!(cache.solve(&db, &first), None);
+        assert_eq!(
+            salsa_bridge::solve(&db, SolverChoice::default(), &first.goal),
+            None
+        );
+    });
+
+    db.set_program_text(Arc::new(EDITED_PROGRAM.to_string()));
+    let second = key(&db, 2);
+    assert_eq!(first.goal, second.goal);
+    db.with_program(|_| {
+        // The tables of the first revision would still say that
+        // `Bar: Clone` does not hold.
+        assert!(cache.solve(&db, &second).unwrap().is_unique());
+        assert_eq!(cache.revision(), 2);
+        assert_eq!(
+            salsa_bridge::solve(&db, SolverChoice::default(), &second.goal),
+            cache.solve(&db, &second)
+        );
+    });
+}
@@ -1,7 +1,12 @@
 use crate::test_util::*;
 use chalk_integration::db::ChalkDatabase;
+use chalk_integration::query::LoweringDatabase;
+use chalk_ir::arena::EnvironmentArena;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::fold::shift::Shift;
+use chalk_ir::{Environment, Goal, InEnvironment, UCanonical};
 use chalk_solve::ext::*;
-use chalk_solve::SolverChoice;
+use chalk_solve::{Guidance, SlgOptions, Solution, SolverChoice, SyncDatabase};
 
 macro_rules! test {
     (program $program:tt $(goal $goal:tt first $n:tt with max $depth:tt { $expected:expr })*) => {
@@ -34,7 +39,7 @@ fn solve_goal(program_text: &str, goals: Vec<(usize, usize, &str, &str)>) {
                 .parse_and_lower_goal(&goal_text[1..goal_text.len() - 1])
                 .unwrap();
             let peeled_goal = goal.into_peeled_goal();
-            let mut slg_solver = SolverChoice::SLG { max_size }.into_solver().into_test();
+            let mut slg_solver = SolverChoice::slg(max_size).into_solver().into_test();
             let result = format!(
                 "{:#?}",
                 slg_solver.force_answers(&db, &peeled_goal, num_answers)
@@ -65,7 +70,7 @@ fn solve_goal_fixed_num_answers(program_text: &str, goals: Vec<(usize, usize, &s
                 .parse_and_lower_goal(&goal_text[1..goal_text.len() - 1])
                 .unwrap();
             let peeled_goal = goal.into_peeled_goal();
-            let mut solver = SolverChoice::SLG { max_size }.into_solver().into_test();
+            let mut solver = SolverChoice::slg(max_size).into_solver().into_test();
             let result = format!("{:?}", solver.solve(&db, &peeled_goal));
 
             // Strip trailing commas to handle both nightly and stable debug formatting
@@ -106,6 +111,7 @@ fn basic() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -141,6 +147,7 @@ fn breadth_first() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -151,6 +158,7 @@ fn breadth_first() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -161,6 +169,7 @@ fn breadth_first() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -171,6 +180,7 @@ fn breadth_first() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -181,6 +191,7 @@ fn breadth_first() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -356,6 +367,7 @@ fn subgoal_cycle_uninhabited() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -374,6 +386,7 @@ fn subgoal_cycle_uninhabited() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -400,6 +413,7 @@ fn subgoal_cycle_uninhabited() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -418,6 +432,7 @@ fn subgoal_cycle_uninhabited() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -449,6 +464,7 @@ fn subgoal_cycle_inhabited() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -483,6 +499,7 @@ fn basic_region_constraint_from_positive_impl() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -520,6 +537,7 @@ fn example_2_1_EWFS() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -530,6 +548,7 @@ fn example_2_1_EWFS() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -540,6 +559,7 @@ fn example_2_1_EWFS() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -579,6 +599,7 @@ fn example_2_2_EWFS() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -702,6 +723,7 @@ fn cached_answers_1() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -712,6 +734,7 @@ fn cached_answers_1() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -722,6 +745,7 @@ fn cached_answers_1() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -732,6 +756,7 @@ fn cached_answers_1() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -744,6 +769,7 @@ fn cached_answers_1() {
                         ]
                     },
                     ambiguous: true
+                    caveat: false
                 }
             ]"
         }
@@ -778,6 +804,7 @@ fn cached_answers_2() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -788,6 +815,7 @@ fn cached_answers_2() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -798,6 +826,7 @@ fn cached_answers_2() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -808,6 +837,7 @@ fn cached_answers_2() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -820,6 +850,7 @@ fn cached_answers_2() {
                         ]
                     },
                     ambiguous: true
+                    caveat: false
                 }
             ]"
         }
@@ -854,6 +885,7 @@ fn cached_answers_3() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -864,6 +896,7 @@ fn cached_answers_3() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -874,6 +907,7 @@ fn cached_answers_3() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -886,6 +920,7 @@ fn cached_answers_3() {
                         ]
                     },
                     ambiguous: true
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -896,6 +931,7 @@ fn cached_answers_3() {
                         binders: []
                     },
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -961,6 +997,7 @@ fn non_enumerable_traits_direct() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 },
                 Answer {
                     subst: Canonical {
@@ -971,6 +1008,7 @@ fn non_enumerable_traits_direct() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -988,6 +1026,7 @@ fn non_enumerable_traits_direct() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -1087,6 +1126,7 @@ fn non_enumerable_traits_reorder() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -1105,6 +1145,7 @@ fn non_enumerable_traits_reorder() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -1173,6 +1214,7 @@ fn negative_reorder() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -1191,6 +1233,7 @@ fn negative_reorder() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
             ]"
         }
@@ -1249,6 +1292,7 @@ fn coinductive_unsound1() {
                         binders: []
                     }
                     ambiguous: false
+                    caveat: false
                 }
            ]"
         }
@@ -1296,3 +1340,534 @@ fn coinductive_unsound2() {
         }
     }
 }
+
+/// Quantifiers nested under `,` and `if` are hoisted into a prefix
+/// before solving, so differently nested spellings of the same goal
+/// share their tables rather than each creating their own, as they do
+/// when solved without being prenexed.
+#[test]
+fn prenex_shares_tables() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo<'a> { }
+            trait Bar { }
+            struct u32 { }
+            impl<'a> Foo<'a> for u32 { }
+            impl Bar for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let nested = db
+            .parse_and_lower_goal("exists<T> { T: Bar, forall<'a> { if (T: Bar) { T: Foo<'a> } } }")
+            .unwrap();
+        let flat = db
+            .parse_and_lower_goal("exists<T> { forall<'a> { T: Bar, if (T: Bar) { T: Foo<'a> } } }")
+            .unwrap();
+        assert_eq!(
+            nested.clone().into_peeled_goal(),
+            flat.clone().into_peeled_goal()
+        );
+
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        let result = solver.solve(&db, &nested.clone().into_peeled_goal());
+        super::assert_result(&result, "Unique; substitution [?0 := u32]");
+        let num_tables = solver.num_tables();
+        let result = solver.solve(&db, &flat.clone().into_peeled_goal());
+        super::assert_result(&result, "Unique; substitution [?0 := u32]");
+        assert_eq!(num_tables, solver.num_tables());
+
+        let mut unprenexed = SolverChoice::default().into_solver().into_test();
+        assert!(unprenexed
+            .solve(&db, &nested.into_closed_goal())
+            .unwrap()
+            .is_unique());
+        assert!(unprenexed
+            .solve(&db, &flat.into_closed_goal())
+            .unwrap()
+            .is_unique());
+        assert!(num_tables < unprenexed.num_tables());
+    });
+}
+
+/// When both conjuncts are quantified, the `exists` is hoisted before
+/// the `forall`, whichever side it is on.
+#[test]
+fn prenex_hoists_exists_first() {
+    let db = ChalkDatabase::with("trait Bar { } trait Foo<T> { }", SolverChoice::default());
+    db.with_program(|_| {
+        let peel = |text| db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+        let flat = peel("exists<U> { forall<T> { T: Bar, U: Foo<U> } }");
+        assert_eq!(peel("forall<T> { T: Bar }, exists<U> { U: Foo<U> }"), flat);
+        assert_eq!(
+            peel("exists<U> { U: Foo<U> }, forall<T> { T: Bar }"),
+            peel("exists<U> { forall<T> { U: Foo<U>, T: Bar } }")
+        );
+    });
+}
+
+#[test]
+fn peeled_goal_map() {
+    let db = ChalkDatabase::with(
+        "
+            trait Bar { }
+            trait Same<X> { }
+            struct u32 { }
+            struct Vec<T> { }
+            impl Bar for u32 { }
+            impl<X> Same<X> for X { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|program| {
+        let (goal, map) = db
+            .parse_and_lower_goal(
+                "exists<T, U> { forall<V> { exists<W> { W: Same<Vec<V>>, T: Bar } } }",
+            )
+            .unwrap()
+            .into_peeled_goal_with_map();
+
+        // `W` appears first, `U` not at all.
+        assert_eq!(map.num_existentials(), 3);
+        assert_eq!(map.num_universals(), 1);
+        assert_eq!(map.canonical_var(0), Some(1));
+        assert_eq!(map.canonical_var(1), None);
+        assert_eq!(map.canonical_var(2), Some(0));
+
+        let placeholder = map.placeholder(0).unwrap();
+        assert_eq!(map.universal_of(placeholder), Some(0));
+
+        let solution = SolverChoice::default()
+            .into_solver()
+            .solve(program, &goal)
+            .unwrap();
+        let subst = match solution {
+            Solution::Unique(constrained) => constrained.value.subst,
+            _ => panic!("expected a unique solution, got {}", solution),
+        };
+        let values: Vec<_> = map
+            .existential_values(&subst)
+            .iter()
+            .map(|value| value.as_ref().map(|value| format!("{:?}", value)))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                Some("u32".to_string()),
+                None,
+                Some(format!("Vec<{:?}>", placeholder)),
+            ]
+        );
+    });
+}
+
+#[test]
+fn snapshot_rollback() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct u32 { }
+            struct Vec<T> { }
+            impl Foo for u32 { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|program| {
+        let goal = |text| db.parse_and_lower_goal(text).unwrap().into_closed_goal();
+        let mut solver = SolverChoice::default().into_solver().into_test();
+
+        let u32_goal = goal("u32: Foo");
+        assert!(solver.solve(program, &u32_goal).unwrap().is_unique());
+        let before = solver.num_tables();
+        let snapshot = solver.snapshot();
+
+        let vec_goal = goal("Vec<Vec<u32>>: Foo");
+        assert!(solver.solve(program, &vec_goal).unwrap().is_unique());
+        let after = solver.num_tables();
+        assert!(after > before);
+
+        solver.rollback_to_snapshot(snapshot);
+        assert_eq!(solver.num_tables(), before);
+
+        // The goal solved before the snapshot is still cached, and the
+        // one solved after it is solved afresh.
+        assert!(solver.solve(program, &u32_goal).unwrap().is_unique());
+        assert_eq!(solver.num_tables(), before);
+        assert!(solver.solve(program, &vec_goal).unwrap().is_unique());
+        assert_eq!(solver.num_tables(), after);
+    });
+}
+
+#[test]
+fn environment_arena_shares_clauses() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct u32 { }
+            struct i32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let environment = |text| match *db.parse_and_lower_goal(text).unwrap() {
+            Goal::Implies(clauses, _) => Environment::new().add_clauses(clauses),
+            goal => panic!("unexpected goal {:?}", goal),
+        };
+        let mut arena = EnvironmentArena::new();
+
+        let a = arena.intern(environment("if (u32: Foo; i32: Foo) { u32: Foo }"));
+        let b = arena.intern(environment("if (i32: Foo; u32: Foo) { i32: Foo }"));
+        let c = arena.intern(environment("if (u32: Foo) { u32: Foo }"));
+        assert!(a.ptr_eq(&b));
+        assert!(!a.ptr_eq(&c));
+        assert_eq!(arena.len(), 2);
+
+        // Folding leaves an environment without variables shared.
+        assert!(a.shifted_in(1).ptr_eq(&a));
+
+        arena.clear();
+        assert!(arena.is_empty());
+        let d = arena.intern(environment("if (u32: Foo; i32: Foo) { u32: Foo }"));
+        assert!(!a.ptr_eq(&d));
+        assert!(a == d && a != c);
+    });
+}
+
+#[test]
+fn tables_dot() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct u32 { }
+            struct Vec<T> { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+            impl Foo for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let goal = db
+            .parse_and_lower_goal("Vec<u32>: Foo")
+            .unwrap()
+            .into_closed_goal();
+        let mut solver = SolverChoice::default().into_solver().into_test();
+        assert!(solver.solve(&db, &goal).unwrap().is_unique());
+
+        let dot = solver.tables_dot();
+        assert!(dot.starts_with("digraph forest {\n"), "{}", dot);
+        assert!(dot.ends_with("}\n"), "{}", dot);
+        assert_eq!(
+            dot.lines().filter(|l| l.contains("[label=")).count(),
+            solver.num_tables()
+        );
+        assert!(dot.contains("Implemented(Vec<u32>: Foo)"), "{}", dot);
+    });
+}
+
+#[test]
+fn max_universes() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let goal = db
+            .parse_and_lower_goal(
+                "forall<A> { forall<B> { forall<C> { if (C: Foo) { C: Foo } } } }",
+            )
+            .unwrap()
+            .into_closed_goal();
+
+        let mut solver = SolverChoice::default().into_solver();
+        assert!(solver.solve(&db, &goal).unwrap().is_unique());
+        assert_eq!(solver.stats().max_universes, 4);
+
+        // With only three universes, the innermost `forall` cannot
+        // be instantiated.
+        let mut solver =
+            SolverChoice::slg_with(SlgOptions::default().max_universes(3)).into_solver();
+        assert_eq!(
+            solver.solve(&db, &goal),
+            Some(Solution::Ambig(Guidance::Unknown))
+        );
+        assert!(solver.stats().max_universes <= 3);
+    });
+}
+
+#[test]
+fn subgoal_stats() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Baz { }
+            trait Marker { }
+            trait Iterator { type Item; }
+            struct Bar { }
+            struct u32 { }
+            impl Foo for Bar where Bar: Baz, Bar: Baz { }
+            impl Baz for Bar { }
+            impl Marker for Bar { }
+            impl Iterator for Bar { type Item = u32; }
+            impl Iterator for u32 { type Item = u32; }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let mut solver = SolverChoice::default().into_solver();
+
+        // The where clause is proven once.
+        let goal = db
+            .parse_and_lower_goal("Bar: Foo")
+            .unwrap()
+            .into_closed_goal();
+        assert!(solver.solve(&db, &goal).unwrap().is_unique());
+        assert_eq!(solver.stats().duplicate_subgoals, 1);
+        assert_eq!(solver.stats().deferred_projections, 0);
+
+        // `T: Marker` is proven before the projection, which then
+        // need not enumerate the impls of `Iterator`.
+        let goal = db
+            .parse_and_lower_goal(
+                "exists<T, U> { Normalize(<T as Iterator>::Item -> U), T: Marker }",
+            )
+            .unwrap()
+            .into_closed_goal();
+        assert!(solver.solve(&db, &goal).unwrap().is_unique());
+        assert_eq!(solver.stats().deferred_projections, 1);
+    });
+}
+
+#[test]
+fn clause_sets_once_per_table() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            struct u32 { }
+            struct Vec<T> { }
+            impl<T> Foo for Vec<T> where T: Bar { }
+            impl<T> Bar for Vec<T> where T: Foo { }
+            impl Foo for u32 { }
+            impl Bar for u32 where u32: Foo { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let mut solver = SolverChoice::default().into_solver();
+
+        // The cycles between `Foo` and `Bar` are iterated without
+        // assembling the clauses of their tables again.
+        let goal = db
+            .parse_and_lower_goal("exists<T> { Vec<Vec<T>>: Foo }")
+            .unwrap()
+            .into_closed_goal();
+        solver.solve(&db, &goal);
+        let stats = solver.stats();
+        assert!(stats.clause_sets > 0);
+        assert!(stats.clause_sets <= stats.num_tables);
+    });
+}
+
+#[test]
+fn hottest_subgoals() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { }
+            struct Vec<T> { }
+            struct u32 { }
+            struct i32 { }
+            impl<T> Foo for Vec<T> where T: Bar { }
+            impl Bar for u32 { }
+            impl Bar for i32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let mut solver = SolverChoice::default().into_solver();
+        assert!(solver.stats().hottest_subgoals.is_empty());
+
+        let goal = db
+            .parse_and_lower_goal("exists<T> { Vec<T>: Foo }")
+            .unwrap()
+            .into_closed_goal();
+        solver.solve(&db, &goal);
+        let stats = solver.stats();
+        let subgoals: Vec<_> = stats
+            .hottest_subgoals
+            .iter()
+            .map(|subgoal| format!("{:?}", subgoal.goal.canonical.value.goal))
+            .collect();
+        assert!(subgoals.len() <= 10);
+        assert!(subgoals.contains(&"Implemented(Vec<^0>: Foo)".to_string()));
+        assert!(subgoals.contains(&"Implemented(^0: Bar)".to_string()));
+        assert!(stats
+            .hottest_subgoals
+            .windows(2)
+            .all(|w| w[0].time >= w[1].time));
+        assert!(stats
+            .hottest_subgoals
+            .iter()
+            .all(|subgoal| subgoal.activations > 0));
+
+        // At least one strand for each impl of `Bar`.
+        let bar = stats
+            .hottest_subgoals
+            .iter()
+            .find(|subgoal| {
+                format!("{:?}", subgoal.goal.canonical.value.goal) == "Implemented(^0: Bar)"
+            })
+            .unwrap();
+        assert!(bar.strands >= 2);
+
+        let rendered = stats.to_string();
+        assert!(rendered.contains("subgoal"));
+        assert!(rendered.contains("Implemented(^0: Bar)"));
+
+        // The profile is that of the last root goal only.
+        let goal = db
+            .parse_and_lower_goal("u32: Bar")
+            .unwrap()
+            .into_closed_goal();
+        solver.solve(&db, &goal);
+        let subgoals: Vec<_> = solver
+            .stats()
+            .hottest_subgoals
+            .iter()
+            .map(|subgoal| format!("{:?}", subgoal.goal.canonical.value.goal))
+            .collect();
+        assert!(subgoals.contains(&"Implemented(u32: Bar)".to_string()));
+        assert!(!subgoals.iter().any(|subgoal| subgoal.contains("Vec")));
+    });
+}
+
+#[test]
+fn unique_modulo_lifetimes() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Ref<'a> { }
+            struct u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let solve = |choice: SolverChoice, goal: &str| {
+            let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+            let solution = choice.into_solver().solve(&db, &goal);
+            solution.map(|s| s.to_string())
+        };
+        let merging = SolverChoice::slg_with(SlgOptions::default().unique_modulo_lifetimes(true));
+
+        // Two answers, `'c := 'a` and `'c := 'b`.
+        let goal =
+            "forall<'a, 'b> { if (Ref<'a>: Foo; Ref<'b>: Foo) { exists<'c> { Ref<'c>: Foo } } }";
+        assert_eq!(
+            solve(SolverChoice::default(), goal).unwrap(),
+            "Ambiguous; definite substitution for<?U1> { [?0 := '^0] }"
+        );
+        assert_eq!(
+            solve(merging, goal).unwrap(),
+            "Unique; substitution [?0 := '!1_0], \
+             lifetime constraints [InEnvironment { \
+             environment: Env([FromEnv(Ref<'!1_0>: Foo), FromEnv(Ref<'!1_1>: Foo)]), \
+             goal: '!1_0 == '!1_1 }]"
+        );
+
+        // The answers differ in a type, so they are not merged.
+        let goal = "forall<'a> { if (Ref<'a>: Foo; u32: Foo) { exists<T> { T: Foo } } }";
+        assert_eq!(
+            solve(merging, goal).unwrap(),
+            "Ambiguous; no inference guidance"
+        );
+    });
+}
+
+#[test]
+fn memory_report() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Vec<T> { }
+            struct u32 { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+            impl Foo for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|_| {
+        let goal = db
+            .parse_and_lower_goal("exists<T> { Vec<T>: Foo }")
+            .unwrap()
+            .into_closed_goal();
+
+        let mut solver = SolverChoice::default().into_solver();
+        assert!(solver.memory_report().tables.is_empty());
+        solver.solve(&db, &goal);
+
+        let report = solver.memory_report();
+        assert_eq!(report.tables.len(), solver.stats().num_tables);
+        assert!(report
+            .tables
+            .windows(2)
+            .all(|w| w[0].total_bytes() >= w[1].total_bytes()));
+        assert!(report.tables.iter().all(|table| table.goal_bytes > 0));
+        assert!(report
+            .tables
+            .iter()
+            .any(|table| table.num_answers > 0 && table.answer_bytes > 0));
+        assert_eq!(
+            report.total_bytes(),
+            report.tables.iter().map(|t| t.total_bytes()).sum::<usize>()
+        );
+    });
+}
+
+/// Each worker thread solves with a solver of its own, sharing the
+/// database.
+#[test]
+fn solve_on_worker_threads() {
+    fn solve_in_parallel<DB: SyncDatabase>(
+        db: &DB,
+        goals: &[UCanonical<InEnvironment<Goal<ChalkIr>>>],
+    ) -> Vec<Option<Solution>> {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = goals
+                .iter()
+                .map(|goal| {
+                    scope.spawn(move || SolverChoice::default().into_solver().solve(db, goal))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        })
+    }
+
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Vec<T> { }
+            struct u32 { }
+            struct i32 { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+            impl Foo for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    let program = db.program_ir().unwrap();
+    let goals: Vec<_> = [
+        "Vec<u32>: Foo",
+        "Vec<i32>: Foo",
+        "exists<T> { Vec<T>: Foo }",
+    ]
+    .iter()
+    .map(|text| db.parse_and_lower_goal(text).unwrap().into_closed_goal())
+    .collect();
+
+    let solutions = solve_in_parallel(&*program, &goals);
+    assert!(solutions[0].as_ref().is_some_and(|s| s.is_unique()));
+    assert!(solutions[1].is_none());
+    assert!(solutions[2].as_ref().is_some_and(|s| s.is_unique()));
+}
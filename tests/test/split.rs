@@ -0,0 +1,54 @@
+//! Tests for the parameter-splitting helpers in `chalk_solve::split`.
+
+use super::*;
+use chalk_ir::*;
+use chalk_solve::split::Split;
+use chalk_solve::RustIrDatabase;
+
+#[test]
+fn split_associated_ty_parameters() {
+    let db = ChalkDatabase::with(
+        "
+            trait Iterable<T> { type Iter<'a, U>; }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let trait_id = match program.type_ids[&chalk_ir::intern("Iterable")] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => panic!("`Iterable` is not a trait"),
+        };
+        let iter = program.trait_datum(trait_id).associated_ty_ids[0];
+        let associated_ty = program.associated_ty_data(iter);
+
+        // `['a, U, Self, T]`
+        let parameters = ["'a", "U", "Self", "T"];
+        let (trait_params, own_params) =
+            program.split_associated_ty_parameters(&parameters, &associated_ty);
+        assert_eq!(trait_params, ["Self", "T"]);
+        assert_eq!(own_params, ["'a", "U"]);
+    });
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "wrong number of parameters")]
+fn split_associated_ty_parameters_arity() {
+    let db = ChalkDatabase::with(
+        "
+            trait Iterable<T> { type Iter<'a>; }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let trait_id = match program.type_ids[&chalk_ir::intern("Iterable")] {
+            TypeKindId::TraitId(trait_id) => trait_id,
+            _ => panic!("`Iterable` is not a trait"),
+        };
+        let iter = program.trait_datum(trait_id).associated_ty_ids[0];
+        let associated_ty = program.associated_ty_data(iter);
+        program.split_associated_ty_parameters(&["Self", "T"], &associated_ty);
+    });
+}
@@ -0,0 +1,151 @@
+//! Tests for `ext::stable_hash_goal`.
+
+use super::*;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::stable_hash::{StableHash, StableHasher};
+use chalk_ir::*;
+use chalk_solve::ext::stable_hash_goal;
+
+const PROGRAM: &str = "
+    trait Clone { }
+    trait Iterator { type Item; }
+    struct Foo { }
+    struct Vec<T> { }
+";
+
+fn hash(db: &ChalkDatabase, goal: &str) -> u64 {
+    db.with_program(|_| {
+        let goal = db.parse_and_lower_goal(goal).unwrap().into_closed_goal();
+        stable_hash_goal(&goal)
+    })
+}
+
+#[test]
+fn stable_hash_goal_is_structural() {
+    let db1 = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let db2 = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+
+    let goals = [
+        "Vec<Foo>: Clone",
+        "forall<T> { if (T: Clone) { Vec<T>: Clone } }",
+        "exists<T> { <Vec<T> as Iterator>::Item = Foo }",
+        "not { Foo: Clone }",
+    ];
+    for goal in &goals {
+        assert_eq!(hash(&db1, goal), hash(&db2, goal), "{}", goal);
+    }
+    for (i, a) in goals.iter().enumerate() {
+        for b in &goals[i + 1..] {
+            assert_ne!(hash(&db1, a), hash(&db1, b), "{} vs {}", a, b);
+        }
+    }
+
+    // Pin down the hash of a simple goal, so that changes to the
+    // format (which would invalidate persisted caches) are noticed.
+    assert_eq!(hash(&db1, "Foo: Clone"), 0xf680_e114_d983_dd20);
+}
+
+#[test]
+fn stable_hash_identifiers() {
+    // Identifiers are hashed by their text, whatever their index in
+    // the interner.
+    let mut hasher = StableHasher::new();
+    hasher.write_str("ConstEvaluatable");
+    assert_eq!(
+        chalk_ir::intern("ConstEvaluatable").stable_hash_value(),
+        hasher.finish()
+    );
+
+    let custom_goal = |name: &str| -> Goal<ChalkIr> {
+        DomainGoal::Custom(CustomGoal {
+            name: chalk_ir::intern(name),
+            parameters: vec![],
+        })
+        .cast()
+    };
+    assert_ne!(
+        custom_goal("ConstEvaluatable").stable_hash_value(),
+        custom_goal("WellFormedConst").stable_hash_value()
+    );
+}
+
+#[test]
+fn solutions_order_and_hash_structurally() {
+    use chalk_solve::Solution;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
+    use std::collections::BTreeMap;
+    use std::hash::{Hash, Hasher};
+
+    let program = "
+        trait Clone { }
+        struct Foo { }
+        struct Bar { }
+        struct Vec<T> { }
+        impl Clone for Foo { }
+        impl Clone for Bar { }
+        impl<T> Clone for Vec<T> where T: Clone { }
+    ";
+    let goals = [
+        "Foo: Clone",
+        "exists<T> { T: Clone }",
+        "exists<T> { Vec<T> = Vec<Foo> }",
+        "exists<T> { Vec<T>: Clone }",
+        "Vec<Foo>: Clone",
+        "exists<T> { Vec<T> = Vec<Bar> }",
+    ];
+    let solve_all = |db: &ChalkDatabase| -> Vec<(UCanonical<_>, Solution)> {
+        db.with_program(|_| {
+            goals
+                .iter()
+                .map(|goal| {
+                    let goal = db.parse_and_lower_goal(goal).unwrap().into_closed_goal();
+                    let solution = db.solve(&goal).unwrap();
+                    (goal, solution)
+                })
+                .collect()
+        })
+    };
+    let std_hash = |value: &dyn Fn(&mut DefaultHasher)| {
+        let mut hasher = DefaultHasher::new();
+        value(&mut hasher);
+        hasher.finish()
+    };
+
+    let results1 = solve_all(&ChalkDatabase::with(program, SolverChoice::default()));
+    let results2 = solve_all(&ChalkDatabase::with(program, SolverChoice::default()));
+
+    // Solutions from separate databases compare (and hash) equal.
+    for ((goal1, solution1), (goal2, solution2)) in results1.iter().zip(&results2) {
+        assert_eq!(goal1.cmp(goal2), Ordering::Equal);
+        assert_eq!(solution1.cmp(solution2), Ordering::Equal);
+        assert_eq!(solution1.stable_hash_value(), solution2.stable_hash_value());
+        assert_eq!(
+            std_hash(&|h| solution1.hash(h)),
+            std_hash(&|h| solution2.hash(h))
+        );
+    }
+
+    // The ordering is consistent with equality, and total.
+    let solutions: Vec<&Solution> = results1.iter().map(|(_, s)| s).collect();
+    for a in &solutions {
+        for b in &solutions {
+            assert_eq!(a.cmp(b) == Ordering::Equal, a == b, "{} vs {}", a, b);
+            assert_eq!(a.cmp(b), b.cmp(a).reverse());
+            if a != b {
+                assert_ne!(a.stable_hash_value(), b.stable_hash_value());
+            }
+        }
+    }
+
+    // And so the solutions can key ordered maps. `Foo: Clone` and
+    // `Vec<Foo>: Clone` have the same solution (with no substitution),
+    // so there are fewer keys than goals.
+    let map: BTreeMap<&Solution, usize> = solutions.iter().map(|&s| (s, 0)).collect();
+    let mut distinct = solutions.clone();
+    distinct.sort();
+    distinct.dedup();
+    assert_eq!(map.len(), distinct.len());
+    assert!(map.len() < solutions.len());
+}
@@ -0,0 +1,95 @@
+//! Tests for `Solver::watch_stack`, which lets other threads see the
+//! subgoals a solver is working on.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::{RustIrDatabase, StackWatch};
+use std::cell::RefCell;
+use std::thread;
+
+type Frames = Vec<UCanonical<InEnvironment<Goal<ChalkIr>>>>;
+
+/// Wraps a program, reading `watch` from another thread each time the
+/// solver asks for the impls of a trait, and keeping the deepest stack
+/// seen.
+#[derive(Debug)]
+struct WatchedDb<'p> {
+    program: &'p Program,
+    watch: StackWatch<UCanonical<InEnvironment<Goal<ChalkIr>>>>,
+    frames: usize,
+    deepest: RefCell<(usize, Frames)>,
+}
+
+impl DbHooks for WatchedDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn impls_for_trait(&self, trait_id: TraitId, parameters: &[Parameter<ChalkIr>]) -> Vec<ImplId> {
+        let watch = self.watch.clone();
+        let frames = self.frames;
+        let (depth, snapshot) = thread::spawn(move || (watch.depth(), watch.snapshot(frames)))
+            .join()
+            .unwrap();
+        let mut deepest = self.deepest.borrow_mut();
+        if depth > deepest.0 {
+            *deepest = (depth, snapshot);
+        }
+        self.program.impls_for_trait(trait_id, parameters)
+    }
+}
+
+#[test]
+fn watch_stack_from_another_thread() {
+    let db = ChalkDatabase::with(
+        "
+            trait Foo { }
+            struct Vec<T> { }
+            struct u32 { }
+            impl<T> Foo for Vec<T> where T: Foo { }
+            impl Foo for u32 { }
+        ",
+        SolverChoice::default(),
+    );
+    db.with_program(|program| {
+        let mut solver = SolverChoice::default().into_solver();
+        let watch = solver.watch_stack();
+        let watched = WatchedDb {
+            program,
+            watch: watch.clone(),
+            frames: 2,
+            deepest: RefCell::new((0, vec![])),
+        };
+
+        let goal = db
+            .parse_and_lower_goal("Vec<Vec<Vec<u32>>>: Foo")
+            .unwrap()
+            .into_closed_goal();
+        assert!(solver
+            .solve(&ForwardingDb(&watched), &goal)
+            .unwrap()
+            .is_unique());
+
+        // The impls for `u32: Foo` were asked for while its three
+        // parents were on the stack; the snapshot has the innermost
+        // two.
+        let (depth, snapshot) = watched.deepest.into_inner();
+        assert_eq!(depth, 3);
+        let snapshot: Vec<_> = snapshot
+            .iter()
+            .map(|goal| format!("{:?}", goal.canonical.value.goal))
+            .collect();
+        assert_eq!(
+            snapshot,
+            vec![
+                "Implemented(Vec<u32>: Foo)",
+                "Implemented(Vec<Vec<u32>>: Foo)"
+            ]
+        );
+
+        assert_eq!(watch.depth(), 0);
+        assert!(watch.snapshot(10).is_empty());
+    });
+}
@@ -0,0 +1,81 @@
+//! Tests for `Solver::suggest_impls`.
+
+use super::*;
+use chalk_solve::SlgOptions;
+
+const PROGRAM: &str = "
+    trait Clone { }
+    trait Debug { }
+    #[upstream] trait Display { }
+    #[upstream] struct String { }
+    #[upstream] struct Vec<T> { }
+    struct Foo { }
+    struct Bar { }
+
+    impl<T> Clone for Vec<T> where T: Clone { }
+    impl Clone for Bar { }
+";
+
+/// The impls suggested for the goal of `text`, as strings, with the
+/// number of impls that require each.
+fn suggestions(text: &str) -> Vec<String> {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(text).unwrap().into_peeled_goal();
+    let mut solver =
+        SolverChoice::slg_with(SlgOptions::default().max_failed_leaves(10)).into_solver();
+    db.with_program(|program| {
+        solver
+            .suggest_impls(program, &goal)
+            .unwrap()
+            .iter()
+            .map(|suggestion| {
+                format!(
+                    "{:?} ({:?}, required by {})",
+                    suggestion.trait_ref,
+                    suggestion.locality,
+                    suggestion.required_by.len()
+                )
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn no_suggestions_for_goals_that_hold() {
+    assert!(suggestions("Bar: Clone").is_empty());
+    assert!(suggestions("Vec<Bar>: Clone").is_empty());
+}
+
+#[test]
+fn leaf_trait_goals() {
+    assert_eq!(
+        suggestions("Foo: Clone"),
+        vec!["Foo as Clone (LocalTraitAndType, required by 0)"]
+    );
+
+    // Ranked by locality; the impl for `String` is not allowed at all.
+    assert_eq!(
+        suggestions("String: Debug, Foo: Display, String: Display, Foo: Debug"),
+        vec![
+            "Foo as Debug (LocalTraitAndType, required by 0)",
+            "Foo as Display (LocalType, required by 0)",
+            "String as Debug (LocalTrait, required by 0)",
+        ]
+    );
+}
+
+#[test]
+fn conditions_of_impls() {
+    // Another impl for `Vec<Foo>` would overlap the one for `Vec<T>`,
+    // whose condition is what fails.
+    assert_eq!(
+        suggestions("Vec<Vec<Foo>>: Clone"),
+        vec!["Foo as Clone (LocalTraitAndType, required by 2)"]
+    );
+}
+
+#[test]
+fn no_suggestions_for_variables() {
+    assert!(suggestions("exists<T> { T: Debug }").is_empty());
+    assert!(suggestions("forall<T> { T: Debug }").is_empty());
+}
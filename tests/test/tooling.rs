@@ -0,0 +1,90 @@
+//! Tests for `Solver::solve_preferring_impls`.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::*;
+use chalk_solve::{RustIrDatabase, ToolingSolution};
+
+/// Wraps a program, preferring the impl for `Self = Foo` (if any).
+#[derive(Debug)]
+struct PreferFooDb<'p> {
+    program: &'p Program,
+}
+
+impl PreferFooDb<'_> {
+    fn is_impl_for_foo(&self, impl_id: ImplId) -> bool {
+        let datum = self.program.impl_datum(impl_id);
+        match datum.binders.value.trait_ref.parameters[0].assert_ty_ref() {
+            Ty::Apply(apply) => match apply.name {
+                TypeName::TypeKindId(id) => self.program.type_name(id).to_string() == "Foo",
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl DbHooks for PreferFooDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn prefer_impl(&self, candidates: &[ImplId]) -> Option<ImplId> {
+        candidates
+            .iter()
+            .cloned()
+            .find(|&impl_id| self.is_impl_for_foo(impl_id))
+    }
+}
+
+#[test]
+fn prefer_impl() {
+    let db = ChalkDatabase::with(
+        "
+            trait Clone { }
+            trait Iterator { }
+            struct Foo { }
+            struct Bar { }
+            struct Baz { }
+            impl Clone for Foo { }
+            impl Clone for Bar { }
+            impl Iterator for Bar { }
+            impl Iterator for Baz { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let solve = |program: &dyn RustIrDatabase, goal: &str| {
+            let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+            SolverChoice::default()
+                .into_solver()
+                .solve_preferring_impls(program, &goal)
+        };
+        let prefer_foo = ForwardingDb(&PreferFooDb { program });
+
+        // Ambiguous between the impls for `Foo` and `Bar`: the impl for
+        // `Foo` is preferred.
+        let solution = solve(&prefer_foo, "exists<T> { T: Clone }").unwrap();
+        assert!(solution.is_heuristic());
+        assert_eq!(
+            solution.solution().to_string(),
+            "Unique; substitution [?0 := Foo], lifetime constraints []"
+        );
+
+        // The database prefers neither of the impls for `Bar` and `Baz`.
+        let solution = solve(&prefer_foo, "exists<T> { T: Iterator }").unwrap();
+        assert!(!solution.is_heuristic());
+        assert!(!solution.solution().is_unique());
+
+        // Without preferences (the default), nothing changes.
+        let solution = solve(program, "exists<T> { T: Clone }").unwrap();
+        assert!(!solution.is_heuristic());
+
+        // Goals that are not ambiguous are solved as usual.
+        assert!(matches!(
+            solve(&prefer_foo, "Foo: Clone"),
+            Some(ToolingSolution::Exact(Solution::Unique(_)))
+        ));
+    });
+}
@@ -282,3 +282,66 @@ fn quantified_types() {
         }
     }
 }
+
+/// Constant expressions are not evaluated: identical ones are equal,
+/// and any others may or may not be.
+#[test]
+fn opaque_consts() {
+    test! {
+        program {
+            struct Array<T, N> { }
+            struct Foo { }
+            struct Bar { }
+
+            trait Eq<T> { }
+            impl<T> Eq<T> for T { }
+        }
+
+        goal {
+            forall<T> {
+                Array<T, const Double<T>>: Eq<Array<T, const Double<T>>>
+            }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<T> {
+                Array<T, const Double<T>>: Eq<Array<T, const Twice<T>>>
+            }
+        } yields {
+            "Ambiguous; no inference guidance"
+        }
+
+        goal {
+            forall<T, U> {
+                Array<T, const Double<T>>: Eq<Array<T, const Double<U>>>
+            }
+        } yields {
+            "Ambiguous; no inference guidance"
+        }
+
+        // The parts that are not constants still have to unify.
+        goal {
+            Array<Foo, const Double<Foo>>: Eq<Array<Bar, const Twice<Bar>>>
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            exists<N> {
+                Array<Foo, const Double<Foo>>: Eq<Array<Foo, N>>
+            }
+        } yields {
+            "Unique; substitution [?0 := const Double<Foo>]"
+        }
+
+        goal {
+            exists<T> {
+                Array<T, const Double<T>>: Eq<Array<Foo, const Twice<Foo>>>
+            }
+        } yields {
+            "Ambiguous; definite substitution [?0 := Foo]"
+        }
+    }
+}
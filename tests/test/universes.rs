@@ -0,0 +1,86 @@
+//! Tests for `Solution::universes` and `ext::check_universes`.
+
+use super::*;
+use chalk_ir::cast::Cast;
+use chalk_ir::family::ChalkIr;
+use chalk_ir::*;
+use chalk_solve::ext::{check_universes, UniverseViolation};
+use chalk_solve::Solution;
+
+const PROGRAM: &str = "
+    trait Eq<T> { }
+    trait Foo { }
+    struct Vec<T> { }
+    struct u32 { }
+    impl<T> Eq<T> for T { }
+    impl Foo for u32 { }
+    impl Foo for Vec<u32> { }
+";
+
+fn solve(goal: &str) -> Solution {
+    let db = ChalkDatabase::with(PROGRAM, SolverChoice::default());
+    let goal = db.parse_and_lower_goal(goal).unwrap().into_peeled_goal();
+    db.with_program(|program| {
+        SolverChoice::default()
+            .into_solver()
+            .solve(program, &goal)
+            .unwrap()
+    })
+}
+
+fn placeholder(ui: usize) -> Parameter<ChalkIr> {
+    PlaceholderIndex {
+        ui: UniverseIndex { counter: ui },
+        idx: 0,
+    }
+    .to_ty::<ChalkIr>()
+    .cast()
+}
+
+fn u(counter: usize) -> UniverseIndex {
+    UniverseIndex { counter }
+}
+
+#[test]
+fn placeholders_in_solution() {
+    let solution = solve("forall<T> { exists<U> { U: Eq<T> } }");
+    let universes = solution.universes().unwrap();
+    assert_eq!(universes.binders, vec![]);
+    assert_eq!(universes.placeholders, vec![u(1)]);
+    assert_eq!(universes.max_universe(), u(1));
+}
+
+#[test]
+fn variables_in_solution() {
+    let solution = solve("forall<T> { exists<U, V, W> { W: Eq<T>, Vec<U>: Eq<Vec<V>> } }");
+    let universes = solution.universes().unwrap();
+    // `?0 := !1_0, ?1 := ^0, ?2 := ^0`, where `^0` is in the root
+    // universe.
+    assert_eq!(universes.binders, vec![u(0)]);
+    assert_eq!(universes.placeholders, vec![u(1)]);
+
+    // The variable may be replayed as any type that its universe can
+    // name, but not as a placeholder of a later universe.
+    let subst = |value: Parameter<ChalkIr>| Substitution {
+        parameters: vec![value],
+    };
+    let unit: Ty<ChalkIr> = Ty::Apply(ApplicationTy {
+        name: TypeName::Tuple(0),
+        parameters: vec![],
+    });
+    assert_eq!(check_universes(&universes, &subst(unit.cast())), Ok(()));
+    assert_eq!(
+        check_universes(&universes, &subst(placeholder(1))),
+        Err(UniverseViolation {
+            var: 0,
+            var_universe: u(0),
+            placeholder_universe: u(1),
+        })
+    );
+}
+
+#[test]
+fn no_guidance() {
+    let solution = solve("exists<T> { T: Foo }");
+    assert_eq!(solution.universes(), None);
+}
@@ -0,0 +1,187 @@
+//! Tests for `Upcast(dyn A => dyn B)` goals.
+
+use super::*;
+use chalk_integration::program::Program;
+use chalk_ir::*;
+use chalk_solve::RustIrDatabase;
+
+#[test]
+fn upcast_to_supertraits() {
+    test! {
+        program {
+            trait Base { }
+            trait Middle<T> where Self: Base { }
+            trait Foo where Self: Middle<u32> { }
+            trait Other { }
+            struct u32 { }
+            struct i32 { }
+        }
+
+        goal {
+            Upcast(dyn Foo => dyn Foo)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Upcast(dyn Foo => dyn Middle<u32>)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Upcast(dyn Foo => dyn Base)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Upcast(dyn Foo => dyn Middle<i32>)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            exists<T> { Upcast(dyn Foo => dyn Middle<T>) }
+        } yields {
+            "Unique; substitution [?0 := u32]"
+        }
+
+        // Downcasting is not upcasting.
+        goal {
+            Upcast(dyn Base => dyn Foo)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Upcast(dyn Foo => dyn Other)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Upcast(u32 => dyn Foo)
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn upcast_preserves_auto_traits() {
+    test! {
+        program {
+            #[auto] trait Send { }
+            #[auto] trait Sync { }
+            trait Base { }
+            trait Foo where Self: Base { }
+        }
+
+        goal {
+            Upcast(dyn Foo + Send => dyn Base + Send)
+        } yields {
+            "Unique"
+        }
+
+        // Auto traits may be dropped...
+        goal {
+            Upcast(dyn Foo + Send => dyn Base)
+        } yields {
+            "Unique"
+        }
+
+        // ...but not added.
+        goal {
+            Upcast(dyn Foo => dyn Base + Send)
+        } yields {
+            "No possible solution"
+        }
+
+        goal {
+            Upcast(dyn Foo + Send => dyn Base + Sync)
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+#[test]
+fn upcast_lifetimes_and_projections() {
+    test! {
+        program {
+            trait Base<'a> { }
+            trait Iterator { type Item; }
+            trait Foo<'a> where Self: Base<'a> { }
+            struct u32 { }
+            struct i32 { }
+        }
+
+        goal {
+            forall<'a> { Upcast(dyn Foo<'a> => dyn Base<'a>) }
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            forall<'a, 'b> { Upcast(dyn Foo<'a> => dyn Base<'b>) }
+        } yields {
+            "Unique; substitution [], lifetime constraints [InEnvironment { environment: Env([FromEnv(!2_0: Foo<'!1_0>)]), goal: '!1_1 == '!1_0 }]"
+        }
+
+        goal {
+            Upcast(dyn Iterator<Item = u32> => dyn Iterator<Item = u32>)
+        } yields {
+            "Unique"
+        }
+
+        goal {
+            Upcast(dyn Iterator<Item = u32> => dyn Iterator<Item = i32>)
+        } yields {
+            "No possible solution"
+        }
+    }
+}
+
+/// Wraps a program, rejecting upcasts from `Foo` to `Base`.
+#[derive(Debug)]
+struct NoUpcastDb<'p> {
+    program: &'p Program,
+}
+
+impl DbHooks for NoUpcastDb<'_> {
+    fn program(&self) -> &dyn RustIrDatabase {
+        self.program
+    }
+
+    fn upcast_vtable_compatible(&self, source: TraitId, target: TraitId) -> bool {
+        let name = |id: TraitId| self.program.type_name(id.into()).to_string();
+        !(name(source) == "Foo" && name(target) == "Base")
+    }
+}
+
+#[test]
+fn upcast_vtable_compatibility_hook() {
+    let db = ChalkDatabase::with(
+        "
+            trait Base { }
+            trait Middle where Self: Base { }
+            trait Foo where Self: Middle { }
+        ",
+        SolverChoice::default(),
+    );
+
+    db.with_program(|program| {
+        let solve = |goal: &str| {
+            let goal = db.parse_and_lower_goal(goal).unwrap().into_closed_goal();
+            SolverChoice::default()
+                .into_solver()
+                .solve(&ForwardingDb(&NoUpcastDb { program }), &goal)
+        };
+
+        assert!(solve("Upcast(dyn Foo => dyn Middle)").is_some());
+        assert!(solve("Upcast(dyn Middle => dyn Base)").is_some());
+        assert!(solve("Upcast(dyn Foo => dyn Foo)").is_some());
+        assert_eq!(solve("Upcast(dyn Foo => dyn Base)"), None);
+    });
+}
@@ -0,0 +1,131 @@
+//! Tests for `chalk_solve::variance`.
+
+use super::*;
+use chalk_ir::{StructId, TypeKindId};
+use chalk_solve::variance::Variance::*;
+use chalk_solve::variance::{struct_variances, Variance};
+
+/// Computes the variances of the structs `names` of `program_text`.
+/// (Variance does not depend on well-formedness, so the program is not
+/// checked.)
+fn variances(program_text: &str, names: &[&str]) -> Vec<Vec<Variance>> {
+    let db = ChalkDatabase::with(program_text, SolverChoice::default());
+    let program = db.program_ir().unwrap();
+    let ids: Vec<StructId> = names
+        .iter()
+        .map(|name| match program.type_ids[&chalk_ir::intern(name)] {
+            TypeKindId::StructId(id) => id,
+            _ => panic!("`{}` is not a struct", name),
+        })
+        .collect();
+    let variances = struct_variances(&*program, ids.iter().cloned());
+    ids.iter().map(|id| variances[id].clone()).collect()
+}
+
+#[test]
+fn field_positions() {
+    let program = "
+        struct u32 { }
+        trait Iterator { type Item; }
+        trait Foo<T> { }
+        struct Co<T> { t: T }
+        struct Contra<T> { f: fn(T) }
+        struct Inv<T> { t: T, f: fn(T) }
+        struct Unused<T> { }
+        struct Pair<A, B> { pair: (A, u32, B) }
+        struct Ret<T> { f: fn(u32) -> T }
+        struct Dyn<T> { d: dyn Foo<T> }
+        struct Proj<T> { p: <T as Iterator>::Item }
+    ";
+    assert_eq!(
+        variances(
+            program,
+            &["Co", "Contra", "Inv", "Unused", "Pair", "Ret", "Dyn", "Proj"]
+        ),
+        vec![
+            vec![Covariant],
+            vec![Contravariant],
+            vec![Invariant],
+            vec![Bivariant],
+            vec![Covariant, Covariant],
+            vec![Covariant],
+            vec![Invariant],
+            vec![Invariant],
+        ]
+    );
+}
+
+#[test]
+fn through_other_structs() {
+    let program = "
+        trait Foo<'a> { }
+        struct Box<T> { t: T }
+        struct Sink<T> { f: fn(T) }
+        struct Unused<T> { }
+        struct Boxed<T> { b: Box<T> }
+        struct DoubleContra<T> { s: Sink<Sink<T>> }
+        struct IgnoredByUnused<T> { u: Unused<fn(T)> }
+        struct Lifetimes<'a, 'b> { d: dyn Foo<'a>, u: Unused<dyn Foo<'b>> }
+    ";
+    assert_eq!(
+        variances(
+            program,
+            &["Boxed", "DoubleContra", "IgnoredByUnused", "Lifetimes"]
+        ),
+        vec![
+            vec![Covariant],
+            vec![Covariant],
+            vec![Bivariant],
+            vec![Invariant, Bivariant],
+        ]
+    );
+}
+
+/// The variances of recursive structs are found as a fixed point.
+#[test]
+fn recursive_structs() {
+    let program = "
+        struct Sink<T> { f: fn(T) }
+        struct List<T> { head: T, tail: List<T> }
+        struct OnlyRecursive<T> { r: OnlyRecursive<T> }
+        struct Even<T> { odd: Odd<T> }
+        struct Odd<T> { even: Even<T>, sink: Sink<T> }
+    ";
+    assert_eq!(
+        variances(program, &["List", "OnlyRecursive", "Even", "Odd"]),
+        vec![
+            vec![Covariant],
+            vec![Bivariant],
+            vec![Contravariant],
+            vec![Contravariant],
+        ]
+    );
+}
+
+#[test]
+fn references_and_pointers() {
+    let program = "
+        struct Ref<'a, T> { r: &'a T }
+        struct RefMut<'a, T> { r: &'a mut T }
+        struct Ptr<T> { p: *const T }
+        struct PtrMut<T> { p: *mut T }
+    ";
+    assert_eq!(
+        variances(program, &["Ref", "RefMut", "Ptr", "PtrMut"]),
+        vec![
+            vec![Covariant, Covariant],
+            vec![Covariant, Invariant],
+            vec![Covariant],
+            vec![Invariant],
+        ]
+    );
+}
+
+#[test]
+fn combinators() {
+    assert_eq!(Contravariant.xform(Contravariant), Covariant);
+    assert_eq!(Invariant.xform(Bivariant), Invariant);
+    assert_eq!(Bivariant.xform(Invariant), Bivariant);
+    assert_eq!(Covariant.glb(Contravariant), Invariant);
+    assert_eq!(Bivariant.glb(Contravariant), Contravariant);
+}
@@ -269,7 +269,7 @@ fn bound_in_header_from_env() {
                 type Item = T;
             }
         } error_msg {
-            "trait impl for \"Bar\" does not meet well-formedness requirements"
+            "associated type \"Item\" in impl of trait \"Bar\" does not satisfy the bound `forall<T> Implemented(T: Baz)`"
         }
     }
 }
@@ -307,7 +307,7 @@ fn mixed_indices_check_projection_bounds() {
                 type Item = U;
             }
         } error_msg {
-            "trait impl for \"Bar\" does not meet well-formedness requirements"
+            "associated type \"Item\" in impl of trait \"Bar\" does not satisfy the bound `forall<T, U> Implemented(U: Baz<T>)`"
         }
     }
 }
@@ -353,7 +353,7 @@ fn mixed_indices_check_generic_projection_bounds() {
                 type Item<V> = Fooey<U, V>;
             }
         } error_msg {
-            "trait impl for \"Bar\" does not meet well-formedness requirements"
+            "associated type \"Item\" in impl of trait \"Bar\" does not satisfy the bound `forall<T, U, V> Implemented(Fooey<V, T>: Baz<U>)`"
         }
     }
 }
@@ -431,7 +431,7 @@ fn generic_projection_bound() {
             // No impl Clone for Cow<T>, so this will fail.
             impl PointerFamily for CowFamily { type Pointer<T> = Cow<T>; }
         } error_msg {
-            "trait impl for \"PointerFamily\" does not meet well-formedness requirements"
+            "associated type \"Pointer\" in impl of trait \"PointerFamily\" does not satisfy the bound `forall<T> Implemented(Cow<T>: Clone)`"
         }
     }
 }
@@ -557,7 +557,7 @@ fn higher_ranked_inline_bound_on_gat() {
                 type Item<T> = fn<T, i32>;
             }
         } error_msg {
-            "trait impl for \"Bar\" does not meet well-formedness requirements"
+            "associated type \"Item\" in impl of trait \"Bar\" does not satisfy the bound `forall<T> forall<U> Implemented(fn<T, i32>: Fn<T, U>)`"
         }
     }
 }
@@ -636,3 +636,374 @@ fn assoc_type_recursive_bound() {
         }
     }
 }
+
+#[test]
+fn impl_missing_associated_ty() {
+    lowering_error! {
+        program {
+            trait Foo {
+                type Item;
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff { }
+        } error_msg {
+            "impl of trait \"Foo\" is missing associated type \"Item\""
+        }
+    }
+}
+
+#[test]
+fn impl_associated_ty_parameters() {
+    lowering_success! {
+        program {
+            trait Foo {
+                type Item<'a, T>;
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                type Item<'b, U> = U;
+            }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                type Item<T>;
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                type Item = Stuff;
+            }
+        } error_msg {
+            "associated type \"Item\" in impl of trait \"Foo\" has parameters [], \
+             but the trait declares [type]"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                type Item<'a>;
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                type Item<T> = T;
+            }
+        } error_msg {
+            "associated type \"Item\" in impl of trait \"Foo\" has parameters [type], \
+             but the trait declares [lifetime]"
+        }
+    }
+}
+
+#[test]
+fn impl_extra_associated_ty() {
+    lowering_error! {
+        program {
+            trait Foo { }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                type Item = Stuff;
+            }
+        } error_msg {
+            "no associated type `Item` defined in trait"
+        }
+    }
+}
+
+/// Lowers `program_text`, then marks the trait `name` as an auto trait
+/// (lowering itself rejects most ill-formed auto traits) and verifies
+/// it.
+fn verify_as_auto_trait(program_text: &str, name: &str) -> Result<(), chalk_solve::wf::WfError> {
+    use chalk_ir::TypeKindId;
+    use std::sync::Arc;
+
+    let db = chalk_integration::db::ChalkDatabase::with(
+        program_text,
+        chalk_solve::SolverChoice::default(),
+    );
+    let mut program = (*db.program_ir().unwrap()).clone();
+    let trait_id = match program.type_ids[&chalk_ir::intern(name)] {
+        TypeKindId::TraitId(trait_id) => trait_id,
+        _ => panic!("`{}` is not a trait", name),
+    };
+    let mut trait_datum = (*program.trait_data[&trait_id]).clone();
+    trait_datum.flags.auto = true;
+    program.trait_data.insert(trait_id, Arc::new(trait_datum));
+
+    chalk_solve::wf::WfSolver::new(&program, chalk_solve::SolverChoice::default())
+        .verify_auto_trait(trait_id)
+}
+
+#[test]
+fn ill_formed_auto_trait() {
+    use chalk_solve::wf::{AutoTraitViolation, WfError};
+
+    let result = verify_as_auto_trait(
+        "trait Bar { } trait Foo<T> where Self: Bar { type Item; }",
+        "Foo",
+    );
+    match result {
+        Err(WfError::IllFormedAutoTrait(_, violations)) => {
+            assert_eq!(violations.len(), 3);
+            assert_eq!(violations[0], AutoTraitViolation::Parameters);
+            assert_eq!(violations[1], AutoTraitViolation::AssociatedTypes);
+            match &violations[2] {
+                AutoTraitViolation::WhereClause(_) => {}
+                violation => panic!("unexpected violation {:?}", violation),
+            }
+        }
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn auto_trait_with_auto_supertrait() {
+    let result = verify_as_auto_trait(
+        "#[auto] trait Send { } trait Sync where Self: Send { }",
+        "Sync",
+    );
+    assert!(result.is_ok());
+}
+
+/// The default of an associated type must meet its bounds in each impl
+/// that uses it.
+#[test]
+fn assoc_ty_default_bounds() {
+    lowering_success! {
+        program {
+            trait Clone { }
+            struct u32 { }
+            struct Foo { }
+            impl Clone for u32 { }
+
+            trait Bar {
+                type Item: Clone = Self;
+            }
+
+            impl Bar for u32 { }
+
+            impl Bar for Foo {
+                type Item = u32;
+            }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Clone { }
+            struct u32 { }
+            struct Foo { }
+            impl Clone for u32 { }
+
+            trait Bar {
+                type Item: Clone = Self;
+            }
+
+            impl Bar for u32 { }
+            impl Bar for Foo { }
+        } error_msg {
+            "trait impl for \"Bar\" does not meet well-formedness requirements"
+        }
+    }
+}
+
+#[test]
+fn impl_method_conformance() {
+    lowering_success! {
+        program {
+            trait Clone { }
+            trait Foo<T> {
+                fn bar<U>(Self, U) -> T where U: Clone;
+                fn baz(Self);
+            }
+
+            struct Stuff { }
+            struct Box<T> { }
+
+            impl<T> Foo<T> for Box<T> {
+                fn bar<V>(Box<T>, V) -> T where V: Clone;
+            }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                fn bar(Self);
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                fn baz(Stuff);
+            }
+        } error_msg {
+            "method \"baz\" is not a member of trait \"Foo\""
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                fn bar<T>(Self, T);
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                fn bar<'a>(Stuff);
+            }
+        } error_msg {
+            "method \"bar\" in impl of trait \"Foo\" has parameters [lifetime], \
+             but the trait declares [type]"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                fn bar(Self) -> Self;
+            }
+
+            struct Stuff { }
+            struct Other { }
+
+            impl Foo for Stuff {
+                fn bar(Stuff) -> Other;
+            }
+        } error_msg {
+            "method \"bar\" in impl of trait \"Foo\" has a signature incompatible with the trait"
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Clone { }
+            trait Debug { }
+            trait Foo {
+                fn bar<U>(Self, U) where U: Clone;
+            }
+
+            struct Stuff { }
+
+            impl Foo for Stuff {
+                fn bar<U>(Stuff, U) where U: Clone, U: Debug;
+            }
+        } error_msg {
+            "method \"bar\" in impl of trait \"Foo\" has the where clause \
+             `forall<T> Implemented(T: Debug)`, which the trait does not require"
+        }
+    }
+}
+
+#[test]
+fn object_safety() {
+    lowering_success! {
+        program {
+            #[lang(sized)] trait Sized { }
+            trait Foo {
+                fn bar(Self);
+                fn baz<T>(Self, T) where Self: Sized;
+            }
+            trait Baz { }
+
+            impl Baz for dyn Foo { }
+        }
+    }
+
+    lowering_error! {
+        program {
+            trait Foo {
+                fn bar<T>(Self, T);
+            }
+
+            struct Stuff { field: dyn Foo }
+        } error_msg {
+            "trait \"Foo\" cannot be made into an object: \
+             method \"bar\" of trait \"Foo\" has type parameters"
+        }
+    }
+
+    lowering_error! {
+        program {
+            #[lang(sized)] trait Sized { }
+            trait Bar where Self: Sized { }
+            trait Foo where Self: Bar { }
+            trait Baz { }
+
+            impl Baz for dyn Foo { }
+        } error_msg {
+            "trait \"Foo\" cannot be made into an object: trait \"Bar\" requires `Self: Sized`"
+        }
+    }
+}
+
+/// The type parameters of a struct are assumed well-formed, so that
+/// structural types over them are too; `T: 'a` for the field `&'a T`
+/// is left to the embedder as a region constraint.
+#[test]
+fn references_and_pointers() {
+    lowering_success! {
+        program {
+            trait Foo { }
+            struct Ref<'a, T> { r: &'a T, p: *mut (T, [T]) }
+            impl<'a, T> Foo for Ref<'a, T> where &'a mut T: Foo { }
+        }
+    }
+}
+
+/// Lowering rejects an impl that gives a value to an associated type
+/// of another trait, but a database can still list one.
+#[test]
+fn extra_associated_ty() {
+    let db = chalk_integration::db::ChalkDatabase::with(
+        "
+            trait Foo { }
+            trait Bar { type Item; }
+            struct Stuff { }
+            impl Foo for Stuff { }
+            impl Bar for Stuff { type Item = Stuff; }
+        ",
+        chalk_solve::SolverChoice::default(),
+    );
+    let mut program = (*db.program_ir().unwrap()).clone();
+    let trait_id = |name| match program.type_ids[&chalk_ir::intern(name)] {
+        chalk_ir::TypeKindId::TraitId(trait_id) => trait_id,
+        id => panic!("`{}` is not a trait: {:?}", name, id),
+    };
+    let impl_id = |trait_id| {
+        program
+            .impl_data
+            .iter()
+            .find(|(_, datum)| datum.trait_id() == trait_id)
+            .map(|(&impl_id, _)| impl_id)
+            .unwrap()
+    };
+    let (foo_impl, bar_impl) = (impl_id(trait_id("Foo")), impl_id(trait_id("Bar")));
+    let item = program.impl_data[&bar_impl].associated_ty_value_ids.clone();
+    std::sync::Arc::make_mut(program.impl_data.get_mut(&foo_impl).unwrap())
+        .associated_ty_value_ids = item;
+
+    let error = chalk_solve::conformance::ConformanceSolver::new(
+        &program,
+        chalk_solve::SolverChoice::default(),
+    )
+    .verify_impl(foo_impl)
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "associated type \"Item\" is not a member of trait \"Foo\""
+    );
+}